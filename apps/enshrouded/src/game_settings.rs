@@ -1,7 +1,9 @@
 use crate::environment::name;
 use crate::utils::config_io::{load_config_with_defaults, save_config};
+use crate::utils::engine_duration::EngineDuration;
 use crate::utils::env_overrides::apply_env_overrides;
 use env_parse::env_parse;
+use gsm_shared::Secret;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
@@ -24,8 +26,8 @@ pub struct GameSettings {
     pub enable_starving_debuff: bool,
     /// Multiplier for food buff duration (default: 1.0)
     pub food_buff_duration_factor: f32,
-    /// Nanoseconds from hunger to starving (default: 600_000_000_000)
-    pub from_hunger_to_starving: u64,
+    /// Time from hunger to starving, e.g. `"10m"` or raw nanoseconds (default: 10m)
+    pub from_hunger_to_starving: EngineDuration,
     /// Multiplier for shroud time (default: 1.0)
     pub shroud_time_factor: f32,
     /// Mode for tombstone behavior (default: "AddBackpackMaterials")
@@ -74,10 +76,10 @@ pub struct GameSettings {
     pub pacify_all_enemies: bool,
     /// Taming startle repercussion mode (default: "LoseSomeProgress")
     pub taming_startle_repercussion: String,
-    /// Nanoseconds for day time duration (default: 1_800_000_000_000)
-    pub day_time_duration: u64,
-    /// Nanoseconds for night time duration (default: 720_000_000_000)
-    pub night_time_duration: u64,
+    /// Length of the day, e.g. `"30m"` or raw nanoseconds (default: 30m)
+    pub day_time_duration: EngineDuration,
+    /// Length of the night, e.g. `"12m"` or raw nanoseconds (default: 12m)
+    pub night_time_duration: EngineDuration,
 }
 
 impl Default for GameSettings {
@@ -90,7 +92,7 @@ impl Default for GameSettings {
             enable_durability: true,
             enable_starving_debuff: false,
             food_buff_duration_factor: 1.0,
-            from_hunger_to_starving: 600_000_000_000,
+            from_hunger_to_starving: EngineDuration::from_nanos(600_000_000_000),
             shroud_time_factor: 1.0,
             tombstone_mode: "AddBackpackMaterials".to_owned(),
             enable_glider_turbulences: true,
@@ -115,8 +117,8 @@ impl Default for GameSettings {
             threat_bonus: 1.0,
             pacify_all_enemies: false,
             taming_startle_repercussion: "LoseSomeProgress".to_owned(),
-            day_time_duration: 1_800_000_000_000,
-            night_time_duration: 720_000_000_000,
+            day_time_duration: EngineDuration::from_nanos(1_800_000_000_000),
+            night_time_duration: EngineDuration::from_nanos(720_000_000_000),
         }
     }
 }
@@ -184,7 +186,7 @@ impl GameSettings {
 ///
 /// # Fields
 /// - `name`: The name of the user group (e.g., "Admin", "Guest").
-/// - `password`: The password required to join this group.
+/// - `password`: The password required to join this group, redacted from debug output.
 /// - `can_kick_ban`: Whether users in this group can kick or ban other players.
 /// - `can_access_inventories`: Whether users can access other players' inventories.
 /// - `can_edit_base`: Whether users can edit the base.
@@ -195,7 +197,7 @@ impl GameSettings {
 #[allow(clippy::struct_excessive_bools)]
 pub struct UserGroup {
     pub name: String,
-    pub password: String,
+    pub password: Secret<String>,
     pub can_kick_ban: bool,
     pub can_access_inventories: bool,
     pub can_edit_base: bool,
@@ -216,7 +218,7 @@ impl Default for UserGroup {
     fn default() -> Self {
         Self {
             name: "Guest".to_owned(),
-            password: "GuestXXXXXXXX".to_owned(),
+            password: Secret::from("GuestXXXXXXXX"),
             can_kick_ban: false,
             can_access_inventories: true,
             can_edit_base: true,
@@ -263,7 +265,7 @@ impl Default for ServerConfig {
             user_groups: vec![
                 UserGroup {
                     name: "Admin".to_owned(),
-                    password: "AdminXXXXXXXX".to_owned(),
+                    password: Secret::from("AdminXXXXXXXX"),
                     can_kick_ban: true,
                     can_access_inventories: true,
                     can_edit_base: true,
@@ -276,6 +278,17 @@ impl Default for ServerConfig {
     }
 }
 
+/// Returns the query port configured in `path`, or the default when the file is missing.
+pub fn query_port(path: &Path) -> u16 {
+    load_config_with_defaults::<ServerConfig>(path).query_port
+}
+
+/// Returns the game port configured in `path`, or the default when the file is missing.
+/// `None` when the configured value is not a valid port.
+pub fn game_port(path: &Path) -> Option<u16> {
+    u16::try_from(load_config_with_defaults::<ServerConfig>(path).game_port).ok()
+}
+
 /// Loads the configuration from a file or creates a new one with defaults.
 /// Environment variables override both file values and defaults.
 pub fn load_or_create_config(path: &Path) -> ServerConfig {
@@ -373,6 +386,7 @@ mod tests {
             "EXPERIENCE_COMBAT_FACTOR",
             "TOMBSTONE_MODE",
             "THREAT_BONUS",
+            "DAY_TIME_DURATION",
         ];
         for var in vars {
             unsafe {
@@ -426,6 +440,24 @@ mod tests {
         assert_eq!(settings.tombstone_mode, "Nothing");
     }
 
+    #[test]
+    fn test_env_override_duration() {
+        let _lock = TEST_MUTEX
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        clear_env_vars();
+        unsafe {
+            env::set_var("DAY_TIME_DURATION", "45m");
+        }
+
+        let settings = GameSettings::from_env();
+        assert_eq!(settings.day_time_duration.as_nanos(), 2_700_000_000_000);
+
+        let json = serde_json::to_value(&settings).expect("serialize settings");
+        assert_eq!(json["dayTimeDuration"].as_u64(), Some(2_700_000_000_000));
+        clear_env_vars();
+    }
+
     #[test]
     fn test_new_config_creation_with_env() {
         use tempfile::TempDir;
@@ -3,54 +3,27 @@ mod game_settings;
 mod utils;
 
 use crate::environment::name;
-use clap::{Parser, Subcommand};
-use gsm_cron::{begin_cron_loop, register_job};
-use gsm_instance::{Instance, InstanceConfig};
+use gsm_instance::InstanceConfig;
+use gsm_instance::cli::{self, CliCustomizations, notify_game_event};
+use gsm_instance::config::DownloadConfig;
+use gsm_instance::config_file::ConfigFile;
+use gsm_instance::hooks::Hooks;
+use gsm_instance::ports::GamePort;
+use gsm_instance::resources::ResourceLimits;
+use gsm_instance::saves::SaveGlobs;
+use gsm_instance::workshop::WorkshopConfig;
 use gsm_monitor::LogRules;
-use gsm_notifications::notifications::{StandardServerEvents, send_notifications};
-use gsm_shared::{fetch_var, is_env_var_truthy};
+use gsm_notifications::notifications::{StandardServerEvents, notify};
+use gsm_shared::{export_missing_vars, fetch_var, load_default_dotenv, parse_duration};
+use std::collections::HashMap;
 use std::env;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::exit;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use std::process::ExitCode;
+use tracing::{debug, error};
 
-#[derive(Parser)]
-#[command(
-    name = "enshrouded",
-    version = "1.1",
-    about = "Manage Enshrouded Server"
-)]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
-}
-
-#[derive(Subcommand)]
-enum Commands {
-    Install {
-        #[arg(long, default_value = "/home/steam/enshrouded")]
-        path: PathBuf,
-    },
-    /// Start the server only (without monitoring jobs)
-    Start,
-    /// Monitor the server: start the server and then run scheduled jobs and watch logs.
-    Monitor {
-        #[arg(long)]
-        update_job: bool,
-        #[arg(long)]
-        restart_job: bool,
-    },
-    Stop,
-    Restart,
-    Update {
-        #[arg(long)]
-        check: bool,
-    },
-}
+/// Enshrouded's Steam App ID.
+const APP_ID: u32 = 2_278_520;
 
 fn setup_configuration(game_root: &Path) {
     let config_path = game_root.join("enshrouded_server.json");
@@ -59,244 +32,96 @@ fn setup_configuration(game_root: &Path) {
     debug!("Config load or creation completed.");
 }
 
-#[allow(clippy::too_many_lines)]
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+/// Warns players through the webhook and waits `STOP_DELAY` (e.g. `90s` or `5m`; a bare
+/// number is seconds) before stopping.
+fn announce_stop() {
+    if env::var("WEBHOOK_URL").is_err() {
+        return;
+    }
+    if let Ok(delay) = env::var("STOP_DELAY") {
+        match parse_duration(&delay) {
+            Ok(delay) => {
+                notify(StandardServerEvents::Stopping);
+                std::thread::sleep(delay);
+            }
+            Err(e) => {
+                error!("Invalid STOP_DELAY: {e}");
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    gsm_shared::logging::init();
     debug!("Tracing subscriber initialized.");
 
+    // Variables from a .env file, then from a mounted gsm.toml/gsm.yaml, must be
+    // exported before anything below reads the environment, and before the runtime
+    // starts threads.
+    // SAFETY: the async runtime is not built yet, so this is the only thread.
+    if let Err(e) = unsafe { load_default_dotenv() } {
+        error!("Failed to load the .env file: {e}");
+        return ExitCode::FAILURE;
+    }
+    let config_file = match ConfigFile::from_env() {
+        Ok(config_file) => config_file,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // SAFETY: as above.
+    unsafe { export_missing_vars(config_file.env_vars(), "the config file") };
+
     // Set the TZ environment variable to your desired timezone.
     #[cfg(unix)]
     unsafe {
         env::set_var("TZ", fetch_var("TZ", "America/Los_Angeles"));
     }
 
-    let cli = Cli::parse();
+    let settings_path = Path::new("/home/steam/enshrouded/enshrouded_server.json");
     let instance_config = InstanceConfig {
-        app_id: 2_278_520, // Enshrouded Steam App ID
+        app_id: APP_ID,
         name: name(),
         command: "enshrouded_server.exe".to_owned(),
         install_args: vec![],
         launch_args: vec![],
-        force_windows: true,
+        skip_validate: false,
         working_dir: PathBuf::from("/home/steam/enshrouded"),
         launch_mode: gsm_instance::config::LaunchMode::Wine,
+        query_port: Some(game_settings::query_port(settings_path)),
+        ports: game_settings::game_port(settings_path)
+            .map(GamePort::udp)
+            .into_iter()
+            .collect(),
+        beta: None,
+        env: HashMap::new(),
+        clear_env: false,
+        hooks: Hooks::default(),
+        daemonize: true,
+        proton: None,
+        workshop: WorkshopConfig::default(),
+        readiness: None,
+        downloads: DownloadConfig::default(),
+        resources: ResourceLimits::default(),
+        shared_install: None,
+        dry_run: false,
     };
-    debug!("Instance configuration set: {:?}", instance_config);
-
-    // Use tokio::sync::Mutex for async locking.
-    let instance = Arc::new(Mutex::new(Instance::new(instance_config)));
-    debug!("Instance created and wrapped in Arc<Mutex<>>");
-
-    match cli.command {
-        Commands::Install { path } => {
-            info!("Installing Enshrouded server to: {:?}", path);
-            debug!("Acquiring lock for installation...");
-            let inst = instance.lock().await;
-            if let Err(e) = inst.install() {
-                error!("Installation failed: {}", e);
-            } else {
-                debug!("Installation successful.");
-                setup_configuration(&path);
-                info!("Enshrouded server installed successfully at: {:?}", path);
-            }
-        }
-        Commands::Start => {
-            info!("Starting server...");
-            let inst = instance.lock().await;
-            setup_configuration(&inst.config.working_dir);
-            if let Err(e) = inst.start() {
-                error!("Failed to start server: {}", e);
-            } else {
-                debug!("Server started successfully.");
-            }
-        }
-        Commands::Monitor {
-            update_job,
-            restart_job,
-        } => {
-            // Start your server and schedule jobs as needed...
-            // Then, to watch the logs:
-            let working_dir = {
-                let inst = instance.lock().await;
-                inst.config.working_dir.clone()
-            };
 
-            let rules = LogRules::default();
-
-            if env::var("WEBHOOK_URL").is_ok() {
-                rules.add_rule(
-                    |line| line.contains("[Session] 'HostOnline' (up)!"),
-                    |_| {
-                        if let Err(e) = send_notifications(StandardServerEvents::Started) {
-                            warn!("Failed to send webhook notification: {e}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-
-                rules.add_rule(
-                    |line| line.contains("logged in with Permissions:"),
-                    |line| {
-                        if let Some(name) = utils::extract_player_joined_name(line) {
-                            if let Err(e) =
-                                send_notifications(StandardServerEvents::PlayerJoined(name))
-                            {
-                                warn!("Failed to send webhook notification: {e}");
-                            }
-                        } else {
-                            error!("Failed to extract player name from:\n{line}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-
-                rules.add_rule(
-                    |line| line.contains("[server] Remove Entity for Player"),
-                    |line| {
-                        if let Some(name) = utils::extract_player_left_name(line) {
-                            if let Err(e) =
-                                send_notifications(StandardServerEvents::PlayerLeft(name))
-                            {
-                                warn!("Failed to send webhook notification: {e}");
-                            }
-                        } else {
-                            error!("Failed to extract player name from:\n{line}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-            }
-
-            // Start monitoring the instance log files.
-            gsm_monitor::start_instance_log_monitor(&working_dir, rules);
-
-            if update_job || is_env_var_truthy("AUTO_UPDATE") {
-                debug!("Auto-update job condition met.");
-                let update_schedule = fetch_var("AUTO_UPDATE_SCHEDULE", "0 3 * * *");
-                debug!("Auto-update schedule: {}", update_schedule);
-                let instance_clone = Arc::clone(&instance);
-                register_job("auto-update", &update_schedule, move || {
-                    debug!("Auto-update job triggered.");
-                    let instance_clone_inner = Arc::clone(&instance_clone);
-                    tokio::spawn(async move {
-                        let inst = instance_clone_inner.lock().await;
-                        if inst.update_available() {
-                            warn!("Update available! Stopping server...");
-                            if let Err(e) = inst.stop() {
-                                error!("Failed to stop server: {}", e);
-                                return;
-                            }
-                            info!("Updating server...");
-                            if let Err(e) = inst.update() {
-                                error!("Update failed: {}", e);
-                                return;
-                            }
-                            info!("Restarting server...");
-                            if let Err(e) = inst.start() {
-                                error!("Failed to start server: {}", e);
-                            }
-                        } else {
-                            debug!("No updates available during auto-update check.");
-                        }
-                    });
-                });
-            } else {
-                debug!("Auto-update job not enabled.");
-            }
-
-            if restart_job || is_env_var_truthy("SCHEDULED_RESTART") {
-                debug!("Scheduled restart job condition met.");
-                let restart_schedule = fetch_var("SCHEDULED_RESTART_SCHEDULE", "0 4 * * *");
-                debug!("Scheduled restart schedule: {}", restart_schedule);
-                let instance_clone = Arc::clone(&instance);
-                register_job("scheduled-restart", &restart_schedule, move || {
-                    debug!("Scheduled restart job triggered.");
-                    let instance_clone_inner = Arc::clone(&instance_clone);
-                    tokio::spawn(async move {
-                        let inst = instance_clone_inner.lock().await;
-                        warn!("Restarting server...");
-                        if let Err(e) = inst.restart() {
-                            error!("Failed to restart server: {}", e);
-                        }
-                    });
-                });
-            } else {
-                debug!("Scheduled restart job not enabled.");
-            }
-
-            debug!("Entering cron loop (monitoring logs and scheduled tasks)...");
-            begin_cron_loop().await;
-            debug!("Cron loop ended.");
-        }
-        Commands::Stop => {
-            let webhook_enabled = env::var("WEBHOOK_URL").is_ok();
-            if webhook_enabled && let Ok(delay_str) = env::var("STOP_DELAY") {
-                match delay_str.parse::<u64>() {
-                    Ok(delay_sec) => {
-                        if let Err(e) = send_notifications(StandardServerEvents::Stopping) {
-                            warn!("Failed to send webhook notification: {e}");
-                        }
-                        tokio::time::sleep(Duration::from_secs(delay_sec)).await;
-                    }
-                    Err(_) => {
-                        error!("Invalid STOP_DELAY value: {}", delay_str);
-                    }
-                }
-            }
-
-            warn!("Stopping Enshrouded server...");
-            debug!("Acquiring lock to stop the server...");
-
-            let inst = instance.lock().await;
-            if let Err(e) = inst.stop() {
-                error!("Failed to stop: {}", e);
-            } else {
-                if webhook_enabled && let Err(e) = send_notifications(StandardServerEvents::Stopped)
-                {
-                    warn!("Failed to send webhook notification: {e}");
-                }
-                debug!("Server stopped successfully.");
-            }
-        }
-        Commands::Restart => {
-            warn!("Restarting Enshrouded server...");
-            debug!("Acquiring lock to restart the server...");
-            let inst = instance.lock().await;
-            if let Err(e) = inst.restart() {
-                error!("Failed to restart server: {}", e);
-            } else {
-                debug!("Server restarted successfully.");
-            }
-        }
-        Commands::Update { check } => {
-            debug!("Update command initiated with check = {}", check);
-            let inst = instance.lock().await;
-            if check {
-                debug!("Performing update check...");
-                if inst.update_available() {
-                    info!("Update available!");
-                    exit(1);
-                } else {
-                    info!("Server is up to date.");
-                    exit(0);
-                }
-            } else {
-                info!("Checking for updates without enforcing check flag...");
-                if inst.update_available() {
-                    warn!("Update available! Updating...");
-                    if let Err(e) = inst.update() {
-                        error!("Update failed: {}", e);
-                    } else {
-                        debug!("Update applied successfully.");
-                    }
-                } else {
-                    debug!("Server is up to date; no update needed.");
-                }
-            }
+    let instance_config = config_file.apply(instance_config);
+    let customizations = CliCustomizations::new("enshrouded", "Manage Enshrouded Server")
+        .with_version("1.1")
+        .with_after_install(setup_configuration)
+        .with_before_start(|instance| setup_configuration(&instance.config.working_dir))
+        .with_before_stop(|_| announce_stop())
+        .with_log_rules(|| Ok(LogRules::enshrouded(notify_game_event)))
+        .with_auto_backup("savegame")
+        .with_saves(SaveGlobs::new(["savegame", "enshrouded_server.json"]));
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(cli::run(instance_config, customizations)),
+        Err(e) => {
+            error!("Failed to start the async runtime: {e}");
+            ExitCode::FAILURE
         }
     }
 }
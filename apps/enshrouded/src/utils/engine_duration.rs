@@ -0,0 +1,188 @@
+use gsm_shared::parse_duration_in;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Units [`EngineDuration`] is displayed in, in nanoseconds.
+const UNITS: [(&str, u64); 6] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("ns", 1),
+];
+
+/// A duration stored by Enshrouded as a raw nanosecond count.
+///
+/// Accepts humane values such as `"10m"`, `"1h30m"` or `"45s"` from the environment
+/// and the JSON config, as well as plain nanosecond integers for existing configs, and
+/// always writes nanoseconds back so the game reads it unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineDuration(Duration);
+
+impl EngineDuration {
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(Duration::from_nanos(nanos))
+    }
+
+    pub fn as_nanos(self) -> u64 {
+        u64::try_from(self.0.as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    fn validated(nanos: u64) -> Option<Self> {
+        (nanos > 0).then(|| Self::from_nanos(nanos))
+    }
+}
+
+/// Why a value could not be read as an [`EngineDuration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineDurationError {
+    value: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for EngineDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration '{}': {} (use e.g. \"10m\", \"1h30m\", \"45s\", or raw nanoseconds)",
+            self.value, self.reason
+        )
+    }
+}
+
+impl std::error::Error for EngineDurationError {}
+
+impl FromStr for EngineDuration {
+    type Err = EngineDurationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = |reason| EngineDurationError {
+            value: value.to_owned(),
+            reason,
+        };
+        // Bare integers keep their historical meaning: nanoseconds.
+        let duration =
+            parse_duration_in(value, Duration::from_nanos(1)).map_err(|e| error(e.reason()))?;
+        let nanos =
+            u64::try_from(duration.as_nanos()).map_err(|_| error("duration is too long"))?;
+        Self::validated(nanos).ok_or_else(|| error("must be greater than zero"))
+    }
+}
+
+impl fmt::Display for EngineDuration {
+    /// Formats the duration in the largest units that represent it exactly, e.g. `1h30m`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut remaining = self.as_nanos();
+        if remaining == 0 {
+            return write!(f, "0s");
+        }
+        for (unit, scale) in UNITS {
+            let count = remaining / scale;
+            if count > 0 {
+                write!(f, "{count}{unit}")?;
+                remaining %= scale;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for EngineDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_nanos())
+    }
+}
+
+impl<'de> Deserialize<'de> for EngineDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EngineDurationVisitor;
+
+        impl Visitor<'_> for EngineDurationVisitor {
+            type Value = EngineDuration;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a nanosecond count or a duration string like \"10m\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, nanos: u64) -> Result<Self::Value, E> {
+                EngineDuration::validated(nanos)
+                    .ok_or_else(|| E::custom("duration must be greater than zero"))
+            }
+
+            fn visit_i64<E: de::Error>(self, nanos: i64) -> Result<Self::Value, E> {
+                u64::try_from(nanos)
+                    .map_err(|_| E::custom("duration must not be negative"))
+                    .and_then(|nanos| self.visit_u64(nanos))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(EngineDurationVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn parses_humane_and_raw_values() {
+        assert_eq!(
+            "10m".parse::<EngineDuration>().unwrap().as_nanos(),
+            600_000_000_000
+        );
+        assert_eq!(
+            "1h30m".parse::<EngineDuration>().unwrap().as_nanos(),
+            5_400_000_000_000
+        );
+        assert_eq!(
+            " 45S ".parse::<EngineDuration>().unwrap().as_nanos(),
+            45_000_000_000
+        );
+        assert_eq!(
+            "720000000000".parse::<EngineDuration>().unwrap().as_nanos(),
+            720_000_000_000
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values_with_hints() {
+        for value in ["", "0", "0m", "m", "10 minutes", "10x", "5m3"] {
+            let err = value.parse::<EngineDuration>().unwrap_err();
+            assert!(err.to_string().contains("\"10m\""), "{err}");
+        }
+    }
+
+    #[test]
+    fn displays_in_largest_exact_units() {
+        assert_eq!(
+            EngineDuration::from_nanos(5_400_000_000_000).to_string(),
+            "1h30m"
+        );
+        assert_eq!(
+            EngineDuration::from_nanos(1_500_000_000).to_string(),
+            "1s500ms"
+        );
+    }
+
+    #[test]
+    fn serializes_as_nanoseconds_and_reads_either_form() {
+        let duration: EngineDuration = serde_json::from_str("\"30m\"").unwrap();
+        assert_eq!(serde_json::to_string(&duration).unwrap(), "1800000000000");
+
+        let duration: EngineDuration = serde_json::from_str("1800000000000").unwrap();
+        assert_eq!(duration.to_string(), "30m");
+
+        assert!(serde_json::from_str::<EngineDuration>("\"soon\"").is_err());
+        assert!(serde_json::from_str::<EngineDuration>("-5").is_err());
+    }
+}
@@ -1,8 +1,18 @@
 use crate::game_settings::ServerConfig;
+use crate::utils::engine_duration::EngineDuration;
+use gsm_shared::Secret;
 use std::env;
+use tracing::warn;
+
+const DURATION_ENV_VARS: [&str; 3] = [
+    "FROM_HUNGER_TO_STARVING",
+    "DAY_TIME_DURATION",
+    "NIGHT_TIME_DURATION",
+];
 
 /// Applies environment variable overrides to the config.
 pub fn apply_env_overrides(config: &mut ServerConfig) {
+    warn_invalid_durations();
     let env_config = crate::game_settings::GameSettings::from_env();
     config.game_settings.merge_env(&env_config);
 
@@ -16,7 +26,7 @@ pub fn apply_env_overrides(config: &mut ServerConfig) {
                     .find(|g| g.name.eq_ignore_ascii_case(group_name))
             {
                 match field_name.to_lowercase().as_str() {
-                    "password" => group.password = value,
+                    "password" => group.password = Secret::new(value),
                     "can_kick_ban" => {
                         group.can_kick_ban = value.parse().unwrap_or(group.can_kick_ban);
                     }
@@ -31,6 +41,17 @@ pub fn apply_env_overrides(config: &mut ServerConfig) {
     }
 }
 
+/// Duration settings fall back to their defaults when unparseable, so say why.
+fn warn_invalid_durations() {
+    for var in DURATION_ENV_VARS {
+        if let Ok(value) = env::var(var)
+            && let Err(e) = value.trim_matches(['"', '\'']).parse::<EngineDuration>()
+        {
+            warn!("Ignoring {var}: {e}; using the default instead");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used, clippy::indexing_slicing)]
@@ -45,7 +66,7 @@ mod tests {
         ServerConfig {
             user_groups: vec![UserGroup {
                 name: name.to_owned(),
-                password: "oldpass".to_owned(),
+                password: Secret::from("oldpass"),
                 can_kick_ban: false,
                 can_access_inventories: false,
                 can_edit_base: false,
@@ -76,7 +97,7 @@ mod tests {
         apply_env_var(&env_var, "newpass");
         let mut config = make_config_with_group(group);
         apply_env_overrides(&mut config);
-        assert_eq!(config.user_groups[0].password, "newpass");
+        assert_eq!(config.user_groups[0].password.expose(), "newpass");
         clear_env_var(&env_var);
     }
 
@@ -111,7 +132,7 @@ mod tests {
         let mut config = make_config_with_group(group);
         apply_env_overrides(&mut config);
         // Should remain as default
-        assert_eq!(config.user_groups[0].password, "oldpass");
+        assert_eq!(config.user_groups[0].password.expose(), "oldpass");
         assert!(!config.user_groups[0].can_kick_ban);
         assert!(!config.user_groups[0].can_access_inventories);
     }
@@ -1,5 +1,3 @@
 pub mod config_io;
+pub mod engine_duration;
 pub mod env_overrides;
-mod extract_player_name;
-
-pub use extract_player_name::*;
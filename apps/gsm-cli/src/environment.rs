@@ -30,10 +30,15 @@ pub fn launch_mode() -> Option<LaunchMode> {
         .and_then(parse_launch_mode)
 }
 
+/// Legacy `FORCE_WINDOWS` switch, read as `LAUNCH_MODE=wine` when no mode is set.
 pub fn force_windows() -> bool {
     gsm_shared::is_env_var_truthy("FORCE_WINDOWS")
 }
 
+pub fn dry_run() -> bool {
+    gsm_shared::is_env_var_truthy(gsm_instance::dry_run::DRY_RUN)
+}
+
 pub fn install_args() -> Vec<String> {
     split_shell_like_values("INSTALL_ARGS")
 }
@@ -66,12 +71,7 @@ fn split_shell_like_values(key: &str) -> Vec<String> {
 }
 
 pub fn parse_launch_mode(value: &str) -> Option<LaunchMode> {
-    match value.trim().to_ascii_lowercase().as_str() {
-        "native" => Some(LaunchMode::Native),
-        "wine" => Some(LaunchMode::Wine),
-        "proton" => Some(LaunchMode::Proton),
-        _ => None,
-    }
+    value.parse().ok()
 }
 
 #[cfg(test)]
@@ -2,15 +2,26 @@ mod environment;
 
 use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
 use environment::{
-    app_id as env_app_id, executable as env_executable, force_windows as env_force_windows,
-    install_args as env_install_args, install_path as env_install_path,
-    launch_args as env_launch_args, launch_mode as env_launch_mode, name,
+    app_id as env_app_id, dry_run as env_dry_run, executable as env_executable,
+    force_windows as env_force_windows, install_args as env_install_args,
+    install_path as env_install_path, launch_args as env_launch_args,
+    launch_mode as env_launch_mode, name,
 };
-use gsm_cron::{begin_cron_loop, register_job};
-use gsm_instance::{Instance, InstanceConfig, config::LaunchMode};
+use gsm_cron::{ChildRegistry, begin_cron_loop, register_job};
+use gsm_instance::hooks::Hooks;
+use gsm_instance::maintenance::{EmptyWait, max_defer_from_env};
+use gsm_instance::resources::ResourceLimits;
+use gsm_instance::update::UpdateStatus;
+use gsm_instance::workshop::WorkshopConfig;
+use gsm_instance::{
+    Instance, InstanceConfig, InstanceError,
+    config::{DownloadConfig, LaunchMode},
+};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::exit;
 use std::sync::Arc;
+use tokio::runtime::Handle;
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 
@@ -28,11 +39,27 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     Install(InstallCommand),
-    Start(RuntimeCommand),
+    Start(StartCommand),
     Stop(RuntimeCommand),
     Restart(RuntimeCommand),
     Update(UpdateCommand),
     Monitor(MonitorCommand),
+    /// Print the server's health; exits non-zero unless it is running and answering.
+    Status(StatusCommand),
+}
+
+impl Commands {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Install(_) => "install",
+            Self::Start(_) => "start",
+            Self::Stop(_) => "stop",
+            Self::Restart(_) => "restart",
+            Self::Update(_) => "update",
+            Self::Monitor(_) => "monitor",
+            Self::Status(_) => "status",
+        }
+    }
 }
 
 #[derive(Args, Debug, Clone)]
@@ -41,7 +68,8 @@ struct SharedOptions {
     app_id: Option<u32>,
     #[arg(long)]
     install_path: Option<PathBuf>,
-    #[arg(long)]
+    /// Deprecated: same as `--launch-mode wine`.
+    #[arg(long, conflicts_with = "launch_mode")]
     force_windows: bool,
     #[arg(long, value_name = "native|wine|proton")]
     launch_mode: Option<String>,
@@ -51,6 +79,9 @@ struct SharedOptions {
     install_args: Vec<String>,
     #[arg(long = "launch-arg")]
     launch_args: Vec<String>,
+    /// Log the SteamCMD and launch commands and stop signals instead of running them.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -65,10 +96,20 @@ struct RuntimeCommand {
     shared: SharedOptions,
 }
 
+#[derive(Args, Debug, Clone)]
+struct StartCommand {
+    #[command(flatten)]
+    shared: SharedOptions,
+    /// Keep the server attached, streaming its output, until it exits.
+    #[arg(long)]
+    foreground: bool,
+}
+
 #[derive(Args, Debug, Clone)]
 struct UpdateCommand {
     #[command(flatten)]
     shared: SharedOptions,
+    /// Only check for an update; exits 1 if one is available, 2 if the check fails.
     #[arg(long)]
     check: bool,
 }
@@ -81,15 +122,24 @@ struct MonitorCommand {
     update_job: bool,
 }
 
+#[derive(Args, Debug, Clone)]
+struct StatusCommand {
+    #[command(flatten)]
+    shared: SharedOptions,
+    /// Steam query port to check the server answers on.
+    #[arg(long)]
+    query_port: Option<u16>,
+}
+
 #[derive(Debug, Clone)]
 struct ResolvedOptions {
     app_id: u32,
     install_path: PathBuf,
     executable: Option<String>,
-    force_windows: bool,
     launch_mode: LaunchMode,
     install_args: Vec<String>,
     launch_args: Vec<String>,
+    dry_run: bool,
 }
 
 impl SharedOptions {
@@ -126,11 +176,7 @@ impl SharedOptions {
             .and_then(environment::parse_launch_mode)
             .or_else(env_launch_mode)
             .unwrap_or_else(|| {
-                if self.force_windows || env_force_windows() {
-                    LaunchMode::Wine
-                } else {
-                    LaunchMode::Native
-                }
+                LaunchMode::from_force_windows(self.force_windows || env_force_windows())
             });
 
         let install_args = if self.install_args.is_empty() {
@@ -149,10 +195,10 @@ impl SharedOptions {
             app_id,
             install_path,
             executable,
-            force_windows: self.force_windows || env_force_windows(),
             launch_mode,
             install_args,
             launch_args,
+            dry_run: self.dry_run || env_dry_run(),
         })
     }
 }
@@ -165,117 +211,235 @@ impl ResolvedOptions {
             command: self.executable.unwrap_or_default(),
             install_args: self.install_args,
             launch_args: self.launch_args,
-            force_windows: self.force_windows,
+            skip_validate: false,
             working_dir: self.install_path,
             launch_mode: self.launch_mode,
+            query_port: None,
+            ports: Vec::new(),
+            beta: None,
+            env: HashMap::new(),
+            clear_env: false,
+            hooks: Hooks::default(),
+            daemonize: true,
+            proton: None,
+            workshop: WorkshopConfig::default(),
+            readiness: None,
+            downloads: DownloadConfig::default(),
+            resources: ResourceLimits::default(),
+            shared_install: None,
+            dry_run: self.dry_run,
+        }
+    }
+
+    /// Builds the instance config and validates it, skipping the `command` check
+    /// for subcommands that never launch the server.
+    fn into_validated_config(
+        self,
+        require_executable: bool,
+    ) -> Result<InstanceConfig, clap::Error> {
+        let config = self.into_instance_config();
+        let issues: Vec<_> = Instance::new(config.clone())
+            .config_issues()
+            .into_iter()
+            .filter(|issue| require_executable || issue.field != "command")
+            .collect();
+
+        if issues.is_empty() {
+            Ok(config)
+        } else {
+            Err(Cli::command().error(
+                ErrorKind::ValueValidation,
+                InstanceError::InvalidConfig(issues),
+            ))
         }
     }
 }
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+// One arm per subcommand, which `exit` from `main` on failure.
+#[allow(clippy::too_many_lines)]
+fn main() {
+    gsm_shared::logging::init();
 
+    // SAFETY: the async runtime is not built yet, so this is the only thread.
+    if let Err(e) = unsafe { gsm_shared::load_default_dotenv() } {
+        error!("Failed to load the .env file: {e}");
+        exit(1);
+    }
     let cli = Cli::parse();
-
-    match cli.command {
-        Commands::Install(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(false));
-            let instance = Instance::new(resolved.into_instance_config());
-
-            info!(
-                "Installing app {} to {}",
-                instance.config.app_id,
-                instance.config.working_dir.display()
-            );
-
-            if let Err(err) = instance.install() {
-                error!("Installation failed: {err}");
-                exit(1);
-            }
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("Failed to start the async runtime: {e}");
+            exit(1);
         }
-        Commands::Start(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(true));
-            let instance = Instance::new(resolved.into_instance_config());
-
-            if let Err(err) = instance.start() {
-                error!("Failed to start server: {err}");
-                exit(1);
+    };
+    runtime.block_on(async move {
+        let _span = gsm_shared::logging::command_span(cli.command.name()).entered();
+
+        match cli.command {
+            Commands::Install(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(false));
+                let instance = Instance::new(unwrap_or_exit(resolved.into_validated_config(false)));
+
+                info!(
+                    "Installing app {} to {}",
+                    instance.config.app_id,
+                    instance.config.working_dir.display()
+                );
+
+                if let Err(err) = instance.install() {
+                    error!("Installation failed: {err}");
+                    exit(1);
+                }
             }
-        }
-        Commands::Stop(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(true));
-            let instance = Instance::new(resolved.into_instance_config());
-
-            if let Err(err) = instance.stop() {
-                error!("Failed to stop server: {err}");
-                exit(1);
+            Commands::Start(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(true));
+                let mut config = unwrap_or_exit(resolved.into_validated_config(true));
+                config.daemonize = !command.foreground;
+                let instance = Instance::new(config);
+
+                if !instance.config.daemonize {
+                    match instance.run_foreground_async().await {
+                        Ok(status) => exit(status.code().unwrap_or(1)),
+                        Err(InstanceError::DryRun(_)) => exit(0),
+                        Err(err) => {
+                            error!("Failed to run server: {err}");
+                            exit(1);
+                        }
+                    }
+                }
+                match instance.start() {
+                    Ok(_) | Err(InstanceError::DryRun(_)) => {}
+                    Err(err) => {
+                        error!("Failed to start server: {err}");
+                        exit(1);
+                    }
+                }
             }
-        }
-        Commands::Restart(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(true));
-            let instance = Instance::new(resolved.into_instance_config());
+            Commands::Stop(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(true));
+                let instance = Instance::new(unwrap_or_exit(resolved.into_validated_config(true)));
 
-            if let Err(err) = instance.restart() {
-                error!("Failed to restart server: {err}");
-                exit(1);
+                if let Err(err) = instance.stop() {
+                    error!("Failed to stop server: {err}");
+                    exit(1);
+                }
             }
-        }
-        Commands::Update(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(false));
-            let instance = Instance::new(resolved.into_instance_config());
+            Commands::Restart(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(true));
+                let instance = Instance::new(unwrap_or_exit(resolved.into_validated_config(true)));
 
-            if command.check {
-                if instance.update_available() {
-                    info!("Update available for app {}", instance.config.app_id);
+                if let Err(err) = instance.restart() {
+                    error!("Failed to restart server: {err}");
                     exit(1);
                 }
-
-                info!("App {} is up to date", instance.config.app_id);
-                exit(0);
             }
-
-            if let Err(err) = instance.update() {
-                error!("Update failed: {err}");
-                exit(1);
+            Commands::Status(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(false));
+                let mut config = unwrap_or_exit(resolved.into_validated_config(false));
+                config.query_port = command.query_port;
+                let health = Instance::new(config).health();
+
+                println!("{health}");
+                if !health.is_healthy() {
+                    exit(1);
+                }
             }
-        }
-        Commands::Monitor(command) => {
-            let resolved = unwrap_or_exit(command.shared.resolve(false));
-            let instance = Arc::new(Mutex::new(Instance::new(resolved.into_instance_config())));
-
-            let working_dir = {
-                let instance = instance.lock().await;
-                instance.config.working_dir.clone()
-            };
-
-            gsm_monitor::start_instance_log_monitor(&working_dir, gsm_monitor::LogRules::default());
-
-            if command.update_job || gsm_shared::is_env_var_truthy("AUTO_UPDATE") {
-                let schedule = gsm_shared::fetch_var("AUTO_UPDATE_SCHEDULE", "0 3 * * *");
-                let update_instance = Arc::clone(&instance);
-
-                register_job("auto-update", &schedule, move || {
-                    let update_instance = Arc::clone(&update_instance);
-                    tokio::spawn(async move {
-                        let instance = update_instance.lock().await;
-                        if instance.update_available() {
-                            warn!(
-                                "Update available for app {}. Applying update.",
+            Commands::Update(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(false));
+                let instance = Instance::new(unwrap_or_exit(resolved.into_validated_config(false)));
+
+                if command.check {
+                    match instance.update_available() {
+                        Ok(UpdateStatus::UpToDate { .. }) => {
+                            info!("App {} is up to date", instance.config.app_id);
+                            exit(0);
+                        }
+                        Ok(status @ UpdateStatus::Available { .. }) => {
+                            info!("App {}: {status}", instance.config.app_id);
+                            exit(1);
+                        }
+                        Ok(UpdateStatus::Unknown { reason }) => {
+                            error!(
+                                "Could not determine whether app {} is up to date: {reason}",
                                 instance.config.app_id
                             );
-
-                            if let Err(err) = instance.update() {
-                                error!("Auto-update failed: {err}");
-                            }
+                            exit(2);
                         }
-                    });
-                });
+                        Err(err) => {
+                            error!("Update check failed: {err}");
+                            exit(2);
+                        }
+                    }
+                }
+
+                if let Err(err) = instance.update() {
+                    error!("Update failed: {err}");
+                    exit(1);
+                }
             }
+            Commands::Monitor(command) => {
+                let resolved = unwrap_or_exit(command.shared.resolve(false));
+                let instance = Arc::new(Mutex::new(Instance::new(unwrap_or_exit(
+                    resolved.into_validated_config(false),
+                ))));
+
+                let working_dir = {
+                    let instance = instance.lock().await;
+                    // Forward container stop signals to a server started before the monitor.
+                    if let Ok(pid) = instance.pid() {
+                        ChildRegistry::global().register(pid);
+                    }
+                    instance.config.working_dir.clone()
+                };
+
+                gsm_monitor::start_instance_log_monitor(&working_dir, gsm_monitor::LogRules::default());
+
+                if command.update_job || gsm_shared::is_env_var_truthy("AUTO_UPDATE") {
+                    let schedule = gsm_shared::fetch_var("AUTO_UPDATE_SCHEDULE", "0 3 * * *");
+                    let update_instance = Arc::clone(&instance);
+                    let max_defer = max_defer_from_env("AUTO_UPDATE");
+
+                    if let Err(e) = register_job("auto-update", &schedule, move || {
+                        let update_instance = Arc::clone(&update_instance);
+                        Handle::current().block_on(async move {
+                            let instance = update_instance.lock().await;
+                            match instance.update_available_async().await {
+                                Ok(UpdateStatus::Available { .. }) => {
+                                    warn!(
+                                        "Update available for app {}. Applying update.",
+                                        instance.config.app_id
+                                    );
+                                    if let Some(max_defer) = max_defer {
+                                        match instance.wait_until_empty(max_defer) {
+                                            EmptyWait::Empty => {}
+                                            waited => warn!("Updating anyway: {waited}"),
+                                        }
+                                    }
+
+                                    if let Err(err) = instance.update_async().await {
+                                        error!("Auto-update failed: {err}");
+                                    }
+                                }
+                                Ok(UpdateStatus::UpToDate { .. }) => {}
+                                Ok(UpdateStatus::Unknown { reason }) => {
+                                    warn!(
+                                        "Could not determine whether an update is available: {reason}"
+                                    );
+                                }
+                                Err(err) => error!("Auto-update check failed: {err}"),
+                            }
+                        });
+                    }) {
+                        error!("{e}; check AUTO_UPDATE_SCHEDULE");
+                        exit(1);
+                    }
+                }
 
-            begin_cron_loop().await;
+                begin_cron_loop().await;
+            }
         }
-    }
+    });
 }
 
 fn unwrap_or_exit<T>(result: Result<T, clap::Error>) -> T {
@@ -331,6 +495,7 @@ mod tests {
             executable: Some(String::from("cli-server")),
             install_args: vec![String::from("+beta")],
             launch_args: vec![String::from("-log")],
+            dry_run: false,
         };
 
         let resolved = options.resolve(true).unwrap();
@@ -370,6 +535,7 @@ mod tests {
             executable: None,
             install_args: Vec::new(),
             launch_args: Vec::new(),
+            dry_run: false,
         };
 
         let error = options.resolve(true).unwrap_err();
@@ -413,6 +579,7 @@ mod tests {
             executable: None,
             install_args: Vec::new(),
             launch_args: Vec::new(),
+            dry_run: false,
         };
 
         let resolved = options.resolve(false).unwrap();
@@ -460,6 +627,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec!["--cli-arg".to_owned()],
+            dry_run: false,
         };
 
         let resolved = options.resolve(false).unwrap();
@@ -493,6 +661,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec![],
+            dry_run: false,
         };
 
         let resolved = options.resolve(false).unwrap();
@@ -526,6 +695,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec![],
+            dry_run: false,
         };
 
         let resolved = options.resolve(false).unwrap();
@@ -560,6 +730,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec![],
+            dry_run: false,
         };
 
         let resolved = options.resolve(false).unwrap();
@@ -580,16 +751,16 @@ mod tests {
             app_id: 42,
             install_path: std::path::PathBuf::from("/srv/game"),
             executable: Some("server.exe".to_owned()),
-            force_windows: true,
             launch_mode: gsm_instance::config::LaunchMode::Wine,
             install_args: vec!["-validate".to_owned()],
             launch_args: vec!["-log".to_owned()],
+            dry_run: false,
         };
 
         let config = opts.into_instance_config();
         assert_eq!(config.app_id, 42);
         assert_eq!(config.command, "server.exe");
-        assert!(config.force_windows);
+        assert!(config.launch_mode.is_windows());
         assert_eq!(config.working_dir, std::path::PathBuf::from("/srv/game"));
         assert_eq!(config.install_args, vec!["-validate"]);
         assert_eq!(config.launch_args, vec!["-log"]);
@@ -614,6 +785,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec![],
+            dry_run: false,
         };
 
         assert!(options.resolve(false).is_err());
@@ -638,6 +810,7 @@ mod tests {
             executable: None,
             install_args: vec![],
             launch_args: vec![],
+            dry_run: false,
         };
 
         assert!(options.resolve(false).is_err());
@@ -646,4 +819,23 @@ mod tests {
             env::remove_var("APP_ID");
         }
     }
+
+    #[test]
+    fn into_validated_config_ignores_missing_executable_when_not_required() {
+        let temp_dir = tempdir().unwrap();
+        let opts = ResolvedOptions {
+            app_id: 42,
+            install_path: temp_dir.path().join("server"),
+            executable: None,
+            launch_mode: LaunchMode::Native,
+            install_args: vec![],
+            launch_args: vec![],
+            dry_run: false,
+        };
+
+        assert!(opts.clone().into_validated_config(false).is_ok());
+
+        let error = opts.into_validated_config(true).unwrap_err();
+        assert!(error.to_string().contains("command is empty"));
+    }
 }
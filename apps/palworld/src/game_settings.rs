@@ -1,10 +1,13 @@
-use env_parse::env_parse;
-use gsm_serde::serde_ini::{IniHeader, to_string};
+use env_derive::EnvConfig;
+use env_parse::EnvConfig as _;
+use gsm_serde::serde_ini::{IniDocument, IniError, IniHeader, unquoted};
+use gsm_shared::Secret;
 use ini_derive::IniSerialize;
 use serde::{Deserialize, Serialize};
+use std::env;
 use std::fs::create_dir_all;
+use std::io;
 use std::path::Path;
-use std::{env, fs};
 
 #[derive(Debug, Clone, Copy, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -21,372 +24,374 @@ pub struct Settings {
     option_settings: GameSettings,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, EnvConfig)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct GameSettings {
     // Core gameplay rates
-    #[serde(rename = "Difficulty")]
+    #[serde(rename = "Difficulty", with = "unquoted")]
+    #[env(default = "None")]
     pub difficulty: String,
 
-    #[serde(rename = "RandomizerType")]
+    #[serde(rename = "RandomizerType", with = "unquoted")]
+    #[env(default = "None")]
     pub randomizer_type: String,
 
     #[serde(rename = "RandomizerSeed")]
+    #[env(default = "")]
     pub randomizer_seed: String,
 
     #[serde(rename = "bIsRandomizerPalLevelRandom")]
+    #[env(name = "B_IS_RANDOMIZER_PAL_LEVEL_RANDOM", default = false)]
     pub is_randomizer_pal_level_random: bool,
 
     #[serde(rename = "DayTimeSpeedRate")]
+    #[env(default = 1.0)]
     pub day_time_speed_rate: f32,
 
     #[serde(rename = "NightTimeSpeedRate")]
+    #[env(default = 1.0)]
     pub night_time_speed_rate: f32,
 
     #[serde(rename = "ExpRate")]
+    #[env(default = 1.0)]
     pub exp_rate: f32,
 
     #[serde(rename = "PalCaptureRate")]
+    #[env(default = 1.0)]
     pub pal_capture_rate: f32,
 
     #[serde(rename = "PalSpawnNumRate")]
+    #[env(default = 1.0)]
     pub pal_spawn_num_rate: f32,
 
     #[serde(rename = "PalDamageRateAttack")]
+    #[env(default = 1.0)]
     pub pal_damage_rate_attack: f32,
 
     #[serde(rename = "PalDamageRateDefense")]
+    #[env(default = 1.0)]
     pub pal_damage_rate_defense: f32,
 
     #[serde(rename = "bAllowGlobalPalboxExport")]
+    #[env(name = "B_ALLOW_GLOBAL_PALBOX_EXPORT", default = false)]
     pub allow_global_palbox_export: bool,
 
     #[serde(rename = "bAllowGlobalPalboxImport")]
+    #[env(name = "B_ALLOW_GLOBAL_PALBOX_IMPORT", default = false)]
     pub allow_global_palbox_import: bool,
 
     #[serde(rename = "bCharacterRecreateInHardcore")]
+    #[env(name = "B_CHARACTER_RECREATE_IN_HARDCORE", default = false)]
     pub character_recreate_in_hardcore: bool,
 
     #[serde(rename = "PlayerDamageRateAttack")]
+    #[env(default = 1.0)]
     pub player_damage_rate_attack: f32,
 
     #[serde(rename = "PlayerDamageRateDefense")]
+    #[env(default = 1.0)]
     pub player_damage_rate_defense: f32,
 
     #[serde(rename = "PlayerStomachDecreaseRate")]
+    #[env(default = 1.0)]
     pub player_stomach_decrease_rate: f32,
 
     #[serde(rename = "PlayerStaminaDecreaseRate")]
+    #[env(default = 1.0)]
     pub player_stamina_decrease_rate: f32,
 
     #[serde(rename = "PlayerAutoHPRegeneRate")]
+    #[env(default = 1.0)]
     pub player_auto_hp_regen_rate: f32,
 
     #[serde(rename = "PlayerAutoHpRegeneRateInSleep")]
+    #[env(default = 1.0)]
     pub player_auto_hp_regen_rate_in_sleep: f32,
 
     #[serde(rename = "PalStomachDecreaseRate")]
+    #[env(default = 1.0)]
     pub pal_stomach_decrease_rate: f32,
 
     #[serde(rename = "PalStaminaDecreaseRate")]
+    #[env(default = 1.0)]
     pub pal_stamina_decrease_rate: f32,
 
     #[serde(rename = "PalAutoHPRegeneRate")]
+    #[env(default = 1.0)]
     pub pal_auto_hp_regen_rate: f32,
 
     #[serde(rename = "PalAutoHpRegeneRateInSleep")]
+    #[env(default = 1.0)]
     pub pal_auto_hp_regen_rate_in_sleep: f32,
 
     // Build and object settings
     #[serde(rename = "BuildObjectHpRate")]
+    #[env(default = 1.0)]
     pub build_object_hp_rate: f32,
 
     #[serde(rename = "BuildObjectDamageRate")]
+    #[env(default = 1.0)]
     pub build_object_damage_rate: f32,
 
     #[serde(rename = "BuildObjectDeteriorationDamageRate")]
+    #[env(default = 1.0)]
     pub build_object_deterioration_damage_rate: f32,
 
     #[serde(rename = "CollectionDropRate")]
+    #[env(default = 1.0)]
     pub collection_drop_rate: f32,
 
     #[serde(rename = "CollectionObjectHpRate")]
+    #[env(default = 1.0)]
     pub collection_object_hp_rate: f32,
 
     #[serde(rename = "CollectionObjectRespawnSpeedRate")]
+    #[env(default = 1.0)]
     pub collection_object_respawn_speed_rate: f32,
 
     #[serde(rename = "EnemyDropItemRate")]
+    #[env(default = 1.0)]
     pub enemy_drop_item_rate: f32,
 
     // Death penalty and PvP settings
-    #[serde(rename = "DeathPenalty")]
+    #[serde(rename = "DeathPenalty", with = "unquoted")]
+    #[env(default = "All")]
     pub death_penalty: String,
 
     #[serde(rename = "bEnablePlayerToPlayerDamage")]
+    #[env(default = false)]
     pub enable_pvp: bool,
 
     #[serde(rename = "bEnableFriendlyFire")]
+    #[env(default = false)]
     pub enable_friendly_fire: bool,
 
     #[serde(rename = "bEnableInvaderEnemy")]
+    #[env(default = true)]
     pub enable_invader_enemy: bool,
 
     #[serde(rename = "bActiveUNKO")]
+    #[env(default = false)]
     pub active_unko: bool,
 
     #[serde(rename = "bEnableAimAssistPad")]
+    #[env(default = true)]
     pub enable_aim_assist_pad: bool,
 
     #[serde(rename = "bEnableAimAssistKeyboard")]
+    #[env(default = false)]
     pub enable_aim_assist_keyboard: bool,
 
     // Drop and base camp settings
     #[serde(rename = "DropItemMaxNum")]
+    #[env(default = 3000)]
     pub drop_item_max_num: u32,
 
     #[serde(rename = "DropItemMaxNum_UNKO")]
+    #[env(default = 100)]
     pub drop_item_max_num_unko: u32,
 
     #[serde(rename = "BaseCampMaxNum")]
+    #[env(default = 128)]
     pub base_camp_max_num: u16,
 
     #[serde(rename = "BaseCampWorkerMaxNum")]
+    #[env(default = 15)]
     pub base_camp_worker_max_num: u16,
 
     #[serde(rename = "DropItemAliveMaxHours")]
+    #[env(default = 1.0)]
     pub drop_item_alive_max_hours: f32,
 
     // Guild and related settings
     #[serde(rename = "bAutoResetGuildNoOnlinePlayers")]
+    #[env(default = false)]
     pub auto_reset_guild_no_online_players: bool,
 
     #[serde(rename = "AutoResetGuildTimeNoOnlinePlayers")]
+    #[env(default = 72.0)]
     pub auto_reset_guild_time_no_online_players: f32,
 
     #[serde(rename = "GuildPlayerMaxNum")]
+    #[env(default = 20)]
     pub guild_player_max_num: u16,
 
     #[serde(rename = "BaseCampMaxNumInGuild")]
+    #[env(default = 4)]
     pub base_camp_max_num_in_guild: u16,
 
     #[serde(rename = "PalEggDefaultHatchingTime")]
+    #[env(default = 72.0)]
     pub pal_egg_default_hatching_time: f32,
 
     // Other gameplay rates
     #[serde(rename = "WorkSpeedRate")]
+    #[env(default = 1.0)]
     pub work_speed_rate: f32,
 
     #[serde(rename = "AutoSaveSpan")]
+    #[env(default = 30.0)]
     pub auto_save_span: f32,
 
     // Multiplayer and PvP modes
     #[serde(rename = "bIsMultiplay")]
+    #[env(default = false)]
     pub is_multiplay: bool,
 
     #[serde(rename = "bIsPvP")]
+    #[env(default = false)]
     pub is_pvp: bool,
 
     #[serde(rename = "bHardcore")]
+    #[env(default = false)]
     pub hardcore: bool,
 
     #[serde(rename = "bPalLost")]
+    #[env(default = false)]
     pub pal_lost: bool,
 
     #[serde(rename = "bCanPickupOtherGuildDeathPenaltyDrop")]
+    #[env(default = false)]
     pub can_pickup_other_guild_death_penalty_drop: bool,
 
     #[serde(rename = "bEnableNonLoginPenalty")]
+    #[env(default = true)]
     pub enable_non_login_penalty: bool,
 
     #[serde(rename = "bEnableFastTravel")]
+    #[env(default = true)]
     pub enable_fast_travel: bool,
 
     #[serde(rename = "bIsStartLocationSelectByMap")]
+    #[env(default = true)]
     pub is_start_location_select_by_map: bool,
 
     #[serde(rename = "bExistPlayerAfterLogout")]
+    #[env(default = false)]
     pub exist_player_after_logout: bool,
 
     #[serde(rename = "bEnableDefenseOtherGuildPlayer")]
+    #[env(default = false)]
     pub enable_defense_other_guild_player: bool,
 
     #[serde(rename = "bInvisibleOtherGuildBaseCampAreaFX")]
+    #[env(default = false)]
     pub invisible_other_guild_base_camp_area_fx: bool,
 
     #[serde(rename = "bBuildAreaLimit")]
+    #[env(default = false)]
     pub build_area_limit: bool,
 
     #[serde(rename = "ItemWeightRate")]
+    #[env(default = 1.0)]
     pub item_weight_rate: f32,
 
     // Server limits and networking
     #[serde(rename = "CoopPlayerMaxNum")]
+    #[env(default = 4)]
     pub coop_player_max_num: u16,
 
     #[serde(rename = "ServerPlayerMaxNum")]
+    #[env(default = 32)]
     pub server_player_max_num: u16,
 
     #[serde(rename = "ServerName")]
+    #[env(default = "Default Palworld Server")]
     pub server_name: String,
 
     #[serde(rename = "ServerDescription")]
+    #[env(default = "")]
     pub server_description: String,
 
     #[serde(rename = "AdminPassword")]
-    pub admin_password: String,
+    #[env(default = "")]
+    pub admin_password: Secret<String>,
 
     #[serde(rename = "ServerPassword")]
-    pub server_password: String,
+    #[env(default = "")]
+    pub server_password: Secret<String>,
 
     #[serde(rename = "PublicPort")]
+    #[env(default = 8211)]
     pub public_port: u16,
 
     #[serde(rename = "PublicIP")]
+    #[env(default = "")]
     pub public_ip: String,
 
     #[serde(rename = "RCONEnabled")]
+    #[env(default = false)]
     pub rcon_enabled: bool,
 
     #[serde(rename = "RCONPort")]
+    #[env(default = 25575)]
     pub rcon_port: u16,
 
     #[serde(rename = "bUseAuth")]
+    #[env(default = true)]
     pub use_auth: bool,
 
     #[serde(rename = "Region")]
+    #[env(default = "")]
     pub region: String,
 
     #[serde(rename = "BanListURL")]
+    #[env(
+        name = "BAN_LIST",
+        default = "https://api.palworldgame.com/api/banlist.txt"
+    )]
     pub ban_list_url: String,
 
-    #[serde(rename = "CrossplayPlatforms")]
+    #[serde(rename = "CrossplayPlatforms", with = "unquoted")]
+    #[env(default = "(Steam,Xbox,PS5,Mac)")]
     pub crossplay_platforms: String, // Default (Steam,Xbox,PS5,Mac)
 
     // REST API and additional networking
     #[serde(rename = "RESTAPIEnabled")]
+    #[env(default = false)]
     pub restapi_enabled: bool,
 
     #[serde(rename = "RESTAPIPort")]
+    #[env(default = 8212)]
     pub restapi_port: u16,
 
     #[serde(rename = "bShowPlayerList")]
+    #[env(default = false)]
     pub show_player_list: bool,
 
     #[serde(rename = "ChatPostLimitPerMinute")]
+    #[env(default = 10)]
     pub chat_post_limit_per_minute: u16,
 
     #[serde(rename = "bIsUseBackupSaveData")]
+    #[env(default = true)]
     pub is_use_backup_save_data: bool,
 
-    #[serde(rename = "LogFormatType")]
+    #[serde(rename = "LogFormatType", with = "unquoted")]
+    #[env(default = "Text")]
     pub log_format_type: String,
 
     #[serde(rename = "SupplyDropSpan")]
+    #[env(default = 180.0)]
     pub supply_drop_span: f32,
 
     #[serde(rename = "EnablePredatorBossPal")]
+    #[env(default = true)]
     pub enable_predator_boss_pal: bool,
 
     #[serde(rename = "MaxBuildingLimitNum")]
+    #[env(default = 0)]
     pub max_building_limit_num: u32,
 
     #[serde(rename = "ServerReplicatePawnCullDistance")]
+    #[env(default = 15000.0)]
     pub server_replicate_pawn_cull_distance: f32,
 }
 
 impl GameSettings {
     /// Constructs the base (Normal preset) configuration based on the golden INI.
     pub fn normal() -> Self {
-        Self {
-            difficulty: "None".to_owned(),
-            randomizer_type: "None".to_owned(),
-            randomizer_seed: String::new(),
-            is_randomizer_pal_level_random: false,
-            day_time_speed_rate: 1.0,
-            night_time_speed_rate: 1.0,
-            exp_rate: 1.0,
-            pal_capture_rate: 1.0,
-            pal_spawn_num_rate: 1.0,
-            pal_damage_rate_attack: 1.0,
-            pal_damage_rate_defense: 1.0,
-            allow_global_palbox_export: false,
-            allow_global_palbox_import: false,
-            character_recreate_in_hardcore: false,
-            player_damage_rate_attack: 1.0,
-            player_damage_rate_defense: 1.0,
-            player_stomach_decrease_rate: 1.0,
-            player_stamina_decrease_rate: 1.0,
-            player_auto_hp_regen_rate: 1.0,
-            player_auto_hp_regen_rate_in_sleep: 1.0,
-            pal_stomach_decrease_rate: 1.0,
-            pal_stamina_decrease_rate: 1.0,
-            pal_auto_hp_regen_rate: 1.0,
-            pal_auto_hp_regen_rate_in_sleep: 1.0,
-            build_object_hp_rate: 1.0,
-            build_object_damage_rate: 1.0,
-            build_object_deterioration_damage_rate: 1.0,
-            collection_drop_rate: 1.0,
-            collection_object_hp_rate: 1.0,
-            collection_object_respawn_speed_rate: 1.0,
-            enemy_drop_item_rate: 1.0,
-            death_penalty: "All".to_owned(),
-            enable_pvp: false,
-            enable_friendly_fire: false,
-            enable_invader_enemy: true,
-            active_unko: false,
-            enable_aim_assist_pad: true,
-            enable_aim_assist_keyboard: false,
-            drop_item_max_num: 3000,
-            drop_item_max_num_unko: 100,
-            base_camp_max_num: 128,
-            base_camp_worker_max_num: 15,
-            drop_item_alive_max_hours: 1.0,
-            auto_reset_guild_no_online_players: false,
-            auto_reset_guild_time_no_online_players: 72.0,
-            guild_player_max_num: 20,
-            base_camp_max_num_in_guild: 4,
-            pal_egg_default_hatching_time: 72.0,
-            work_speed_rate: 1.0,
-            auto_save_span: 30.0,
-            is_multiplay: false,
-            is_pvp: false,
-            hardcore: false,
-            pal_lost: false,
-            can_pickup_other_guild_death_penalty_drop: false,
-            enable_non_login_penalty: true,
-            enable_fast_travel: true,
-            is_start_location_select_by_map: true,
-            exist_player_after_logout: false,
-            enable_defense_other_guild_player: false,
-            invisible_other_guild_base_camp_area_fx: false,
-            build_area_limit: false,
-            item_weight_rate: 1.0,
-            coop_player_max_num: 4,
-            server_player_max_num: 32,
-            server_name: "Default Palworld Server".to_owned(),
-            server_description: String::new(),
-            admin_password: String::new(),
-            server_password: String::new(),
-            public_port: 8211,
-            public_ip: String::new(),
-            rcon_enabled: false,
-            rcon_port: 25575,
-            use_auth: true,
-            region: String::new(),
-            ban_list_url: "https://api.palworldgame.com/api/banlist.txt".to_owned(),
-            restapi_enabled: false,
-            restapi_port: 8212,
-            show_player_list: false,
-            chat_post_limit_per_minute: 10,
-            crossplay_platforms: "(Steam,Xbox,PS5,Mac)".to_owned(),
-            is_use_backup_save_data: true,
-            log_format_type: "Text".to_owned(),
-            supply_drop_span: 180.0,
-            enable_predator_boss_pal: true,
-            max_building_limit_num: 0,
-            server_replicate_pawn_cull_distance: 15000.0,
-        }
+        Self::env_defaults()
     }
 
     /// Applies preset-specific overrides.
@@ -443,7 +448,6 @@ impl GameSettings {
 }
 
 impl Default for GameSettings {
-    #[allow(clippy::too_many_lines)]
     fn default() -> Self {
         // Start with Normal preset as our base.
         let mut settings = Self::normal();
@@ -455,317 +459,35 @@ impl Default for GameSettings {
             settings.apply_preset(preset);
         }
 
-        Self {
-            difficulty: env::var("DIFFICULTY").unwrap_or_else(|_| settings.difficulty.clone()),
-            randomizer_type: env::var("RANDOMIZER_TYPE")
-                .unwrap_or_else(|_| settings.randomizer_type.clone()),
-            randomizer_seed: env::var("RANDOMIZER_SEED")
-                .unwrap_or_else(|_| settings.randomizer_seed.clone()),
-            is_randomizer_pal_level_random: env_parse!(
-                "B_IS_RANDOMIZER_PAL_LEVEL_RANDOM",
-                settings.is_randomizer_pal_level_random,
-                bool
-            ),
-            day_time_speed_rate: env_parse!(
-                "DAY_TIME_SPEED_RATE",
-                settings.day_time_speed_rate,
-                f32
-            ),
-            night_time_speed_rate: env_parse!(
-                "NIGHT_TIME_SPEED_RATE",
-                settings.night_time_speed_rate,
-                f32
-            ),
-            exp_rate: env_parse!("EXP_RATE", settings.exp_rate, f32),
-            pal_capture_rate: env_parse!("PAL_CAPTURE_RATE", settings.pal_capture_rate, f32),
-            pal_spawn_num_rate: env_parse!("PAL_SPAWN_NUM_RATE", settings.pal_spawn_num_rate, f32),
-            pal_damage_rate_attack: env_parse!(
-                "PAL_DAMAGE_RATE_ATTACK",
-                settings.pal_damage_rate_attack,
-                f32
-            ),
-            pal_damage_rate_defense: env_parse!(
-                "PAL_DAMAGE_RATE_DEFENSE",
-                settings.pal_damage_rate_defense,
-                f32
-            ),
-            allow_global_palbox_export: env_parse!(
-                "B_ALLOW_GLOBAL_PALBOX_EXPORT",
-                settings.allow_global_palbox_export,
-                bool
-            ),
-            allow_global_palbox_import: env_parse!(
-                "B_ALLOW_GLOBAL_PALBOX_IMPORT",
-                settings.allow_global_palbox_import,
-                bool
-            ),
-            character_recreate_in_hardcore: env_parse!(
-                "B_CHARACTER_RECREATE_IN_HARDCORE",
-                settings.character_recreate_in_hardcore,
-                bool
-            ),
-            player_damage_rate_attack: env_parse!(
-                "PLAYER_DAMAGE_RATE_ATTACK",
-                settings.player_damage_rate_attack,
-                f32
-            ),
-            player_damage_rate_defense: env_parse!(
-                "PLAYER_DAMAGE_RATE_DEFENSE",
-                settings.player_damage_rate_defense,
-                f32
-            ),
-            player_stomach_decrease_rate: env_parse!(
-                "PLAYER_STOMACH_DECREASE_RATE",
-                settings.player_stomach_decrease_rate,
-                f32
-            ),
-            player_stamina_decrease_rate: env_parse!(
-                "PLAYER_STAMINA_DECREASE_RATE",
-                settings.player_stamina_decrease_rate,
-                f32
-            ),
-            player_auto_hp_regen_rate: env_parse!(
-                "PLAYER_AUTO_HP_REGEN_RATE",
-                settings.player_auto_hp_regen_rate,
-                f32
-            ),
-            player_auto_hp_regen_rate_in_sleep: env_parse!(
-                "PLAYER_AUTO_HP_REGEN_RATE_IN_SLEEP",
-                settings.player_auto_hp_regen_rate_in_sleep,
-                f32
-            ),
-            pal_stomach_decrease_rate: env_parse!(
-                "PAL_STOMACH_DECREASE_RATE",
-                settings.pal_stomach_decrease_rate,
-                f32
-            ),
-            pal_stamina_decrease_rate: env_parse!(
-                "PAL_STAMINA_DECREASE_RATE",
-                settings.pal_stamina_decrease_rate,
-                f32
-            ),
-            pal_auto_hp_regen_rate: env_parse!(
-                "PAL_AUTO_HP_REGEN_RATE",
-                settings.pal_auto_hp_regen_rate,
-                f32
-            ),
-            pal_auto_hp_regen_rate_in_sleep: env_parse!(
-                "PAL_AUTO_HP_REGEN_RATE_IN_SLEEP",
-                settings.pal_auto_hp_regen_rate_in_sleep,
-                f32
-            ),
-            build_object_hp_rate: env_parse!(
-                "BUILD_OBJECT_HP_RATE",
-                settings.build_object_hp_rate,
-                f32
-            ),
-            build_object_damage_rate: env_parse!(
-                "BUILD_OBJECT_DAMAGE_RATE",
-                settings.build_object_damage_rate,
-                f32
-            ),
-            build_object_deterioration_damage_rate: env_parse!(
-                "BUILD_OBJECT_DETERIORATION_DAMAGE_RATE",
-                settings.build_object_deterioration_damage_rate,
-                f32
-            ),
-            collection_drop_rate: env_parse!(
-                "COLLECTION_DROP_RATE",
-                settings.collection_drop_rate,
-                f32
-            ),
-            collection_object_hp_rate: env_parse!(
-                "COLLECTION_OBJECT_HP_RATE",
-                settings.collection_object_hp_rate,
-                f32
-            ),
-            collection_object_respawn_speed_rate: env_parse!(
-                "COLLECTION_OBJECT_RESPAWN_SPEED_RATE",
-                settings.collection_object_respawn_speed_rate,
-                f32
-            ),
-            enemy_drop_item_rate: env_parse!(
-                "ENEMY_DROP_ITEM_RATE",
-                settings.enemy_drop_item_rate,
-                f32
-            ),
-            death_penalty: env::var("DEATH_PENALTY")
-                .unwrap_or_else(|_| settings.death_penalty.clone()),
-            enable_pvp: env_parse!("ENABLE_PVP", settings.enable_pvp, bool),
-            enable_friendly_fire: env_parse!(
-                "ENABLE_FRIENDLY_FIRE",
-                settings.enable_friendly_fire,
-                bool
-            ),
-            enable_invader_enemy: env_parse!(
-                "ENABLE_INVADER_ENEMY",
-                settings.enable_invader_enemy,
-                bool
-            ),
-            active_unko: env_parse!("ACTIVE_UNKO", settings.active_unko, bool),
-            enable_aim_assist_pad: env_parse!(
-                "ENABLE_AIM_ASSIST_PAD",
-                settings.enable_aim_assist_pad,
-                bool
-            ),
-            enable_aim_assist_keyboard: env_parse!(
-                "ENABLE_AIM_ASSIST_KEYBOARD",
-                settings.enable_aim_assist_keyboard,
-                bool
-            ),
-            drop_item_max_num: env_parse!("DROP_ITEM_MAX_NUM", settings.drop_item_max_num, u32),
-            drop_item_max_num_unko: env_parse!(
-                "DROP_ITEM_MAX_NUM_UNKO",
-                settings.drop_item_max_num_unko,
-                u32
-            ),
-            base_camp_max_num: env_parse!("BASE_CAMP_MAX_NUM", settings.base_camp_max_num, u16),
-            base_camp_worker_max_num: env_parse!(
-                "BASE_CAMP_WORKER_MAX_NUM",
-                settings.base_camp_worker_max_num,
-                u16
-            ),
-            drop_item_alive_max_hours: env_parse!(
-                "DROP_ITEM_ALIVE_MAX_HOURS",
-                settings.drop_item_alive_max_hours,
-                f32
-            ),
-            auto_reset_guild_no_online_players: env_parse!(
-                "AUTO_RESET_GUILD_NO_ONLINE_PLAYERS",
-                settings.auto_reset_guild_no_online_players,
-                bool
-            ),
-            auto_reset_guild_time_no_online_players: env_parse!(
-                "AUTO_RESET_GUILD_TIME_NO_ONLINE_PLAYERS",
-                settings.auto_reset_guild_time_no_online_players,
-                f32
-            ),
-            guild_player_max_num: env_parse!(
-                "GUILD_PLAYER_MAX_NUM",
-                settings.guild_player_max_num,
-                u16
-            ),
-            base_camp_max_num_in_guild: env_parse!(
-                "BASE_CAMP_MAX_NUM_IN_GUILD",
-                settings.base_camp_max_num_in_guild,
-                u16
-            ),
-            pal_egg_default_hatching_time: env_parse!(
-                "PAL_EGG_DEFAULT_HATCHING_TIME",
-                settings.pal_egg_default_hatching_time,
-                f32
-            ),
-            work_speed_rate: env_parse!("WORK_SPEED_RATE", settings.work_speed_rate, f32),
-            auto_save_span: env_parse!("AUTO_SAVE_SPAN", settings.auto_save_span, f32),
-            is_multiplay: env_parse!("IS_MULTIPLAY", settings.is_multiplay, bool),
-            is_pvp: env_parse!("IS_PVP", settings.is_pvp, bool),
-            hardcore: env_parse!("HARDCORE", settings.hardcore, bool),
-            pal_lost: env_parse!("PAL_LOST", settings.pal_lost, bool),
-            can_pickup_other_guild_death_penalty_drop: env_parse!(
-                "CAN_PICKUP_OTHER_GUILD_DEATH_PENALTY_DROP",
-                settings.can_pickup_other_guild_death_penalty_drop,
-                bool
-            ),
-            enable_non_login_penalty: env_parse!(
-                "ENABLE_NON_LOGIN_PENALTY",
-                settings.enable_non_login_penalty,
-                bool
-            ),
-            enable_fast_travel: env_parse!("ENABLE_FAST_TRAVEL", settings.enable_fast_travel, bool),
-            is_start_location_select_by_map: env_parse!(
-                "IS_START_LOCATION_SELECT_BY_MAP",
-                settings.is_start_location_select_by_map,
-                bool
-            ),
-            exist_player_after_logout: env_parse!(
-                "EXIST_PLAYER_AFTER_LOGOUT",
-                settings.exist_player_after_logout,
-                bool
-            ),
-            enable_defense_other_guild_player: env_parse!(
-                "ENABLE_DEFENSE_OTHER_GUILD_PLAYER",
-                settings.enable_defense_other_guild_player,
-                bool
-            ),
-            invisible_other_guild_base_camp_area_fx: env_parse!(
-                "INVISIBLE_OTHER_GUILD_BASE_CAMP_AREA_FX",
-                settings.invisible_other_guild_base_camp_area_fx,
-                bool
-            ),
-            build_area_limit: env_parse!("BUILD_AREA_LIMIT", settings.build_area_limit, bool),
-            item_weight_rate: env_parse!("ITEM_WEIGHT_RATE", settings.item_weight_rate, f32),
-            coop_player_max_num: env_parse!(
-                "COOP_PLAYER_MAX_NUM",
-                settings.coop_player_max_num,
-                u16
-            ),
-            server_player_max_num: env_parse!(
-                "SERVER_PLAYER_MAX_NUM",
-                settings.server_player_max_num,
-                u16
-            ),
-            server_name: env::var("SERVER_NAME").unwrap_or_else(|_| settings.server_name.clone()),
-            server_description: env::var("SERVER_DESCRIPTION")
-                .unwrap_or_else(|_| settings.server_description.clone()),
-            admin_password: env::var("ADMIN_PASSWORD")
-                .unwrap_or_else(|_| settings.admin_password.clone()),
-            server_password: env::var("SERVER_PASSWORD")
-                .unwrap_or_else(|_| settings.server_password.clone()),
-            public_port: env_parse!("PUBLIC_PORT", settings.public_port, u16),
-            public_ip: env::var("PUBLIC_IP").unwrap_or_else(|_| settings.public_ip.clone()),
-            rcon_enabled: env_parse!("RCON_ENABLED", settings.rcon_enabled, bool),
-            rcon_port: env_parse!("RCON_PORT", settings.rcon_port, u16),
-            use_auth: env_parse!("USE_AUTH", settings.use_auth, bool),
-            region: env_parse!("REGION", settings.region, String),
-            ban_list_url: env::var("BAN_LIST").unwrap_or_else(|_| settings.ban_list_url.clone()),
-            restapi_enabled: env_parse!("RESTAPI_ENABLED", settings.restapi_enabled, bool),
-            restapi_port: env_parse!("RESTAPI_PORT", settings.restapi_port, u16),
-            show_player_list: env_parse!("SHOW_PLAYER_LIST", settings.show_player_list, bool),
-            chat_post_limit_per_minute: env_parse!(
-                "CHAT_POST_LIMIT_PER_MINUTE",
-                settings.chat_post_limit_per_minute,
-                u16
-            ),
-            crossplay_platforms: env::var("CROSSPLAY_PLATFORMS")
-                .unwrap_or_else(|_| settings.crossplay_platforms.clone()),
-            is_use_backup_save_data: env_parse!(
-                "IS_USE_BACKUP_SAVE_DATA",
-                settings.is_use_backup_save_data,
-                bool
-            ),
-            log_format_type: env::var("LOG_FORMAT_TYPE")
-                .unwrap_or_else(|_| settings.log_format_type.clone()),
-            supply_drop_span: env_parse!("SUPPLY_DROP_SPAN", settings.supply_drop_span, f32),
-            enable_predator_boss_pal: env_parse!(
-                "ENABLE_PREDATOR_BOSS_PAL",
-                settings.enable_predator_boss_pal,
-                bool
-            ),
-            max_building_limit_num: env_parse!(
-                "MAX_BUILDING_LIMIT_NUM",
-                settings.max_building_limit_num,
-                u32
-            ),
-            server_replicate_pawn_cull_distance: env_parse!(
-                "SERVER_REPLICATE_PAWN_CULL_DISTANCE",
-                settings.server_replicate_pawn_cull_distance,
-                f32
-            ),
+        // Variables holding invalid values leave the preset's values in place.
+        if let Err(error) = settings.apply_env() {
+            eprintln!("Ignoring {error}");
         }
+        settings
     }
 }
 
 /// Saves the configuration to an INI file.
+///
+/// An existing file is patched rather than replaced, so settings added by game updates
+/// that `GameSettings` does not know about yet, and comments, are kept.
 pub fn save_config(path: &Path, settings: &Settings) {
-    let ini_config = match to_string(&settings) {
-        Ok(config) => config,
+    let mut document = match IniDocument::from_file(path) {
+        Ok(document) => document,
+        Err(IniError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            IniDocument::default()
+        }
         Err(error) => {
-            eprintln!("Failed to serialize config: {error}");
+            eprintln!("Failed to read config {}: {error}", path.display());
             return;
         }
     };
+    if let Err(error) = document.patch(settings) {
+        eprintln!("Failed to serialize config: {error}");
+        return;
+    }
 
-    if let Err(e) = fs::write(path, ini_config) {
+    if let Err(e) = document.to_file(path) {
         eprintln!("Failed to save config: {e}");
     }
 }
@@ -791,15 +513,14 @@ mod tests {
     #![allow(clippy::float_cmp, clippy::unwrap_used)]
 
     use super::*;
+    use gsm_serde::serde_ini::{from_str, to_string};
     use std::env;
     use std::fs;
-    use std::path::Path;
     use std::sync::{LazyLock, Mutex};
+    use tempfile::tempdir;
 
     static TEST_MUTEX: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
-    const TEST_DIR: &str = "./tmp/tests";
-
     /// Helper function to reset environment variables
     fn clear_env_vars() {
         let vars = [
@@ -810,6 +531,7 @@ mod tests {
             "PAL_CAPTURE_RATE",
             "PRESET",
             "SERVER_NAME",
+            "ADMIN_PASSWORD",
         ];
         for var in &vars {
             unsafe { env::remove_var(var) };
@@ -895,10 +617,8 @@ mod tests {
         let _lock = TEST_MUTEX.lock().unwrap();
 
         clear_env_vars();
-        let test_path = Path::new(TEST_DIR).join("test_config.ini");
-
-        // Ensure the test directory exists
-        fs::create_dir_all(TEST_DIR).unwrap();
+        let test_dir = tempdir().unwrap();
+        let test_path = test_dir.path().join("test_config.ini");
 
         let settings = Settings::default();
         save_config(&test_path, &settings);
@@ -909,4 +629,59 @@ mod tests {
         assert_eq!(loaded_settings.server_name, "Default Palworld Server");
         assert_eq!(loaded_settings.exp_rate, 1.0);
     }
+
+    #[test]
+    fn save_config_keeps_settings_it_does_not_know() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+
+        clear_env_vars();
+        let test_dir = tempdir().unwrap();
+        let test_path = test_dir.path().join("patched_config.ini");
+        fs::write(
+            &test_path,
+            "; Written by the game\n[/Script/Pal.PalGameWorldSettings]\n\
+OptionSettings=(Difficulty=None,ExpRate=1.000000,FutureSetting=True)\n",
+        )
+        .unwrap();
+        unsafe { env::set_var("EXP_RATE", "2.5") };
+
+        save_config(&test_path, &Settings::default());
+        let written = fs::read_to_string(&test_path).unwrap();
+        assert!(written.starts_with("; Written by the game\n"));
+        assert!(written.contains("(Difficulty=None,ExpRate=2.5,FutureSetting=True,"));
+
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_passwords_are_written_but_not_logged() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+
+        clear_env_vars();
+        unsafe { env::set_var("ADMIN_PASSWORD", "hunter2") };
+
+        let settings = Settings::default();
+        assert_eq!(settings.option_settings.admin_password.expose(), "hunter2");
+        assert!(!format!("{settings:?}").contains("hunter2"));
+        assert!(to_string(&settings).unwrap().contains("hunter2"));
+
+        clear_env_vars();
+    }
+
+    #[test]
+    fn enum_like_settings_are_written_unquoted() {
+        let _lock = TEST_MUTEX.lock().unwrap();
+
+        clear_env_vars();
+        let written = to_string(&Settings::default()).unwrap();
+        assert!(written.contains("Difficulty=None,"));
+        assert!(written.contains("CrossplayPlatforms=(Steam,Xbox,PS5,Mac),"));
+        assert!(written.contains("ServerName=\"Default Palworld Server\","));
+
+        let loaded: Settings = from_str(&written).unwrap();
+        assert_eq!(
+            loaded.option_settings.crossplay_platforms,
+            "(Steam,Xbox,PS5,Mac)"
+        );
+    }
 }
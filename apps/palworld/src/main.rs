@@ -1,241 +1,152 @@
 mod environment;
 mod game_settings;
-mod utils;
 
 use crate::environment::name;
-use clap::{Parser, Subcommand};
-use gsm_cron::{begin_cron_loop, register_job};
-use gsm_instance::{Instance, InstanceConfig};
-use gsm_monitor::LogRules;
-use gsm_notifications::notifications::{StandardServerEvents, send_notifications};
-use gsm_shared::{fetch_var, is_env_var_truthy};
+use gsm_instance::InstanceConfig;
+use gsm_instance::cli::{self, CliCustomizations, notify_game_event};
+use gsm_instance::config::DownloadConfig;
+use gsm_instance::config_file::ConfigFile;
+use gsm_instance::hooks::Hooks;
+use gsm_instance::ports::GamePort;
+use gsm_instance::resources::ResourceLimits;
+use gsm_instance::saves::SaveGlobs;
+use gsm_instance::workshop::WorkshopConfig;
+use gsm_monitor::{LogRules, PALWORLD_CHAT_PATTERN};
+use gsm_notifications::notifications::{StandardServerEvents, notify};
+use gsm_shared::{
+    VarSpec, export_missing_vars, fetch_var, is_env_var_truthy, load_default_dotenv, validate_flag,
+};
+use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
-use std::process::exit;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use std::process::ExitCode;
+use tracing::{debug, error};
 
-#[derive(Parser)]
-#[command(name = "palworld", version = "1.0", about = "Manage Palworld Server")]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
-}
+/// Palworld's Steam App ID.
+const APP_ID: u32 = 2_394_010;
 
-#[derive(Subcommand)]
-enum Commands {
-    Install {
-        #[arg(long, default_value = "/home/steam/palworld")]
-        path: PathBuf,
-    },
-    Start,
-    Monitor {
-        #[arg(long)]
-        update_job: bool,
-    },
-    Stop,
-    Restart,
-    Update {
-        #[arg(long)]
-        check: bool,
-    },
-}
+fn launch_args() -> Vec<String> {
+    let mut args = vec!["./PalServer.sh".to_owned()];
 
-#[allow(clippy::too_many_lines)]
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
-    debug!("Tracing subscriber initialized.");
+    if let Ok(public_ip) = env::var("PUBLIC_IP") {
+        args.push(format!("-publicip={public_ip}"));
+    }
 
-    let cli = Cli::parse();
-    let instance_config = InstanceConfig {
-        app_id: 2_394_010, // Palworld Steam App ID
-        name: name(),
-        command: "/bin/bash".to_owned(),
-        install_args: vec![],
-        launch_args: {
-            let mut args = vec!["./PalServer.sh".to_owned()];
+    if let Some(public_port) = env::var("PORT").ok().or_else(|| Some("8211".to_owned())) {
+        args.push(format!("-port={public_port}"));
+    }
 
-            if let Ok(public_ip) = env::var("PUBLIC_IP") {
-                args.push(format!("-publicip={public_ip}"));
-            }
+    if let Some(public_port) = env::var("PUBLIC_PORT")
+        .ok()
+        .or_else(|| Some("8211".to_owned()))
+    {
+        args.push(format!("-publicport={public_port}"));
+    }
 
-            if let Some(public_port) = env::var("PORT").ok().or_else(|| Some("8211".to_owned())) {
-                args.push(format!("-port={public_port}"));
-            }
+    if is_env_var_truthy("PUBLIC_LOBBY") {
+        args.push("-publiclobby".to_owned());
+    }
 
-            if let Some(public_port) = env::var("PUBLIC_PORT")
-                .ok()
-                .or_else(|| Some("8211".to_owned()))
-            {
-                args.push(format!("-publicport={public_port}"));
-            }
+    if is_env_var_truthy("MULTITHREADING") {
+        args.push("-useperfthreads".to_owned());
+        args.push("-NoAsyncLoadingThread".to_owned());
+        args.push("-UseMultithreadForDS".to_owned());
+    }
 
-            if is_env_var_truthy("PUBLIC_LOBBY") {
-                args.push("-publiclobby".to_owned());
-            }
+    args
+}
 
-            if is_env_var_truthy("MULTITHREADING") {
-                args.push("-useperfthreads".to_owned());
-                args.push("-NoAsyncLoadingThread".to_owned());
-                args.push("-UseMultithreadForDS".to_owned());
-            }
+/// Returns the UDP port the server listens on, from `PORT`, so it can be checked before
+/// launch.
+fn game_port() -> Option<u16> {
+    fetch_var("PORT", "8211").parse().ok()
+}
 
-            args
-        },
-        force_windows: false,
-        launch_mode: gsm_instance::config::LaunchMode::Native,
-        working_dir: PathBuf::from("/home/steam/palworld"),
-    };
-    debug!("Instance configuration set: {:?}", instance_config);
+/// Matches Palworld's log lines and, with `CHAT_RELAY`, relays in-game chat to the
+/// webhook, e.g. a Discord channel.
+fn log_rules() -> Result<LogRules, String> {
+    let rules = LogRules::palworld(notify_game_event);
+    if env::var("WEBHOOK_URL").is_ok() && is_env_var_truthy("CHAT_RELAY") {
+        let pattern = fetch_var("CHAT_PATTERN", PALWORLD_CHAT_PATTERN);
+        rules
+            .add_chat_rule(&pattern, |chat| {
+                notify(StandardServerEvents::ChatMessage {
+                    player: chat.player,
+                    message: chat.message,
+                });
+            })
+            .map_err(|e| format!("{e}; check CHAT_PATTERN"))?;
+    }
+    Ok(rules)
+}
 
-    let instance = Arc::new(Mutex::new(Instance::new(instance_config)));
-    debug!("Instance created and wrapped in Arc<Mutex<>>");
+fn main() -> ExitCode {
+    gsm_shared::logging::init();
+    debug!("Tracing subscriber initialized.");
 
-    match cli.command {
-        Commands::Install { path } => {
-            info!("Installing Palworld server to: {:?}", path);
-            let inst = instance.lock().await;
-            if let Err(e) = inst.install() {
-                error!("Installation failed: {}", e);
-            } else {
-                debug!("Installation successful.");
-                let config_path = path.join("Pal/Saved/Config/LinuxServer/PalWorldSettings.ini");
-                game_settings::load_or_create_config(&config_path);
-            }
-        }
-        Commands::Start => {
-            info!("Starting server...");
-            let inst = instance.lock().await;
-            if let Err(e) = inst.start() {
-                error!("Failed to start server: {}", e);
-            }
+    // Variables from a .env file, then from a mounted gsm.toml/gsm.yaml, must be
+    // exported before anything below reads the environment, and before the runtime
+    // starts threads.
+    // SAFETY: the async runtime is not built yet, so this is the only thread.
+    if let Err(e) = unsafe { load_default_dotenv() } {
+        error!("Failed to load the .env file: {e}");
+        return ExitCode::FAILURE;
+    }
+    let config_file = match ConfigFile::from_env() {
+        Ok(config_file) => config_file,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
         }
-        Commands::Monitor { update_job } => {
-            let working_dir = {
-                let inst = instance.lock().await;
-                inst.config.working_dir.clone()
-            };
-
-            let rules = LogRules::default();
-
-            if env::var("WEBHOOK_URL").is_ok() {
-                rules.add_rule(
-                    |line| line.contains("Running Palworld dedicated server on"),
-                    |_| {
-                        if let Err(e) = send_notifications(StandardServerEvents::Started) {
-                            warn!("Failed to send webhook notification: {e}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-
-                rules.add_rule(
-                    |line| line.contains("joined the server."),
-                    |line| {
-                        if let Some(name) = utils::extract_player_joined_name(line) {
-                            if let Err(e) =
-                                send_notifications(StandardServerEvents::PlayerJoined(name))
-                            {
-                                warn!("Failed to send webhook notification: {e}");
-                            }
-                        } else {
-                            error!("Failed to extract player name from:\n{line}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-
-                rules.add_rule(
-                    |line| line.contains("left the server."),
-                    |line| {
-                        if let Some(name) = utils::extract_player_left_name(line) {
-                            if let Err(e) =
-                                send_notifications(StandardServerEvents::PlayerLeft(name))
-                            {
-                                warn!("Failed to send webhook notification: {e}");
-                            }
-                        } else {
-                            error!("Failed to extract player name from:\n{line}");
-                        }
-                    },
-                    false,
-                    None,
-                );
-            }
-
-            gsm_monitor::start_instance_log_monitor(&working_dir, rules);
+    };
+    // SAFETY: as above.
+    unsafe { export_missing_vars(config_file.env_vars(), "the config file") };
 
-            if update_job || is_env_var_truthy("AUTO_UPDATE") {
-                let update_schedule = fetch_var("AUTO_UPDATE_SCHEDULE", "0 3 * * *");
-                let instance_clone = Arc::clone(&instance);
-                register_job("auto-update", &update_schedule, move || {
-                    let instance_clone_inner = Arc::clone(&instance_clone);
-                    tokio::spawn(async move {
-                        let inst = instance_clone_inner.lock().await;
-                        if inst.update_available() {
-                            warn!("Update available! Stopping server...");
-                            if let Err(e) = inst.stop() {
-                                error!("Failed to stop server: {}", e);
-                                return;
-                            }
-                            info!("Updating server...");
-                            if let Err(e) = inst.update() {
-                                error!("Update failed: {}", e);
-                                return;
-                            }
-                            info!("Restarting server...");
-                            if let Err(e) = inst.start() {
-                                error!("Failed to start server: {}", e);
-                            }
-                        }
-                    });
-                });
-            }
+    let instance_config = InstanceConfig {
+        app_id: APP_ID,
+        name: name(),
+        command: "/bin/bash".to_owned(),
+        install_args: vec![],
+        launch_args: launch_args(),
+        skip_validate: false,
+        launch_mode: gsm_instance::config::LaunchMode::Native,
+        working_dir: PathBuf::from("/home/steam/palworld"),
+        query_port: None,
+        ports: game_port().map(GamePort::udp).into_iter().collect(),
+        beta: None,
+        env: HashMap::new(),
+        clear_env: false,
+        hooks: Hooks::default(),
+        daemonize: true,
+        proton: None,
+        workshop: WorkshopConfig::default(),
+        readiness: None,
+        downloads: DownloadConfig::default(),
+        resources: ResourceLimits::default(),
+        shared_install: None,
+        dry_run: false,
+    };
 
-            debug!("Entering cron loop (monitoring logs and scheduled tasks)...");
-            begin_cron_loop().await;
-        }
-        Commands::Stop => {
-            warn!("Stopping Palworld server...");
-            let inst = instance.lock().await;
-            if let Err(e) = inst.stop() {
-                error!("Failed to stop: {}", e);
-            } else {
-                if env::var("WEBHOOK_URL").is_ok()
-                    && let Err(e) = send_notifications(StandardServerEvents::Stopped)
-                {
-                    warn!("Failed to send webhook notification: {e}");
-                }
-                debug!("Server stopped successfully.");
-            }
-        }
-        Commands::Restart => {
-            warn!("Restarting Palworld server...");
-            let inst = instance.lock().await;
-            if let Err(e) = inst.restart() {
-                error!("Failed to restart server: {}", e);
-            }
-        }
-        Commands::Update { check } => {
-            let inst = instance.lock().await;
-            if check {
-                if inst.update_available() {
-                    info!("Update available!");
-                    exit(1);
-                } else {
-                    info!("Server is up to date.");
-                    exit(0);
-                }
-            } else if inst.update_available() {
-                warn!("Update available! Updating...");
-                if let Err(e) = inst.update() {
-                    error!("Update failed: {}", e);
-                }
-            }
+    let instance_config = config_file.apply(instance_config);
+    let customizations = CliCustomizations::new("palworld", "Manage Palworld Server")
+        .with_version("1.0")
+        .with_after_install(|path| {
+            let config_path = path.join("Pal/Saved/Config/LinuxServer/PalWorldSettings.ini");
+            game_settings::load_or_create_config(&config_path);
+        })
+        .with_log_rules(log_rules)
+        .with_auto_backup("Pal/Saved")
+        .with_saves(SaveGlobs::new(["Pal/Saved"]))
+        .with_env([
+            VarSpec::optional("CHAT_RELAY").with_validator(validate_flag),
+            VarSpec::optional("MULTITHREADING").with_validator(validate_flag),
+            VarSpec::optional("PUBLIC_LOBBY").with_validator(validate_flag),
+        ]);
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(cli::run(instance_config, customizations)),
+        Err(e) => {
+            error!("Failed to start the async runtime: {e}");
+            ExitCode::FAILURE
         }
     }
 }
@@ -1,3 +0,0 @@
-mod extract_player_name;
-
-pub use extract_player_name::*;
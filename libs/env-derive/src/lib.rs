@@ -0,0 +1,213 @@
+//! # Environment Configuration Derive
+//!
+//! This crate provides `#[derive(EnvConfig)]`, which implements `env_parse::EnvConfig` for a struct so the whole
+//! struct is loaded from environment variables at once, instead of one `env_parse!` per field.
+//!
+//! Each field is read from the variable named after it in upper case, unless told otherwise with `#[env(...)]`:
+//!
+//! - `name = "VAR"`: the variable the field is read from.
+//! - `default = expr`: the field's value when the variable is not set. String literals are converted to the
+//!   field's type. Fields without one default to `Default::default()`.
+//! - `parse_with = "path::to::fn"`: a `fn(&str) -> Result<T, E>` to parse the value with, instead of `FromStr`.
+//! - `skip`: the field is not read from the environment.
+//!
+//! A field's doc comment becomes the description of its variable in `EnvConfig::env_vars`.
+extern crate proc_macro;
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Expr, ExprLit, Fields, Ident, Lit, LitStr, Meta, Type, parse_macro_input,
+};
+
+/// A field of the derive target and how it is read from the environment.
+struct EnvField {
+    ident: Ident,
+    ty: Type,
+    name: String,
+    default: Option<Expr>,
+    parser: Option<syn::Path>,
+    doc: String,
+    skip: bool,
+}
+
+impl EnvField {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let Some(ident) = field.ident.clone() else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "EnvConfig can only be derived for structs with named fields",
+            ));
+        };
+        let mut env_field = Self {
+            name: ident.to_string().trim_start_matches("r#").to_uppercase(),
+            ident,
+            ty: field.ty.clone(),
+            default: None,
+            parser: None,
+            doc: String::new(),
+            skip: false,
+        };
+        let mut doc = Vec::new();
+
+        for attr in &field.attrs {
+            if attr.path().is_ident("doc") {
+                if let Meta::NameValue(meta) = &attr.meta
+                    && let Expr::Lit(ExprLit {
+                        lit: Lit::Str(line),
+                        ..
+                    }) = &meta.value
+                {
+                    doc.push(line.value().trim().to_owned());
+                }
+            } else if attr.path().is_ident("env") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("name") {
+                        env_field.name = meta.value()?.parse::<LitStr>()?.value();
+                    } else if meta.path.is_ident("default") {
+                        env_field.default = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("parse_with") {
+                        env_field.parser = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    } else if meta.path.is_ident("skip") {
+                        env_field.skip = true;
+                    } else {
+                        return Err(
+                            meta.error("expected `name`, `default`, `parse_with` or `skip`")
+                        );
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        env_field.doc = doc.join(" ");
+        Ok(env_field)
+    }
+
+    /// The expression the field is initialized with before the environment is read.
+    fn default_value(&self) -> TokenStream2 {
+        let ty = &self.ty;
+        match &self.default {
+            Some(Expr::Lit(ExprLit {
+                lit: Lit::Str(text),
+                ..
+            })) => quote! { <#ty as ::core::convert::From<&'static str>>::from(#text) },
+            Some(expr) => quote! { #expr },
+            None => quote! { <#ty as ::core::default::Default>::default() },
+        }
+    }
+
+    /// The default as documented in the variable list.
+    fn default_text(&self) -> TokenStream2 {
+        match &self.default {
+            Some(Expr::Lit(ExprLit {
+                lit: Lit::Str(text),
+                ..
+            })) => quote! { ::core::option::Option::Some(#text) },
+            Some(expr) => {
+                let text = quote!(#expr).to_string();
+                quote! { ::core::option::Option::Some(#text) }
+            }
+            None => quote! { ::core::option::Option::None },
+        }
+    }
+}
+
+#[proc_macro_derive(EnvConfig, attributes(env))]
+/// Derives `env_parse::EnvConfig` for a struct with named fields, configured per field
+/// with `#[env(name = "...", default = ..., parse_with = "...", skip)]`.
+pub fn env_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "EnvConfig can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "EnvConfig can only be derived for structs with named fields",
+        ));
+    };
+    let fields = fields
+        .named
+        .iter()
+        .map(EnvField::parse)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let defaults = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let value = field.default_value();
+        quote! { #ident: #value }
+    });
+
+    let read = fields.iter().filter(|field| !field.skip).map(|field| {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let var = &field.name;
+        let parser = field.parser.as_ref().map_or_else(
+            || quote! { ::env_parse::__parse::<#ty> },
+            |parser| quote! { #parser },
+        );
+        quote! {
+            if let ::core::option::Option::Some(value) = ::env_parse::__read_var(#var) {
+                match #parser(&value) {
+                    ::core::result::Result::Ok(parsed) => self.#ident = parsed,
+                    ::core::result::Result::Err(error) => invalid.push(::env_parse::InvalidEnvVar {
+                        name: #var,
+                        value,
+                        reason: ::std::string::ToString::to_string(&error),
+                    }),
+                }
+            }
+        }
+    });
+
+    let vars = fields.iter().filter(|field| !field.skip).map(|field| {
+        let var = &field.name;
+        let ident = field.ident.to_string();
+        let ty = &field.ty;
+        let ty = quote!(#ty).to_string().replace(' ', "");
+        let default = field.default_text();
+        let doc = &field.doc;
+        quote! {
+            ::env_parse::EnvVar {
+                name: #var,
+                field: #ident,
+                ty: #ty,
+                default: #default,
+                doc: #doc,
+            }
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::env_parse::EnvConfig for #name #ty_generics #where_clause {
+            fn env_defaults() -> Self {
+                Self {
+                    #(#defaults,)*
+                }
+            }
+
+            fn apply_env(&mut self) -> ::core::result::Result<(), ::env_parse::EnvConfigError> {
+                #[allow(unused_mut)]
+                let mut invalid = ::std::vec::Vec::new();
+                #(#read)*
+                ::env_parse::EnvConfigError::__check(invalid)
+            }
+
+            fn env_vars() -> &'static [::env_parse::EnvVar] {
+                const VARS: &[::env_parse::EnvVar] = &[#(#vars),*];
+                VARS
+            }
+        }
+    })
+}
@@ -3,7 +3,14 @@
 //! This crate provides a convenient macro for parsing environment variables into a specified type, with a fallback to a default value.
 //!
 //! The `env_parse!` macro simplifies the common pattern of reading an environment variable, parsing it, and using a default value if the variable is not set or parsing fails.
+//!
+//! For a whole struct of settings, the [`EnvConfig`] trait, derived with `env_derive::EnvConfig`, loads every field at once, reports every invalid value together and lists the variables it reads.
 extern crate proc_macro;
+#[cfg(test)]
+extern crate self as env_parse;
+
+use std::fmt;
+use std::str::FromStr;
 
 /// Parses an environment variable into a specified type, falling back to a default value.
 ///
@@ -57,6 +64,107 @@ macro_rules! env_parse {
     };
 }
 
+/// An environment variable an [`EnvConfig`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvVar {
+    /// The variable's name, e.g. `EXP_RATE`.
+    pub name: &'static str,
+    /// The field the variable sets.
+    pub field: &'static str,
+    /// The field's type, e.g. `f32`.
+    pub ty: &'static str,
+    /// The field's value when the variable is not set, as written in the source.
+    pub default: Option<&'static str>,
+    /// The field's doc comment.
+    pub doc: &'static str,
+}
+
+/// An environment variable whose value could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEnvVar {
+    /// The variable's name.
+    pub name: &'static str,
+    /// The value it is set to.
+    pub value: String,
+    /// Why the value was rejected.
+    pub reason: String,
+}
+
+/// The environment variables an [`EnvConfig`] could not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvConfigError {
+    /// Every variable that was set to an invalid value.
+    pub invalid: Vec<InvalidEnvVar>,
+}
+
+impl EnvConfigError {
+    #[doc(hidden)]
+    pub fn __check(invalid: Vec<InvalidEnvVar>) -> Result<(), Self> {
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(Self { invalid })
+        }
+    }
+}
+
+impl fmt::Display for EnvConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid environment variables: ")?;
+        for (index, var) in self.invalid.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}={:?} ({})", var.name, var.value, var.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EnvConfigError {}
+
+/// A struct of settings loaded from environment variables, usually derived with
+/// `#[derive(env_derive::EnvConfig)]`.
+pub trait EnvConfig: Sized {
+    /// Returns the settings every variable leaves unchanged when it is not set.
+    fn env_defaults() -> Self;
+
+    /// Sets each field whose variable is set. Fields whose variable holds an invalid
+    /// value are left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns every variable holding a value that could not be parsed.
+    fn apply_env(&mut self) -> Result<(), EnvConfigError>;
+
+    /// Returns the variables the settings are read from.
+    fn env_vars() -> &'static [EnvVar];
+
+    /// Loads the settings from the environment over [`EnvConfig::env_defaults`].
+    ///
+    /// # Errors
+    ///
+    /// Returns every variable holding a value that could not be parsed.
+    fn from_env() -> Result<Self, EnvConfigError> {
+        let mut config = Self::env_defaults();
+        config.apply_env()?;
+        Ok(config)
+    }
+}
+
+/// Returns the value of the variable `name` without wrapping quotes, if it is set.
+#[doc(hidden)]
+pub fn __read_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| __strip_wrapping_quotes(&value).to_owned())
+}
+
+#[doc(hidden)]
+pub fn __parse<T: FromStr>(value: &str) -> Result<T, T::Err> {
+    value.parse()
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Mutex, OnceLock};
@@ -173,4 +281,76 @@ mod tests {
             std::env::remove_var("ENV_PARSE_STRING_VALUE");
         }
     }
+
+    #[allow(clippy::unwrap_used)]
+    mod config {
+        use crate::EnvConfig;
+        use env_derive::EnvConfig;
+
+        fn parse_minutes(value: &str) -> Result<u64, std::num::ParseIntError> {
+            value.trim_end_matches('m').parse()
+        }
+
+        #[derive(Debug, EnvConfig)]
+        struct Settings {
+            /// The server's name.
+            #[env(name = "ENV_CONFIG_NAME", default = "My Server")]
+            name: String,
+            #[env(name = "ENV_CONFIG_RATE", default = 1.5)]
+            rate: f32,
+            #[env(name = "ENV_CONFIG_PORT")]
+            port: u16,
+            #[env(name = "ENV_CONFIG_DELAY", parse_with = "parse_minutes")]
+            delay: u64,
+            #[env(skip, default = true)]
+            loaded: bool,
+        }
+
+        #[test]
+        fn loads_every_field_and_reports_every_invalid_value() {
+            let _lock = super::env_lock()
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            unsafe {
+                std::env::set_var("ENV_CONFIG_NAME", "'Pals'");
+                std::env::set_var("ENV_CONFIG_RATE", "fast");
+                std::env::set_var("ENV_CONFIG_PORT", "99999");
+                std::env::set_var("ENV_CONFIG_DELAY", "5m");
+            }
+
+            let mut settings = Settings::env_defaults();
+            let error = settings.apply_env().unwrap_err();
+            assert_eq!(settings.name, "Pals");
+            assert!((settings.rate - 1.5).abs() < f32::EPSILON);
+            assert_eq!(settings.port, 0);
+            assert_eq!(settings.delay, 5);
+            assert!(settings.loaded);
+            assert_eq!(
+                error.invalid.iter().map(|var| var.name).collect::<Vec<_>>(),
+                ["ENV_CONFIG_RATE", "ENV_CONFIG_PORT"]
+            );
+
+            unsafe {
+                std::env::remove_var("ENV_CONFIG_RATE");
+                std::env::remove_var("ENV_CONFIG_PORT");
+            }
+            assert!(Settings::from_env().is_ok());
+            unsafe {
+                std::env::remove_var("ENV_CONFIG_NAME");
+                std::env::remove_var("ENV_CONFIG_DELAY");
+            }
+        }
+
+        #[test]
+        fn lists_the_variables_it_reads() {
+            let vars = Settings::env_vars();
+            assert_eq!(vars.len(), 4);
+            let name = vars.first().unwrap();
+            assert_eq!(name.name, "ENV_CONFIG_NAME");
+            assert_eq!(name.field, "name");
+            assert_eq!(name.ty, "String");
+            assert_eq!(name.default, Some("My Server"));
+            assert_eq!(name.doc, "The server's name.");
+        }
+    }
 }
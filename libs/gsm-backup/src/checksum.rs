@@ -0,0 +1,136 @@
+//! # Checksums
+//!
+//! Every archive written by [`crate::backup_with_options`] gets a `<archive>.sha256` file
+//! next to it, in the format `sha256sum -c` reads. [`crate::restore`] and
+//! [`crate::restore_paths`] check the archive against it before extracting anything, so a
+//! backup that was truncated or corrupted in storage is refused instead of being
+//! restored over a working server. Archives without a checksum file, such as those made
+//! before checksums were written, are restored unverified.
+use crate::BackupError;
+use gsm_shared::hash_file;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Extension appended to an archive's file name for its checksum file.
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// Returns the path of the checksum file for `archive`, e.g. `backup.tar.gz.sha256`.
+pub fn checksum_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".");
+    path.push(CHECKSUM_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Hashes `archive` and writes its checksum file, returning the SHA-256 checksum.
+///
+/// # Errors
+///
+/// Returns an error when the archive cannot be read or the checksum file written.
+pub fn write_checksum(archive: &Path) -> Result<String, BackupError> {
+    let checksum = hash_file(archive)?;
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    fs::write(checksum_path(archive), format!("{checksum}  {name}\n"))?;
+    debug!("Recorded checksum {checksum} for {}", archive.display());
+    Ok(checksum)
+}
+
+/// Checks `archive` against its checksum file. Returns `false` when there is no checksum
+/// file to check against, and `true` when the archive matches it.
+///
+/// # Errors
+///
+/// Returns [`BackupError::ChecksumMismatch`] when the archive does not match its checksum
+/// file, and an error when either file cannot be read.
+pub fn verify_checksum(archive: &Path) -> Result<bool, BackupError> {
+    let expected = match fs::read_to_string(checksum_path(archive)) {
+        Ok(contents) => contents
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            warn!(
+                "{} has no checksum file; restoring it unverified",
+                archive.display()
+            );
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let actual = hash_file(archive)?;
+    if actual != expected {
+        return Err(BackupError::ChecksumMismatch {
+            archive: archive.display().to_string(),
+            expected,
+            actual,
+        });
+    }
+    debug!("Verified checksum of {}", archive.display());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::{backup, restore};
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn create_backup() -> (tempfile::TempDir, PathBuf) {
+        let temp_dir = tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("world.sav"), "world").unwrap();
+        let archive = temp_dir.path().join("backup.tar.gz");
+        backup(&input, &archive).unwrap();
+        (temp_dir, archive)
+    }
+
+    #[test]
+    fn backups_record_the_archive_checksum() {
+        let (_temp_dir, archive) = create_backup();
+
+        let contents = fs::read_to_string(checksum_path(&archive)).unwrap();
+        assert_eq!(
+            contents,
+            format!("{}  backup.tar.gz\n", hash_file(&archive).unwrap())
+        );
+        assert!(verify_checksum(&archive).unwrap());
+    }
+
+    #[test]
+    fn corrupted_archives_are_not_restored() {
+        let (temp_dir, archive) = create_backup();
+        OpenOptions::new()
+            .append(true)
+            .open(&archive)
+            .unwrap()
+            .write_all(b"corruption")
+            .unwrap();
+
+        let target = temp_dir.path().join("restored");
+        let result = restore(&archive, &target);
+        assert!(matches!(result, Err(BackupError::ChecksumMismatch { .. })));
+        assert!(!target.join("world.sav").exists());
+    }
+
+    #[test]
+    fn archives_without_a_checksum_are_restored_unverified() {
+        let (temp_dir, archive) = create_backup();
+        fs::remove_file(checksum_path(&archive)).unwrap();
+
+        assert!(!verify_checksum(&archive).unwrap());
+        let target = temp_dir.path().join("restored");
+        restore(&archive, &target).unwrap();
+        assert!(target.join("world.sav").exists());
+    }
+}
@@ -0,0 +1,559 @@
+//! # Deduplicating Store
+//!
+//! An alternative to archive backups for worlds that change little between runs. Files
+//! are split into fixed-size chunks stored once under `objects/`, addressed by their
+//! SHA-256 hash, and each snapshot is a small manifest under `snapshots/` listing which
+//! chunks make up which file. Repeated snapshots of a mostly-unchanged world therefore
+//! only add the chunks that actually changed.
+//!
+//! ```text
+//! <root>/
+//!   objects/ab/ab12...ef      chunk contents
+//!   snapshots/<name>.manifest one line per directory or file
+//! ```
+use crate::{BackupError, collect_entries};
+use gsm_shared::{WorkdirLock, sha256_hex};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::{self, File, create_dir_all};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info};
+
+/// Default chunk size: large enough to keep manifests small, small enough that a
+/// save file touched in one place does not rewrite the whole file.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+const OBJECTS_DIR: &str = "objects";
+const SNAPSHOTS_DIR: &str = "snapshots";
+const MANIFEST_EXTENSION: &str = "manifest";
+
+/// What a call to [`DedupStore::snapshot`] stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    /// Regular files recorded in the snapshot.
+    pub files: usize,
+    /// Chunks referenced by the snapshot, including ones already in the store.
+    pub chunks: usize,
+    /// Chunks that were not yet in the store and had to be written.
+    pub new_chunks: usize,
+    /// Bytes written for those new chunks.
+    pub new_bytes: u64,
+}
+
+/// What a call to [`DedupStore::gc`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub removed_chunks: usize,
+    pub freed_bytes: u64,
+}
+
+/// One line of a snapshot manifest.
+#[derive(Debug)]
+enum ManifestEntry {
+    Dir(PathBuf),
+    File {
+        path: PathBuf,
+        mode: u32,
+        chunks: Vec<String>,
+    },
+}
+
+/// A content-addressed chunk store holding any number of named snapshots.
+#[derive(Debug, Clone)]
+pub struct DedupStore {
+    root: PathBuf,
+    chunk_size: usize,
+}
+
+impl DedupStore {
+    /// Opens the store at `root`, creating its directories if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the store directories cannot be created.
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, BackupError> {
+        let root = root.as_ref().to_path_buf();
+        create_dir_all(root.join(OBJECTS_DIR))?;
+        create_dir_all(root.join(SNAPSHOTS_DIR))?;
+        Ok(Self {
+            root,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    /// Sets the chunk size used for new snapshots. Existing snapshots are unaffected.
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Records the contents of `input` as the snapshot `name`, replacing any snapshot
+    /// of the same name. Paths containing `"backup_auto"` are skipped, as in
+    /// [`crate::backup`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `name` is not a plain file name, `input` is not a
+    /// directory, another process holds the [`WorkdirLock`] of `input` or of the store,
+    /// or a file cannot be read or a chunk written.
+    pub fn snapshot<P: AsRef<Path>>(
+        &self,
+        input: P,
+        name: &str,
+    ) -> Result<SnapshotStats, BackupError> {
+        let input = input.as_ref();
+        let manifest_path = self.manifest_path(name)?;
+        if !input.is_dir() {
+            return Err(BackupError::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Input directory {} does not exist or is not a directory",
+                    input.display()
+                ),
+            )));
+        }
+
+        let _lock = WorkdirLock::acquire(input)?;
+        // A concurrent gc would delete chunks this snapshot reuses before its manifest
+        // is written.
+        let _store_lock = self.lock()?;
+        info!("Creating snapshot {name} of {}", input.display());
+        let mut stats = SnapshotStats::default();
+        let mut manifest = String::new();
+        for (path, relative) in collect_entries(input)? {
+            let encoded = encode_path(&relative)?;
+            let metadata = path.symlink_metadata()?;
+            if metadata.is_dir() {
+                let _ = writeln!(manifest, "d\t{encoded}");
+            } else if metadata.is_file() {
+                let chunks = self.store_file(&path, &mut stats)?;
+                stats.files += 1;
+                let _ = writeln!(
+                    manifest,
+                    "f\t{:o}\t{}\t{encoded}",
+                    file_mode(&metadata),
+                    chunks.join(",")
+                );
+            } else {
+                debug!("Skipping non-regular file {}", path.display());
+            }
+        }
+
+        // Write the manifest last, and atomically, so a failed snapshot never leaves a
+        // manifest referencing chunks that were not stored.
+        let staging = manifest_path.with_extension("tmp");
+        fs::write(&staging, manifest)?;
+        fs::rename(&staging, &manifest_path)?;
+
+        info!(
+            "Snapshot {name}: {} files, {} chunks ({} new, {} bytes)",
+            stats.files, stats.chunks, stats.new_chunks, stats.new_bytes
+        );
+        Ok(stats)
+    }
+
+    /// Lists the names of every snapshot in the store, sorted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the snapshots directory cannot be read.
+    pub fn snapshots(&self) -> Result<Vec<String>, BackupError> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(self.root.join(SNAPSHOTS_DIR))? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == MANIFEST_EXTENSION)
+                && let Some(stem) = path.file_stem()
+            {
+                names.push(stem.to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Deletes the snapshot `name`. Its chunks are reclaimed by the next [`Self::gc`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the snapshot does not exist or cannot be removed.
+    pub fn remove_snapshot(&self, name: &str) -> Result<(), BackupError> {
+        fs::remove_file(self.manifest_path(name)?)?;
+        Ok(())
+    }
+
+    /// Rebuilds the snapshot `name` under `target`, returning the relative paths written.
+    ///
+    /// Existing files in `target` are overwritten; files not in the snapshot are left
+    /// untouched, matching [`crate::restore`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the snapshot does not exist, its manifest is corrupt, a
+    /// chunk is missing or no longer matches its checksum, or a file cannot be written
+    /// under `target`.
+    pub fn materialize<P: AsRef<Path>>(
+        &self,
+        snapshot: &str,
+        target: P,
+    ) -> Result<Vec<PathBuf>, BackupError> {
+        let target = target.as_ref();
+        info!(
+            "Materializing snapshot {snapshot} into {}",
+            target.display()
+        );
+        create_dir_all(target)?;
+
+        let mut restored = Vec::new();
+        for entry in self.read_manifest(snapshot)? {
+            match entry {
+                ManifestEntry::Dir(path) => {
+                    create_dir_all(target.join(&path))?;
+                    restored.push(path);
+                }
+                ManifestEntry::File { path, mode, chunks } => {
+                    let destination = target.join(&path);
+                    if let Some(parent) = destination.parent() {
+                        create_dir_all(parent)?;
+                    }
+                    let mut file = File::create(&destination)?;
+                    for hash in &chunks {
+                        let chunk = fs::read(self.object_path(hash)).map_err(|e| {
+                            BackupError::DedupError(format!(
+                                "Chunk {hash} of {} is missing: {e}",
+                                path.display()
+                            ))
+                        })?;
+                        if sha256_hex(&chunk) != *hash {
+                            return Err(BackupError::DedupError(format!(
+                                "Chunk {hash} of {} is corrupt",
+                                path.display()
+                            )));
+                        }
+                        file.write_all(&chunk)?;
+                    }
+                    set_file_mode(&destination, mode)?;
+                    debug!("Materialized {}", path.display());
+                    restored.push(path);
+                }
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Deletes every chunk not referenced by any remaining snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when another process holds the store's [`WorkdirLock`], a
+    /// manifest cannot be read or a chunk cannot be removed.
+    pub fn gc(&self) -> Result<GcStats, BackupError> {
+        let _lock = self.lock()?;
+        let mut live = HashSet::new();
+        for name in self.snapshots()? {
+            for entry in self.read_manifest(&name)? {
+                if let ManifestEntry::File { chunks, .. } = entry {
+                    live.extend(chunks);
+                }
+            }
+        }
+
+        let mut stats = GcStats::default();
+        for prefix in fs::read_dir(self.root.join(OBJECTS_DIR))? {
+            let prefix = prefix?.path();
+            if !prefix.is_dir() {
+                continue;
+            }
+            for object in fs::read_dir(&prefix)? {
+                let object = object?;
+                let hash = object.file_name().to_string_lossy().into_owned();
+                if live.contains(&hash) {
+                    continue;
+                }
+                stats.freed_bytes += object.metadata()?.len();
+                fs::remove_file(object.path())?;
+                stats.removed_chunks += 1;
+            }
+            // Only succeeds once the prefix directory is empty.
+            let _ = fs::remove_dir(&prefix);
+        }
+
+        info!(
+            "Garbage collection removed {} chunks ({} bytes)",
+            stats.removed_chunks, stats.freed_bytes
+        );
+        Ok(stats)
+    }
+
+    /// Locks the store root against concurrent snapshots and collections.
+    fn lock(&self) -> Result<WorkdirLock, BackupError> {
+        Ok(WorkdirLock::acquire(&self.root)?)
+    }
+
+    /// Splits `path` into chunks, writing any the store does not have yet.
+    fn store_file(
+        &self,
+        path: &Path,
+        stats: &mut SnapshotStats,
+    ) -> Result<Vec<String>, BackupError> {
+        let mut reader = File::open(path)?;
+        let mut buffer = vec![0_u8; self.chunk_size];
+        let mut chunks = Vec::new();
+        loop {
+            let len = read_full(&mut reader, &mut buffer)?;
+            let Some(data) = buffer.get(..len).filter(|data| !data.is_empty()) else {
+                break;
+            };
+            let hash = sha256_hex(data);
+            let object = self.object_path(&hash);
+            if !object.exists() {
+                if let Some(parent) = object.parent() {
+                    create_dir_all(parent)?;
+                }
+                let staging = object.with_extension("tmp");
+                File::create(&staging)?.write_all(data)?;
+                fs::rename(&staging, &object)?;
+                stats.new_chunks += 1;
+                stats.new_bytes += data.len() as u64;
+            }
+            stats.chunks += 1;
+            chunks.push(hash);
+            if len < buffer.len() {
+                break;
+            }
+        }
+        Ok(chunks)
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        let prefix = hash.get(..2).unwrap_or(hash);
+        self.root.join(OBJECTS_DIR).join(prefix).join(hash)
+    }
+
+    fn manifest_path(&self, name: &str) -> Result<PathBuf, BackupError> {
+        let mut components = Path::new(name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(BackupError::DedupError(format!(
+                "Invalid snapshot name: {name}"
+            )));
+        }
+        Ok(self
+            .root
+            .join(SNAPSHOTS_DIR)
+            .join(format!("{name}.{MANIFEST_EXTENSION}")))
+    }
+
+    fn read_manifest(&self, name: &str) -> Result<Vec<ManifestEntry>, BackupError> {
+        let path = self.manifest_path(name)?;
+        let reader = BufReader::new(File::open(&path).map_err(|e| {
+            BackupError::DedupError(format!("Snapshot {name} cannot be opened: {e}"))
+        })?);
+        let corrupt = |line: &str| {
+            BackupError::DedupError(format!("Corrupt manifest line in {name}: {line}"))
+        };
+
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.splitn(4, '\t').collect();
+            let entry = match fields.as_slice() {
+                ["d", path] => ManifestEntry::Dir(decode_path(path).ok_or_else(|| corrupt(&line))?),
+                ["f", mode, chunks, path] => ManifestEntry::File {
+                    path: decode_path(path).ok_or_else(|| corrupt(&line))?,
+                    mode: u32::from_str_radix(mode, 8).map_err(|_| corrupt(&line))?,
+                    chunks: chunks
+                        .split(',')
+                        .filter(|hash| !hash.is_empty())
+                        .map(str::to_owned)
+                        .collect(),
+                },
+                _ => return Err(corrupt(&line)),
+            };
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Encodes a relative path for a manifest line, rejecting characters the format uses.
+fn encode_path(path: &Path) -> Result<String, BackupError> {
+    let encoded = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if encoded.contains(['\t', '\n', '\r']) {
+        return Err(BackupError::DedupError(format!(
+            "Cannot snapshot path with control characters: {encoded:?}"
+        )));
+    }
+    Ok(encoded)
+}
+
+/// Parses a manifest path, refusing anything that would escape the target directory.
+fn decode_path(encoded: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(encoded);
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then_some(path)
+}
+
+/// Reads until `buffer` is full or the reader is exhausted.
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while let Some(rest) = buffer.get_mut(filled..).filter(|rest| !rest.is_empty()) {
+        match reader.read(rest) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn unchanged_files_are_stored_once() {
+        let source = tempdir().unwrap();
+        fs::create_dir_all(source.path().join("Saved")).unwrap();
+        fs::write(source.path().join("Saved/world.sav"), vec![7_u8; 10_000]).unwrap();
+        fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+        let root = tempdir().unwrap();
+        let store = DedupStore::open(root.path()).unwrap().with_chunk_size(4096);
+
+        let first = store.snapshot(source.path(), "first").unwrap();
+        assert_eq!(first.files, 2);
+        // 10 000 bytes of 7s: two identical full chunks and one tail, plus the config.
+        assert_eq!(first.chunks, 4);
+        assert_eq!(first.new_chunks, 3);
+
+        fs::write(source.path().join("server.cfg"), "changed").unwrap();
+        let second = store.snapshot(source.path(), "second").unwrap();
+        assert_eq!(second.new_chunks, 1);
+        assert_eq!(store.snapshots().unwrap(), vec!["first", "second"]);
+
+        let target = tempdir().unwrap();
+        store.materialize("first", target.path()).unwrap();
+        assert_eq!(
+            fs::read(target.path().join("Saved/world.sav")).unwrap(),
+            vec![7_u8; 10_000]
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("server.cfg")).unwrap(),
+            "config"
+        );
+    }
+
+    #[test]
+    fn gc_removes_only_unreferenced_chunks() {
+        let source = tempdir().unwrap();
+        fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+        let root = tempdir().unwrap();
+        let store = DedupStore::open(root.path()).unwrap();
+        store.snapshot(source.path(), "old").unwrap();
+        fs::write(source.path().join("server.cfg"), "changed").unwrap();
+        store.snapshot(source.path(), "new").unwrap();
+
+        assert_eq!(store.gc().unwrap().removed_chunks, 0);
+
+        store.remove_snapshot("old").unwrap();
+        let stats = store.gc().unwrap();
+        assert_eq!(stats.removed_chunks, 1);
+        assert_eq!(stats.freed_bytes, 6);
+
+        let target = tempdir().unwrap();
+        store.materialize("new", target.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(target.path().join("server.cfg")).unwrap(),
+            "changed"
+        );
+    }
+
+    #[test]
+    fn gc_and_snapshot_refuse_a_locked_store() {
+        let source = tempdir().unwrap();
+        fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+        let root = tempdir().unwrap();
+        let store = DedupStore::open(root.path()).unwrap();
+        let held = WorkdirLock::acquire(root.path()).unwrap();
+        assert!(store.gc().is_err());
+        assert!(store.snapshot(source.path(), "snapshot").is_err());
+
+        drop(held);
+        store.snapshot(source.path(), "snapshot").unwrap();
+        assert_eq!(store.snapshots().unwrap(), vec!["snapshot"]);
+        assert_eq!(store.gc().unwrap().removed_chunks, 0);
+    }
+
+    #[test]
+    fn materialize_rejects_corrupt_chunks() {
+        let source = tempdir().unwrap();
+        fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+        let root = tempdir().unwrap();
+        let store = DedupStore::open(root.path()).unwrap();
+        store.snapshot(source.path(), "snapshot").unwrap();
+        let hash = sha256_hex(b"config");
+        fs::write(store.object_path(&hash), "tampered").unwrap();
+
+        let target = tempdir().unwrap();
+        assert!(matches!(
+            store.materialize("snapshot", target.path()),
+            Err(BackupError::DedupError(message)) if message.contains("corrupt")
+        ));
+    }
+
+    #[test]
+    fn rejects_snapshot_names_with_separators() {
+        let root = tempdir().unwrap();
+        let store = DedupStore::open(root.path()).unwrap();
+        assert!(matches!(
+            store.snapshot(root.path(), "../escape"),
+            Err(BackupError::DedupError(_))
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+//! # Hooks
+//!
+//! Lifecycle events for a backup run, so callers can log, notify, or record metrics
+//! without wrapping every call site themselves.
+use crate::{BackupError, BackupOptions, backup_with_options};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A step in the lifecycle of a single backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupEvent {
+    /// The backup is about to read `input` and write `output`.
+    Started { input: PathBuf, output: PathBuf },
+    /// The archive at `output` was written successfully.
+    Completed {
+        output: PathBuf,
+        /// Size of the finished archive in bytes.
+        size: u64,
+        duration: Duration,
+    },
+    /// The backup failed; no archive was left at `output`.
+    Failed {
+        output: PathBuf,
+        error: String,
+        duration: Duration,
+    },
+}
+
+/// Runs [`backup_with_options`], reporting its progress to `on_event`.
+///
+/// `on_event` always sees exactly one `Started` event followed by either `Completed`
+/// or `Failed`.
+///
+/// # Errors
+///
+/// Returns the same errors as [`backup_with_options`].
+pub fn backup_with_hooks<P, Q, F>(
+    input: P,
+    output: Q,
+    options: &BackupOptions,
+    mut on_event: F,
+) -> Result<(), BackupError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&BackupEvent),
+{
+    let input = input.as_ref();
+    let output = output.as_ref();
+    on_event(&BackupEvent::Started {
+        input: input.to_path_buf(),
+        output: output.to_path_buf(),
+    });
+
+    let started = Instant::now();
+    let result =
+        backup_with_options(input, output, options).and_then(|()| Ok(output.metadata()?.len()));
+
+    match result {
+        Ok(size) => {
+            on_event(&BackupEvent::Completed {
+                output: output.to_path_buf(),
+                size,
+                duration: started.elapsed(),
+            });
+            Ok(())
+        }
+        Err(err) => {
+            on_event(&BackupEvent::Failed {
+                output: output.to_path_buf(),
+                error: err.to_string(),
+                duration: started.elapsed(),
+            });
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reports_started_then_completed() {
+        let source = tempdir().unwrap();
+        fs::write(source.path().join("world.sav"), "world").unwrap();
+        let output = tempdir().unwrap();
+        let archive = output.path().join("backup.tar.gz");
+
+        let mut events = Vec::new();
+        backup_with_hooks(
+            source.path(),
+            &archive,
+            &BackupOptions::default(),
+            |event| {
+                events.push(event.clone());
+            },
+        )
+        .unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events.first(), Some(BackupEvent::Started { .. })));
+        assert!(matches!(
+            events.get(1),
+            Some(BackupEvent::Completed { size, .. }) if *size == fs::metadata(&archive).unwrap().len()
+        ));
+    }
+
+    #[test]
+    fn reports_failure() {
+        let output = tempdir().unwrap();
+        let missing = output.path().join("missing");
+
+        let mut events = Vec::new();
+        let result = backup_with_hooks(
+            &missing,
+            output.path().join("backup.tar.gz"),
+            &BackupOptions::default(),
+            |event| events.push(event.clone()),
+        );
+
+        assert!(result.is_err());
+        assert!(matches!(events.last(), Some(BackupEvent::Failed { .. })));
+    }
+}
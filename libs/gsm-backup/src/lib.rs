@@ -1,20 +1,56 @@
 //! # Game Server Backup Utility
 //!
-//! This crate provides functionality for creating compressed tarball backups of game server data.
+//! This crate provides functionality for creating compressed backups of game server data.
 //! It is designed to be a reusable component within the Game Server Management (GSM) workspace.
 //!
 //! The primary function, `backup`, takes an input directory and an output path, and creates a
-//! `.tar.gz` archive of the directory's contents. It includes features for skipping certain
-//! files, such as auto-backups, to avoid redundant data in the archives.
+//! `.tar.gz` archive of the directory's contents; `backup_with_options` can also write
+//! `.tar.zst` and `.zip` archives. It includes features for skipping certain files, such as
+//! auto-backups, to avoid redundant data in the archives.
+//!
+//! Archives of any supported format can be restored in full with `restore`, or filtered down
+//! to individual paths with `restore_paths`. Each archive is written with a SHA-256 checksum
+//! file beside it, which restoring checks first.
+//!
+//! `backup_with_hooks` reports `BackupEvent`s as a backup starts, completes, or fails, and
+//! `run_scheduled_backup` uses it to write timestamped archives for scheduled jobs.
+//!
+//! For worlds that change little between backups, `DedupStore` keeps content-addressed
+//! chunks instead of whole archives, so each snapshot only stores what changed.
 use flate2::Compression;
 use flate2::write::GzEncoder;
 use glob::glob;
+use gsm_shared::{LOCK_FILE_NAME, WorkdirLock};
 use std::fs::{File, remove_file};
-use std::io::{Error as IoError, ErrorKind};
-use std::path::Path;
-use tar::Builder;
+use std::io::{self, Error as IoError, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tar::{Builder, Header};
 use thiserror::Error;
 use tracing::{debug, error, info};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+mod checksum;
+pub use checksum::*;
+
+mod dedup;
+pub use dedup::*;
+
+mod hooks;
+pub use hooks::*;
+
+mod options;
+pub use options::*;
+
+mod restore;
+pub use restore::*;
+
+mod scheduled;
+pub use scheduled::*;
+
+mod throttle;
+pub use throttle::BackupPriority;
+use throttle::{Throttle, ThrottledReader, with_priority};
 
 /// Custom error type for backup failures.
 ///
@@ -29,8 +65,20 @@ pub enum BackupError {
     GlobEntryError(#[from] glob::GlobError),
     #[error("Tar archive error: {0}")]
     TarError(String),
+    #[error("Zip archive error: {0}")]
+    ZipError(String),
     #[error("I/O error: {0}")]
     IoError(#[from] IoError),
+    #[error("No archive entries matched: {0}")]
+    NoMatchingPaths(String),
+    #[error("Dedup store error: {0}")]
+    DedupError(String),
+    #[error("Checksum mismatch for {archive}: expected {expected}, found {actual}")]
+    ChecksumMismatch {
+        archive: String,
+        expected: String,
+        actual: String,
+    },
 }
 
 /// Creates a compressed tar archive (`.tar.gz`) of all files under a specified directory.
@@ -54,11 +102,14 @@ pub enum BackupError {
 /// - The backup file is created using a gzip encoder with default compression settings.
 /// - If an error occurs during the archiving process, the partially created output file
 ///   will be deleted to avoid leaving incomplete backups.
+/// - Once the archive is complete, its SHA-256 checksum is written to `<output>.sha256`
+///   (see [`write_checksum`]), which [`restore`] verifies before extracting.
 ///
 /// # Errors
 ///
 /// This function will return a `BackupError` if any of the following occurs:
 /// - The `input` directory does not exist or is not a directory.
+/// - Another process holds the `input` directory's [`WorkdirLock`].
 /// - The `output` file cannot be created (e.g., due to file permissions).
 /// - A glob pattern for traversing files is invalid.
 /// - An error occurs while reading a file or directory entry.
@@ -87,11 +138,31 @@ pub enum BackupError {
 /// # Ok(())
 /// # }
 /// ```
+pub fn backup<P, Q>(input: P, output: Q) -> Result<(), BackupError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    backup_with_options(input, output, &BackupOptions::default())
+}
+
+/// Creates an archive of all files under `input` using the given [`BackupOptions`].
 ///
-/// # Panics
+/// Traversal and skipping behave exactly as in [`backup`], limited to `options.include`
+/// when it is not empty; `options.format` selects
+/// whether a `.tar.gz`, `.tar.zst`, or `.zip` archive is written to `output`, while
+/// `options.max_read_bytes_per_sec` and `options.priority` keep the backup from
+/// competing with a running server for disk and CPU.
 ///
-/// Panics if internal glob pattern expansion fails while enumerating the input tree.
-pub fn backup<P, Q>(input: P, output: Q) -> Result<(), BackupError>
+/// # Errors
+///
+/// Returns the same errors as [`backup`], plus [`BackupError::ZipError`] when a ZIP
+/// archive cannot be written.
+pub fn backup_with_options<P, Q>(
+    input: P,
+    output: Q,
+    options: &BackupOptions,
+) -> Result<(), BackupError>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
@@ -110,46 +181,156 @@ where
         )));
     }
 
-    debug!("Creating archive of {:?}", input);
+    // Another container sharing the directory must not change it mid-backup.
+    let _lock = WorkdirLock::acquire(input)?;
+    debug!("Creating {:?} archive of {:?}", options.format, input);
     debug!("Output set to {:?}", output);
 
+    let entries: Vec<_> = collect_entries(input)?
+        .into_iter()
+        .filter(|(_, relative)| {
+            options.include.is_empty()
+                || options
+                    .include
+                    .iter()
+                    .any(|include| relative.starts_with(include))
+        })
+        .collect();
+    if entries.is_empty() && !options.include.is_empty() {
+        return Err(BackupError::NoMatchingPaths(format!(
+            "none of {:?} exist under {}",
+            options.include,
+            input.display()
+        )));
+    }
+
     // Attempt to create the output backup file.
-    let tar_gz = File::create(output)
+    let file = File::create(output)
         .map_err(|_| BackupError::CreateBackupError(format!("{}", output.display())))?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
-    let mut tar = Builder::new(enc);
 
+    let result = with_priority(options.priority, || {
+        let mut throttle = options.max_read_bytes_per_sec.map(Throttle::new);
+        let throttle = throttle.as_mut();
+        match options.format {
+            BackupFormat::TarGz => write_tar(
+                GzEncoder::new(file, Compression::default()),
+                &entries,
+                throttle,
+            )
+            .and_then(|encoder| encoder.finish().map_err(BackupError::IoError))
+            .map(drop),
+            BackupFormat::TarZst => zstd::Encoder::new(file, 0)
+                .map_err(BackupError::IoError)
+                .and_then(|encoder| write_tar(encoder, &entries, throttle))
+                .and_then(|encoder| encoder.finish().map_err(BackupError::IoError))
+                .map(drop),
+            BackupFormat::Zip => write_zip(file, &entries, throttle),
+        }
+    });
+
+    if let Err(err) = result.and_then(|()| write_checksum(output).map(drop)) {
+        error!("Backup error: {err}");
+        let _ = remove_file(output);
+        let _ = remove_file(checksum_path(output));
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Lists every file and directory under `input` as `(absolute, relative)` pairs,
+/// skipping any path containing `"backup_auto"` and the [`WorkdirLock`] file.
+fn collect_entries(input: &Path) -> Result<Vec<(PathBuf, PathBuf)>, BackupError> {
     // Build a glob pattern for all files and directories under the input.
     let pattern = format!("{}/**/*", input.display());
     let entries = glob(&pattern).map_err(BackupError::GlobPatternError)?;
 
+    let mut collected = Vec::new();
     for entry in entries {
         match entry {
             Ok(path) => {
-                let path_str = path.to_string_lossy().into_owned();
                 // Skip files whose names contain "backup_auto"
-                if path_str.contains("backup_auto") {
+                if path.to_string_lossy().contains("backup_auto") {
                     continue;
                 }
                 // Compute the relative path from the input directory.
-                let relative = path.strip_prefix(input).unwrap_or(&path);
-                info!(
-                    "Adding {} to backup file, with relative path {:?}",
-                    path_str, relative
-                );
-                if let Err(err) = tar.append_path_with_name(&path, relative) {
-                    error!("Failed to add {} to backup file", path_str);
-                    error!("Backup error: {err}");
-                    let _ = remove_file(output);
-                    return Err(BackupError::TarError(err.to_string()));
+                let relative = path.strip_prefix(input).unwrap_or(&path).to_path_buf();
+                if relative == Path::new(LOCK_FILE_NAME) {
+                    continue;
                 }
-                debug!("Successfully added {} to backup file", path_str);
+                collected.push((path, relative));
             }
             Err(e) => error!("Error reading glob entry: {:?}", e),
         }
     }
-    tar.finish()
-        .map_err(|e| BackupError::TarError(e.to_string()))?;
+    Ok(collected)
+}
+
+/// Appends `entries` to a tar stream written to `writer`, returning the inner writer.
+///
+/// Regular file contents are read through `throttle`, when one is given.
+fn write_tar<W: Write>(
+    writer: W,
+    entries: &[(PathBuf, PathBuf)],
+    mut throttle: Option<&mut Throttle>,
+) -> Result<W, BackupError> {
+    let mut tar = Builder::new(writer);
+    for (path, relative) in entries {
+        info!(
+            "Adding {} to backup file, with relative path {:?}",
+            path.display(),
+            relative
+        );
+        let metadata = path.symlink_metadata()?;
+        let appended = if metadata.is_file() {
+            let mut header = Header::new_gnu();
+            header.set_metadata(&metadata);
+            let reader = ThrottledReader::new(File::open(path)?, throttle.as_deref_mut());
+            tar.append_data(&mut header, relative, reader)
+        } else {
+            tar.append_path_with_name(path, relative)
+        };
+        appended.map_err(|err| {
+            error!("Failed to add {} to backup file", path.display());
+            BackupError::TarError(err.to_string())
+        })?;
+        debug!("Successfully added {} to backup file", path.display());
+    }
+    tar.into_inner()
+        .map_err(|e| BackupError::TarError(e.to_string()))
+}
+
+/// Writes `entries` into a deflate-compressed ZIP archive, reading through `throttle`.
+fn write_zip(
+    file: File,
+    entries: &[(PathBuf, PathBuf)],
+    mut throttle: Option<&mut Throttle>,
+) -> Result<(), BackupError> {
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    let zip_err = |e: zip::result::ZipError| BackupError::ZipError(e.to_string());
+
+    for (path, relative) in entries {
+        // ZIP entry names always use forward slashes, regardless of platform.
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        info!(
+            "Adding {} to backup file, with relative path {:?}",
+            path.display(),
+            name
+        );
+        if path.is_dir() {
+            zip.add_directory(name, options).map_err(zip_err)?;
+        } else {
+            zip.start_file(name, options).map_err(zip_err)?;
+            let mut reader = ThrottledReader::new(File::open(path)?, throttle.as_deref_mut());
+            io::copy(&mut reader, &mut zip)?;
+        }
+        debug!("Successfully added {} to backup file", path.display());
+    }
+    zip.finish().map_err(zip_err)?;
     Ok(())
 }
 
@@ -217,6 +398,62 @@ mod tests {
         assert!(!archived_files.iter().any(|s| s.contains("backup_auto")));
     }
 
+    #[test]
+    fn test_backup_respects_the_workdir_lock() {
+        let test_dir = setup_test_dir();
+        let backup_file = NamedTempFile::new().expect("Failed to create temp file");
+
+        let lock = WorkdirLock::acquire(test_dir.path()).unwrap();
+        let error = backup(test_dir.path(), backup_file.path()).unwrap_err();
+        assert!(error.to_string().contains("is locked by pid"), "{error}");
+
+        drop(lock);
+        backup(test_dir.path(), backup_file.path()).expect("Backup failed");
+        let archived_files = read_archive(backup_file.path());
+        assert!(!archived_files.iter().any(|s| s.contains(LOCK_FILE_NAME)));
+    }
+
+    #[test]
+    fn test_backup_with_throttle_and_low_priority() {
+        let test_dir = setup_test_dir();
+        let backup_file = NamedTempFile::new().expect("Failed to create temp file");
+        let options = BackupOptions {
+            max_read_bytes_per_sec: Some(1024 * 1024),
+            priority: BackupPriority::Idle,
+            ..BackupOptions::default()
+        };
+
+        backup_with_options(test_dir.path(), backup_file.path(), &options).expect("Backup failed");
+
+        let archived_files = read_archive(backup_file.path());
+        assert!(archived_files.iter().any(|s| s.contains("sub/bar.txt")));
+    }
+
+    #[test]
+    fn test_backup_only_includes_listed_paths() {
+        let test_dir = setup_test_dir();
+        let backup_file = NamedTempFile::new().expect("Failed to create temp file");
+        let options = BackupOptions {
+            include: vec![PathBuf::from("sub")],
+            ..BackupOptions::default()
+        };
+
+        backup_with_options(test_dir.path(), backup_file.path(), &options).expect("Backup failed");
+
+        let archived_files = read_archive(backup_file.path());
+        assert!(archived_files.iter().any(|s| s.contains("sub/bar.txt")));
+        assert!(!archived_files.iter().any(|s| s.contains("foo.txt")));
+
+        let options = BackupOptions {
+            include: vec![PathBuf::from("missing")],
+            ..BackupOptions::default()
+        };
+        assert!(matches!(
+            backup_with_options(test_dir.path(), backup_file.path(), &options),
+            Err(BackupError::NoMatchingPaths(_))
+        ));
+    }
+
     #[test]
     fn test_backup_nonexistent_input() {
         let tmp_dir = tempdir().unwrap();
@@ -0,0 +1,104 @@
+//! # Backup Options
+//!
+//! Settings that control how [`crate::backup_with_options`] produces an archive.
+use crate::BackupPriority;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// The archive format written by a backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackupFormat {
+    /// A gzip-compressed tarball (`.tar.gz`). This is the default.
+    #[default]
+    TarGz,
+    /// A zstd-compressed tarball (`.tar.zst`), smaller and faster than gzip.
+    TarZst,
+    /// A deflate-compressed ZIP file (`.zip`) that opens natively on Windows.
+    Zip,
+}
+
+impl BackupFormat {
+    /// Returns the conventional file extension for this format, without a leading dot.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+            Self::Zip => "zip",
+        }
+    }
+
+    /// Detects the format of an existing archive from its leading magic bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be read or is not a supported archive.
+    pub fn detect(path: &Path) -> io::Result<Self> {
+        let mut magic = [0_u8; 4];
+        File::open(path)?.read_exact(&mut magic)?;
+        match magic {
+            [0x1f, 0x8b, _, _] => Ok(Self::TarGz),
+            [0x28, 0xb5, 0x2f, 0xfd] => Ok(Self::TarZst),
+            [b'P', b'K', 0x03, 0x04] => Ok(Self::Zip),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a supported backup archive", path.display()),
+            )),
+        }
+    }
+}
+
+/// Options for [`crate::backup_with_options`].
+///
+/// # Example
+///
+/// ```rust
+/// use gsm_backup::{BackupFormat, BackupOptions};
+///
+/// let options = BackupOptions {
+///     format: BackupFormat::Zip,
+///     // Read at most 10 MB/s so a running server keeps its disk bandwidth.
+///     max_read_bytes_per_sec: Some(10 * 1024 * 1024),
+///     ..BackupOptions::default()
+/// };
+/// assert_eq!(options.format.extension(), "zip");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BackupOptions {
+    /// The archive format to write.
+    pub format: BackupFormat,
+    /// Upper bound on how fast source files are read, in bytes per second.
+    /// `None` reads as fast as the disk allows.
+    pub max_read_bytes_per_sec: Option<u64>,
+    /// CPU/IO scheduling priority hint for the archiving thread.
+    pub priority: BackupPriority,
+    /// Paths relative to the input directory to archive, with everything under them,
+    /// e.g. a game's save directories. Empty archives the whole input directory.
+    pub include: Vec<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn detect_recognizes_magic_bytes() -> io::Result<()> {
+        let dir = tempdir()?;
+        let cases = [
+            (vec![0x1f, 0x8b, 0x08, 0x00], BackupFormat::TarGz),
+            (vec![0x28, 0xb5, 0x2f, 0xfd], BackupFormat::TarZst),
+            (b"PK\x03\x04".to_vec(), BackupFormat::Zip),
+        ];
+        for (bytes, expected) in cases {
+            let path = dir.path().join(expected.extension());
+            std::fs::write(&path, bytes)?;
+            assert_eq!(BackupFormat::detect(&path)?, expected);
+        }
+
+        let unknown = dir.path().join("unknown");
+        std::fs::write(&unknown, b"nope")?;
+        assert!(BackupFormat::detect(&unknown).is_err());
+        Ok(())
+    }
+}
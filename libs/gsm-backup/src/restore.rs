@@ -0,0 +1,262 @@
+//! # Restore
+//!
+//! Extracts archives produced by [`crate::backup`] and [`crate::backup_with_options`] back
+//! onto disk, either in full or filtered down to individual paths. The archive format is
+//! detected from the file itself, so every [`BackupFormat`] can be restored. Archives are
+//! checked against their checksum file first; see [`crate::verify_checksum`].
+use crate::{BackupError, BackupFormat, verify_checksum};
+use flate2::read::GzDecoder;
+use glob::Pattern;
+use gsm_shared::WorkdirLock;
+use std::fs::{File, create_dir_all};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tar::Archive;
+use tracing::{debug, info};
+use zip::ZipArchive;
+
+/// Restores every entry of a backup archive into `target`.
+///
+/// Existing files in `target` are overwritten by their archived versions; files that
+/// are not part of the archive are left untouched.
+///
+/// # Errors
+///
+/// Returns an error when the archive cannot be opened or read, does not match its
+/// checksum file, another process holds `target`'s [`WorkdirLock`], or an entry cannot be
+/// written under `target`.
+pub fn restore<P, Q>(archive: P, target: Q) -> Result<Vec<PathBuf>, BackupError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    restore_matching(archive.as_ref(), target.as_ref(), &[])
+}
+
+/// Restores only the archive entries whose relative path matches one of `patterns`.
+///
+/// Patterns are glob expressions relative to the backed-up directory, e.g.
+/// `"Pal/Saved/SaveGames/**"`, which lets operators pull a single corrupted world out
+/// of a backup without rolling back the rest of the install.
+///
+/// Returns the relative paths that were restored.
+///
+/// # Errors
+///
+/// Returns an error when a pattern is invalid, no entry matches any pattern, the
+/// archive cannot be read, or an entry cannot be written under `target`.
+pub fn restore_paths<P, Q>(
+    archive: P,
+    target: Q,
+    patterns: &[&str],
+) -> Result<Vec<PathBuf>, BackupError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let patterns = patterns
+        .iter()
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let restored = restore_matching(archive.as_ref(), target.as_ref(), &patterns)?;
+    if restored.is_empty() {
+        return Err(BackupError::NoMatchingPaths(
+            patterns
+                .iter()
+                .map(Pattern::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+    Ok(restored)
+}
+
+/// Returns true when `path` should be restored given `patterns` (empty means everything).
+fn is_selected(path: &Path, patterns: &[Pattern]) -> bool {
+    patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches_path(path))
+}
+
+/// Extracts entries matching any of `patterns` (or all entries when `patterns` is empty).
+fn restore_matching(
+    archive: &Path,
+    target: &Path,
+    patterns: &[Pattern],
+) -> Result<Vec<PathBuf>, BackupError> {
+    info!("Restoring {} into {}", archive.display(), target.display());
+    let format = BackupFormat::detect(archive)?;
+    verify_checksum(archive)?;
+    create_dir_all(target)?;
+    let _lock = WorkdirLock::acquire(target)?;
+
+    let file = File::open(archive)?;
+    match format {
+        BackupFormat::TarGz => restore_tar(GzDecoder::new(file), target, patterns),
+        BackupFormat::TarZst => restore_tar(zstd::Decoder::new(file)?, target, patterns),
+        BackupFormat::Zip => restore_zip(file, target, patterns),
+    }
+}
+
+/// Extracts matching entries from a (decompressed) tar stream.
+fn restore_tar<R: Read>(
+    reader: R,
+    target: &Path,
+    patterns: &[Pattern],
+) -> Result<Vec<PathBuf>, BackupError> {
+    let mut archive = Archive::new(reader);
+    let mut restored = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !is_selected(&path, patterns) {
+            continue;
+        }
+
+        debug!("Restoring {}", path.display());
+        // `unpack_in` refuses entries that would escape `target` (e.g. `../`).
+        if !entry.unpack_in(target)? {
+            return Err(BackupError::TarError(format!(
+                "Refusing to restore {} outside of {}",
+                path.display(),
+                target.display()
+            )));
+        }
+        restored.push(path);
+    }
+
+    Ok(restored)
+}
+
+/// Extracts matching entries from a ZIP archive.
+fn restore_zip(
+    file: File,
+    target: &Path,
+    patterns: &[Pattern],
+) -> Result<Vec<PathBuf>, BackupError> {
+    let zip_err = |e: zip::result::ZipError| BackupError::ZipError(e.to_string());
+    let mut archive = ZipArchive::new(file).map_err(zip_err)?;
+    let mut restored = Vec::new();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(zip_err)?;
+        // `enclosed_name` rejects entries that would escape `target` (e.g. `../`).
+        let Some(path) = entry.enclosed_name() else {
+            return Err(BackupError::ZipError(format!(
+                "Refusing to restore {} outside of {}",
+                entry.name(),
+                target.display()
+            )));
+        };
+        if !is_selected(&path, patterns) {
+            continue;
+        }
+
+        debug!("Restoring {}", path.display());
+        let destination = target.join(&path);
+        if entry.is_dir() {
+            create_dir_all(&destination)?;
+        } else {
+            if let Some(parent) = destination.parent() {
+                create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&destination)?)?;
+        }
+        restored.push(path);
+    }
+
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::{BackupOptions, backup, backup_with_options};
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// Backs up a small fake install and returns the output dir and archive path.
+    fn create_backup() -> (tempfile::TempDir, PathBuf) {
+        let source = tempdir().unwrap();
+        let saves = source.path().join("Pal/Saved/SaveGames");
+        fs::create_dir_all(&saves).unwrap();
+        fs::write(saves.join("world.sav"), "world").unwrap();
+        fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+        let output = tempdir().unwrap();
+        let archive = output.path().join("backup.tar.gz");
+        backup(source.path(), &archive).unwrap();
+        (output, archive)
+    }
+
+    #[test]
+    fn restore_extracts_every_entry() {
+        let (_output, archive) = create_backup();
+        let target = tempdir().unwrap();
+
+        restore(&archive, target.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(target.path().join("Pal/Saved/SaveGames/world.sav")).unwrap(),
+            "world"
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("server.cfg")).unwrap(),
+            "config"
+        );
+    }
+
+    #[test]
+    fn restore_paths_only_extracts_matching_entries() {
+        let (_output, archive) = create_backup();
+        let target = tempdir().unwrap();
+        fs::write(target.path().join("server.cfg"), "live config").unwrap();
+
+        let restored = restore_paths(&archive, target.path(), &["Pal/Saved/SaveGames/**"]).unwrap();
+
+        assert!(restored.contains(&PathBuf::from("Pal/Saved/SaveGames/world.sav")));
+        assert!(target.path().join("Pal/Saved/SaveGames/world.sav").exists());
+        assert_eq!(
+            fs::read_to_string(target.path().join("server.cfg")).unwrap(),
+            "live config"
+        );
+    }
+
+    #[test]
+    fn restore_paths_supports_every_format() {
+        for format in [BackupFormat::TarGz, BackupFormat::TarZst, BackupFormat::Zip] {
+            let source = tempdir().unwrap();
+            let saves = source.path().join("Pal/Saved/SaveGames");
+            fs::create_dir_all(&saves).unwrap();
+            fs::write(saves.join("world.sav"), "world").unwrap();
+            fs::write(source.path().join("server.cfg"), "config").unwrap();
+
+            let output = tempdir().unwrap();
+            let archive = output.path().join(format!("backup.{}", format.extension()));
+            let options = BackupOptions {
+                format,
+                ..BackupOptions::default()
+            };
+            backup_with_options(source.path(), &archive, &options).unwrap();
+            assert_eq!(BackupFormat::detect(&archive).unwrap(), format);
+
+            let target = tempdir().unwrap();
+            restore_paths(&archive, target.path(), &["Pal/Saved/SaveGames/*.sav"]).unwrap();
+            assert_eq!(
+                fs::read_to_string(target.path().join("Pal/Saved/SaveGames/world.sav")).unwrap(),
+                "world"
+            );
+            assert!(!target.path().join("server.cfg").exists());
+        }
+    }
+
+    #[test]
+    fn restore_paths_errors_when_nothing_matches() {
+        let (_output, archive) = create_backup();
+        let target = tempdir().unwrap();
+
+        let error = restore_paths(&archive, target.path(), &["missing/**"]).unwrap_err();
+        assert!(matches!(error, BackupError::NoMatchingPaths(_)));
+    }
+}
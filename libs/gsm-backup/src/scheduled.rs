@@ -0,0 +1,69 @@
+//! # Scheduled Backups
+//!
+//! Timestamped archives for backup jobs that run on a schedule, so each run keeps its own
+//! file next to the earlier ones.
+use crate::{BackupError, BackupEvent, BackupOptions, backup_with_hooks};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Archives `input` into `backup-<unix seconds>.tar.gz` under `backup_dir` and returns
+/// the archive's path.
+///
+/// `backup_dir` is created when needed, and progress is reported to `on_event` like
+/// [`backup_with_hooks`].
+///
+/// # Errors
+///
+/// Returns the same errors as [`backup_with_hooks`].
+pub fn run_scheduled_backup<F>(
+    input: &Path,
+    backup_dir: &Path,
+    on_event: F,
+) -> Result<PathBuf, BackupError>
+where
+    F: FnMut(&BackupEvent),
+{
+    if let Err(e) = fs::create_dir_all(backup_dir) {
+        warn!(
+            "Failed to create backup directory {}: {e}",
+            backup_dir.display()
+        );
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let output = backup_dir.join(format!("backup-{timestamp}.tar.gz"));
+    backup_with_hooks(input, &output, &BackupOptions::default(), on_event)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn writes_a_timestamped_archive_into_a_new_directory() {
+        let saves = tempdir().unwrap();
+        fs::write(saves.path().join("world.sav"), "world").unwrap();
+        let backups = tempdir().unwrap();
+        let backup_dir = backups.path().join("nested/backups");
+
+        let mut events = Vec::new();
+        let output = run_scheduled_backup(saves.path(), &backup_dir, |event| {
+            events.push(event.clone());
+        })
+        .unwrap();
+
+        assert!(output.is_file());
+        assert_eq!(output.parent(), Some(backup_dir.as_path()));
+        let name = output.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("backup-") && name.ends_with(".tar.gz"));
+        assert!(matches!(events.last(), Some(BackupEvent::Completed { .. })));
+    }
+}
@@ -0,0 +1,175 @@
+//! # Throttling
+//!
+//! Keeps a backup from starving a running game server of disk bandwidth and CPU, by
+//! capping read throughput and lowering the scheduling priority of the archiving thread.
+use std::io::{self, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Scheduling priority hint for the thread that produces an archive.
+///
+/// On Linux, `Low` and `Idle` map to `nice`/`ionice`-style settings; elsewhere they
+/// are ignored. The backup runs on a dedicated thread whenever the priority is not
+/// `Normal`, so the caller's own thread keeps its priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackupPriority {
+    /// Run with the caller's priority. This is the default.
+    #[default]
+    Normal,
+    /// `nice 10` and best-effort IO at the lowest level (`ionice -c2 -n7`).
+    Low,
+    /// `nice 19` and idle-class IO (`ionice -c3`): only use the disk when nothing else is.
+    Idle,
+}
+
+/// Caps the average rate at which bytes are consumed.
+#[derive(Debug)]
+pub struct Throttle {
+    max_bytes_per_sec: u64,
+    started: Instant,
+    consumed: u64,
+}
+
+impl Throttle {
+    pub fn new(max_bytes_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec: max_bytes_per_sec.max(1),
+            started: Instant::now(),
+            consumed: 0,
+        }
+    }
+
+    /// Records `bytes` as consumed, sleeping long enough to stay under the limit.
+    fn consume(&mut self, bytes: usize) {
+        self.consumed = self.consumed.saturating_add(bytes as u64);
+        let expected_nanos =
+            u128::from(self.consumed) * 1_000_000_000 / u128::from(self.max_bytes_per_sec);
+        let expected = Duration::from_nanos(u64::try_from(expected_nanos).unwrap_or(u64::MAX));
+        if let Some(ahead) = expected.checked_sub(self.started.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+/// A reader that optionally paces its reads through a shared [`Throttle`].
+pub struct ThrottledReader<'a, R> {
+    inner: R,
+    throttle: Option<&'a mut Throttle>,
+}
+
+impl<'a, R> ThrottledReader<'a, R> {
+    pub const fn new(inner: R, throttle: Option<&'a mut Throttle>) -> Self {
+        Self { inner, throttle }
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(throttle) = self.throttle.as_deref_mut() {
+            throttle.consume(read);
+        }
+        Ok(read)
+    }
+}
+
+/// Runs `task` with the given priority, on a dedicated thread unless it is `Normal`.
+pub fn with_priority<T, F>(priority: BackupPriority, task: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    if priority == BackupPriority::Normal {
+        return task();
+    }
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                apply_priority(priority);
+                task()
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Lowers the CPU and IO priority of the calling thread.
+#[cfg(target_os = "linux")]
+fn apply_priority(priority: BackupPriority) {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+    let (nice, ioprio) = match priority {
+        BackupPriority::Normal => return,
+        BackupPriority::Low => (10, (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7),
+        BackupPriority::Idle => (19, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT),
+    };
+
+    // Both nice values and IO priorities are per-thread on Linux, so these only
+    // affect the dedicated backup thread.
+    // SAFETY: gettid takes no arguments and cannot fail.
+    let tid = unsafe { libc::gettid() };
+    let id = libc::id_t::try_from(tid).unwrap_or_default();
+    // SAFETY: setpriority only reads its integer arguments; an unknown id fails with
+    // ESRCH rather than touching memory.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, id, nice) } != 0 {
+        warn!(
+            "Failed to lower backup CPU priority: {}",
+            io::Error::last_os_error()
+        );
+    }
+    // SAFETY: ioprio_set takes three integers and no pointers, so no memory is
+    // passed to the kernel.
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio) } != 0 {
+        warn!(
+            "Failed to lower backup IO priority: {}",
+            io::Error::last_os_error()
+        );
+    }
+    debug!("Backup thread priority set to {:?}", priority);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_priority(priority: BackupPriority) {
+    debug!(
+        "Backup priority {:?} is not supported on this platform",
+        priority
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttled_reader_limits_throughput() -> io::Result<()> {
+        let data = vec![0_u8; 64 * 1024];
+        let mut throttle = Throttle::new(256 * 1024);
+        let started = Instant::now();
+
+        let mut reader = ThrottledReader::new(data.as_slice(), Some(&mut throttle));
+        io::copy(&mut reader, &mut io::sink())?;
+
+        // 64 KiB at 256 KiB/s should take roughly a quarter of a second.
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        Ok(())
+    }
+
+    #[test]
+    fn unthrottled_reader_passes_data_through() -> io::Result<()> {
+        let mut reader = ThrottledReader::new(&b"hello"[..], None);
+        let mut out = String::new();
+        reader.read_to_string(&mut out)?;
+        assert_eq!(out, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn with_priority_returns_task_result() {
+        assert_eq!(with_priority(BackupPriority::Normal, || 1), 1);
+        assert_eq!(with_priority(BackupPriority::Idle, || 2), 2);
+    }
+}
@@ -0,0 +1,237 @@
+//! # Blackout Windows
+//!
+//! This module keeps jobs out of peak play hours. A job can declare windows such as
+//! `18:00-23:00` or `Sat` during which its runs are skipped, so a nightly restart or an
+//! update never lands while players are online. Windows are evaluated in the job's
+//! timezone when a run comes due.
+use crate::CronError;
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Timelike, Weekday};
+use gsm_shared::fetch_var;
+use std::fmt;
+use std::str::FromStr;
+use tracing::warn;
+
+/// A recurring period during which a job must not run.
+///
+/// Windows are written as optional days followed by an optional time range:
+///
+/// - `18:00-23:00`: every day from 18:00 until 23:00;
+/// - `Sat` or `Sat,Sun`: all day on those days;
+/// - `Mon-Fri 17:00-23:00`: weekday evenings;
+/// - `Fri 22:00-02:00`: a range past midnight, starting on Friday and ending on Saturday.
+///
+/// ```rust
+/// use gsm_cron::BlackoutWindow;
+///
+/// let window: BlackoutWindow = "Sat,Sun 12:00-23:00".parse()?;
+/// assert_eq!(window.to_string(), "Sat,Sun 12:00-23:00");
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlackoutWindow {
+    /// Bit `n` is set when the window starts on the day `n` days after Monday.
+    days: u8,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+const ALL_DAYS: u8 = 0b111_1111;
+
+impl BlackoutWindow {
+    /// Reads `;`-separated windows from `<PREFIX>_BLACKOUT`, e.g.
+    /// `AUTO_UPDATE_BLACKOUT="18:00-23:00;Sat"`. Invalid windows are logged and ignored.
+    pub fn from_env(prefix: &str) -> Vec<Self> {
+        let name = format!("{prefix}_BLACKOUT");
+        fetch_var(&name, "")
+            .split(';')
+            .filter(|window| !window.trim().is_empty())
+            .filter_map(|window| {
+                window
+                    .parse()
+                    .map_err(|e| warn!("Ignoring blackout window in {name}: {e}"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Returns whether `at`, seen in its own timezone, falls inside this window.
+    pub fn contains<Z: TimeZone>(&self, at: &DateTime<Z>) -> bool {
+        let time = at.time();
+        let today = at.weekday();
+        if self.start == self.end {
+            return self.starts_on(today);
+        }
+        if self.start < self.end {
+            return self.starts_on(today) && self.start <= time && time < self.end;
+        }
+        (self.starts_on(today) && time >= self.start)
+            || (self.starts_on(today.pred()) && time < self.end)
+    }
+
+    const fn starts_on(&self, day: Weekday) -> bool {
+        self.days & (1 << day.num_days_from_monday()) != 0
+    }
+}
+
+fn invalid(window: &str, reason: impl Into<String>) -> CronError {
+    CronError::InvalidBlackout {
+        window: window.to_owned(),
+        reason: reason.into(),
+    }
+}
+
+fn parse_days(window: &str, days: &str) -> Result<u8, CronError> {
+    let day = |name: &str| {
+        name.trim()
+            .parse::<Weekday>()
+            .map_err(|_| invalid(window, format!("'{name}' is not a day of the week")))
+    };
+    let mut mask = 0;
+    for part in days.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (day(first)?, day(last)?),
+            None => (day(part)?, day(part)?),
+        };
+        let mut current = first;
+        loop {
+            mask |= 1 << current.num_days_from_monday();
+            if current == last {
+                break;
+            }
+            current = current.succ();
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_time(window: &str, time: &str) -> Result<NaiveTime, CronError> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| invalid(window, format!("'{time}' is not a time like 18:00")))
+}
+
+impl FromStr for BlackoutWindow {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let window = s.trim();
+        let mut parts = window.split_whitespace();
+        let (days, range) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(first), None, None) if first.contains(':') => (None, Some(first)),
+            (Some(first), None, None) => (Some(first), None),
+            (Some(days), Some(range), None) => (Some(days), Some(range)),
+            _ => return Err(invalid(window, "expected '[days] [HH:MM-HH:MM]'")),
+        };
+        let days = days.map_or(Ok(ALL_DAYS), |days| parse_days(window, days))?;
+        let (start, end) = match range {
+            Some(range) => {
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or_else(|| invalid(window, "expected a time range like 18:00-23:00"))?;
+                let (start, end) = (parse_time(window, start)?, parse_time(window, end)?);
+                if start == end {
+                    return Err(invalid(window, "the time range is empty"));
+                }
+                (start, end)
+            }
+            None => (NaiveTime::MIN, NaiveTime::MIN),
+        };
+        Ok(Self { days, start, end })
+    }
+}
+
+impl fmt::Display for BlackoutWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let all_day = self.start == self.end;
+        if self.days != ALL_DAYS || all_day {
+            let days: Vec<String> = (0..7)
+                .filter(|day| self.days & (1 << day) != 0)
+                .filter_map(|day| Weekday::try_from(day).ok())
+                .map(|day| day.to_string())
+                .collect();
+            f.write_str(&days.join(","))?;
+            if all_day {
+                return Ok(());
+            }
+            f.write_str(" ")?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start.hour(),
+            self.start.minute(),
+            self.end.hour(),
+            self.end.minute()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::Utc;
+
+    // 2026-06-05 is a Friday.
+    fn friday(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 6, 5, hour, minute, 0).unwrap()
+    }
+
+    fn window(s: &str) -> BlackoutWindow {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn daily_ranges_cover_their_hours() {
+        let evening = window("18:00-23:00");
+        assert!(evening.contains(&friday(18, 0)));
+        assert!(evening.contains(&friday(22, 59)));
+        assert!(!evening.contains(&friday(23, 0)));
+        assert!(!evening.contains(&friday(4, 0)));
+    }
+
+    #[test]
+    fn day_windows_cover_the_whole_day() {
+        let weekend = window("Sat,Sun");
+        assert!(!weekend.contains(&friday(23, 59)));
+        assert!(weekend.contains(&(friday(23, 59) + chrono::Duration::minutes(1))));
+        assert!(window("Mon-Fri").contains(&friday(12, 0)));
+    }
+
+    #[test]
+    fn ranges_past_midnight_end_the_next_day() {
+        let late = window("Fri 22:00-02:00");
+        assert!(late.contains(&friday(23, 0)));
+        assert!(late.contains(&(friday(1, 0) + chrono::Duration::days(1))));
+        assert!(!late.contains(&friday(1, 0)));
+    }
+
+    #[test]
+    fn windows_are_evaluated_in_the_given_timezone() {
+        let evening = window("18:00-23:00");
+        let at = friday(16, 0).with_timezone(&chrono_tz::Tz::Europe__Berlin);
+        assert!(evening.contains(&at));
+    }
+
+    #[test]
+    fn windows_display_as_they_are_written() {
+        for written in ["18:00-23:00", "Sat,Sun", "Mon,Tue,Wed 17:00-23:30"] {
+            assert_eq!(window(written).to_string(), written);
+        }
+        assert_eq!(window("Mon-Wed").to_string(), "Mon,Tue,Wed");
+    }
+
+    #[test]
+    fn invalid_windows_are_rejected() {
+        for invalid in [
+            "",
+            "Someday",
+            "25:00-26:00",
+            "18:00",
+            "Sat 18:00-18:00",
+            "a b c",
+        ] {
+            assert!(invalid.parse::<BlackoutWindow>().is_err(), "{invalid}");
+        }
+    }
+}
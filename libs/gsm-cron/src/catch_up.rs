@@ -0,0 +1,202 @@
+//! # Catch-up
+//!
+//! This module decides what happens to runs that were due while the process was not
+//! running, e.g. a 03:00 update missed because the container restarted at 03:05. For
+//! jobs with [`CatchUpPolicy::RunImmediately`], the start of each run is persisted to
+//! `CRON_STATE_FILE`; when such a job is registered again, a scheduled time between its
+//! last run and now counts as missed.
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use gsm_shared::fetch_var;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use tracing::{debug, warn};
+
+/// Environment variable with the default [`CatchUpPolicy`] (`skip` or `run-immediately`).
+pub const CRON_CATCH_UP: &str = "CRON_CATCH_UP";
+
+/// Environment variable naming the file the last run of each job is persisted to.
+pub const CRON_STATE_FILE: &str = "CRON_STATE_FILE";
+
+/// Default for [`CRON_STATE_FILE`].
+pub const DEFAULT_STATE_FILE: &str = "/home/steam/.gsm-cron-state";
+
+/// Serializes read-modify-write cycles on the state file within this process.
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// What to do with a run that was missed while the process was not running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Wait for the next scheduled time. This is the default.
+    #[default]
+    Skip,
+    /// Run once as soon as the job is registered, however many runs were missed.
+    RunImmediately,
+}
+
+impl CatchUpPolicy {
+    /// Whether runs must be persisted for this policy to detect missed ones.
+    pub const fn needs_state(self) -> bool {
+        matches!(self, Self::RunImmediately)
+    }
+}
+
+impl FromStr for CatchUpPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "skip" => Ok(Self::Skip),
+            "run-immediately" => Ok(Self::RunImmediately),
+            other => Err(format!(
+                "unknown catch-up policy '{other}' (expected skip or run-immediately)"
+            )),
+        }
+    }
+}
+
+/// Returns the policy named by `CRON_CATCH_UP`, or [`CatchUpPolicy::Skip`] when it is
+/// unset or invalid.
+pub fn default_catch_up() -> CatchUpPolicy {
+    let value = fetch_var(CRON_CATCH_UP, "");
+    if value.is_empty() {
+        return CatchUpPolicy::default();
+    }
+    value.parse().unwrap_or_else(|e| {
+        warn!("Ignoring {CRON_CATCH_UP}: {e}");
+        CatchUpPolicy::default()
+    })
+}
+
+fn state_file() -> PathBuf {
+    PathBuf::from(fetch_var(CRON_STATE_FILE, DEFAULT_STATE_FILE))
+}
+
+fn read_state(path: &Path) -> BTreeMap<String, DateTime<Utc>> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (name, at) = line.rsplit_once('\t')?;
+            let at = DateTime::parse_from_rfc3339(at).ok()?;
+            Some((name.to_owned(), at.with_timezone(&Utc)))
+        })
+        .collect()
+}
+
+fn write_state(path: &Path, state: &BTreeMap<String, DateTime<Utc>>) -> std::io::Result<()> {
+    let contents = state
+        .iter()
+        .fold(String::new(), |mut contents, (name, at)| {
+            // Writing to a `String` cannot fail.
+            let _ = writeln!(contents, "{name}\t{}", at.to_rfc3339());
+            contents
+        });
+    // Write then rename, so a crash mid-write never leaves a truncated file behind.
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents)?;
+    fs::rename(temp, path)
+}
+
+fn last_run_in(path: &Path, name: &str) -> Option<DateTime<Utc>> {
+    let _guard = STATE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    read_state(path).remove(name)
+}
+
+fn record_run_in(path: &Path, name: &str, at: DateTime<Utc>) -> std::io::Result<()> {
+    let _guard = STATE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut state = read_state(path);
+    state.insert(name.to_owned(), at);
+    write_state(path, &state)
+}
+
+/// Persists `at` as the start of the latest run of `name`.
+pub fn record_run(name: &str, at: DateTime<Utc>) {
+    let path = state_file();
+    if let Err(e) = record_run_in(&path, name, at) {
+        debug!(
+            "Could not record the run of job '{name}' in {}: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Returns the first scheduled time of `name` that was missed, if any: one after its
+/// persisted last run but before now. Jobs that never ran have nothing to catch up.
+pub fn missed_run(name: &str, schedule: &Schedule, tz: Tz) -> Option<DateTime<Utc>> {
+    let last_run = last_run_in(&state_file(), name)?;
+    first_missed(schedule, tz, last_run, Utc::now())
+}
+
+fn first_missed(
+    schedule: &Schedule,
+    tz: Tz,
+    last_run: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    schedule
+        .after(&last_run.with_timezone(&tz))
+        .next()
+        .map(|next| next.with_timezone(&Utc))
+        .filter(|next| *next < now)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 5, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!("skip".parse(), Ok(CatchUpPolicy::Skip));
+        assert_eq!("Run_Immediately".parse(), Ok(CatchUpPolicy::RunImmediately));
+        assert!("sometimes".parse::<CatchUpPolicy>().is_err());
+    }
+
+    #[test]
+    fn only_catching_up_needs_the_state_file() {
+        assert!(!CatchUpPolicy::Skip.needs_state());
+        assert!(CatchUpPolicy::RunImmediately.needs_state());
+    }
+
+    #[test]
+    fn detects_a_run_missed_while_down() {
+        let schedule = Schedule::from_str("0 0 3 * * *").unwrap();
+        // Last ran yesterday at 03:00; restarted today at 03:05.
+        let last_run = at(3, 0) - chrono::Duration::days(1);
+        assert_eq!(
+            first_missed(&schedule, Tz::UTC, last_run, at(3, 5)),
+            Some(at(3, 0))
+        );
+        // Restarted at 02:55: today's run has not come due yet.
+        assert_eq!(first_missed(&schedule, Tz::UTC, last_run, at(2, 55)), None);
+    }
+
+    #[test]
+    fn persists_last_runs_per_job() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cron-state");
+        assert_eq!(last_run_in(&path, "auto-update"), None);
+
+        record_run_in(&path, "auto-update", at(3, 0)).unwrap();
+        record_run_in(&path, "auto-backup", at(6, 0)).unwrap();
+        record_run_in(&path, "auto-update", at(3, 1)).unwrap();
+
+        assert_eq!(last_run_in(&path, "auto-update"), Some(at(3, 1)));
+        assert_eq!(last_run_in(&path, "auto-backup"), Some(at(6, 0)));
+    }
+}
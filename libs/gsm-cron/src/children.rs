@@ -0,0 +1,196 @@
+//! # Child Processes
+//!
+//! This module tracks processes started on behalf of the scheduler, such as the game
+//! server itself. When [`begin_cron_loop`](crate::begin_cron_loop) receives SIGINT or
+//! SIGTERM it forwards the signal to every registered process and waits for them to exit,
+//! so a container stop shuts the server down cleanly instead of killing it with the
+//! manager.
+use nix::errno::Errno;
+use nix::sys::signal::{Signal, kill};
+use nix::unistd::Pid;
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How long shutdown waits for registered processes before giving up on them.
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_mins(2);
+
+/// How often shutdown checks whether registered processes have exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The set of child process ids the scheduler forwards termination signals to.
+#[derive(Debug, Default)]
+pub struct ChildRegistry {
+    pids: Mutex<BTreeSet<u32>>,
+}
+
+static GLOBAL: LazyLock<ChildRegistry> = LazyLock::new(ChildRegistry::default);
+
+impl ChildRegistry {
+    /// The registry [`begin_cron_loop`](crate::begin_cron_loop) shuts down.
+    pub fn global() -> &'static Self {
+        &GLOBAL
+    }
+
+    /// Starts forwarding termination signals to `pid`.
+    pub fn register(&self, pid: u32) {
+        debug!("Tracking child process {pid}");
+        self.lock().insert(pid);
+    }
+
+    /// Stops tracking `pid`, e.g. after it was stopped some other way.
+    pub fn unregister(&self, pid: u32) {
+        self.lock().remove(&pid);
+    }
+
+    /// Returns the registered process ids, in ascending order.
+    pub fn pids(&self) -> Vec<u32> {
+        self.lock().iter().copied().collect()
+    }
+
+    /// Sends `signal` to every registered process. Processes that no longer exist are
+    /// dropped from the registry.
+    pub fn signal_all(&self, signal: Signal) {
+        self.lock().retain(|&pid| {
+            let Some(target) = to_pid(pid) else {
+                return false;
+            };
+            match kill(target, signal) {
+                Ok(()) => {
+                    info!("Forwarded {signal} to child process {pid}");
+                    true
+                }
+                Err(Errno::ESRCH) => false,
+                Err(e) => {
+                    warn!("Failed to send {signal} to child process {pid}: {e}");
+                    true
+                }
+            }
+        });
+    }
+
+    /// Waits up to `grace` for every registered process to exit, removing each one as it
+    /// does. Returns the processes still running when the grace period ran out.
+    pub fn wait_all(&self, grace: Duration) -> Vec<u32> {
+        let deadline = Instant::now() + grace;
+        loop {
+            self.lock().retain(|&pid| is_process_running(pid));
+            let remaining = self.pids();
+            if remaining.is_empty() || Instant::now() >= deadline {
+                return remaining;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Forwards `signal` to every registered process and waits up to `grace` for them
+    /// to exit.
+    pub fn shutdown(&self, signal: Signal, grace: Duration) {
+        if self.lock().is_empty() {
+            return;
+        }
+        self.signal_all(signal);
+        let remaining = self.wait_all(grace);
+        if remaining.is_empty() {
+            info!("All child processes exited");
+        } else {
+            warn!("Child processes {remaining:?} did not exit within {grace:?}");
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<u32>> {
+        self.pids.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn to_pid(pid: u32) -> Option<Pid> {
+    i32::try_from(pid).ok().map(Pid::from_raw)
+}
+
+/// Returns whether `pid` is still running.
+///
+/// The process is only probed, never reaped, so whoever owns it, such as the
+/// [`Child`](std::process::Child) that spawned it, can still collect its exit status; an
+/// exited child not collected yet counts as not running.
+pub fn is_process_running(pid: u32) -> bool {
+    let Some(target) = to_pid(pid).filter(|target| target.as_raw() > 0) else {
+        return false;
+    };
+    // EPERM means the process exists but belongs to another user.
+    !matches!(kill(target, None), Err(Errno::ESRCH)) && !is_zombie(pid)
+}
+
+/// Returns whether `pid` has exited but not been waited for yet, from the state in
+/// `/proc/<pid>/stat`. Without `/proc`, processes are assumed not to be zombies.
+fn is_zombie(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+        // The state follows the command name, which is in parentheses and may itself
+        // contain spaces and parentheses.
+        stat.rsplit_once(')')
+            .and_then(|(_, fields)| fields.split_whitespace().next())
+            .is_some_and(|state| matches!(state, "Z" | "X"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn register_and_unregister_track_pids() {
+        let registry = ChildRegistry::default();
+        registry.register(20);
+        registry.register(10);
+        registry.register(20);
+        assert_eq!(registry.pids(), vec![10, 20]);
+
+        registry.unregister(10);
+        assert_eq!(registry.pids(), vec![20]);
+    }
+
+    #[test]
+    fn shutdown_signals_children_and_waits_for_them() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let registry = ChildRegistry::default();
+        registry.register(child.id());
+
+        registry.shutdown(Signal::SIGTERM, Duration::from_secs(5));
+
+        assert!(registry.pids().is_empty());
+        // The exit status is left for the child's owner to collect.
+        assert!(child.try_wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn exited_children_are_not_running_but_not_reaped() {
+        let mut child = Command::new("true").spawn().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while is_process_running(child.id()) && Instant::now() < deadline {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        assert!(!is_process_running(child.id()));
+        assert!(child.try_wait().unwrap().unwrap().success());
+        assert!(!is_process_running(0));
+    }
+
+    #[test]
+    fn wait_all_reports_children_still_running() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let registry = ChildRegistry::default();
+        registry.register(child.id());
+
+        assert_eq!(
+            registry.wait_all(Duration::from_millis(50)),
+            vec![child.id()]
+        );
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
@@ -1,15 +1,17 @@
 //! # Cron Loop
 //!
 //! This module provides the main event loop for the cron scheduler.
-use std::time::Duration;
-use tokio::time::sleep;
+use crate::children::{ChildRegistry, DEFAULT_SHUTDOWN_GRACE};
+use nix::sys::signal::Signal;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{info, warn};
 
-/// Begins the main cron loop, which runs indefinitely.
+/// Begins the main cron loop, which runs until the process receives SIGINT or SIGTERM.
 ///
-/// This function starts an infinite loop that can be used to keep a program running
-/// while scheduled cron jobs execute in the background. The loop currently sleeps for
-/// 60 seconds at a time, but this can be adapted for other purposes, such as handling
-/// signals for graceful shutdown.
+/// Scheduled jobs execute in the background while this keeps the program alive. On
+/// SIGINT or SIGTERM the signal is forwarded to every process in the
+/// [`ChildRegistry`], and the loop returns once they have exited or
+/// [`DEFAULT_SHUTDOWN_GRACE`] has passed.
 ///
 /// In a typical application, you would spawn your cron jobs using `spawn_scheduled_job`
 /// or `register_job`, and then call this function to keep the main thread alive.
@@ -24,20 +26,40 @@ use tokio::time::sleep;
 ///     // Register a job to run every minute.
 ///     register_job("heartbeat", "* * * * *", || {
 ///         println!("Cron loop is alive!");
-///     });
+///     })
+///     .expect("valid schedule");
 ///
-///     // Start the cron loop to keep the application running.
+///     // Keep the application running until it is asked to stop.
 ///     begin_cron_loop().await;
 /// }
 /// ```
 pub async fn begin_cron_loop() {
-    loop {
-        tokio::select! {
-            () = sleep(Duration::from_mins(1)) => {
-                // Normal tick every 60 seconds. This loop can be used to
-                // integrate with signal handling for graceful shutdown.
-            }
+    let received = wait_for_termination().await;
+    info!("Received {received}; shutting down child processes");
+    let shutdown = tokio::task::spawn_blocking(move || {
+        ChildRegistry::global().shutdown(received, DEFAULT_SHUTDOWN_GRACE);
+    });
+    if let Err(e) = shutdown.await {
+        warn!("Child process shutdown did not complete: {e}");
+    }
+}
+
+/// Waits for SIGINT or SIGTERM and returns which arrived. If the handlers cannot be
+/// installed this waits forever, as the loop did before it handled signals.
+pub async fn wait_for_termination() -> Signal {
+    let (mut interrupt, mut terminate) = match (
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+    ) {
+        (Ok(interrupt), Ok(terminate)) => (interrupt, terminate),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Failed to install signal handlers: {e}; child processes will not be stopped");
+            return std::future::pending().await;
         }
+    };
+    tokio::select! {
+        _ = interrupt.recv() => Signal::SIGINT,
+        _ = terminate.recv() => Signal::SIGTERM,
     }
 }
 
@@ -0,0 +1,107 @@
+//! # Intervals
+//!
+//! This module turns "every N" schedules into cron expressions, for operators who just
+//! want a job every 6 hours. It also expands the `@hourly`-style shorthands, so
+//! `register_job` accepts `@daily` or `@every 6h` wherever it accepts a cron expression.
+//!
+//! Cron counts from the top of each minute, hour or day, so only intervals that divide
+//! one of those evenly can be expressed; others are rejected rather than drifting.
+use crate::CronError;
+use gsm_shared::parse_duration;
+use std::time::Duration;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Returns the 6-field cron expression running every `every`.
+pub fn interval_schedule(every: Duration) -> Result<String, CronError> {
+    let secs = every.as_secs();
+    let expression = if secs == 0 || every.subsec_nanos() != 0 {
+        None
+    } else if secs < MINUTE && MINUTE.is_multiple_of(secs) {
+        Some(format!("*/{secs} * * * * *"))
+    } else if secs < HOUR && secs.is_multiple_of(MINUTE) && HOUR.is_multiple_of(secs) {
+        Some(format!("0 */{} * * * *", secs / MINUTE))
+    } else if secs < DAY && secs.is_multiple_of(HOUR) && DAY.is_multiple_of(secs) {
+        Some(format!("0 0 */{} * * *", secs / HOUR))
+    } else if secs == DAY {
+        Some("0 0 0 * * *".to_owned())
+    } else {
+        None
+    };
+    expression.ok_or_else(|| CronError::InvalidSchedule {
+        schedule: format!("every {every:?}"),
+        reason: "intervals must evenly divide a minute, an hour or a day".to_owned(),
+    })
+}
+
+/// Expands `@`-shorthands into cron expressions; other schedules are returned unchanged.
+pub fn expand_shorthand(schedule: &str) -> Result<String, CronError> {
+    let trimmed = schedule.trim();
+    let expanded = match trimmed {
+        "@yearly" | "@annually" => "0 0 0 1 1 *",
+        "@monthly" => "0 0 0 1 * *",
+        "@weekly" => "0 0 0 * * SUN",
+        "@daily" | "@midnight" => "0 0 0 * * *",
+        "@hourly" => "0 0 * * * *",
+        _ => {
+            if let Some(every) = trimmed.strip_prefix("@every") {
+                let every = parse_duration(every).map_err(|e| CronError::InvalidSchedule {
+                    schedule: schedule.to_owned(),
+                    reason: format!(
+                        "{e}; expected an interval such as '@every 6h' or '@every 30m'"
+                    ),
+                })?;
+                return interval_schedule(every);
+            }
+            return Ok(schedule.to_owned());
+        }
+    };
+    Ok(expanded.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn intervals_dividing_a_unit_become_cron_steps() {
+        let cases = [
+            (Duration::from_secs(15), "*/15 * * * * *"),
+            (Duration::from_mins(1), "0 */1 * * * *"),
+            (Duration::from_mins(20), "0 */20 * * * *"),
+            (Duration::from_hours(6), "0 0 */6 * * *"),
+            (Duration::from_hours(24), "0 0 0 * * *"),
+        ];
+        for (every, expected) in cases {
+            assert_eq!(interval_schedule(every).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn uneven_intervals_are_rejected() {
+        for every in [
+            Duration::ZERO,
+            Duration::from_secs(7),
+            Duration::from_mins(7),
+            Duration::from_mins(90),
+            Duration::from_hours(5),
+            Duration::from_hours(48),
+            Duration::from_millis(1500),
+        ] {
+            assert!(interval_schedule(every).is_err(), "{every:?}");
+        }
+    }
+
+    #[test]
+    fn shorthands_expand_and_other_schedules_pass_through() {
+        assert_eq!(expand_shorthand("@daily").unwrap(), "0 0 0 * * *");
+        assert_eq!(expand_shorthand("@every 6h").unwrap(), "0 0 */6 * * *");
+        assert_eq!(expand_shorthand("@every 1h30m").ok(), None);
+        assert_eq!(expand_shorthand("@every soon").ok(), None);
+        assert_eq!(expand_shorthand("0 3 * * *").unwrap(), "0 3 * * *");
+    }
+}
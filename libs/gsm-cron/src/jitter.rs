@@ -0,0 +1,39 @@
+//! # Jitter
+//!
+//! This module spreads job start times. Many servers sharing a schedule, e.g. a fleet
+//! of containers all updating at 03:00, would otherwise hit Steam at the same moment;
+//! with jitter each run is delayed by a random amount up to a configured maximum.
+use gsm_shared::fetch_duration;
+use std::time::Duration;
+
+/// Environment variable with the default maximum jitter for jobs, e.g. `10m`; a bare
+/// number is seconds.
+pub const CRON_JITTER: &str = "CRON_JITTER";
+
+/// Returns the maximum jitter named by `CRON_JITTER`, or zero when it is unset or invalid.
+pub fn default_jitter() -> Duration {
+    fetch_duration(CRON_JITTER, Duration::ZERO)
+}
+
+/// Returns a random delay between zero and `max`, inclusive.
+pub fn random_delay(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let max_millis = u64::try_from(max.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(fastrand::u64(0..=max_millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_delay_stays_within_bounds() {
+        assert_eq!(random_delay(Duration::ZERO), Duration::ZERO);
+        let max = Duration::from_secs(5);
+        for _ in 0..100 {
+            assert!(random_delay(max) <= max);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! # Job Handle
+//!
+//! This module provides [`JobHandle`], returned whenever a job is scheduled, so callers can
+//! inspect a job and stop it on shutdown or when its configuration changes.
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::debug;
+
+/// When the job fires next, shared between the scheduler task and its handles.
+type NextRun = Arc<Mutex<Option<DateTime<Utc>>>>;
+
+/// A handle to a scheduled job.
+///
+/// Dropping the handle does not stop the job; it keeps running in the background until
+/// [`JobHandle::cancel`] is called or the runtime shuts down.
+#[derive(Debug)]
+pub struct JobHandle {
+    task: JoinHandle<()>,
+    next_run: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+
+impl JobHandle {
+    pub(crate) const fn new(
+        task: JoinHandle<()>,
+        next_run: Arc<Mutex<Option<DateTime<Utc>>>>,
+    ) -> Self {
+        Self { task, next_run }
+    }
+
+    /// The pieces the [`JobRegistry`](crate::JobRegistry) needs to report on this job
+    /// after the handle has been returned to the caller.
+    pub(crate) fn shared_parts(&self) -> (AbortHandle, NextRun) {
+        (self.task.abort_handle(), Arc::clone(&self.next_run))
+    }
+
+    /// Stops the job. A run already in progress finishes, but no further runs start.
+    pub fn cancel(&self) {
+        debug!("Cancelling scheduled job");
+        self.task.abort();
+        *self.next_run.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Returns whether the job is still scheduled.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Returns when the job will next run, or `None` if it is no longer scheduled.
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        if !self.is_running() {
+            return None;
+        }
+        *self.next_run.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
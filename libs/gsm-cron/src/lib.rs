@@ -5,15 +5,62 @@
 //!
 //! The crate uses the `cron` and `tokio` crates to provide a flexible and efficient scheduling mechanism.
 //! It supports standard cron expressions for scheduling jobs.
+mod blackout;
+mod catch_up;
+mod children;
 mod cron_loop;
+mod interval;
+mod jitter;
+mod job_handle;
+mod metrics;
+mod options;
+mod overlap;
+mod registry;
+mod retry;
+mod scheduler;
+mod timeout;
+mod timezone;
 
 use chrono::Utc;
 use cron::Schedule;
 use std::str::FromStr;
-use tokio::time::{Duration, sleep};
-use tracing::{debug, error, info};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info};
 
-pub use cron_loop::begin_cron_loop;
+pub use blackout::BlackoutWindow;
+pub use catch_up::{
+    CRON_CATCH_UP, CRON_STATE_FILE, CatchUpPolicy, DEFAULT_STATE_FILE, default_catch_up,
+};
+pub use children::{ChildRegistry, DEFAULT_SHUTDOWN_GRACE, is_process_running};
+pub use chrono_tz::Tz;
+pub use cron_loop::{begin_cron_loop, wait_for_termination};
+pub use jitter::{CRON_JITTER, default_jitter};
+pub use job_handle::JobHandle;
+pub use metrics::{
+    DURATION_METRIC, FAILURES_METRIC, RUNS_METRIC, SKIPPED_METRIC, TIMEOUTS_METRIC, attach_metrics,
+};
+pub use options::JobOptions;
+pub use overlap::OverlapPolicy;
+pub use registry::{JobRegistry, JobResult, JobStatus};
+pub use retry::{JobFailure, RetryPolicy};
+pub use timeout::cancellation_requested;
+pub use timezone::{CRON_TIMEZONE, default_timezone};
+
+use interval::{expand_shorthand, interval_schedule};
+use options::Settings;
+use overlap::{Job, JobState};
+use retry::with_retries;
+
+/// Errors returned when scheduling a job.
+#[derive(Debug, Error)]
+pub enum CronError {
+    #[error("Invalid cron schedule '{schedule}': {reason}")]
+    InvalidSchedule { schedule: String, reason: String },
+    #[error("Invalid blackout window '{window}': {reason}")]
+    InvalidBlackout { window: String, reason: String },
+}
 
 fn normalize_schedule(schedule: &str) -> String {
     let field_count = schedule.split_whitespace().count();
@@ -24,10 +71,47 @@ fn normalize_schedule(schedule: &str) -> String {
     }
 }
 
+/// Expands shorthands such as `@daily` and adapts 5-field expressions to 6 fields.
+fn resolve_schedule(schedule: &str) -> Result<String, CronError> {
+    Ok(normalize_schedule(&expand_shorthand(schedule)?))
+}
+
+fn parse_schedule(schedule: &str) -> Result<Schedule, CronError> {
+    let normalized = resolve_schedule(schedule)?;
+    debug!("Attempting to parse schedule: {}", normalized);
+    Schedule::from_str(&normalized).map_err(|e| CronError::InvalidSchedule {
+        schedule: schedule.to_owned(),
+        reason: e.to_string(),
+    })
+}
+
+/// Checks that `schedule` is a valid cron expression, without scheduling anything.
+///
+/// Accepts the same 5-field and 6-field expressions as [`register_job`], so configuration
+/// can be checked up front, before a server is started.
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the expression cannot be parsed.
+///
+/// # Example
+///
+/// ```rust
+/// use gsm_cron::validate_schedule;
+///
+/// assert!(validate_schedule("0 3 * * *").is_ok());
+/// assert!(validate_schedule("every day at 3").is_err());
+/// ```
+pub fn validate_schedule(schedule: &str) -> Result<(), CronError> {
+    parse_schedule(schedule).map(|_| ())
+}
+
 /// Spawns a job to run on a cron-like schedule asynchronously.
 ///
 /// This function takes a cron schedule string and a closure, and spawns a `tokio` task
-/// to execute the closure at the specified times. The schedule is based on UTC.
+/// to execute the closure at the specified times. The schedule is evaluated in the
+/// timezone named by `CRON_TIMEZONE`, or UTC when unset (see [`default_timezone`]), and
+/// runs are delayed by up to `CRON_JITTER` (see [`default_jitter`]).
 ///
 /// # Arguments
 ///
@@ -35,9 +119,15 @@ fn normalize_schedule(schedule: &str) -> String {
 /// * `job`: A closure that will be executed when the schedule is met. The closure must be
 ///   `Send`, `Sync`, and have a `'static` lifetime.
 ///
-/// # Panics
+/// Each run executes on tokio's blocking pool. If a run is still in progress when the
+/// next one is due, the new run is skipped (see [`OverlapPolicy::Skip`]).
+///
+/// Returns a [`JobHandle`] that can cancel or inspect the job.
+///
+/// # Errors
 ///
-/// This function does not panic, but it will log an error if the schedule string is invalid.
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed; nothing is
+/// scheduled in that case.
 ///
 /// # Example
 ///
@@ -45,36 +135,56 @@ fn normalize_schedule(schedule: &str) -> String {
 /// use gsm_cron::spawn_scheduled_job;
 ///
 /// // Schedule a job to run every minute.
-/// spawn_scheduled_job("0 * * * * *", || {
+/// let handle = spawn_scheduled_job("0 * * * * *", || {
 ///     println!("This job runs every minute!");
-/// });
+/// })
+/// .expect("valid schedule");
+/// println!("Next run: {:?}", handle.next_run());
+/// handle.cancel();
 /// ```
-pub fn spawn_scheduled_job(schedule_str: &str, job: impl Fn() + Send + Sync + 'static) {
-    debug!("Attempting to parse schedule: {}", schedule_str);
-    let schedule = match Schedule::from_str(schedule_str) {
-        Ok(s) => {
-            debug!("Schedule parsed successfully: {:?}", s);
-            s
-        }
-        Err(e) => {
-            error!("Invalid cron schedule '{}': {}", schedule_str, e);
-            return;
-        }
-    };
+pub fn spawn_scheduled_job(
+    schedule_str: &str,
+    job: impl Fn() + Send + Sync + 'static,
+) -> Result<JobHandle, CronError> {
+    spawn_with_state(
+        schedule_str,
+        "scheduled job",
+        JobOptions::default().resolve(),
+        Arc::new(JobState::default()),
+        infallible(job),
+    )
+}
 
-    tokio::spawn(async move {
-        for datetime in schedule.upcoming(Utc) {
-            let now = Utc::now();
-            let wait_time = (datetime - now).to_std().unwrap_or(Duration::ZERO);
-            sleep(wait_time).await;
-            debug!(
-                "Woke up at: {:?} for scheduled time: {:?}",
-                Utc::now(),
-                datetime
-            );
-            job();
-        }
-    });
+fn infallible(job: impl Fn() + Send + Sync + 'static) -> Job {
+    Arc::new(move || {
+        job();
+        Ok(())
+    })
+}
+
+fn spawn_with_state(
+    schedule_str: &str,
+    label: &str,
+    settings: Settings,
+    state: Arc<JobState>,
+    job: Job,
+) -> Result<JobHandle, CronError> {
+    let schedule = parse_schedule(schedule_str)?;
+    debug!("Schedule parsed successfully: {:?}", schedule);
+    let next_run = Arc::new(Mutex::new(scheduler::next_fire(
+        &schedule,
+        settings.timezone,
+        Utc::now(),
+    )));
+    let task = tokio::spawn(scheduler::run_schedule(
+        schedule,
+        settings,
+        label.to_owned(),
+        state,
+        job,
+        Arc::clone(&next_run),
+    ));
+    Ok(JobHandle::new(task, next_run))
 }
 
 /// A helper function to register a job with a name and a cron schedule.
@@ -86,9 +196,22 @@ pub fn spawn_scheduled_job(schedule_str: &str, job: impl Fn() + Send + Sync + 's
 ///
 /// * `name`: A name for the job, used for logging.
 /// * `schedule`: The cron schedule string. This can be a standard 6-field cron expression
-///   (including seconds) or a 5-field expression (which will be adapted).
+///   (including seconds), a 5-field expression (which will be adapted), or a shorthand:
+///   `@hourly`, `@daily`, `@weekly`, `@monthly`, `@yearly` or `@every <interval>`
+///   (e.g. `@every 6h`, see [`register_interval_job`]).
 /// * `job`: The closure to execute.
 ///
+/// Runs that come due while a previous run of a job with the same name is still in
+/// progress are skipped; use [`register_job_with_policy`] to choose otherwise.
+///
+/// Returns a [`JobHandle`] for the registered job. The job is also recorded in
+/// [`JobRegistry::global`], replacing any earlier job registered under `name`.
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed; nothing is
+/// scheduled or recorded in that case.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -97,44 +220,281 @@ pub fn spawn_scheduled_job(schedule_str: &str, job: impl Fn() + Send + Sync + 's
 /// // Register a daily backup job.
 /// register_job("daily-backup", "0 0 0 * * *", || {
 ///     println!("Running daily backup...");
-/// });
+/// })?;
 ///
 /// // Register a job with a 5-field schedule (runs every minute).
 /// register_job("minute-ping", "* * * * *", || {
 ///     println!("Pinging server...");
-/// });
+/// })?;
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+pub fn register_job<F>(name: &str, schedule: &str, job: F) -> Result<JobHandle, CronError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    register_job_with_policy(name, schedule, OverlapPolicy::default(), job)
+}
+
+/// Registers a named job like [`register_job`], evaluating its schedule in `tz` instead
+/// of the `CRON_TIMEZONE` default.
+///
+/// Runs keep their wall-clock time across daylight saving transitions, so a 4am restart
+/// stays at 4am local time all year.
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use gsm_cron::{Tz, register_job_tz};
+///
+/// register_job_tz("scheduled-restart", "0 4 * * *", Tz::Europe__Berlin, || {
+///     println!("Restarting at 4am Berlin time...");
+/// })?;
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+pub fn register_job_tz<F>(
+    name: &str,
+    schedule: &str,
+    tz: Tz,
+    job: F,
+) -> Result<JobHandle, CronError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let options = JobOptions {
+        timezone: Some(tz),
+        ..JobOptions::default()
+    };
+    register_named(name, schedule, options, infallible(job))
+}
+
+/// Registers a named job like [`register_job`], with an explicit [`OverlapPolicy`].
+///
+/// The policy is enforced across every job registered under `name`, so re-registering
+/// a job (e.g. after a configuration change) never lets two runs overlap unless the
+/// policy is [`OverlapPolicy::Concurrent`].
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use gsm_cron::{OverlapPolicy, register_job_with_policy};
+///
+/// // Let a slow backup finish, then run the one that came due meanwhile.
+/// register_job_with_policy("backup", "*/10 * * * *", OverlapPolicy::Queue, || {
+///     println!("Backing up...");
+/// })?;
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+pub fn register_job_with_policy<F>(
+    name: &str,
+    schedule: &str,
+    policy: OverlapPolicy,
+    job: F,
+) -> Result<JobHandle, CronError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let options = JobOptions {
+        overlap: policy,
+        ..JobOptions::default()
+    };
+    register_named(name, schedule, options, infallible(job))
+}
+
+/// Registers a named job like [`register_job`], with explicit [`JobOptions`].
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use gsm_cron::{JobOptions, register_job_with_options};
+/// use std::time::Duration;
+///
+/// // Spread updates across a fleet over up to 10 minutes after 03:00.
+/// let options = JobOptions {
+///     jitter: Some(Duration::from_mins(10)),
+///     ..JobOptions::default()
+/// };
+/// register_job_with_options("auto-update", "0 3 * * *", options, || {
+///     println!("Checking for updates...");
+/// })?;
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+pub fn register_job_with_options<F>(
+    name: &str,
+    schedule: &str,
+    options: JobOptions,
+    job: F,
+) -> Result<JobHandle, CronError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    register_named(name, schedule, options, infallible(job))
+}
+
+/// Registers a named job like [`register_job`] that runs every `every`, e.g. every 6
+/// hours.
+///
+/// Runs are aligned to the clock like any cron schedule: an hourly job runs on the hour,
+/// not an hour after registration.
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] unless `every` is a whole number of seconds
+/// that evenly divides a minute, an hour or a day (e.g. 15 seconds, 20 minutes or
+/// 6 hours), or is exactly one day.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use gsm_cron::register_interval_job;
+/// use std::time::Duration;
+///
+/// register_interval_job("auto-backup", Duration::from_hours(6), || {
+///     println!("Backing up...");
+/// })?;
+/// # Ok::<(), gsm_cron::CronError>(())
 /// ```
-pub fn register_job<F>(name: &str, schedule: &str, job: F)
+pub fn register_interval_job<F>(name: &str, every: Duration, job: F) -> Result<JobHandle, CronError>
 where
     F: Fn() + Send + Sync + 'static,
 {
+    let schedule = interval_schedule(every)?;
+    register_named(name, &schedule, JobOptions::default(), infallible(job))
+}
+
+/// Registers a named job like [`register_job`] whose runs can fail.
+///
+/// A run that returns an error is retried according to [`JobOptions::retry`]; if every
+/// attempt fails,
+/// `on_failure` is called with the last error, e.g. to send a notification. The outcome
+/// of each run is recorded in the [`JobRegistry`].
+///
+/// # Errors
+///
+/// Returns [`CronError::InvalidSchedule`] if the schedule cannot be parsed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use gsm_cron::{JobOptions, RetryPolicy, register_fallible_job};
+/// use std::time::Duration;
+///
+/// // Retry a failed update up to 3 times, 10 minutes apart.
+/// let options = JobOptions {
+///     retry: RetryPolicy::new(3, Duration::from_mins(10)),
+///     ..JobOptions::default()
+/// };
+/// register_fallible_job(
+///     "auto-update",
+///     "0 3 * * *",
+///     options,
+///     |failure| eprintln!("{} failed: {}", failure.name, failure.error),
+///     || -> Result<(), String> { Err("SteamCMD is unavailable".to_owned()) },
+/// )?;
+/// # Ok::<(), gsm_cron::CronError>(())
+/// ```
+pub fn register_fallible_job<F, E>(
+    name: &str,
+    schedule: &str,
+    options: JobOptions,
+    on_failure: impl Fn(&JobFailure) + Send + Sync + 'static,
+    job: F,
+) -> Result<JobHandle, CronError>
+where
+    F: Fn() -> Result<(), E> + Send + Sync + 'static,
+    E: std::fmt::Display,
+{
+    let job = with_retries(name, options.retry, Arc::new(on_failure), job);
+    register_named(name, schedule, options, job)
+}
+
+fn register_named(
+    name: &str,
+    schedule: &str,
+    options: JobOptions,
+    job: Job,
+) -> Result<JobHandle, CronError> {
+    let settings = options.resolve();
     let name_owned = name.to_owned();
-    let adjusted_schedule = normalize_schedule(schedule);
-    if schedule.split_whitespace().count() == 5 {
+    let adjusted_schedule = resolve_schedule(schedule)?;
+    if adjusted_schedule == schedule {
         debug!(
-            "Adjusted schedule from 5-field to 6-field for job '{}': {} (original: {})",
-            name_owned, adjusted_schedule, schedule
+            "Schedule for job '{}' is already 6-field: {}",
+            name_owned, schedule
         );
     } else {
         debug!(
-            "Schedule for job '{}' is already 6-field: {}",
-            name_owned, schedule
+            "Adjusted schedule for job '{}': {} (original: {})",
+            name_owned, adjusted_schedule, schedule
         );
     }
 
     info!(
-        "Registering job '{}' with schedule: {}",
-        name_owned, adjusted_schedule
+        "Registering job '{}' with schedule: {} {} (overlap: {:?}, jitter: {}s, catch-up: {:?})",
+        name_owned,
+        adjusted_schedule,
+        settings.timezone,
+        settings.overlap,
+        settings.jitter.as_secs(),
+        settings.catch_up
     );
+    if !settings.blackouts.is_empty() {
+        let windows: Vec<String> = settings.blackouts.iter().map(ToString::to_string).collect();
+        info!(
+            "Job '{name_owned}' will not run during: {}",
+            windows.join("; ")
+        );
+    }
 
-    spawn_scheduled_job(&adjusted_schedule, move || {
+    let missed = match settings.catch_up {
+        CatchUpPolicy::RunImmediately => catch_up::missed_run(
+            name,
+            &parse_schedule(&adjusted_schedule)?,
+            settings.timezone,
+        ),
+        CatchUpPolicy::Skip => None,
+    };
+
+    let label = format!("job '{name_owned}'");
+    let state = JobState::named(name);
+    let records_runs = settings.catch_up.needs_state();
+    let job: Job = Arc::new(move || {
         info!("Executing job: {}", name_owned);
-        job();
+        if records_runs {
+            catch_up::record_run(&name_owned, Utc::now());
+        }
+        job()
     });
+    let handle = spawn_with_state(
+        &adjusted_schedule,
+        &label,
+        settings.clone(),
+        Arc::clone(&state),
+        Arc::clone(&job),
+    )?;
+    if let Some(missed) = missed {
+        info!("Job '{name}' missed its run at {missed} while stopped; running it now");
+        scheduler::start_run(&label, &settings, &state, &job, missed);
+    }
+    JobRegistry::global().insert(name, &adjusted_schedule, settings, &handle, state);
+    Ok(handle)
 }
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
     #[test]
@@ -147,28 +507,203 @@ mod tests {
         assert_eq!(normalize_schedule("0 * * * * *"), "0 * * * * *");
     }
 
+    #[test]
+    fn validate_schedule_accepts_shorthands() {
+        assert!(validate_schedule("@hourly").is_ok());
+        assert!(validate_schedule("@every 6h").is_ok());
+        assert!(validate_schedule("@every 5h").is_err());
+    }
+
     #[tokio::test]
-    async fn spawn_scheduled_job_with_invalid_schedule_does_not_panic() {
-        // Invalid schedule must be silently rejected (error logged, no panic).
-        spawn_scheduled_job("not-a-cron-expression", || {});
-        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    async fn register_interval_job_runs_on_interval_boundaries() {
+        let handle =
+            register_interval_job("test-interval", std::time::Duration::from_hours(6), || {})
+                .unwrap();
+        let next = handle.next_run().unwrap();
+        assert_eq!(chrono::Timelike::minute(&next), 0);
+        assert_eq!(
+            JobRegistry::global().get("test-interval").unwrap().schedule,
+            "0 0 */6 * * *"
+        );
+        handle.cancel();
+    }
+
+    #[test]
+    fn validate_schedule_accepts_five_and_six_field_cron() {
+        assert!(validate_schedule("0 3 * * *").is_ok());
+        assert!(validate_schedule("0 0 3 * * *").is_ok());
+    }
+
+    #[test]
+    fn validate_schedule_reports_the_bad_expression() {
+        let err = validate_schedule("0 25 * * *").unwrap_err();
+        assert!(err.to_string().contains("'0 25 * * *'"));
     }
 
     #[tokio::test]
-    async fn register_job_accepts_six_field_schedule_without_panic() {
-        register_job("test-6field", "0 59 23 31 12 *", || {});
-        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    async fn register_job_accepts_six_field_schedule() {
+        register_job("test-6field", "0 59 23 31 12 *", || {}).unwrap();
+    }
+
+    #[tokio::test]
+    async fn register_job_adjusts_five_field_schedule() {
+        register_job("test-5field", "59 23 31 12 *", || {}).unwrap();
     }
 
     #[tokio::test]
-    async fn register_job_adjusts_five_field_schedule_without_panic() {
-        register_job("test-5field", "59 23 31 12 *", || {});
+    async fn spawn_scheduled_job_with_invalid_schedule_returns_error() {
+        let result = spawn_scheduled_job("not-a-cron-expression", || {});
+        assert!(matches!(result, Err(CronError::InvalidSchedule { .. })));
+    }
+
+    #[tokio::test]
+    async fn job_handle_reports_next_run_and_cancels() {
+        let handle = register_job("test-handle", "0 59 23 31 12 *", || {}).unwrap();
+        assert!(handle.is_running());
+        assert!(handle.next_run().is_some_and(|next| next > Utc::now()));
+
+        handle.cancel();
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert!(!handle.is_running());
+        assert!(handle.next_run().is_none());
     }
 
     #[tokio::test]
-    async fn register_job_with_invalid_schedule_does_not_panic() {
-        register_job("test-invalid", "garbage schedule", || {});
+    async fn register_job_tz_reports_next_run_in_utc() {
+        let handle = register_job_tz("test-tz", "0 0 4 * * *", Tz::Asia__Tokyo, || {}).unwrap();
+        let next = handle.next_run().unwrap();
+        // 04:00 in Tokyo (UTC+9, no DST) is always 19:00 UTC.
+        assert_eq!(chrono::Timelike::hour(&next), 19);
+        handle.cancel();
+    }
+
+    #[tokio::test]
+    async fn jitter_delays_the_next_run_within_bounds() {
+        let jitter = std::time::Duration::from_mins(30);
+        let options = JobOptions {
+            timezone: Some(Tz::UTC),
+            jitter: Some(jitter),
+            ..JobOptions::default()
+        };
+        let handle =
+            register_job_with_options("test-jitter", "0 0 0 1 1 *", options, || {}).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        let scheduled = parse_schedule("0 0 0 1 1 *")
+            .unwrap()
+            .upcoming(Utc)
+            .next()
+            .unwrap();
+        let next = handle.next_run().unwrap();
+        assert!(next >= scheduled);
+        assert!(next <= scheduled + jitter);
+        handle.cancel();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn registry_reports_registered_jobs() {
+        let options = JobOptions {
+            overlap: OverlapPolicy::Queue,
+            jitter: Some(std::time::Duration::ZERO),
+            ..JobOptions::default()
+        };
+        let handle =
+            register_job_with_options("test-registry", "* * * * * *", options, || {}).unwrap();
+        let status = JobRegistry::global().get("test-registry").unwrap();
+        assert_eq!(status.schedule, "* * * * * *");
+        assert_eq!(status.overlap, OverlapPolicy::Queue);
+        assert_eq!(status.jitter, std::time::Duration::ZERO);
+        assert!(status.scheduled);
+        assert!(status.next_run.is_some());
+
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        let status = JobRegistry::global().get("test-registry").unwrap();
+        assert!(status.run_count >= 1);
+        assert!(status.last_run.is_some());
+        assert_eq!(status.last_result, Some(JobResult::Succeeded));
+
+        handle.cancel();
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let status = JobRegistry::global().get("test-registry").unwrap();
+        assert!(!status.scheduled);
+        assert!(status.next_run.is_none());
+        assert!(
+            JobRegistry::global()
+                .list()
+                .iter()
+                .any(|job| job.name == "test-registry")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fallible_job_failures_are_reported_and_recorded() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = register_fallible_job(
+            "test-fallible",
+            "* * * * * *",
+            JobOptions {
+                retry: RetryPolicy::new(1, std::time::Duration::ZERO),
+                jitter: Some(std::time::Duration::ZERO),
+                ..JobOptions::default()
+            },
+            move |failure| {
+                let _ = tx.send(failure.clone());
+            },
+            || Err("no luck"),
+        )
+        .unwrap();
+
+        let failure =
+            tokio::task::spawn_blocking(move || rx.recv_timeout(std::time::Duration::from_secs(3)))
+                .await
+                .unwrap()
+                .unwrap();
+        handle.cancel();
+        assert_eq!(failure.attempts, 2);
+        assert_eq!(failure.error, "no luck");
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let status = JobRegistry::global().get("test-fallible").unwrap();
+        assert_eq!(
+            status.last_result,
+            Some(JobResult::Failed("no luck".to_owned()))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_inside_blackout_windows_are_skipped() {
+        let options = JobOptions {
+            jitter: Some(std::time::Duration::ZERO),
+            blackouts: vec!["Mon-Sun".parse().unwrap()],
+            ..JobOptions::default()
+        };
+        let handle =
+            register_job_with_options("test-blackout", "* * * * * *", options, || {}).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        handle.cancel();
+
+        let status = JobRegistry::global().get("test-blackout").unwrap();
+        assert_eq!(status.blackouts.len(), 1);
+        assert_eq!(status.run_count, 0);
+        assert!(status.skipped_count >= 1);
+    }
+
+    #[tokio::test]
+    async fn registry_pauses_and_resumes_jobs() {
+        let handle = register_job("test-pause", "0 0 4 * * *", || {}).unwrap();
+        let registry = JobRegistry::global();
+
+        assert!(registry.pause("test-pause"));
+        assert!(registry.get("test-pause").unwrap().paused);
+        assert!(registry.resume("test-pause"));
+        assert!(!registry.get("test-pause").unwrap().paused);
+        assert!(!registry.pause("test-missing"));
+        handle.cancel();
+    }
+
+    #[tokio::test]
+    async fn register_job_with_invalid_schedule_is_not_recorded() {
+        assert!(register_job("test-invalid", "garbage schedule", || {}).is_err());
+        assert!(JobRegistry::global().get("test-invalid").is_none());
     }
 }
@@ -0,0 +1,79 @@
+//! # Job Options
+//!
+//! This module collects the per-job settings accepted by
+//! [`register_job_with_options`](crate::register_job_with_options). Settings left unset
+//! fall back to their environment defaults when the job is registered.
+use crate::BlackoutWindow;
+use crate::catch_up::default_catch_up;
+use crate::jitter::default_jitter;
+use crate::{CatchUpPolicy, OverlapPolicy, RetryPolicy, default_timezone};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::time::Duration;
+
+/// Per-job scheduling settings.
+///
+/// ```rust
+/// use gsm_cron::{JobOptions, OverlapPolicy};
+/// use std::time::Duration;
+///
+/// let options = JobOptions {
+///     overlap: OverlapPolicy::Queue,
+///     jitter: Some(Duration::from_mins(10)),
+///     ..JobOptions::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobOptions {
+    /// What to do when a run comes due while another is in progress.
+    pub overlap: OverlapPolicy,
+    /// Timezone the schedule is evaluated in; `None` uses `CRON_TIMEZONE`.
+    pub timezone: Option<Tz>,
+    /// Maximum random delay added to each run; `None` uses `CRON_JITTER`. Keep it
+    /// shorter than the interval between runs.
+    pub jitter: Option<Duration>,
+    /// How failed runs are retried; only jobs registered with
+    /// [`register_fallible_job`](crate::register_fallible_job) can fail.
+    pub retry: RetryPolicy,
+    /// What to do with a run missed while the process was down; `None` uses
+    /// `CRON_CATCH_UP`.
+    pub catch_up: Option<CatchUpPolicy>,
+    /// How long a run may take before it is recorded as timed out and abandoned; see
+    /// [`cancellation_requested`](crate::cancellation_requested). `None` never times out.
+    pub timeout: Option<Duration>,
+    /// Windows during which runs are skipped, evaluated in the job's timezone when a run
+    /// comes due.
+    pub blackouts: Vec<BlackoutWindow>,
+}
+
+/// [`JobOptions`] with the environment defaults filled in.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub overlap: OverlapPolicy,
+    pub timezone: Tz,
+    pub jitter: Duration,
+    pub catch_up: CatchUpPolicy,
+    pub timeout: Option<Duration>,
+    pub blackouts: Vec<BlackoutWindow>,
+}
+
+impl JobOptions {
+    pub(crate) fn resolve(self) -> Settings {
+        Settings {
+            overlap: self.overlap,
+            timezone: self.timezone.unwrap_or_else(default_timezone),
+            jitter: self.jitter.unwrap_or_else(default_jitter),
+            catch_up: self.catch_up.unwrap_or_else(default_catch_up),
+            timeout: self.timeout,
+            blackouts: self.blackouts,
+        }
+    }
+}
+
+impl Settings {
+    /// Returns the blackout window `at` falls in, if any.
+    pub(crate) fn blackout_at(&self, at: DateTime<Utc>) -> Option<&BlackoutWindow> {
+        let local = at.with_timezone(&self.timezone);
+        self.blackouts.iter().find(|window| window.contains(&local))
+    }
+}
@@ -0,0 +1,320 @@
+//! # Overlap Policy
+//!
+//! This module decides what happens when a job is due while its previous run is still in
+//! progress, e.g. an update that takes longer than its schedule interval. Runs execute on
+//! tokio's blocking pool, and state is shared by every job registered under the same name,
+//! so two registrations of `auto-update` never run SteamCMD at the same time.
+use crate::metrics::{record_run, record_skip};
+use crate::registry::JobResult;
+use crate::timeout::with_cancellation;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as RunLock;
+use tracing::{Instrument, Span, debug, error, info, warn};
+
+/// The closure run by a scheduled job. An error marks the run as failed.
+pub type Job = Arc<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+/// What to do when a job is due while a previous run of it is still in progress.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Drop the new run. This is the default.
+    #[default]
+    Skip,
+    /// Run once the previous run finishes. At most one run waits; further triggers
+    /// while one is waiting are dropped.
+    Queue,
+    /// Start the new run immediately, alongside the previous one.
+    Concurrent,
+}
+
+static NAMED_JOBS: LazyLock<Mutex<HashMap<String, Arc<JobState>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Counters describing the runs of one job so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_result: Option<JobResult>,
+    pub last_duration: Option<Duration>,
+    pub run_count: u64,
+    pub failure_count: u64,
+    pub skipped_count: u64,
+}
+
+/// Run coordination shared by every trigger of one job.
+#[derive(Debug, Default)]
+pub struct JobState {
+    run_lock: Arc<RunLock<()>>,
+    queued: AtomicBool,
+    paused: AtomicBool,
+    stats: Mutex<RunStats>,
+}
+
+impl JobState {
+    /// Returns the state shared by all jobs registered as `name`.
+    pub fn named(name: &str) -> Arc<Self> {
+        let mut jobs = NAMED_JOBS.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(jobs.entry(name.to_owned()).or_default())
+    }
+
+    /// Returns whether a run is currently in progress.
+    pub fn is_busy(&self) -> bool {
+        self.run_lock.try_lock().is_err()
+    }
+
+    /// Returns whether triggers are currently being skipped.
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Skips every trigger while `paused` is set. Runs already in progress finish.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    /// Returns a snapshot of the run counters.
+    pub(crate) fn stats(&self) -> RunStats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn update_stats(&self, update: impl FnOnce(&mut RunStats)) {
+        update(&mut self.stats.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Runs `job` on tokio's blocking pool and records its outcome. A panic is logged and
+    /// recorded rather than propagated, so one bad run does not end the schedule.
+    ///
+    /// With a `timeout`, a run still going when it expires is recorded as timed out and
+    /// abandoned: the caller stops waiting for it and its cancellation flag is raised.
+    async fn run(&self, label: &str, job: Job, timeout: Option<Duration>) {
+        self.update_stats(|stats| stats.last_run = Some(Utc::now()));
+        let started = Instant::now();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let task = {
+            let cancelled = Arc::clone(&cancelled);
+            let label = label.to_owned();
+            let span = Span::current();
+            tokio::task::spawn_blocking(move || {
+                let _entered = span.enter();
+                with_cancellation(cancelled, || run_blocking(&label, &job))
+            })
+        };
+        let joined = match timeout {
+            Some(limit) => tokio::time::timeout(limit, task).await.unwrap_or_else(|_| {
+                cancelled.store(true, Ordering::SeqCst);
+                warn!("Run of {label} exceeded its {limit:?} timeout; no longer waiting for it");
+                Ok(JobResult::TimedOut)
+            }),
+            None => task.await,
+        };
+        let result = joined.unwrap_or_else(|e| JobResult::Failed(e.to_string()));
+        let duration = started.elapsed();
+        record_run(&result, duration);
+        self.update_stats(|stats| {
+            stats.run_count += 1;
+            if result != JobResult::Succeeded {
+                stats.failure_count += 1;
+            }
+            stats.last_duration = Some(duration);
+            stats.last_result = Some(result);
+        });
+    }
+
+    /// Records a run of `label` that was not started, and why.
+    pub(crate) fn skip(&self, label: &str, reason: &str) {
+        info!("Skipping run of {label}: {reason}");
+        record_skip();
+        self.update_stats(|stats| stats.skipped_count += 1);
+    }
+
+    /// Starts a run of `job` according to `policy`, giving up on it after `timeout`.
+    ///
+    /// The run executes in the span current at the time of the call.
+    pub fn trigger(
+        self: &Arc<Self>,
+        label: &str,
+        policy: OverlapPolicy,
+        timeout: Option<Duration>,
+        job: &Job,
+    ) {
+        if self.is_paused() {
+            self.skip(label, "the job is paused");
+            return;
+        }
+        let job = Arc::clone(job);
+        let state = Arc::clone(self);
+        let label = label.to_owned();
+        let span = Span::current();
+        match policy {
+            OverlapPolicy::Concurrent => {
+                tokio::spawn(async move { state.run(&label, job, timeout).await }.instrument(span));
+            }
+            OverlapPolicy::Skip => {
+                if let Ok(guard) = Arc::clone(&self.run_lock).try_lock_owned() {
+                    tokio::spawn(
+                        async move {
+                            state.run(&label, job, timeout).await;
+                            drop(guard);
+                        }
+                        .instrument(span),
+                    );
+                } else {
+                    self.skip(&label, "the previous run is still in progress");
+                }
+            }
+            OverlapPolicy::Queue => {
+                if self.queued.swap(true, Ordering::SeqCst) {
+                    self.skip(&label, "a run is already queued");
+                    return;
+                }
+                if self.is_busy() {
+                    info!("Queueing run of {label} until the previous run finishes");
+                }
+                tokio::spawn(
+                    async move {
+                        let guard = Arc::clone(&state.run_lock).lock_owned().await;
+                        state.queued.store(false, Ordering::SeqCst);
+                        debug!("Starting queued run of {label}");
+                        state.run(&label, job, timeout).await;
+                        drop(guard);
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+    }
+}
+
+/// Runs `job` on the current thread, catching panics.
+fn run_blocking(label: &str, job: &Job) -> JobResult {
+    match panic::catch_unwind(AssertUnwindSafe(|| job())) {
+        Ok(Ok(())) => JobResult::Succeeded,
+        Ok(Err(error)) => {
+            error!("Run of {label} failed: {error}");
+            JobResult::Failed(error)
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| (*message).to_owned())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            error!("Run of {label} panicked: {message}");
+            JobResult::Panicked(message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    fn slow_job(runs: &Arc<AtomicUsize>) -> Job {
+        let runs = Arc::clone(runs);
+        Arc::new(move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+            Ok(())
+        })
+    }
+
+    async fn trigger_three_times(policy: OverlapPolicy) -> usize {
+        let state = Arc::new(JobState::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job = slow_job(&runs);
+        for _ in 0..3 {
+            state.trigger("test", policy, None, &job);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        runs.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn skip_drops_overlapping_runs() {
+        assert_eq!(trigger_three_times(OverlapPolicy::Skip).await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_are_counted_and_panics_recorded() {
+        let state = JobState::default();
+        let job: Job = Arc::new(|| panic!("boom"));
+        state.run("test", job, None).await;
+
+        let counters = state.stats();
+        assert_eq!(counters.run_count, 1);
+        assert_eq!(counters.failure_count, 1);
+        assert!(counters.last_duration.is_some());
+        assert!(counters.last_run.is_some());
+        assert_eq!(
+            counters.last_result,
+            Some(JobResult::Panicked("boom".to_owned()))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn timed_out_runs_are_recorded_and_release_the_lock() {
+        let state = Arc::new(JobState::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job = slow_job(&runs);
+        let timeout = Some(Duration::from_millis(20));
+        state.trigger("test", OverlapPolicy::Skip, timeout, &job);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(state.stats().last_result, Some(JobResult::TimedOut));
+        assert!(!state.is_busy());
+        state.trigger("test", OverlapPolicy::Skip, timeout, &job);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn queue_runs_one_more_after_the_current_run() {
+        assert_eq!(trigger_three_times(OverlapPolicy::Queue).await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_runs_every_trigger() {
+        assert_eq!(trigger_three_times(OverlapPolicy::Concurrent).await, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn paused_jobs_skip_triggers_until_resumed() {
+        let state = Arc::new(JobState::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job = slow_job(&runs);
+
+        state.set_paused(true);
+        state.trigger("test", OverlapPolicy::Concurrent, None, &job);
+        state.set_paused(false);
+        state.trigger("test", OverlapPolicy::Concurrent, None, &job);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(state.stats().skipped_count, 1);
+    }
+
+    #[test]
+    fn named_state_is_shared() {
+        assert!(Arc::ptr_eq(
+            &JobState::named("shared"),
+            &JobState::named("shared")
+        ));
+        assert!(!Arc::ptr_eq(
+            &JobState::named("shared"),
+            &JobState::named("other")
+        ));
+    }
+}
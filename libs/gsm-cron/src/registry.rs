@@ -0,0 +1,200 @@
+//! # Job Registry
+//!
+//! This module keeps track of every job registered by name, so callers can ask what is
+//! scheduled, when it last ran and how that went, e.g. to back a `status` command. Jobs
+//! re-registered under the same name replace their previous entry.
+use crate::options::Settings;
+use crate::overlap::JobState;
+use crate::{BlackoutWindow, JobHandle, OverlapPolicy};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+use tracing::info;
+
+/// The outcome of a job's most recent run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobResult {
+    /// The run returned normally.
+    Succeeded,
+    /// The run returned the given error, after any retries.
+    Failed(String),
+    /// The run panicked with the given message.
+    Panicked(String),
+    /// The run exceeded its timeout and was abandoned.
+    TimedOut,
+}
+
+/// A point-in-time view of a registered job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobStatus {
+    /// The name the job was registered under.
+    pub name: String,
+    /// The normalized 6-field cron schedule.
+    pub schedule: String,
+    /// The timezone the schedule is evaluated in.
+    pub timezone: Tz,
+    /// What happens when a run comes due while another is in progress.
+    pub overlap: OverlapPolicy,
+    /// The maximum random delay added to each run.
+    pub jitter: Duration,
+    /// How long a run may take before it is abandoned.
+    pub timeout: Option<Duration>,
+    /// Windows during which runs are skipped.
+    pub blackouts: Vec<BlackoutWindow>,
+    /// Whether the job is still scheduled.
+    pub scheduled: bool,
+    /// Whether a run is in progress right now.
+    pub running: bool,
+    /// Whether runs are being skipped; see [`JobRegistry::pause`].
+    pub paused: bool,
+    /// When the job will next run, if it is still scheduled.
+    pub next_run: Option<DateTime<Utc>>,
+    /// When the most recent run started.
+    pub last_run: Option<DateTime<Utc>>,
+    /// How the most recent finished run ended.
+    pub last_result: Option<JobResult>,
+    /// How long the most recent finished run took.
+    pub last_duration: Option<Duration>,
+    /// How many runs have finished.
+    pub run_count: u64,
+    /// How many finished runs failed, panicked or timed out.
+    pub failure_count: u64,
+    /// How many runs were dropped by the overlap policy, a pause or a blackout window.
+    pub skipped_count: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    schedule: String,
+    settings: Settings,
+    task: AbortHandle,
+    next_run: Arc<Mutex<Option<DateTime<Utc>>>>,
+    state: Arc<JobState>,
+}
+
+impl Entry {
+    fn status(&self, name: &str) -> JobStatus {
+        let scheduled = !self.task.is_finished();
+        let stats = self.state.stats();
+        JobStatus {
+            name: name.to_owned(),
+            schedule: self.schedule.clone(),
+            timezone: self.settings.timezone,
+            overlap: self.settings.overlap,
+            jitter: self.settings.jitter,
+            timeout: self.settings.timeout,
+            blackouts: self.settings.blackouts.clone(),
+            scheduled,
+            running: self.state.is_busy(),
+            paused: self.state.is_paused(),
+            next_run: if scheduled {
+                *self.next_run.lock().unwrap_or_else(PoisonError::into_inner)
+            } else {
+                None
+            },
+            last_run: stats.last_run,
+            last_result: stats.last_result,
+            last_duration: stats.last_duration,
+            run_count: stats.run_count,
+            failure_count: stats.failure_count,
+            skipped_count: stats.skipped_count,
+        }
+    }
+}
+
+/// The set of jobs registered by name.
+///
+/// Jobs registered through [`register_job`](crate::register_job) and its variants are
+/// recorded in [`JobRegistry::global`]; anonymous jobs from
+/// [`spawn_scheduled_job`](crate::spawn_scheduled_job) are not.
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    jobs: Mutex<BTreeMap<String, Entry>>,
+}
+
+static GLOBAL: LazyLock<JobRegistry> = LazyLock::new(JobRegistry::default);
+
+impl JobRegistry {
+    /// The registry every named job is recorded in.
+    pub fn global() -> &'static Self {
+        &GLOBAL
+    }
+
+    /// Records `handle` as the job named `name`.
+    pub(crate) fn insert(
+        &self,
+        name: &str,
+        schedule: &str,
+        settings: Settings,
+        handle: &JobHandle,
+        state: Arc<JobState>,
+    ) {
+        let (task, next_run) = handle.shared_parts();
+        let entry = Entry {
+            schedule: schedule.to_owned(),
+            settings,
+            task,
+            next_run,
+            state,
+        };
+        self.jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_owned(), entry);
+    }
+
+    /// Returns the status of every registered job, ordered by name.
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(name, entry)| entry.status(name))
+            .collect()
+    }
+
+    /// Returns the status of the job registered as `name`, if any.
+    pub fn get(&self, name: &str) -> Option<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .map(|entry| entry.status(name))
+    }
+
+    /// Stops starting runs of the job registered as `name` until it is resumed, e.g.
+    /// to hold scheduled restarts during maintenance. A run already in progress
+    /// finishes, and the job stays scheduled. The pause also applies if the job is
+    /// registered again under the same name.
+    ///
+    /// Returns `false` if no job is registered as `name`.
+    pub fn pause(&self, name: &str) -> bool {
+        self.set_paused(name, true)
+    }
+
+    /// Resumes a job paused with [`JobRegistry::pause`]. Runs missed while paused are
+    /// not made up; the job next runs at its next scheduled time.
+    ///
+    /// Returns `false` if no job is registered as `name`.
+    pub fn resume(&self, name: &str) -> bool {
+        self.set_paused(name, false)
+    }
+
+    fn set_paused(&self, name: &str, paused: bool) -> bool {
+        let state = self
+            .jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .map(|entry| Arc::clone(&entry.state));
+        let Some(state) = state else {
+            return false;
+        };
+        state.set_paused(paused);
+        info!("Job '{name}' {}", if paused { "paused" } else { "resumed" });
+        true
+    }
+}
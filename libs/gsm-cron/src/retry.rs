@@ -0,0 +1,220 @@
+//! # Retries
+//!
+//! This module retries fallible jobs. A run that returns an error is attempted again after
+//! a delay, growing by a backoff factor each time, before it is given up on and reported
+//! through the job's failure callback. Retries happen within the run, so scheduled runs
+//! that come due meanwhile are handled by the job's [`OverlapPolicy`](crate::OverlapPolicy).
+use crate::overlap::Job;
+use crate::timeout::cancellation_requested;
+use gsm_shared::{fetch_duration, fetch_var};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// How often and how soon a failed run is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first failed attempt; `0` disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry.
+    pub delay: Duration,
+    /// Factor the delay is multiplied by after each retry; `1` keeps it constant.
+    pub backoff_factor: u32,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub const NONE: Self = Self {
+        max_retries: 0,
+        delay: Duration::ZERO,
+        backoff_factor: 1,
+    };
+
+    /// Retries up to `max_retries` times, waiting `delay` between attempts.
+    pub const fn new(max_retries: u32, delay: Duration) -> Self {
+        Self {
+            max_retries,
+            delay,
+            backoff_factor: 1,
+        }
+    }
+
+    /// Reads `<PREFIX>_MAX_RETRIES`, `<PREFIX>_RETRY_DELAY` (e.g. `10` seconds or `2m`)
+    /// and `<PREFIX>_RETRY_BACKOFF` from the environment, using `default` for any that
+    /// are unset or invalid.
+    pub fn from_env(prefix: &str, default: Self) -> Self {
+        fn read<T: std::str::FromStr>(name: &str, default: T) -> T {
+            let value = fetch_var(name, "");
+            if value.is_empty() {
+                return default;
+            }
+            value.trim().parse().unwrap_or_else(|_| {
+                warn!("Ignoring invalid {name}='{value}'");
+                default
+            })
+        }
+
+        Self {
+            max_retries: read(&format!("{prefix}_MAX_RETRIES"), default.max_retries),
+            delay: fetch_duration(&format!("{prefix}_RETRY_DELAY"), default.delay),
+            backoff_factor: read(&format!("{prefix}_RETRY_BACKOFF"), default.backoff_factor),
+        }
+    }
+
+    /// Multiplies the delay by `factor` after each retry.
+    #[must_use]
+    pub const fn with_backoff(mut self, factor: u32) -> Self {
+        self.backoff_factor = factor;
+        self
+    }
+
+    /// The delay before retry number `retry`, counting from 1.
+    pub fn delay_before(&self, retry: u32) -> Duration {
+        let factor = self
+            .backoff_factor
+            .max(1)
+            .saturating_pow(retry.saturating_sub(1));
+        self.delay.saturating_mul(factor)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Details of a job that failed every attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobFailure {
+    /// The name the job was registered under.
+    pub name: String,
+    /// How many attempts were made, including the first.
+    pub attempts: u32,
+    /// The error returned by the last attempt.
+    pub error: String,
+}
+
+/// Callback invoked when a job has failed every attempt.
+pub type FailureCallback = Arc<dyn Fn(&JobFailure) + Send + Sync>;
+
+/// Wraps `job` so each run is retried according to `policy`, calling `on_failure` once
+/// the retries are exhausted.
+pub fn with_retries<F, E>(
+    name: &str,
+    policy: RetryPolicy,
+    on_failure: FailureCallback,
+    job: F,
+) -> Job
+where
+    F: Fn() -> Result<(), E> + Send + Sync + 'static,
+    E: std::fmt::Display,
+{
+    let name = name.to_owned();
+    Arc::new(move || {
+        let mut attempts = 1;
+        loop {
+            let error = match job() {
+                Ok(()) => return Ok(()),
+                Err(e) => e.to_string(),
+            };
+            if attempts > policy.max_retries {
+                let failure = JobFailure {
+                    name: name.clone(),
+                    attempts,
+                    error: error.clone(),
+                };
+                on_failure(&failure);
+                return Err(error);
+            }
+            let delay = policy.delay_before(attempts);
+            warn!(
+                "Job '{name}' failed (attempt {attempts} of {}): {error}; retrying in {}s",
+                policy.max_retries + 1,
+                delay.as_secs()
+            );
+            thread::sleep(delay);
+            if cancellation_requested() {
+                warn!("Job '{name}' timed out; not retrying");
+                return Err(error);
+            }
+            attempts += 1;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn backoff_multiplies_the_delay() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(10)).with_backoff(2);
+        assert_eq!(policy.delay_before(1), Duration::from_secs(10));
+        assert_eq!(policy.delay_before(2), Duration::from_secs(20));
+        assert_eq!(policy.delay_before(3), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn from_env_overrides_defaults() {
+        let default = RetryPolicy::new(2, Duration::from_mins(10));
+        unsafe {
+            std::env::set_var("TEST_RETRY_MAX_RETRIES", "5");
+            std::env::set_var("TEST_RETRY_RETRY_BACKOFF", "not-a-number");
+        }
+        let policy = RetryPolicy::from_env("TEST_RETRY", default);
+        assert_eq!(policy.max_retries, 5);
+        assert_eq!(policy.delay, Duration::from_mins(10));
+        assert_eq!(policy.backoff_factor, 1);
+        unsafe {
+            std::env::remove_var("TEST_RETRY_MAX_RETRIES");
+            std::env::remove_var("TEST_RETRY_RETRY_BACKOFF");
+        }
+    }
+
+    #[test]
+    fn succeeds_once_a_retry_works() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let job = with_retries(
+            "flaky",
+            RetryPolicy::new(2, Duration::ZERO),
+            Arc::new(|failure: &JobFailure| unreachable!("reported {failure:?}")),
+            move || {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err("first attempt fails")
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert!(job().is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn reports_failure_after_exhausting_retries() {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&failures);
+        let job = with_retries(
+            "broken",
+            RetryPolicy::new(2, Duration::ZERO),
+            Arc::new(move |failure: &JobFailure| recorded.lock().unwrap().push(failure.clone())),
+            || Err("still broken"),
+        );
+        assert_eq!(job(), Err("still broken".to_owned()));
+        assert_eq!(
+            *failures.lock().unwrap(),
+            vec![JobFailure {
+                name: "broken".to_owned(),
+                attempts: 3,
+                error: "still broken".to_owned(),
+            }]
+        );
+    }
+}
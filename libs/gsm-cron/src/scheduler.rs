@@ -0,0 +1,151 @@
+//! # Scheduler Loop
+//!
+//! This module drives one job's schedule. Rather than precomputing every fire time and
+//! sleeping until each, it works out the next fire time after every wake and sleeps in
+//! short steps, re-reading the wall clock between them. That keeps runs on time across
+//! suspends, NTP corrections and daylight saving changes:
+//!
+//! - when the clock jumps forward past several fire times, the job runs once, late, and
+//!   continues from the next time still in the future instead of running repeatedly;
+//! - when the clock jumps backward, a time that already fired is not fired again.
+use crate::jitter::random_delay;
+use crate::metrics::run_span;
+use crate::options::Settings;
+use crate::overlap::{Job, JobState};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+/// Longest single sleep before the wall clock is checked again.
+const MAX_SLEEP: Duration = Duration::from_mins(1);
+
+/// Disagreement between wall-clock and monotonic time treated as a clock jump.
+const CLOCK_JUMP_TOLERANCE: TimeDelta = TimeDelta::seconds(30);
+
+/// Returns the first fire time of `schedule` strictly after `after`.
+pub fn next_fire(schedule: &Schedule, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|next| next.with_timezone(&Utc))
+}
+
+/// Sleeps until the wall clock reaches `fire_at`, in steps of at most [`MAX_SLEEP`].
+async fn sleep_until(label: &str, fire_at: DateTime<Utc>) {
+    loop {
+        let before = Utc::now();
+        let Ok(remaining) = (fire_at - before).to_std() else {
+            return;
+        };
+        if remaining.is_zero() {
+            return;
+        }
+        let step = remaining.min(MAX_SLEEP);
+        let started = Instant::now();
+        sleep(step).await;
+
+        let wall = Utc::now() - before;
+        let monotonic = TimeDelta::from_std(started.elapsed()).unwrap_or(TimeDelta::MAX);
+        let drift = wall - monotonic;
+        if drift.abs() > CLOCK_JUMP_TOLERANCE {
+            warn!(
+                "System clock jumped by {}s while waiting to run {label}; rechecking",
+                drift.num_seconds()
+            );
+        }
+    }
+}
+
+/// Starts the run of `job` due at `scheduled`, unless it falls in a blackout window.
+pub fn start_run(
+    label: &str,
+    settings: &Settings,
+    state: &Arc<JobState>,
+    job: &Job,
+    scheduled: DateTime<Utc>,
+) {
+    run_span(label, scheduled).in_scope(|| match settings.blackout_at(Utc::now()) {
+        Some(window) => state.skip(label, &format!("it is inside blackout window {window}")),
+        None => state.trigger(label, settings.overlap, settings.timeout, job),
+    });
+}
+
+/// Runs `job` on `schedule` until the task is aborted, keeping `next_run` current.
+pub async fn run_schedule(
+    schedule: Schedule,
+    settings: Settings,
+    label: String,
+    state: Arc<JobState>,
+    job: Job,
+    next_run: Arc<Mutex<Option<DateTime<Utc>>>>,
+) {
+    let mut after = Utc::now();
+    while let Some(scheduled) = next_fire(&schedule, settings.timezone, after) {
+        let fire_at = scheduled + random_delay(settings.jitter);
+        *next_run.lock().unwrap_or_else(PoisonError::into_inner) = Some(fire_at);
+        sleep_until(&label, fire_at).await;
+
+        let now = Utc::now();
+        let late = now - fire_at;
+        if late > CLOCK_JUMP_TOLERANCE {
+            warn!(
+                "Running {label} {}s late (scheduled for {fire_at})",
+                late.num_seconds()
+            );
+        } else {
+            debug!("Woke up at: {:?} for scheduled time: {:?}", now, fire_at);
+        }
+        start_run(&label, &settings, &state, &job, fire_at);
+
+        // Continue from now, so fire times skipped by a forward jump do not bunch up,
+        // but never from before this fire time, so a backward jump cannot repeat it.
+        after = now.max(scheduled);
+    }
+    *next_run.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 5, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn a_forward_jump_resumes_from_the_next_future_time() {
+        let hourly = Schedule::from_str("0 0 * * * *").unwrap();
+        // Fired 01:00, then the clock jumped to 04:30: 02:00-04:00 are not run.
+        let after = at(4, 30).max(at(1, 0));
+        assert_eq!(next_fire(&hourly, Tz::UTC, after), Some(at(5, 0)));
+    }
+
+    #[test]
+    fn a_backward_jump_does_not_repeat_a_fire_time() {
+        let hourly = Schedule::from_str("0 0 * * * *").unwrap();
+        // Fired 03:00, then the clock was set back to 02:10.
+        let after = at(2, 10).max(at(3, 0));
+        assert_eq!(next_fire(&hourly, Tz::UTC, after), Some(at(4, 0)));
+    }
+
+    #[test]
+    fn next_fire_is_strictly_after() {
+        let hourly = Schedule::from_str("0 0 * * * *").unwrap();
+        assert_eq!(next_fire(&hourly, Tz::UTC, at(3, 0)), Some(at(4, 0)));
+    }
+
+    #[tokio::test]
+    async fn sleep_until_a_past_time_returns_immediately() {
+        let started = Instant::now();
+        sleep_until("test", at(0, 0)).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+}
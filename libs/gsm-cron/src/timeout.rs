@@ -0,0 +1,64 @@
+//! # Timeouts
+//!
+//! This module lets a job check whether its run has been given up on. A blocking closure
+//! cannot be aborted from outside, so when a run exceeds its
+//! [`JobOptions::timeout`](crate::JobOptions::timeout) the scheduler records it as timed
+//! out, stops waiting for it, and raises a flag the closure can poll with
+//! [`cancellation_requested`] to stop early.
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    static CANCELLED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Returns whether the run executing on this thread has exceeded its timeout.
+///
+/// Long-running jobs should check this between steps and return early when it is set.
+/// Outside a scheduled run it is always `false`.
+///
+/// # Example
+///
+/// ```rust
+/// use gsm_cron::cancellation_requested;
+///
+/// for step in ["stop", "update", "start"] {
+///     if cancellation_requested() {
+///         return;
+///     }
+///     println!("{step}");
+/// }
+/// ```
+pub fn cancellation_requested() -> bool {
+    CANCELLED.with(|flag| {
+        flag.borrow()
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::SeqCst))
+    })
+}
+
+/// Runs `f` with `flag` as the cancellation flag of the current thread.
+pub fn with_cancellation<T>(flag: Arc<AtomicBool>, f: impl FnOnce() -> T) -> T {
+    CANCELLED.with(|current| current.replace(Some(flag)));
+    let result = f();
+    CANCELLED.with(|current| current.replace(None));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_is_visible_only_inside_the_scope() {
+        let flag = Arc::new(AtomicBool::new(false));
+        assert!(!cancellation_requested());
+        with_cancellation(Arc::clone(&flag), || {
+            assert!(!cancellation_requested());
+            flag.store(true, Ordering::SeqCst);
+            assert!(cancellation_requested());
+        });
+        assert!(!cancellation_requested());
+    }
+}
@@ -0,0 +1,66 @@
+//! # Timezones
+//!
+//! This module picks the timezone schedules are evaluated in. Schedules registered without
+//! an explicit timezone use `CRON_TIMEZONE` (an IANA name such as `Europe/Berlin`), so
+//! "restart at 4am" can mean 4am local time. Daylight saving transitions follow the zone's
+//! rules, so runs stay at the configured wall-clock time when the UTC offset changes.
+use chrono_tz::Tz;
+use tracing::warn;
+
+/// Environment variable naming the default timezone for schedules.
+pub const CRON_TIMEZONE: &str = "CRON_TIMEZONE";
+
+/// Returns the timezone named by `CRON_TIMEZONE`, or UTC when it is unset or invalid.
+pub fn default_timezone() -> Tz {
+    match std::env::var(CRON_TIMEZONE) {
+        Ok(name) if !name.trim().is_empty() => name.trim().parse().unwrap_or_else(|e| {
+            warn!("Ignoring {CRON_TIMEZONE}='{name}': {e}; schedules will use UTC");
+            Tz::UTC
+        }),
+        _ => Tz::UTC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::{TimeZone, Timelike, Utc};
+    use cron::Schedule;
+    use std::str::FromStr;
+
+    #[test]
+    fn default_timezone_reads_env_and_falls_back_to_utc() {
+        unsafe {
+            std::env::set_var(CRON_TIMEZONE, "America/New_York");
+        }
+        assert_eq!(default_timezone(), Tz::America__New_York);
+
+        unsafe {
+            std::env::set_var(CRON_TIMEZONE, "Mars/Olympus_Mons");
+        }
+        assert_eq!(default_timezone(), Tz::UTC);
+
+        unsafe {
+            std::env::remove_var(CRON_TIMEZONE);
+        }
+        assert_eq!(default_timezone(), Tz::UTC);
+    }
+
+    #[test]
+    fn local_schedule_keeps_wall_clock_time_across_dst() {
+        let schedule = Schedule::from_str("0 0 4 * * *").unwrap();
+        let tz = Tz::America__New_York;
+        // The US springs forward on 2026-03-08.
+        let start = tz.with_ymd_and_hms(2026, 3, 6, 12, 0, 0).unwrap();
+
+        let runs: Vec<_> = schedule
+            .after(&start)
+            .take(3)
+            .map(|run| run.with_timezone(&Utc))
+            .collect();
+        let utc_hours: Vec<u32> = runs.iter().map(Timelike::hour).collect();
+        assert_eq!(utc_hours, vec![9, 8, 8]);
+    }
+}
@@ -0,0 +1,1175 @@
+//! # Command-Line Interface
+//!
+//! This module implements the subcommands every game binary offers: `install`, `start`,
+//! `monitor`, `stop`, `restart`, `update`, `export`, `import` and `systemd-unit`, including the auto-update and scheduled
+//! restart jobs `monitor` runs. A game's `main` supplies its [`InstanceConfig`] and the
+//! game-specific parts, such as config files written after install and log rules, as
+//! hooks on [`CliCustomizations`].
+//!
+//! Unless a game overrides them, the webhook in `WEBHOOK_URL` is told when the server
+//! stops, updates, is about to restart or hangs, when a backup runs, and when a scheduled
+//! job fails.
+//!
+//! The commands that run or change the server load the plugins in `GSM_PLUGIN_DIR` (see
+//! [`gsm_plugins`]), forward them lifecycle events and, under `monitor`, log lines, and
+//! shut them down when the command finishes.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::cli::{self, CliCustomizations};
+//! use std::process::ExitCode;
+//!
+//! #[tokio::main]
+//! async fn main() -> ExitCode {
+//!     let customizations = CliCustomizations::new("mygame", "Manage My Game Server")
+//!         .with_after_install(|path| println!("Installed to {}", path.display()));
+//!     cli::run(InstanceConfig::default(), customizations).await
+//! }
+//! ```
+use crate::config::InstanceConfig;
+use crate::dry_run::DRY_RUN;
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use crate::lifecycle::LifecycleEvent;
+use crate::maintenance::{EmptyWait, max_defer_from_env};
+use crate::restart::{RestartPlan, shell_broadcast};
+use crate::saves::{SaveGlobs, SaveLocator};
+use crate::shutdown::StopOutcome;
+use crate::systemd::SystemdUnit;
+use crate::update::UpdateStatus;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use gsm_backup::{BackupEvent, run_scheduled_backup};
+use gsm_cron::{
+    BlackoutWindow, ChildRegistry, CronError, JobFailure, JobOptions, RetryPolicy, begin_cron_loop,
+    register_fallible_job, register_job, register_job_with_options, validate_schedule,
+};
+use gsm_monitor::{GameEvent, LogRules, MonitorWatchdog};
+use gsm_notifications::notifications::{StandardServerEvents, notify, send_update_notification};
+use gsm_plugins::{LifecycleEvent as PluginEvent, PluginHost};
+use gsm_shared::ddns::{DEFAULT_DDNS_SCHEDULE, DdnsUpdater};
+use gsm_shared::logging::command_span;
+use gsm_shared::{
+    DEFAULT_STAGING_MAX_AGE, EnvValidator, VarSpec, clean_orphaned_staging, fetch_duration_in,
+    fetch_var, is_env_var_truthy, validate_env, validate_flag,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+use tracing::{Instrument, debug, error, info, warn};
+
+/// Retries for a failed auto-update unless overridden by `AUTO_UPDATE_MAX_RETRIES`,
+/// `AUTO_UPDATE_RETRY_DELAY` (seconds) and `AUTO_UPDATE_RETRY_BACKOFF`.
+pub const AUTO_UPDATE_RETRY: RetryPolicy = RetryPolicy::new(2, Duration::from_mins(10));
+
+/// When the `auto-backup` job runs unless `AUTO_BACKUP_SCHEDULE` is set: every six hours.
+pub const DEFAULT_AUTO_BACKUP_SCHEDULE: &str = "0 */6 * * *";
+
+/// Where the `auto-backup` job writes its archives unless `BACKUP_DIR` is set.
+pub const DEFAULT_BACKUP_DIR: &str = "/home/steam/backups";
+
+/// The arguments of a game binary.
+#[derive(Parser, Debug)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+    /// Log the SteamCMD and launch commands and stop signals instead of running them.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+/// The subcommands of a game binary.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Commands {
+    /// Install the server with SteamCMD
+    Install {
+        /// Install here instead of the configured working directory.
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+    /// Start the server only (without monitoring jobs)
+    Start {
+        /// Keep the server attached, streaming its output, until it exits.
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Monitor the server: watch its logs and run scheduled jobs.
+    Monitor {
+        /// Run the auto-update job even if `AUTO_UPDATE` is unset.
+        #[arg(long)]
+        update_job: bool,
+        /// Run the scheduled restart job even if `SCHEDULED_RESTART` is unset.
+        #[arg(long)]
+        restart_job: bool,
+    },
+    /// Stop the server
+    Stop,
+    /// Restart the server
+    Restart,
+    /// Update the server if an update is available
+    Update {
+        /// Only check for an update; exits 1 if one is available or the check fails.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Export the configuration, installed build and saves to an archive
+    Export {
+        /// The `.tar.gz` to write.
+        path: PathBuf,
+    },
+    /// Install the server and restore the saves from an exported archive
+    Import {
+        /// The `.tar.gz` written by `export`.
+        path: PathBuf,
+    },
+    /// Print a systemd unit that installs and runs the server
+    SystemdUnit {
+        /// Run the server as this user instead of root.
+        #[arg(long)]
+        user: Option<String>,
+        /// Read the game's environment variables from this file.
+        #[arg(long)]
+        env_file: Option<PathBuf>,
+    },
+}
+
+impl Commands {
+    /// Returns the subcommand's name, as typed on the command line.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Install { .. } => "install",
+            Self::Start { .. } => "start",
+            Self::Monitor { .. } => "monitor",
+            Self::Stop => "stop",
+            Self::Restart => "restart",
+            Self::Update { .. } => "update",
+            Self::Export { .. } => "export",
+            Self::Import { .. } => "import",
+            Self::SystemdUnit { .. } => "systemd-unit",
+        }
+    }
+}
+
+type PathHook = Box<dyn Fn(&Path) + Send + Sync>;
+type InstanceHook = Box<dyn Fn(&Instance) + Send + Sync>;
+type MonitorHook = Box<dyn FnOnce(&Path) -> Result<(), String> + Send + Sync>;
+type LogRulesHook = Box<dyn FnOnce() -> Result<LogRules, String> + Send + Sync>;
+type UpdateHook = Arc<dyn Fn(&LifecycleEvent) + Send + Sync>;
+type FailureHook = Arc<dyn Fn(&JobFailure) + Send + Sync>;
+type BroadcastHook = Box<dyn Fn(&Instance, &str) -> Result<(), String> + Send + Sync>;
+
+/// The game-specific parts of a game binary's CLI.
+pub struct CliCustomizations {
+    name: &'static str,
+    about: &'static str,
+    version: Option<&'static str>,
+    after_install: Option<PathHook>,
+    before_start: Option<InstanceHook>,
+    before_stop: Option<InstanceHook>,
+    after_stop: Option<InstanceHook>,
+    on_monitor: Option<MonitorHook>,
+    log_rules: Option<LogRulesHook>,
+    on_update: Option<UpdateHook>,
+    on_job_failure: Option<FailureHook>,
+    broadcast: Option<BroadcastHook>,
+    saves: Option<Box<dyn SaveLocator + Send + Sync>>,
+    auto_backup: Option<PathBuf>,
+    env: Vec<VarSpec>,
+}
+
+impl CliCustomizations {
+    /// Creates customizations for the binary `name`, described by `about` in `--help`.
+    pub const fn new(name: &'static str, about: &'static str) -> Self {
+        Self {
+            name,
+            about,
+            version: None,
+            after_install: None,
+            before_start: None,
+            before_stop: None,
+            after_stop: None,
+            on_monitor: None,
+            log_rules: None,
+            on_update: None,
+            on_job_failure: None,
+            broadcast: None,
+            saves: None,
+            auto_backup: None,
+            env: Vec::new(),
+        }
+    }
+
+    /// Sets the version printed by `--version`.
+    #[must_use]
+    pub const fn with_version(mut self, version: &'static str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Calls `hook` with the install directory after a successful `install`, e.g. to
+    /// write a default config file.
+    #[must_use]
+    pub fn with_after_install(mut self, hook: impl Fn(&Path) + Send + Sync + 'static) -> Self {
+        self.after_install = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` before `start` launches the server.
+    #[must_use]
+    pub fn with_before_start(mut self, hook: impl Fn(&Instance) + Send + Sync + 'static) -> Self {
+        self.before_start = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` before `stop` signals the server, e.g. to warn players.
+    #[must_use]
+    pub fn with_before_stop(mut self, hook: impl Fn(&Instance) + Send + Sync + 'static) -> Self {
+        self.before_stop = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` after `stop` has stopped the server.
+    #[must_use]
+    pub fn with_after_stop(mut self, hook: impl Fn(&Instance) + Send + Sync + 'static) -> Self {
+        self.after_stop = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` with the working directory when `monitor` starts, before the
+    /// scheduled jobs are registered. Register game-specific jobs here; an error ends
+    /// `monitor` with a failure.
+    #[must_use]
+    pub fn with_on_monitor(
+        mut self,
+        hook: impl FnOnce(&Path) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.on_monitor = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` when `monitor` starts to build the rules matched against the server
+    /// logs, e.g. `LogRules::palworld(cli::notify_game_event)`; an error ends `monitor`
+    /// with a failure. Without one, the logs are watched with [`LogRules::default`].
+    #[must_use]
+    pub fn with_log_rules(
+        mut self,
+        hook: impl FnOnce() -> Result<LogRules, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.log_rules = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` with each step of an auto-update run by `monitor`. Without one, a
+    /// finished update is announced in-game and over the webhook.
+    #[must_use]
+    pub fn with_on_update(
+        mut self,
+        hook: impl Fn(&LifecycleEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_update = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a job run by `monitor` fails after its retries. Without one, the
+    /// failure is sent to the webhook.
+    #[must_use]
+    pub fn with_on_job_failure(
+        mut self,
+        hook: impl Fn(&JobFailure) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_job_failure = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` to broadcast each restart warning to players before a scheduled
+    /// restart; see [`Instance::restart_with_warning`]. Without one, warnings are sent
+    /// to the webhook and with [`shell_broadcast`].
+    #[must_use]
+    pub fn with_broadcast(
+        mut self,
+        hook: impl Fn(&Instance, &str) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.broadcast = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets where the game keeps its saves, for `export`. Without a locator, `export`
+    /// writes the configuration and build only.
+    #[must_use]
+    pub fn with_saves(mut self, locator: impl SaveLocator + Send + Sync + 'static) -> Self {
+        self.saves = Some(Box::new(locator));
+        self
+    }
+
+    /// Lets `monitor` back up `save_dir`, relative to the working directory, when
+    /// `AUTO_BACKUP` is set. Backups run on `AUTO_BACKUP_SCHEDULE` (default
+    /// [`DEFAULT_AUTO_BACKUP_SCHEDULE`]) into `BACKUP_DIR` (default [`DEFAULT_BACKUP_DIR`]),
+    /// and each one is reported to the webhook.
+    #[must_use]
+    pub fn with_auto_backup(mut self, save_dir: impl Into<PathBuf>) -> Self {
+        self.auto_backup = Some(save_dir.into());
+        self
+    }
+
+    /// Adds the game's own environment variables to those checked before any command
+    /// runs; see [`validate_env`].
+    #[must_use]
+    pub fn with_env(mut self, specs: impl IntoIterator<Item = VarSpec>) -> Self {
+        self.env.extend(specs);
+        self
+    }
+
+    fn command(&self) -> clap::Command {
+        let command = Cli::command().name(self.name).about(self.about);
+        match self.version {
+            Some(version) => command.version(version),
+            None => command,
+        }
+    }
+
+    /// Parses `args`, the first of which is the binary name.
+    ///
+    /// # Errors
+    ///
+    /// Returns a clap error for unknown subcommands or arguments, and for `--help`.
+    pub fn parse_from<I, T>(&self, args: I) -> Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Cli::from_arg_matches(&self.command().try_get_matches_from(args)?)
+    }
+}
+
+/// Parses the process arguments and runs the subcommand against `config`.
+///
+/// Invalid arguments print usage and exit, like any clap binary.
+pub async fn run(mut config: InstanceConfig, customizations: CliCustomizations) -> ExitCode {
+    let cli = customizations
+        .parse_from(std::env::args_os())
+        .unwrap_or_else(|e| e.exit());
+    config.dry_run |= cli.dry_run || is_env_var_truthy(DRY_RUN);
+    execute(config, cli.command, customizations).await
+}
+
+/// Runs `command` against `config`, after validating it.
+pub async fn execute(
+    config: InstanceConfig,
+    command: Commands,
+    customizations: CliCustomizations,
+) -> ExitCode {
+    debug!("Instance configuration set: {:?}", config);
+    let instance = Instance::new(config);
+    if let Err(e) = instance.validate_config() {
+        error!("{e}");
+        return ExitCode::FAILURE;
+    }
+    let specs: Vec<VarSpec> = env_specs()
+        .into_iter()
+        .chain(customizations.env.iter().copied())
+        .collect();
+    if let Err(e) = validate_env(&specs) {
+        error!("{e}");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = clean_orphaned_staging(&instance.config.working_dir, DEFAULT_STAGING_MAX_AGE) {
+        warn!("Failed to clean orphaned staging directories: {e}");
+    }
+
+    let plugins = Arc::new(load_plugins(
+        &command,
+        &instance.config,
+        customizations.name,
+    ));
+    let span = command_span(command.name());
+    let succeeded = dispatch(instance, command, customizations, Arc::clone(&plugins))
+        .instrument(span)
+        .await;
+    // Plugins get a grace period to exit, which must not stall the runtime.
+    if tokio::task::spawn_blocking(move || plugins.shutdown())
+        .await
+        .is_err()
+    {
+        warn!("Shutting down the plugins panicked");
+    }
+    if succeeded {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Loads the plugins for the commands that run or change the server, making their
+/// `plugin://` notification dispatchers available.
+fn load_plugins(command: &Commands, config: &InstanceConfig, game: &str) -> PluginHost {
+    let changes_server = matches!(
+        command,
+        Commands::Start { .. }
+            | Commands::Monitor { .. }
+            | Commands::Stop
+            | Commands::Restart
+            | Commands::Update { check: false }
+    );
+    if !changes_server || config.dry_run {
+        return PluginHost::default();
+    }
+    let plugins = PluginHost::from_env(game);
+    plugins.register_dispatchers();
+    plugins
+}
+
+/// The variables the CLI reads itself.
+fn env_specs() -> Vec<VarSpec> {
+    let schedule: EnvValidator = |value| validate_schedule(value).map_err(|e| e.to_string());
+    vec![
+        VarSpec::optional(DRY_RUN).with_validator(validate_flag),
+        VarSpec::optional("AUTO_UPDATE").with_validator(validate_flag),
+        VarSpec::optional("AUTO_UPDATE_SCHEDULE").with_validator(schedule),
+        VarSpec::optional("SCHEDULED_RESTART").with_validator(validate_flag),
+        VarSpec::optional("SCHEDULED_RESTART_SCHEDULE").with_validator(schedule),
+        VarSpec::optional("SCHEDULED_RESTART_SKIP_IF_PLAYERS").with_validator(validate_flag),
+        VarSpec::optional("AUTO_BACKUP").with_validator(validate_flag),
+        VarSpec::optional("AUTO_BACKUP_SCHEDULE").with_validator(schedule),
+        VarSpec::optional("BACKUP_DIR"),
+        VarSpec::optional("DDNS_SCHEDULE").with_validator(schedule),
+        VarSpec::optional("WEBHOOK_URL"),
+    ]
+}
+
+/// Runs `command` against the validated `instance`, returning whether it succeeded.
+async fn dispatch(
+    mut instance: Instance,
+    command: Commands,
+    customizations: CliCustomizations,
+    plugins: Arc<PluginHost>,
+) -> bool {
+    match command {
+        Commands::Install { path } => {
+            if let Some(path) = path {
+                instance.config.working_dir = path;
+            }
+            let path = instance.config.working_dir.clone();
+            info!("Installing {} server to: {:?}", customizations.name, path);
+            let report = instance.preflight();
+            for check in &report.checks {
+                info!("Preflight {check}");
+            }
+            if !report.is_ok() {
+                error!("Preflight checks failed; not installing");
+                return false;
+            }
+            match instance.install() {
+                Ok(()) if instance.config.dry_run => true,
+                Ok(()) => {
+                    if let Some(hook) = &customizations.after_install {
+                        hook(&path);
+                    }
+                    info!("{} server installed successfully", customizations.name);
+                    true
+                }
+                Err(e) => {
+                    error!("Installation failed: {e}");
+                    false
+                }
+            }
+        }
+        Commands::Start { foreground } => {
+            instance.config.daemonize &= !foreground;
+            start(&instance, &customizations, &plugins).await
+        }
+        Commands::Monitor {
+            update_job,
+            restart_job,
+        } => monitor(instance, update_job, restart_job, customizations, plugins).await,
+        Commands::Stop => stop(&instance, &customizations, &plugins).await,
+        Commands::Restart => {
+            warn!("Restarting {} server...", customizations.name);
+            plugins.emit_lifecycle(PluginEvent::Stopping);
+            instance
+                .restart()
+                .inspect(|()| plugins.emit_lifecycle(PluginEvent::Started))
+                .inspect_err(|e| error!("Failed to restart server: {e}"))
+                .is_ok()
+        }
+        Commands::Update { check } => update(&instance, check, &plugins).await,
+        Commands::Export { path } => {
+            let no_saves = SaveGlobs::new(Vec::<String>::new());
+            let locator: &dyn SaveLocator = match &customizations.saves {
+                Some(saves) => saves.as_ref(),
+                None => &no_saves,
+            };
+            instance
+                .export_state(&path, locator)
+                .inspect_err(|e| error!("Export failed: {e}"))
+                .is_ok()
+        }
+        Commands::SystemdUnit { user, env_file } => systemd_unit(&instance.config, user, env_file),
+        Commands::Import { path } => match instance.import_state(&path) {
+            Ok(_) if instance.config.dry_run => true,
+            Ok(_) => {
+                if let Some(hook) = &customizations.after_install {
+                    hook(&instance.config.working_dir);
+                }
+                info!(
+                    "{} server imported from {}",
+                    customizations.name,
+                    path.display()
+                );
+                true
+            }
+            Err(e) => {
+                error!("Import failed: {e}");
+                false
+            }
+        },
+    }
+}
+
+/// Prints a systemd unit running this binary with `config`'s working directory.
+fn systemd_unit(config: &InstanceConfig, user: Option<String>, env_file: Option<PathBuf>) -> bool {
+    let binary = match std::env::current_exe() {
+        Ok(binary) => binary,
+        Err(e) => {
+            error!("Cannot find this binary's path for the unit: {e}");
+            return false;
+        }
+    };
+    let mut unit = SystemdUnit::new(config, binary);
+    if let Some(user) = user {
+        unit = unit.with_user(user);
+    }
+    if let Some(env_file) = env_file {
+        unit = unit.with_environment_file(env_file);
+    }
+    print!("{unit}");
+    true
+}
+
+/// Starts the server, in the background or, without `daemonize`, in the foreground until
+/// it exits.
+async fn start(
+    instance: &Instance,
+    customizations: &CliCustomizations,
+    plugins: &PluginHost,
+) -> bool {
+    info!("Starting server...");
+    if let Some(hook) = &customizations.before_start
+        && !instance.config.dry_run
+    {
+        hook(instance);
+    }
+    plugins.emit_lifecycle(PluginEvent::Starting);
+    if instance.config.daemonize {
+        return match instance.start() {
+            Ok(_) => {
+                plugins.emit_lifecycle(PluginEvent::Started);
+                true
+            }
+            Err(InstanceError::DryRun(_)) => true,
+            Err(e) => {
+                error!("Failed to start server: {e}");
+                false
+            }
+        };
+    }
+    let _forwarding = instance.forward_ports();
+    match instance.run_foreground_async().await {
+        Ok(status) => {
+            info!("{} server exited with {status}", customizations.name);
+            if let Some(hook) = &customizations.after_stop {
+                hook(instance);
+            }
+            plugins.emit_lifecycle(PluginEvent::Stopped);
+            notify(StandardServerEvents::Stopped);
+            status.success()
+        }
+        Err(InstanceError::DryRun(_)) => true,
+        Err(e) => {
+            error!("Failed to run server: {e}");
+            false
+        }
+    }
+}
+
+/// Stops the server, calling the stop hooks around it.
+async fn stop(
+    instance: &Instance,
+    customizations: &CliCustomizations,
+    plugins: &PluginHost,
+) -> bool {
+    if let Some(hook) = &customizations.before_stop
+        && !instance.config.dry_run
+    {
+        hook(instance);
+    }
+    warn!("Stopping {} server...", customizations.name);
+    plugins.emit_lifecycle(PluginEvent::Stopping);
+    match instance.stop_async().await {
+        Ok(outcome @ StopOutcome::DryRun) => {
+            info!("Server {outcome}");
+            true
+        }
+        Ok(outcome) => {
+            info!("Server {outcome}");
+            if let Some(hook) = &customizations.after_stop {
+                hook(instance);
+            }
+            plugins.emit_lifecycle(PluginEvent::Stopped);
+            notify(StandardServerEvents::Stopped);
+            true
+        }
+        Err(e) => {
+            error!("Failed to stop: {e}");
+            false
+        }
+    }
+}
+
+/// Updates the server if an update is available. With `check`, only reports whether
+/// one is, failing if so or if it cannot be determined.
+async fn update(instance: &Instance, check: bool, plugins: &PluginHost) -> bool {
+    let status = match instance.update_available_async().await {
+        Ok(UpdateStatus::Unknown { reason }) => {
+            error!("Could not determine whether an update is available: {reason}");
+            return false;
+        }
+        Ok(status) => status,
+        Err(e) => {
+            error!("Update check failed: {e}");
+            return false;
+        }
+    };
+    if check {
+        if status.is_available() {
+            info!("Update available! ({status})");
+        } else {
+            info!("Server is up to date.");
+        }
+        !status.is_available()
+    } else if status.is_available() {
+        warn!("Update available ({status})! Updating...");
+        instance
+            .update_async()
+            .await
+            .inspect(|()| plugins.emit_lifecycle(PluginEvent::Updated))
+            .inspect_err(|e| error!("Update failed: {e}"))
+            .is_ok()
+    } else {
+        debug!("Server is up to date; no update needed.");
+        true
+    }
+}
+
+fn log_update_progress(event: &LifecycleEvent) {
+    match event {
+        LifecycleEvent::CheckingForUpdate => debug!("Checking for updates..."),
+        LifecycleEvent::UpToDate => debug!("No updates available during auto-update check."),
+        LifecycleEvent::UpdateStatusUnknown { reason } => {
+            warn!("Could not determine whether an update is available: {reason}");
+        }
+        LifecycleEvent::Stopping => warn!("Update available! Stopping server..."),
+        LifecycleEvent::Updating => info!("Updating server..."),
+        LifecycleEvent::Starting => info!("Restarting server..."),
+        LifecycleEvent::Updated { pid, build_id } => info!(
+            "Server updated to build {} (pid {pid})",
+            build_id.as_deref().unwrap_or("unknown")
+        ),
+        LifecycleEvent::RollingBack { reason } => {
+            warn!("Update failed ({reason}); rolling back to the previous build...");
+        }
+        LifecycleEvent::RolledBack { pid, build_id } => warn!(
+            "Server rolled back to build {} (pid {pid})",
+            build_id.as_deref().unwrap_or("unknown")
+        ),
+    }
+}
+
+/// The plugin lifecycle events for a step of an update.
+const fn plugin_events(event: &LifecycleEvent) -> &'static [PluginEvent] {
+    match event {
+        LifecycleEvent::Stopping => &[PluginEvent::Stopping],
+        // The server stays down while SteamCMD runs.
+        LifecycleEvent::Updating => &[PluginEvent::Stopped],
+        LifecycleEvent::Starting => &[PluginEvent::Starting],
+        LifecycleEvent::Updated { .. } => &[PluginEvent::Started, PluginEvent::Updated],
+        LifecycleEvent::RolledBack { .. } => &[PluginEvent::Started],
+        LifecycleEvent::CheckingForUpdate
+        | LifecycleEvent::UpToDate
+        | LifecycleEvent::UpdateStatusUnknown { .. }
+        | LifecycleEvent::RollingBack { .. } => &[],
+    }
+}
+
+/// Sends the webhook notification for an event recognized in the server logs.
+pub fn notify_game_event(event: GameEvent) {
+    notify(match event {
+        GameEvent::Started => StandardServerEvents::Started,
+        GameEvent::PlayerJoined(name) => StandardServerEvents::PlayerJoined(name),
+        GameEvent::PlayerLeft(name) => StandardServerEvents::PlayerLeft(name),
+    });
+}
+
+/// Supervises the log monitors and, when `LOG_QUIET_MINUTES` is set, warns through the
+/// webhook that the server might be hung once its log goes quiet for that long. Bare
+/// numbers are minutes, but any duration such as `90s` or `1h` works.
+fn log_watchdog() -> MonitorWatchdog {
+    let watchdog = MonitorWatchdog::default();
+    let quiet = fetch_duration_in("LOG_QUIET_MINUTES", Duration::ZERO, Duration::from_mins(1));
+    if quiet.is_zero() {
+        return watchdog;
+    }
+    watchdog.on_quiet(quiet, |path, quiet_for| {
+        notify(StandardServerEvents::Unresponsive {
+            log: path.display().to_string(),
+            quiet_for,
+        });
+    })
+}
+
+fn notify_job_failure(failure: &JobFailure) {
+    notify(StandardServerEvents::JobFailed {
+        job: failure.name.clone(),
+        error: failure.error.clone(),
+    });
+}
+
+/// Warns players of a scheduled restart over the webhook and, when set up,
+/// `RESTART_BROADCAST_COMMAND`.
+fn warn_players(instance: &Instance, message: &str) -> Result<(), String> {
+    notify(StandardServerEvents::RestartWarning(message.to_owned()));
+    shell_broadcast(&instance.config, message)
+}
+
+/// Announces a finished update in-game through `RESTART_BROADCAST_COMMAND` and over the
+/// webhook.
+fn announce_update(config: &InstanceConfig, event: &LifecycleEvent) {
+    if let LifecycleEvent::Updated { build_id, .. } = event
+        && let Err(e) = send_update_notification(
+            config.app_id,
+            build_id.clone().unwrap_or_default(),
+            |message| shell_broadcast(config, message),
+        )
+    {
+        warn!("Failed to send webhook notification: {e}");
+    }
+}
+
+/// Registers the game's hooks and the scheduled jobs, then runs them until the
+/// container is stopped.
+async fn monitor(
+    instance: Instance,
+    update_job: bool,
+    restart_job: bool,
+    customizations: CliCustomizations,
+    plugins: Arc<PluginHost>,
+) -> bool {
+    // Forward container stop signals to a server started before the monitor.
+    if let Ok(pid) = instance.pid() {
+        ChildRegistry::global().register(pid);
+    }
+    let rules = customizations
+        .log_rules
+        .map_or_else(|| Ok(LogRules::default()), |hook| hook());
+    match rules {
+        Ok(rules) => {
+            plugins.register_log_rules(&rules);
+            log_watchdog().start_instance(&instance.config.working_dir, rules);
+        }
+        Err(e) => {
+            error!("{e}");
+            return false;
+        }
+    }
+    if let Some(hook) = customizations.on_monitor
+        && let Err(e) = hook(&instance.config.working_dir)
+    {
+        error!("{e}");
+        return false;
+    }
+    match customizations.auto_backup {
+        Some(save_dir) if is_env_var_truthy("AUTO_BACKUP") => {
+            if let Err(e) = register_auto_backup(
+                instance.config.working_dir.join(save_dir),
+                Arc::clone(&plugins),
+            ) {
+                error!("{e}; check AUTO_BACKUP_SCHEDULE");
+                return false;
+            }
+        }
+        _ => debug!("Auto-backup job not enabled."),
+    }
+    // Kept for as long as the monitor runs, which outlives restarts of the server.
+    let _forwarding = instance.forward_ports();
+
+    let instance = Arc::new(Mutex::new(instance));
+    let on_job_failure = customizations.on_job_failure;
+    let on_failure = move |failure: &JobFailure| {
+        on_job_failure
+            .as_ref()
+            .map_or_else(|| notify_job_failure(failure), |hook| hook(failure));
+    };
+
+    if update_job || is_env_var_truthy("AUTO_UPDATE") {
+        if let Err(e) = register_auto_update(
+            Arc::clone(&instance),
+            customizations.on_update,
+            Arc::clone(&plugins),
+            on_failure.clone(),
+        ) {
+            error!("{e}; check AUTO_UPDATE_SCHEDULE");
+            return false;
+        }
+    } else {
+        debug!("Auto-update job not enabled.");
+    }
+
+    if restart_job || is_env_var_truthy("SCHEDULED_RESTART") {
+        if let Err(e) =
+            register_scheduled_restart(Arc::clone(&instance), customizations.broadcast, plugins)
+        {
+            error!("{e}; check SCHEDULED_RESTART_SCHEDULE");
+            return false;
+        }
+    } else {
+        debug!("Scheduled restart job not enabled.");
+    }
+
+    match DdnsUpdater::from_env() {
+        Ok(Some(updater)) => {
+            if let Err(e) = register_ddns(updater, on_failure) {
+                error!("{e}; check DDNS_SCHEDULE");
+                return false;
+            }
+        }
+        Ok(None) => debug!("DDNS job not enabled."),
+        Err(e) => {
+            error!("{e}");
+            return false;
+        }
+    }
+
+    debug!("Entering cron loop (monitoring logs and scheduled tasks)...");
+    begin_cron_loop().await;
+    debug!("Cron loop ended.");
+    true
+}
+
+/// Registers the `auto-backup` job, which archives `save_dir` into `BACKUP_DIR`.
+fn register_auto_backup(save_dir: PathBuf, plugins: Arc<PluginHost>) -> Result<(), CronError> {
+    let schedule = fetch_var("AUTO_BACKUP_SCHEDULE", DEFAULT_AUTO_BACKUP_SCHEDULE);
+    debug!("Auto-backup schedule: {}", schedule);
+    let backup_dir = PathBuf::from(fetch_var("BACKUP_DIR", DEFAULT_BACKUP_DIR));
+    register_job("auto-backup", &schedule, move || {
+        debug!("Auto-backup job triggered.");
+        let on_event = |event: &BackupEvent| {
+            plugins.emit_lifecycle(match event {
+                BackupEvent::Started { .. } => PluginEvent::BackupStarted,
+                BackupEvent::Completed { .. } => PluginEvent::BackupCompleted,
+                BackupEvent::Failed { .. } => PluginEvent::BackupFailed,
+            });
+            notify_backup_event(event);
+        };
+        match run_scheduled_backup(&save_dir, &backup_dir, on_event) {
+            Ok(output) => info!("Scheduled backup written to {}", output.display()),
+            Err(e) => error!("Scheduled backup failed: {e}"),
+        }
+    })
+    .map(drop)
+}
+
+fn notify_backup_event(event: &BackupEvent) {
+    notify(match event {
+        BackupEvent::Started { .. } => StandardServerEvents::BackupStarted,
+        BackupEvent::Completed { size, duration, .. } => StandardServerEvents::BackupCompleted {
+            size: *size,
+            duration: *duration,
+        },
+        BackupEvent::Failed { error, .. } => StandardServerEvents::BackupFailed {
+            error: error.clone(),
+        },
+    });
+}
+
+/// Registers the `auto-update` job, which updates and restarts the server when a new
+/// build is available. With `AUTO_UPDATE_MAX_DEFER` set, an available update waits up
+/// to that long for players to leave before the server is stopped.
+fn register_auto_update(
+    instance: Arc<Mutex<Instance>>,
+    on_update: Option<UpdateHook>,
+    plugins: Arc<PluginHost>,
+    on_failure: impl Fn(&JobFailure) + Send + Sync + 'static,
+) -> Result<(), CronError> {
+    let update_schedule = fetch_var("AUTO_UPDATE_SCHEDULE", "0 3 * * *");
+    debug!("Auto-update schedule: {}", update_schedule);
+    let max_defer = max_defer_from_env("AUTO_UPDATE");
+    register_fallible_job(
+        "auto-update",
+        &update_schedule,
+        JobOptions {
+            retry: RetryPolicy::from_env("AUTO_UPDATE", AUTO_UPDATE_RETRY),
+            blackouts: BlackoutWindow::from_env("AUTO_UPDATE"),
+            ..JobOptions::default()
+        },
+        on_failure,
+        move || {
+            debug!("Auto-update job triggered.");
+            let instance = Arc::clone(&instance);
+            let on_update = on_update.clone();
+            let plugins = Arc::clone(&plugins);
+            // Runs on the scheduler's blocking pool; the instance operations
+            // themselves run as separate blocking tasks, so the lock is held
+            // without stalling the runtime.
+            Handle::current().block_on(async move {
+                let inst = instance.lock().await;
+                if let Some(max_defer) = max_defer
+                    && matches!(
+                        inst.update_available_async().await,
+                        Ok(UpdateStatus::Available { .. })
+                    )
+                {
+                    defer_for_players(&inst, max_defer);
+                }
+                inst.apply_update_async(|event| {
+                    log_update_progress(&event);
+                    for &plugin_event in plugin_events(&event) {
+                        plugins.emit_lifecycle(plugin_event);
+                    }
+                    on_update.as_ref().map_or_else(
+                        || announce_update(&inst.config, &event),
+                        |hook| hook(&event),
+                    );
+                })
+                .await
+                .map(drop)
+                .map_err(|e| format!("auto-update failed: {e}"))
+            })
+        },
+    )
+    .map(drop)
+}
+
+/// Waits up to `max_defer` for players to leave before maintenance stops the server.
+fn defer_for_players(instance: &Instance, max_defer: Duration) {
+    info!("Update available; waiting up to {max_defer:?} for players to leave");
+    match instance.wait_until_empty(max_defer) {
+        EmptyWait::Empty => {}
+        waited => warn!("Updating anyway: {waited}"),
+    }
+}
+
+/// Registers the `scheduled-restart` job. With `SCHEDULED_RESTART_SKIP_IF_PLAYERS` set,
+/// restarts are skipped while players are online. Players are warned with `broadcast`
+/// (or [`shell_broadcast`]) at `SCHEDULED_RESTART_WARNINGS` minutes before the restart;
+/// see [`RestartPlan::from_env`].
+fn register_scheduled_restart(
+    instance: Arc<Mutex<Instance>>,
+    broadcast: Option<BroadcastHook>,
+    plugins: Arc<PluginHost>,
+) -> Result<(), CronError> {
+    let restart_schedule = fetch_var("SCHEDULED_RESTART_SCHEDULE", "0 4 * * *");
+    debug!("Scheduled restart schedule: {}", restart_schedule);
+    let skip_if_players = is_env_var_truthy("SCHEDULED_RESTART_SKIP_IF_PLAYERS");
+    let plan = RestartPlan::from_env("SCHEDULED_RESTART");
+    let options = JobOptions {
+        blackouts: BlackoutWindow::from_env("SCHEDULED_RESTART"),
+        ..JobOptions::default()
+    };
+    register_job_with_options("scheduled-restart", &restart_schedule, options, move || {
+        debug!("Scheduled restart job triggered.");
+        let inst = instance.blocking_lock();
+        if skip_if_players {
+            match inst.query() {
+                Ok(info) if info.human_players() > 0 => {
+                    info!(
+                        "Skipping scheduled restart: {} player(s) online",
+                        info.human_players()
+                    );
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Could not query players ({e}); restarting anyway"),
+            }
+        }
+        warn!("Restarting server...");
+        plugins.emit_lifecycle(PluginEvent::Stopping);
+        let restarted = inst.restart_with_warning(&plan, |message| {
+            broadcast.as_ref().map_or_else(
+                || warn_players(&inst, message),
+                |broadcast| broadcast(&inst, message),
+            )
+        });
+        drop(inst);
+        match restarted {
+            Ok(()) => plugins.emit_lifecycle(PluginEvent::Started),
+            Err(e) => error!("Failed to restart server: {}", e),
+        }
+    })
+    .map(drop)
+}
+
+/// Registers the `ddns` job, which points the DNS record at the server's public address
+/// whenever it changes. The record is checked once straight away, so it is right before
+/// players first look it up.
+fn register_ddns(
+    updater: DdnsUpdater,
+    on_failure: impl Fn(&JobFailure) + Send + Sync + 'static,
+) -> Result<(), CronError> {
+    let schedule = fetch_var("DDNS_SCHEDULE", DEFAULT_DDNS_SCHEDULE);
+    debug!("DDNS schedule: {}", schedule);
+    let updater = Arc::new(updater);
+    let first_check = Arc::clone(&updater);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = first_check.check() {
+            warn!("Failed to update {}: {e}", first_check.hostname());
+        }
+    });
+    register_fallible_job(
+        "ddns",
+        &schedule,
+        JobOptions::default(),
+        on_failure,
+        move || updater.check().map(drop),
+    )
+    .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    fn customizations() -> CliCustomizations {
+        CliCustomizations::new("mygame", "Manage My Game Server").with_version("1.2")
+    }
+
+    #[test]
+    fn parses_subcommands_under_the_game_name() {
+        let cli = customizations()
+            .parse_from(["mygame", "monitor", "--update-job"])
+            .unwrap();
+        assert_eq!(
+            cli.command,
+            Commands::Monitor {
+                update_job: true,
+                restart_job: false,
+            }
+        );
+
+        let cli = customizations()
+            .parse_from(["mygame", "install", "--path", "/srv/game"])
+            .unwrap();
+        assert_eq!(
+            cli.command,
+            Commands::Install {
+                path: Some(PathBuf::from("/srv/game")),
+            }
+        );
+
+        let cli = customizations()
+            .parse_from(["mygame", "export", "/tmp/mygame.tar.gz"])
+            .unwrap();
+        assert_eq!(
+            cli.command,
+            Commands::Export {
+                path: PathBuf::from("/tmp/mygame.tar.gz"),
+            }
+        );
+
+        let error = customizations()
+            .parse_from(["mygame", "--version"])
+            .unwrap_err();
+        assert!(error.to_string().contains("mygame 1.2"));
+        assert!(customizations().parse_from(["mygame", "explode"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn stop_calls_the_stop_hooks() {
+        let temp_dir = tempdir().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let before = Arc::clone(&calls);
+        let after = Arc::clone(&calls);
+        let config = InstanceConfig {
+            app_id: 1,
+            command: "server".to_owned(),
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        };
+
+        let code = execute(
+            config,
+            Commands::Stop,
+            customizations()
+                .with_before_stop(move |_| {
+                    before.fetch_add(1, Ordering::SeqCst);
+                })
+                .with_after_stop(move |_| {
+                    after.fetch_add(10, Ordering::SeqCst);
+                }),
+        )
+        .await;
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(calls.load(Ordering::SeqCst), 11);
+    }
+
+    #[tokio::test]
+    async fn monitor_fails_when_the_log_rules_cannot_be_built() {
+        let temp_dir = tempdir().unwrap();
+        let config = InstanceConfig {
+            app_id: 1,
+            command: "server".to_owned(),
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        };
+        let code = execute(
+            config,
+            Commands::Monitor {
+                update_job: false,
+                restart_job: false,
+            },
+            customizations()
+                .with_log_rules(|| Err("bad CHAT_PATTERN".to_owned()))
+                .with_on_monitor(|_| unreachable!("monitored")),
+        )
+        .await;
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[tokio::test]
+    async fn invalid_configs_fail_before_running_the_command() {
+        let code = execute(
+            InstanceConfig::default(),
+            Commands::Start { foreground: false },
+            customizations().with_before_start(|_| unreachable!("started")),
+        )
+        .await;
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[tokio::test]
+    async fn missing_variables_fail_before_running_the_command() {
+        let temp_dir = tempdir().unwrap();
+        let config = InstanceConfig {
+            app_id: 1,
+            command: "server".to_owned(),
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        };
+        let code = execute(
+            config,
+            Commands::Stop,
+            customizations()
+                .with_env([VarSpec::required("TEST_CLI_MISSING_VARIABLE")])
+                .with_before_stop(|_| unreachable!("stopped")),
+        )
+        .await;
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+}
@@ -0,0 +1,246 @@
+//! # Instance Cloning
+//!
+//! Installing a second server of the same game normally downloads the whole depot again.
+//! This module seeds a new install directory from an existing one on the same host, by
+//! copying or hardlinking its files, so the SteamCMD `validate` pass that follows only
+//! has to fetch whatever differs.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::path::Path;
+//! use gsm_instance::clone::{CloneMode, clone_install};
+//!
+//! let stats = clone_install(
+//!     Path::new("/home/steam/palworld-1"),
+//!     Path::new("/home/steam/palworld-2"),
+//!     2_394_010,
+//!     CloneMode::Hardlink,
+//! )
+//! .expect("Clone failed");
+//! println!("Seeded {} files", stats.files);
+//! ```
+use crate::errors::InstanceError;
+use gsm_shared::LOCK_FILE_NAME;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info, warn};
+
+/// Paths, relative to an install directory, that belong to a running instance rather
+/// than to the game depot and are therefore never cloned.
+pub(crate) const INSTANCE_LOCAL_PATHS: &[&str] = &[
+    ".gsm-rollback",
+    LOCK_FILE_NAME,
+    "instance.json",
+    "logs",
+    "steamapps/downloading",
+    "steamapps/temp",
+];
+
+/// How files are transferred from the source install.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CloneMode {
+    /// Copy every file. Slower and uses more disk, but the instances share nothing.
+    #[default]
+    Copy,
+    /// Hardlink every file, falling back to a copy when the directories are on
+    /// different filesystems. Nearly instant and uses no extra disk, but a file edited
+    /// in place by one server changes for both, so only use it when per-instance
+    /// configuration lives outside the install directory.
+    Hardlink,
+}
+
+impl FromStr for CloneMode {
+    type Err = InstanceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "copy" => Ok(Self::Copy),
+            "hardlink" | "link" => Ok(Self::Hardlink),
+            other => Err(InstanceError::ConfigError(format!(
+                "Unknown clone mode '{other}' (expected copy or hardlink)"
+            ))),
+        }
+    }
+}
+
+/// What [`clone_install`] transferred.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CloneStats {
+    /// Files copied or linked.
+    pub files: usize,
+    /// Files hardlinked rather than copied.
+    pub linked: usize,
+    /// Bytes in the cloned files.
+    pub bytes: u64,
+}
+
+/// Returns the SteamCMD app manifest path of `app_id` under `install_dir`.
+pub fn app_manifest_path(install_dir: &Path, app_id: u32) -> PathBuf {
+    install_dir
+        .join("steamapps")
+        .join(format!("appmanifest_{app_id}.acf"))
+}
+
+/// Seeds `target` with the game files of the existing install at `source`.
+///
+/// `source` must contain a SteamCMD manifest for `app_id`, which guards against cloning
+/// the wrong game. Files already present in `target` are overwritten; the source
+/// instance's own state, such as its logs, lock file and `instance.json`, is skipped
+/// (see `INSTANCE_LOCAL_PATHS`).
+///
+/// Callers should run a validating install afterwards, as
+/// [`crate::Instance::install_from`] does, so SteamCMD reconciles any differences.
+///
+/// # Errors
+///
+/// Returns an error when `source` is not an install of `app_id`, or when a file cannot
+/// be read or written.
+pub fn clone_install(
+    source: &Path,
+    target: &Path,
+    app_id: u32,
+    mode: CloneMode,
+) -> Result<CloneStats, InstanceError> {
+    if !app_manifest_path(source, app_id).is_file() {
+        return Err(InstanceError::ConfigError(format!(
+            "{} is not an install of app {app_id} (no appmanifest_{app_id}.acf)",
+            source.display()
+        )));
+    }
+    if source.canonicalize()?
+        == target
+            .canonicalize()
+            .unwrap_or_else(|_| target.to_path_buf())
+    {
+        return Err(InstanceError::ConfigError(format!(
+            "Cannot clone {} onto itself",
+            source.display()
+        )));
+    }
+
+    info!(
+        "Cloning app {app_id} from {} to {} ({mode:?})",
+        source.display(),
+        target.display()
+    );
+    let mut stats = CloneStats::default();
+    clone_dir(source, target, Path::new(""), mode, &mut stats)?;
+    info!(
+        "Cloned {} files ({} bytes, {} hardlinked)",
+        stats.files, stats.bytes, stats.linked
+    );
+    Ok(stats)
+}
+
+pub(crate) fn clone_dir(
+    source: &Path,
+    target: &Path,
+    relative: &Path,
+    mode: CloneMode,
+    stats: &mut CloneStats,
+) -> io::Result<()> {
+    fs::create_dir_all(target.join(relative))?;
+    for entry in fs::read_dir(source.join(relative))? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        if INSTANCE_LOCAL_PATHS
+            .iter()
+            .any(|skipped| relative == Path::new(skipped))
+        {
+            debug!("Skipping instance-local path {}", relative.display());
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            clone_dir(source, target, &relative, mode, stats)?;
+        } else if file_type.is_file() {
+            let from = entry.path();
+            let to = target.join(&relative);
+            if to.exists() {
+                fs::remove_file(&to)?;
+            }
+            stats.bytes += entry.metadata()?.len();
+            stats.files += 1;
+            // SteamCMD rewrites its own bookkeeping under `steamapps`, so those files are
+            // always copied to keep one instance's manifest from changing the other's.
+            if mode == CloneMode::Hardlink && !relative.starts_with("steamapps") {
+                match fs::hard_link(&from, &to) {
+                    Ok(()) => {
+                        stats.linked += 1;
+                        continue;
+                    }
+                    Err(e) => debug!("Hardlink of {} failed ({e}); copying", from.display()),
+                }
+            }
+            fs::copy(&from, &to)?;
+        } else {
+            warn!(
+                "Skipping unsupported file type at {}",
+                entry.path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    fn fake_install(app_id: u32) -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let manifest = app_manifest_path(dir.path(), app_id);
+        fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        fs::write(&manifest, r#""AppState" { "buildid" "1000" }"#).unwrap();
+        fs::create_dir_all(dir.path().join("bin")).unwrap();
+        fs::write(dir.path().join("bin/server"), "binary").unwrap();
+        fs::create_dir_all(dir.path().join("logs")).unwrap();
+        fs::write(dir.path().join("logs/server.log"), "log").unwrap();
+        fs::write(dir.path().join("instance.json"), "{}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn clones_game_files_but_not_instance_state() {
+        let source = fake_install(2_394_010);
+        let target = tempdir().unwrap();
+
+        let stats =
+            clone_install(source.path(), target.path(), 2_394_010, CloneMode::Hardlink).unwrap();
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(
+            fs::read_to_string(target.path().join("bin/server")).unwrap(),
+            "binary"
+        );
+        assert!(app_manifest_path(target.path(), 2_394_010).is_file());
+        assert!(!target.path().join("logs").exists());
+        assert!(!target.path().join("instance.json").exists());
+    }
+
+    #[test]
+    fn refuses_source_for_another_app() {
+        let source = fake_install(2_394_010);
+        let target = tempdir().unwrap();
+
+        let result = clone_install(source.path(), target.path(), 2_278_520, CloneMode::Copy);
+        assert!(matches!(result, Err(InstanceError::ConfigError(_))));
+    }
+
+    #[test]
+    fn parses_clone_modes() {
+        assert_eq!("copy".parse::<CloneMode>().unwrap(), CloneMode::Copy);
+        assert_eq!(
+            " Hardlink ".parse::<CloneMode>().unwrap(),
+            CloneMode::Hardlink
+        );
+        assert!("symlink".parse::<CloneMode>().is_err());
+    }
+}
@@ -0,0 +1,546 @@
+//! # Config File
+//!
+//! This module loads a declarative `gsm.toml` or `gsm.yaml` file, so a container can mount
+//! one file instead of setting dozens of environment variables. The file is read from the
+//! path in `GSM_CONFIG`, or else from `gsm.toml`, `gsm.yaml` or `gsm.yml` in the current
+//! directory. It has four optional sections:
+//!
+//! - `instance`: overrides fields of the game's [`InstanceConfig`], such as `working_dir`
+//!   or `beta`, and adds shell command `hooks`.
+//! - `schedules`: cron schedules that enable the `auto_update`, `auto_backup` and
+//!   `scheduled_restart` jobs.
+//! - `webhook`: the `url` notifications are sent to.
+//! - `env`: any other environment variable the game reads, such as `PUBLIC_IP`.
+//!
+//! Values may reference the environment as `${VAR}` or `${VAR:-default}`; write `$$` for a
+//! literal `$`. Variables already set in the environment take precedence over the file,
+//! so a single value can still be overridden without editing it.
+//!
+//! # Example
+//!
+//! ```toml
+//! [instance]
+//! working_dir = "/home/steam/palworld"
+//! beta = { branch = "experimental" }
+//! downloads = { throttle_kbps = 4096 }
+//! resources = { cpu_affinity = [2, 3], memory_limit_mb = 16384 }
+//!
+//! [schedules]
+//! auto_update = "0 3 * * *"
+//!
+//! [webhook]
+//! url = "${DISCORD_WEBHOOK}"
+//!
+//! [env]
+//! PUBLIC_PORT = 8211
+//! ```
+//!
+//! ```rust,no_run
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::config_file::ConfigFile;
+//! use gsm_shared::export_missing_vars;
+//!
+//! let file = ConfigFile::from_env().expect("Invalid config file");
+//! // SAFETY: called before any other thread is started.
+//! unsafe { export_missing_vars(file.env_vars(), "the config file") };
+//! let config = file.apply(InstanceConfig::default());
+//! ```
+use crate::config::{BetaConfig, DownloadConfig, InstanceConfig, LaunchMode};
+use crate::errors::InstanceError;
+use crate::hooks::Hooks;
+use crate::ports::GamePort;
+use crate::proton::ProtonSelector;
+use crate::readiness::Readiness;
+use crate::resources::ResourceLimits;
+use crate::shared::SharedInstall;
+use crate::workshop::WorkshopConfig;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Environment variable with the path of the config file.
+pub const GSM_CONFIG: &str = "GSM_CONFIG";
+
+/// The file names searched for in the current directory when `GSM_CONFIG` is unset.
+pub const DEFAULT_CONFIG_FILES: [&str; 3] = ["gsm.toml", "gsm.yaml", "gsm.yml"];
+
+/// A parsed config file. The default value is an empty file, which changes nothing.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Overrides for the game's [`InstanceConfig`].
+    pub instance: InstanceOverrides,
+    /// Schedules of the jobs run by `monitor`.
+    pub schedules: Schedules,
+    /// Where notifications are sent.
+    pub webhook: Webhook,
+    /// Other environment variables, by name.
+    pub env: BTreeMap<String, EnvValue>,
+}
+
+/// The [`InstanceConfig`] fields set by a config file; unset fields keep the game's
+/// defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstanceOverrides {
+    /// Overrides [`InstanceConfig::app_id`].
+    pub app_id: Option<u32>,
+    /// Overrides [`InstanceConfig::name`].
+    pub name: Option<String>,
+    /// Overrides [`InstanceConfig::command`].
+    pub command: Option<String>,
+    /// Overrides [`InstanceConfig::install_args`].
+    pub install_args: Option<Vec<String>>,
+    /// Overrides [`InstanceConfig::launch_args`].
+    pub launch_args: Option<Vec<String>>,
+    /// Deprecated: use `launch_mode`. `true` selects [`LaunchMode::Wine`] (unless
+    /// `launch_mode` already runs the Windows build) and `false` [`LaunchMode::Native`];
+    /// ignored when `launch_mode` is set.
+    pub force_windows: Option<bool>,
+    /// Overrides [`InstanceConfig::skip_validate`].
+    pub skip_validate: Option<bool>,
+    /// Overrides [`InstanceConfig::working_dir`].
+    pub working_dir: Option<PathBuf>,
+    /// Overrides [`InstanceConfig::launch_mode`].
+    pub launch_mode: Option<LaunchMode>,
+    /// Overrides [`InstanceConfig::query_port`].
+    pub query_port: Option<u16>,
+    /// Overrides [`InstanceConfig::ports`].
+    pub ports: Option<Vec<GamePort>>,
+    /// Overrides [`InstanceConfig::beta`].
+    pub beta: Option<BetaConfig>,
+    /// Added to [`InstanceConfig::env`], replacing variables of the same name.
+    pub env: Option<HashMap<String, String>>,
+    /// Overrides [`InstanceConfig::clear_env`].
+    pub clear_env: Option<bool>,
+    /// Shell command hooks, added after the game's own hooks of each stage.
+    pub hooks: Option<Hooks>,
+    /// Overrides [`InstanceConfig::daemonize`].
+    pub daemonize: Option<bool>,
+    /// Overrides [`InstanceConfig::proton`].
+    pub proton: Option<ProtonSelector>,
+    /// Overrides [`InstanceConfig::workshop`].
+    pub workshop: Option<WorkshopConfig>,
+    /// Overrides [`InstanceConfig::readiness`].
+    pub readiness: Option<Readiness>,
+    /// Overrides [`InstanceConfig::downloads`].
+    pub downloads: Option<DownloadConfig>,
+    /// Overrides [`InstanceConfig::resources`].
+    pub resources: Option<ResourceLimits>,
+    /// Overrides [`InstanceConfig::shared_install`].
+    pub shared_install: Option<SharedInstall>,
+    /// Overrides [`InstanceConfig::dry_run`].
+    pub dry_run: Option<bool>,
+}
+
+/// Cron schedules of the jobs run by `monitor`. Setting a schedule enables its job.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedules {
+    /// Sets `AUTO_UPDATE` and `AUTO_UPDATE_SCHEDULE`.
+    pub auto_update: Option<String>,
+    /// Sets `AUTO_BACKUP` and `AUTO_BACKUP_SCHEDULE`.
+    pub auto_backup: Option<String>,
+    /// Sets `SCHEDULED_RESTART` and `SCHEDULED_RESTART_SCHEDULE`.
+    pub scheduled_restart: Option<String>,
+}
+
+/// Where notifications are sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Webhook {
+    /// Sets `WEBHOOK_URL`.
+    pub url: Option<String>,
+}
+
+/// A value in the `env` section. Numbers and booleans are accepted so `PORT = 8211`
+/// does not need quoting.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    /// `true` or `false`.
+    Bool(bool),
+    /// A whole number.
+    Integer(i64),
+    /// A decimal number.
+    Float(f64),
+    /// Any other value.
+    String(String),
+}
+
+impl fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::String(value) => f.write_str(value),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Loads the file named by `GSM_CONFIG`, or the first of [`DEFAULT_CONFIG_FILES`] in
+    /// the current directory. Returns an empty file when there is neither.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::ConfigError`] when `GSM_CONFIG` names a missing file, or
+    /// when the file cannot be read, interpolated or parsed.
+    pub fn from_env() -> Result<Self, InstanceError> {
+        if let Some(path) = std::env::var_os(GSM_CONFIG).filter(|path| !path.is_empty()) {
+            return Self::load(path);
+        }
+        DEFAULT_CONFIG_FILES
+            .iter()
+            .map(Path::new)
+            .find(|path| path.is_file())
+            .map_or_else(|| Ok(Self::default()), Self::load)
+    }
+
+    /// Loads the config file at `path`, choosing TOML or YAML by its extension and
+    /// interpolating environment variables first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::ConfigError`] when the file cannot be read, has an
+    /// unsupported extension, references an unset variable without a default, or does not
+    /// parse.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InstanceError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| {
+            InstanceError::ConfigError(format!("failed to read {}: {e}", path.display()))
+        })?;
+        let text = interpolate(&text, |key| std::env::var(key).ok()).map_err(|missing| {
+            InstanceError::ConfigError(format!(
+                "{} references unset variables: {}; set them or give a default as ${{VAR:-default}}",
+                path.display(),
+                missing.join(", ")
+            ))
+        })?;
+        let file = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("yaml" | "yml") => Self::from_yaml(&text),
+            _ => Err(format!(
+                "unsupported extension; use {}",
+                DEFAULT_CONFIG_FILES.join(", ")
+            )),
+        }
+        .map_err(|e| InstanceError::ConfigError(format!("{}: {e}", path.display())))?;
+        info!("Loaded configuration from {}", path.display());
+        Ok(file)
+    }
+
+    fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    fn from_yaml(text: &str) -> Result<Self, String> {
+        if text.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Returns the environment variables the file sets, in the order they are exported.
+    ///
+    /// Export them with [`gsm_shared::export_missing_vars`], which keeps variables that
+    /// are already set, at the start of a synchronous `main`.
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        for (flag, schedule) in [
+            ("AUTO_UPDATE", &self.schedules.auto_update),
+            ("AUTO_BACKUP", &self.schedules.auto_backup),
+            ("SCHEDULED_RESTART", &self.schedules.scheduled_restart),
+        ] {
+            if let Some(schedule) = schedule {
+                vars.push((flag.to_owned(), "1".to_owned()));
+                vars.push((format!("{flag}_SCHEDULE"), schedule.clone()));
+            }
+        }
+        if let Some(url) = &self.webhook.url {
+            vars.push(("WEBHOOK_URL".to_owned(), url.clone()));
+        }
+        vars.extend(
+            self.env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string())),
+        );
+        vars
+    }
+
+    /// Applies the file's `instance` section to `config`.
+    pub fn apply(&self, mut config: InstanceConfig) -> InstanceConfig {
+        let overrides = self.instance.clone();
+        if let Some(app_id) = overrides.app_id {
+            config.app_id = app_id;
+        }
+        if let Some(name) = overrides.name {
+            config.name = name;
+        }
+        if let Some(command) = overrides.command {
+            config.command = command;
+        }
+        if let Some(install_args) = overrides.install_args {
+            config.install_args = install_args;
+        }
+        if let Some(launch_args) = overrides.launch_args {
+            config.launch_args = launch_args;
+        }
+        if let Some(force_windows) = overrides.force_windows {
+            warn!("force_windows is deprecated; set launch_mode to Wine, Proton or Native");
+            if overrides.launch_mode.is_none() && force_windows != config.launch_mode.is_windows() {
+                config.launch_mode = LaunchMode::from_force_windows(force_windows);
+            }
+        }
+        if let Some(skip_validate) = overrides.skip_validate {
+            config.skip_validate = skip_validate;
+        }
+        if let Some(working_dir) = overrides.working_dir {
+            config.working_dir = working_dir;
+        }
+        if let Some(launch_mode) = overrides.launch_mode {
+            config.launch_mode = launch_mode;
+        }
+        if overrides.query_port.is_some() {
+            config.query_port = overrides.query_port;
+        }
+        if let Some(ports) = overrides.ports {
+            config.ports = ports;
+        }
+        if overrides.beta.is_some() {
+            config.beta = overrides.beta;
+        }
+        if let Some(env) = overrides.env {
+            config.env.extend(env);
+        }
+        if let Some(clear_env) = overrides.clear_env {
+            config.clear_env = clear_env;
+        }
+        if let Some(hooks) = overrides.hooks {
+            config.hooks.pre_start.extend(hooks.pre_start);
+            config.hooks.post_stop.extend(hooks.post_stop);
+            config.hooks.post_update.extend(hooks.post_update);
+        }
+        if let Some(daemonize) = overrides.daemonize {
+            config.daemonize = daemonize;
+        }
+        if overrides.proton.is_some() {
+            config.proton = overrides.proton;
+        }
+        if let Some(workshop) = overrides.workshop {
+            config.workshop = workshop;
+        }
+        if overrides.readiness.is_some() {
+            config.readiness = overrides.readiness;
+        }
+        if let Some(downloads) = overrides.downloads {
+            config.downloads = downloads;
+        }
+        if let Some(resources) = overrides.resources {
+            config.resources = resources;
+        }
+        if overrides.shared_install.is_some() {
+            config.shared_install = overrides.shared_install;
+        }
+        if let Some(dry_run) = overrides.dry_run {
+            config.dry_run = dry_run;
+        }
+        config
+    }
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` in `text` with values from `lookup`, and `$$`
+/// with `$`. Returns the names of unset variables without a default.
+fn interpolate(text: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, Vec<String>> {
+    let mut output = String::with_capacity(text.len());
+    let mut missing = Vec::new();
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once('$') {
+        output.push_str(before);
+        rest = after;
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+        let Some((expression, after)) = rest
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+        else {
+            output.push('$');
+            continue;
+        };
+        rest = after;
+        let (name, default) = expression
+            .split_once(":-")
+            .map_or((expression, None), |(name, default)| (name, Some(default)));
+        let Some(value) = lookup(name)
+            .filter(|value| !value.is_empty())
+            .or_else(|| default.map(str::to_owned))
+        else {
+            missing.push(name.to_owned());
+            continue;
+        };
+        output.push_str(&value);
+    }
+    output.push_str(rest);
+    if missing.is_empty() {
+        Ok(output)
+    } else {
+        Err(missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // `${VAR:-default}` placeholders look like format arguments.
+    #![allow(clippy::unwrap_used, clippy::literal_string_with_formatting_args)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    fn lookup(key: &str) -> Option<String> {
+        match key {
+            "HOST" => Some("example.com".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolate_replaces_variables_and_defaults() {
+        assert_eq!(
+            interpolate("https://${HOST}/${PATH:-hook} costs $$5 or $5", lookup).unwrap(),
+            "https://example.com/hook costs $5 or $5"
+        );
+        assert_eq!(
+            interpolate("${EMPTY:-fallback}", lookup).unwrap(),
+            "fallback"
+        );
+        assert_eq!(
+            interpolate("${MISSING} ${HOST} ${OTHER}", lookup).unwrap_err(),
+            vec!["MISSING", "OTHER"]
+        );
+    }
+
+    #[test]
+    fn toml_and_yaml_files_parse_to_the_same_config() {
+        let toml = r#"
+            [instance]
+            working_dir = "/srv/palworld"
+            launch_mode = "Proton"
+            proton = { version = "GE-Proton9-20" }
+            beta = { branch = "experimental" }
+
+            [schedules]
+            auto_update = "0 3 * * *"
+
+            [webhook]
+            url = "https://example.com/hook"
+
+            [env]
+            PUBLIC_PORT = 8211
+            PUBLIC_LOBBY = true
+        "#;
+        let yaml = r#"
+instance:
+  working_dir: /srv/palworld
+  launch_mode: Proton
+  proton:
+    version: GE-Proton9-20
+  beta:
+    branch: experimental
+schedules:
+  auto_update: "0 3 * * *"
+webhook:
+  url: https://example.com/hook
+env:
+  PUBLIC_PORT: 8211
+  PUBLIC_LOBBY: true
+"#;
+        let from_toml = ConfigFile::from_toml(toml).unwrap();
+        assert_eq!(from_toml, ConfigFile::from_yaml(yaml).unwrap());
+
+        assert_eq!(
+            from_toml.env_vars(),
+            vec![
+                ("AUTO_UPDATE".to_owned(), "1".to_owned()),
+                ("AUTO_UPDATE_SCHEDULE".to_owned(), "0 3 * * *".to_owned()),
+                (
+                    "WEBHOOK_URL".to_owned(),
+                    "https://example.com/hook".to_owned()
+                ),
+                ("PUBLIC_LOBBY".to_owned(), "true".to_owned()),
+                ("PUBLIC_PORT".to_owned(), "8211".to_owned()),
+            ]
+        );
+
+        let config = from_toml.apply(InstanceConfig {
+            app_id: 2_394_010,
+            command: "./PalServer.sh".to_owned(),
+            ..InstanceConfig::default()
+        });
+        assert_eq!(config.app_id, 2_394_010);
+        assert_eq!(config.command, "./PalServer.sh");
+        assert_eq!(config.working_dir, PathBuf::from("/srv/palworld"));
+        assert!(matches!(config.launch_mode, LaunchMode::Proton));
+        assert_eq!(
+            config.proton,
+            Some(ProtonSelector::Version("GE-Proton9-20".to_owned()))
+        );
+        assert_eq!(config.beta, Some(BetaConfig::new("experimental")));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let error = ConfigFile::from_toml("[instance]\nworkdir = \"/srv\"\n").unwrap_err();
+        assert!(error.contains("workdir"));
+        assert!(ConfigFile::from_yaml("webhooks:\n  url: x\n").is_err());
+        assert_eq!(ConfigFile::from_yaml("").unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    fn force_windows_still_selects_the_windows_build() {
+        let wine = ConfigFile::from_toml("[instance]\nforce_windows = true\n").unwrap();
+        let config = wine.apply(InstanceConfig::default());
+        assert_eq!(config.launch_mode, LaunchMode::Wine);
+
+        let proton = InstanceConfig {
+            launch_mode: LaunchMode::Proton,
+            ..InstanceConfig::default()
+        };
+        assert_eq!(wine.apply(proton).launch_mode, LaunchMode::Proton);
+
+        let both =
+            ConfigFile::from_toml("[instance]\nforce_windows = true\nlaunch_mode = \"native\"\n")
+                .unwrap();
+        assert_eq!(
+            both.apply(InstanceConfig::default()).launch_mode,
+            LaunchMode::Native
+        );
+    }
+
+    #[test]
+    fn load_reports_the_path_and_unset_variables() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("gsm.toml");
+        fs::write(&path, "[webhook]\nurl = \"${GSM_CONFIG_TEST_UNSET_URL}\"\n").unwrap();
+
+        let InstanceError::ConfigError(message) = ConfigFile::load(&path).unwrap_err() else {
+            unreachable!("expected ConfigError");
+        };
+        assert!(message.contains("gsm.toml"));
+        assert!(message.contains("GSM_CONFIG_TEST_UNSET_URL"));
+
+        let path = temp_dir.path().join("gsm.json");
+        fs::write(&path, "{}").unwrap();
+        assert!(matches!(
+            ConfigFile::load(&path),
+            Err(InstanceError::ConfigError(_))
+        ));
+    }
+}
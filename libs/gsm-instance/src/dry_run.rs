@@ -0,0 +1,139 @@
+//! # Dry Runs
+//!
+//! With [`InstanceConfig::dry_run`] set, the [`Instance`](crate::Instance) operations that
+//! change anything log what they would do instead: installs, updates, rollbacks and
+//! workshop downloads log the exact SteamCMD command line, `start` the command that
+//! launches the server, and `stop` the signals it would send. Operators can validate a
+//! container's configuration this way before letting it loose on a live server.
+//!
+//! Read-only operations, such as checking for an update or running the preflight checks,
+//! still run. Hooks do not, and `start` returns [`InstanceError::DryRun`], as there is no
+//! server process to return.
+//!
+//! The game binaries turn dry runs on with `--dry-run` or `DRY_RUN=1`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig {
+//!     app_id: 2_394_010,
+//!     dry_run: true,
+//!     ..InstanceConfig::default()
+//! });
+//! // Logs `steamcmd +force_install_dir ... +app_update 2394010 validate +quit`.
+//! instance.install().expect("Invalid SteamCMD environment");
+//! ```
+use crate::config::{DownloadConfig, InstanceConfig, LaunchMode};
+use crate::steamcmd::steamcmd_command;
+use gsm_shared::{redact_args, redact_secrets};
+use std::time::Duration;
+
+/// Environment variable that turns on dry runs for the game binaries.
+pub const DRY_RUN: &str = "DRY_RUN";
+
+/// Returns the command line that runs SteamCMD with `args` and the `downloads` limits,
+/// with beta branch passwords and other secrets redacted.
+pub(crate) fn steamcmd_line(args: &[String], downloads: &DownloadConfig) -> String {
+    let program = steamcmd_command()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    let args = downloads
+        .steamcmd_args()
+        .into_iter()
+        .chain(args.iter().map(|arg| redact_secrets(arg)));
+    std::iter::once(program)
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the command that launches `config`'s server and where, with secrets redacted,
+/// e.g. `./PalServer.sh -port=8211 in /home/steam/palworld`.
+pub(crate) fn launch_line(config: &InstanceConfig) -> String {
+    let command = std::iter::once(config.command.clone())
+        .chain(redact_args(&config.launch_args))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let layer = match config.launch_mode {
+        LaunchMode::Native => "",
+        LaunchMode::Proton => " through Proton",
+        LaunchMode::Wine => " through Wine",
+    };
+    let env = if config.env.is_empty() {
+        String::new()
+    } else {
+        format!(" with environment {:?}", config.redacted_env())
+    };
+    format!("{command}{layer} in {}{env}", config.working_dir.display())
+}
+
+/// Describes the signals stopping process `pid` with `grace` sends.
+pub(crate) fn stop_plan(pid: u32, grace: Duration) -> String {
+    format!(
+        "send SIGINT to process {pid}, then SIGTERM after {0}s and SIGKILL after another {0}s",
+        grace.as_secs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::config::BetaConfig;
+    use crate::env_config::EnvConfig;
+    use crate::install::install_args;
+    use crate::test_support::env_lock;
+    use gsm_shared::Secret;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn steamcmd_line_redacts_beta_passwords() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+        let env_config = EnvConfig {
+            beta: Some(BetaConfig {
+                branch: "staging".to_owned(),
+                password: Some(Secret::from("hunter2")),
+            }),
+            ..EnvConfig::default()
+        };
+        let args = install_args(1, Path::new("/srv/game"), false, true, &[], &env_config);
+        let downloads = DownloadConfig {
+            throttle_kbps: Some(512),
+            http_proxy: None,
+        };
+
+        assert_eq!(
+            steamcmd_line(&args, &downloads),
+            "steamcmd +set_download_throttle 512 +force_install_dir /srv/game +login anonymous \
+             +app_update 1 -beta staging -betapassword <redacted> +quit"
+        );
+    }
+
+    #[test]
+    fn launch_line_shows_the_command_and_layer() {
+        let config = InstanceConfig {
+            command: "./Server.exe".to_owned(),
+            launch_args: vec!["-port=15636".to_owned()],
+            launch_mode: LaunchMode::Proton,
+            working_dir: PathBuf::from("/srv/game"),
+            env: HashMap::from([("ADMIN_PASSWORD".to_owned(), "hunter2".to_owned())]),
+            ..InstanceConfig::default()
+        };
+
+        assert_eq!(
+            launch_line(&config),
+            "./Server.exe -port=15636 through Proton in /srv/game with environment \
+             {\"ADMIN_PASSWORD\": \"<redacted>\"}"
+        );
+    }
+}
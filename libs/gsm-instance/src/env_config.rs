@@ -0,0 +1,244 @@
+//! # Environment Configuration
+//!
+//! This module gathers the SteamCMD options operators set through the environment into
+//! one validated [`EnvConfig`], which [`install`](crate::install::install) and
+//! [`update_server`](crate::update::update_server) turn into command-line arguments:
+//!
+//! - `USE_BETA`: set to `1`, `true` or `yes` to install the branch named by `BETA_BRANCH`.
+//! - `BETA_BRANCH`: the beta branch to install, e.g. `experimental`.
+//! - `BETA_BRANCH_PASSWORD`: the password of a private beta branch.
+//! - `ADDITIONAL_STEAMCMD_ARGS`: extra SteamCMD arguments appended before `+quit`.
+//! - `STEAMCMD_DOWNLOAD_THROTTLE_KBPS`: caps SteamCMD's download rate, in kilobits per
+//!   second.
+//! - `STEAMCMD_HTTP_PROXY`: the HTTP proxy SteamCMD downloads through.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::env_config::EnvConfig;
+//!
+//! let env = EnvConfig::from_env().expect("Invalid SteamCMD environment");
+//! if let Some(beta) = &env.beta {
+//!     println!("Installing beta branch {}", beta.branch);
+//! }
+//! ```
+use crate::config::{BetaConfig, ConfigIssue, DownloadConfig};
+use crate::errors::InstanceError;
+use gsm_shared::Secret;
+use std::env;
+use tracing::warn;
+
+/// Environment variable that enables installing the `BETA_BRANCH` beta branch.
+pub const USE_BETA: &str = "USE_BETA";
+/// Environment variable with the beta branch to install.
+pub const BETA_BRANCH: &str = "BETA_BRANCH";
+/// Environment variable with the password of a private beta branch.
+pub const BETA_BRANCH_PASSWORD: &str = "BETA_BRANCH_PASSWORD";
+/// Environment variable with extra arguments appended to every SteamCMD install or update.
+pub const ADDITIONAL_STEAMCMD_ARGS: &str = "ADDITIONAL_STEAMCMD_ARGS";
+/// Environment variable with SteamCMD's download rate limit in kilobits per second.
+pub const STEAMCMD_DOWNLOAD_THROTTLE_KBPS: &str = "STEAMCMD_DOWNLOAD_THROTTLE_KBPS";
+/// Environment variable with the HTTP proxy SteamCMD downloads through.
+pub const STEAMCMD_HTTP_PROXY: &str = "STEAMCMD_HTTP_PROXY";
+
+/// SteamCMD options read from the environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvConfig {
+    /// The beta branch to install, when `USE_BETA` is set.
+    pub beta: Option<BetaConfig>,
+    /// Extra SteamCMD arguments, e.g. `+app_info_update 1`.
+    pub additional_args: Option<String>,
+    /// The download rate limit and proxy.
+    pub downloads: DownloadConfig,
+}
+
+impl EnvConfig {
+    /// Reads and validates the SteamCMD options from the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::InvalidConfig`] listing each invalid variable and a
+    /// suggested fix.
+    pub fn from_env() -> Result<Self, InstanceError> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Reads and validates the SteamCMD options from `lookup`, which returns the value
+    /// of a variable when it is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::InvalidConfig`] listing each invalid variable and a
+    /// suggested fix.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, InstanceError> {
+        let read = |key| {
+            lookup(key)
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+        };
+        let use_beta = read(USE_BETA).is_some_and(|value| is_truthy(&value));
+        let branch = read(BETA_BRANCH);
+        let password = read(BETA_BRANCH_PASSWORD).map(Secret::new);
+        let additional_args = read(ADDITIONAL_STEAMCMD_ARGS)
+            .map(|args| args.trim_matches('"').trim().to_owned())
+            .filter(|args| !args.is_empty());
+
+        let throttle = read(STEAMCMD_DOWNLOAD_THROTTLE_KBPS);
+        let http_proxy = read(STEAMCMD_HTTP_PROXY);
+
+        if !use_beta && branch.is_some() {
+            warn!("Ignoring {BETA_BRANCH} because {USE_BETA} is not set");
+        }
+        let mut issues = Vec::new();
+        if use_beta && branch.is_none() {
+            issues.push(ConfigIssue::new(
+                BETA_BRANCH,
+                "USE_BETA is set but BETA_BRANCH is empty",
+                "set BETA_BRANCH to the branch to install, or unset USE_BETA",
+            ));
+        }
+        let beta = branch
+            .filter(|_| use_beta)
+            .map(|branch| BetaConfig { branch, password });
+        if let Some(beta) = &beta {
+            issues.extend(beta.issues(BETA_BRANCH, BETA_BRANCH_PASSWORD));
+        }
+        if let Some(args) = additional_args
+            .as_deref()
+            .filter(|args| !args.starts_with(['+', '-']))
+        {
+            issues.push(ConfigIssue::new(
+                ADDITIONAL_STEAMCMD_ARGS,
+                format!("'{args}' does not start with a SteamCMD command or option"),
+                "prefix commands with '+' (e.g. +app_info_update 1) and options with '-'",
+            ));
+        }
+        let throttle_kbps = throttle.and_then(|kbps| {
+            kbps.parse()
+                .map_err(|_| {
+                    issues.push(ConfigIssue::new(
+                        STEAMCMD_DOWNLOAD_THROTTLE_KBPS,
+                        format!("'{kbps}' is not a whole number of kilobits per second"),
+                        "set a rate such as 2048, or 0 for no limit",
+                    ));
+                })
+                .ok()
+        });
+        let downloads = DownloadConfig {
+            throttle_kbps,
+            http_proxy,
+        };
+        issues.extend(downloads.issues(STEAMCMD_DOWNLOAD_THROTTLE_KBPS, STEAMCMD_HTTP_PROXY));
+        if !issues.is_empty() {
+            return Err(InstanceError::InvalidConfig(issues));
+        }
+
+        Ok(Self {
+            beta,
+            additional_args,
+            downloads,
+        })
+    }
+}
+
+/// Checks if a string value represents a truthy value.
+pub(crate) fn is_truthy(val: &str) -> bool {
+    val == "1" || val == "true" || val == "yes"
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<EnvConfig, InstanceError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect();
+        EnvConfig::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn empty_environment_uses_no_options() {
+        assert_eq!(parse(&[]).unwrap(), EnvConfig::default());
+    }
+
+    #[test]
+    fn beta_branch_requires_use_beta() {
+        let env = parse(&[(BETA_BRANCH, "experimental"), (BETA_BRANCH_PASSWORD, "pw")]).unwrap();
+        assert_eq!(env, EnvConfig::default());
+
+        let env = parse(&[
+            (USE_BETA, "true"),
+            (BETA_BRANCH, "experimental"),
+            (BETA_BRANCH_PASSWORD, "pw"),
+        ])
+        .unwrap();
+        assert_eq!(
+            env.beta,
+            Some(BetaConfig {
+                branch: "experimental".to_owned(),
+                password: Some(Secret::from("pw")),
+            })
+        );
+    }
+
+    #[test]
+    fn additional_args_are_trimmed_of_wrapping_quotes() {
+        let env = parse(&[(ADDITIONAL_STEAMCMD_ARGS, "\"+app_info_update 1\"")]).unwrap();
+        assert_eq!(env.additional_args.as_deref(), Some("+app_info_update 1"));
+
+        let env = parse(&[(ADDITIONAL_STEAMCMD_ARGS, " \"\" ")]).unwrap();
+        assert_eq!(env.additional_args, None);
+    }
+
+    #[test]
+    fn invalid_values_are_all_reported() {
+        let error = parse(&[
+            (USE_BETA, "1"),
+            (ADDITIONAL_STEAMCMD_ARGS, "app_info_update"),
+        ])
+        .unwrap_err();
+        let InstanceError::InvalidConfig(issues) = error else {
+            unreachable!("expected InvalidConfig, got {error}");
+        };
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field).collect();
+        assert_eq!(fields, vec![BETA_BRANCH, ADDITIONAL_STEAMCMD_ARGS]);
+
+        let error = parse(&[
+            (USE_BETA, "yes"),
+            (BETA_BRANCH, "bad branch"),
+            (BETA_BRANCH_PASSWORD, "two words"),
+        ])
+        .unwrap_err();
+        let InstanceError::InvalidConfig(issues) = error else {
+            unreachable!("expected InvalidConfig, got {error}");
+        };
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field).collect();
+        assert_eq!(fields, vec![BETA_BRANCH, BETA_BRANCH_PASSWORD]);
+    }
+
+    #[test]
+    fn download_limits_are_read() {
+        let env = parse(&[
+            (STEAMCMD_DOWNLOAD_THROTTLE_KBPS, "2048"),
+            (STEAMCMD_HTTP_PROXY, "http://proxy.lan:3128"),
+        ])
+        .unwrap();
+        assert_eq!(env.downloads.throttle_kbps, Some(2048));
+        assert_eq!(
+            env.downloads.http_proxy.as_deref(),
+            Some("http://proxy.lan:3128")
+        );
+
+        let error = parse(&[(STEAMCMD_DOWNLOAD_THROTTLE_KBPS, "2 MB")]).unwrap_err();
+        let InstanceError::InvalidConfig(issues) = error else {
+            unreachable!("expected InvalidConfig, got {error}");
+        };
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field).collect();
+        assert_eq!(fields, vec![STEAMCMD_DOWNLOAD_THROTTLE_KBPS]);
+    }
+}
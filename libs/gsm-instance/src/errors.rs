@@ -5,6 +5,10 @@
 //!
 //! The `InstanceError` enum consolidates all possible errors that can occur during the
 //! management of a game server instance, from SteamCMD operations to process management.
+use crate::config::ConfigIssue;
+use crate::hooks::HookStage;
+use crate::ports::GamePort;
+use crate::steamcmd::SteamCmdError;
 use std::io;
 use std::num::ParseIntError;
 use thiserror::Error;
@@ -16,10 +20,10 @@ use thiserror::Error;
 /// trait and provide descriptive error messages.
 #[derive(Error, Debug)]
 pub enum InstanceError {
-    /// An error occurred while executing a SteamCMD command. This could be due to a
-    /// network issue, an invalid App ID, or other SteamCMD-related problems.
+    /// A SteamCMD run failed, classified by what went wrong (network, disk,
+    /// authentication, ...) after transient failures were retried.
     #[error("SteamCMD error: {0}")]
-    SteamCmdError(String),
+    SteamCmdError(#[from] SteamCmdError),
 
     /// An error related to managing the server process, such as failing to start,
     /// stop, or check the status of the server process.
@@ -31,13 +35,59 @@ pub enum InstanceError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
+    /// The instance configuration failed validation. Every problem found is collected
+    /// so operators can fix them all in one pass rather than one restart at a time.
+    #[error("Invalid configuration:\n{}", format_issues(.0))]
+    InvalidConfig(Vec<ConfigIssue>),
+
     /// An error that occurred during the execution of an external command, other than
     /// SteamCMD. This is often used for launch or compatibility tool errors.
     #[error("Command execution error: {0}")]
     CommandExecutionError(String),
 
+    /// A lifecycle hook whose failure policy is `Abort` failed or timed out.
+    #[error("{stage} hook '{hook}' failed: {reason}")]
+    HookFailed {
+        /// The name of the hook.
+        hook: String,
+        /// The stage the hook ran in.
+        stage: HookStage,
+        /// Why the hook failed, with the end of its output.
+        reason: String,
+    },
+
+    /// A Steam Workshop item could not be installed, e.g. because SteamCMD did not
+    /// download it.
+    #[error("Workshop error: {0}")]
+    WorkshopError(String),
+
+    /// An instance export could not be written or imported, e.g. because the archive is
+    /// not an export or is of another app; see [`migration`](crate::migration).
+    #[error("Export error: {0}")]
+    ExportError(String),
+
+    /// A port the server binds is already in use by another process, so the server was
+    /// not launched; see [`ports`](crate::ports).
+    #[error("Port {0} is already in use; stop whatever is bound to it or change the server's port")]
+    PortInUse(GamePort),
+
+    /// The server was started, but exited or did not pass its readiness probe in time;
+    /// see [`Readiness`](crate::readiness::Readiness).
+    #[error("Server not ready: {0}")]
+    NotReady(String),
+
+    /// `dry_run` is set, so the operation was logged instead of run and has no result
+    /// to return; see [`dry_run`](crate::dry_run).
+    #[error("Dry run: {0} was logged but not run")]
+    DryRun(&'static str),
+
+    /// A Steam (A2S) query of the running server failed, e.g. because it did not answer
+    /// or sent a malformed response.
+    #[error("Query error: {0}")]
+    QueryError(String),
+
     /// A general I/O error, which can occur during file operations like reading or
-    /// writing configuration files, logs, or the PID file. This variant wraps the
+    /// writing configuration files, logs, or the instance state file. This variant wraps the
     /// standard `std::io::Error`.
     #[error("I/O error: {0}")]
     IoError(#[from] io::Error),
@@ -52,3 +102,11 @@ pub enum InstanceError {
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
+
+fn format_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| format!("  - {issue}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,149 @@
+//! # Health Checks
+//!
+//! This module reports whether a server is alive and well, for `status` commands and
+//! Docker `HEALTHCHECK`s. It combines the instance state, the process table and, when the
+//! instance has a `query_port`, a Steam query, so a server that is running but no
+//! longer answering players is told apart from a healthy one.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Health, Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let health = instance.health();
+//! println!("Server {health}");
+//! std::process::exit(i32::from(!health.is_healthy()));
+//! ```
+use crate::instance::Instance;
+use crate::usage::{ProcessProbe, ProcessSample};
+use std::fmt;
+use std::time::Duration;
+
+/// The health of a server instance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Health {
+    /// The server process is running and, when it can be queried, answering.
+    Running {
+        pid: u32,
+        /// How long the process has been running.
+        uptime: Duration,
+        /// Resident memory in bytes.
+        rss: u64,
+        /// CPU usage in percent of one core.
+        cpu: f32,
+    },
+    /// No server process is running.
+    Stopped,
+    /// The server process is running but did not answer a Steam query.
+    Unresponsive { pid: u32, reason: String },
+}
+
+impl Health {
+    /// Returns whether the server is running and answering.
+    pub const fn is_healthy(&self) -> bool {
+        matches!(self, Self::Running { .. })
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running {
+                pid,
+                uptime,
+                rss,
+                cpu,
+            } => write!(
+                f,
+                "running (pid {pid}, up {}s, {} MiB, {cpu:.1}% CPU)",
+                uptime.as_secs(),
+                rss / (1024 * 1024)
+            ),
+            Self::Stopped => f.write_str("stopped"),
+            Self::Unresponsive { pid, reason } => write!(f, "unresponsive (pid {pid}): {reason}"),
+        }
+    }
+}
+
+impl Instance {
+    /// Checks whether the server is running and, if it has a `query_port`, answering
+    /// Steam queries.
+    ///
+    /// This takes a moment, because CPU usage is measured over a short interval.
+    pub fn health(&self) -> Health {
+        let Ok(pid) = self.pid() else {
+            return Health::Stopped;
+        };
+        let Some(ProcessSample { uptime, cpu, rss }) = ProcessProbe::new(pid).sample_now() else {
+            return Health::Stopped;
+        };
+        if self.config.query_port.is_some()
+            && let Err(e) = self.query()
+        {
+            return Health::Unresponsive {
+                pid,
+                reason: e.to_string(),
+            };
+        }
+        Health::Running {
+            pid,
+            uptime,
+            rss,
+            cpu,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use crate::state::InstanceState;
+    use tempfile::tempdir;
+
+    fn instance_with_pid(dir: &std::path::Path, pid: u32) -> Instance {
+        let config = InstanceConfig {
+            working_dir: dir.to_path_buf(),
+            ..InstanceConfig::default()
+        };
+        InstanceState::capture(pid, &config)
+            .write(&config.state_file())
+            .unwrap();
+        Instance::new(config)
+    }
+
+    #[test]
+    fn missing_or_stale_state_is_stopped() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        assert_eq!(instance.health(), Health::Stopped);
+
+        let instance = instance_with_pid(temp_dir.path(), 999_999);
+        assert_eq!(instance.health(), Health::Stopped);
+    }
+
+    #[test]
+    fn running_processes_report_their_usage() {
+        let temp_dir = tempdir().unwrap();
+        let instance = instance_with_pid(temp_dir.path(), std::process::id());
+        let health = instance.health();
+        assert!(health.is_healthy(), "{health}");
+        assert!(matches!(health, Health::Running { rss, .. } if rss > 0));
+    }
+
+    #[test]
+    fn unanswered_queries_are_unresponsive() {
+        let temp_dir = tempdir().unwrap();
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut instance = instance_with_pid(temp_dir.path(), std::process::id());
+        instance.config.query_port = Some(socket.local_addr().unwrap().port());
+
+        assert!(matches!(instance.health(), Health::Unresponsive { .. }));
+    }
+}
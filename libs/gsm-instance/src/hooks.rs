@@ -0,0 +1,453 @@
+//! # Lifecycle Hooks
+//!
+//! This module runs operator-supplied hooks around the server's lifecycle: before it
+//! starts, after it stops and after it is updated. A hook is either a shell command,
+//! which can be set in the instance config, or a Rust closure added by a game binary.
+//! Typical hooks repair a world before start or sync saves to network storage after stop.
+//!
+//! Each hook has a timeout, after which it is killed, and a [`FailurePolicy`] that decides
+//! whether its failure aborts the operation or is only logged. The output of shell hooks
+//! is captured and logged line by line under the `hook` target.
+//!
+//! Shell hooks run with `sh -c` in the instance's working directory, with the instance's
+//! `env` applied and `GSM_HOOK_STAGE` set to the stage name.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::hooks::{FailurePolicy, Hook};
+//! use std::time::Duration;
+//!
+//! let mut config = InstanceConfig::default();
+//! config.hooks.pre_start.push(
+//!     Hook::shell("repair-world", "./WorldRepair --fix")
+//!         .with_timeout(Duration::from_secs(600)),
+//! );
+//! config.hooks.post_stop.push(
+//!     Hook::closure("sync-saves", |config| {
+//!         Ok(format!("synced {}", config.working_dir.display()))
+//!     })
+//!     .with_on_failure(FailurePolicy::Continue),
+//! );
+//! ```
+use crate::config::InstanceConfig;
+use crate::errors::InstanceError;
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// How long a hook may run unless its `timeout_secs` says otherwise.
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// How often a running shell hook is checked for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The closure run by a [`HookAction::Closure`] hook. It returns the output to log, or
+/// a description of the failure.
+pub type HookFn = Arc<dyn Fn(&InstanceConfig) -> Result<String, String> + Send + Sync>;
+
+/// When in the server's lifecycle a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookStage {
+    /// Before the server process is launched.
+    PreStart,
+    /// After the server process has stopped.
+    PostStop,
+    /// After SteamCMD has updated the server.
+    PostUpdate,
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PreStart => "pre_start",
+            Self::PostStop => "post_stop",
+            Self::PostUpdate => "post_update",
+        })
+    }
+}
+
+/// What happens when a hook fails or times out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Stop running hooks and fail the operation. For a pre-start hook, the server is
+    /// not started.
+    #[default]
+    Abort,
+    /// Log the failure and carry on.
+    Continue,
+}
+
+/// What a hook runs.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HookAction {
+    /// A shell command, run with `sh -c`.
+    Shell(String),
+    /// A Rust closure. Cannot be set from a config file.
+    #[serde(skip)]
+    Closure(HookFn),
+}
+
+impl PartialEq for HookAction {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Shell(a), Self::Shell(b)) => a == b,
+            (Self::Closure(a), Self::Closure(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for HookAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shell(command) => f.debug_tuple("Shell").field(command).finish(),
+            Self::Closure(_) => f.write_str("Closure"),
+        }
+    }
+}
+
+/// A single hook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hook {
+    /// A name for the hook, used in logs and errors.
+    pub name: String,
+    /// What the hook runs.
+    #[serde(rename = "command")]
+    pub action: HookAction,
+    /// How long the hook may run, in seconds, before it is killed and counted as failed.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// What happens when the hook fails.
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+const fn default_timeout_secs() -> u64 {
+    DEFAULT_HOOK_TIMEOUT.as_secs()
+}
+
+impl Hook {
+    /// Creates a hook running `command` with `sh -c`.
+    pub fn shell(name: impl Into<String>, command: impl Into<String>) -> Self {
+        Self::new(name, HookAction::Shell(command.into()))
+    }
+
+    /// Creates a hook running `closure`.
+    pub fn closure(
+        name: impl Into<String>,
+        closure: impl Fn(&InstanceConfig) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self::new(name, HookAction::Closure(Arc::new(closure)))
+    }
+
+    fn new(name: impl Into<String>, action: HookAction) -> Self {
+        Self {
+            name: name.into(),
+            action,
+            timeout_secs: default_timeout_secs(),
+            on_failure: FailurePolicy::default(),
+        }
+    }
+
+    /// Sets how long the hook may run.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_secs = timeout.as_secs();
+        self
+    }
+
+    /// Sets what happens when the hook fails.
+    #[must_use]
+    pub const fn with_on_failure(mut self, on_failure: FailurePolicy) -> Self {
+        self.on_failure = on_failure;
+        self
+    }
+
+    /// Runs the hook, returning its captured output.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the failure when the hook cannot be started, exits
+    /// unsuccessfully or times out.
+    pub fn run(&self, stage: HookStage, config: &InstanceConfig) -> Result<String, String> {
+        let timeout = Duration::from_secs(self.timeout_secs);
+        match &self.action {
+            HookAction::Shell(command) => run_shell(command, stage, config, timeout),
+            HookAction::Closure(closure) => run_closure(closure, config, timeout),
+        }
+    }
+}
+
+/// The hooks of an instance, by stage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run before the server starts. An aborting failure keeps the server from starting.
+    pub pre_start: Vec<Hook>,
+    /// Run after the server has stopped.
+    pub post_stop: Vec<Hook>,
+    /// Run after the server has been updated.
+    pub post_update: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Returns the hooks of `stage`.
+    pub fn stage(&self, stage: HookStage) -> &[Hook] {
+        match stage {
+            HookStage::PreStart => &self.pre_start,
+            HookStage::PostStop => &self.post_stop,
+            HookStage::PostUpdate => &self.post_update,
+        }
+    }
+}
+
+/// Runs the hooks of `stage` in order.
+///
+/// # Errors
+///
+/// Returns [`InstanceError::HookFailed`] for the first failing hook whose policy is
+/// [`FailurePolicy::Abort`]; the hooks after it are not run.
+pub fn run_hooks(stage: HookStage, config: &InstanceConfig) -> Result<(), InstanceError> {
+    for hook in config.hooks.stage(stage) {
+        info!("Running {stage} hook '{}'", hook.name);
+        let started = Instant::now();
+        match hook.run(stage, config) {
+            Ok(_) => debug!(
+                "{stage} hook '{}' finished in {:?}",
+                hook.name,
+                started.elapsed()
+            ),
+            Err(reason) if hook.on_failure == FailurePolicy::Continue => {
+                warn!("{stage} hook '{}' failed, continuing: {reason}", hook.name);
+            }
+            Err(reason) => {
+                error!("{stage} hook '{}' failed: {reason}", hook.name);
+                return Err(InstanceError::HookFailed {
+                    hook: hook.name.clone(),
+                    stage,
+                    reason,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_shell(
+    command: &str,
+    stage: HookStage,
+    config: &InstanceConfig,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(&config.working_dir)
+        .envs(&config.env)
+        .env("GSM_HOOK_STAGE", stage.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Its own process group, so a timeout also kills whatever the shell started.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("failed to run sh: {e}"))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| thread::spawn(|| log_lines(out)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| thread::spawn(|| log_lines(err)));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() >= deadline => break None,
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("failed to wait for hook: {e}")),
+        }
+    };
+    if status.is_none() {
+        if let Ok(pid) = i32::try_from(child.id()) {
+            let _ = killpg(Pid::from_raw(pid), Signal::SIGKILL);
+        }
+        let _ = child.wait();
+    }
+
+    let mut output = Vec::new();
+    for reader in [stdout, stderr].into_iter().flatten() {
+        output.extend(reader.join().unwrap_or_default());
+    }
+    let output = output.join("\n");
+    match status {
+        Some(status) if status.success() => Ok(output),
+        Some(status) => Err(with_output(format!("exited with {status}"), &output)),
+        None => Err(with_output(format!("timed out after {timeout:?}"), &output)),
+    }
+}
+
+fn run_closure(
+    closure: &HookFn,
+    config: &InstanceConfig,
+    timeout: Duration,
+) -> Result<String, String> {
+    let (sender, receiver) = mpsc::channel();
+    let closure = Arc::clone(closure);
+    let config = config.clone();
+    thread::spawn(move || {
+        let _ = sender.send(closure(&config));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => {
+            if let Ok(output) = &result {
+                output
+                    .lines()
+                    .for_each(|line| info!(target: "hook", "{line}"));
+            }
+            result
+        }
+        // A closure cannot be killed; it is left to finish in the background.
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(format!("timed out after {timeout:?}")),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err("the hook panicked".to_owned()),
+    }
+}
+
+/// Logs each line read from `output` and returns them.
+fn log_lines(output: impl Read) -> Vec<String> {
+    BufReader::new(output)
+        .lines()
+        .map_while(Result::ok)
+        .inspect(|line| info!(target: "hook", "{line}"))
+        .collect()
+}
+
+/// Appends the last lines of a failed hook's output to `reason`.
+fn with_output(reason: String, output: &str) -> String {
+    const TAIL_LINES: usize = 10;
+    let lines: Vec<&str> = output.lines().collect();
+    let tail = lines
+        .get(lines.len().saturating_sub(TAIL_LINES)..)
+        .unwrap_or_default();
+    if tail.is_empty() {
+        reason
+    } else {
+        format!("{reason}; output:\n{}", tail.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config_with(hooks: Hooks) -> (tempfile::TempDir, InstanceConfig) {
+        let temp_dir = tempdir().unwrap();
+        let config = InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            hooks,
+            ..InstanceConfig::default()
+        };
+        (temp_dir, config)
+    }
+
+    #[test]
+    fn shell_hooks_capture_output_and_see_the_instance_environment() {
+        let (_temp_dir, mut config) = config_with(Hooks::default());
+        config
+            .env
+            .insert("GSM_TEST_HOOK".to_owned(), "hello".to_owned());
+
+        let output = Hook::shell("echo", "echo \"$GSM_TEST_HOOK $GSM_HOOK_STAGE\"; echo done")
+            .run(HookStage::PreStart, &config)
+            .unwrap();
+        assert_eq!(output, "hello pre_start\ndone");
+
+        let error = Hook::shell("fail", "echo broken >&2; exit 3")
+            .run(HookStage::PostStop, &config)
+            .unwrap_err();
+        assert!(error.contains("exit status: 3"));
+        assert!(error.contains("broken"));
+    }
+
+    #[test]
+    fn hooks_time_out() {
+        let (_temp_dir, config) = config_with(Hooks::default());
+        let started = Instant::now();
+
+        let error = Hook::shell("sleep", "sleep 30")
+            .with_timeout(Duration::from_secs(1))
+            .run(HookStage::PreStart, &config)
+            .unwrap_err();
+        assert!(error.contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let error = Hook::closure("slow", |_| {
+            thread::sleep(Duration::from_secs(2));
+            Ok(String::new())
+        })
+        .with_timeout(Duration::ZERO)
+        .run(HookStage::PostUpdate, &config)
+        .unwrap_err();
+        assert!(error.contains("timed out"));
+    }
+
+    #[test]
+    fn failure_policy_decides_whether_later_hooks_run() {
+        let (temp_dir, config) = config_with(Hooks {
+            post_stop: vec![
+                Hook::shell("ignored", "exit 1").with_on_failure(FailurePolicy::Continue),
+                Hook::shell("marker", "touch first"),
+                Hook::closure("abort", |_| Err("disk unavailable".to_owned())),
+                Hook::shell("never", "touch second"),
+            ],
+            ..Hooks::default()
+        });
+
+        let error = run_hooks(HookStage::PostStop, &config).unwrap_err();
+        assert!(matches!(
+            error,
+            InstanceError::HookFailed { ref hook, stage: HookStage::PostStop, ref reason }
+                if hook == "abort" && reason == "disk unavailable"
+        ));
+        assert!(temp_dir.path().join("first").exists());
+        assert!(!temp_dir.path().join("second").exists());
+        assert!(run_hooks(HookStage::PreStart, &config).is_ok());
+    }
+
+    #[test]
+    fn shell_hooks_deserialize_with_defaults() {
+        let hooks: Hooks = serde_json::from_str(
+            r#"{"pre_start": [{"name": "repair", "command": "./repair"}],
+                "post_stop": [{"name": "sync", "command": "rsync -a saves/ /mnt/nfs/",
+                               "timeout_secs": 60, "on_failure": "continue"}]}"#,
+        )
+        .unwrap();
+
+        let repair = hooks.pre_start.first().unwrap();
+        assert!(matches!(&repair.action, HookAction::Shell(command) if command == "./repair"));
+        assert_eq!(repair.timeout_secs, DEFAULT_HOOK_TIMEOUT.as_secs());
+        assert_eq!(repair.on_failure, FailurePolicy::Abort);
+        let sync = hooks.post_stop.first().unwrap();
+        assert_eq!(sync.timeout_secs, 60);
+        assert_eq!(sync.on_failure, FailurePolicy::Continue);
+        assert!(hooks.post_update.is_empty());
+    }
+}
@@ -6,45 +6,46 @@
 //!
 //! The main function, `install`, takes care of logging in, setting the installation
 //! directory, and running the `app_update` command with validation. It also supports
-//! additional arguments and environment variables for more advanced configurations,
-//! such as installing a beta branch.
+//! additional arguments and the options read into an [`EnvConfig`], such as installing a
+//! beta branch.
 //!
 //! # Example
 //!
 //! ```rust,no_run
 //! use std::path::Path;
+//! use gsm_instance::config::BetaConfig;
+//! use gsm_instance::env_config::EnvConfig;
 //! use gsm_instance::install::install;
 //!
-//! // Install a server with App ID 123456 to a specified directory.
+//! // Install the preview branch of App ID 123456 to a specified directory.
 //! let app_id = 123456;
 //! let install_dir = Path::new("/home/steam/myserver");
-//! let extra_args = vec!["-beta".to_string(), "preview".to_string()];
+//! let env_config = EnvConfig {
+//!     beta: Some(BetaConfig::new("preview")),
+//!     ..EnvConfig::default()
+//! };
 //!
-//! let status = install(app_id, install_dir, false, false, &extra_args)
-//!     .expect("Installation failed");
-//!
-//! assert!(status.success());
+//! install(app_id, install_dir, false, false, &[], &env_config).expect("Installation failed");
 //! ```
-use crate::executable::execute_mut;
-use crate::steamcmd::steamcmd_command;
-use std::env;
-use std::io;
+use crate::config::BetaConfig;
+use crate::env_config::EnvConfig;
+use crate::steamcmd::{STEAMCMD_RETRY, SteamCmdError, run_with_retries};
+use gsm_cron::RetryPolicy;
+use gsm_shared::redact_args;
 use std::path::Path;
-use std::process::{ExitStatus, Stdio};
 use tracing::{debug, info};
 
-/// Adds additional SteamCMD arguments from the `ADDITIONAL_STEAMCMD_ARGS` environment variable.
-///
-/// This function checks for the `ADDITIONAL_STEAMCMD_ARGS` environment variable and, if it
-/// is set, appends its value to the provided argument list. The value is trimmed of
-/// whitespace and surrounding quotes.
-fn add_additional_args(args: &mut Vec<String>) {
-    if let Ok(extra_args) = env::var("ADDITIONAL_STEAMCMD_ARGS") {
-        let trimmed = extra_args.trim_matches('"').trim();
-        if !trimmed.is_empty() {
-            args.push(trimmed.to_owned());
-        }
+/// Builds SteamCMD's `+app_update` command, selecting the beta branch from `env_config`.
+fn app_update_command(app_id: u32, env_config: &EnvConfig, validate: bool) -> String {
+    let mut command = format!("+app_update {app_id}");
+    for arg in env_config.beta.iter().flat_map(BetaConfig::steamcmd_args) {
+        command.push(' ');
+        command.push_str(&arg);
     }
+    if validate {
+        command.push_str(" validate");
+    }
+    command
 }
 
 /// Installs or updates a game server using SteamCMD.
@@ -62,48 +63,72 @@ fn add_additional_args(args: &mut Vec<String>) {
 /// - `skip_validate`: If `true`, omits SteamCMD's `validate` flag from `app_update`, so
 ///   existing files are trusted as-is instead of being re-checksummed. Useful for fast
 ///   restarts once a server is known-good, since validation can take a long time.
-/// - `extra_args`: A slice of extra arguments to append to the SteamCMD command, which
-///   can be used for things like specifying a beta branch.
+/// - `extra_args`: A slice of extra arguments to append to the SteamCMD command.
+/// - `env_config`: The beta branch and additional arguments read from the environment;
+///   see [`EnvConfig::from_env`].
 ///
 /// # Returns
 ///
-/// Returns an `io::Result<ExitStatus>` that indicates whether the SteamCMD process
-/// executed successfully.
+/// Returns `Ok(())` once SteamCMD has installed the server.
 ///
 /// # Behavior
 ///
 /// - The function logs in to Steam as an anonymous user.
 /// - It forces the installation to the specified `install_dir`.
-/// - It runs `app_update` with the `validate` option to ensure file integrity.
+/// - It runs `app_update` with the `validate` option to ensure file integrity, selecting
+///   the beta branch from `env_config` if one is set.
 /// - It appends any extra arguments from the `extra_args` parameter and the
-///   `ADDITIONAL_STEAMCMD_ARGS` environment variable.
-/// - The command's standard output and error are inherited, so they will be displayed
-///   in the console.
+///   additional arguments from `env_config`.
+/// - SteamCMD's output is logged line by line, and transient failures are retried; see
+///   [`run_with_retries`].
 ///
 /// # Errors
 ///
-/// Returns any I/O error encountered while spawning or waiting on the SteamCMD process.
+/// Returns a [`SteamCmdError`] when SteamCMD cannot be started, fails with a
+/// non-transient error, or keeps failing after its retries.
 pub fn install<P: AsRef<Path>>(
     app_id: u32,
     install_dir: P,
     force_windows: bool,
     skip_validate: bool,
     extra_args: &[String],
-) -> io::Result<ExitStatus> {
+    env_config: &EnvConfig,
+) -> Result<(), SteamCmdError> {
     info!(
         "Installing app {} to {}",
         app_id,
         install_dir.as_ref().display()
     );
+    let args = install_args(
+        app_id,
+        install_dir.as_ref(),
+        force_windows,
+        skip_validate,
+        extra_args,
+        env_config,
+    );
+    debug!("Launching install command: {:?}", redact_args(&args));
+    run_with_retries(
+        &args,
+        &env_config.downloads,
+        RetryPolicy::from_env("STEAMCMD", STEAMCMD_RETRY),
+        Some(&install_dir.as_ref().join("logs")),
+    )
+}
 
+/// Returns the SteamCMD arguments [`install`] runs, for the same parameters.
+pub(crate) fn install_args(
+    app_id: u32,
+    install_dir: &Path,
+    force_windows: bool,
+    skip_validate: bool,
+    extra_args: &[String],
+    env_config: &EnvConfig,
+) -> Vec<String> {
     // Base SteamCMD arguments.
     let login = "+login anonymous".to_owned();
-    let force_install_dir = format!("+force_install_dir {}", install_dir.as_ref().display());
-    let app_update = if skip_validate {
-        format!("+app_update {app_id}")
-    } else {
-        format!("+app_update {app_id} validate")
-    };
+    let force_install_dir = format!("+force_install_dir {}", install_dir.display());
+    let app_update = app_update_command(app_id, env_config, !skip_validate);
 
     // Start building the argument list.
     let mut args = vec![force_install_dir, login, app_update];
@@ -116,20 +141,10 @@ pub fn install<P: AsRef<Path>>(
     // Append any extra installation arguments.
     args.extend_from_slice(extra_args);
     // Append any additional arguments from environment variables.
-    add_additional_args(&mut args);
+    args.extend(env_config.additional_args.clone());
 
-    // Build the full SteamCMD command.
-    let mut steamcmd = steamcmd_command();
-    let command = steamcmd
-        .args(&args)
-        .arg("+quit")
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
-
-    debug!("Launching install command: {:#?}", command);
-
-    // Execute the command using our helper (assumed to be defined in executable.rs)
-    execute_mut(command)
+    args.push("+quit".to_owned());
+    args
 }
 
 #[cfg(test)]
@@ -141,7 +156,9 @@ mod tests {
         clippy::unreadable_literal
     )]
 
-    use super::{add_additional_args, install};
+    use super::install;
+    use crate::config::BetaConfig;
+    use crate::env_config::EnvConfig;
     use crate::test_support::env_lock;
     use std::fs;
     use std::path::Path;
@@ -157,49 +174,6 @@ mod tests {
         fs::set_permissions(path, permissions).unwrap();
     }
 
-    #[test]
-    fn add_additional_args_ignores_missing_or_blank_values() {
-        let _lock = env_lock()
-            .lock()
-            .unwrap_or_else(std::sync::PoisonError::into_inner);
-        let mut args = Vec::new();
-
-        unsafe {
-            std::env::remove_var("ADDITIONAL_STEAMCMD_ARGS");
-        }
-        add_additional_args(&mut args);
-        assert!(args.is_empty());
-
-        unsafe {
-            std::env::set_var("ADDITIONAL_STEAMCMD_ARGS", "   ");
-        }
-        add_additional_args(&mut args);
-        assert!(args.is_empty());
-
-        unsafe {
-            std::env::remove_var("ADDITIONAL_STEAMCMD_ARGS");
-        }
-    }
-
-    #[test]
-    fn add_additional_args_trims_wrapping_quotes() {
-        let _lock = env_lock()
-            .lock()
-            .unwrap_or_else(std::sync::PoisonError::into_inner);
-        let mut args = Vec::new();
-
-        unsafe {
-            std::env::set_var("ADDITIONAL_STEAMCMD_ARGS", "\"+app_info_update 1\"");
-        }
-
-        add_additional_args(&mut args);
-        assert_eq!(args, vec![String::from("+app_info_update 1")]);
-
-        unsafe {
-            std::env::remove_var("ADDITIONAL_STEAMCMD_ARGS");
-        }
-    }
-
     #[cfg(unix)]
     #[test]
     fn install_passes_expected_args_to_steamcmd() {
@@ -217,12 +191,23 @@ mod tests {
 
         unsafe {
             std::env::set_var("STEAMCMD_PATH", &script_path);
-            std::env::set_var("ADDITIONAL_STEAMCMD_ARGS", "\"+app_info_update 1\"");
         }
 
         let extra_args = vec![String::from("+download_depot 123 456")];
-        let status = install(2_278_520, temp_dir.path(), true, false, &extra_args).unwrap();
-        assert!(status.success());
+        let env_config = EnvConfig {
+            beta: Some(BetaConfig::new("experimental")),
+            additional_args: Some(String::from("+app_info_update 1")),
+            ..EnvConfig::default()
+        };
+        install(
+            2_278_520,
+            temp_dir.path(),
+            true,
+            false,
+            &extra_args,
+            &env_config,
+        )
+        .unwrap();
 
         let recorded_args = fs::read_to_string(&args_path).unwrap();
         let lines: Vec<&str> = recorded_args.lines().collect();
@@ -237,14 +222,16 @@ mod tests {
             Some(expected_force_install_dir.as_str())
         );
         assert_eq!(lines.get(2).copied(), Some("+login anonymous"));
-        assert_eq!(lines.get(3).copied(), Some("+app_update 2278520 validate"));
+        assert_eq!(
+            lines.get(3).copied(),
+            Some("+app_update 2278520 -beta experimental validate")
+        );
         assert_eq!(lines.get(4).copied(), Some("+download_depot 123 456"));
         assert_eq!(lines.get(5).copied(), Some("+app_info_update 1"));
         assert_eq!(lines.get(6).copied(), Some("+quit"));
 
         unsafe {
             std::env::remove_var("STEAMCMD_PATH");
-            std::env::remove_var("ADDITIONAL_STEAMCMD_ARGS");
         }
     }
 
@@ -267,8 +254,15 @@ mod tests {
             std::env::set_var("STEAMCMD_PATH", &script_path);
         }
 
-        let status = install(2_278_520, temp_dir.path(), false, true, &[]).unwrap();
-        assert!(status.success());
+        install(
+            2_278_520,
+            temp_dir.path(),
+            false,
+            true,
+            &[],
+            &EnvConfig::default(),
+        )
+        .unwrap();
 
         let recorded_args = fs::read_to_string(&args_path).unwrap();
         let lines: Vec<&str> = recorded_args.lines().collect();
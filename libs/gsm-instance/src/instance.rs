@@ -1,11 +1,23 @@
-use crate::config::InstanceConfig;
+use crate::clone::{CloneMode, CloneStats, app_manifest_path, clone_install};
+use crate::config::{ConfigIssue, InstanceConfig};
+use crate::env_config::EnvConfig;
 use crate::errors::InstanceError;
-use crate::process::send_interrupt_to_pid;
-use crate::{install, startup, update};
-use std::fs;
-use std::path::PathBuf;
-use std::process::Child; // Using synchronous std process Child
-use tracing::warn;
+use crate::hooks::{HookStage, run_hooks};
+use crate::process::ServerProcess;
+use crate::query::{PlayerInfo, QUERY_TIMEOUT, ServerInfo, query_info, query_players};
+use crate::rollback::{RollbackOutcome, RollbackPoint};
+use crate::shutdown::{StopOutcome, stop_grace_period, stop_process};
+use crate::state::InstanceState;
+use crate::update::UpdateStatus;
+use crate::workshop::WorkshopManifest;
+use crate::{dry_run, install, rollback, startup, update, workshop};
+use gsm_cron::ChildRegistry;
+use gsm_shared::WorkdirLock;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus}; // Using synchronous std process Child
+use std::time::Duration;
+use tracing::{info, warn};
 
 /// The main struct representing a game server instance.
 ///
@@ -22,124 +34,510 @@ impl Instance {
         Self { config }
     }
 
-    /// Reads and parses the current server PID from the pid file.
+    /// Collects every problem with this instance's configuration: those found by
+    /// [`InstanceConfig::issues`], plus a working directory this process cannot write to
+    /// and, once the server is installed, a `command` that resolves to no executable in
+    /// the working directory or on `PATH`.
+    pub fn config_issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = self.config.issues();
+        if issues.iter().any(|issue| issue.field == "working_dir") {
+            return issues;
+        }
+
+        let writable_dir = if self.config.working_dir.is_dir() {
+            Some(self.config.working_dir.as_path())
+        } else {
+            self.config
+                .working_dir
+                .parent()
+                .filter(|parent| parent.is_dir())
+        };
+        if let Some(dir) = writable_dir
+            && let Err(e) = tempfile::tempfile_in(dir)
+        {
+            issues.push(ConfigIssue::new(
+                "working_dir",
+                format!("{} is not writable: {e}", dir.display()),
+                "fix its ownership or permissions, or choose another directory",
+            ));
+        }
+
+        let command = self.config.command.trim();
+        if !command.is_empty() && self.manifest_path().exists() && !self.command_exists(command) {
+            issues.push(ConfigIssue::new(
+                "command",
+                format!(
+                    "'{command}' is neither in {} nor on PATH",
+                    self.config.working_dir.display()
+                ),
+                "check the executable's name and path relative to working_dir, or reinstall",
+            ));
+        }
+        issues
+    }
+
+    /// Validates this instance's configuration, failing with every problem found by
+    /// [`Self::config_issues`]. Apps call this before installing or starting the server,
+    /// so a misconfigured container fails with a list of fixes instead of a cryptic
+    /// SteamCMD or launch error.
     ///
     /// # Errors
     ///
-    /// Returns an error when the pid file is missing, unreadable, or contains
-    /// an invalid integer PID.
-    pub fn pid(&self) -> Result<u32, InstanceError> {
-        let pid_file = self.config.pid_file();
-        if pid_file.exists() {
-            // Read the PID from the file
-            return fs::read_to_string(&pid_file)
-                .map_err(InstanceError::IoError)?
-                .trim()
-                .parse::<u32>()
-                .map_err(InstanceError::ParseError);
+    /// Returns [`InstanceError::InvalidConfig`] listing each problem and a suggested fix.
+    pub fn validate_config(&self) -> Result<(), InstanceError> {
+        let issues = self.config_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InstanceError::InvalidConfig(issues))
         }
-        Err(InstanceError::Unknown("Failed to find pid".to_owned()))
+    }
+
+    /// Returns whether `command` names a file in the working directory or, when it is a
+    /// bare name, an executable on `PATH`.
+    fn command_exists(&self, command: &str) -> bool {
+        self.config.working_dir.join(command).is_file()
+            || (!command.contains('/') && which::which(command).is_ok())
+    }
+
+    /// Returns the state recorded when the server was started, or `None` when it is not
+    /// running. State left behind by a server that is gone, or whose pid now belongs to
+    /// another process, is discarded; see [`InstanceState::load`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the state file is unreadable or invalid.
+    pub fn state(&self) -> Result<Option<InstanceState>, InstanceError> {
+        InstanceState::load(&self.config.state_file())
+    }
+
+    /// Returns the pid of the running server, from its validated state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the server is not running, or the state file is
+    /// unreadable or invalid.
+    pub fn pid(&self) -> Result<u32, InstanceError> {
+        self.state()?
+            .map(|state| state.pid)
+            .ok_or_else(|| InstanceError::Unknown("Failed to find pid".to_owned()))
     }
 
     /// Installs the server using SteamCMD.
     ///
+    /// When `CLONE_FROM` points at an existing install of the same app and this instance
+    /// has not been installed yet, the files are seeded from there first (see
+    /// [`Self::install_from`]); `CLONE_MODE` selects `copy` (default) or `hardlink`.
+    ///
+    /// With a [`shared_install`](InstanceConfig::shared_install), the working directory is
+    /// linked to the shared install instead; see
+    /// [`SharedInstall::link`](crate::shared::SharedInstall::link).
+    ///
     /// # Errors
     ///
-    /// Returns an error when SteamCMD cannot be launched or exits with a failure status.
+    /// Returns an error when linking a shared install fails, cloning fails, the SteamCMD environment (see
+    /// [`EnvConfig`]) is invalid, another process holds the working directory (see
+    /// [`WorkdirLock`]), or SteamCMD fails; see
+    /// [`SteamCmdError`](crate::steamcmd::SteamCmdError).
     pub fn install(&self) -> Result<(), InstanceError> {
-        let status = install::install(
+        if let Some(shared) = &self.config.shared_install {
+            if self.config.dry_run {
+                info!(
+                    "Dry run: would link {} to the shared install at {}",
+                    self.config.working_dir.display(),
+                    shared.base.display()
+                );
+                return Ok(());
+            }
+            return shared
+                .link(&self.config.working_dir, self.config.app_id)
+                .map(drop);
+        }
+        let clone_from = std::env::var("CLONE_FROM").unwrap_or_default();
+        if !clone_from.trim().is_empty() && !self.manifest_path().exists() {
+            let mode = std::env::var("CLONE_MODE")
+                .map_or_else(|_| Ok(CloneMode::default()), |mode| mode.parse())?;
+            return self
+                .install_from(Path::new(clone_from.trim()), mode)
+                .map(drop);
+        }
+        let _lock = self.lock_workdir()?;
+        self.run_install(self.config.skip_validate)
+    }
+
+    /// Installs the server by cloning an existing install of the same app from `source`,
+    /// then running a validating SteamCMD install to reconcile any differences.
+    ///
+    /// Validation always runs here, regardless of `skip_validate`, since the cloned
+    /// files have not been checked against the depot. A dry run clones nothing and
+    /// returns empty stats.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `source` is not an install of this app, another process
+    /// holds the working directory, a file cannot be cloned, or the validating install
+    /// fails.
+    pub fn install_from(
+        &self,
+        source: &Path,
+        mode: CloneMode,
+    ) -> Result<CloneStats, InstanceError> {
+        if self.config.dry_run {
+            info!(
+                "Dry run: would clone app {} from {} ({mode:?})",
+                self.config.app_id,
+                source.display()
+            );
+            self.run_install(false)?;
+            return Ok(CloneStats::default());
+        }
+        let _lock = self.lock_workdir()?;
+        let stats = clone_install(source, &self.config.working_dir, self.config.app_id, mode)?;
+        info!("Validating cloned install of app {}", self.config.app_id);
+        self.run_install(false)?;
+        Ok(stats)
+    }
+
+    fn run_install(&self, skip_validate: bool) -> Result<(), InstanceError> {
+        let options = self.steamcmd_options()?;
+        if self.config.dry_run {
+            self.log_steamcmd_dry_run(skip_validate, &options);
+            return Ok(());
+        }
+        install::install(
             self.config.app_id,
             &self.config.working_dir,
-            self.config.force_windows,
-            self.config.skip_validate,
+            self.config.launch_mode.is_windows(),
+            skip_validate,
             &self.config.install_args,
-        )
-        .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(InstanceError::CommandExecutionError(format!(
-                "Install failed with status {status:?}"
-            )))
-        }
+            &options,
+        )?;
+        Ok(())
     }
 
-    /// Updates the server installation.
+    /// Logs the SteamCMD command line an install with `options` would run.
+    fn log_steamcmd_dry_run(&self, skip_validate: bool, options: &EnvConfig) {
+        let args = install::install_args(
+            self.config.app_id,
+            &self.config.working_dir,
+            self.config.launch_mode.is_windows(),
+            skip_validate,
+            &self.config.install_args,
+            options,
+        );
+        info!(
+            "Dry run: would run {}",
+            dry_run::steamcmd_line(&args, &options.downloads)
+        );
+    }
+
+    /// Updates the server installation, first recording the installed build as the
+    /// point [`Self::rollback`] returns to.
     ///
     /// # Errors
     ///
-    /// Returns an error when update command execution fails.
+    /// Returns an error when the SteamCMD environment (see [`EnvConfig`]) is invalid,
+    /// another process holds the working directory (see [`WorkdirLock`]), the rollback
+    /// point cannot be recorded, update command execution fails, or an aborting
+    /// `post_update` hook fails. Instances of a shared install cannot be updated.
     pub fn update(&self) -> Result<(), InstanceError> {
+        self.refuse_shared("updated")?;
+        let options = self.steamcmd_options()?;
+        if self.config.dry_run {
+            self.log_steamcmd_dry_run(false, &options);
+            return Ok(());
+        }
+        let _lock = self.lock_workdir()?;
+        RollbackPoint::record(&self.config, rollback::snapshot_enabled())?;
         update::update_server(
             self.config.app_id,
             &self.config.working_dir,
-            self.config.force_windows,
+            self.config.launch_mode.is_windows(),
             &self.config.install_args,
+            &options,
         )?;
-        Ok(())
+        run_hooks(HookStage::PostUpdate, &self.config)
     }
 
-    /// Checks whether an update is available for the server.
-    pub fn update_available(&self) -> bool {
-        let manifest_path: PathBuf = self
-            .config
-            .working_dir
-            .join("steamapps")
-            .join(format!("appmanifest_{}.acf", self.config.app_id));
-        let appinfo_path: PathBuf = std::env::var("STEAM_APPINFO_PATH").map_or_else(
-            |_| PathBuf::from("/home/steam/Steam/appcache/appinfo.vdf"),
-            PathBuf::from,
-        );
+    /// Returns the server to the build installed before the last [`Self::update`]; see
+    /// [`rollback::rollback`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the SteamCMD environment is invalid, another process holds
+    /// the working directory, no build was recorded before an update, or reinstalling
+    /// fails without a snapshot to restore. Instances of a shared install cannot be
+    /// rolled back.
+    pub fn rollback(&self) -> Result<RollbackOutcome, InstanceError> {
+        self.refuse_shared("rolled back")?;
+        let options = self.steamcmd_options()?;
+        let _lock = self.lock_workdir()?;
+        rollback::rollback(&self.config, &options)
+    }
+
+    /// Locks the working directory for an operation that changes the install, so another
+    /// container sharing it cannot run SteamCMD in it at the same time. Dry runs change
+    /// nothing and take no lock.
+    fn lock_workdir(&self) -> Result<Option<WorkdirLock>, InstanceError> {
+        if self.config.dry_run {
+            return Ok(None);
+        }
+        Ok(Some(WorkdirLock::acquire(&self.config.working_dir)?))
+    }
+
+    /// Fails for an instance of a shared install, whose game files belong to the base
+    /// install and cannot be `changed` through it.
+    fn refuse_shared(&self, changed: &str) -> Result<(), InstanceError> {
+        self.config
+            .shared_install
+            .as_ref()
+            .map_or(Ok(()), |shared| {
+                Err(InstanceError::ConfigError(format!(
+                    "{} shares the install at {}, which must be {changed} instead",
+                    self.config.working_dir.display(),
+                    shared.base.display()
+                )))
+            })
+    }
+
+    /// Returns the SteamCMD options from the environment, with this instance's `beta`
+    /// branch, if set, taking precedence over `USE_BETA`/`BETA_BRANCH`, and its
+    /// `downloads` options over the environment's.
+    fn steamcmd_options(&self) -> Result<EnvConfig, InstanceError> {
+        let mut options = EnvConfig::from_env()?;
+        if self.config.beta.is_some() {
+            options.beta.clone_from(&self.config.beta);
+        }
+        options.downloads = self.config.downloads.clone().or(options.downloads);
+        Ok(options)
+    }
+
+    /// Downloads (or updates) the Steam Workshop items `items` and installs them as set
+    /// up in the instance's [`WorkshopConfig`](crate::workshop::WorkshopConfig), returning
+    /// the updated manifest of installed items.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the SteamCMD environment is invalid, SteamCMD fails, an item
+    /// was not downloaded, or the items or manifest cannot be written; see
+    /// [`workshop::download_items`].
+    pub fn download_workshop_items(
+        &self,
+        items: &[u64],
+    ) -> Result<WorkshopManifest, InstanceError> {
+        workshop::download_items(&self.config, items, &self.steamcmd_options()?.downloads)
+    }
+
+    /// Returns the Steam Workshop items installed for this instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the manifest exists but cannot be read.
+    pub fn workshop_items(&self) -> Result<WorkshopManifest, InstanceError> {
+        WorkshopManifest::load(&WorkshopManifest::path(&self.config.working_dir))
+    }
+
+    /// Returns the path to the SteamCMD app manifest for this instance.
+    fn manifest_path(&self) -> PathBuf {
+        app_manifest_path(&self.config.working_dir, self.config.app_id)
+    }
+
+    /// Returns the build ID of the currently installed server, if known.
+    pub fn installed_build_id(&self) -> Option<String> {
+        update::installed_build_id(&self.manifest_path())
+    }
+
+    /// Returns the branch updates are checked against: the instance's `beta` branch,
+    /// else `BETA_BRANCH` when `USE_BETA` is set, else `public`.
+    fn update_branch(&self) -> Result<String, InstanceError> {
+        Ok(self
+            .steamcmd_options()?
+            .beta
+            .map_or_else(|| "public".to_owned(), |beta| beta.branch))
+    }
+
+    /// Asks SteamCMD for the latest build ID of the server's branch; see
+    /// [`update::latest_build_id`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the SteamCMD environment is invalid or SteamCMD fails.
+    pub fn latest_build_id(&self) -> Result<Option<String>, InstanceError> {
+        update::latest_build_id(self.config.app_id, &self.update_branch()?)
+    }
+
+    /// Checks whether an update is available for the server, comparing the installed
+    /// build with the latest build of its branch (see [`Self::latest_build_id`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the SteamCMD environment is invalid, the app manifest cannot
+    /// be read (e.g. the server is not installed), or SteamCMD fails to print the app
+    /// info. Build IDs that cannot be parsed are reported as [`UpdateStatus::Unknown`].
+    pub fn update_available(&self) -> Result<UpdateStatus, InstanceError> {
+        let branch = self.update_branch()?;
+        let app_info = update::fetch_app_info(self.config.app_id)?;
+        update::update_status(
+            &self.manifest_path(),
+            &app_info,
+            self.config.app_id,
+            &branch,
+        )
+    }
+
+    /// Queries the running server for its name, map and player count.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `query_port` is not configured, or when the server does not
+    /// answer the query.
+    pub fn query(&self) -> Result<ServerInfo, InstanceError> {
+        query_info(self.query_addr()?, QUERY_TIMEOUT)
+    }
+
+    /// Queries the running server for the players currently online.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `query_port` is not configured, or when the server does not
+    /// answer the query.
+    pub fn query_players(&self) -> Result<Vec<PlayerInfo>, InstanceError> {
+        query_players(self.query_addr()?, QUERY_TIMEOUT)
+    }
 
-        update::update_is_available(&manifest_path, &appinfo_path).unwrap_or(false)
+    fn query_addr(&self) -> Result<SocketAddr, InstanceError> {
+        self.config
+            .query_port
+            .map(|port| SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .ok_or_else(|| {
+                InstanceError::ConfigError("query_port is not set; cannot query the server".into())
+            })
     }
 
     /// Starts the server as a daemonized process.
     ///
-    /// This method uses the synchronous startup function from startup.rs.
+    /// This method uses the synchronous startup function from startup.rs. When the
+    /// config has a [`readiness`](InstanceConfig::readiness) check, it then waits for the
+    /// server to pass it.
     /// # Returns
     /// A handle to the spawned child process.
     ///
     /// # Errors
     ///
-    /// Returns an error when process launch or startup verification fails.
+    /// Returns [`InstanceError::PortInUse`] when one of the server's ports is taken, and
+    /// an error when an aborting `pre_start` hook fails, or process launch or startup
+    /// verification fails. A server that starts but does not become ready is left
+    /// running and reported as [`InstanceError::NotReady`]. A dry run logs the launch
+    /// command and returns [`InstanceError::DryRun`].
     pub fn start(&self) -> Result<Child, InstanceError> {
-        startup::start_daemonized(&self.config)
-            .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))
+        self.dry_run_launch("start")?;
+        self.check_ports()?;
+        run_hooks(HookStage::PreStart, &self.config)?;
+        let readiness = self
+            .config
+            .readiness
+            .as_ref()
+            .map(|readiness| readiness.watch(&self.config.working_dir));
+        let child = startup::start_daemonized(&self.config)
+            .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
+        if let Some(readiness) = readiness {
+            readiness.wait(&ServerProcess::new(child.id()))?;
+        }
+        Ok(child)
+    }
+
+    /// Runs the server in the foreground until it exits, with the `pre_start` hooks before
+    /// it and the `post_stop` hooks after; see [`startup::run_foreground`]. Used instead
+    /// of [`Instance::start`] when [`InstanceConfig::daemonize`] is `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::PortInUse`] when one of the server's ports is taken, and
+    /// an error when an aborting hook fails or the server cannot be launched or waited on.
+    /// A server that exits unsuccessfully is reported through the returned status
+    /// instead. A dry run logs the launch command and returns [`InstanceError::DryRun`].
+    pub fn run_foreground(&self) -> Result<ExitStatus, InstanceError> {
+        self.dry_run_launch("run_foreground")?;
+        self.check_ports()?;
+        run_hooks(HookStage::PreStart, &self.config)?;
+        let status = startup::run_foreground(&self.config)?;
+        run_hooks(HookStage::PostStop, &self.config)?;
+        Ok(status)
+    }
+
+    /// In a dry run, logs the command that would launch the server and fails with
+    /// [`InstanceError::DryRun`] for `operation`, as there is no process to return.
+    fn dry_run_launch(&self, operation: &'static str) -> Result<(), InstanceError> {
+        if !self.config.dry_run {
+            return Ok(());
+        }
+        info!(
+            "Dry run: would launch {}",
+            dry_run::launch_line(&self.config)
+        );
+        Err(InstanceError::DryRun(operation))
     }
 
-    /// Stops the server gracefully.
+    /// Stops the server, escalating from SIGINT to SIGTERM to SIGKILL when it does not
+    /// exit within the grace period from `STOP_GRACE_PERIOD`; see
+    /// [`Instance::stop_with_grace`].
     ///
-    /// Without a pid file we have no reliable way to identify which running
+    /// # Errors
+    ///
+    /// Returns an error when the process survives SIGKILL, the state file cannot be
+    /// removed, or an aborting `post_stop` hook fails.
+    pub fn stop(&self) -> Result<StopOutcome, InstanceError> {
+        self.stop_with_grace(stop_grace_period())
+    }
+
+    /// Stops the server in stages: SIGINT, then SIGTERM after `grace`, then SIGKILL after
+    /// `grace` again. The state file is only removed once the process has exited.
+    ///
+    /// Without valid state we have no reliable way to identify which running
     /// process is "the server" — falling back to a fuzzy name match against
     /// `config.command` (e.g. `/bin/bash` for scripted launches) can match
     /// unrelated processes, including the caller's own parent shell. So if
-    /// the pid file is missing, this is treated as already-stopped rather
-    /// than guessing.
+    /// the state is missing or stale, this is treated as already-stopped
+    /// rather than guessing.
+    ///
+    /// The `post_stop` hooks run once a running server has stopped. A dry run logs the
+    /// signals it would send and leaves the server running.
     ///
     /// # Errors
     ///
-    /// Returns an error when the pid file cannot be removed after signalling the process.
-    pub fn stop(&self) -> Result<(), InstanceError> {
-        if let Ok(pid) = self.pid() {
-            send_interrupt_to_pid(pid);
-            fs::remove_file(self.config.pid_file()).map_err(InstanceError::IoError)?;
-        } else {
-            warn!("No pid file found; assuming server is already stopped.");
+    /// Returns an error when the process survives SIGKILL, the state file cannot be
+    /// read or removed, or an aborting `post_stop` hook fails.
+    pub fn stop_with_grace(&self, grace: Duration) -> Result<StopOutcome, InstanceError> {
+        let Some(state) = self.state()? else {
+            warn!("No running server recorded; assuming server is already stopped.");
+            return Ok(StopOutcome::NotRunning);
+        };
+        if self.config.dry_run {
+            info!("Dry run: would {}", dry_run::stop_plan(state.pid, grace));
+            return Ok(StopOutcome::DryRun);
         }
-        Ok(())
+        let outcome = stop_process(&state.process(), grace)?;
+        ChildRegistry::global().unregister(state.pid);
+        InstanceState::remove(&self.config.state_file())?;
+        if outcome != StopOutcome::NotRunning {
+            run_hooks(HookStage::PostStop, &self.config)?;
+        }
+        Ok(outcome)
     }
 
-    /// Restarts the server by stopping and then starting it.
+    /// Restarts the server by stopping and then starting it. A server running in the
+    /// foreground is relaunched there; see [`Instance::relaunch`].
     ///
     /// # Errors
     ///
     /// Returns an error when either stopping or starting the server fails.
     pub fn restart(&self) -> Result<(), InstanceError> {
+        let hold = self.hold_foreground_relaunch()?;
         self.stop()?;
-        // Optionally, insert a delay if needed.
-        self.start()?;
-        Ok(())
+        match self.relaunch(hold) {
+            Ok(_) | Err(InstanceError::DryRun(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
     }
 }
 
@@ -148,47 +546,83 @@ mod tests {
     #![allow(clippy::unwrap_used)]
 
     use super::*;
+    use crate::config::BetaConfig;
+    use crate::shared::SharedInstall;
+    use crate::test_support::env_lock;
     use std::fs;
     use tempfile::tempdir;
 
     #[test]
-    fn pid_reads_pid_file_and_reports_missing_files() {
+    fn pid_reads_the_state_and_rejects_reused_pids() {
         let temp_dir = tempdir().unwrap();
-        let pid_path = temp_dir.path().join("instance.pid");
-        fs::write(&pid_path, "12345\n").unwrap();
-
         let instance = Instance::new(InstanceConfig {
             working_dir: temp_dir.path().to_path_buf(),
             ..InstanceConfig::default()
         });
+        assert!(instance.pid().is_err());
 
-        assert_eq!(instance.pid().unwrap(), 12345);
+        let state = InstanceState::capture(std::process::id(), &instance.config);
+        state.write(&instance.config.state_file()).unwrap();
+        assert_eq!(instance.pid().unwrap(), std::process::id());
 
-        fs::remove_file(&pid_path).unwrap();
+        // The same pid, but a different process: the recorded one is gone.
+        InstanceState {
+            started_at: state.started_at.saturating_sub(3600),
+            ..state
+        }
+        .write(&instance.config.state_file())
+        .unwrap();
         assert!(instance.pid().is_err());
+        assert!(!instance.config.state_file().exists());
+    }
+
+    #[test]
+    fn query_requires_a_query_port() {
+        let instance = Instance::new(InstanceConfig::default());
+        assert!(matches!(
+            instance.query(),
+            Err(InstanceError::ConfigError(_))
+        ));
     }
 
     #[test]
     fn update_available_uses_environment_override() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
         let temp_dir = tempdir().unwrap();
         let manifest_path = temp_dir.path().join("steamapps/appmanifest_2278520.acf");
         fs::create_dir_all(manifest_path.parent().unwrap()).unwrap();
         fs::write(&manifest_path, r#""AppState" { "buildid" "1000" }"#).unwrap();
 
-        let appinfo_path = temp_dir.path().join("appinfo.vdf");
-        fs::write(&appinfo_path, r#""appinfo" { "buildid" "2000" }"#).unwrap();
+        let appinfo_path = temp_dir.path().join("appinfo.txt");
+        fs::write(
+            &appinfo_path,
+            r#""2278520" { "depots" { "branches" {
+                "public" { "buildid" "2000" }
+                "experimental" { "buildid" "1000" }
+            } } }"#,
+        )
+        .unwrap();
 
         unsafe {
             std::env::set_var("STEAM_APPINFO_PATH", &appinfo_path);
         }
 
-        let instance = Instance::new(InstanceConfig {
+        let mut instance = Instance::new(InstanceConfig {
             app_id: 2_278_520,
             working_dir: temp_dir.path().to_path_buf(),
             ..InstanceConfig::default()
         });
+        assert!(instance.update_available().unwrap().is_available());
 
-        assert!(instance.update_available());
+        instance.config.beta = Some(BetaConfig::new("experimental"));
+        assert_eq!(
+            instance.update_available().unwrap(),
+            UpdateStatus::UpToDate {
+                build_id: "1000".to_owned()
+            }
+        );
 
         unsafe {
             std::env::remove_var("STEAM_APPINFO_PATH");
@@ -196,18 +630,139 @@ mod tests {
     }
 
     #[test]
-    fn stop_removes_pid_file_when_present() {
+    fn stop_removes_stale_state_when_present() {
         let temp_dir = tempdir().unwrap();
-        let pid_path = temp_dir.path().join("instance.pid");
-        fs::write(&pid_path, "999999\n").unwrap();
+        let instance = Instance::new(InstanceConfig {
+            command: "nonexistent-command".to_owned(),
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        InstanceState::capture(999_999, &instance.config)
+            .write(&instance.config.state_file())
+            .unwrap();
+
+        assert_eq!(instance.stop().unwrap(), StopOutcome::NotRunning);
+        assert!(!instance.config.state_file().exists());
+    }
 
+    #[test]
+    fn dry_runs_change_nothing() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", temp_dir.path().join("missing-steamcmd"));
+        }
         let instance = Instance::new(InstanceConfig {
+            app_id: 2_278_520,
             command: "nonexistent-command".to_owned(),
             working_dir: temp_dir.path().to_path_buf(),
+            dry_run: true,
+            ..InstanceConfig::default()
+        });
+
+        instance.install().unwrap();
+        instance.update().unwrap();
+        assert!(RollbackPoint::load(temp_dir.path()).unwrap().is_none());
+        assert!(!temp_dir.path().join(gsm_shared::LOCK_FILE_NAME).exists());
+        assert!(matches!(
+            instance.start(),
+            Err(InstanceError::DryRun("start"))
+        ));
+        instance.restart().unwrap();
+
+        // This process stands in for a running server; it must not be signalled.
+        InstanceState::capture(std::process::id(), &instance.config)
+            .write(&instance.config.state_file())
+            .unwrap();
+        assert_eq!(instance.stop().unwrap(), StopOutcome::DryRun);
+        assert!(instance.config.state_file().exists());
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+
+    #[test]
+    fn updates_refuse_a_locked_working_directory() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            app_id: 2_278_520,
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+
+        let _held = WorkdirLock::acquire(temp_dir.path()).unwrap();
+        let error = instance.update().unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains(&format!("is locked by pid {}", std::process::id())),
+            "{error}"
+        );
+        assert!(RollbackPoint::load(temp_dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn shared_installs_are_linked_and_not_updated() {
+        let temp_dir = tempdir().unwrap();
+        let base = Instance::new(InstanceConfig {
+            app_id: 2_278_520,
+            working_dir: temp_dir.path().join("base"),
+            ..InstanceConfig::default()
+        });
+        fs::create_dir_all(base.config.working_dir.join("steamapps")).unwrap();
+        fs::write(base.manifest_path(), r#""AppState" { "buildid" "7" }"#).unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().join("test"),
+            shared_install: Some(SharedInstall::new(&base.config.working_dir)),
+            ..base.config
+        });
+
+        instance.install().unwrap();
+        assert_eq!(instance.installed_build_id().as_deref(), Some("7"));
+        let error = instance.update().unwrap_err();
+        assert!(
+            error.to_string().contains("must be updated instead"),
+            "{error}"
+        );
+        assert!(instance.rollback().is_err());
+    }
+
+    #[test]
+    fn config_issues_check_the_installed_command() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            app_id: 2_278_520,
+            command: "./PalServer.sh".to_owned(),
+            working_dir: temp_dir.path().to_path_buf(),
             ..InstanceConfig::default()
         });
+        // Not installed yet, so the command is not expected to exist.
+        instance.validate_config().unwrap();
 
-        instance.stop().unwrap();
-        assert!(!pid_path.exists());
+        fs::create_dir_all(temp_dir.path().join("steamapps")).unwrap();
+        fs::write(instance.manifest_path(), r#""AppState" { "buildid" "1" }"#).unwrap();
+        let fields: Vec<_> = instance
+            .config_issues()
+            .iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert_eq!(fields, ["command"]);
+
+        fs::write(temp_dir.path().join("PalServer.sh"), "").unwrap();
+        instance.validate_config().unwrap();
+        assert!(
+            Instance::new(InstanceConfig {
+                command: "sh".to_owned(),
+                ..instance.config
+            })
+            .validate_config()
+            .is_ok()
+        );
     }
 }
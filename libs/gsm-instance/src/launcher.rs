@@ -8,13 +8,16 @@
 //! and constructs a `Command` ready to be executed.
 use crate::config::InstanceConfig;
 use crate::config::LaunchMode;
+use crate::env_config::is_truthy;
 use crate::errors::InstanceError;
 use crate::proton;
-use crate::proton::ProtonConfig;
+use crate::proton::{ProtonConfig, ProtonSelector};
+use crate::resources;
 use std::env;
+use std::ffi::OsString;
 use std::fs::File;
 use std::fs::create_dir_all;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use tracing::{debug, error};
 use which::which;
@@ -51,26 +54,50 @@ impl WindowsCompat {
     }
 }
 
-/// Tries to find a Proton installation, either a specific version or any available version.
+/// Returns the Proton compatdata directory for `config`.
+///
+/// That is `STEAM_COMPAT_DATA_PATH` from the instance's `env` or the process environment,
+/// else `steamapps/compatdata/<app id>` under the working directory, where Steam keeps it.
+pub fn compatdata_path(config: &InstanceConfig) -> PathBuf {
+    config
+        .env
+        .get("STEAM_COMPAT_DATA_PATH")
+        .cloned()
+        .or_else(|| env::var("STEAM_COMPAT_DATA_PATH").ok())
+        .filter(|path| !path.trim().is_empty())
+        .map_or_else(
+            || {
+                config
+                    .working_dir
+                    .join("steamapps")
+                    .join("compatdata")
+                    .join(config.app_id.to_string())
+            },
+            PathBuf::from,
+        )
+}
+
+/// Tries to find the Proton installation chosen by `selector`.
 fn try_find_proton(
-    version_option: Option<&str>,
+    selector: &ProtonSelector,
     force_proton: bool,
-    app_id: u32,
+    instance: &InstanceConfig,
 ) -> Result<WindowsCompat, String> {
-    match proton::find_proton(version_option) {
+    match proton::find_proton(selector) {
         Ok(mut config) => {
-            let version_desc = version_option.unwrap_or("any version");
-            debug!("Found Proton {} at {}", version_desc, config.path);
-            config.app_id = app_id.to_string();
-            Ok(setup_proton_config(config))
+            debug!("Found Proton {} at {}", config.version, config.path);
+            config.app_id = instance.app_id.to_string();
+            Ok(setup_proton_config(config, &compatdata_path(instance)))
         }
         Err(e) => {
-            let err_msg = version_option.map_or_else(
-                || format!("Proton not found: {e}"),
-                |v| format!("Failed to find or download Proton {v}: {e}"),
-            );
+            let pinned = *selector != ProtonSelector::Latest;
+            let err_msg = if pinned {
+                format!("Failed to find or download Proton {selector}: {e}")
+            } else {
+                format!("Proton not found: {e}")
+            };
 
-            if version_option.is_some() {
+            if pinned {
                 error!("{}", err_msg);
             } else {
                 debug!("{}", err_msg);
@@ -85,16 +112,15 @@ fn try_find_proton(
     }
 }
 
-/// Sets up the Proton prefix and environment variables for a given `ProtonConfig`.
-fn setup_proton_config(mut config: ProtonConfig) -> WindowsCompat {
-    if let Ok(home) = env::var("HOME") {
-        let prefix_path = format!("{home}/.proton/prefixes/gsm");
-        debug!("Setting up Proton prefix at: {}", prefix_path);
-        if let Err(e) = proton::setup_prefix(&mut config, &prefix_path) {
-            error!("Failed to set up Proton prefix: {}", e);
-        } else {
-            debug!("Successfully set up Proton prefix");
-        }
+/// Sets up the Proton prefix in `compatdata`, creating it on first launch, and the
+/// environment variables for a given `ProtonConfig`.
+fn setup_proton_config(mut config: ProtonConfig, compatdata: &Path) -> WindowsCompat {
+    let prefix_path = compatdata.to_string_lossy();
+    debug!("Setting up Proton prefix at: {}", prefix_path);
+    if let Err(e) = proton::setup_prefix(&mut config, &prefix_path) {
+        error!("Failed to set up Proton prefix: {}", e);
+    } else {
+        debug!("Successfully set up Proton prefix");
     }
 
     debug!("Initializing Proton environment variables");
@@ -107,47 +133,44 @@ fn setup_proton_config(mut config: ProtonConfig) -> WindowsCompat {
     WindowsCompat::Proton { config }
 }
 
-/// Checks if a string value represents a truthy value.
-fn is_truthy(val: &str) -> bool {
-    val == "1" || val == "true" || val == "yes"
+/// Returns the Proton build to use: `config.proton`, else `PROTON_VERSION`, else the
+/// newest installed build.
+fn proton_selector(config: &InstanceConfig) -> ProtonSelector {
+    if let Some(selector) = &config.proton {
+        return selector.clone();
+    }
+    env::var("PROTON_VERSION").map_or_else(
+        |_| ProtonSelector::Latest,
+        |version| {
+            debug!("PROTON_VERSION is set to: {}", version);
+            ProtonSelector::from(version.as_str())
+        },
+    )
 }
 
-/// Finds a suitable Windows compatibility layer (Proton or Wine) based on the launch mode
-/// and environment variables.
-fn find_windows_compatibility(
-    app_id: u32,
-    launch_mode: &LaunchMode,
-) -> Result<WindowsCompat, String> {
+/// Finds a suitable Windows compatibility layer (Proton or Wine) based on the launch mode,
+/// the pinned Proton build and environment variables.
+fn find_windows_compatibility(config: &InstanceConfig) -> Result<WindowsCompat, String> {
     debug!("Searching for Windows compatibility layers");
     let force_proton = env::var("FORCE_PROTON").is_ok_and(|v| is_truthy(&v));
 
-    if matches!(launch_mode, LaunchMode::Proton) {
-        // Check if PROTON_VERSION is set
-        if let Ok(version) = env::var("PROTON_VERSION") {
-            debug!("PROTON_VERSION is set to: {}", version);
-
-            let parsed_version = match proton::parse_version(&version) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Failed to parse PROTON_VERSION: {}", e);
-                    version
-                }
-            };
+    if matches!(config.launch_mode, LaunchMode::Proton) {
+        let selector = proton_selector(config);
+        let result = try_find_proton(&selector, force_proton, config);
+        if result.is_ok() || force_proton {
+            return result;
+        }
 
-            let result = try_find_proton(Some(&parsed_version), force_proton, app_id);
-            if result.is_ok() || force_proton {
+        // If the pinned build is unavailable, fall back to the newest one
+        if selector != ProtonSelector::Latest {
+            let result = try_find_proton(&ProtonSelector::Latest, force_proton, config);
+            if result.is_ok() {
                 return result;
             }
         }
-
-        // If no specific version requested, try to find any version
-        let result = try_find_proton(None, force_proton, app_id);
-        if result.is_ok() || force_proton {
-            return result;
-        }
     }
 
-    if matches!(launch_mode, LaunchMode::Wine) {
+    if matches!(config.launch_mode, LaunchMode::Wine) {
         if let Ok(wine_path) = find_wine() {
             debug!("Found Wine at: {}", wine_path);
             return Ok(WindowsCompat::Wine { path: wine_path });
@@ -184,15 +207,12 @@ fn find_wine() -> Result<String, String> {
 }
 
 /// Creates a `Command` for a Windows executable, using a compatibility layer if available.
-fn get_command_for_windows(
-    exe_path: &str,
-    app_id: u32,
-    launch_mode: &LaunchMode,
-) -> Result<Command, InstanceError> {
+fn get_command_for_windows(config: &InstanceConfig) -> Result<Command, InstanceError> {
+    let exe_path = config.command.as_str();
     debug!("Getting Windows command for: {}", exe_path);
 
     // Try to find a suitable Windows compatibility layer
-    let compat = find_windows_compatibility(app_id, launch_mode).map_err(|e| {
+    let compat = find_windows_compatibility(config).map_err(|e| {
         // Check if we need to exit immediately due to FORCE_PROTON
         if env::var("FORCE_PROTON").is_ok_and(|v| is_truthy(&v)) {
             error!("FORCE_PROTON set but Proton setup failed: {}", e);
@@ -233,6 +253,25 @@ fn get_command_for_windows(
     Ok(cmd)
 }
 
+/// Applies `config.env` to `command`, first clearing the inherited environment when
+/// `config.clear_env` is set. Variables the compatibility layer already set on `command`
+/// survive the clear.
+fn apply_env(command: &mut Command, config: &InstanceConfig) {
+    if config.clear_env {
+        let compat_env: Vec<(OsString, OsString)> = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_owned(), value?.to_owned())))
+            .collect();
+        debug!("Clearing inherited environment");
+        command.env_clear();
+        command.envs(compat_env);
+    }
+    if !config.env.is_empty() {
+        debug!("Setting environment: {:?}", config.redacted_env());
+        command.envs(&config.env);
+    }
+}
+
 /// Prepares a `Command` to launch the game server based on the provided configuration.
 ///
 /// This function constructs a `Command` that is ready to be spawned as a child process.
@@ -254,6 +293,9 @@ fn get_command_for_windows(
 /// - If `launch_mode` is `Proton` or `Wine`, it attempts to find a suitable compatibility
 ///   layer and constructs the command accordingly.
 /// - It appends any `launch_args` from the configuration.
+/// - It sets the variables in `env`, on top of the inherited environment unless
+///   `clear_env` is set.
+/// - It applies the CPU, priority and memory limits in `resources`.
 /// - It sets the working directory to `config.working_dir`.
 /// - It creates the log directory and redirects the command's `stdout` and `stderr` to
 ///   log files (`server.log` and `server.err`).
@@ -272,7 +314,7 @@ pub fn launch_server(config: &InstanceConfig) -> Result<Command, InstanceError>
         }
         LaunchMode::Proton | LaunchMode::Wine => {
             debug!("Windows executable detected, finding compatibility layer");
-            get_command_for_windows(&config.command, config.app_id, &config.launch_mode)?
+            get_command_for_windows(config)?
         }
     };
 
@@ -284,6 +326,9 @@ pub fn launch_server(config: &InstanceConfig) -> Result<Command, InstanceError>
         }
     }
 
+    apply_env(&mut command, config);
+    resources::apply_limits(&mut command, config)?;
+
     // Set the working directory.
     debug!("Setting working directory: {:?}", config.working_dir);
     command.current_dir(&config.working_dir);
@@ -314,7 +359,12 @@ pub fn launch_server(config: &InstanceConfig) -> Result<Command, InstanceError>
     command.stdout(Stdio::from(stdout_file));
     command.stderr(Stdio::from(stderr_file));
 
-    debug!("Final command: {:?}", command);
+    // Command's Debug output includes the environment, which may hold secrets.
+    debug!(
+        "Final command: {:?} {:?}",
+        command.get_program(),
+        command.get_args().collect::<Vec<_>>()
+    );
 
     Ok(command)
 }
@@ -329,7 +379,11 @@ mod tests {
     )]
 
     use super::*;
-    use crate::config::InstanceConfig;
+    use crate::config::{DownloadConfig, InstanceConfig};
+    use crate::hooks::Hooks;
+    use crate::resources::ResourceLimits;
+    use crate::workshop::WorkshopConfig;
+    use std::collections::HashMap;
     use std::fs;
     use tempfile::tempdir;
 
@@ -376,14 +430,27 @@ mod tests {
             launch_args: vec![dummy_arg()],
             launch_mode,
             working_dir: path,
-            force_windows: false,
             skip_validate: false,
+            query_port: None,
+            ports: Vec::new(),
+            beta: None,
+            env: HashMap::new(),
+            clear_env: false,
+            hooks: Hooks::default(),
+            daemonize: true,
+            proton: None,
+            workshop: WorkshopConfig::default(),
+            readiness: None,
+            downloads: DownloadConfig::default(),
+            resources: ResourceLimits::default(),
+            shared_install: None,
+            dry_run: false,
         }
     }
 
     #[test]
     fn test_launch_server_with_wine() {
-        // For testing force_windows, check if "wine64" is available.
+        // For testing Wine launches, check if "wine64" is available.
         if which::which("wine64").is_err() {
             eprintln!("wine64 not found, skipping test_launch_server_with_wine");
             return;
@@ -452,6 +519,33 @@ mod tests {
         assert!(args.contains(&"--arg2".to_owned()));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn launch_server_sets_env_and_optionally_clears_inherited_env() {
+        let _lock = crate::test_support::env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let config = InstanceConfig {
+            command: "/bin/sh".to_owned(),
+            launch_args: vec!["-c".to_owned(), "echo \"$GSM_TEST_VAR:$HOME\"".to_owned()],
+            env: HashMap::from([("GSM_TEST_VAR".to_owned(), "set".to_owned())]),
+            ..test_config(LaunchMode::Native)
+        };
+        let home = std::env::var("HOME").unwrap_or_default();
+
+        launch_server(&config).unwrap().status().unwrap();
+        let output = fs::read_to_string(config.stdout()).unwrap();
+        assert_eq!(output.trim(), format!("set:{home}"));
+
+        let config = InstanceConfig {
+            clear_env: true,
+            ..config
+        };
+        launch_server(&config).unwrap().status().unwrap();
+        let output = fs::read_to_string(config.stdout()).unwrap();
+        assert_eq!(output.trim(), "set:");
+    }
+
     #[cfg(unix)]
     #[test]
     fn windows_compat_none_returns_error_when_no_compat_layer() {
@@ -521,8 +615,21 @@ mod tests {
             launch_args: vec![String::from("-log")],
             launch_mode: LaunchMode::Proton,
             working_dir: temp_home.join("server"),
-            force_windows: false,
             skip_validate: false,
+            query_port: None,
+            ports: Vec::new(),
+            beta: None,
+            env: HashMap::new(),
+            clear_env: false,
+            hooks: Hooks::default(),
+            daemonize: true,
+            proton: None,
+            workshop: WorkshopConfig::default(),
+            readiness: None,
+            downloads: DownloadConfig::default(),
+            resources: ResourceLimits::default(),
+            shared_install: None,
+            dry_run: false,
         };
 
         let command = launch_server(&config).unwrap();
@@ -568,8 +675,21 @@ mod tests {
             launch_args: vec![],
             launch_mode: LaunchMode::Proton,
             working_dir: temp_home.join("server"),
-            force_windows: false,
             skip_validate: false,
+            query_port: None,
+            ports: Vec::new(),
+            beta: None,
+            env: HashMap::new(),
+            clear_env: false,
+            hooks: Hooks::default(),
+            daemonize: true,
+            proton: None,
+            workshop: WorkshopConfig::default(),
+            readiness: None,
+            downloads: DownloadConfig::default(),
+            resources: ResourceLimits::default(),
+            shared_install: None,
+            dry_run: false,
         };
 
         let error = launch_server(&config).unwrap_err();
@@ -581,4 +701,25 @@ mod tests {
             std::env::remove_var("PROTON_VERSION");
         }
     }
+
+    #[test]
+    fn compatdata_defaults_to_the_steam_layout() {
+        let _lock = crate::test_support::env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        unsafe {
+            std::env::remove_var("STEAM_COMPAT_DATA_PATH");
+        }
+        let mut config = test_config(LaunchMode::Proton);
+        assert_eq!(
+            compatdata_path(&config),
+            config.working_dir.join("steamapps/compatdata/123456")
+        );
+
+        config.env.insert(
+            "STEAM_COMPAT_DATA_PATH".to_owned(),
+            "/srv/compat".to_owned(),
+        );
+        assert_eq!(compatdata_path(&config), PathBuf::from("/srv/compat"));
+    }
 }
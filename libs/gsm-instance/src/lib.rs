@@ -6,42 +6,118 @@
 //!
 //! ## Modules
 //!
+//! - **clone**: Seeds a new install directory from an existing install of the same app, so a
+//!   second instance does not re-download the whole game.
 //! - **config**: Defines the `InstanceConfig` struct, which holds configuration options (e.g. app ID,
 //!   server name, command, extra arguments, working directory, etc.).
-//! - **env_config**: Centralizes environment variable parsing and defaulting. Use this module to
-//!   manage environment-based configuration (e.g. beta options, additional arguments).
+//! - **config_file**: Loads a `gsm.toml` or `gsm.yaml` file with environment variable
+//!   interpolation, so containers can mount one file instead of setting many variables.
+//! - **dry_run**: Logs the SteamCMD command lines, launch commands and stop signals an instance
+//!   would run instead of running them, for validating a configuration.
+//! - **env_config**: Parses and validates the SteamCMD options set through the environment
+//!   (`USE_BETA`, `BETA_BRANCH`, `BETA_BRANCH_PASSWORD`, `ADDITIONAL_STEAMCMD_ARGS`).
 //! - **errors**: Defines custom error types (`InstanceError`) for the crate.
+//! - **health**: Reports whether the server is running and answering, for status commands and
+//!   Docker health checks.
+//! - **hooks**: Runs shell command or closure hooks before start and after stop or update,
+//!   with timeouts, captured output and an abort-or-continue failure policy.
 //! - **instance**: Exposes the main API through the `Instance` struct. Methods include install, update,
 //!   start, stop, and restart.
+//! - **lifecycle**: Async variants of the instance operations, which run on tokio's blocking pool
+//!   and report the steps of an update as they happen.
 //! - **launcher**: Provides functionality for launching the server process (including support for
 //!   running Windows executables via Wine when forced).
-//! - **process**: Contains utilities for detecting and managing running server processes.
-//! - **shutdown**: Offers functionality to gracefully shut down the server by sending interrupts.
+//! - **maintenance**: Waits for players to leave before stopping the server, with a hard
+//!   deadline, so routine maintenance does not kick active sessions.
+//! - **migration**: Exports an instance's configuration, build and saves to one archive, and
+//!   imports it on another host, for moving a server between machines.
+//! - **query**: Queries a running server over Steam's A2S protocol for its name, map and
+//!   player count.
+//! - **ports**: Checks that the ports the server binds are free before launching it, so a taken
+//!   port fails with a clear error instead of a bind failure buried in the game's logs.
+//! - **preflight**: Checks for SteamCMD, its 32-bit libraries, a writable working directory and
+//!   enough free disk space before an install or update.
+//! - **process**: Identifies the server process an instance owns, and the process group it leads,
+//!   for signalling it.
+//! - **relaunch**: Lets maintenance that stops a server started in the foreground hand it back
+//!   to the foreground process to launch again, instead of starting a daemonized replacement.
+//! - **restart**: Restarts the server after broadcasting a countdown, so players are warned
+//!   before they are dropped.
+//! - **readiness**: Probes a started server's TCP port, Steam query port or log until it is ready
+//!   for players, so `start` can tell a server that is up from one that never became ready.
+//! - **resources**: Pins the server to CPU cores, sets its nice level and limits its memory
+//!   through cgroup v2, so one server cannot starve the others on a shared host.
+//! - **rollback**: Records the installed build before an update and returns to it when the update
+//!   fails, from the game's rollback branch or a snapshot of the app manifests.
+//! - **saves**: Finds a game's world and save files through a pluggable `SaveLocator`, with
+//!   their sizes and modification times, so backups can include only the saves.
+//! - **shared**: Links an instance's working directory to another install of the game, keeping
+//!   only its saves and settings as its own, so test and production servers share one install.
+//! - **shutdown**: Offers functionality to gracefully shut down the server, escalating from SIGINT
+//!   to SIGTERM and SIGKILL when it does not exit in time.
+//! - **state**: Records the running server's pid, start time, build and configuration hash in
+//!   `instance.json`, detecting stale state left by a crash or a reused pid.
 //! - **startup**: Wraps daemonization logic for starting the server process in the background.
-//! - **steamcmd**: Provides helper functions for constructing and running SteamCMD commands.
+//! - **steamcmd**: Runs SteamCMD, classifying its failures and retrying transient ones.
+//! - **systemd**: Generates a systemd unit for an instance and reports readiness and watchdog
+//!   keepalives to systemd while the server runs in the foreground.
+//! - **usage**: Measures the server's CPU, memory, threads and disk usage, once or on a
+//!   background sampling loop.
+//! - **workshop**: Downloads Steam Workshop items with SteamCMD, copies them where the game
+//!   expects its mods and records them in a manifest.
 //! - **update**: Contains functions to check for and perform updates by comparing build IDs.
+//! - **vdf**: Parses the text VDF (KeyValues) format of Steam's app manifests and SteamCMD's app
+//!   info.
 //! - **cli**: Offers a command‑line interface for managing server operations (install, update, start, etc.).
 //!
 
+pub mod cli;
+pub mod clone;
 pub mod config;
+pub mod config_file;
+pub mod dry_run;
+pub mod env_config;
 pub mod errors;
-mod executable;
+mod health;
+pub mod hooks;
 pub mod install;
 mod instance;
 pub mod launcher;
-mod process;
+pub mod lifecycle;
+pub mod maintenance;
+pub mod migration;
+pub mod ports;
+pub mod preflight;
+pub mod process;
 pub mod proton;
+pub mod query;
+pub mod readiness;
+pub mod relaunch;
+pub mod resources;
+pub mod restart;
+pub mod rollback;
+pub mod saves;
+pub mod shared;
 pub mod shutdown;
 pub mod startup;
+pub mod state;
 pub mod steamcmd;
+pub mod systemd;
 pub mod update;
+mod usage;
+pub mod vdf;
+pub mod workshop;
 
 // CLI interface for the crate
 
 // Re-export key types for easier usage.
 pub use config::InstanceConfig;
 pub use errors::InstanceError;
+pub use health::Health;
 pub use instance::Instance;
+pub use lifecycle::LifecycleEvent;
+pub use shutdown::StopOutcome;
+pub use usage::{ResourceUsage, UsageSampler};
 
 #[cfg(test)]
 pub(crate) mod test_support {
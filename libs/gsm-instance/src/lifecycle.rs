@@ -0,0 +1,360 @@
+//! # Async Lifecycle
+//!
+//! Installing, updating and starting a server block on SteamCMD and the server process,
+//! sometimes for minutes. The async variants in this module run those operations on
+//! tokio's blocking pool, so callers holding an async lock around an [`Instance`] do not
+//! stall the runtime's other tasks, such as log monitoring, while they wait.
+//!
+//! [`Instance::apply_update_async`] also keeps a failed update from leaving the server
+//! down: it rolls back to the build installed before the update and starts that again.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! # async fn run() -> Result<(), gsm_instance::InstanceError> {
+//! let instance = Instance::new(InstanceConfig::default());
+//! let updated = instance
+//!     .apply_update_async(|event| println!("{event:?}"))
+//!     .await?;
+//! println!("Updated: {updated}");
+//! # Ok(())
+//! # }
+//! ```
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use crate::relaunch::{RelaunchHold, reap_in_background};
+use crate::rollback::{RollbackOutcome, RollbackPoint};
+use crate::shutdown::StopOutcome;
+use crate::update::UpdateStatus;
+use gsm_cron::{ChildRegistry, wait_for_termination};
+use std::process::ExitStatus;
+use tracing::{info, warn};
+
+/// A step of [`Instance::apply_update_async`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// Comparing the installed build with the latest one.
+    CheckingForUpdate,
+    /// The installed build is the latest; nothing else happens.
+    UpToDate,
+    /// Whether an update is available could not be determined, for `reason`; nothing
+    /// else happens.
+    UpdateStatusUnknown { reason: String },
+    /// Stopping the server before updating it.
+    Stopping,
+    /// Running SteamCMD to update the server files.
+    Updating,
+    /// Starting the updated server.
+    Starting,
+    /// The updated server is running as `pid`, on build `build_id` when known.
+    Updated { pid: u32, build_id: Option<String> },
+    /// Updating or starting the updated server failed with `reason`; returning to the
+    /// build installed before the update.
+    RollingBack { reason: String },
+    /// The previous build is running again as `pid`, on build `build_id` when known.
+    RolledBack { pid: u32, build_id: Option<String> },
+}
+
+impl Instance {
+    /// Runs `operation` on a clone of this instance on tokio's blocking pool.
+    async fn run_blocking<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&Self) -> Result<T, InstanceError> + Send + 'static,
+    ) -> Result<T, InstanceError> {
+        let instance = self.clone();
+        tokio::task::spawn_blocking(move || operation(&instance))
+            .await
+            .map_err(|e| InstanceError::Unknown(format!("instance task failed: {e}")))?
+    }
+
+    /// Async variant of [`Instance::install`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::install`].
+    pub async fn install_async(&self) -> Result<(), InstanceError> {
+        self.run_blocking(Self::install).await
+    }
+
+    /// Async variant of [`Instance::update`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::update`].
+    pub async fn update_async(&self) -> Result<(), InstanceError> {
+        self.run_blocking(Self::update).await
+    }
+
+    /// Async variant of [`Instance::rollback`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::rollback`].
+    pub async fn rollback_async(&self) -> Result<RollbackOutcome, InstanceError> {
+        self.run_blocking(Self::rollback).await
+    }
+
+    /// Async variant of [`Instance::start`], returning the server's pid. The server's
+    /// exit status is collected in the background once it exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::start`].
+    pub async fn start_async(&self) -> Result<u32, InstanceError> {
+        self.run_blocking(|instance| instance.start().map(reap_in_background))
+            .await
+    }
+
+    /// Async variant of [`Instance::relaunch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::relaunch`].
+    pub async fn relaunch_async(&self, hold: Option<RelaunchHold>) -> Result<u32, InstanceError> {
+        self.run_blocking(move |instance| instance.relaunch(hold))
+            .await
+    }
+
+    /// Async variant of [`Instance::stop`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::stop`].
+    pub async fn stop_async(&self) -> Result<StopOutcome, InstanceError> {
+        self.run_blocking(Self::stop).await
+    }
+
+    /// Async variant of [`Instance::run_foreground`] that also forwards SIGINT and SIGTERM
+    /// received by this process to the server, so stopping the container shuts the
+    /// server down cleanly.
+    ///
+    /// When maintenance stops the server with a [`RelaunchHold`] in place, the server is
+    /// launched again once the hold is released, instead of returning; see
+    /// [`crate::relaunch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::run_foreground`].
+    pub async fn run_foreground_async(&self) -> Result<ExitStatus, InstanceError> {
+        RelaunchHold::clear(&self.config.working_dir);
+        loop {
+            let mut server = std::pin::pin!(self.run_blocking(Self::run_foreground));
+            let mut terminating = false;
+            let status = loop {
+                tokio::select! {
+                    result = &mut server => break result?,
+                    received = wait_for_termination() => {
+                        info!("Received {received}; forwarding it to the server");
+                        ChildRegistry::global().signal_all(received);
+                        terminating = true;
+                    }
+                }
+            };
+            if terminating
+                || !RelaunchHold::is_placed(&self.config.working_dir)
+                || !self.await_relaunch().await
+            {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Async variant of [`Instance::update_available`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::update_available`].
+    pub async fn update_available_async(&self) -> Result<UpdateStatus, InstanceError> {
+        self.run_blocking(Self::update_available).await
+    }
+
+    /// Stops, updates and restarts the server if an update is available, reporting each
+    /// step to `on_event`. Returns whether the server was updated.
+    ///
+    /// When updating or starting the updated server fails, the server is rolled back to
+    /// the build installed before the update (see [`Instance::rollback`]) and started
+    /// again, so it does not stay down. A server running in the foreground is relaunched
+    /// there; see [`Instance::relaunch`]. A dry run logs each step and returns `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when checking for an update, stopping, updating or starting the
+    /// server fails; the steps after the failed one are not attempted. A failed update is returned as the error
+    /// even when the rollback brought the server back.
+    pub async fn apply_update_async(
+        &self,
+        mut on_event: impl FnMut(LifecycleEvent),
+    ) -> Result<bool, InstanceError> {
+        on_event(LifecycleEvent::CheckingForUpdate);
+        match self.update_available_async().await? {
+            UpdateStatus::Available { .. } => {}
+            UpdateStatus::UpToDate { .. } => {
+                on_event(LifecycleEvent::UpToDate);
+                return Ok(false);
+            }
+            UpdateStatus::Unknown { reason } => {
+                on_event(LifecycleEvent::UpdateStatusUnknown { reason });
+                return Ok(false);
+            }
+        }
+        on_event(LifecycleEvent::Stopping);
+        let hold = self.hold_foreground_relaunch()?;
+        self.stop_async().await?;
+        on_event(LifecycleEvent::Updating);
+        // The hold is kept through a failed update, so the old files are not relaunched
+        // before the rollback.
+        let (updated, hold) = match self.update_async().await {
+            Ok(()) => {
+                on_event(LifecycleEvent::Starting);
+                (self.relaunch_async(hold).await, None)
+            }
+            Err(e) => (Err(e), hold),
+        };
+        match updated {
+            // Nothing was stopped, updated or started.
+            Err(InstanceError::DryRun(_)) => Ok(false),
+            Ok(pid) => {
+                on_event(LifecycleEvent::Updated {
+                    pid,
+                    build_id: self.installed_build_id(),
+                });
+                Ok(true)
+            }
+            Err(e) => {
+                self.roll_back_failed_update(&e, hold, &mut on_event).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Rolls back and restarts the server after `error` interrupted an update, when a
+    /// build was recorded before it, releasing `hold` once it is back. Rollback failures
+    /// are logged, as `error` is what the caller needs to see.
+    async fn roll_back_failed_update(
+        &self,
+        error: &InstanceError,
+        hold: Option<RelaunchHold>,
+        on_event: &mut impl FnMut(LifecycleEvent),
+    ) {
+        if !matches!(RollbackPoint::load(&self.config.working_dir), Ok(Some(_))) {
+            return;
+        }
+        on_event(LifecycleEvent::RollingBack {
+            reason: error.to_string(),
+        });
+        // A new build that started but never became ready is still running.
+        let hold = hold.or_else(|| {
+            self.hold_foreground_relaunch().unwrap_or_else(|e| {
+                warn!("Failed to hold the server for the rollback: {e}");
+                None
+            })
+        });
+        if let Err(e) = self.stop_async().await {
+            warn!("Failed to stop the server before rolling back: {e}");
+        }
+        let restarted = match self.rollback_async().await {
+            Ok(outcome) => {
+                info!("Rolled back the failed update: {outcome}");
+                self.relaunch_async(hold).await
+            }
+            Err(e) => Err(e),
+        };
+        match restarted {
+            Ok(pid) => on_event(LifecycleEvent::RolledBack {
+                pid,
+                build_id: self.installed_build_id(),
+            }),
+            Err(e) => warn!("Rolling back the failed update failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use crate::clone::app_manifest_path;
+    use crate::state::InstanceState;
+    use crate::test_support::env_lock;
+    use std::fs;
+    use tempfile::tempdir;
+
+    // The environment must stay as set for the whole test, and the test runtime runs
+    // on this thread alone, so nothing else awaits the lock.
+    #[allow(clippy::await_holding_lock)]
+    #[tokio::test]
+    async fn apply_update_async_reports_up_to_date_installs() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            app_id: 1,
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        let manifest_path = app_manifest_path(temp_dir.path(), 1);
+        fs::create_dir_all(manifest_path.parent().unwrap()).unwrap();
+        fs::write(&manifest_path, r#""AppState" { "buildid" "1000" }"#).unwrap();
+        let appinfo_path = temp_dir.path().join("appinfo.txt");
+        fs::write(
+            &appinfo_path,
+            r#""1" { "depots" { "branches" { "public" { "buildid" "1000" } } } }"#,
+        )
+        .unwrap();
+        unsafe {
+            std::env::set_var("STEAM_APPINFO_PATH", &appinfo_path);
+        }
+
+        let mut events = Vec::new();
+        let updated = instance
+            .apply_update_async(|event| events.push(event))
+            .await
+            .unwrap();
+        assert!(!updated);
+        assert_eq!(
+            events,
+            vec![LifecycleEvent::CheckingForUpdate, LifecycleEvent::UpToDate]
+        );
+
+        // Unparseable app info is reported, not treated as up to date.
+        fs::write(&appinfo_path, "not app info").unwrap();
+        let mut events = Vec::new();
+        assert!(
+            !instance
+                .apply_update_async(|event| events.push(event))
+                .await
+                .unwrap()
+        );
+        assert!(matches!(
+            events.last(),
+            Some(LifecycleEvent::UpdateStatusUnknown { .. })
+        ));
+
+        unsafe {
+            std::env::remove_var("STEAM_APPINFO_PATH");
+        }
+    }
+
+    #[tokio::test]
+    async fn stop_async_removes_the_state_file() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        InstanceState::capture(999_999, &instance.config)
+            .write(&instance.config.state_file())
+            .unwrap();
+
+        assert_eq!(
+            instance.stop_async().await.unwrap(),
+            StopOutcome::NotRunning
+        );
+        assert!(!instance.config.state_file().exists());
+    }
+}
@@ -0,0 +1,306 @@
+//! # Player-Aware Maintenance
+//!
+//! Stopping a server for an update or a restart drops everyone playing on it. The
+//! methods here wait for the server to empty first: [`Instance::wait_until_empty`] polls
+//! the player count over a Steam (A2S) query until no one is online or a hard deadline
+//! passes, and [`Instance::stop_when_empty`] stops the server once it returns.
+//!
+//! Scheduled jobs take the deadline from `{prefix}_MAX_DEFER`, e.g. `45` minutes or `2h`; see
+//! [`max_defer_from_env`]. The auto-update jobs use `AUTO_UPDATE_MAX_DEFER`.
+//!
+//! Bots are not counted. When the server cannot be queried, e.g. because it has no
+//! `query_port`, the wait falls back to the traffic on its game port (see
+//! [`TrafficSampler`]) and ends once the port goes quiet. If that cannot be read either,
+//! the server is treated as empty rather than deferring maintenance forever.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//! use std::time::Duration;
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let outcome = instance
+//!     .stop_when_empty(Duration::from_mins(30))
+//!     .expect("Stop failed");
+//! println!("Server {outcome}");
+//! ```
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use crate::shutdown::StopOutcome;
+use gsm_monitor::TrafficSampler;
+use gsm_shared::fetch_duration_in;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How often the player count is checked while waiting for a server to empty.
+pub const EMPTY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long game port traffic is measured when the player count cannot be queried.
+pub const TRAFFIC_SAMPLE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Returns how long maintenance may wait for players to leave, from `{prefix}_MAX_DEFER`,
+/// a duration whose bare numbers are minutes. `None`, for no waiting, when it is unset,
+/// 0 or invalid.
+pub fn max_defer_from_env(prefix: &str) -> Option<Duration> {
+    let name = format!("{prefix}_MAX_DEFER");
+    Some(fetch_duration_in(
+        &name,
+        Duration::ZERO,
+        Duration::from_mins(1),
+    ))
+    .filter(|max_defer| !max_defer.is_zero())
+}
+
+/// How waiting for a server to empty ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmptyWait {
+    /// No players were online.
+    Empty,
+    /// `players` were still online when the deadline passed.
+    DeadlineReached { players: u8 },
+    /// The player count could not be read, and the game port still had traffic when the
+    /// deadline passed.
+    BusyAtDeadline,
+    /// The player count could not be read, for `reason`.
+    Unknown { reason: String },
+}
+
+impl fmt::Display for EmptyWait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("no players online"),
+            Self::DeadlineReached { players } => {
+                write!(f, "{players} player(s) still online at the deadline")
+            }
+            Self::BusyAtDeadline => f.write_str("game port still busy at the deadline"),
+            Self::Unknown { reason } => write!(f, "player count unknown ({reason})"),
+        }
+    }
+}
+
+impl Instance {
+    /// Waits up to `max_wait` for the running server to have no players online, checking
+    /// every [`EMPTY_POLL_INTERVAL`]. When the server cannot be queried, it waits for the
+    /// game port to go quiet instead. Returns right away when the server is not running
+    /// or neither can be read. A dry run only logs the wait.
+    pub fn wait_until_empty(&self, max_wait: Duration) -> EmptyWait {
+        if self.pid().is_err() {
+            return EmptyWait::Empty;
+        }
+        if self.config.dry_run {
+            info!("Dry run: would wait up to {max_wait:?} for players to leave");
+            return EmptyWait::Empty;
+        }
+        let mut sampler = self
+            .game_ports()
+            .first()
+            .map(|port| TrafficSampler::new(port.port));
+        wait_for_players(
+            max_wait,
+            EMPTY_POLL_INTERVAL,
+            || {
+                self.query()
+                    .map(|info| info.human_players())
+                    .map_err(|e| e.to_string())
+            },
+            || {
+                let sampler = sampler.as_mut().ok_or("no game port to sample")?;
+                // The first sample only sets the baseline for the UDP counter.
+                sampler.sample().map_err(|e| e.to_string())?;
+                thread::sleep(TRAFFIC_SAMPLE_WINDOW);
+                sampler.players_likely_online().map_err(|e| e.to_string())
+            },
+        )
+    }
+
+    /// Stops the server once no players are online, or when `max_wait` has passed even if
+    /// some still are. Use it for maintenance that should not kick active sessions.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Instance::stop`].
+    pub fn stop_when_empty(&self, max_wait: Duration) -> Result<StopOutcome, InstanceError> {
+        match self.wait_until_empty(max_wait) {
+            EmptyWait::Empty => {}
+            EmptyWait::DeadlineReached { players } => {
+                warn!("Stopping with {players} player(s) online: waited {max_wait:?}");
+            }
+            EmptyWait::BusyAtDeadline => {
+                warn!("Stopping with traffic on the game port: waited {max_wait:?}");
+            }
+            EmptyWait::Unknown { reason } => {
+                warn!("Could not count players ({reason}); stopping anyway");
+            }
+        }
+        self.stop()
+    }
+}
+
+/// Polls `players` every `interval` until it reports no one online or `max_wait` has
+/// passed. While `players` fails, `traffic` reports whether the game port is busy
+/// instead; the wait gives up when both fail.
+fn wait_for_players(
+    max_wait: Duration,
+    interval: Duration,
+    players: impl Fn() -> Result<u8, String>,
+    mut traffic: impl FnMut() -> Result<bool, String>,
+) -> EmptyWait {
+    let started = Instant::now();
+    loop {
+        // `None` when only the traffic shows someone is still playing.
+        let online = match players() {
+            Ok(0) => {
+                debug!("No players online");
+                return EmptyWait::Empty;
+            }
+            Ok(online) => Some(online),
+            Err(reason) => match traffic() {
+                Ok(false) => {
+                    debug!("Could not count players ({reason}); the game port is quiet");
+                    return EmptyWait::Empty;
+                }
+                Ok(true) => None,
+                Err(e) => {
+                    return EmptyWait::Unknown {
+                        reason: format!("{reason}; traffic unavailable: {e}"),
+                    };
+                }
+            },
+        };
+        let waited = started.elapsed();
+        if waited >= max_wait {
+            return online.map_or(EmptyWait::BusyAtDeadline, |players| {
+                EmptyWait::DeadlineReached { players }
+            });
+        }
+        let left = max_wait.saturating_sub(waited);
+        if let Some(online) = online {
+            info!(
+                "Waiting for {online} player(s) to leave ({}s left)",
+                left.as_secs()
+            );
+        } else {
+            info!(
+                "Waiting for the game port to go quiet ({}s left)",
+                left.as_secs()
+            );
+        }
+        thread::sleep(interval.min(left));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::cell::Cell;
+
+    fn no_traffic() -> Result<bool, String> {
+        Err("unused".to_owned())
+    }
+
+    #[test]
+    fn waits_until_the_players_leave() {
+        let counts = Cell::new(3_u8);
+        let outcome = wait_for_players(
+            Duration::from_secs(5),
+            Duration::ZERO,
+            || {
+                let online = counts.get();
+                counts.set(online.saturating_sub(1));
+                Ok(online)
+            },
+            no_traffic,
+        );
+        assert_eq!(outcome, EmptyWait::Empty);
+        assert_eq!(counts.get(), 0);
+    }
+
+    #[test]
+    fn gives_up_at_the_deadline_or_when_the_count_is_unknown() {
+        let outcome = wait_for_players(
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+            || Ok(2),
+            no_traffic,
+        );
+        assert_eq!(outcome, EmptyWait::DeadlineReached { players: 2 });
+
+        let outcome = wait_for_players(
+            Duration::from_secs(5),
+            Duration::ZERO,
+            || Err("no query_port".to_owned()),
+            || Err("no game port to sample".to_owned()),
+        );
+        assert_eq!(
+            outcome,
+            EmptyWait::Unknown {
+                reason: "no query_port; traffic unavailable: no game port to sample".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn falls_back_to_game_port_traffic_when_the_query_fails() {
+        let busy = Cell::new(2);
+        let outcome = wait_for_players(
+            Duration::from_secs(5),
+            Duration::ZERO,
+            || Err("no query_port".to_owned()),
+            || {
+                busy.set(busy.get() - 1);
+                Ok(busy.get() > 0)
+            },
+        );
+        assert_eq!(outcome, EmptyWait::Empty);
+        assert_eq!(busy.get(), 0);
+
+        let outcome = wait_for_players(
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+            || Err("no query_port".to_owned()),
+            || Ok(true),
+        );
+        assert_eq!(outcome, EmptyWait::BusyAtDeadline);
+    }
+
+    #[test]
+    fn max_defer_is_read_in_minutes() {
+        let _lock = crate::test_support::env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        assert_eq!(max_defer_from_env("TEST_MAINTENANCE"), None);
+        for (value, expected) in [
+            ("45", Some(Duration::from_mins(45))),
+            ("1h30m", Some(Duration::from_mins(90))),
+            ("0", None),
+            ("soon", None),
+        ] {
+            unsafe {
+                std::env::set_var("TEST_MAINTENANCE_MAX_DEFER", value);
+            }
+            assert_eq!(max_defer_from_env("TEST_MAINTENANCE"), expected, "{value}");
+        }
+        unsafe {
+            std::env::remove_var("TEST_MAINTENANCE_MAX_DEFER");
+        }
+    }
+
+    #[test]
+    fn stopped_servers_are_empty() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let instance = Instance::new(crate::InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..crate::InstanceConfig::default()
+        });
+        assert_eq!(instance.wait_until_empty(Duration::ZERO), EmptyWait::Empty);
+        assert_eq!(
+            instance.stop_when_empty(Duration::ZERO).unwrap(),
+            StopOutcome::NotRunning
+        );
+    }
+}
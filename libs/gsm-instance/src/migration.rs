@@ -0,0 +1,400 @@
+//! # Instance Migration
+//!
+//! Moving a server to another machine means carrying over its configuration and its
+//! worlds, and installing the same game there. [`Instance::export_state`] writes all of
+//! that to one `.tar.gz`: an [`ExportManifest`] with the instance's configuration and
+//! installed build, and the saves a [`SaveLocator`] finds. On the new host,
+//! [`Instance::import_state`] installs the game and restores the saves into the working
+//! directory, so a migration is one command on each side:
+//!
+//! ```text
+//! palworld export /tmp/palworld.tar.gz   # old host, with the server stopped
+//! palworld import /tmp/palworld.tar.gz   # new host
+//! ```
+//!
+//! The game files themselves are not exported; SteamCMD downloads them again. The
+//! configuration is exported as is, including its environment variables and beta branch
+//! password, so treat the archive like the secrets it holds.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::Instance;
+//! use std::path::Path;
+//!
+//! let archive = Path::new("/tmp/palworld.tar.gz");
+//! let instance = Instance::from_export(archive, Path::new("/home/steam/palworld"))
+//!     .expect("Not an instance export");
+//! let manifest = instance.import_state(archive).expect("Import failed");
+//! println!("Imported build {:?}", manifest.build_id);
+//! ```
+use crate::config::InstanceConfig;
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use crate::saves::SaveLocator;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tar::{Archive, Builder, Header};
+use tracing::{info, warn};
+
+/// The name of the manifest in an export archive.
+pub const EXPORT_MANIFEST: &str = "gsm-export.json";
+
+/// The directory of an export archive the saves are stored under.
+const SAVES_DIR: &str = "saves";
+
+/// The export format written by [`Instance::export_state`]. Archives of a newer format
+/// are refused.
+pub const EXPORT_FORMAT: u32 = 1;
+
+/// What an export archive holds, besides the saves themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    /// The export format; see [`EXPORT_FORMAT`].
+    pub format: u32,
+    /// When the export was written, in seconds since the Unix epoch.
+    pub exported_at: u64,
+    /// The build ID that was installed, if known.
+    pub build_id: Option<String>,
+    /// The exported instance's configuration.
+    pub config: InstanceConfig,
+    /// The save files and directories in the archive, relative to the working directory.
+    pub saves: Vec<PathBuf>,
+}
+
+impl ExportManifest {
+    /// Reads the manifest of the export archive at `archive`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::ExportError`] when `archive` cannot be read, has no
+    /// manifest or was written in a newer format.
+    pub fn read(archive: &Path) -> Result<Self, InstanceError> {
+        let mut tar = open_archive(archive)?;
+        for entry in tar.entries().map_err(|e| export_error(archive, &e))? {
+            let entry = entry.map_err(|e| export_error(archive, &e))?;
+            if entry
+                .path()
+                .is_ok_and(|path| path == Path::new(EXPORT_MANIFEST))
+            {
+                let manifest: Self =
+                    serde_json::from_reader(entry).map_err(|e| export_error(archive, &e))?;
+                if manifest.format > EXPORT_FORMAT {
+                    return Err(InstanceError::ExportError(format!(
+                        "{} is in export format {}, but only {EXPORT_FORMAT} is supported",
+                        archive.display(),
+                        manifest.format
+                    )));
+                }
+                return Ok(manifest);
+            }
+        }
+        Err(InstanceError::ExportError(format!(
+            "{} has no {EXPORT_MANIFEST}; it is not an instance export",
+            archive.display()
+        )))
+    }
+}
+
+impl Instance {
+    /// Writes the instance's configuration, installed build and the saves `saves` finds
+    /// to a `.tar.gz` at `archive`, for [`Self::import_state`] on another host.
+    ///
+    /// Stop the server first: saves written during the export may be archived half
+    /// written, so exporting a running server only warns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the archive or a save cannot be written or read.
+    pub fn export_state(
+        &self,
+        archive: &Path,
+        saves: &dyn SaveLocator,
+    ) -> Result<ExportManifest, InstanceError> {
+        if self.pid().is_ok() {
+            warn!("The server is running; saves it writes during the export may be incomplete");
+        }
+        let found = self.saves(saves);
+        if found.is_empty() {
+            warn!("No saves found; exporting the configuration only");
+        }
+        let manifest = ExportManifest {
+            format: EXPORT_FORMAT,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            build_id: self.installed_build_id(),
+            config: self.config.clone(),
+            saves: found.include_list(),
+        };
+
+        if let Some(parent) = archive
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let written = write_archive(archive, &manifest, &self.config.working_dir);
+        if let Err(e) = written {
+            let _ = fs::remove_file(archive);
+            return Err(export_error(archive, &e));
+        }
+        info!(
+            "Exported {} save file(s), {} bytes, and build {} to {}",
+            found.files.len(),
+            found.total_size(),
+            manifest.build_id.as_deref().unwrap_or("unknown"),
+            archive.display()
+        );
+        Ok(manifest)
+    }
+
+    /// Creates the instance exported to `archive`, installed in `working_dir` instead of
+    /// its original working directory. Call [`Self::import_state`] on it to install it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`ExportManifest::read`].
+    pub fn from_export(archive: &Path, working_dir: &Path) -> Result<Self, InstanceError> {
+        let mut config = ExportManifest::read(archive)?.config;
+        config.working_dir = working_dir.to_path_buf();
+        Ok(Self::new(config))
+    }
+
+    /// Recreates the instance exported to `archive` in this instance's working directory:
+    /// installs the server with SteamCMD, then restores the exported saves. Returns the
+    /// archive's manifest.
+    ///
+    /// A dry run logs the install and the saves it would restore.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the archive is not an export of this instance's app, the
+    /// server is running, a save would overwrite an existing one, or the install or the
+    /// restore fails.
+    pub fn import_state(&self, archive: &Path) -> Result<ExportManifest, InstanceError> {
+        let manifest = ExportManifest::read(archive)?;
+        if manifest.config.app_id != self.config.app_id {
+            return Err(InstanceError::ExportError(format!(
+                "{} is an export of app {}, not app {}",
+                archive.display(),
+                manifest.config.app_id,
+                self.config.app_id
+            )));
+        }
+        if self.pid().is_ok() {
+            return Err(InstanceError::ProcessError(
+                "The server is running; stop it before importing".to_owned(),
+            ));
+        }
+        let working_dir = &self.config.working_dir;
+        if let Some(existing) = manifest
+            .saves
+            .iter()
+            .find(|save| working_dir.join(save).exists())
+        {
+            return Err(InstanceError::ExportError(format!(
+                "{} already exists in {}; move it away before importing",
+                existing.display(),
+                working_dir.display()
+            )));
+        }
+
+        self.install()?;
+        if self.config.dry_run {
+            info!(
+                "Dry run: would restore {} save path(s) into {}",
+                manifest.saves.len(),
+                working_dir.display()
+            );
+            return Ok(manifest);
+        }
+        let restored = restore_saves(archive, working_dir)?;
+        info!(
+            "Restored {restored} save file(s) into {}",
+            working_dir.display()
+        );
+
+        if let (Some(exported), Some(installed)) = (&manifest.build_id, self.installed_build_id())
+            && *exported != installed
+        {
+            warn!(
+                "The saves were exported from build {exported}, but build {installed} is \
+                 installed; the game may convert them on its next start"
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+/// Writes `manifest` and the saves it lists from `working_dir` to `archive`.
+fn write_archive(
+    archive: &Path,
+    manifest: &ExportManifest,
+    working_dir: &Path,
+) -> std::io::Result<()> {
+    let mut tar = Builder::new(GzEncoder::new(
+        File::create(archive)?,
+        Compression::default(),
+    ));
+    tar.follow_symlinks(false);
+
+    let json = serde_json::to_vec_pretty(manifest).map_err(std::io::Error::other)?;
+    let mut header = Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(manifest.exported_at);
+    header.set_cksum();
+    tar.append_data(&mut header, EXPORT_MANIFEST, json.as_slice())?;
+
+    for save in &manifest.saves {
+        let source = working_dir.join(save);
+        let name = Path::new(SAVES_DIR).join(save);
+        if source.is_dir() {
+            tar.append_dir_all(&name, &source)?;
+        } else {
+            tar.append_path_with_name(&source, &name)?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpacks the saves in `archive` into `working_dir`, returning how many files it
+/// restored. Links and paths that would leave `working_dir` are skipped.
+fn restore_saves(archive: &Path, working_dir: &Path) -> Result<usize, InstanceError> {
+    let mut tar = open_archive(archive)?;
+    let mut restored = 0;
+    for entry in tar.entries().map_err(|e| export_error(archive, &e))? {
+        let mut entry = entry.map_err(|e| export_error(archive, &e))?;
+        let path = entry
+            .path()
+            .map_err(|e| export_error(archive, &e))?
+            .into_owned();
+        let Ok(relative) = path.strip_prefix(SAVES_DIR) else {
+            continue;
+        };
+        let kind = entry.header().entry_type();
+        if kind.is_symlink()
+            || kind.is_hard_link()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            warn!("Skipping {} in {}", path.display(), archive.display());
+            continue;
+        }
+        let target = working_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry
+            .unpack(&target)
+            .map_err(|e| export_error(archive, &e))?;
+        if kind.is_file() {
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+fn open_archive(archive: &Path) -> Result<Archive<GzDecoder<File>>, InstanceError> {
+    let file = File::open(archive).map_err(|e| export_error(archive, &e))?;
+    Ok(Archive::new(GzDecoder::new(file)))
+}
+
+fn export_error(archive: &Path, error: &dyn std::fmt::Display) -> InstanceError {
+    InstanceError::ExportError(format!("{}: {error}", archive.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::clone::app_manifest_path;
+    use crate::saves::SaveGlobs;
+    use tempfile::tempdir;
+
+    fn instance_in(working_dir: &Path) -> Instance {
+        Instance::new(InstanceConfig {
+            app_id: 2_394_010,
+            name: "palworld".to_owned(),
+            working_dir: working_dir.to_path_buf(),
+            ..InstanceConfig::default()
+        })
+    }
+
+    #[test]
+    fn exports_restore_the_saves_on_another_host() {
+        let temp_dir = tempdir().unwrap();
+        let old_host = temp_dir.path().join("old");
+        let saves = old_host.join("Pal/Saved/SaveGames/0/world");
+        fs::create_dir_all(&saves).unwrap();
+        fs::write(saves.join("Level.sav"), "level").unwrap();
+        fs::write(old_host.join("Pal/Saved/settings.ini"), "[/Script]").unwrap();
+        fs::write(old_host.join("PalServer.sh"), "game file").unwrap();
+        let manifest_path = app_manifest_path(&old_host, 2_394_010);
+        fs::create_dir_all(manifest_path.parent().unwrap()).unwrap();
+        fs::write(&manifest_path, r#""AppState" { "buildid" "1000" }"#).unwrap();
+
+        let archive = temp_dir.path().join("exports/palworld.tar.gz");
+        let exported = instance_in(&old_host)
+            .export_state(&archive, &SaveGlobs::new(["Pal/Saved/*"]))
+            .unwrap();
+        assert_eq!(exported.build_id.as_deref(), Some("1000"));
+        assert_eq!(exported.saves.len(), 2);
+
+        let manifest = ExportManifest::read(&archive).unwrap();
+        assert_eq!(manifest.config.name, "palworld");
+        assert_eq!(manifest.saves, exported.saves);
+
+        let new_host = temp_dir.path().join("new");
+        let instance = Instance::from_export(&archive, &new_host).unwrap();
+        assert_eq!(instance.config.working_dir, new_host);
+        assert_eq!(restore_saves(&archive, &new_host).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(new_host.join("Pal/Saved/SaveGames/0/world/Level.sav")).unwrap(),
+            "level"
+        );
+        assert!(!new_host.join("PalServer.sh").exists());
+
+        // The saves are in place now, so importing again would overwrite them.
+        let error = instance.import_state(&archive).unwrap_err();
+        assert!(error.to_string().contains("already exists"), "{error}");
+    }
+
+    #[test]
+    fn archives_of_other_apps_or_without_a_manifest_are_refused() {
+        let temp_dir = tempdir().unwrap();
+        let archive = temp_dir.path().join("export.tar.gz");
+        instance_in(temp_dir.path())
+            .export_state(&archive, &SaveGlobs::new(["missing"]))
+            .unwrap();
+        let other = Instance::new(InstanceConfig {
+            app_id: 1_203_620,
+            working_dir: temp_dir.path().join("other"),
+            ..InstanceConfig::default()
+        });
+        let error = other.import_state(&archive).unwrap_err();
+        assert!(error.to_string().contains("not app 1203620"), "{error}");
+
+        let mut tar = Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        let mut header = Header::new_gnu();
+        header.set_size(0);
+        header.set_cksum();
+        tar.append_data(&mut header, "saves/world.sav", &[][..])
+            .unwrap();
+        fs::write(&archive, tar.into_inner().unwrap().finish().unwrap()).unwrap();
+        let error = ExportManifest::read(&archive).unwrap_err();
+        assert!(
+            error.to_string().contains("not an instance export"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! # Port Checks
+//!
+//! A game server whose port is already taken, e.g. by a second instance left on the
+//! default `8211`, usually starts anyway and only logs a bind failure somewhere in its
+//! own logs. The ports listed in [`InstanceConfig::ports`](crate::InstanceConfig::ports),
+//! and the `query_port`, are checked before [`Instance::start`](crate::Instance::start)
+//! and [`Instance::run_foreground`](crate::Instance::run_foreground) launch the server,
+//! which fail with [`InstanceError::PortInUse`] instead.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::ports::GamePort;
+//!
+//! let config = InstanceConfig {
+//!     ports: vec![GamePort::udp(8211), GamePort::tcp(25575)],
+//!     ..InstanceConfig::default()
+//! };
+//! ```
+use crate::config::ConfigIssue;
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use gsm_shared::port_mapping::PortForwarder;
+use gsm_shared::{Protocol, is_port_free};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A port the server binds when it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GamePort {
+    /// The port number.
+    pub port: u16,
+    /// The protocol the port is bound with; game traffic is usually UDP.
+    #[serde(default = "default_protocol")]
+    pub protocol: Protocol,
+}
+
+const fn default_protocol() -> Protocol {
+    Protocol::Udp
+}
+
+impl GamePort {
+    /// A UDP port, such as a game or Steam query port.
+    pub const fn udp(port: u16) -> Self {
+        Self {
+            port,
+            protocol: Protocol::Udp,
+        }
+    }
+
+    /// A TCP port, such as an RCON or REST API port.
+    pub const fn tcp(port: u16) -> Self {
+        Self {
+            port,
+            protocol: Protocol::Tcp,
+        }
+    }
+
+    /// Collects the problems with this port.
+    pub(crate) fn issues(self) -> Vec<ConfigIssue> {
+        if self.port == 0 {
+            vec![ConfigIssue::new(
+                "ports.port",
+                "port 0 is not a port the server can be reached on",
+                "set the port the server listens on",
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl fmt::Display for GamePort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.port, self.protocol)
+    }
+}
+
+impl Instance {
+    /// Returns the ports the server binds: [`InstanceConfig::ports`](crate::InstanceConfig::ports)
+    /// and the `query_port` (UDP), without duplicates.
+    pub fn game_ports(&self) -> Vec<GamePort> {
+        let mut ports = self.config.ports.clone();
+        if let Some(query_port) = self.config.query_port {
+            ports.push(GamePort::udp(query_port));
+        }
+        let mut unique: Vec<GamePort> = Vec::with_capacity(ports.len());
+        for port in ports {
+            if port.port != 0 && !unique.contains(&port) {
+                unique.push(port);
+            }
+        }
+        unique
+    }
+
+    /// Checks that no other process has bound any of the server's
+    /// [ports](Self::game_ports).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::PortInUse`] for the first port that is taken.
+    pub fn check_ports(&self) -> Result<(), InstanceError> {
+        self.game_ports()
+            .into_iter()
+            .find(|port| !is_port_free(port.port, port.protocol))
+            .map_or(Ok(()), |port| Err(InstanceError::PortInUse(port)))
+    }
+
+    /// Forwards the server's [ports](Self::game_ports) on the router when
+    /// `PORT_FORWARDING` asks for it, until the returned forwarder is dropped. Returns
+    /// `None` when forwarding is off, fails, or this is a dry run.
+    pub fn forward_ports(&self) -> Option<PortForwarder> {
+        if self.config.dry_run {
+            return None;
+        }
+        PortForwarder::from_env(
+            self.game_ports()
+                .into_iter()
+                .map(|port| (port.port, port.protocol)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use std::net::{Ipv4Addr, UdpSocket};
+
+    #[test]
+    fn game_ports_include_the_query_port_once() {
+        let instance = Instance::new(InstanceConfig {
+            ports: vec![GamePort::udp(8211), GamePort::tcp(8211), GamePort::udp(0)],
+            query_port: Some(8211),
+            ..InstanceConfig::default()
+        });
+        assert_eq!(
+            instance.game_ports(),
+            [GamePort::udp(8211), GamePort::tcp(8211)]
+        );
+    }
+
+    #[test]
+    fn check_ports_reports_the_taken_port() {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let instance = Instance::new(InstanceConfig {
+            ports: vec![GamePort::udp(port)],
+            ..InstanceConfig::default()
+        });
+
+        let error = instance.check_ports().unwrap_err();
+        assert!(matches!(error, InstanceError::PortInUse(taken) if taken == GamePort::udp(port)));
+        assert!(error.to_string().contains(&format!("{port}/udp")));
+
+        drop(socket);
+        instance.check_ports().unwrap();
+    }
+
+    #[test]
+    fn protocol_defaults_to_udp() {
+        let port: GamePort = serde_json::from_str(r#"{ "port": 8211 }"#).unwrap();
+        assert_eq!(port, GamePort::udp(8211));
+    }
+}
@@ -0,0 +1,381 @@
+//! # Preflight Checks
+//!
+//! Installs and updates tend to fail late: SteamCMD is missing, its 32-bit libraries are
+//! not installed, or the disk fills up halfway through a multi-gigabyte download.
+//! [`Instance::preflight`] checks for these up front and returns a [`PreflightReport`]
+//! listing every problem at once, each with a suggested fix.
+//!
+//! The space an install needs is taken from the sizes of the app's depots, as SteamCMD
+//! reports them, less what is already installed.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let report = instance.preflight();
+//! println!("{report}");
+//! if report.is_ok() {
+//!     instance.install().expect("Install failed");
+//! }
+//! ```
+use crate::config::InstanceConfig;
+use crate::instance::Instance;
+use crate::update::{app_info_section, fetch_app_info};
+use crate::usage::disk_usage;
+use crate::vdf::Vdf;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::Disks;
+use tracing::debug;
+use which::which;
+
+/// The 32-bit libraries SteamCMD needs, and the directories distributions install them in.
+const LIBS_32BIT: &[(&str, &[&str])] = &[
+    (
+        "ld-linux.so.2",
+        &[
+            "lib",
+            "lib32",
+            "usr/lib32",
+            "lib/i386-linux-gnu",
+            "usr/lib/i386-linux-gnu",
+        ],
+    ),
+    (
+        "libgcc_s.so.1",
+        &[
+            "lib32",
+            "usr/lib32",
+            "lib/i386-linux-gnu",
+            "usr/lib/i386-linux-gnu",
+        ],
+    ),
+];
+
+/// How a preflight check turned out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// Nothing to fix; the message says what was found.
+    Passed(String),
+    /// The check could not be completed, but need not stop an install.
+    Warning(String),
+    /// An install or update would fail.
+    Failed {
+        /// What is wrong.
+        problem: String,
+        /// How to fix it.
+        suggestion: String,
+    },
+}
+
+/// The outcome of one preflight check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightCheck {
+    /// What was checked, e.g. `steamcmd`.
+    pub name: &'static str,
+    /// How the check turned out.
+    pub status: CheckStatus,
+}
+
+impl fmt::Display for PreflightCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            CheckStatus::Passed(message) => write!(f, "[ok]   {}: {message}", self.name),
+            CheckStatus::Warning(message) => write!(f, "[warn] {}: {message}", self.name),
+            CheckStatus::Failed {
+                problem,
+                suggestion,
+            } => write!(f, "[fail] {}: {problem} ({suggestion})", self.name),
+        }
+    }
+}
+
+/// The results of [`Instance::preflight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// Every check, in the order they ran.
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Returns whether no check failed.
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Returns the checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.status, CheckStatus::Failed { .. }))
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{check}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Instance {
+    /// Checks that SteamCMD and its 32-bit libraries are installed, that the working
+    /// directory is writable, and that its disk has room for the install, before an
+    /// install or update starts downloading.
+    pub fn preflight(&self) -> PreflightReport {
+        let steamcmd = check_steamcmd();
+        let steamcmd_found = matches!(steamcmd.status, CheckStatus::Passed(_));
+        let checks = vec![
+            steamcmd,
+            check_32bit_libs(Path::new("/")),
+            check_writable(&self.config.working_dir),
+            check_disk_space(&self.config, steamcmd_found),
+        ];
+        PreflightReport { checks }
+    }
+}
+
+fn check_steamcmd() -> PreflightCheck {
+    let path = std::env::var_os("STEAMCMD_PATH").map_or_else(
+        || which("steamcmd").ok(),
+        |path| {
+            let path = PathBuf::from(path);
+            path.is_file().then_some(path)
+        },
+    );
+    PreflightCheck {
+        name: "steamcmd",
+        status: path.map_or_else(
+            || CheckStatus::Failed {
+                problem: "SteamCMD was not found".to_owned(),
+                suggestion: "install it, set STEAMCMD_PATH to it, or let installs download \
+                             it into STEAMCMD_DIR"
+                    .to_owned(),
+            },
+            |path| CheckStatus::Passed(path.display().to_string()),
+        ),
+    }
+}
+
+/// Checks for SteamCMD's 32-bit libraries under `root`.
+fn check_32bit_libs(root: &Path) -> PreflightCheck {
+    let missing: Vec<&str> = LIBS_32BIT
+        .iter()
+        .filter(|(lib, dirs)| !dirs.iter().any(|dir| root.join(dir).join(lib).exists()))
+        .map(|(lib, _)| *lib)
+        .collect();
+    PreflightCheck {
+        name: "32-bit libraries",
+        status: if missing.is_empty() {
+            CheckStatus::Passed("found".to_owned())
+        } else {
+            CheckStatus::Failed {
+                problem: format!("missing {}", missing.join(", ")),
+                suggestion: "install lib32gcc-s1 (Debian/Ubuntu) or glibc.i686 and \
+                             libgcc.i686 (Fedora)"
+                    .to_owned(),
+            }
+        },
+    }
+}
+
+fn check_writable(working_dir: &Path) -> PreflightCheck {
+    let writable =
+        fs::create_dir_all(working_dir).and_then(|()| tempfile::tempfile_in(working_dir));
+    PreflightCheck {
+        name: "working directory",
+        status: match writable {
+            Ok(_) => CheckStatus::Passed(format!("{} is writable", working_dir.display())),
+            Err(e) => CheckStatus::Failed {
+                problem: format!("{} is not writable: {e}", working_dir.display()),
+                suggestion: "fix its ownership or permissions, or choose another directory"
+                    .to_owned(),
+            },
+        },
+    }
+}
+
+fn check_disk_space(config: &InstanceConfig, query_steam: bool) -> PreflightCheck {
+    let name = "disk space";
+    let Some(available) = available_space(&config.working_dir) else {
+        return PreflightCheck {
+            name,
+            status: CheckStatus::Warning(format!(
+                "could not determine the free space at {}",
+                config.working_dir.display()
+            )),
+        };
+    };
+    let expected = if query_steam {
+        fetch_app_info(config.app_id)
+            .map_err(|e| e.to_string())
+            .and_then(|app_info| app_info_section(&app_info, config.app_id))
+            .and_then(|app| install_size(&app, config.launch_mode.is_windows()))
+    } else {
+        Err("SteamCMD is not available".to_owned())
+    };
+    let expected = match expected {
+        Ok(expected) => expected,
+        Err(reason) => {
+            debug!(
+                "Could not determine the install size of app {}: {reason}",
+                config.app_id
+            );
+            return PreflightCheck {
+                name,
+                status: CheckStatus::Warning(format!(
+                    "{} free; the install size is unknown",
+                    mib(available)
+                )),
+            };
+        }
+    };
+    let needed = expected.saturating_sub(disk_usage(&config.working_dir));
+    PreflightCheck {
+        name,
+        status: if available >= needed {
+            CheckStatus::Passed(format!("{} free, {} needed", mib(available), mib(needed)))
+        } else {
+            CheckStatus::Failed {
+                problem: format!("{} free, but {} needed", mib(available), mib(needed)),
+                suggestion: format!(
+                    "free up {} or move the working directory to a larger disk",
+                    mib(needed - available)
+                ),
+            }
+        },
+    }
+}
+
+/// Returns the free space on the disk holding `dir`, or its nearest existing ancestor.
+fn available_space(dir: &Path) -> Option<u64> {
+    let dir = dir.ancestors().find_map(|dir| dir.canonicalize().ok())?;
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(sysinfo::Disk::available_space)
+}
+
+/// Sums the `maxsize` of the app's depots for the platform it is installed for. Depots
+/// limited to another OS by `config.oslist` are skipped.
+fn install_size(app: &Vdf, force_windows: bool) -> Result<u64, String> {
+    let os = if force_windows { "windows" } else { "linux" };
+    let Some(Vdf::Section(depots)) = app.get("depots") else {
+        return Err("the app info lists no depots".to_owned());
+    };
+    let sizes: Vec<u64> = depots
+        .iter()
+        .filter(|(id, _)| id.bytes().all(|b| b.is_ascii_digit()))
+        .filter(|(_, depot)| {
+            depot
+                .find(&["config", "oslist"])
+                .is_none_or(|oslist| oslist.split(',').any(|listed| listed.trim() == os))
+        })
+        .filter_map(|(_, depot)| depot.find(&["maxsize"])?.parse().ok())
+        .collect();
+    if sizes.is_empty() {
+        return Err("the app info lists no depot sizes".to_owned());
+    }
+    Ok(sizes.iter().sum())
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{} MiB", bytes / (1024 * 1024))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_support::env_lock;
+    use tempfile::tempdir;
+
+    const APP: &str = r#""1"
+{
+    "depots"
+    {
+        "2" { "maxsize" "1000" "config" { "oslist" "windows" } }
+        "3" { "maxsize" "200" "config" { "oslist" "linux,macos" } }
+        "4" { "maxsize" "30" }
+        "branches" { "public" { "buildid" "1" } }
+    }
+}"#;
+
+    #[test]
+    fn install_size_counts_the_depots_for_the_platform() {
+        let app = Vdf::parse(APP).unwrap();
+        let app = app.get("1").unwrap();
+
+        assert_eq!(install_size(app, false), Ok(230));
+        assert_eq!(install_size(app, true), Ok(1030));
+        assert!(install_size(&Vdf::parse("\"depots\" {}").unwrap(), false).is_err());
+    }
+
+    #[test]
+    fn missing_32bit_libraries_are_reported() {
+        let root = tempdir().unwrap();
+        let check = check_32bit_libs(root.path());
+        assert!(matches!(
+            check.status,
+            CheckStatus::Failed { ref problem, .. } if problem == "missing ld-linux.so.2, libgcc_s.so.1"
+        ));
+
+        fs::create_dir_all(root.path().join("usr/lib32")).unwrap();
+        fs::write(root.path().join("usr/lib32/ld-linux.so.2"), "").unwrap();
+        fs::write(root.path().join("usr/lib32/libgcc_s.so.1"), "").unwrap();
+        assert!(matches!(
+            check_32bit_libs(root.path()).status,
+            CheckStatus::Passed(_)
+        ));
+    }
+
+    #[test]
+    fn preflight_reports_every_check() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", temp_dir.path().join("missing-steamcmd"));
+        }
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().join("server"),
+            ..InstanceConfig::default()
+        });
+
+        let report = instance.preflight();
+        let names: Vec<_> = report.checks.iter().map(|check| check.name).collect();
+        assert_eq!(
+            names,
+            [
+                "steamcmd",
+                "32-bit libraries",
+                "working directory",
+                "disk space"
+            ]
+        );
+        assert!(!report.is_ok());
+        assert_eq!(report.failures().next().unwrap().name, "steamcmd");
+        assert!(matches!(
+            report.checks.get(2).unwrap().status,
+            CheckStatus::Passed(_)
+        ));
+        assert!(!matches!(
+            report.checks.get(3).unwrap().status,
+            CheckStatus::Failed { .. }
+        ));
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+}
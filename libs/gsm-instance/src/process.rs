@@ -1,206 +1,156 @@
-use std::cmp::Ordering;
-use strsim::jaro_winkler;
-use sysinfo::{Pid, Signal, System};
-use tracing::{debug, error, info}; // Fuzzy matching
-
-/// Sends an interrupt signal (SIGINT) to the process with the given PID.
-///
-/// # Parameters
-/// - `pid`: The process ID to send SIGINT to.
-pub fn send_interrupt_to_pid(pid: u32) {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    let sys_pid = Pid::from(pid as usize);
-    if let Some(process) = sys.process(sys_pid) {
-        info!("Found process with PID: {}", pid);
-        if process.kill_with(Signal::Interrupt).is_some() {
-            info!("Sent interrupt signal to PID: {}", pid);
-        } else {
-            error!("Failed to send interrupt signal to PID: {}", pid);
-        }
-    } else {
-        debug!(
-            "Process with PID {} not found (it may have already stopped)",
-            pid
-        );
-    }
-}
-
-/// A struct for managing server processes.
+//! # Server Processes
+//!
+//! This module identifies the processes an instance owns. A [`ServerProcess`] is the
+//! server's own pid, recorded when it was spawned, and the process group it leads, if
+//! any. Daemonized servers are started in a process group of their own, so signalling
+//! the group also reaches the processes the server started itself, such as Wine's
+//! `wineserver` or the game binary behind a launch script, and nothing else.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::process::ServerProcess;
+//!
+//! let server = ServerProcess::new(12345);
+//! if server.is_running() {
+//!     server.send_interrupt().expect("Failed to interrupt the server");
+//! }
+//! ```
+use crate::errors::InstanceError;
+use gsm_cron::is_process_running;
+use nix::errno::Errno;
+use nix::sys::signal::{Signal, kill, killpg};
+use nix::unistd::{Pid, getpgid};
+use tracing::{debug, info};
+
+/// A server process owned by an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ServerProcess {
-    system: System,
+    pid: u32,
+    process_group: Option<u32>,
 }
 
 impl ServerProcess {
-    /// Creates a new instance of `ServerProcess` by initializing the system information.
-    pub fn new() -> Self {
-        let mut sys = System::new_all();
-        sys.refresh_all();
-        Self { system: sys }
+    /// Returns the process `pid`, along with its process group when it leads one.
+    pub fn new(pid: u32) -> Self {
+        let process_group = to_pid(pid)
+            .and_then(|target| getpgid(Some(target)).ok())
+            .filter(|group| group.as_raw().unsigned_abs() == pid)
+            .map(|_| pid);
+        Self { pid, process_group }
     }
 
-    /// Finds all processes whose executable path contains the specified substring.
-    ///
-    /// # Parameters
-    /// - `executable_name`: A substring of the executable name to search for.
-    ///
-    /// # Returns
-    /// A vector of references to matching processes.
-    pub fn find_processes(&mut self, executable_name: &str) -> Vec<&sysinfo::Process> {
-        self.system.refresh_all();
-        let executable_name = executable_name.to_ascii_lowercase();
-
-        debug!(
-            "Scanning for processes similar to '{}'. Total processes: {}",
-            executable_name,
-            self.system.processes().len()
-        );
-
-        let mut processes: Vec<(&sysinfo::Process, f64)> = self
-            .system
-            .processes()
-            .values()
-            .map(|process| {
-                let binding = process.name().to_ascii_lowercase();
-
-                let process_name = binding.to_str().unwrap_or("unknown");
-                let similarity = jaro_winkler(&executable_name, process_name);
-                (process, similarity)
-            })
-            .filter(|(_, similarity)| *similarity > 0.75) // Only consider high-confidence matches
-            .collect();
-
-        // Sort by confidence score (descending)
-        processes.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-
-        // Return only the processes (ignoring similarity scores)
-        processes.into_iter().map(|(process, _)| process).collect()
+    /// Returns the process `pid`, known to lead `process_group` (if `Some`).
+    pub const fn with_process_group(pid: u32, process_group: Option<u32>) -> Self {
+        Self { pid, process_group }
     }
 
-    /// Returns true if any process matching the given executable substring is running.
-    pub fn are_processes_running(&mut self, executable_name: &str) -> bool {
-        !self.find_processes(executable_name).is_empty()
+    /// The server's process id.
+    pub const fn pid(&self) -> u32 {
+        self.pid
     }
 
-    /// Sends an interrupt signal (SIGINT) to all processes whose executable path contains the given substring.
-    ///
-    /// # Parameters
-    /// - `executable_name`: The substring to match against the executable paths.
-    pub fn send_interrupt(&mut self, executable_name: &str) {
-        let processes = self.find_processes(executable_name);
-        if processes.is_empty() {
-            error!(
-                "Failed to find process with executable name: {}",
-                executable_name
-            );
-            return;
+    /// The process group the server leads, if it was started in one of its own.
+    pub const fn process_group(&self) -> Option<u32> {
+        self.process_group
+    }
+
+    /// Returns whether the server process is still running. It is never reaped here, so
+    /// the exit status stays with whoever owns the process; see [`is_process_running`].
+    pub fn is_running(&self) -> bool {
+        is_process_running(self.pid)
+    }
+
+    /// Sends `signal` to the server's process group, or to the server alone when it
+    /// does not lead one.
+    pub(crate) fn signal(&self, signal: Signal) -> nix::Result<()> {
+        match self.process_group {
+            Some(group) => killpg(to_pid(group).ok_or(Errno::EINVAL)?, signal),
+            None => kill(to_pid(self.pid).ok_or(Errno::EINVAL)?, signal),
         }
+    }
 
-        for process in processes {
-            let pid = process.pid().as_u32();
-            info!("Sending interrupt to process with PID: {}", pid);
-            send_interrupt_to_pid(pid);
+    /// Kills whatever is left of the server's process group once the server itself has
+    /// exited, such as helpers that ignored the shutdown signals.
+    pub(crate) fn kill_leftovers(&self) {
+        let Some(group) = self.process_group.and_then(to_pid) else {
+            return;
+        };
+        if killpg(group, Signal::SIGKILL).is_ok() {
+            debug!("Killed leftover processes in process group {group}");
         }
     }
-}
 
-/// Manual implementation of Clone for ServerProcess.
-/// This simply creates a new instance to refresh system information.
-impl Clone for ServerProcess {
-    fn clone(&self) -> Self {
-        Self::new()
+    /// Sends an interrupt signal (SIGINT) to the server, asking it to shut down.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::ProcessError`] when the server is not running or cannot
+    /// be signalled.
+    pub fn send_interrupt(&self) -> Result<(), InstanceError> {
+        self.signal(Signal::SIGINT).map_err(|e| {
+            InstanceError::ProcessError(format!(
+                "failed to send SIGINT to process {}: {e}",
+                self.pid
+            ))
+        })?;
+        info!("Sent interrupt signal to PID: {}", self.pid);
+        Ok(())
     }
 }
 
+fn to_pid(pid: u32) -> Option<Pid> {
+    i32::try_from(pid)
+        .ok()
+        .filter(|&pid| pid > 0)
+        .map(Pid::from_raw)
+}
+
 #[cfg(test)]
 mod tests {
-    #![allow(
-        clippy::unwrap_used,
-        clippy::expect_used,
-        clippy::indexing_slicing,
-        clippy::unreadable_literal
-    )]
+    #![allow(clippy::unwrap_used)]
 
     use super::*;
-    use std::process::{Child, Command};
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
     use std::thread;
     use std::time::Duration;
 
-    /// Helper to spawn a dummy process that sleeps for a specified number of seconds.
-    #[cfg(unix)]
-    fn spawn_dummy_process() -> Child {
-        Command::new("sleep")
+    #[test]
+    fn process_groups_are_only_recorded_for_group_leaders() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        let process = ServerProcess::new(child.id());
+        assert_eq!(process.process_group(), None);
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let mut leader = Command::new("sleep")
             .arg("5")
+            .process_group(0)
             .spawn()
-            .expect("Failed to spawn dummy process")
-    }
-
-    #[cfg(windows)]
-    fn spawn_dummy_process() -> Child {
-        Command::new("timeout")
-            .args(&["/T", "5"])
-            .spawn()
-            .expect("Failed to spawn dummy process")
-    }
-
-    #[test]
-    fn test_find_processes_none() {
-        let mut sp = ServerProcess::new();
-        let processes = sp.find_processes("nonexistent_executable_xyz");
-        assert!(processes.is_empty());
-    }
-
-    #[test]
-    fn test_are_processes_running() {
-        let mut sp = ServerProcess::new();
-        let mut child = spawn_dummy_process();
-        // Give the process a moment to start.
-        thread::sleep(Duration::from_millis(500));
-        let process_name = if cfg!(unix) { "sleep" } else { "timeout" };
-        let running = sp.are_processes_running(process_name);
-        // Clean up: terminate the dummy process.
-        let _ = child.kill();
-        // this code is technically unreachable, but kept for clippy purposes
-        if child.try_wait().is_ok() {
-            thread::sleep(Duration::from_secs(1)); // Wait for the process to terminate.
-        }
-        assert!(running);
+            .unwrap();
+        let process = ServerProcess::new(leader.id());
+        assert_eq!(process.process_group(), Some(leader.id()));
+        leader.kill().unwrap();
+        leader.wait().unwrap();
     }
 
     #[test]
-    fn test_send_interrupt_to_pid() {
-        let mut child = spawn_dummy_process();
-        let pid = child.id();
-        // Send interrupt to the dummy process.
-        send_interrupt_to_pid(pid);
-        // Allow some time for the signal to be delivered.
-        thread::sleep(Duration::from_secs(1));
-        // Try to wait for the process.
-        let result = child.try_wait().expect("Failed to wait on process");
-        // The dummy process should have terminated.
-        assert!(result.is_some());
-    }
+    fn send_interrupt_stops_the_process_and_fails_once_it_is_gone() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+        let process = ServerProcess::new(child.id());
+        assert!(process.is_running());
 
-    #[test]
-    fn test_send_interrupt() {
-        // Spawn two dummy processes.
-        let mut child1 = spawn_dummy_process();
-        let mut child2 = spawn_dummy_process();
-
-        // Give them a moment to start.
+        process.send_interrupt().unwrap();
         thread::sleep(Duration::from_millis(500));
 
-        // Use the common command name.
-        let process_name = if cfg!(unix) { "sleep" } else { "timeout" };
-        let mut sp = ServerProcess::new();
-        sp.send_interrupt(process_name);
-
-        // Allow time for interrupts.
-        thread::sleep(Duration::from_secs(1));
-
-        // Check that at least one of the processes has terminated.
-        let result1 = child1.try_wait().expect("Failed to wait on process 1");
-        let result2 = child2.try_wait().expect("Failed to wait on process 2");
-        assert!(result1.is_some() || result2.is_some());
+        assert!(!process.is_running());
+        // The exit status is left for the owner of the child to collect.
+        assert!(child.try_wait().unwrap().is_some());
+        assert!(matches!(
+            process.send_interrupt(),
+            Err(InstanceError::ProcessError(_))
+        ));
+        assert!(ServerProcess::new(999_999).send_interrupt().is_err());
     }
 }
@@ -4,16 +4,17 @@
 //! Valve's compatibility tool for running Windows games on Linux. It is a key component
 //! for enabling Windows-based game servers to run in a Linux environment.
 //!
-//! The module can automatically locate installed Proton versions, download specific
-//! versions from GitHub, and set up the necessary environment for a game server to
-//! use Proton.
+//! The module can automatically locate installed Proton versions, including GE-Proton
+//! builds in Steam's `compatibilitytools.d`, pick the newest by version number or the
+//! one pinned by a [`ProtonSelector`], download specific versions from GitHub, and set
+//! up the necessary environment for a game server to use Proton.
 use flate2::read::GzDecoder;
 use glob::glob;
-use reqwest;
+use gsm_shared::{http, paths};
 use std::env;
 use std::fs::{File, create_dir_all};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use tar::Archive;
 use tempfile::tempdir;
@@ -27,7 +28,9 @@ mod types;
 pub use releases::{
     ReleaseError, fetch_latest_release, fetch_specific_release, list_available_releases,
 };
-pub use types::{ProtonRelease, ProtonVersion, VersionError, parse_version};
+pub use types::{
+    ProtonRelease, ProtonSelector, ProtonVersion, VersionError, compare_versions, parse_version,
+};
 
 /// Represents errors that can occur during Proton-related operations.
 #[derive(Error, Debug)]
@@ -38,7 +41,7 @@ pub enum ProtonError {
 
     /// A network error occurred, such as failing to download a Proton release.
     #[error("Network error: {0}")]
-    Network(#[from] reqwest::Error),
+    Network(#[from] http::HttpError),
 
     /// Proton could not be found, either locally or through download.
     #[error("Failed to find proton: {0}")]
@@ -89,92 +92,112 @@ impl ProtonConfig {
     }
 }
 
-/// Finds an installed Proton version based on a version pattern.
-///
-/// This function searches for a Proton installation in common Steam and custom directories.
-/// It can search for a specific version or the first one it finds. If a version is not
-/// found locally, it will attempt to download it if a version string is provided.
+/// Returns the directories Proton builds are installed into: Steam's
+/// `compatibilitytools.d` directories (where GE-Proton and other custom builds live),
+/// `steamapps/common` (Valve's builds) and `PROTON_DIR`.
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = paths::compatibility_tool_dirs();
+    dirs.push(paths::steam_apps_common());
+    dirs.push(proton_dir());
+    dirs
+}
+
+/// Returns `PROTON_DIR`, else `~/proton`.
+fn proton_dir() -> PathBuf {
+    env::var_os("PROTON_DIR").map_or_else(|| paths::home_dir().join("proton"), PathBuf::from)
+}
+
+/// Lists the installed Proton builds, oldest first by [`compare_versions`].
 ///
-/// # Arguments
+/// A build is any directory in one of the search directories that contains a `proton`
+/// script; directories reachable through several search paths are listed once.
+pub fn installed_versions() -> Vec<ProtonVersion> {
+    let mut versions = Vec::new();
+    let mut seen = Vec::new();
+    for dir in search_dirs() {
+        let pattern = dir.join("*").join("proton");
+        let Ok(paths) = glob(&pattern.to_string_lossy()) else {
+            continue;
+        };
+        for path in paths.flatten().filter(|path| path.is_file()) {
+            let Some(dir) = path.parent().map(Path::to_path_buf) else {
+                continue;
+            };
+            let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+            if seen.contains(&canonical) {
+                continue;
+            }
+            seen.push(canonical);
+            let name = dir
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            debug!("Found Proton {} at {:?}", name, path);
+            versions.push(ProtonVersion { name, path, dir });
+        }
+    }
+    versions.sort_by(|a, b| compare_versions(&a.name, &b.name));
+    versions
+}
+
+/// Finds the Proton build chosen by `selector`.
 ///
-/// * `version`: An optional version string. If `Some`, it will look for a matching version.
-///   If `None`, it will return the first Proton installation it finds.
+/// - [`ProtonSelector::Latest`] picks the installed build with the highest version,
+///   falling back to a system-wide `proton` (e.g. `/usr/bin/proton`).
+/// - [`ProtonSelector::Version`] picks the installed build with exactly that name, or
+///   else the newest one whose name contains it; a missing GE-Proton release is
+///   downloaded.
+/// - [`ProtonSelector::Path`] uses the given install directory or `proton` script.
 ///
 /// # Errors
 ///
-/// Returns an error when no compatible Proton install can be found or downloaded,
+/// Returns an error when no matching Proton install can be found or downloaded,
 /// or when discovered paths/configuration cannot be converted into a valid config.
-pub fn find_proton(version: Option<&str>) -> Result<ProtonConfig, ProtonError> {
-    let home = env::var("HOME").unwrap_or_else(|_| "/home/steam".to_owned());
-    let proton_dir = env::var("PROTON_DIR").unwrap_or_else(|_| format!("{home}/proton"));
-
-    // Try glob search in common compatibility tools directories first
-    let glob_patterns = [
-        "/home/steam/.steam/root/compatibilitytools.d/*Proton*/proton".to_owned(),
-        "/home/steam/.steam/steam/compatibilitytools.d/*Proton*/proton".to_owned(),
-        format!("{home}/.local/share/Steam/compatibilitytools.d/*Proton*/proton"),
-        format!("{home}/.steam/root/compatibilitytools.d/*Proton*/proton"),
-        format!("{home}/.steam/steam/compatibilitytools.d/*Proton*/proton"),
-        format!("{home}/.steam/compatibilitytools.d/*Proton*/proton"),
-        format!("{proton_dir}/GE-Proton*/proton"),
-        format!("{proton_dir}/*Proton*/proton"),
-    ];
-
-    // If version is specified, try to find a specific version first
-    if let Some(v) = version {
-        debug!("Searching for specific Proton version: {}", v);
-
-        for pattern in &glob_patterns {
-            let version_pattern = pattern.replace("*Proton*", &format!("*{v}*"));
-            debug!("Trying pattern: {}", version_pattern);
-
-            if let Ok(paths) = glob(&version_pattern) {
-                for path in paths.flatten() {
-                    if path.is_file() {
-                        debug!("Found specific Proton version at: {:?}", path);
-                        return create_proton_config(&path, v);
-                    }
-                }
+pub fn find_proton(selector: &ProtonSelector) -> Result<ProtonConfig, ProtonError> {
+    match selector {
+        ProtonSelector::Latest => find_latest_proton(),
+        ProtonSelector::Version(version) => find_proton_version(version),
+        ProtonSelector::Path(path) => {
+            let script = if path.is_dir() {
+                path.join("proton")
+            } else {
+                path.clone()
+            };
+            if !script.is_file() {
+                return Err(ProtonError::NotFound(format!(
+                    "no proton script at {}",
+                    script.display()
+                )));
             }
+            let version = script.parent().and_then(Path::file_name).map_or_else(
+                || "custom".to_owned(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            create_proton_config(&script, &version)
         }
     }
+}
 
-    // If specific version wasn't found or not specified, try generic patterns
-    debug!("Searching for any Proton version using glob patterns");
-    for pattern in &glob_patterns {
-        debug!("Trying pattern: {}", pattern);
-        if let Ok(paths) = glob(pattern) {
-            for path in paths.flatten() {
-                if path.is_file() {
-                    debug!("Found Proton at: {:?}", path);
-                    // Extract version from path
-                    let version = path
-                        .parent()
-                        .and_then(|p| p.file_name())
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown");
-
-                    return create_proton_config(&path, version);
-                }
-            }
-        }
+/// Finds the newest installed Proton build, or a system-wide one.
+fn find_latest_proton() -> Result<ProtonConfig, ProtonError> {
+    if let Some(newest) = installed_versions().pop() {
+        debug!("Using newest installed Proton: {}", newest.name);
+        return create_proton_config(&newest.path, &newest.name);
     }
 
-    // If glob search failed, try specific paths
+    let home = paths::home_dir();
     let fallback_paths = [
-        "/usr/bin/proton".to_owned(),
-        format!("{home}/.local/share/Steam/steamapps/common/Proton/proton"),
-        "/usr/local/bin/proton".to_owned(),
-        format!("{home}/Proton/proton"),
-        format!("{home}/proton/proton"),
-        format!("{proton_dir}/proton"),
+        PathBuf::from("/usr/bin/proton"),
+        PathBuf::from("/usr/local/bin/proton"),
+        home.join("Proton/proton"),
+        home.join("proton/proton"),
+        proton_dir().join("proton"),
     ];
 
-    debug!("Glob search failed, trying specific paths");
+    debug!("No Proton builds installed, trying specific paths");
     for path in &fallback_paths {
-        debug!("Checking path: {}", path);
-        if Path::new(path).exists() {
-            debug!("Found Proton at: {}", path);
+        debug!("Checking path: {:?}", path);
+        if path.exists() {
+            debug!("Found Proton at: {:?}", path);
             return create_proton_config(path, "system");
         } else if let Ok(resolved_path) = which(path) {
             debug!("Found Proton at: {:?}", resolved_path);
@@ -182,22 +205,40 @@ pub fn find_proton(version: Option<&str>) -> Result<ProtonConfig, ProtonError> {
         }
     }
 
-    // If version was specified and not found, try to download it
-    if let Some(v) = version {
-        // Don't try to download if it looks like a path
-        if v.contains('/') {
-            debug!("Version '{}' looks like a path but wasn't found", v);
-        } else {
-            debug!("Attempting to download Proton version: {}", v);
-            return download_proton(v);
-        }
-    }
-
     Err(ProtonError::NotFound(
         "No Proton installation found.".to_owned(),
     ))
 }
 
+/// Finds the installed Proton build named `version`, downloading GE-Proton releases
+/// that are not installed.
+fn find_proton_version(version: &str) -> Result<ProtonConfig, ProtonError> {
+    debug!("Searching for specific Proton version: {}", version);
+    let installed = installed_versions();
+    let found = installed
+        .iter()
+        .find(|installed| installed.name == version)
+        .or_else(|| {
+            installed
+                .iter()
+                .rev()
+                .find(|installed| installed.name.contains(version))
+        });
+    if let Some(found) = found {
+        debug!("Found Proton {} at {:?}", found.name, found.path);
+        return create_proton_config(&found.path, &found.name);
+    }
+
+    if version.starts_with("GE-Proton") {
+        debug!("Attempting to download Proton version: {}", version);
+        return download_proton(version);
+    }
+
+    Err(ProtonError::NotFound(format!(
+        "Proton {version} is not installed"
+    )))
+}
+
 /// Creates a `ProtonConfig` from a given path and version string.
 fn create_proton_config<P: AsRef<Path>>(
     path: P,
@@ -245,18 +286,18 @@ pub fn download_proton(version: &str) -> Result<ProtonConfig, ProtonError> {
     );
 
     // Create the compatibility tools directory
-    let home = env::var("HOME")
-        .map_err(|_| ProtonError::EnvError("HOME environment variable not found".to_owned()))?;
-    let target_dir = format!("{home}/.steam/steam/compatibilitytools.d");
-    let proton_dir = format!("{target_dir}/{version}");
+    let target_dir = paths::compatibility_tools_dir();
 
-    debug!("Creating directory: {}", target_dir);
+    debug!("Creating directory: {:?}", target_dir);
     create_dir_all(&target_dir)?;
 
     // Check if this version is already installed
-    let proton_path = format!("{proton_dir}/proton");
-    if Path::new(&proton_path).exists() {
-        debug!("Proton {} is already installed at {}", version, proton_path);
+    let proton_path = target_dir.join(version).join("proton");
+    if proton_path.exists() {
+        debug!(
+            "Proton {} is already installed at {:?}",
+            version, proton_path
+        );
         return create_proton_config(&proton_path, version);
     }
 
@@ -267,14 +308,11 @@ pub fn download_proton(version: &str) -> Result<ProtonConfig, ProtonError> {
     let temp_dir = tempdir()?;
     let tar_gz_path = temp_dir.path().join(format!("{version}.tar.gz"));
 
-    let mut response = reqwest::blocking::get(&download_url)?;
-    let mut file = File::create(&tar_gz_path)?;
-
-    response.copy_to(&mut file)?;
+    http::download_to(&download_url, &tar_gz_path, None, |_| {})?;
     debug!("Downloaded Proton package to {:?}", tar_gz_path);
 
     // Extract the archive
-    info!("Extracting Proton to {}", target_dir);
+    info!("Extracting Proton to {:?}", target_dir);
     let tar_gz = File::open(&tar_gz_path)?;
     let tar = GzDecoder::new(tar_gz);
     let mut archive = Archive::new(tar);
@@ -358,8 +396,7 @@ pub fn init_proton_env(config: &mut ProtonConfig) -> Result<(), ProtonError> {
     config.env_vars.push(("WINEPREFIX".to_owned(), pfx_path));
 
     // Setup Steam client paths
-    let home = env::var("HOME").unwrap_or_else(|_| "/home/steam".to_owned());
-    let steam_root = format!("{home}/.local/share/Steam");
+    let steam_root = paths::steam_root().to_string_lossy().into_owned();
     let steam_lib_paths = [
         format!("{steam_root}/linux64"),
         format!("{steam_root}/ubuntu12_32/steam-runtime/amd64/usr/lib/x86_64-linux-gnu"),
@@ -585,7 +622,8 @@ mod tests {
             std::env::set_var("HOME", temp_home.path());
         }
 
-        let config = find_proton(Some("GE-Protontemp-test")).unwrap();
+        let config =
+            find_proton(&ProtonSelector::Version("GE-Protontemp-test".to_owned())).unwrap();
         assert_eq!(config.path, proton_path.to_string_lossy());
         assert_eq!(config.version, "GE-Protontemp-test");
 
@@ -594,6 +632,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn find_proton_picks_the_newest_build_and_honours_pins() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_home = tempdir().unwrap();
+        let install = |dir: &str| {
+            let dir = temp_home.path().join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("proton"), "fake").unwrap();
+            dir
+        };
+        install(".steam/steam/compatibilitytools.d/GE-Proton9-20");
+        let newest = install(".steam/steam/compatibilitytools.d/GE-Proton10-3");
+        let valve = install(".local/share/Steam/steamapps/common/Proton 9.0");
+        install(".local/share/Steam/steamapps/common/Proton - Experimental");
+
+        unsafe {
+            std::env::set_var("HOME", temp_home.path());
+        }
+
+        assert_eq!(installed_versions().len(), 4);
+        let latest = find_proton(&ProtonSelector::Latest).unwrap();
+        assert_eq!(latest.version, "GE-Proton10-3");
+        assert_eq!(latest.path, newest.join("proton").to_string_lossy());
+
+        let pinned = find_proton(&ProtonSelector::Version("Proton 9.0".to_owned())).unwrap();
+        assert_eq!(pinned.path, valve.join("proton").to_string_lossy());
+
+        let by_path = find_proton(&ProtonSelector::Path(valve)).unwrap();
+        assert_eq!(by_path.version, "Proton 9.0");
+        assert!(find_proton(&ProtonSelector::Path(temp_home.path().join("missing"))).is_err());
+        assert!(find_proton(&ProtonSelector::Version("Proton 7.0".to_owned())).is_err());
+
+        unsafe {
+            std::env::remove_var("HOME");
+        }
+    }
+
     #[test]
     fn create_proton_config_builds_basic_config() {
         let temp_dir = tempdir().unwrap();
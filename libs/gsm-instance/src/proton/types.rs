@@ -2,9 +2,112 @@
 //!
 //! This module defines the data structures and error types used for representing
 //! Proton versions and releases within the `gsm-instance` crate.
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
 use std::path::PathBuf;
 use thiserror::Error;
 
+/// Selects which Proton build runs a Windows server.
+///
+/// In configuration files this is written as `"latest"`, `{ version = "GE-Proton9-20" }`
+/// or `{ path = "/opt/proton" }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SelectorRepr", into = "SelectorRepr")]
+pub enum ProtonSelector {
+    /// The installed build with the highest version number; see [`compare_versions`].
+    #[default]
+    Latest,
+    /// The installed build with this name, e.g. `GE-Proton9-20` or `Proton 9.0`. A
+    /// GE-Proton release that is not installed is downloaded.
+    Version(String),
+    /// A Proton install directory, or the `proton` script inside one.
+    Path(PathBuf),
+}
+
+/// How a [`ProtonSelector`] is written. YAML only reads externally tagged enums as
+/// `!tags`, so the variants are told apart by their shape instead.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SelectorRepr {
+    Keyword(SelectorKeyword),
+    Version { version: String },
+    Path { path: PathBuf },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SelectorKeyword {
+    Latest,
+}
+
+impl From<SelectorRepr> for ProtonSelector {
+    fn from(repr: SelectorRepr) -> Self {
+        match repr {
+            SelectorRepr::Keyword(SelectorKeyword::Latest) => Self::Latest,
+            SelectorRepr::Version { version } => Self::Version(version),
+            SelectorRepr::Path { path } => Self::Path(path),
+        }
+    }
+}
+
+impl From<ProtonSelector> for SelectorRepr {
+    fn from(selector: ProtonSelector) -> Self {
+        match selector {
+            ProtonSelector::Latest => Self::Keyword(SelectorKeyword::Latest),
+            ProtonSelector::Version(version) => Self::Version { version },
+            ProtonSelector::Path(path) => Self::Path { path },
+        }
+    }
+}
+
+impl From<&str> for ProtonSelector {
+    /// Parses a `PROTON_VERSION` value: `latest`, a path, a short GE-Proton version
+    /// accepted by [`parse_version`] such as `9-20`, or a build name.
+    fn from(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("latest") {
+            Self::Latest
+        } else if value.contains('/') {
+            Self::Path(PathBuf::from(value))
+        } else if value.starts_with(|c: char| c.is_ascii_digit()) {
+            Self::Version(parse_version(value).unwrap_or_else(|_| value.to_owned()))
+        } else {
+            Self::Version(value.to_owned())
+        }
+    }
+}
+
+impl fmt::Display for ProtonSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Latest => f.write_str("latest"),
+            Self::Version(version) => f.write_str(version),
+            Self::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Orders Proton build names by the version numbers in them, so `GE-Proton10-1` is newer
+/// than `GE-Proton9-20` and `Proton 10.0` newer than `Proton 9.0`.
+///
+/// Builds without a version number, such as `Proton - Experimental`, sort below numbered
+/// releases: they track a moving branch rather than a release. Ties are broken by name.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |name: &str| -> Vec<u64> {
+        name.split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+    let (a_numbers, b_numbers) = (numbers(a), numbers(b));
+    match (a_numbers.is_empty(), b_numbers.is_empty()) {
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+        _ => a_numbers.cmp(&b_numbers),
+    }
+    .then_with(|| a.cmp(b))
+}
+
 /// Represents a specific, locally installed Proton version.
 #[derive(Debug, Clone)]
 pub struct ProtonVersion {
@@ -108,4 +211,73 @@ mod tests {
     fn test_parse_version_invalid() {
         assert!(parse_version("invalid").is_err());
     }
+
+    #[test]
+    fn compare_versions_orders_by_version_number() {
+        let mut names = vec![
+            "Proton 9.0",
+            "GE-Proton9-20",
+            "Proton - Experimental",
+            "GE-Proton10-1",
+            "GE-Proton9-3",
+            "Proton 10.0",
+        ];
+        names.sort_by(|a, b| compare_versions(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "Proton - Experimental",
+                "Proton 9.0",
+                "GE-Proton9-3",
+                "GE-Proton9-20",
+                "Proton 10.0",
+                "GE-Proton10-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn selector_parses_proton_version_values() {
+        assert_eq!(ProtonSelector::from("latest"), ProtonSelector::Latest);
+        assert_eq!(
+            ProtonSelector::from("9-20"),
+            ProtonSelector::Version("GE-Proton9-20".to_owned())
+        );
+        assert_eq!(
+            ProtonSelector::from("Proton - Experimental"),
+            ProtonSelector::Version("Proton - Experimental".to_owned())
+        );
+        assert_eq!(
+            ProtonSelector::from("/opt/proton"),
+            ProtonSelector::Path(PathBuf::from("/opt/proton"))
+        );
+    }
+
+    #[test]
+    fn selector_is_written_the_same_in_json_and_yaml() {
+        let selectors = [
+            ProtonSelector::Latest,
+            ProtonSelector::Version("GE-Proton9-20".to_owned()),
+            ProtonSelector::Path(PathBuf::from("/opt/proton")),
+        ];
+        let json: Vec<String> = selectors
+            .iter()
+            .map(|selector| serde_json::to_string(selector).unwrap())
+            .collect();
+        assert_eq!(
+            json,
+            [
+                r#""latest""#,
+                r#"{"version":"GE-Proton9-20"}"#,
+                r#"{"path":"/opt/proton"}"#
+            ]
+        );
+        for selector in selectors {
+            let yaml = serde_yaml::to_string(&selector).unwrap();
+            assert_eq!(
+                serde_yaml::from_str::<ProtonSelector>(&yaml).unwrap(),
+                selector
+            );
+        }
+    }
 }
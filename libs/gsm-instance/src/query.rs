@@ -0,0 +1,341 @@
+//! # Steam Server Queries
+//!
+//! This module speaks Valve's A2S protocol, the UDP query most Steam dedicated servers
+//! answer on their query port, to read live server details: name, map and how many
+//! players are online. It handles the challenge handshake newer servers require before
+//! answering, but not multi-packet responses, which only very large player lists need.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::query::{QUERY_TIMEOUT, query_info};
+//!
+//! let info = query_info(("127.0.0.1", 15637), QUERY_TIMEOUT).expect("Query failed");
+//! println!("{} on {}: {}/{} players", info.name, info.map, info.players, info.max_players);
+//! ```
+use crate::errors::InstanceError;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+use tracing::debug;
+
+/// How long a query waits for each response before giving up.
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const SINGLE_PACKET: [u8; 4] = [0xFF; 4];
+const INFO_REQUEST: u8 = b'T';
+const INFO_RESPONSE: u8 = b'I';
+const PLAYER_REQUEST: u8 = b'U';
+const PLAYER_RESPONSE: u8 = b'D';
+const CHALLENGE_RESPONSE: u8 = b'A';
+const INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+/// Servers answer a challenge at most this many times before we give up on them.
+const MAX_CHALLENGES: usize = 3;
+
+/// Details a server reports in answer to `A2S_INFO`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// The server name shown in the server browser.
+    pub name: String,
+    /// The map or world currently loaded.
+    pub map: String,
+    /// The game's folder name, e.g. `enshrouded`.
+    pub folder: String,
+    /// The full game name.
+    pub game: String,
+    /// Players currently online, including bots.
+    pub players: u8,
+    /// The maximum number of players.
+    pub max_players: u8,
+    /// Bots among `players`.
+    pub bots: u8,
+    /// Whether joining requires a password.
+    pub password: bool,
+    /// The server version.
+    pub version: String,
+}
+
+impl ServerInfo {
+    /// Players online, not counting bots.
+    pub const fn human_players(&self) -> u8 {
+        self.players.saturating_sub(self.bots)
+    }
+}
+
+/// One player reported in answer to `A2S_PLAYER`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerInfo {
+    /// The player's name; may be empty while they are still connecting.
+    pub name: String,
+    /// The player's score.
+    pub score: i32,
+    /// How long the player has been connected.
+    pub duration: Duration,
+}
+
+/// Queries the server at `addr` for its [`ServerInfo`].
+///
+/// # Errors
+///
+/// Returns [`InstanceError::QueryError`] if the server does not answer within `timeout`
+/// or its answer cannot be parsed.
+pub fn query_info(
+    addr: impl ToSocketAddrs,
+    timeout: Duration,
+) -> Result<ServerInfo, InstanceError> {
+    let socket = connect(addr, timeout)?;
+    let response = request(
+        &socket,
+        INFO_REQUEST,
+        INFO_PAYLOAD,
+        INFO_RESPONSE,
+        |challenge| challenge.map_or_else(Vec::new, |challenge| challenge.to_vec()),
+    )?;
+    parse_info(&response)
+}
+
+/// Queries the server at `addr` for the players currently online.
+///
+/// # Errors
+///
+/// Returns [`InstanceError::QueryError`] if the server does not answer within `timeout`
+/// or its answer cannot be parsed.
+pub fn query_players(
+    addr: impl ToSocketAddrs,
+    timeout: Duration,
+) -> Result<Vec<PlayerInfo>, InstanceError> {
+    let socket = connect(addr, timeout)?;
+    let response = request(&socket, PLAYER_REQUEST, &[], PLAYER_RESPONSE, |challenge| {
+        challenge.unwrap_or(SINGLE_PACKET).to_vec()
+    })?;
+    parse_players(&response)
+}
+
+fn query_error(message: impl Into<String>) -> InstanceError {
+    InstanceError::QueryError(message.into())
+}
+
+fn connect(addr: impl ToSocketAddrs, timeout: Duration) -> Result<UdpSocket, InstanceError> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(addr)?;
+    socket.set_read_timeout(Some(timeout))?;
+    Ok(socket)
+}
+
+/// Sends a `kind` request and returns the body of the `expected` response, answering
+/// challenges along the way. `challenge_suffix` builds the bytes appended to the
+/// request from the latest challenge, if any.
+fn request(
+    socket: &UdpSocket,
+    kind: u8,
+    payload: &[u8],
+    expected: u8,
+    challenge_suffix: impl Fn(Option<[u8; 4]>) -> Vec<u8>,
+) -> Result<Vec<u8>, InstanceError> {
+    let mut challenge = None;
+    for _ in 0..=MAX_CHALLENGES {
+        let mut packet = SINGLE_PACKET.to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(payload);
+        packet.extend(challenge_suffix(challenge));
+        socket.send(&packet)?;
+
+        let mut buffer = [0; 1400];
+        let len = socket
+            .recv(&mut buffer)
+            .map_err(|e| query_error(format!("no answer from server: {e}")))?;
+        let response = buffer.get(..len).unwrap_or_default();
+        let body = response
+            .strip_prefix(&SINGLE_PACKET)
+            .ok_or_else(|| query_error("multi-packet responses are not supported"))?;
+        match body.split_first() {
+            Some((&CHALLENGE_RESPONSE, rest)) => {
+                let number = rest
+                    .get(..4)
+                    .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+                    .ok_or_else(|| query_error("truncated challenge"))?;
+                debug!("Server answered with a challenge; retrying");
+                challenge = Some(number);
+            }
+            Some((&header, rest)) if header == expected => return Ok(rest.to_vec()),
+            Some((&header, _)) => {
+                return Err(query_error(format!(
+                    "unexpected response type 0x{header:02X}"
+                )));
+            }
+            None => return Err(query_error("empty response")),
+        }
+    }
+    Err(query_error("server kept answering with challenges"))
+}
+
+/// Reads the little-endian fields of a response body.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], InstanceError> {
+        let (head, tail) = self
+            .bytes
+            .split_first_chunk::<N>()
+            .ok_or_else(|| query_error("truncated response"))?;
+        self.bytes = tail;
+        Ok(*head)
+    }
+
+    fn u8(&mut self) -> Result<u8, InstanceError> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn string(&mut self) -> Result<String, InstanceError> {
+        let end = self
+            .bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| query_error("unterminated string"))?;
+        let (text, tail) = self.bytes.split_at(end);
+        self.bytes = tail.get(1..).unwrap_or_default();
+        Ok(String::from_utf8_lossy(text).into_owned())
+    }
+}
+
+fn parse_info(body: &[u8]) -> Result<ServerInfo, InstanceError> {
+    let mut reader = Reader { bytes: body };
+    let _protocol = reader.u8()?;
+    let name = reader.string()?;
+    let map = reader.string()?;
+    let folder = reader.string()?;
+    let game = reader.string()?;
+    let _app_id = reader.take::<2>()?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let bots = reader.u8()?;
+    let _server_type = reader.u8()?;
+    let _environment = reader.u8()?;
+    let password = reader.u8()? != 0;
+    let _vac = reader.u8()?;
+    let version = reader.string()?;
+    Ok(ServerInfo {
+        name,
+        map,
+        folder,
+        game,
+        players,
+        max_players,
+        bots,
+        password,
+        version,
+    })
+}
+
+fn parse_players(body: &[u8]) -> Result<Vec<PlayerInfo>, InstanceError> {
+    let mut reader = Reader { bytes: body };
+    let count = reader.u8()?;
+    (0..count)
+        .map(|_| {
+            let _index = reader.u8()?;
+            let name = reader.string()?;
+            let score = i32::from_le_bytes(reader.take()?);
+            let seconds = f32::from_le_bytes(reader.take()?);
+            Ok(PlayerInfo {
+                name,
+                score,
+                duration: Duration::try_from_secs_f32(seconds).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used, clippy::indexing_slicing)]
+
+    use super::*;
+    use std::thread;
+
+    const CHALLENGE: [u8; 4] = [1, 2, 3, 4];
+
+    fn info_body() -> Vec<u8> {
+        let mut body = vec![17];
+        for text in ["My Server", "Embervale", "enshrouded", "Enshrouded"] {
+            body.extend_from_slice(text.as_bytes());
+            body.push(0);
+        }
+        body.extend_from_slice(&[0x78, 0x56, 3, 16, 1, b'd', b'w', 1, 0]);
+        body.extend_from_slice(b"0.7.4\0");
+        body
+    }
+
+    fn players_body() -> Vec<u8> {
+        let mut body = vec![1, 0];
+        body.extend_from_slice(b"Tester\0");
+        body.extend_from_slice(&42_i32.to_le_bytes());
+        body.extend_from_slice(&90.0_f32.to_le_bytes());
+        body
+    }
+
+    /// Answers one request with a challenge, then the retried request with `body`.
+    fn fake_server(expected_request: u8, header: u8, body: Vec<u8>) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buffer = [0; 1400];
+            let (_, client) = socket.recv_from(&mut buffer).unwrap();
+            let mut challenge = SINGLE_PACKET.to_vec();
+            challenge.push(CHALLENGE_RESPONSE);
+            challenge.extend_from_slice(&CHALLENGE);
+            socket.send_to(&challenge, client).unwrap();
+
+            let (len, client) = socket.recv_from(&mut buffer).unwrap();
+            assert_eq!(buffer[4], expected_request);
+            assert!(buffer[..len].ends_with(&CHALLENGE));
+            let mut response = SINGLE_PACKET.to_vec();
+            response.push(header);
+            response.extend_from_slice(&body);
+            socket.send_to(&response, client).unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn query_info_answers_challenges_and_parses_the_response() {
+        let port = fake_server(INFO_REQUEST, INFO_RESPONSE, info_body());
+        let info = query_info(("127.0.0.1", port), QUERY_TIMEOUT).unwrap();
+        assert_eq!(info.name, "My Server");
+        assert_eq!(info.map, "Embervale");
+        assert_eq!(info.game, "Enshrouded");
+        assert_eq!((info.players, info.max_players, info.bots), (3, 16, 1));
+        assert_eq!(info.human_players(), 2);
+        assert!(info.password);
+        assert_eq!(info.version, "0.7.4");
+    }
+
+    #[test]
+    fn query_players_parses_each_player() {
+        let port = fake_server(PLAYER_REQUEST, PLAYER_RESPONSE, players_body());
+        let players = query_players(("127.0.0.1", port), QUERY_TIMEOUT).unwrap();
+        assert_eq!(
+            players,
+            vec![PlayerInfo {
+                name: "Tester".to_owned(),
+                score: 42,
+                duration: Duration::from_secs(90),
+            }]
+        );
+    }
+
+    #[test]
+    fn silent_servers_time_out() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let result = query_info(("127.0.0.1", port), Duration::from_millis(50));
+        assert!(matches!(result, Err(InstanceError::QueryError(_))));
+    }
+
+    #[test]
+    fn truncated_responses_are_rejected() {
+        let body = info_body();
+        assert!(parse_info(&body[..body.len() - 3]).is_err());
+        assert!(parse_players(&[2, 0]).is_err());
+    }
+}
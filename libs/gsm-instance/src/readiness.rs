@@ -0,0 +1,315 @@
+//! # Readiness Probes
+//!
+//! A server process is spawned long before players can join it: worlds load, ports are
+//! bound and Steam is contacted first. A [`Readiness`] check, set in
+//! [`InstanceConfig::readiness`](crate::InstanceConfig::readiness), lets
+//! [`Instance::start`](crate::Instance::start) wait until the server is actually up, and
+//! tell "listening on 8211" apart from "started but never became ready".
+//!
+//! A server is ready once its probe succeeds: a TCP port accepts connections, the
+//! server answers a Steam query on its (UDP) query port, or a line containing a pattern
+//! appears in its log. A server that exits or stays unready past the timeout is not.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::readiness::{Readiness, ReadinessProbe};
+//!
+//! let config = InstanceConfig {
+//!     readiness: Some(Readiness::new(ReadinessProbe::Tcp { port: 8211 })),
+//!     ..InstanceConfig::default()
+//! };
+//! ```
+use crate::config::ConfigIssue;
+use crate::errors::InstanceError;
+use crate::process::ServerProcess;
+use crate::query::{QUERY_TIMEOUT, query_info};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How long a server may take to become ready unless `timeout_secs` says otherwise.
+pub const DEFAULT_READINESS_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// How often the probe is retried while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What shows that a server is ready.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReadinessProbe {
+    /// A TCP connection to `port` on localhost succeeds.
+    Tcp { port: u16 },
+    /// The server answers a Steam A2S query on the UDP `port`.
+    Query { port: u16 },
+    /// A line containing `pattern` is written to the log at `path`, relative to the
+    /// working directory.
+    LogLine { path: PathBuf, pattern: String },
+}
+
+impl fmt::Display for ReadinessProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp { port } => write!(f, "listening on {port}"),
+            Self::Query { port } => write!(f, "answering queries on {port}"),
+            Self::LogLine { path, pattern } => {
+                write!(f, "logged '{pattern}' to {}", path.display())
+            }
+        }
+    }
+}
+
+/// A readiness check: a probe and how long to wait for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Readiness {
+    /// What shows that the server is ready.
+    pub probe: ReadinessProbe,
+    /// How long the server may take to become ready, in seconds.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+const fn default_timeout_secs() -> u64 {
+    DEFAULT_READINESS_TIMEOUT.as_secs()
+}
+
+impl Readiness {
+    /// Waits up to [`DEFAULT_READINESS_TIMEOUT`] for `probe`.
+    pub const fn new(probe: ReadinessProbe) -> Self {
+        Self {
+            probe,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+
+    /// Sets how long the server may take to become ready.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_secs = timeout.as_secs();
+        self
+    }
+
+    /// Collects the problems with this check.
+    pub(crate) fn issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        match &self.probe {
+            ReadinessProbe::Tcp { port: 0 } | ReadinessProbe::Query { port: 0 } => {
+                issues.push(ConfigIssue::new(
+                    "readiness.probe.port",
+                    "port 0 cannot be probed",
+                    "set the port the server listens on",
+                ));
+            }
+            ReadinessProbe::LogLine { pattern, .. } if pattern.is_empty() => {
+                issues.push(ConfigIssue::new(
+                    "readiness.probe.pattern",
+                    "the log line pattern is empty, so any line would match",
+                    "set text the server logs once it is ready, e.g. 'Server started'",
+                ));
+            }
+            _ => {}
+        }
+        if self.timeout_secs == 0 {
+            issues.push(ConfigIssue::new(
+                "readiness.timeout_secs",
+                "a timeout of 0 seconds leaves no time to become ready",
+                "allow the server the time it takes to start, e.g. 300",
+            ));
+        }
+        issues
+    }
+
+    /// Starts watching for readiness in `working_dir`. Call this before spawning the
+    /// server, so only log lines written by the new server count.
+    pub fn watch(&self, working_dir: &Path) -> ReadinessWatch {
+        let log_offset = match &self.probe {
+            ReadinessProbe::LogLine { path, .. } => {
+                std::fs::metadata(working_dir.join(path)).map_or(0, |metadata| metadata.len())
+            }
+            _ => 0,
+        };
+        ReadinessWatch {
+            readiness: self.clone(),
+            working_dir: working_dir.to_path_buf(),
+            log_offset,
+        }
+    }
+}
+
+/// A readiness check started by [`Readiness::watch`].
+#[derive(Debug)]
+pub struct ReadinessWatch {
+    readiness: Readiness,
+    working_dir: PathBuf,
+    log_offset: u64,
+}
+
+impl ReadinessWatch {
+    /// Waits for the server `process` to become ready, returning how long it took.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstanceError::NotReady`] when the server exits or does not become
+    /// ready within the timeout.
+    pub fn wait(self, process: &ServerProcess) -> Result<Duration, InstanceError> {
+        self.wait_while(|| process.is_running())
+    }
+
+    /// Waits for the server to become ready while `running` reports it still runs, for
+    /// callers that reap the server process themselves.
+    pub(crate) fn wait_while(
+        mut self,
+        running: impl Fn() -> bool,
+    ) -> Result<Duration, InstanceError> {
+        let probe = self.readiness.probe.clone();
+        let timeout = Duration::from_secs(self.readiness.timeout_secs);
+        let started = Instant::now();
+        debug!("Waiting up to {timeout:?} for the server to be {probe}");
+        loop {
+            if self.probe() {
+                let elapsed = started.elapsed();
+                info!("Server is ready: {probe} after {}s", elapsed.as_secs());
+                return Ok(elapsed);
+            }
+            if !running() {
+                return Err(InstanceError::NotReady(format!(
+                    "the server exited before it was {probe}"
+                )));
+            }
+            if started.elapsed() >= timeout {
+                return Err(InstanceError::NotReady(format!(
+                    "the server started but was not {probe} within {}s",
+                    timeout.as_secs()
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Runs the probe once.
+    fn probe(&mut self) -> bool {
+        match &self.readiness.probe {
+            ReadinessProbe::Tcp { port } => TcpStream::connect_timeout(
+                &SocketAddr::from((Ipv4Addr::LOCALHOST, *port)),
+                POLL_INTERVAL,
+            )
+            .is_ok(),
+            ReadinessProbe::Query { port } => {
+                query_info((Ipv4Addr::LOCALHOST, *port), QUERY_TIMEOUT).is_ok()
+            }
+            ReadinessProbe::LogLine { path, pattern } => {
+                let path = self.working_dir.join(path);
+                let (found, offset) = scan_log(&path, self.log_offset, pattern);
+                self.log_offset = offset;
+                found
+            }
+        }
+    }
+}
+
+/// Reads the complete lines of the log at `path` from `offset` on, returning whether one
+/// contains `pattern` and the offset to continue from. A log that shrank was rotated or
+/// truncated, and is read from the start.
+fn scan_log(path: &Path, offset: u64, pattern: &str) -> (bool, u64) {
+    let Ok(mut file) = File::open(path) else {
+        return (false, offset);
+    };
+    let len = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut offset = if len < offset { 0 } else { offset };
+    if file.seek(SeekFrom::Start(offset)).is_err() {
+        return (false, offset);
+    }
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    while let Ok(read @ 1..) = reader.read_line(&mut line) {
+        if !line.ends_with('\n') {
+            // A partially written line; read it again once it is complete.
+            break;
+        }
+        offset += read as u64;
+        if line.contains(pattern) {
+            return (true, offset);
+        }
+        line.clear();
+    }
+    (false, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    #[test]
+    fn tcp_probe_waits_for_the_port() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let readiness = Readiness::new(ReadinessProbe::Tcp { port });
+        let process = ServerProcess::new(std::process::id());
+
+        assert!(readiness.watch(Path::new(".")).wait(&process).is_ok());
+
+        drop(listener);
+        let error = readiness
+            .with_timeout(Duration::from_secs(1))
+            .watch(Path::new("."))
+            .wait(&process)
+            .unwrap_err();
+        assert!(matches!(error, InstanceError::NotReady(reason) if reason.contains("within 1s")));
+    }
+
+    #[test]
+    fn log_probe_ignores_lines_from_before_the_start() {
+        let temp_dir = tempdir().unwrap();
+        let log = temp_dir.path().join("server.log");
+        fs::write(&log, "Server started\n").unwrap();
+        let readiness = Readiness::new(ReadinessProbe::LogLine {
+            path: PathBuf::from("server.log"),
+            pattern: "Server started".to_owned(),
+        });
+
+        let mut watch = readiness.watch(temp_dir.path());
+        assert!(!watch.probe());
+        let mut file = fs::OpenOptions::new().append(true).open(&log).unwrap();
+        write!(file, "Loading world\nServer sta").unwrap();
+        assert!(!watch.probe());
+        writeln!(file, "rted").unwrap();
+        assert!(watch.probe());
+    }
+
+    #[test]
+    fn exited_servers_are_not_ready() {
+        let mut child = Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        let readiness = Readiness::new(ReadinessProbe::Tcp { port: 1 });
+
+        let error = readiness
+            .watch(Path::new("."))
+            .wait(&ServerProcess::new(child.id()))
+            .unwrap_err();
+        assert!(matches!(error, InstanceError::NotReady(reason) if reason.contains("exited")));
+    }
+
+    #[test]
+    fn invalid_checks_are_reported() {
+        let readiness = Readiness {
+            probe: ReadinessProbe::Query { port: 0 },
+            timeout_secs: 0,
+        };
+        let fields: Vec<_> = readiness.issues().iter().map(|issue| issue.field).collect();
+        assert_eq!(fields, ["readiness.probe.port", "readiness.timeout_secs"]);
+    }
+}
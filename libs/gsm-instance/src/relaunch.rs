@@ -0,0 +1,252 @@
+//! # Foreground Relaunch
+//!
+//! A server run with `start --foreground` belongs to the process streaming its output,
+//! and when that process exits, so does the container. Maintenance that stops such a
+//! server, like an auto-update or a scheduled restart, therefore must not start a
+//! daemonized replacement. Before stopping it, maintenance places a [`RelaunchHold`] in
+//! the working directory instead. The foreground process sees the hold when the server
+//! exits, waits for it to be released, and launches the server in the foreground again,
+//! while maintenance waits for the new server's state file.
+//!
+//! Daemonized servers are simply started again by the maintenance itself.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let hold = instance.hold_foreground_relaunch().expect("Unreadable state");
+//! instance.stop().expect("Stop failed");
+//! let pid = instance.relaunch(hold).expect("Relaunch failed");
+//! println!("Server running again as pid {pid}");
+//! ```
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use gsm_cron::wait_for_termination;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Name of the hold file in the working directory.
+pub const RELAUNCH_HOLD_FILE: &str = "relaunch.hold";
+
+/// How long the foreground process waits for a hold to be released before relaunching the
+/// server anyway, e.g. because the maintenance holding it crashed.
+pub const RELAUNCH_HOLD_TIMEOUT: Duration = Duration::from_hours(2);
+
+/// How long maintenance waits for the foreground process to relaunch the server.
+pub const RELAUNCH_TIMEOUT: Duration = Duration::from_mins(2);
+
+/// How often both sides check on each other.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Asks the foreground process to relaunch the server once the hold is released, which
+/// happens when it is dropped or passed to [`Instance::relaunch`].
+#[derive(Debug)]
+pub struct RelaunchHold {
+    path: PathBuf,
+    /// The pid of the server being stopped, to tell its replacement apart.
+    pid: u32,
+}
+
+impl RelaunchHold {
+    fn place(working_dir: &Path, pid: u32) -> io::Result<Self> {
+        let path = working_dir.join(RELAUNCH_HOLD_FILE);
+        fs::write(&path, pid.to_string())?;
+        debug!("Placed relaunch hold {}", path.display());
+        Ok(Self { path, pid })
+    }
+
+    /// Returns whether maintenance holds the server in `working_dir`.
+    pub fn is_placed(working_dir: &Path) -> bool {
+        working_dir.join(RELAUNCH_HOLD_FILE).exists()
+    }
+
+    /// Removes a hold left in `working_dir`, e.g. by maintenance that did not finish.
+    pub fn clear(working_dir: &Path) {
+        match fs::remove_file(working_dir.join(RELAUNCH_HOLD_FILE)) {
+            Ok(()) => debug!("Removed a stale relaunch hold"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove the relaunch hold: {e}"),
+        }
+    }
+}
+
+impl Drop for RelaunchHold {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            warn!("Failed to release the relaunch hold: {e}");
+        }
+    }
+}
+
+impl Instance {
+    /// Places a [`RelaunchHold`] when the running server was started in the foreground,
+    /// before maintenance stops it. Returns `None` for daemonized servers, stopped
+    /// servers and dry runs, which [`Instance::relaunch`] starts itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the state file is unreadable or the hold cannot be written.
+    pub fn hold_foreground_relaunch(&self) -> Result<Option<RelaunchHold>, InstanceError> {
+        if self.config.dry_run {
+            return Ok(None);
+        }
+        match self.state()? {
+            Some(state) if state.foreground => Ok(Some(RelaunchHold::place(
+                &self.config.working_dir,
+                state.pid,
+            )?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Starts the server again after maintenance stopped it, returning its pid. With a
+    /// `hold`, the hold is released and the foreground process relaunches the server;
+    /// otherwise it is started daemonized, like [`Instance::start`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Instance::start`], or an error when the foreground process
+    /// does not relaunch the server within [`RELAUNCH_TIMEOUT`].
+    pub fn relaunch(&self, hold: Option<RelaunchHold>) -> Result<u32, InstanceError> {
+        let Some(hold) = hold else {
+            return self.start().map(reap_in_background);
+        };
+        let previous = hold.pid;
+        drop(hold);
+        info!("Waiting for the foreground process to relaunch the server...");
+        let deadline = Instant::now() + RELAUNCH_TIMEOUT;
+        loop {
+            if let Some(state) = self.state()?
+                && state.pid != previous
+            {
+                return Ok(state.pid);
+            }
+            if Instant::now() >= deadline {
+                return Err(InstanceError::CommandExecutionError(format!(
+                    "the foreground process did not relaunch the server within {RELAUNCH_TIMEOUT:?}"
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Waits, in the foreground process, for maintenance to release its hold. Returns
+    /// `false` when a termination signal arrives first, as the server should then stay
+    /// down.
+    pub(crate) async fn await_relaunch(&self) -> bool {
+        info!("Server stopped for maintenance; waiting to relaunch it...");
+        let working_dir = &self.config.working_dir;
+        let deadline = Instant::now() + RELAUNCH_HOLD_TIMEOUT;
+        loop {
+            tokio::select! {
+                () = tokio::time::sleep(POLL_INTERVAL) => {
+                    if !RelaunchHold::is_placed(working_dir) {
+                        return true;
+                    }
+                    if Instant::now() >= deadline {
+                        warn!("Maintenance held the server for over {RELAUNCH_HOLD_TIMEOUT:?}; relaunching it anyway");
+                        RelaunchHold::clear(working_dir);
+                        return true;
+                    }
+                }
+                received = wait_for_termination() => {
+                    info!("Received {received} while the server was stopped; not relaunching it");
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Collects the exit status of a server started for good in a background thread, so it
+/// does not linger as a zombie in long-running processes. Returns its pid.
+pub(crate) fn reap_in_background(mut child: Child) -> u32 {
+    let pid = child.id();
+    thread::spawn(move || {
+        if let Ok(status) = child.wait() {
+            debug!("Server process {pid} exited with {status}");
+        }
+    });
+    pid
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use crate::state::InstanceState;
+    use tempfile::tempdir;
+
+    #[test]
+    fn only_foreground_servers_are_held() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        assert!(instance.hold_foreground_relaunch().unwrap().is_none());
+
+        // This process stands in for a running server.
+        let state = InstanceState::capture(std::process::id(), &instance.config);
+        state.write(&instance.config.state_file()).unwrap();
+        assert!(instance.hold_foreground_relaunch().unwrap().is_none());
+
+        InstanceState {
+            foreground: true,
+            ..state
+        }
+        .write(&instance.config.state_file())
+        .unwrap();
+        let hold = instance.hold_foreground_relaunch().unwrap().unwrap();
+        assert!(RelaunchHold::is_placed(temp_dir.path()));
+        drop(hold);
+        assert!(!RelaunchHold::is_placed(temp_dir.path()));
+    }
+
+    #[test]
+    fn relaunching_waits_for_the_replacement_server() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        let mut replacement = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let hold = RelaunchHold::place(temp_dir.path(), std::process::id()).unwrap();
+
+        // Stands in for the foreground process, which relaunches once the hold is gone.
+        let state_file = instance.config.state_file();
+        let config = instance.config.clone();
+        let replacement_pid = replacement.id();
+        let working_dir = temp_dir.path().to_path_buf();
+        let foreground = thread::spawn(move || {
+            while RelaunchHold::is_placed(&working_dir) {
+                thread::sleep(Duration::from_millis(20));
+            }
+            InstanceState {
+                foreground: true,
+                ..InstanceState::capture(replacement_pid, &config)
+            }
+            .write(&state_file)
+            .unwrap();
+        });
+
+        assert_eq!(instance.relaunch(Some(hold)).unwrap(), replacement_pid);
+        foreground.join().unwrap();
+        replacement.kill().unwrap();
+        replacement.wait().unwrap();
+    }
+}
@@ -0,0 +1,281 @@
+//! # Resource Limits
+//!
+//! Game servers often share a host, and one that leaks memory or busy-loops on every core
+//! starves the rest. [`ResourceLimits`] constrains a launched server: the CPU cores it
+//! may run on, the nice level it is scheduled at and, where cgroup v2 is available, a
+//! hard memory limit. Each limit also covers the processes the server starts, such as
+//! Proton's wineserver.
+//!
+//! The CPU affinity and nice level are applied in the forked process before it executes
+//! the server, so a limit the kernel refuses, such as a negative nice level without
+//! `CAP_SYS_NICE`, fails the start. The memory limit needs a writable, delegated cgroup v2
+//! hierarchy (e.g. a systemd unit with `Delegate=yes`); without one it is skipped with a
+//! warning. The server's cgroup, named by [`InstanceConfig::service_name`], is created
+//! under this process's own and reused by later starts.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::resources::ResourceLimits;
+//!
+//! let config = InstanceConfig {
+//!     name: "palworld".to_owned(),
+//!     resources: ResourceLimits {
+//!         cpu_affinity: vec![2, 3],
+//!         nice: Some(5),
+//!         memory_limit_mb: Some(16_384),
+//!     },
+//!     ..InstanceConfig::default()
+//! };
+//! assert!(config.issues().iter().all(|issue| issue.field != "resources.nice"));
+//! ```
+use crate::config::{ConfigIssue, InstanceConfig};
+use crate::errors::InstanceError;
+use nix::fcntl::{OFlag, open};
+use nix::sched::{CpuSet, sched_setaffinity};
+use nix::sys::stat::Mode;
+use nix::unistd::{Pid, write};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Where the cgroup v2 hierarchy is mounted.
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Limits on the CPU and memory a launched server may use. All are unset by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// The CPU cores, numbered from 0, the server may run on. Empty means any.
+    pub cpu_affinity: Vec<usize>,
+    /// The nice level to run the server at, from -20 (highest priority) to 19 (lowest).
+    /// Levels below this process's own need `CAP_SYS_NICE`.
+    pub nice: Option<i32>,
+    /// The most memory, in MiB, the server and its children may use before the kernel
+    /// reclaims from them and then OOM-kills them.
+    pub memory_limit_mb: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Returns whether no limit is set.
+    pub const fn is_empty(&self) -> bool {
+        self.cpu_affinity.is_empty() && self.nice.is_none() && self.memory_limit_mb.is_none()
+    }
+
+    /// Collects the problems with these limits.
+    pub(crate) fn issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        if let Some(cpu) = self
+            .cpu_affinity
+            .iter()
+            .find(|&&cpu| cpu >= CpuSet::count())
+        {
+            issues.push(ConfigIssue::new(
+                "resources.cpu_affinity",
+                format!(
+                    "CPU {cpu} is beyond the {} the kernel supports",
+                    CpuSet::count()
+                ),
+                "list the cores to run on, numbered from 0, e.g. [2, 3]",
+            ));
+        }
+        if let Some(nice) = self.nice.filter(|nice| !(-20..=19).contains(nice)) {
+            issues.push(ConfigIssue::new(
+                "resources.nice",
+                format!("nice level {nice} is outside -20 to 19"),
+                "use a level from -20 (highest priority) to 19 (lowest), e.g. 5",
+            ));
+        }
+        if self.memory_limit_mb == Some(0) {
+            issues.push(ConfigIssue::new(
+                "resources.memory_limit_mb",
+                "a memory limit of 0 MiB leaves the server no memory",
+                "set the limit in MiB, e.g. 8192, or leave it unset",
+            ));
+        }
+        issues
+    }
+
+    /// Returns the CPU set to pin the server to, if any.
+    fn cpu_set(&self) -> Result<Option<CpuSet>, InstanceError> {
+        if self.cpu_affinity.is_empty() {
+            return Ok(None);
+        }
+        let mut cpus = CpuSet::new();
+        for &cpu in &self.cpu_affinity {
+            cpus.set(cpu).map_err(|_| {
+                InstanceError::ConfigError(format!(
+                    "CPU {cpu} in resources.cpu_affinity is out of range"
+                ))
+            })?;
+        }
+        Ok(Some(cpus))
+    }
+}
+
+/// Makes `command`, the server's launch command, run within `config.resources`. The
+/// server's memory cgroup is set up here; the remaining limits are applied when the
+/// command is spawned.
+///
+/// # Errors
+///
+/// Returns [`InstanceError::ConfigError`] when a CPU in the affinity list is out of range.
+pub(crate) fn apply_limits(
+    command: &mut Command,
+    config: &InstanceConfig,
+) -> Result<(), InstanceError> {
+    let limits = &config.resources;
+    if limits.is_empty() {
+        return Ok(());
+    }
+    info!("Applying resource limits: {limits:?}");
+    let cpus = limits.cpu_set()?;
+    let nice = limits.nice;
+    let cgroup_procs = limits.memory_limit_mb.and_then(|limit_mb| {
+        memory_cgroup(&config.service_name(), limit_mb)
+            .inspect_err(|e| warn!("Memory limit not applied: {e}"))
+            .ok()
+    });
+
+    // SAFETY: between fork and exec the closure only makes system calls, on values
+    // prepared before the fork, and allocates nothing.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(procs) = &cgroup_procs {
+                // Writing 0 moves the writing process itself.
+                let fd = open(
+                    procs.as_c_str(),
+                    OFlag::O_WRONLY | OFlag::O_CLOEXEC,
+                    Mode::empty(),
+                )?;
+                write(&fd, b"0")?;
+            }
+            if let Some(cpus) = &cpus {
+                sched_setaffinity(Pid::from_raw(0), cpus)?;
+            }
+            if let Some(nice) = nice
+                && nix::libc::setpriority(nix::libc::PRIO_PROCESS, 0, nice) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Returns this process's cgroup v2 path from the contents of `/proc/self/cgroup`.
+fn own_cgroup(proc_cgroup: &str) -> Option<&str> {
+    proc_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+}
+
+/// Creates the cgroup `name`, under this process's own, with a memory limit of
+/// `limit_mb` MiB. Returns the path of its `cgroup.procs`, to move the server into.
+fn memory_cgroup(name: &str, limit_mb: u64) -> io::Result<CString> {
+    let root = Path::new(CGROUP_ROOT);
+    if !root.join("cgroup.controllers").is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("cgroup v2 is not mounted at {CGROUP_ROOT}"),
+        ));
+    }
+    let proc_cgroup = fs::read_to_string("/proc/self/cgroup")?;
+    let own = own_cgroup(&proc_cgroup).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "this process is not in a cgroup v2 group",
+        )
+    })?;
+    let parent: PathBuf = root.join(own.trim_start_matches('/'));
+    // Lets child groups limit memory; fails harmlessly when they already can.
+    let _ = fs::write(parent.join("cgroup.subtree_control"), "+memory");
+
+    let cgroup = parent.join(name);
+    fs::create_dir_all(&cgroup)?;
+    fs::write(
+        cgroup.join("memory.max"),
+        limit_mb.saturating_mul(1024 * 1024).to_string(),
+    )
+    .map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot set {}/memory.max: {e}", cgroup.display()),
+        )
+    })?;
+    info!("Limiting memory to {limit_mb} MiB in {}", cgroup.display());
+    CString::new(cgroup.join("cgroup.procs").into_os_string().into_vec()).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn limits_outside_their_ranges_are_reported() {
+        let limits = ResourceLimits {
+            cpu_affinity: vec![0, CpuSet::count()],
+            nice: Some(20),
+            memory_limit_mb: Some(0),
+        };
+        let fields: Vec<&str> = limits.issues().iter().map(|issue| issue.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "resources.cpu_affinity",
+                "resources.nice",
+                "resources.memory_limit_mb"
+            ]
+        );
+        assert!(ResourceLimits::default().is_empty());
+        assert!(ResourceLimits::default().issues().is_empty());
+    }
+
+    #[test]
+    fn cgroups_are_named_after_the_server() {
+        let mut config = InstanceConfig {
+            app_id: 2_394_010,
+            ..InstanceConfig::default()
+        };
+        assert_eq!(config.service_name(), "gsm-2394010");
+        config.name = "My Server/1".to_owned();
+        assert_eq!(config.service_name(), "gsm-My-Server-1");
+
+        let proc_cgroup = "12:cpu:/legacy\n0::/system.slice/gsm.service\n";
+        assert_eq!(own_cgroup(proc_cgroup), Some("/system.slice/gsm.service"));
+        assert_eq!(own_cgroup("12:cpu:/legacy\n"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn the_server_runs_with_its_affinity_and_nice_level() {
+        let config = InstanceConfig {
+            resources: ResourceLimits {
+                cpu_affinity: vec![0],
+                nice: Some(19),
+                memory_limit_mb: None,
+            },
+            ..InstanceConfig::default()
+        };
+        let mut command = Command::new("sh");
+        command.args(["-c", "nice; grep Cpus_allowed_list /proc/self/status"]);
+        apply_limits(&mut command, &config).unwrap();
+
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "19\nCpus_allowed_list:\t0\n"
+        );
+    }
+}
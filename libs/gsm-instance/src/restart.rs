@@ -0,0 +1,280 @@
+//! # Announced Restarts
+//!
+//! Restarting a server drops everyone on it. [`Instance::restart_with_warning`] warns
+//! players first: it broadcasts a countdown, by default 10, 5 and 1 minute before the
+//! restart, waits it out and then stops and starts the server.
+//!
+//! How a message reaches players depends on the game, so the broadcast is a closure
+//! supplied by the caller, e.g. one sending the message over the game's admin console or
+//! a webhook. [`shell_broadcast`] runs the command in `RESTART_BROADCAST_COMMAND` with the
+//! message in `GSM_BROADCAST_MESSAGE`, for games with a command-line admin tool.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::restart::RestartPlan;
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! instance
+//!     .restart_with_warning(&RestartPlan::default(), |message| {
+//!         println!("Broadcast: {message}");
+//!         Ok(())
+//!     })
+//!     .expect("Restart failed");
+//! ```
+use crate::config::InstanceConfig;
+use crate::errors::InstanceError;
+use crate::instance::Instance;
+use gsm_shared::fetch_var;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Environment variable holding a shell command that broadcasts `GSM_BROADCAST_MESSAGE`
+/// to players; see [`shell_broadcast`].
+pub const RESTART_BROADCAST_COMMAND: &str = "RESTART_BROADCAST_COMMAND";
+
+/// When and how players are warned of a restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartPlan {
+    /// How long before the restart each warning is sent.
+    pub warnings: Vec<Duration>,
+    /// The warning; `{time}` is replaced with the time left, e.g. `5 minutes`.
+    pub message: String,
+}
+
+impl Default for RestartPlan {
+    fn default() -> Self {
+        Self {
+            warnings: [10, 5, 1].map(Duration::from_mins).to_vec(),
+            message: "The server will restart in {time}.".to_owned(),
+        }
+    }
+}
+
+impl RestartPlan {
+    /// Reads the plan from `{prefix}_WARNINGS`, a comma-separated list of minutes before
+    /// the restart (empty for no warnings), and `{prefix}_MESSAGE`. Unset variables keep
+    /// the defaults; invalid minutes are skipped.
+    pub fn from_env(prefix: &str) -> Self {
+        let default = Self::default();
+        let warnings = std::env::var(format!("{prefix}_WARNINGS")).map_or_else(
+            |_| default.warnings.clone(),
+            |minutes| {
+                minutes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|minutes| !minutes.is_empty())
+                    .filter_map(|minutes| {
+                        minutes.parse().map_or_else(
+                            |_| {
+                                warn!("Ignoring invalid {prefix}_WARNINGS entry '{minutes}'");
+                                None
+                            },
+                            |minutes| Some(Duration::from_mins(minutes)),
+                        )
+                    })
+                    .collect()
+            },
+        );
+        Self {
+            warnings,
+            message: fetch_var(&format!("{prefix}_MESSAGE"), &default.message),
+        }
+    }
+
+    /// Returns each warning message with how long to wait after sending it: until the
+    /// next warning, or until the restart after the last one.
+    fn countdown(&self) -> Vec<(String, Duration)> {
+        let mut warnings = self.warnings.clone();
+        warnings.sort_unstable_by(|a, b| b.cmp(a));
+        warnings.dedup();
+        let waits = warnings
+            .iter()
+            .skip(1)
+            .copied()
+            .chain([Duration::ZERO])
+            .zip(&warnings)
+            .map(|(next, &left)| left.saturating_sub(next));
+        warnings
+            .iter()
+            .map(|&left| self.message.replace("{time}", &time_left(left)))
+            .zip(waits)
+            .collect()
+    }
+}
+
+impl Instance {
+    /// Warns players of the restart by calling `broadcast` with each message of `plan`'s
+    /// countdown, then restarts the server. A failed broadcast is logged and the
+    /// countdown carries on. When the server is not running, it is started right away.
+    /// A dry run logs the countdown without broadcasting or waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when stopping or starting the server fails.
+    pub fn restart_with_warning(
+        &self,
+        plan: &RestartPlan,
+        broadcast: impl Fn(&str) -> Result<(), String>,
+    ) -> Result<(), InstanceError> {
+        if self.pid().is_ok() {
+            for (message, wait) in plan.countdown() {
+                if self.config.dry_run {
+                    info!("Dry run: would announce '{message}' and wait {wait:?}");
+                    continue;
+                }
+                info!("Announcing restart: {message}");
+                if let Err(e) = broadcast(&message) {
+                    warn!("Failed to broadcast the restart warning: {e}");
+                }
+                thread::sleep(wait);
+            }
+        }
+        self.restart()
+    }
+}
+
+/// Broadcasts `message` by running the `RESTART_BROADCAST_COMMAND` shell command.
+///
+/// The command runs in the working directory, with the instance's `env` and the message
+/// in `GSM_BROADCAST_MESSAGE`. Does nothing when the variable is unset.
+///
+/// # Errors
+///
+/// Returns a description of the failure when the command cannot be run or exits
+/// unsuccessfully.
+pub fn shell_broadcast(config: &InstanceConfig, message: &str) -> Result<(), String> {
+    let command = fetch_var(RESTART_BROADCAST_COMMAND, "");
+    if command.is_empty() {
+        return Ok(());
+    }
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .current_dir(&config.working_dir)
+        .envs(&config.env)
+        .env("GSM_BROADCAST_MESSAGE", message)
+        .output()
+        .map_err(|e| format!("failed to run '{command}': {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(format!(
+        "'{command}' exited with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+/// Formats `duration` for a countdown message, e.g. `5 minutes` or `30 seconds`.
+fn time_left(duration: Duration) -> String {
+    let (count, unit) = if duration.as_secs() >= 60 && duration.as_secs().is_multiple_of(60) {
+        (duration.as_secs() / 60, "minute")
+    } else {
+        (duration.as_secs(), "second")
+    };
+    format!("{count} {unit}{}", if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_support::env_lock;
+
+    #[test]
+    fn countdown_waits_between_warnings_in_order() {
+        let plan = RestartPlan {
+            warnings: [1, 10, 5, 5].map(Duration::from_mins).to_vec(),
+            message: "Restart in {time}!".to_owned(),
+        };
+        assert_eq!(
+            plan.countdown(),
+            vec![
+                ("Restart in 10 minutes!".to_owned(), Duration::from_mins(5)),
+                ("Restart in 5 minutes!".to_owned(), Duration::from_mins(4)),
+                ("Restart in 1 minute!".to_owned(), Duration::from_mins(1)),
+            ]
+        );
+        assert_eq!(time_left(Duration::from_secs(90)), "90 seconds");
+        assert!(
+            RestartPlan {
+                warnings: Vec::new(),
+                ..plan
+            }
+            .countdown()
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn shell_broadcast_passes_the_message() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        };
+        assert_eq!(shell_broadcast(&config, "ignored"), Ok(()));
+
+        unsafe {
+            std::env::set_var(
+                RESTART_BROADCAST_COMMAND,
+                "printf '%s' \"$GSM_BROADCAST_MESSAGE\" > said.txt",
+            );
+        }
+        shell_broadcast(&config, "Restart in 1 minute").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("said.txt")).unwrap(),
+            "Restart in 1 minute"
+        );
+
+        unsafe {
+            std::env::set_var(RESTART_BROADCAST_COMMAND, "echo nope >&2; exit 3");
+        }
+        assert!(shell_broadcast(&config, "x").unwrap_err().contains("nope"));
+
+        unsafe {
+            std::env::remove_var(RESTART_BROADCAST_COMMAND);
+        }
+    }
+
+    #[test]
+    fn from_env_reads_warnings_and_message() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        assert_eq!(
+            RestartPlan::from_env("TEST_RESTART"),
+            RestartPlan::default()
+        );
+
+        unsafe {
+            std::env::set_var("TEST_RESTART_WARNINGS", "15, soon, 2");
+            std::env::set_var("TEST_RESTART_MESSAGE", "Bye in {time}");
+        }
+        assert_eq!(
+            RestartPlan::from_env("TEST_RESTART"),
+            RestartPlan {
+                warnings: [15, 2].map(Duration::from_mins).to_vec(),
+                message: "Bye in {time}".to_owned(),
+            }
+        );
+
+        unsafe {
+            std::env::set_var("TEST_RESTART_WARNINGS", "");
+        }
+        assert!(RestartPlan::from_env("TEST_RESTART").warnings.is_empty());
+
+        unsafe {
+            std::env::remove_var("TEST_RESTART_WARNINGS");
+            std::env::remove_var("TEST_RESTART_MESSAGE");
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! # Update Rollback
+//!
+//! An update can leave a server down: SteamCMD fails halfway, or the new build will not
+//! start. This module records the installed build before an update as a
+//! [`RollbackPoint`], and [`rollback`] returns to it afterwards.
+//!
+//! Rolling back reinstalls from the game's rollback branch (`ROLLBACK_BRANCH`, default
+//! `previous`, the branch Steam games commonly publish their last build under). If that
+//! fails too, e.g. because Steam cannot be reached, the SteamCMD app manifests saved
+//! with the rollback point are restored instead. A failed download is only committed by
+//! SteamCMD once it completes, so the files on disk still belong to the previous build
+//! and the restored manifests describe them again. Snapshots are taken unless
+//! `ROLLBACK_SNAPSHOT` is set to a false value.
+//!
+//! [`Instance::apply_update_async`](crate::Instance::apply_update_async) rolls back and
+//! restarts the server whenever updating or starting the new build fails.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! if let Err(e) = instance.update() {
+//!     eprintln!("Update failed ({e}), rolling back");
+//!     let outcome = instance.rollback().expect("Rollback failed");
+//!     println!("{outcome}");
+//! }
+//! ```
+use crate::config::{BetaConfig, InstanceConfig};
+use crate::dry_run;
+use crate::env_config::EnvConfig;
+use crate::errors::InstanceError;
+use crate::install::{install, install_args};
+use crate::update::installed_build_id;
+use gsm_shared::fetch_var;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Environment variable naming the Steam branch that holds a game's previous build.
+pub const ROLLBACK_BRANCH: &str = "ROLLBACK_BRANCH";
+
+/// Environment variable that, when set to a false value, stops rollback points from
+/// snapshotting the SteamCMD app manifests.
+pub const ROLLBACK_SNAPSHOT: &str = "ROLLBACK_SNAPSHOT";
+
+/// The directory, relative to the working directory, rollback points are kept in.
+pub const ROLLBACK_DIR: &str = ".gsm-rollback";
+
+/// The build installed before an update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollbackPoint {
+    /// The build id that was installed.
+    pub build_id: String,
+    /// When the point was recorded, in seconds since the Unix epoch.
+    pub recorded_at: u64,
+    /// Whether the SteamCMD app manifests were snapshotted along with it.
+    pub snapshot: bool,
+}
+
+impl RollbackPoint {
+    /// Records the build installed for `config`, replacing the previous rollback point,
+    /// and snapshots the app manifests when `snapshot` is set. Returns `None`, recording
+    /// nothing, when no build is installed yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the rollback point or snapshot cannot be written.
+    pub fn record(config: &InstanceConfig, snapshot: bool) -> Result<Option<Self>, InstanceError> {
+        let Some(build_id) = installed_build_id(&crate::clone::app_manifest_path(
+            &config.working_dir,
+            config.app_id,
+        )) else {
+            return Ok(None);
+        };
+        let dir = config.working_dir.join(ROLLBACK_DIR);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        if snapshot {
+            for manifest in manifests(&steamapps(&config.working_dir))? {
+                if let Some(name) = manifest.file_name() {
+                    fs::copy(&manifest, dir.join(name))?;
+                }
+            }
+        }
+
+        let point = Self {
+            build_id,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            snapshot,
+        };
+        fs::write(
+            dir.join("rollback.json"),
+            serde_json::to_vec_pretty(&point).map_err(io::Error::from)?,
+        )?;
+        info!("Recorded build {} as the rollback point", point.build_id);
+        Ok(Some(point))
+    }
+
+    /// Reads the rollback point recorded under `working_dir`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the rollback point cannot be read or is invalid.
+    pub fn load(working_dir: &Path) -> Result<Option<Self>, InstanceError> {
+        match fs::read(working_dir.join(ROLLBACK_DIR).join("rollback.json")) {
+            Ok(data) => Ok(Some(
+                serde_json::from_slice(&data).map_err(io::Error::from)?,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Copies the snapshotted app manifests back into `steamapps`, returning how many
+    /// were restored.
+    fn restore_snapshot(working_dir: &Path) -> Result<usize, InstanceError> {
+        let saved = manifests(&working_dir.join(ROLLBACK_DIR))?;
+        let steamapps = steamapps(working_dir);
+        fs::create_dir_all(&steamapps)?;
+        for manifest in &saved {
+            if let Some(name) = manifest.file_name() {
+                fs::copy(manifest, steamapps.join(name))?;
+            }
+        }
+        Ok(saved.len())
+    }
+}
+
+/// How [`rollback`] returned to the previous build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollbackOutcome {
+    /// The server was reinstalled from the rollback branch, and is now on `build_id`
+    /// when known.
+    Reinstalled {
+        /// The build installed from the rollback branch.
+        build_id: Option<String>,
+    },
+    /// Reinstalling failed, so the app manifests of the previous build were restored.
+    RestoredSnapshot {
+        /// The build the restored manifests describe.
+        build_id: String,
+    },
+}
+
+impl fmt::Display for RollbackOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reinstalled { build_id } => write!(
+                f,
+                "reinstalled build {} from the rollback branch",
+                build_id.as_deref().unwrap_or("unknown")
+            ),
+            Self::RestoredSnapshot { build_id } => {
+                write!(f, "restored the snapshot of build {build_id}")
+            }
+        }
+    }
+}
+
+/// Returns whether rollback points should snapshot the app manifests, per
+/// `ROLLBACK_SNAPSHOT`.
+pub fn snapshot_enabled() -> bool {
+    !matches!(
+        fetch_var(ROLLBACK_SNAPSHOT, "1")
+            .trim()
+            .to_ascii_lowercase()
+            .as_str(),
+        "0" | "false" | "no" | "off"
+    )
+}
+
+/// Returns `config`'s server to the build recorded before its last update.
+///
+/// The build is reinstalled from the rollback branch (see [`ROLLBACK_BRANCH`]), falling
+/// back to the manifest snapshot. `env_config` supplies the additional SteamCMD
+/// arguments; its beta branch is replaced by the rollback branch.
+///
+/// A dry run only logs the SteamCMD command line, and reports the recorded build as
+/// reinstalled.
+///
+/// # Errors
+///
+/// Returns an error when no rollback point was recorded, or reinstalling fails and
+/// there is no snapshot to restore.
+pub fn rollback(
+    config: &InstanceConfig,
+    env_config: &EnvConfig,
+) -> Result<RollbackOutcome, InstanceError> {
+    let point = RollbackPoint::load(&config.working_dir)?.ok_or_else(|| {
+        InstanceError::ConfigError("No build was recorded before the last update".to_owned())
+    })?;
+    let branch = fetch_var(ROLLBACK_BRANCH, "previous");
+    info!(
+        "Rolling back app {} to build {} from branch '{branch}'",
+        config.app_id, point.build_id
+    );
+
+    let options = EnvConfig {
+        beta: Some(BetaConfig::new(branch)),
+        ..env_config.clone()
+    };
+    if config.dry_run {
+        let args = install_args(
+            config.app_id,
+            &config.working_dir,
+            config.launch_mode.is_windows(),
+            false,
+            &config.install_args,
+            &options,
+        );
+        info!(
+            "Dry run: would run {}",
+            dry_run::steamcmd_line(&args, &options.downloads)
+        );
+        return Ok(RollbackOutcome::Reinstalled {
+            build_id: Some(point.build_id),
+        });
+    }
+    let reinstalled = install(
+        config.app_id,
+        &config.working_dir,
+        config.launch_mode.is_windows(),
+        false,
+        &config.install_args,
+        &options,
+    );
+    match reinstalled {
+        Ok(()) => {
+            let build_id = installed_build_id(&crate::clone::app_manifest_path(
+                &config.working_dir,
+                config.app_id,
+            ));
+            if build_id.as_deref() != Some(point.build_id.as_str()) {
+                warn!(
+                    "The rollback branch holds build {}, not the recorded build {}",
+                    build_id.as_deref().unwrap_or("unknown"),
+                    point.build_id
+                );
+            }
+            Ok(RollbackOutcome::Reinstalled { build_id })
+        }
+        Err(e) if point.snapshot => {
+            warn!("Reinstalling from the rollback branch failed ({e}); restoring the snapshot");
+            let restored = RollbackPoint::restore_snapshot(&config.working_dir)?;
+            info!(
+                "Restored {restored} app manifest(s) of build {}",
+                point.build_id
+            );
+            Ok(RollbackOutcome::RestoredSnapshot {
+                build_id: point.build_id,
+            })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn steamapps(working_dir: &Path) -> PathBuf {
+    working_dir.join("steamapps")
+}
+
+/// Returns the SteamCMD app manifests (`*.acf`) directly in `dir`.
+fn manifests(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut manifests = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "acf") {
+            manifests.push(path);
+        }
+    }
+    Ok(manifests)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::clone::app_manifest_path;
+    use crate::test_support::env_lock;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn write_manifest(working_dir: &Path, build_id: &str) {
+        let manifest = app_manifest_path(working_dir, 1);
+        fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        fs::write(
+            manifest,
+            format!("\"AppState\" {{ \"buildid\" \"{build_id}\" }}"),
+        )
+        .unwrap();
+    }
+
+    /// Installs a fake SteamCMD running `body` and returns a config using it.
+    fn setup(dir: &Path, body: &str) -> InstanceConfig {
+        let script = dir.join("fake-steamcmd.sh");
+        fs::write(&script, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", &script);
+        }
+        let working_dir = dir.join("server");
+        write_manifest(&working_dir, "1000");
+        InstanceConfig {
+            app_id: 1,
+            working_dir,
+            ..InstanceConfig::default()
+        }
+    }
+
+    #[test]
+    fn rollback_reinstalls_from_the_rollback_branch() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let args = temp_dir.path().join("args.txt");
+        let config = setup(
+            temp_dir.path(),
+            &format!(
+                "printf '%s\\n' \"$@\" > '{}'\nprintf '\"AppState\" {{ \"buildid\" \"1000\" }}' > '{}'",
+                args.display(),
+                app_manifest_path(&temp_dir.path().join("server"), 1).display()
+            ),
+        );
+
+        assert_eq!(
+            RollbackPoint::record(&config, false)
+                .unwrap()
+                .unwrap()
+                .build_id,
+            "1000"
+        );
+        write_manifest(&config.working_dir, "2000");
+
+        assert_eq!(
+            rollback(&config, &EnvConfig::default()).unwrap(),
+            RollbackOutcome::Reinstalled {
+                build_id: Some("1000".to_owned())
+            }
+        );
+        assert!(
+            fs::read_to_string(args)
+                .unwrap()
+                .contains("+app_update 1 -beta previous validate\n")
+        );
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+
+    #[test]
+    fn rollback_restores_the_snapshot_when_reinstalling_fails() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let config = setup(temp_dir.path(), "echo 'Login Failure'\nexit 5");
+
+        assert!(matches!(
+            rollback(&config, &EnvConfig::default()),
+            Err(InstanceError::ConfigError(_))
+        ));
+
+        RollbackPoint::record(&config, true).unwrap();
+        write_manifest(&config.working_dir, "2000");
+        assert_eq!(
+            rollback(&config, &EnvConfig::default()).unwrap(),
+            RollbackOutcome::RestoredSnapshot {
+                build_id: "1000".to_owned()
+            }
+        );
+        assert_eq!(
+            installed_build_id(&app_manifest_path(&config.working_dir, 1)).as_deref(),
+            Some("1000")
+        );
+
+        // Without a snapshot, the failed reinstall is the error.
+        RollbackPoint::record(&config, false).unwrap();
+        assert!(matches!(
+            rollback(&config, &EnvConfig::default()),
+            Err(InstanceError::SteamCmdError(_))
+        ));
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! # Save Games
+//!
+//! A server install is mostly game files that SteamCMD can download again; only the
+//! worlds and saves in it are irreplaceable. A [`SaveLocator`] knows where a game keeps
+//! them, and [`Instance::saves`] lists what it finds: each file's size and last-modified
+//! time, and the save paths to pass to a backup as its include list, so a backup holds
+//! the world rather than the whole install.
+//!
+//! [`SaveGlobs`] locates saves by glob patterns relative to the working directory,
+//! which covers most games; implement [`SaveLocator`] for games whose saves need more
+//! logic to find, e.g. a world named in their settings file.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::saves::SaveGlobs;
+//! use gsm_instance::{Instance, InstanceConfig};
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let saves = instance.saves(&SaveGlobs::new(["Pal/Saved/SaveGames/*"]));
+//! println!(
+//!     "{} save file(s), {} bytes, back up {:?}",
+//!     saves.files.len(),
+//!     saves.total_size(),
+//!     saves.include_list()
+//! );
+//! ```
+use crate::instance::Instance;
+use glob::glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Finds a game's world and save files.
+pub trait SaveLocator {
+    /// Returns the save files and directories of the server installed in `working_dir`,
+    /// relative to it. Paths that do not exist are ignored.
+    fn locate(&self, working_dir: &Path) -> Vec<PathBuf>;
+}
+
+/// Locates saves with glob patterns relative to the working directory, e.g.
+/// `savegame` or `Pal/Saved/SaveGames/*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveGlobs {
+    patterns: Vec<String>,
+}
+
+impl SaveGlobs {
+    /// Locates the paths matching any of `patterns`.
+    pub fn new<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            patterns: patterns.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl SaveLocator for SaveGlobs {
+    fn locate(&self, working_dir: &Path) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for pattern in &self.patterns {
+            let full = working_dir.join(pattern);
+            match glob(&full.to_string_lossy()) {
+                Ok(matches) => {
+                    paths.extend(matches.flatten().filter_map(|path| {
+                        path.strip_prefix(working_dir).ok().map(Path::to_path_buf)
+                    }));
+                }
+                Err(e) => warn!("Ignoring invalid save pattern '{pattern}': {e}"),
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// A save file found by a [`SaveLocator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveFile {
+    /// The file's path relative to the working directory.
+    pub path: PathBuf,
+    /// The file's size in bytes.
+    pub size: u64,
+    /// When the file was last written, if the filesystem records it.
+    pub modified: Option<SystemTime>,
+}
+
+/// The saves of an instance; see [`Instance::saves`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Saves {
+    /// The save files and directories located, relative to the working directory.
+    pub paths: Vec<PathBuf>,
+    /// Every file in or under `paths`.
+    pub files: Vec<SaveFile>,
+}
+
+impl Saves {
+    /// Returns the bytes the save files take up.
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    /// Returns when a save was last written, i.e. when the world last changed.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.files.iter().filter_map(|file| file.modified).max()
+    }
+
+    /// Returns the paths to include in a backup of the working directory, e.g. as
+    /// `gsm_backup::BackupOptions::include`.
+    pub fn include_list(&self) -> Vec<PathBuf> {
+        self.paths.clone()
+    }
+
+    /// Returns whether no saves were found, e.g. before the world was first created.
+    pub const fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl Instance {
+    /// Lists the saves `locator` finds in the working directory.
+    pub fn saves(&self, locator: &dyn SaveLocator) -> Saves {
+        let working_dir = &self.config.working_dir;
+        let paths: Vec<PathBuf> = locator
+            .locate(working_dir)
+            .into_iter()
+            .filter(|path| working_dir.join(path).exists())
+            .collect();
+        let mut files = Vec::new();
+        for path in &paths {
+            collect_files(working_dir, path, &mut files);
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files.dedup_by(|a, b| a.path == b.path);
+        Saves { paths, files }
+    }
+}
+
+/// Adds the file at `relative`, or every file under it, to `files`, without following
+/// symlinks.
+fn collect_files(working_dir: &Path, relative: &Path, files: &mut Vec<SaveFile>) {
+    let path = working_dir.join(relative);
+    let Ok(metadata) = fs::symlink_metadata(&path) else {
+        return;
+    };
+    if metadata.is_file() {
+        files.push(SaveFile {
+            path: relative.to_path_buf(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    } else if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(&path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            collect_files(working_dir, &relative.join(entry.file_name()), files);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use tempfile::tempdir;
+
+    #[test]
+    fn saves_are_listed_with_their_sizes() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("Saved/SaveGames/world-1/players")).unwrap();
+        fs::create_dir_all(root.join("Saved/SaveGames/world-2")).unwrap();
+        fs::write(root.join("Saved/SaveGames/world-1/Level.sav"), "level").unwrap();
+        fs::write(root.join("Saved/SaveGames/world-1/players/1.sav"), "p").unwrap();
+        fs::write(root.join("Saved/SaveGames/world-2/Level.sav"), "lvl").unwrap();
+        fs::write(root.join("Server.bin"), "not a save").unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: root.to_path_buf(),
+            ..InstanceConfig::default()
+        });
+
+        let saves = instance.saves(&SaveGlobs::new(["Saved/SaveGames/*", "missing", "["]));
+        assert_eq!(
+            saves.include_list(),
+            [
+                PathBuf::from("Saved/SaveGames/world-1"),
+                PathBuf::from("Saved/SaveGames/world-2")
+            ]
+        );
+        let files: Vec<_> = saves.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("Saved/SaveGames/world-1/Level.sav"),
+                PathBuf::from("Saved/SaveGames/world-1/players/1.sav"),
+                PathBuf::from("Saved/SaveGames/world-2/Level.sav"),
+            ]
+        );
+        assert_eq!(saves.total_size(), 9);
+        assert!(saves.last_modified().is_some());
+
+        assert!(instance.saves(&SaveGlobs::new(["savegame"])).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+//! # Shared Installs
+//!
+//! A test and a production server of the same game need their own ports, settings, saves
+//! and logs, but not their own 60 GB copy of the game. An instance with a
+//! [`SharedInstall`] takes its game files from a base install instead: its working
+//! directory mirrors the base with symlinks, and only its `private` paths, such as the
+//! save and settings directories, are its own. A private path is copied from the base
+//! the first time the instance is linked, or created by the server if the base has none;
+//! logs and `instance.json` always belong to the instance.
+//!
+//! [`Instance::install`](crate::Instance::install) links the working directory instead of
+//! running SteamCMD, and relinks it to pick up files an update added to the base.
+//! Instances sharing a base cannot be updated or rolled back themselves; update the base
+//! instance, with every instance sharing it stopped.
+//!
+//! Anything the server writes outside its private paths goes through a symlink into the
+//! base and is seen by every instance, so list each directory the server writes to.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::shared::SharedInstall;
+//! use gsm_instance::{Instance, InstanceConfig};
+//! use std::path::PathBuf;
+//!
+//! let instance = Instance::new(InstanceConfig {
+//!     app_id: 2_394_010,
+//!     working_dir: PathBuf::from("/home/steam/palworld-test"),
+//!     shared_install: Some(
+//!         SharedInstall::new("/home/steam/palworld").with_private(["Pal/Saved"]),
+//!     ),
+//!     ..InstanceConfig::default()
+//! });
+//! instance.install().expect("Failed to link the shared install");
+//! ```
+use crate::clone::{CloneMode, CloneStats, INSTANCE_LOCAL_PATHS, app_manifest_path, clone_dir};
+use crate::config::ConfigIssue;
+use crate::errors::InstanceError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info};
+
+/// Where an instance takes its game files from instead of installing them itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedInstall {
+    /// The install the game files are shared from, kept up to date by its own instance.
+    pub base: PathBuf,
+    /// Paths, relative to the working directory, the instance keeps its own copy of,
+    /// such as its save and settings directories.
+    #[serde(default)]
+    pub private: Vec<PathBuf>,
+}
+
+/// What [`SharedInstall::link`] changed in the working directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Symlinks created to files and directories of the base.
+    pub linked: usize,
+    /// Private paths copied from the base.
+    pub copied: usize,
+    /// Symlinks removed because their target left the base or became private.
+    pub removed: usize,
+}
+
+impl SharedInstall {
+    /// Shares the game files of the install at `base`, with no private paths.
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            private: Vec::new(),
+        }
+    }
+
+    /// Keeps the instance's own copy of each of `paths`, relative to the working
+    /// directory.
+    #[must_use]
+    pub fn with_private<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.private.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Collects the problems with this configuration.
+    pub(crate) fn issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        if self.base.as_os_str().is_empty() {
+            issues.push(ConfigIssue::new(
+                "shared_install.base",
+                "the shared install's base is empty",
+                "set it to the working directory of the instance that installs the game",
+            ));
+        }
+        for path in &self.private {
+            if !is_plain_relative(path) {
+                issues.push(ConfigIssue::new(
+                    "shared_install.private",
+                    format!(
+                        "'{}' is not a path inside the working directory",
+                        path.display()
+                    ),
+                    "use a relative path without '..', e.g. Pal/Saved",
+                ));
+            }
+        }
+        issues
+    }
+
+    /// Mirrors the base install of `app_id` into `working_dir` with symlinks, keeping the
+    /// private paths as the instance's own. Links only what is missing, so relinking
+    /// after the base was updated keeps the instance's files and picks up new ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the base is not an install of `app_id` or is `working_dir`
+    /// itself, or when a link or copy cannot be made.
+    pub fn link(&self, working_dir: &Path, app_id: u32) -> Result<LinkStats, InstanceError> {
+        if !app_manifest_path(&self.base, app_id).is_file() {
+            return Err(InstanceError::ConfigError(format!(
+                "{} is not an install of app {app_id} (no appmanifest_{app_id}.acf)",
+                self.base.display()
+            )));
+        }
+        let base = self.base.canonicalize()?;
+        fs::create_dir_all(working_dir)?;
+        if base == working_dir.canonicalize()? {
+            return Err(InstanceError::ConfigError(format!(
+                "{} cannot share its own install",
+                working_dir.display()
+            )));
+        }
+
+        info!(
+            "Linking {} to the shared install at {}",
+            working_dir.display(),
+            base.display()
+        );
+        let mut stats = LinkStats::default();
+        self.mirror(&base, working_dir, Path::new(""), &mut stats)?;
+        info!(
+            "Linked {} path(s), copied {} private path(s), removed {} stale link(s)",
+            stats.linked, stats.copied, stats.removed
+        );
+        Ok(stats)
+    }
+
+    /// Mirrors the directory `relative` of `base` into `working_dir`.
+    fn mirror(
+        &self,
+        base: &Path,
+        working_dir: &Path,
+        relative: &Path,
+        stats: &mut LinkStats,
+    ) -> io::Result<()> {
+        let dir = working_dir.join(relative);
+        fs::create_dir_all(&dir)?;
+        stats.removed += remove_stale_links(&dir, base)?;
+
+        for entry in fs::read_dir(base.join(relative))? {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            if INSTANCE_LOCAL_PATHS
+                .iter()
+                .any(|local| relative == Path::new(local))
+            {
+                continue;
+            }
+            let target = working_dir.join(&relative);
+            let existing = fs::symlink_metadata(&target).ok();
+            let is_link = existing.as_ref().is_some_and(fs::Metadata::is_symlink);
+
+            if self.private.contains(&relative) {
+                if is_link {
+                    fs::remove_file(&target)?;
+                    stats.removed += 1;
+                } else if existing.is_some() {
+                    continue;
+                }
+                debug!("Copying private path {}", relative.display());
+                if entry.file_type()?.is_dir() {
+                    clone_dir(
+                        base,
+                        working_dir,
+                        &relative,
+                        CloneMode::Copy,
+                        &mut CloneStats::default(),
+                    )?;
+                } else {
+                    fs::copy(entry.path(), &target)?;
+                }
+                stats.copied += 1;
+            } else if self
+                .private
+                .iter()
+                .any(|private| private.starts_with(&relative))
+                && entry.file_type()?.is_dir()
+            {
+                // A private path lies below, so this directory must be a real one.
+                if is_link {
+                    fs::remove_file(&target)?;
+                    stats.removed += 1;
+                }
+                self.mirror(base, working_dir, &relative, stats)?;
+            } else if existing.is_none() {
+                symlink(entry.path(), &target)?;
+                stats.linked += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the symlinks in `dir` that point into `base` at something no longer there,
+/// returning how many it removed.
+fn remove_stale_links(dir: &Path, base: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Ok(target) = fs::read_link(&path)
+            && target.starts_with(base)
+            && !target.exists()
+        {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns whether `path` is relative and stays inside the directory it is joined to.
+fn is_plain_relative(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    const APP_ID: u32 = 2_394_010;
+
+    fn base_install(root: &Path) -> PathBuf {
+        let base = root.join("base");
+        let manifest = app_manifest_path(&base, APP_ID);
+        fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        fs::write(&manifest, r#""AppState" { "buildid" "1000" }"#).unwrap();
+        fs::create_dir_all(base.join("Pal/Binaries")).unwrap();
+        fs::write(base.join("Pal/Binaries/server"), "binary").unwrap();
+        fs::create_dir_all(base.join("Pal/Saved/Config")).unwrap();
+        fs::write(base.join("Pal/Saved/Config/settings.ini"), "base").unwrap();
+        fs::create_dir_all(base.join("logs")).unwrap();
+        base
+    }
+
+    #[test]
+    fn instances_link_the_game_and_keep_their_private_paths() {
+        let temp_dir = tempdir().unwrap();
+        let base = base_install(temp_dir.path());
+        let instance = temp_dir.path().join("test");
+        let shared = SharedInstall::new(&base).with_private(["Pal/Saved"]);
+
+        let stats = shared.link(&instance, APP_ID).unwrap();
+        assert_eq!(
+            stats,
+            LinkStats {
+                linked: 2,
+                copied: 1,
+                removed: 0
+            }
+        );
+        assert!(
+            fs::symlink_metadata(instance.join("steamapps"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert!(
+            fs::symlink_metadata(instance.join("Pal/Binaries"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert!(
+            !fs::symlink_metadata(instance.join("Pal"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert!(!instance.join("logs").exists());
+
+        // The instance's settings are its own; the base keeps its copy.
+        fs::write(instance.join("Pal/Saved/Config/settings.ini"), "test").unwrap();
+        assert_eq!(
+            fs::read_to_string(base.join("Pal/Saved/Config/settings.ini")).unwrap(),
+            "base"
+        );
+
+        // An update adds a file and removes a directory; relinking follows the base.
+        fs::write(base.join("README.txt"), "readme").unwrap();
+        fs::remove_dir_all(base.join("Pal/Binaries")).unwrap();
+        let stats = shared.link(&instance, APP_ID).unwrap();
+        assert_eq!(
+            stats,
+            LinkStats {
+                linked: 1,
+                copied: 0,
+                removed: 1
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(instance.join("Pal/Saved/Config/settings.ini")).unwrap(),
+            "test"
+        );
+    }
+
+    #[test]
+    fn bases_must_be_other_installs_of_the_app() {
+        let temp_dir = tempdir().unwrap();
+        let base = base_install(temp_dir.path());
+        assert!(SharedInstall::new(&base).link(&base, APP_ID).is_err());
+        assert!(
+            SharedInstall::new(&base)
+                .link(&temp_dir.path().join("other"), 1)
+                .is_err()
+        );
+
+        let shared = SharedInstall::new("").with_private(["../escape", "/abs", "Pal/Saved"]);
+        let fields: Vec<&str> = shared.issues().iter().map(|issue| issue.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "shared_install.base",
+                "shared_install.private",
+                "shared_install.private"
+            ]
+        );
+    }
+}
@@ -1,83 +1,251 @@
 //! # Shutdown Module
 //!
 //! This module provides functionality to gracefully shut down a running game server instance.
-//! It sends an interrupt signal to all running server processes (identified by a specific substring in their
-//! executable path) and waits until they have terminated.
+//! It signals the server process the instance owns (see [`ServerProcess`]), along with its
+//! process group, and waits until it has terminated.
 //!
 //! ## Usage
 //!
 //! ```rust,no_run
+//! use gsm_instance::process::ServerProcess;
 //! use gsm_instance::shutdown::blocking_shutdown;
 //!
 //! // Gracefully shut down the server.
-//! // Replace "my_game_server.exe" with the actual executable name.
-//! blocking_shutdown("my_game_server.exe");
+//! // Replace 12345 with the server's pid, e.g. from `Instance::pid`.
+//! blocking_shutdown(&ServerProcess::new(12345)).expect("Failed to stop the server");
 //! ```
 
+use std::fmt;
+use std::time::Instant;
 use std::{thread, time::Duration};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::errors::InstanceError;
 use crate::process::ServerProcess;
+use gsm_shared::fetch_duration;
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
 
-/// Sends an interrupt signal to all running server processes and waits until they terminate.
-///
-/// This function is designed to perform a graceful shutdown of all processes associated
-/// with a game server instance. It identifies running processes by checking their executable
-/// path against a provided string.
+/// Environment variable with how long a server may take to exit after SIGINT and again
+/// after SIGTERM before it is signalled more forcefully, e.g. `30` (seconds) or `2m`.
+pub const STOP_GRACE_PERIOD: &str = "STOP_GRACE_PERIOD";
+
+/// The grace period used when `STOP_GRACE_PERIOD` is unset or invalid.
+pub const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(30);
+
+/// How long to wait for the process to disappear after SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a server process ended when it was stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// No process was running.
+    NotRunning,
+    /// The process exited after SIGINT, i.e. shut down gracefully.
+    Interrupted,
+    /// The process ignored SIGINT and exited after SIGTERM.
+    Terminated,
+    /// The process ignored SIGINT and SIGTERM and was killed.
+    Killed,
+    /// `dry_run` is set, so the process was left running.
+    DryRun,
+}
+
+impl fmt::Display for StopOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotRunning => "was not running",
+            Self::Interrupted => "exited after SIGINT",
+            Self::Terminated => "exited after SIGTERM",
+            Self::Killed => "was killed with SIGKILL",
+            Self::DryRun => "was left running (dry run)",
+        })
+    }
+}
+
+/// Returns the grace period named by `STOP_GRACE_PERIOD`, or [`DEFAULT_STOP_GRACE`].
+pub fn stop_grace_period() -> Duration {
+    fetch_duration(STOP_GRACE_PERIOD, DEFAULT_STOP_GRACE)
+}
+
+/// Stops `process` in stages: SIGINT, then SIGTERM once `grace` has passed, then SIGKILL
+/// once `grace` has passed again. Returns how the process ended.
 ///
-/// # Arguments
+/// When the server leads a process group, each signal goes to the whole group, and
+/// anything left in the group once the server has exited is killed.
 ///
-/// * `executable`: A string slice representing a unique part of the server executable's
-///   path or name. This is used to identify the target processes to shut down.
+/// # Errors
 ///
-/// # Behavior
+/// Returns [`InstanceError::ProcessError`] if the process cannot be signalled, or is
+/// still running after SIGKILL.
+pub fn stop_process(
+    process: &ServerProcess,
+    grace: Duration,
+) -> Result<StopOutcome, InstanceError> {
+    let pid = process.pid();
+    if !process.is_running() {
+        debug!("Process {pid} is not running");
+        return Ok(StopOutcome::NotRunning);
+    }
+    let stages = [
+        (Signal::SIGINT, grace, StopOutcome::Interrupted),
+        (Signal::SIGTERM, grace, StopOutcome::Terminated),
+        (Signal::SIGKILL, KILL_GRACE, StopOutcome::Killed),
+    ];
+    for (signal, wait, outcome) in stages {
+        info!("Sending {signal} to process {pid}");
+        match process.signal(signal) {
+            Ok(()) => {}
+            Err(Errno::ESRCH) => {
+                process.kill_leftovers();
+                return Ok(outcome);
+            }
+            Err(e) => {
+                return Err(InstanceError::ProcessError(format!(
+                    "failed to send {signal} to process {pid}: {e}"
+                )));
+            }
+        }
+        if wait_for_exit(process, wait) {
+            info!("Process {pid} {outcome}");
+            process.kill_leftovers();
+            return Ok(outcome);
+        }
+        warn!("Process {pid} still running {wait:?} after {signal}");
+    }
+    Err(InstanceError::ProcessError(format!(
+        "process {pid} is still running after SIGKILL"
+    )))
+}
+
+fn wait_for_exit(process: &ServerProcess, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if !process.is_running() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Sends an interrupt signal to the server and waits until it terminates.
 ///
-/// 1. A `ServerProcess` instance is created to manage process operations.
-/// 2. An interrupt signal (e.g., SIGINT on Unix-like systems) is sent to all processes
-///    whose executable path or name contains the `executable` string.
-/// 3. The function then waits for a short period (5 seconds) to allow processes to begin
-///    their shutdown sequence.
-/// 4. It enters a loop, periodically checking (every 5 seconds) if any matching server
-///    processes are still running.
-/// 5. The loop continues until no matching processes are found, at which point the function
-///    concludes that the server has been successfully stopped.
+/// Unlike [`stop_process`], this never escalates: it waits for as long as the server
+/// takes to shut down, checking every 5 seconds.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function does not explicitly panic, but underlying `ServerProcess` operations
-/// might in extreme cases of system resource exhaustion.
-pub fn blocking_shutdown(executable: &str) {
-    blocking_shutdown_with_delay(executable, Duration::from_secs(5));
+/// Returns [`InstanceError::ProcessError`] when the interrupt cannot be sent.
+pub fn blocking_shutdown(process: &ServerProcess) -> Result<(), InstanceError> {
+    blocking_shutdown_with_delay(process, Duration::from_secs(5))
 }
 
-fn blocking_shutdown_with_delay(executable: &str, delay: Duration) {
-    let mut server_process = ServerProcess::new();
-    info!("Sending interrupt signal to server processes...");
-    server_process.send_interrupt(executable);
-    thread::sleep(delay);
-    loop {
-        let mut sp = server_process.clone();
-        debug!("Checking if server processes are still running...");
-        if sp.are_processes_running(executable) {
-            debug!("Server processes still running. Waiting...");
-            thread::sleep(delay);
-        } else {
-            info!("Server processes have been stopped successfully!");
-            break;
-        }
+fn blocking_shutdown_with_delay(
+    process: &ServerProcess,
+    delay: Duration,
+) -> Result<(), InstanceError> {
+    if !process.is_running() {
+        info!("Server process {} is not running", process.pid());
+        return Ok(());
+    }
+    info!("Sending interrupt signal to server process...");
+    process.send_interrupt()?;
+    while !wait_for_exit(process, delay) {
+        debug!("Server process still running. Waiting...");
     }
+    process.kill_leftovers();
+    info!("Server process has been stopped successfully!");
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
+    use std::fs;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    // `stop_process` reaps the shell once it exits.
+    #[allow(clippy::zombie_processes)]
+    fn spawn_shell(script: &str) -> ServerProcess {
+        let child = Command::new("sh").args(["-c", script]).spawn().unwrap();
+        // Give the shell time to install its traps.
+        thread::sleep(Duration::from_millis(200));
+        ServerProcess::new(child.id())
+    }
+
+    /// Returns whether `pid` has exited, counting unreaped zombies as exited.
+    fn exited(pid: &str) -> bool {
+        fs::read_to_string(format!("/proc/{pid}/stat")).map_or(true, |stat| stat.contains(") Z"))
+    }
 
     #[test]
-    fn blocking_shutdown_exits_when_no_processes_match() {
-        // Use a name that will never match a real process; the loop exits immediately.
-        blocking_shutdown_with_delay(
-            "gsm-test-nonexistent-process-xyz123abc",
-            Duration::from_millis(10),
+    fn stop_process_escalates_until_the_process_exits() {
+        let grace = Duration::from_millis(300);
+        assert_eq!(
+            stop_process(&spawn_shell("exec sleep 5"), grace).unwrap(),
+            StopOutcome::Interrupted
+        );
+        assert_eq!(
+            stop_process(&spawn_shell("trap '' INT; sleep 5 & wait"), grace).unwrap(),
+            StopOutcome::Terminated
         );
+        assert_eq!(
+            stop_process(&spawn_shell("trap '' INT TERM; sleep 5 & wait"), grace).unwrap(),
+            StopOutcome::Killed
+        );
+    }
+
+    #[test]
+    fn stop_process_signals_the_whole_process_group() {
+        let temp_dir = tempdir().unwrap();
+        let helper_pid = temp_dir.path().join("helper.pid");
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & echo $! > \"$1\"; wait", "sh"])
+            .arg(&helper_pid)
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let process = ServerProcess::new(child.id());
+        assert_eq!(process.process_group(), Some(child.id()));
+
+        // The backgrounded helper ignores SIGINT, but does not outlive the server.
+        assert_eq!(
+            stop_process(&process, Duration::from_millis(300)).unwrap(),
+            StopOutcome::Interrupted
+        );
+        // The exit status is left for the owner of the child to collect.
+        assert!(child.try_wait().unwrap().is_some());
+        let helper = fs::read_to_string(&helper_pid).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert!(exited(helper.trim()));
+    }
+
+    #[test]
+    fn stop_process_reports_processes_that_are_gone() {
+        assert_eq!(
+            stop_process(&ServerProcess::new(999_999), Duration::from_millis(10)).unwrap(),
+            StopOutcome::NotRunning
+        );
+    }
+
+    #[test]
+    fn blocking_shutdown_waits_for_the_server_to_exit() {
+        let process = spawn_shell("exec sleep 5");
+        blocking_shutdown_with_delay(&process, Duration::from_millis(10)).unwrap();
+        assert!(!process.is_running());
+
+        // A server that is not running is already shut down.
+        blocking_shutdown_with_delay(&ServerProcess::new(999_999), Duration::from_millis(10))
+            .unwrap();
     }
 }
@@ -4,13 +4,23 @@
 //! particularly focusing on daemonizing the process and managing its lifecycle.
 //! It handles the creation of necessary directories and the redirection of
 //! standard output/error to log files.
+//!
+//! With [`InstanceConfig::daemonize`] unset, [`run_foreground`] instead keeps the server
+//! attached: its output streams to this process's stdout/stderr (so `docker logs` shows
+//! it) as well as to the log files, and the call returns when the server exits. Under
+//! systemd, it also reports the server's readiness and sends watchdog keepalives; see
+//! [`systemd`](crate::systemd).
 use crate::config::InstanceConfig;
 use crate::errors::InstanceError;
 use crate::launcher::launch_server;
-use std::fs;
-use std::fs::create_dir_all;
+use crate::state::InstanceState;
+use crate::systemd;
+use gsm_cron::ChildRegistry;
+use std::fs::{File, OpenOptions, create_dir_all};
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
 use std::path::Path;
-use std::process::Child;
+use std::process::{Child, ExitStatus, Stdio};
 use std::thread;
 use std::time::Duration;
 use tracing::info;
@@ -61,36 +71,33 @@ pub(crate) fn ensure_log_dir_test(working_dir: &Path) -> Result<(), InstanceErro
 ///
 /// - Creates a `logs` directory within the `working_dir` if it doesn't exist.
 /// - Constructs the launch command using `launcher::launch_server`.
-/// - Spawns the server process in the background.
-/// - Writes the process ID (PID) of the spawned server to an `instance.pid` file
-///   within the `working_dir`. This PID file is crucial for managing the server's
-///   lifecycle (e.g., stopping it).
+/// - Spawns the server process in the background, in a process group of its own, so
+///   stopping it also stops the processes it started and nothing else.
+/// - Records the spawned server's [`InstanceState`] (pid, start time, build and
+///   configuration hash) in `instance.json` within the `working_dir`. This state is
+///   crucial for managing the server's lifecycle (e.g., stopping it).
+/// - Registers the PID in the [`ChildRegistry`], so the cron loop forwards termination
+///   signals to the server when the manager is asked to stop.
 /// - Waits for a short duration (10 seconds) after spawning to detect if the server
-///   process immediately exits, indicating a startup failure. If it exits, the PID
+///   process immediately exits, indicating a startup failure. If it exits, the state
 ///   file is removed, and an error is returned.
 /// - Standard output and error of the child process are redirected to `server.log`
 ///   and `server.err` files in the `logs` directory.
 ///
 /// # Errors
 ///
-/// Returns an error when log setup, command launch, pid file writes, or immediate
+/// Returns an error when log setup, command launch, state file writes, or immediate
 /// startup validation fails.
 pub fn start_daemonized(config: &InstanceConfig) -> Result<Child, InstanceError> {
     info!("Starting server as a daemonized process...");
-    let working_dir = config.working_dir.clone();
-    ensure_log_dir(&working_dir)?;
+    ensure_log_dir(&config.working_dir)?;
 
     match launch_server(config) {
-        Ok(mut cmd) => match cmd.spawn() {
+        Ok(mut cmd) => match cmd.process_group(0).spawn() {
             Ok(mut child) => {
                 let pid = child.id();
-                let pid_file = working_dir.join("instance.pid");
-
-                if pid_file.exists() {
-                    fs::remove_file(&pid_file)?;
-                }
-
-                fs::write(pid_file, pid.to_string())?;
+                InstanceState::capture(pid, config).write(&config.state_file())?;
+                ChildRegistry::global().register(pid);
 
                 // Surface immediate startup failures so callers do not assume
                 // a zombie/failed process is a healthy server start.
@@ -101,7 +108,8 @@ pub fn start_daemonized(config: &InstanceConfig) -> Result<Child, InstanceError>
                     .try_wait()
                     .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?
                 {
-                    let _ = fs::remove_file(working_dir.join("instance.pid"));
+                    ChildRegistry::global().unregister(pid);
+                    let _ = InstanceState::remove(&config.state_file());
                     return Err(InstanceError::CommandExecutionError(format!(
                         "Server process exited immediately with status {status}"
                     )));
@@ -115,6 +123,88 @@ pub fn start_daemonized(config: &InstanceConfig) -> Result<Child, InstanceError>
     }
 }
 
+/// Starts the game server attached to this process and supervises it until it exits.
+///
+/// Unlike [`start_daemonized`], the server's stdout and stderr stream to this process's
+/// own, while still being appended to `server.log` and `server.err` for log monitoring.
+/// The state file and [`ChildRegistry`] entry exist while the server runs, so `stop` and
+/// the cron loop's signal forwarding work as usual, and both are removed once it exits.
+///
+/// When started by systemd, readiness and watchdog keepalives are reported to it while
+/// the server runs; see [`systemd`].
+///
+/// Termination signals sent to this process are not forwarded; see
+/// [`Instance::run_foreground_async`](crate::Instance::run_foreground_async).
+///
+/// # Errors
+///
+/// Returns an error when log setup, command launch or state file writes fail, or the
+/// server cannot be waited on.
+pub fn run_foreground(config: &InstanceConfig) -> Result<ExitStatus, InstanceError> {
+    info!("Starting server in the foreground...");
+    ensure_log_dir(&config.working_dir)?;
+    let mut cmd =
+        launch_server(config).map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let readiness = config
+        .readiness
+        .as_ref()
+        .map(|readiness| readiness.watch(&config.working_dir));
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
+    let pid = child.id();
+    InstanceState {
+        foreground: true,
+        ..InstanceState::capture(pid, config)
+    }
+    .write(&config.state_file())?;
+    ChildRegistry::global().register(pid);
+    let supervisor = systemd::supervise(readiness);
+
+    let append = |path| OpenOptions::new().append(true).create(true).open(path);
+    let (stdout_log, stderr_log) = (append(config.stdout())?, append(config.stderr())?);
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| thread::spawn(move || tee(out, io::stdout(), stdout_log)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| thread::spawn(move || tee(err, io::stderr(), stderr_log)));
+
+    let status = child.wait();
+    if let Some(supervisor) = supervisor {
+        supervisor.stop();
+    }
+    for copier in [stdout, stderr].into_iter().flatten() {
+        let _ = copier.join();
+    }
+    ChildRegistry::global().unregister(pid);
+    let _ = InstanceState::remove(&config.state_file());
+
+    let status = status.map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
+    info!("Server exited with {status}");
+    Ok(status)
+}
+
+/// Copies `source` to both `console` and `log` until it closes.
+fn tee(mut source: impl Read, mut console: impl Write, mut log: File) {
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let chunk = buffer.get(..read).unwrap_or_default();
+        // A closed console must not stop the server's output reaching the log.
+        let _ = console.write_all(chunk).and_then(|()| console.flush());
+        let _ = log.write_all(chunk);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
@@ -157,4 +247,25 @@ mod tests {
 
         assert!(start_daemonized(&config).is_err());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_foreground_waits_for_the_server_and_keeps_its_logs() {
+        let temp = tempdir().unwrap();
+        let config = InstanceConfig {
+            command: "/bin/sh".to_owned(),
+            launch_args: vec!["-c".to_owned(), "echo out; echo err >&2; exit 3".to_owned()],
+            working_dir: temp.path().to_path_buf(),
+            launch_mode: LaunchMode::Native,
+            daemonize: false,
+            ..InstanceConfig::default()
+        };
+
+        let status = run_foreground(&config).unwrap();
+
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(fs::read_to_string(config.stdout()).unwrap(), "out\n");
+        assert_eq!(fs::read_to_string(config.stderr()).unwrap(), "err\n");
+        assert!(!config.state_file().exists());
+    }
 }
@@ -0,0 +1,287 @@
+//! # Instance State
+//!
+//! This module records what was started, so later commands can find the running server
+//! again. When the server is spawned, an [`InstanceState`] with its pid and process
+//! group, the process's start time, the installed build and a hash of the launch
+//! configuration is written to `instance.json` in the working directory. The file is
+//! removed when the server is stopped cleanly.
+//!
+//! A bare pid is not enough to identify the server: after a crash or a container
+//! restart, the operating system may hand the same pid to an unrelated process. Reading
+//! the state therefore checks that the process with that pid started when the recorded
+//! one did, and discards the state as stale otherwise.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::state::InstanceState;
+//! use std::path::Path;
+//!
+//! let state_file = Path::new("/home/steam/myserver/instance.json");
+//! match InstanceState::load(state_file).expect("Unreadable state file") {
+//!     Some(state) => println!("Server running as pid {}", state.pid),
+//!     None => println!("Server not running"),
+//! }
+//! ```
+use crate::clone::app_manifest_path;
+use crate::config::InstanceConfig;
+use crate::errors::InstanceError;
+use crate::process::ServerProcess;
+use crate::update::installed_build_id;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysinfo::{Pid, ProcessStatus, ProcessesToUpdate, System};
+use tracing::{debug, warn};
+
+/// How far apart, in seconds, the recorded and actual process start times may be. Start
+/// times are derived from clock ticks since boot, so they can be off by a second.
+const START_TIME_TOLERANCE: u64 = 2;
+
+/// What is known about a started server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceState {
+    /// The server's process id.
+    pub pid: u32,
+    /// The process group the server leads, if it was started in one of its own; see
+    /// [`ServerProcess`].
+    #[serde(default)]
+    pub process_group: Option<u32>,
+    /// When the server process started, in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// The build id installed when the server started, if known.
+    #[serde(default)]
+    pub build_id: Option<String>,
+    /// A hash of the configuration the server was launched with; see
+    /// [`InstanceState::config_hash`].
+    pub config_hash: String,
+    /// Whether the server runs attached to the process that started it, which then
+    /// relaunches it after maintenance; see [`crate::relaunch`].
+    #[serde(default)]
+    pub foreground: bool,
+}
+
+impl InstanceState {
+    /// Records the server just spawned as `pid` with `config`.
+    pub fn capture(pid: u32, config: &InstanceConfig) -> Self {
+        let started_at = process_start_time(pid).unwrap_or_else(now);
+        Self {
+            pid,
+            process_group: ServerProcess::new(pid).process_group(),
+            started_at,
+            build_id: installed_build_id(&app_manifest_path(&config.working_dir, config.app_id)),
+            config_hash: Self::config_hash(config),
+            foreground: false,
+        }
+    }
+
+    /// Returns the recorded server process, for signalling it.
+    pub const fn process(&self) -> ServerProcess {
+        ServerProcess::with_process_group(self.pid, self.process_group)
+    }
+
+    /// Returns a stable hash of the parts of `config` that affect the running server, so
+    /// callers can tell whether it was started with a different configuration.
+    pub fn config_hash(config: &InstanceConfig) -> String {
+        let mut env: Vec<_> = config.env.iter().collect();
+        env.sort();
+        let canonical = format!(
+            "{}\0{}\0{:?}\0{:?}\0{}\0{}\0{:?}\0{}\0{:?}",
+            config.app_id,
+            config.command,
+            config.launch_args,
+            config.launch_mode,
+            config.working_dir.display(),
+            config.launch_mode.is_windows(),
+            env,
+            config.clear_env,
+            config.proton,
+        );
+        // FNV-1a, which unlike std's hasher is stable across Rust releases.
+        let hash = canonical
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        format!("{hash:016x}")
+    }
+
+    /// Returns `true` when the recorded process is still running, i.e. a live process
+    /// has the recorded pid and started at the recorded time.
+    pub fn is_running(&self) -> bool {
+        process_start_time(self.pid)
+            .is_some_and(|started_at| started_at.abs_diff(self.started_at) <= START_TIME_TOLERANCE)
+    }
+
+    /// Writes the state to `path`, replacing it atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be written.
+    pub fn write(&self, path: &Path) -> Result<(), InstanceError> {
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(
+            &temp_path,
+            serde_json::to_vec_pretty(self).map_err(io::Error::from)?,
+        )?;
+        fs::rename(&temp_path, path)?;
+        debug!("Recorded instance state in {}", path.display());
+        Ok(())
+    }
+
+    /// Reads the state from `path` as written, without checking it. Returns `None` when
+    /// there is no state file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be read or is not a valid state file.
+    pub fn read(path: &Path) -> Result<Option<Self>, InstanceError> {
+        match fs::read(path) {
+            Ok(data) => Ok(Some(
+                serde_json::from_slice(&data).map_err(io::Error::from)?,
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads the state from `path`, returning it only while the recorded server is
+    /// running. Stale state, left behind by a server that crashed or whose pid now
+    /// belongs to another process, is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be read or is not a valid state file.
+    pub fn load(path: &Path) -> Result<Option<Self>, InstanceError> {
+        let Some(state) = Self::read(path)? else {
+            return Ok(None);
+        };
+        if state.is_running() {
+            return Ok(Some(state));
+        }
+        warn!(
+            "Discarding stale instance state for pid {}: the process is gone or was reused",
+            state.pid
+        );
+        Self::remove(path)?;
+        Ok(None)
+    }
+
+    /// Removes the state file at `path`, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file exists but cannot be removed.
+    pub fn remove(path: &Path) -> Result<(), InstanceError> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns when the live process `pid` started, in seconds since the Unix epoch.
+fn process_start_time(pid: u32) -> Option<u64> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    let process = system.process(pid)?;
+    (!matches!(
+        process.status(),
+        ProcessStatus::Zombie | ProcessStatus::Dead
+    ))
+    .then(|| process.start_time())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config(dir: &Path) -> InstanceConfig {
+        InstanceConfig {
+            app_id: 1,
+            command: "./server".to_owned(),
+            working_dir: dir.to_path_buf(),
+            ..InstanceConfig::default()
+        }
+    }
+
+    #[test]
+    fn state_round_trips_and_detects_the_running_process() {
+        let temp_dir = tempdir().unwrap();
+        let config = config(temp_dir.path());
+        let state = InstanceState::capture(std::process::id(), &config);
+        state.write(&config.state_file()).unwrap();
+
+        assert_eq!(
+            InstanceState::read(&config.state_file()).unwrap(),
+            Some(state.clone())
+        );
+        assert_eq!(
+            InstanceState::load(&config.state_file()).unwrap(),
+            Some(state)
+        );
+        assert!(!temp_dir.path().join("instance.json.tmp").exists());
+    }
+
+    #[test]
+    fn reused_or_dead_pids_are_discarded() {
+        let temp_dir = tempdir().unwrap();
+        let config = config(temp_dir.path());
+
+        // Our own pid, but recorded as started long ago: the pid was reused.
+        let reused = InstanceState {
+            started_at: 1,
+            ..InstanceState::capture(std::process::id(), &config)
+        };
+        reused.write(&config.state_file()).unwrap();
+        assert!(!reused.is_running());
+        assert_eq!(InstanceState::load(&config.state_file()).unwrap(), None);
+        assert!(!config.state_file().exists());
+
+        InstanceState::capture(999_999, &config)
+            .write(&config.state_file())
+            .unwrap();
+        assert_eq!(InstanceState::load(&config.state_file()).unwrap(), None);
+        assert_eq!(InstanceState::load(&config.state_file()).unwrap(), None);
+        InstanceState::remove(&config.state_file()).unwrap();
+    }
+
+    #[test]
+    fn config_hash_changes_with_the_launch_configuration() {
+        let temp_dir = tempdir().unwrap();
+        let hash = InstanceState::config_hash(&config(temp_dir.path()));
+
+        assert_eq!(hash, InstanceState::config_hash(&config(temp_dir.path())));
+        assert_ne!(
+            hash,
+            InstanceState::config_hash(&InstanceConfig {
+                launch_args: vec!["-port=2457".to_owned()],
+                ..config(temp_dir.path())
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_state_files_are_errors() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("instance.json");
+        fs::write(&path, "12345").unwrap();
+
+        assert!(matches!(
+            InstanceState::read(&path),
+            Err(InstanceError::IoError(_))
+        ));
+    }
+}
@@ -28,20 +28,335 @@
 //! let output = run_steamcmd(args).expect("Failed to run steamcmd");
 //! println!("SteamCMD output: {:?}", output);
 //! ```
+//!
+//! ## Failures and Retries
+//!
+//! SteamCMD often fails for reasons that go away on their own: dropped connections,
+//! busy content servers or a disk write that did not stick. [`run_with_retries`] reads
+//! SteamCMD's output, classifies a failure as a [`SteamCmdErrorKind`] and retries the
+//! transient ones according to `STEAMCMD_MAX_RETRIES`, `STEAMCMD_RETRY_DELAY` (seconds)
+//! and `STEAMCMD_RETRY_BACKOFF`.
+//!
+//! A failure carries the last [`OUTPUT_TAIL_LINES`] lines SteamCMD printed. Given a log
+//! directory, each run's full output is also written to `steamcmd/<unix time>.log` in
+//! it; the newest [`KEPT_RUN_LOGS`] are kept.
+//!
+//! ## Installing SteamCMD
+//!
+//! Bare containers and fresh hosts often lack SteamCMD. When it is neither on `PATH` nor
+//! set by `STEAMCMD_PATH`, [`run_with_retries`] installs it with [`ensure_installed`]
+//! into `STEAMCMD_DIR` (default `~/steamcmd`) first. Set `STEAMCMD_SHA256` to the
+//! checksum of Valve's archive to have the download verified.
+
+use crate::config::DownloadConfig;
+use flate2::read::GzDecoder;
+use gsm_cron::RetryPolicy;
+use gsm_shared::{http, paths, redact_args, redact_secrets, sha256_hex};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tar::Archive;
+use tracing::{debug, info, warn};
+use zip::ZipArchive;
+
+/// Retries of a transient SteamCMD failure unless overridden by `STEAMCMD_MAX_RETRIES`,
+/// `STEAMCMD_RETRY_DELAY` (seconds) and `STEAMCMD_RETRY_BACKOFF`.
+pub const STEAMCMD_RETRY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_secs(10)).with_backoff(2);
+
+/// Where [`ensure_installed`] downloads SteamCMD for this platform from.
+#[cfg(windows)]
+pub const STEAMCMD_DOWNLOAD_URL: &str =
+    "https://steamcdn-a.akamaihd.net/client/installer/steamcmd.zip";
+#[cfg(not(windows))]
+pub const STEAMCMD_DOWNLOAD_URL: &str =
+    "https://steamcdn-a.akamaihd.net/client/installer/steamcmd_linux.tar.gz";
 
-use std::process::Command;
-use tracing::debug;
+/// The SteamCMD entry point within its install directory.
+#[cfg(windows)]
+const STEAMCMD_EXECUTABLE: &str = "steamcmd.exe";
+#[cfg(not(windows))]
+const STEAMCMD_EXECUTABLE: &str = "steamcmd.sh";
+
+/// The SteamCMD installed by [`ensure_installed`], used when `STEAMCMD_PATH` is unset.
+static INSTALLED: OnceLock<PathBuf> = OnceLock::new();
+
+/// How many of the last output lines a [`SteamCmdError`] keeps.
+pub const OUTPUT_TAIL_LINES: usize = 20;
+
+/// How many per-run SteamCMD logs are kept in the log directory.
+pub const KEPT_RUN_LOGS: usize = 10;
+
+/// SteamCMD's exit code for a failed `app_update`, which is usually worth retrying.
+const UPDATE_FAILED_EXIT_CODE: i32 = 8;
+
+/// Output fragments, lowercased, identifying each kind of failure. Checked in order, so
+/// the more specific fragments come first.
+const CLASSIFIERS: &[(SteamCmdErrorKind, &[&str])] = &[
+    (
+        SteamCmdErrorKind::DiskFull,
+        &["not enough disk space", "0x202"],
+    ),
+    (
+        SteamCmdErrorKind::DiskWrite,
+        &["disk write failure", "failed to write", "0x602"],
+    ),
+    (
+        SteamCmdErrorKind::Auth,
+        &[
+            "invalid password",
+            "login failure",
+            "no subscription",
+            "account logon denied",
+            "rate limit exceeded",
+        ],
+    ),
+    (SteamCmdErrorKind::Timeout, &["timed out", "timeout"]),
+    (
+        SteamCmdErrorKind::Network,
+        &[
+            "no connection",
+            "failed to connect",
+            "connection refused",
+            "servers are too busy",
+            "download failed",
+            "0x402",
+        ],
+    ),
+];
+
+/// What kind of failure a SteamCMD run ended in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteamCmdErrorKind {
+    /// SteamCMD could not reach Steam or its content servers.
+    Network,
+    /// A connection or download timed out.
+    Timeout,
+    /// Writing the downloaded files failed.
+    DiskWrite,
+    /// The install directory's disk is full.
+    DiskFull,
+    /// Logging in failed, or the account does not own the app.
+    Auth,
+    /// SteamCMD could not be started.
+    Launch,
+    /// The failure did not match a known pattern.
+    Unknown,
+}
+
+impl SteamCmdErrorKind {
+    /// Classifies a failed run from its output.
+    pub fn classify(output: &str) -> Self {
+        classify_line(&output.to_lowercase()).unwrap_or(Self::Unknown)
+    }
+
+    /// Returns whether failures of this kind usually succeed when retried.
+    pub const fn is_transient(self) -> bool {
+        matches!(self, Self::Network | Self::Timeout | Self::DiskWrite)
+    }
+}
+
+impl fmt::Display for SteamCmdErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Network => "network",
+            Self::Timeout => "timeout",
+            Self::DiskWrite => "disk write",
+            Self::DiskFull => "disk full",
+            Self::Auth => "authentication",
+            Self::Launch => "launch",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// A failed SteamCMD run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SteamCmdError {
+    /// What kind of failure it was.
+    pub kind: SteamCmdErrorKind,
+    /// SteamCMD's exit code, if it exited rather than being killed or not starting.
+    pub exit_code: Option<i32>,
+    /// The output line that identified the failure, or the last line of output.
+    pub message: String,
+    /// How many times SteamCMD was run, including retries.
+    pub attempts: u32,
+    /// The last lines SteamCMD printed, up to [`OUTPUT_TAIL_LINES`].
+    pub output: Vec<String>,
+    /// The file the run's full output was written to, if any.
+    pub log_file: Option<PathBuf>,
+}
+
+impl SteamCmdError {
+    /// Returns whether retrying the run is likely to help.
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+            || (self.kind == SteamCmdErrorKind::Unknown
+                && self.exit_code == Some(UPDATE_FAILED_EXIT_CODE))
+    }
+}
+
+impl fmt::Display for SteamCmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failure after {} attempt(s)",
+            self.kind, self.attempts
+        )?;
+        if let Some(code) = self.exit_code {
+            write!(f, " (exit code {code})")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if !self.output.is_empty() {
+            write!(f, "\nLast output:")?;
+            for line in &self.output {
+                write!(f, "\n  {line}")?;
+            }
+        }
+        if let Some(log_file) = &self.log_file {
+            write!(f, "\nFull log: {}", log_file.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SteamCmdError {}
+
+fn classify_line(lowercase: &str) -> Option<SteamCmdErrorKind> {
+    CLASSIFIERS
+        .iter()
+        .find(|(_, fragments)| fragments.iter().any(|f| lowercase.contains(f)))
+        .map(|(kind, _)| *kind)
+}
 
 /// Returns a `Command` configured to execute SteamCMD.
 ///
 /// It checks the `STEAMCMD_PATH` environment variable to override the default location.
-/// If not set, it defaults to `"steamcmd"`.
+/// If not set, it uses the SteamCMD installed by [`ensure_installed`], or else
+/// `"steamcmd"`.
 pub fn steamcmd_command() -> Command {
-    let cmd = std::env::var("STEAMCMD_PATH").unwrap_or_else(|_| "steamcmd".to_owned());
-    debug!("Using steamcmd executable: {}", cmd);
+    let cmd = std::env::var_os("STEAMCMD_PATH")
+        .or_else(|| INSTALLED.get().map(|path| path.clone().into_os_string()))
+        .unwrap_or_else(|| "steamcmd".into());
+    debug!("Using steamcmd executable: {}", cmd.to_string_lossy());
     Command::new(cmd)
 }
 
+/// Makes sure SteamCMD is installed in `dir` and returns the path to run it by.
+///
+/// When it is missing, Valve's archive (a tarball on Linux, a zip on Windows) is
+/// downloaded and unpacked. Later [`steamcmd_command`]s use it. When `STEAMCMD_PATH` is
+/// set, that path is returned and nothing is installed.
+///
+/// The download is checked against `STEAMCMD_SHA256` when set; otherwise its checksum
+/// is logged so it can be pinned.
+///
+/// # Errors
+///
+/// Returns an error when the download fails, its checksum does not match, or it cannot
+/// be unpacked into `dir`.
+pub fn ensure_installed(dir: &Path) -> Result<PathBuf, io::Error> {
+    if let Some(path) = std::env::var_os("STEAMCMD_PATH") {
+        return Ok(PathBuf::from(path));
+    }
+    let executable = install(dir)?;
+    let _ = INSTALLED.set(executable.clone());
+    Ok(executable)
+}
+
+/// Downloads SteamCMD into `dir` unless it is already there, returning its executable.
+fn install(dir: &Path) -> Result<PathBuf, io::Error> {
+    let executable = dir.join(STEAMCMD_EXECUTABLE);
+    if executable.is_file() {
+        return Ok(executable);
+    }
+    info!(
+        "Downloading SteamCMD from {STEAMCMD_DOWNLOAD_URL} into {}",
+        dir.display()
+    );
+    let archive = http::get(STEAMCMD_DOWNLOAD_URL)
+        .map_err(io::Error::other)?
+        .response
+        .bytes()
+        .map_err(io::Error::other)?;
+    verify_checksum(
+        &archive,
+        &std::env::var("STEAMCMD_SHA256").unwrap_or_default(),
+    )?;
+    unpack(&archive, dir)?;
+    if !executable.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("the SteamCMD archive has no {STEAMCMD_EXECUTABLE}"),
+        ));
+    }
+    info!("Installed SteamCMD at {}", executable.display());
+    Ok(executable)
+}
+
+/// Checks that `data` has the SHA-256 checksum `expected`, in hex. An empty `expected`
+/// only logs the checksum.
+fn verify_checksum(data: &[u8], expected: &str) -> Result<(), io::Error> {
+    let actual = sha256_hex(data);
+    let expected = expected.trim().to_ascii_lowercase();
+    if expected.is_empty() {
+        warn!("SteamCMD download not verified: set STEAMCMD_SHA256={actual} to pin it");
+        return Ok(());
+    }
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("SteamCMD checksum mismatch: expected {expected}, got {actual}"),
+        ));
+    }
+    debug!("SteamCMD checksum verified: {actual}");
+    Ok(())
+}
+
+/// Unpacks the SteamCMD `archive` into `dir` through a staging directory, so a failed
+/// unpack leaves no partial install behind.
+fn unpack(archive: &[u8], dir: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(dir)?;
+    let staging = tempfile::tempdir_in(dir)?;
+    if archive.starts_with(b"PK") {
+        ZipArchive::new(Cursor::new(archive))
+            .and_then(|mut zip| zip.extract(staging.path()))
+            .map_err(io::Error::other)?;
+    } else {
+        Archive::new(GzDecoder::new(archive)).unpack(staging.path())?;
+    }
+    for entry in fs::read_dir(staging.path())? {
+        let entry = entry?;
+        fs::rename(entry.path(), dir.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Installs SteamCMD unless `STEAMCMD_PATH` is set or it is on `PATH`.
+fn ensure_available() -> Result<(), SteamCmdError> {
+    if std::env::var_os("STEAMCMD_PATH").is_some()
+        || INSTALLED.get().is_some()
+        || which::which("steamcmd").is_ok()
+    {
+        return Ok(());
+    }
+    ensure_installed(&paths::steamcmd_dir())
+        .map(drop)
+        .map_err(|e| SteamCmdError {
+            kind: SteamCmdErrorKind::Launch,
+            exit_code: None,
+            message: format!("SteamCMD is missing and could not be installed: {e}"),
+            attempts: 1,
+            output: Vec::new(),
+            log_file: None,
+        })
+}
+
 /// Runs SteamCMD with the provided arguments and returns its output.
 ///
 /// # Parameters
@@ -75,11 +390,359 @@ pub fn run_steamcmd(args: &[&str]) -> Result<std::process::Output, std::io::Erro
     Ok(output)
 }
 
+/// Runs `args` through SteamCMD once with the `downloads` limits applied, logging its
+/// output (and writing it to `log`, if given), and classifies a failure.
+fn run_once(
+    args: &[String],
+    downloads: &DownloadConfig,
+    log: Option<&Mutex<fs::File>>,
+) -> Result<(), SteamCmdError> {
+    let launch_error = |e: std::io::Error| SteamCmdError {
+        kind: SteamCmdErrorKind::Launch,
+        exit_code: None,
+        message: e.to_string(),
+        attempts: 1,
+        output: Vec::new(),
+        log_file: None,
+    };
+    let mut command = steamcmd_command();
+    downloads.apply_proxy(&mut command);
+    command
+        .args(downloads.steamcmd_args())
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!(
+        "Launching SteamCMD: {}",
+        redact_secrets(&format!("{command:?}"))
+    );
+    let mut child = command.spawn().map_err(launch_error)?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let lines = thread::scope(|scope| {
+        let stderr = stderr.map(|stderr| scope.spawn(move || log_lines(stderr, log)));
+        let mut lines = stdout
+            .map(|stdout| log_lines(stdout, log))
+            .unwrap_or_default();
+        if let Some(stderr) = stderr {
+            lines.extend(stderr.join().unwrap_or_default());
+        }
+        lines
+    });
+    let status = child.wait().map_err(launch_error)?;
+    if status.success() {
+        return Ok(());
+    }
+
+    let (kind, message) = lines
+        .iter()
+        .find_map(|line| classify_line(&line.to_lowercase()).map(|kind| (kind, line.clone())))
+        .unwrap_or_else(|| {
+            let last = lines.iter().rev().find(|line| !line.trim().is_empty());
+            (
+                SteamCmdErrorKind::Unknown,
+                last.cloned()
+                    .unwrap_or_else(|| format!("SteamCMD exited with {status}")),
+            )
+        });
+    Err(SteamCmdError {
+        kind,
+        exit_code: status.code(),
+        message: message.trim().to_owned(),
+        attempts: 1,
+        output: output_tail(&lines),
+        log_file: None,
+    })
+}
+
+/// Returns the last [`OUTPUT_TAIL_LINES`] non-blank lines of `lines`.
+pub(crate) fn output_tail<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+    let mut tail: Vec<String> = lines
+        .iter()
+        .rev()
+        .map(|line| line.as_ref().trim_end())
+        .filter(|line| !line.trim().is_empty())
+        .take(OUTPUT_TAIL_LINES)
+        .map(str::to_owned)
+        .collect();
+    tail.reverse();
+    tail
+}
+
+/// Logs each line read from `output`, writes it to `log` and returns them.
+fn log_lines(output: impl Read, log: Option<&Mutex<fs::File>>) -> Vec<String> {
+    BufReader::new(output)
+        .lines()
+        .map_while(Result::ok)
+        .inspect(|line| {
+            info!(target: "steamcmd", "{line}");
+            if let Some(log) = log {
+                let _ = writeln!(log.lock().unwrap_or_else(PoisonError::into_inner), "{line}");
+            }
+        })
+        .collect()
+}
+
+/// Creates the log file for a run in `log_dir`, removing all but the newest
+/// [`KEPT_RUN_LOGS`] older ones. A log that cannot be created is skipped with a warning.
+fn open_run_log(log_dir: &Path) -> Option<(PathBuf, fs::File)> {
+    let dir = log_dir.join("steamcmd");
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join(format!("{started}.log"));
+    let file = fs::create_dir_all(&dir)
+        .and_then(|()| fs::OpenOptions::new().create(true).append(true).open(&path));
+    match file {
+        Ok(file) => {
+            prune_run_logs(&dir);
+            Some((path, file))
+        }
+        Err(e) => {
+            warn!("Failed to create SteamCMD log {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Removes all but the newest [`KEPT_RUN_LOGS`] run logs in `dir`.
+fn prune_run_logs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<(u64, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let started = path.file_stem()?.to_str()?.parse().ok()?;
+            (path.extension()? == "log").then_some((started, path))
+        })
+        .collect();
+    logs.sort_unstable();
+    let excess = logs.len().saturating_sub(KEPT_RUN_LOGS);
+    for (_, path) in logs.drain(..excess) {
+        if let Err(e) = fs::remove_file(&path) {
+            debug!("Failed to remove old SteamCMD log {}: {e}", path.display());
+        }
+    }
+}
+
+/// Runs SteamCMD with `args` and the `downloads` rate limit and proxy, retrying
+/// transient failures according to `policy`.
+///
+/// SteamCMD is installed first when it is missing. When `log_dir` is given, the output
+/// of every attempt is written to a new run log in it.
+///
+/// # Errors
+///
+/// Returns a launch failure when SteamCMD is missing and cannot be installed, or the
+/// last failure when SteamCMD fails with a non-transient error or keeps failing after
+/// the retries are exhausted.
+pub fn run_with_retries(
+    args: &[String],
+    downloads: &DownloadConfig,
+    policy: RetryPolicy,
+    log_dir: Option<&Path>,
+) -> Result<(), SteamCmdError> {
+    ensure_available()?;
+    let (log_file, log) = log_dir
+        .and_then(open_run_log)
+        .map(|(path, file)| (Some(path), Some(Mutex::new(file))))
+        .unwrap_or_default();
+    let mut attempt = 1;
+    loop {
+        if let Some(log) = &log {
+            let _ = writeln!(
+                log.lock().unwrap_or_else(PoisonError::into_inner),
+                "== Attempt {attempt}: steamcmd {}",
+                redact_args(args).join(" ")
+            );
+        }
+        match run_once(args, downloads, log.as_ref()) {
+            Ok(()) => return Ok(()),
+            Err(mut error) => {
+                error.attempts = attempt;
+                error.log_file.clone_from(&log_file);
+                if !error.is_transient() || attempt > policy.max_retries {
+                    return Err(error);
+                }
+                let delay = policy.delay_before(attempt);
+                warn!("SteamCMD failed ({error}); retrying in {delay:?}");
+                thread::sleep(delay);
+                attempt += 1;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::steamcmd_command;
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
     use crate::test_support::env_lock;
     use std::ffi::OsStr;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn classify_recognizes_common_failures() {
+        let cases = [
+            (
+                "Error! App '2278520' state is 0x202 after update job.",
+                SteamCmdErrorKind::DiskFull,
+            ),
+            (
+                "Error! App '2278520' state is 0x402 after update job.",
+                SteamCmdErrorKind::Network,
+            ),
+            ("Disk write failure", SteamCmdErrorKind::DiskWrite),
+            ("FAILED (Invalid Password)", SteamCmdErrorKind::Auth),
+            ("Connection timed out", SteamCmdErrorKind::Timeout),
+            ("Success! App fully installed.", SteamCmdErrorKind::Unknown),
+        ];
+        for (output, kind) in cases {
+            assert_eq!(SteamCmdErrorKind::classify(output), kind, "{output}");
+        }
+    }
+
+    #[test]
+    fn exit_code_8_is_transient_when_unclassified() {
+        let error = SteamCmdError {
+            kind: SteamCmdErrorKind::Unknown,
+            exit_code: Some(8),
+            message: String::new(),
+            attempts: 1,
+            output: Vec::new(),
+            log_file: None,
+        };
+        assert!(error.is_transient());
+        assert!(
+            !SteamCmdError {
+                exit_code: Some(1),
+                ..error.clone()
+            }
+            .is_transient()
+        );
+        assert!(
+            !SteamCmdError {
+                kind: SteamCmdErrorKind::Auth,
+                ..error
+            }
+            .is_transient()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_with_retries_retries_transient_failures_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let counter = temp_dir.path().join("attempts");
+        let script = temp_dir.path().join("steamcmd.sh");
+        // Times out on the first run, then fails with the message passed as argument.
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho x >> '{0}'\n[ $(wc -l < '{0}') -eq 1 ] && echo 'Connection timed out' && exit 8\necho \"$1\"\n[ \"$1\" = ok ] || exit 5\n",
+                counter.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", &script);
+        }
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+
+        run_with_retries(&["ok".to_owned()], &DownloadConfig::default(), policy, None).unwrap();
+        assert_eq!(fs::read_to_string(&counter).unwrap().lines().count(), 2);
+
+        fs::remove_file(&counter).unwrap();
+        let error = run_with_retries(
+            &["FAILED (Invalid Password)".to_owned()],
+            &DownloadConfig::default(),
+            policy,
+            Some(&temp_dir.path().join("logs")),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, SteamCmdErrorKind::Auth);
+        assert_eq!(error.exit_code, Some(5));
+        assert_eq!(error.attempts, 2);
+        assert_eq!(error.message, "FAILED (Invalid Password)");
+        assert_eq!(error.output, ["FAILED (Invalid Password)"]);
+        let log = fs::read_to_string(error.log_file.as_ref().unwrap()).unwrap();
+        assert!(log.starts_with("== Attempt 1: steamcmd FAILED (Invalid Password)\n"));
+        assert!(log.contains("Connection timed out\n== Attempt 2"));
+        assert!(
+            error
+                .to_string()
+                .contains("Last output:\n  FAILED (Invalid Password)")
+        );
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+
+    #[test]
+    fn install_unpacks_verified_archives_once() {
+        let temp_dir = tempdir().unwrap();
+        let mut tarball = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::fast(),
+        ));
+        let script = b"#!/bin/sh\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        tarball
+            .append_data(&mut header, "steamcmd.sh", &script[..])
+            .unwrap();
+        let archive = tarball.into_inner().unwrap().finish().unwrap();
+
+        let error = verify_checksum(&archive, "00ff").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        verify_checksum(&archive, &sha256_hex(&archive).to_uppercase()).unwrap();
+
+        let dir = temp_dir.path().join("steamcmd");
+        unpack(&archive, &dir).unwrap();
+        assert_eq!(fs::read(dir.join("steamcmd.sh")).unwrap(), script);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert_eq!(install(&dir).unwrap(), dir.join("steamcmd.sh"));
+    }
+
+    #[test]
+    fn run_logs_are_pruned_and_output_is_tailed() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("steamcmd");
+        fs::create_dir_all(&dir).unwrap();
+        for started in 0..12 {
+            fs::write(dir.join(format!("{started}.log")), "").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        let (path, _) = open_run_log(temp_dir.path()).unwrap();
+
+        assert!(path.is_file());
+        assert!(!dir.join("2.log").exists());
+        assert!(dir.join("3.log").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        let lines: Vec<String> = (0..30)
+            .map(|line| format!("line {line}"))
+            .chain([" ".to_owned()])
+            .collect();
+        let tail = output_tail(&lines);
+        assert_eq!(tail.len(), OUTPUT_TAIL_LINES);
+        assert_eq!(tail.first().unwrap(), "line 10");
+        assert_eq!(tail.last().unwrap(), "line 29");
+    }
 
     #[test]
     fn steamcmd_command_defaults_to_steamcmd_binary() {
@@ -0,0 +1,387 @@
+//! # systemd Integration
+//!
+//! Outside a container, systemd is the natural supervisor for a game server. This module
+//! generates a [`SystemdUnit`] for an instance, which installs the server before each
+//! start and runs it in the foreground, and speaks systemd's notification protocol from
+//! within that foreground run:
+//!
+//! - `READY=1` once the server passes its [`Readiness`](crate::readiness::Readiness)
+//!   probe, or as soon as it is spawned when it has none, so units ordered after it
+//!   start only when players can connect.
+//! - `WATCHDOG=1` at half the unit's `WatchdogSec` while the server runs, so systemd
+//!   restarts a manager that hangs.
+//! - `STOPPING=1` when the server exits.
+//!
+//! Outside systemd (`NOTIFY_SOCKET` unset) the notifications do nothing. The game
+//! binaries print a unit with `systemd-unit`:
+//!
+//! ```text
+//! palworld systemd-unit --user steam | sudo tee /etc/systemd/system/gsm-palworld.service
+//! sudo systemctl enable --now gsm-palworld
+//! ```
+//!
+//! The unit runs `start --foreground` only; run `monitor` in a unit of its own for the
+//! scheduled jobs.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::InstanceConfig;
+//! use gsm_instance::systemd::SystemdUnit;
+//!
+//! let config = InstanceConfig {
+//!     name: "palworld".to_owned(),
+//!     ..InstanceConfig::default()
+//! };
+//! let unit = SystemdUnit::new(&config, "/usr/local/bin/palworld").with_user("steam");
+//! assert_eq!(unit.file_name(), "gsm-palworld.service");
+//! assert!(unit.to_string().contains("ExecStart=/usr/local/bin/palworld start --foreground"));
+//! ```
+use crate::config::InstanceConfig;
+use crate::readiness::ReadinessWatch;
+use crate::resources::ResourceLimits;
+use crate::shutdown::stop_grace_period;
+use std::env;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// The variable systemd passes the notification socket in.
+pub const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+
+/// The `WatchdogSec` of generated units.
+pub const DEFAULT_WATCHDOG: Duration = Duration::from_mins(1);
+
+/// The `TimeoutStartSec` of generated units, which covers the install before the start.
+pub const DEFAULT_START_TIMEOUT: Duration = Duration::from_mins(30);
+
+/// Sends `state`, such as `READY=1`, to systemd. Returns `false` without sending anything
+/// when this process was not started by systemd with a notification socket.
+///
+/// # Errors
+///
+/// Returns an error when the notification cannot be sent.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(socket) = env::var_os(NOTIFY_SOCKET) else {
+        return Ok(false);
+    };
+    let datagram = UnixDatagram::unbound()?;
+    if let Some(name) = socket.as_bytes().strip_prefix(b"@") {
+        send_abstract(&datagram, name, state)?;
+    } else {
+        datagram.send_to(state.as_bytes(), Path::new(&socket))?;
+    }
+    Ok(true)
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(datagram: &UnixDatagram, name: &[u8], state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    datagram.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_: &UnixDatagram, _: &[u8], _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract notification sockets need Linux",
+    ))
+}
+
+/// Sends `state` to systemd, logging rather than returning a failure.
+fn notify_or_warn(state: &str) {
+    match notify(state) {
+        Ok(true) => debug!("Notified systemd: {state}"),
+        Ok(false) => {}
+        Err(e) => warn!("Failed to notify systemd ({state}): {e}"),
+    }
+}
+
+/// Returns how often to send `WATCHDOG=1`: half the unit's `WatchdogSec`, from
+/// `WATCHDOG_USEC`. `None` when the unit has no watchdog or it is meant for another
+/// process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+    if let Ok(pid) = env::var("WATCHDOG_PID")
+        && pid.trim().parse::<u32>().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Reports a server running in the foreground to systemd; see [`supervise`].
+pub(crate) struct Supervisor {
+    done: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Supervisor {
+    /// Tells systemd the server exited and stops the keepalive.
+    pub(crate) fn stop(self) {
+        self.done.store(true, Ordering::SeqCst);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+        notify_or_warn("STOPPING=1");
+    }
+}
+
+/// Starts reporting a server that was just spawned in the foreground to systemd:
+/// `READY=1` once `readiness` passes, then `WATCHDOG=1` until [`Supervisor::stop`].
+/// `None` outside systemd.
+pub(crate) fn supervise(readiness: Option<ReadinessWatch>) -> Option<Supervisor> {
+    env::var_os(NOTIFY_SOCKET)?;
+    let done = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&done);
+    let thread = thread::spawn(move || {
+        let running = || !stopped.load(Ordering::SeqCst);
+        if let Some(readiness) = readiness
+            && let Err(e) = readiness.wait_while(running)
+        {
+            // Without READY=1, systemd fails the start once TimeoutStartSec passes.
+            notify_or_warn(&format!("STATUS={e}"));
+            return;
+        }
+        notify_or_warn("READY=1\nSTATUS=Server is running");
+        let Some(interval) = watchdog_interval() else {
+            return;
+        };
+        while running() {
+            notify_or_warn("WATCHDOG=1");
+            thread::park_timeout(interval);
+        }
+    });
+    Some(Supervisor { done, thread })
+}
+
+/// A systemd service unit for an instance; its `Display` output is the unit file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemdUnit {
+    name: String,
+    description: String,
+    binary: PathBuf,
+    working_dir: PathBuf,
+    resources: ResourceLimits,
+    user: Option<String>,
+    environment_file: Option<PathBuf>,
+    watchdog: Option<Duration>,
+    start_timeout: Duration,
+    stop_timeout: Duration,
+}
+
+impl SystemdUnit {
+    /// Creates a unit that runs `config`'s server with the game binary at `binary`.
+    /// `resources` become the unit's `CPUAffinity`, `Nice` and `MemoryMax`, which
+    /// systemd applies without the delegated cgroup the launcher would need.
+    pub fn new(config: &InstanceConfig, binary: impl Into<PathBuf>) -> Self {
+        let description = if config.name.trim().is_empty() {
+            format!("Game server for Steam app {}", config.app_id)
+        } else {
+            format!("{} game server", config.name.trim())
+        };
+        Self {
+            name: config.service_name(),
+            description,
+            binary: binary.into(),
+            working_dir: config.working_dir.clone(),
+            resources: config.resources.clone(),
+            user: None,
+            environment_file: None,
+            watchdog: Some(DEFAULT_WATCHDOG),
+            start_timeout: DEFAULT_START_TIMEOUT,
+            // Room for the server's own grace period, plus SIGKILL and the stop hooks.
+            stop_timeout: stop_grace_period() + Duration::from_secs(30),
+        }
+    }
+
+    /// Runs the server as `user` instead of root.
+    #[must_use]
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Reads the game's environment variables, such as `PUBLIC_IP`, from `path` if it
+    /// exists.
+    #[must_use]
+    pub fn with_environment_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.environment_file = Some(path.into());
+        self
+    }
+
+    /// Sets the watchdog timeout, or turns the watchdog off with `None`.
+    #[must_use]
+    pub const fn with_watchdog(mut self, watchdog: Option<Duration>) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Returns the unit's file name, e.g. `gsm-palworld.service`.
+    pub fn file_name(&self) -> String {
+        format!("{}.service", self.name)
+    }
+
+    /// Returns the command line running the game binary with `subcommand`.
+    fn command(&self, subcommand: &str) -> String {
+        format!("{} {subcommand}", quote(&self.binary.to_string_lossy()))
+    }
+}
+
+impl fmt::Display for SystemdUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Unit]")?;
+        writeln!(f, "Description={}", self.description)?;
+        writeln!(f, "Wants=network-online.target")?;
+        writeln!(f, "After=network-online.target")?;
+        writeln!(f)?;
+        writeln!(f, "[Service]")?;
+        writeln!(f, "Type=notify")?;
+        writeln!(f, "NotifyAccess=main")?;
+        if let Some(user) = &self.user {
+            writeln!(f, "User={user}")?;
+        }
+        writeln!(
+            f,
+            "WorkingDirectory={}",
+            quote(&self.working_dir.to_string_lossy())
+        )?;
+        if let Some(path) = &self.environment_file {
+            writeln!(f, "EnvironmentFile=-{}", path.display())?;
+        }
+        writeln!(f, "ExecStartPre={}", self.command("install"))?;
+        writeln!(f, "ExecStart={}", self.command("start --foreground"))?;
+        // The manager forwards SIGTERM to the server; only the final SIGKILL goes to all.
+        writeln!(f, "KillMode=mixed")?;
+        writeln!(f, "TimeoutStartSec={}", self.start_timeout.as_secs())?;
+        writeln!(f, "TimeoutStopSec={}", self.stop_timeout.as_secs())?;
+        if let Some(watchdog) = self.watchdog {
+            writeln!(f, "WatchdogSec={}", watchdog.as_secs().max(1))?;
+        }
+        writeln!(f, "Restart=on-failure")?;
+        writeln!(f, "RestartSec=10")?;
+        if !self.resources.cpu_affinity.is_empty() {
+            let cpus: Vec<String> = self
+                .resources
+                .cpu_affinity
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            writeln!(f, "CPUAffinity={}", cpus.join(" "))?;
+        }
+        if let Some(nice) = self.resources.nice {
+            writeln!(f, "Nice={nice}")?;
+        }
+        if let Some(limit_mb) = self.resources.memory_limit_mb {
+            writeln!(f, "MemoryMax={limit_mb}M")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[Install]")?;
+        writeln!(f, "WantedBy=multi-user.target")
+    }
+}
+
+/// Quotes `value` for a unit file: `%` and `$` are escaped, and values with whitespace
+/// or quotes are wrapped in double quotes.
+fn quote(value: &str) -> String {
+    let escaped = value.replace('%', "%%").replace('$', "$$");
+    if escaped.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn units_run_the_game_binary_in_the_foreground() {
+        let config = InstanceConfig {
+            name: "My Palworld".to_owned(),
+            working_dir: PathBuf::from("/srv/pal world"),
+            resources: ResourceLimits {
+                cpu_affinity: vec![2, 3],
+                nice: Some(5),
+                memory_limit_mb: Some(8192),
+            },
+            ..InstanceConfig::default()
+        };
+        let unit = SystemdUnit::new(&config, "/opt/gsm/palworld")
+            .with_user("steam")
+            .with_environment_file("/etc/gsm/palworld.env");
+        assert_eq!(unit.file_name(), "gsm-My-Palworld.service");
+
+        let text = unit.to_string();
+        for line in [
+            "Description=My Palworld game server",
+            "Type=notify",
+            "User=steam",
+            "WorkingDirectory=\"/srv/pal world\"",
+            "EnvironmentFile=-/etc/gsm/palworld.env",
+            "ExecStartPre=/opt/gsm/palworld install",
+            "ExecStart=/opt/gsm/palworld start --foreground",
+            "WatchdogSec=60",
+            "CPUAffinity=2 3",
+            "Nice=5",
+            "MemoryMax=8192M",
+            "WantedBy=multi-user.target",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+
+        let text = SystemdUnit::new(&InstanceConfig::default(), "/bin/game")
+            .with_watchdog(None)
+            .to_string();
+        assert!(!text.contains("WatchdogSec"));
+        assert!(!text.contains("User="));
+        assert_eq!(quote("50%$"), "50%%$$");
+    }
+
+    #[test]
+    fn notifications_reach_the_socket() {
+        let _lock = crate::test_support::env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        unsafe {
+            std::env::remove_var(NOTIFY_SOCKET);
+        }
+        assert!(!notify("READY=1").unwrap());
+        unsafe {
+            std::env::set_var(NOTIFY_SOCKET, &path);
+            std::env::set_var("WATCHDOG_USEC", "4000000");
+        }
+        assert!(notify("READY=1").unwrap());
+        let mut buffer = [0; 64];
+        let read = socket.recv(&mut buffer).unwrap();
+        assert_eq!(buffer.get(..read).unwrap(), b"READY=1");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+
+        unsafe {
+            std::env::set_var("WATCHDOG_PID", u32::MAX.to_string());
+        }
+        assert_eq!(watchdog_interval(), None);
+        unsafe {
+            std::env::remove_var(NOTIFY_SOCKET);
+            std::env::remove_var("WATCHDOG_USEC");
+            std::env::remove_var("WATCHDOG_PID");
+        }
+    }
+}
@@ -2,107 +2,238 @@
 //!
 //! This module provides functionality to check for and perform updates of the game server.
 //!
-//! It compares the build IDs from the current app manifest and the latest app info from SteamCMD.
-//! If an update is available (i.e. the build IDs differ), the `update_server` function can be used
-//! to update the installation via SteamCMD.
+//! It compares the build ID in the installed app manifest with the latest build ID SteamCMD
+//! reports for the app's branch (`+app_info_update 1 +app_info_print <appid>`), so no
+//! `appinfo.vdf` needs to be present at a container-specific path. Both are parsed as VDF (see
+//! [`crate::vdf`]) and looked up for the specific app and branch. If an update is available,
+//! the `update_server` function can be used to update the installation via SteamCMD.
 //!
 //! ## Example
 //!
 //! ```rust,no_run
 //! use std::path::Path;
-//! use gsm_instance::update::{update_is_available, update_server};
+//! use gsm_instance::env_config::EnvConfig;
+//! use gsm_instance::update::{UpdateStatus, fetch_app_info, update_status, update_server};
 //! use gsm_instance::errors::InstanceError;
 //!
-//! // Paths to the manifest and app info files
 //! let manifest_path = Path::new("/home/steam/myserver/steamapps/appmanifest_123456.acf");
-//! let appinfo_path = Path::new("/home/steam/Steam/appcache/appinfo.vdf");
+//! let app_info = fetch_app_info(123456)?;
 //!
-//! // Check if an update is available
-//! let available = update_is_available(manifest_path, appinfo_path)?;
-//! if available {
-//!     // Run the update with any extra arguments (if needed)
-//!     update_server(123456, Path::new("/home/steam/myserver"), false, &vec!["verbose".to_string()])?;
+//! // Check if an update is available on the public branch
+//! if let UpdateStatus::Available { .. } = update_status(manifest_path, &app_info, 123456, "public")? {
+//!     let env_config = EnvConfig::from_env()?;
+//!     update_server(123456, Path::new("/home/steam/myserver"), false, &[], &env_config)?;
 //! }
 //! # Ok::<(), InstanceError>(())
 //! ```
 
+use crate::env_config::EnvConfig;
 use crate::errors::InstanceError;
-use crate::steamcmd::steamcmd_command;
-use regex::Regex;
+use crate::install::install_args;
+use crate::steamcmd::{
+    STEAMCMD_RETRY, SteamCmdError, SteamCmdErrorKind, output_tail, run_steamcmd, run_with_retries,
+};
+use crate::vdf::Vdf;
+use gsm_cron::RetryPolicy;
+use gsm_shared::redact_args;
+use std::fmt;
 use std::fs;
 use std::path::Path;
 use tracing::{debug, info};
 
-/// Struct holding build ID information.
-#[derive(Debug, PartialEq, Eq)]
-pub struct UpdateInfo {
-    pub current_build_id: String,
-    pub latest_build_id: String,
+/// Environment variable pointing at a saved `+app_info_print` output to read instead of
+/// asking SteamCMD, e.g. for offline checks and tests.
+pub const STEAM_APPINFO_PATH: &str = "STEAM_APPINFO_PATH";
+
+/// Whether the installed build is the latest one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateStatus {
+    /// The installed build is the latest.
+    UpToDate {
+        /// The installed build ID.
+        build_id: String,
+    },
+    /// A newer build is available.
+    Available {
+        /// The installed build ID.
+        current_build_id: String,
+        /// The latest build ID.
+        latest_build_id: String,
+    },
+    /// The build IDs could not be determined from the manifest or app info.
+    Unknown {
+        /// Why the status is unknown.
+        reason: String,
+    },
 }
 
-impl UpdateInfo {
-    /// Creates a new UpdateInfo by reading the manifest and appinfo files.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error when either file cannot be read.
-    pub fn new(manifest_path: &Path, appinfo_path: &Path) -> Result<Self, InstanceError> {
-        let manifest_data = fs::read_to_string(manifest_path)
-            .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
-        let current_build_id = extract_build_id_from_manifest(&manifest_data).to_owned();
-
-        let appinfo_data = fs::read_to_string(appinfo_path)
-            .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
-        let latest_build_id = extract_build_id_from_app_info(&appinfo_data).to_owned();
-
-        Ok(Self {
-            current_build_id,
-            latest_build_id,
-        })
+impl UpdateStatus {
+    /// Returns true if an update is available.
+    pub const fn is_available(&self) -> bool {
+        matches!(self, Self::Available { .. })
     }
+}
 
-    /// Returns true if an update is available (build IDs differ).
-    pub fn update_available(&self) -> bool {
-        self.current_build_id != self.latest_build_id
+impl fmt::Display for UpdateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UpToDate { build_id } => write!(f, "up to date (build {build_id})"),
+            Self::Available {
+                current_build_id,
+                latest_build_id,
+            } => write!(
+                f,
+                "update available (build {current_build_id} -> {latest_build_id})"
+            ),
+            Self::Unknown { reason } => write!(f, "unknown ({reason})"),
+        }
     }
 }
 
-/// Extracts the build ID from the manifest file contents using regex.
+/// Extracts the build ID from the contents of an app manifest (`AppState.buildid`).
+fn extract_build_id_from_manifest(manifest: &str) -> Result<String, String> {
+    let manifest = Vdf::parse(manifest).map_err(|e| e.to_string())?;
+    manifest
+        .find(&["AppState", "buildid"])
+        .filter(|build_id| !build_id.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| "the app manifest has no build ID".to_owned())
+}
+
+/// Returns `app_id`'s section of SteamCMD's `+app_info_print` output, skipping the
+/// lines SteamCMD prints around it.
+pub(crate) fn app_info_section(app_info: &str, app_id: u32) -> Result<Vdf, String> {
+    let key = format!("\"{app_id}\"");
+    let start = app_info
+        .match_indices(&key)
+        .map(|(index, _)| index)
+        .find(|&index| {
+            app_info
+                .get(index + key.len()..)
+                .is_some_and(|rest| rest.trim_start().starts_with('{'))
+        })
+        .ok_or_else(|| format!("the app info has no section for app {app_id}"))?;
+    let (_, section) =
+        Vdf::parse_entry(app_info.get(start..).unwrap_or_default()).map_err(|e| e.to_string())?;
+    Ok(section)
+}
+
+/// Extracts the latest build ID of `app_id`'s `branch` from SteamCMD's `+app_info_print`
+/// output (`<app_id>.depots.branches.<branch>.buildid`).
+fn extract_build_id_from_app_info(
+    app_info: &str,
+    app_id: u32,
+    branch: &str,
+) -> Result<String, String> {
+    app_info_section(app_info, app_id)?
+        .find(&["depots", "branches", branch, "buildid"])
+        .map(str::to_owned)
+        .ok_or_else(|| format!("the app info has no build ID for branch '{branch}'"))
+}
+
+/// Reads the currently installed build ID from an app manifest.
 ///
-/// Expected format: `"buildid"    "123456"`.
-fn extract_build_id_from_manifest(manifest: &str) -> &str {
-    let Ok(re) = Regex::new(r#""buildid"\s+"(\d+)""#) else {
-        return "";
-    };
-    re.captures(manifest)
-        .and_then(|caps| caps.get(1).map(|m| m.as_str()))
-        .unwrap_or("")
+/// Returns `None` when the manifest is missing or does not contain a build ID.
+pub fn installed_build_id(manifest_path: &Path) -> Option<String> {
+    let manifest_data = fs::read_to_string(manifest_path).ok()?;
+    extract_build_id_from_manifest(&manifest_data).ok()
 }
 
-/// Extracts the build ID from the appinfo file contents using regex.
+/// Compares the build ID in the manifest at `manifest_path` with the latest build ID of
+/// `app_id`'s `branch` in `app_info`, the output of SteamCMD's `+app_info_print`.
+///
+/// # Errors
 ///
-/// Expected format (simplified): `"buildid"    "123456"`.
-fn extract_build_id_from_app_info(app_info: &str) -> &str {
-    let Ok(re) = Regex::new(r#""buildid"\s+"(\d+)""#) else {
-        return "";
+/// Returns an error when the manifest cannot be read. A manifest or app info that cannot
+/// be parsed is reported as [`UpdateStatus::Unknown`].
+pub fn update_status(
+    manifest_path: &Path,
+    app_info: &str,
+    app_id: u32,
+    branch: &str,
+) -> Result<UpdateStatus, InstanceError> {
+    let manifest_data = fs::read_to_string(manifest_path)?;
+    let status = match (
+        extract_build_id_from_manifest(&manifest_data),
+        extract_build_id_from_app_info(app_info, app_id, branch),
+    ) {
+        (Ok(current), Ok(latest)) if current == latest => {
+            UpdateStatus::UpToDate { build_id: current }
+        }
+        (Ok(current_build_id), Ok(latest_build_id)) => UpdateStatus::Available {
+            current_build_id,
+            latest_build_id,
+        },
+        (Err(reason), _) | (_, Err(reason)) => UpdateStatus::Unknown { reason },
     };
-    re.captures(app_info)
-        .and_then(|caps| caps.get(1).map(|m| m.as_str()))
-        .unwrap_or("")
+    debug!("Update status of app {app_id}: {status}");
+    Ok(status)
 }
 
-/// Checks if an update is available by comparing the build IDs from the manifest and appinfo files.
+/// Asks SteamCMD for the latest build ID of `app_id`'s `branch` (`public`, or a beta
+/// branch).
+///
+/// No local `appinfo.vdf` is needed. Returns `None` when the app info has no build ID
+/// for the branch, e.g. because the branch does not exist.
 ///
 /// # Errors
 ///
-/// Returns an error when build metadata cannot be loaded from either source file.
-pub fn update_is_available(
-    manifest_path: &Path,
-    appinfo_path: &Path,
-) -> Result<bool, InstanceError> {
-    let update_info = UpdateInfo::new(manifest_path, appinfo_path)?;
-    debug!("Update info: {:?}", update_info);
-    Ok(update_info.update_available())
+/// Returns an error when SteamCMD fails; see [`fetch_app_info`].
+pub fn latest_build_id(app_id: u32, branch: &str) -> Result<Option<String>, InstanceError> {
+    let app_info = fetch_app_info(app_id)?;
+    Ok(extract_build_id_from_app_info(&app_info, app_id, branch)
+        .inspect_err(|reason| debug!("No latest build ID for app {app_id}: {reason}"))
+        .ok())
+}
+
+/// Returns SteamCMD's `+app_info_print` output for `app_id`, refreshed from Steam, or
+/// the saved output at `STEAM_APPINFO_PATH` when set.
+///
+/// # Errors
+///
+/// Returns an error when the saved output cannot be read, or SteamCMD fails.
+pub fn fetch_app_info(app_id: u32) -> Result<String, InstanceError> {
+    if let Ok(path) = std::env::var(STEAM_APPINFO_PATH) {
+        let data = fs::read(path)?;
+        return Ok(String::from_utf8_lossy(&data).into_owned());
+    }
+
+    let app_id = app_id.to_string();
+    let args = [
+        "+login",
+        "anonymous",
+        "+app_info_update",
+        "1",
+        "+app_info_print",
+        app_id.as_str(),
+        "+quit",
+    ];
+    let output = run_steamcmd(&args).map_err(|e| SteamCmdError {
+        kind: SteamCmdErrorKind::Launch,
+        exit_code: None,
+        message: e.to_string(),
+        attempts: 1,
+        output: Vec::new(),
+        log_file: None,
+    })?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+        return Err(SteamCmdError {
+            kind: SteamCmdErrorKind::classify(&format!("{stdout}{stderr}")),
+            exit_code: output.status.code(),
+            message: lines
+                .iter()
+                .rfind(|line| !line.trim().is_empty())
+                .map_or_else(String::new, |line| line.trim().to_owned()),
+            attempts: 1,
+            output: output_tail(&lines),
+            log_file: None,
+        }
+        .into());
+    }
+    Ok(stdout)
 }
 
 /// Updates the server installation using SteamCMD.
@@ -111,6 +242,7 @@ pub fn update_is_available(
 /// - `app_id`: The Steam App ID of the server.
 /// - `install_dir`: The directory where the server is installed.
 /// - `extra_args`: Additional arguments to pass to SteamCMD during update.
+/// - `env_config`: The beta branch and additional arguments read from the environment.
 ///
 /// # Behavior
 /// Builds a SteamCMD command to update the app (with validation) and executes it.
@@ -124,47 +256,40 @@ pub fn update_is_available(
 /// ```rust,no_run
 /// # use std::path::Path;
 /// # use gsm_instance::update::update_server;
-/// update_server(123456, Path::new("/home/steam/myserver"), false, &vec!["verbose".to_string()]).expect("Update failed");
+/// # use gsm_instance::env_config::EnvConfig;
+/// update_server(123456, Path::new("/home/steam/myserver"), false, &[], &EnvConfig::default())
+///     .expect("Update failed");
 /// ```
 pub fn update_server<P: AsRef<Path>>(
     app_id: u32,
     install_dir: P,
     force_windows: bool,
     extra_args: &[String],
+    env_config: &EnvConfig,
 ) -> Result<(), InstanceError> {
     info!(
         "Updating app {} in {}",
         app_id,
         install_dir.as_ref().display()
     );
-    let login = "+login anonymous".to_owned();
-    let force_install_dir = format!("+force_install_dir {}", install_dir.as_ref().display());
-    let app_update = format!("+app_update {app_id} validate");
-    let mut args = vec![force_install_dir, login, app_update];
-
-    if force_windows {
-        let platform = "windows";
-        args.insert(0, format!("+@sSteamCmdForcePlatformType {platform}"));
-    }
+    let args = install_args(
+        app_id,
+        install_dir.as_ref(),
+        force_windows,
+        false,
+        extra_args,
+        env_config,
+    );
 
-    args.extend_from_slice(extra_args);
-    args.push(String::from("+quit"));
-
-    let mut steamcmd = steamcmd_command();
-    let command = steamcmd.args(&args);
-    debug!("Executing update command: {:?}", command);
-    let output = command
-        .output()
-        .map_err(|e| InstanceError::CommandExecutionError(e.to_string()))?;
-    if output.status.success() {
-        info!("Update successful.");
-        Ok(())
-    } else {
-        Err(InstanceError::CommandExecutionError(format!(
-            "Update failed with status: {:?}",
-            output.status
-        )))
-    }
+    debug!("Executing update command: {:?}", redact_args(&args));
+    run_with_retries(
+        &args,
+        &env_config.downloads,
+        RetryPolicy::from_env("STEAMCMD", STEAMCMD_RETRY),
+        Some(&install_dir.as_ref().join("logs")),
+    )?;
+    info!("Update successful.");
+    Ok(())
 }
 
 #[cfg(test)]
@@ -190,10 +315,30 @@ mod tests {
 "#;
 
     const SAMPLE_APPINFO: &str = r#"
-"appinfo"
+AppID : 123456, change number : 2548151/0, last change : Wed Oct 14 09:12:45 2026
+"123456"
 {
-    "buildid"      "1001"
+    "common"
+    {
+        "name"     "Test Dedicated Server"
+        "buildid"  "1"
+    }
+    "depots"
+    {
+        "branches"
+        {
+            "public"
+            {
+                "buildid"      "1001"
+            }
+            "experimental"
+            {
+                "buildid"      "1002"
+            }
+        }
+    }
 }
+Unloading Steam API...OK
 "#;
 
     #[cfg(unix)]
@@ -208,68 +353,127 @@ mod tests {
 
     #[test]
     fn test_extract_build_id_from_manifest() {
-        let build_id = extract_build_id_from_manifest(SAMPLE_MANIFEST);
+        let build_id = extract_build_id_from_manifest(SAMPLE_MANIFEST).unwrap();
         assert_eq!(build_id, "1000");
     }
 
     #[test]
-    fn test_extract_build_id_from_app_info() {
-        let build_id = extract_build_id_from_app_info(SAMPLE_APPINFO);
+    fn test_extract_build_id_from_app_info_reads_the_branch() {
+        let build_id = extract_build_id_from_app_info(SAMPLE_APPINFO, 123456, "public").unwrap();
         assert_eq!(build_id, "1001");
+        let build_id =
+            extract_build_id_from_app_info(SAMPLE_APPINFO, 123456, "experimental").unwrap();
+        assert_eq!(build_id, "1002");
     }
 
     #[test]
-    fn test_extract_build_id_from_manifest_returns_empty_when_missing() {
-        let build_id = extract_build_id_from_manifest("\"AppState\" {}\n");
-        assert_eq!(build_id, "");
+    fn test_extract_build_id_from_manifest_fails_when_missing() {
+        assert!(extract_build_id_from_manifest("\"AppState\" {}\n").is_err());
+        assert!(extract_build_id_from_manifest("\"AppState\" {\n").is_err());
     }
 
     #[test]
-    fn test_extract_build_id_from_app_info_returns_empty_when_missing() {
-        let build_id = extract_build_id_from_app_info("\"appinfo\" {}\n");
-        assert_eq!(build_id, "");
+    fn test_extract_build_id_from_app_info_fails_for_other_apps_and_branches() {
+        assert!(extract_build_id_from_app_info(SAMPLE_APPINFO, 654321, "public").is_err());
+        assert!(extract_build_id_from_app_info(SAMPLE_APPINFO, 123456, "missing").is_err());
+        assert!(
+            extract_build_id_from_app_info("\x07\x28\x44\x56binary", 123456, "public").is_err()
+        );
     }
 
     #[test]
-    fn test_update_info_update_available() {
+    fn test_installed_build_id_reads_manifest() {
         let temp_dir = tempdir().unwrap();
         let manifest_path = temp_dir.path().join("appmanifest.acf");
-        let appinfo_path = temp_dir.path().join("appinfo.txt");
-        fs::write(&manifest_path, SAMPLE_MANIFEST).unwrap();
-        fs::write(&appinfo_path, SAMPLE_APPINFO).unwrap();
+        assert_eq!(installed_build_id(&manifest_path), None);
 
-        let available = update_is_available(&manifest_path, &appinfo_path).unwrap();
-        assert!(available);
+        fs::write(&manifest_path, SAMPLE_MANIFEST).unwrap();
+        assert_eq!(installed_build_id(&manifest_path).as_deref(), Some("1000"));
     }
 
     #[test]
-    fn test_update_info_no_update() {
+    fn test_update_status_reports_each_outcome() {
         let temp_dir = tempdir().unwrap();
         let manifest_path = temp_dir.path().join("appmanifest.acf");
-        let appinfo_path = temp_dir.path().join("appinfo.txt");
-        let sample = r#"
-"AppState"
-{
-    "appid"        "123456"
-    "buildid"      "2000"
-}
-"#;
-        fs::write(&manifest_path, sample).unwrap();
-        fs::write(&appinfo_path, sample).unwrap();
+        fs::write(&manifest_path, SAMPLE_MANIFEST).unwrap();
 
-        let available = update_is_available(&manifest_path, &appinfo_path).unwrap();
-        assert!(!available);
+        assert_eq!(
+            update_status(&manifest_path, SAMPLE_APPINFO, 123456, "public").unwrap(),
+            UpdateStatus::Available {
+                current_build_id: "1000".to_owned(),
+                latest_build_id: "1001".to_owned(),
+            }
+        );
+        assert!(matches!(
+            update_status(&manifest_path, "garbage", 123456, "public").unwrap(),
+            UpdateStatus::Unknown { .. }
+        ));
+
+        fs::write(
+            &manifest_path,
+            SAMPLE_MANIFEST.replace("\"1000\"", "\"1002\""),
+        )
+        .unwrap();
+        assert_eq!(
+            update_status(&manifest_path, SAMPLE_APPINFO, 123456, "experimental").unwrap(),
+            UpdateStatus::UpToDate {
+                build_id: "1002".to_owned()
+            }
+        );
     }
 
     #[test]
-    fn test_update_info_new_returns_error_for_missing_manifest() {
+    fn test_update_status_returns_error_for_missing_manifest() {
         let temp_dir = tempdir().unwrap();
         let manifest_path = temp_dir.path().join("missing_manifest.acf");
-        let appinfo_path = temp_dir.path().join("appinfo.txt");
-        fs::write(&appinfo_path, SAMPLE_APPINFO).unwrap();
 
-        let error = UpdateInfo::new(&manifest_path, &appinfo_path).unwrap_err();
-        assert!(matches!(error, InstanceError::CommandExecutionError(_)));
+        let error = update_status(&manifest_path, SAMPLE_APPINFO, 123456, "public").unwrap_err();
+        assert!(matches!(error, InstanceError::IoError(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fetch_app_info_and_latest_build_id_run_steamcmd() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        let script_path = temp_dir.path().join("fake-steamcmd.sh");
+        write_executable_script(&script_path, "#!/bin/sh\necho \"$@\"\n");
+
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", &script_path);
+        }
+
+        assert_eq!(
+            fetch_app_info(123456).unwrap(),
+            "+login anonymous +app_info_update 1 +app_info_print 123456 +quit\n"
+        );
+
+        let script = format!("#!/bin/sh\ncat <<'EOF'\n{SAMPLE_APPINFO}EOF\n");
+        write_executable_script(&script_path, &script);
+        assert_eq!(
+            latest_build_id(123456, "experimental").unwrap().as_deref(),
+            Some("1002")
+        );
+        assert_eq!(latest_build_id(123456, "missing").unwrap(), None);
+
+        write_executable_script(
+            &script_path,
+            "#!/bin/sh\necho 'FAILED (No Connection)'\nexit 7\n",
+        );
+        assert!(matches!(
+            fetch_app_info(123456),
+            Err(InstanceError::SteamCmdError(SteamCmdError {
+                kind: SteamCmdErrorKind::Network,
+                exit_code: Some(7),
+                ..
+            }))
+        ));
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
     }
 
     #[cfg(unix)]
@@ -292,7 +496,14 @@ mod tests {
         }
 
         let extra_args = vec![String::from("+app_info_update 1")];
-        update_server(2278520, temp_dir.path(), true, &extra_args).unwrap();
+        update_server(
+            2278520,
+            temp_dir.path(),
+            true,
+            &extra_args,
+            &EnvConfig::default(),
+        )
+        .unwrap();
 
         let recorded_args = fs::read_to_string(&args_path).unwrap();
         let lines: Vec<&str> = recorded_args.lines().collect();
@@ -325,13 +536,17 @@ mod tests {
             std::env::set_var("STEAMCMD_PATH", &script_path);
         }
 
-        let error = update_server(2278520, temp_dir.path(), false, &[]).unwrap_err();
-        match error {
-            InstanceError::CommandExecutionError(message) => {
-                assert!(message.contains("Update failed with status"));
-            }
-            other => assert!(matches!(other, InstanceError::CommandExecutionError(_))),
-        }
+        let error =
+            update_server(2278520, temp_dir.path(), false, &[], &EnvConfig::default()).unwrap_err();
+        assert!(matches!(
+            error,
+            InstanceError::SteamCmdError(SteamCmdError {
+                kind: SteamCmdErrorKind::Unknown,
+                exit_code: Some(1),
+                attempts: 1,
+                ..
+            })
+        ));
 
         unsafe {
             std::env::remove_var("STEAMCMD_PATH");
@@ -0,0 +1,285 @@
+//! # Resource Usage
+//!
+//! This module measures what a running server costs: CPU, resident memory, threads and
+//! the disk space its working directory takes up. [`Instance::resource_usage`] takes one
+//! measurement; [`Instance::sample_usage`] keeps measuring in the background and hands
+//! each sample to a callback, e.g. to export it as metrics or to warn when memory keeps
+//! growing.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//! use std::time::Duration;
+//!
+//! let instance = Instance::new(InstanceConfig::default());
+//! let sampler = instance.sample_usage(Duration::from_secs(60), |usage| {
+//!     println!("{} MiB resident", usage.rss / (1024 * 1024));
+//! });
+//! // ...
+//! sampler.stop();
+//! ```
+use crate::instance::Instance;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use sysinfo::{MINIMUM_CPU_UPDATE_INTERVAL, Pid, ProcessStatus, ProcessesToUpdate, System};
+use tracing::debug;
+
+/// One measurement of a running server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceUsage {
+    pub pid: u32,
+    /// How long the process has been running.
+    pub uptime: Duration,
+    /// CPU usage in percent of one core since the previous measurement.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub rss: u64,
+    /// Threads in the process.
+    pub threads: usize,
+    /// Bytes taken up by the files in the working directory.
+    pub disk: u64,
+}
+
+/// Process statistics read from the process table.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessSample {
+    pub uptime: Duration,
+    pub cpu: f32,
+    pub rss: u64,
+}
+
+/// Reads one process's statistics repeatedly. CPU usage is measured between reads, so
+/// the first read always reports zero.
+pub struct ProcessProbe {
+    system: System,
+    pid: Pid,
+}
+
+impl ProcessProbe {
+    pub fn new(pid: u32) -> Self {
+        Self {
+            system: System::new(),
+            pid: Pid::from_u32(pid),
+        }
+    }
+
+    /// Returns the process's current statistics, or `None` when it is not running.
+    pub fn sample(&mut self) -> Option<ProcessSample> {
+        self.system
+            .refresh_processes(ProcessesToUpdate::Some(&[self.pid]), true);
+        let process = self.system.process(self.pid)?;
+        if matches!(
+            process.status(),
+            ProcessStatus::Zombie | ProcessStatus::Dead
+        ) {
+            return None;
+        }
+        Some(ProcessSample {
+            uptime: Duration::from_secs(process.run_time()),
+            cpu: process.cpu_usage(),
+            rss: process.memory(),
+        })
+    }
+
+    /// Returns the process's statistics with CPU usage measured over a short interval.
+    pub fn sample_now(&mut self) -> Option<ProcessSample> {
+        self.sample()?;
+        thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+        self.sample()
+    }
+
+    fn threads(&self) -> usize {
+        fs::read_dir(format!("/proc/{}/task", self.pid)).map_or(0, Iterator::count)
+    }
+}
+
+/// Returns the bytes taken up by the files under `dir`, without following symlinks.
+pub fn disk_usage(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => disk_usage(&entry.path()),
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn measure(probe: &ProcessProbe, sample: ProcessSample, dir: &Path) -> ResourceUsage {
+    ResourceUsage {
+        pid: probe.pid.as_u32(),
+        uptime: sample.uptime,
+        cpu: sample.cpu,
+        rss: sample.rss,
+        threads: probe.threads(),
+        disk: disk_usage(dir),
+    }
+}
+
+/// A background thread sampling a server's resource usage; see
+/// [`Instance::sample_usage`]. Sampling stops when this is stopped or dropped.
+#[derive(Debug)]
+pub struct UsageSampler {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UsageSampler {
+    /// Stops sampling and waits for the sampling thread to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for UsageSampler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Instance {
+    /// Measures the running server, or returns `None` when it is not running.
+    ///
+    /// This takes a moment, because CPU usage is measured over a short interval, and
+    /// longer for large working directories.
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        let mut probe = ProcessProbe::new(self.pid().ok()?);
+        let sample = probe.sample_now()?;
+        Some(measure(&probe, sample, &self.config.working_dir))
+    }
+
+    /// Measures the running server every `interval` on a background thread, calling
+    /// `on_sample` with each measurement. Intervals while the server is not running are
+    /// skipped; a restarted server is picked up from its new state file.
+    ///
+    /// Measuring walks the whole working directory, so keep `interval` to a minute or
+    /// more for large installs.
+    pub fn sample_usage(
+        &self,
+        interval: Duration,
+        mut on_sample: impl FnMut(&ResourceUsage) + Send + 'static,
+    ) -> UsageSampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let instance = self.clone();
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            let mut probe: Option<ProcessProbe> = None;
+            while !stopped.load(Ordering::SeqCst) {
+                let started = Instant::now();
+                if let Ok(pid) = instance.pid() {
+                    let probe = match &mut probe {
+                        Some(probe) if probe.pid.as_u32() == pid => probe,
+                        _ => probe.insert(ProcessProbe::new(pid)),
+                    };
+                    if let Some(sample) = probe.sample() {
+                        on_sample(&measure(probe, sample, &instance.config.working_dir));
+                    } else {
+                        debug!("Server process {pid} is not running; skipping sample");
+                    }
+                } else {
+                    debug!("Server is not running; skipping sample");
+                }
+                while !stopped.load(Ordering::SeqCst) && started.elapsed() < interval {
+                    thread::sleep(interval.min(Duration::from_millis(100)));
+                }
+            }
+        });
+        UsageSampler {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::InstanceConfig;
+    use crate::state::InstanceState;
+    use std::sync::mpsc;
+    use tempfile::tempdir;
+
+    fn own_instance(dir: &Path) -> Instance {
+        let config = InstanceConfig {
+            working_dir: dir.to_path_buf(),
+            ..InstanceConfig::default()
+        };
+        InstanceState::capture(std::process::id(), &config)
+            .write(&config.state_file())
+            .unwrap();
+        Instance::new(config)
+    }
+
+    #[test]
+    fn disk_usage_sums_nested_files() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("a/b")).unwrap();
+        fs::write(temp_dir.path().join("one"), [0; 10]).unwrap();
+        fs::write(temp_dir.path().join("a/b/two"), [0; 32]).unwrap();
+        assert_eq!(disk_usage(temp_dir.path()), 42);
+        assert_eq!(disk_usage(&temp_dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn resource_usage_measures_the_running_process() {
+        let temp_dir = tempdir().unwrap();
+        let instance = own_instance(temp_dir.path());
+        let usage = instance.resource_usage().unwrap();
+        assert_eq!(usage.pid, std::process::id());
+        assert!(usage.rss > 0);
+        assert!(usage.threads >= 1);
+        // Only the state file is in the working directory.
+        assert_eq!(
+            usage.disk,
+            fs::metadata(instance.config.state_file()).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn resource_usage_is_none_when_stopped() {
+        let temp_dir = tempdir().unwrap();
+        let instance = Instance::new(InstanceConfig {
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        });
+        assert!(instance.resource_usage().is_none());
+    }
+
+    #[test]
+    fn sample_usage_calls_back_until_stopped() {
+        let temp_dir = tempdir().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let sampler =
+            own_instance(temp_dir.path()).sample_usage(Duration::from_millis(20), move |usage| {
+                let _ = tx.send(usage.pid);
+            });
+
+        for _ in 0..2 {
+            assert_eq!(
+                rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+                std::process::id()
+            );
+        }
+        sampler.stop();
+        while rx.try_recv().is_ok() {}
+        thread::sleep(Duration::from_millis(60));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! # VDF Parsing
+//!
+//! Steam writes its app manifests (`appmanifest_<id>.acf`) and SteamCMD prints app info
+//! (`+app_info_print`) in the text form of Valve's KeyValues format, VDF: quoted keys
+//! followed by either a quoted value or a braced section of further keys. This module
+//! parses that text into a [`Vdf`] tree, so build ids are read from the right app and
+//! branch instead of whichever `"buildid"` appears first.
+//!
+//! Keys are matched case-insensitively, as Steam does.
+//!
+//! # Example
+//!
+//! ```rust
+//! use gsm_instance::vdf::Vdf;
+//!
+//! let manifest = Vdf::parse(r#""AppState" { "appid" "2278520" "buildid" "1000" }"#)?;
+//! assert_eq!(manifest.find(&["AppState", "buildid"]), Some("1000"));
+//! # Ok::<(), gsm_instance::vdf::VdfError>(())
+//! ```
+use std::fmt;
+
+/// A parsed VDF value: a string, or a section of keyed values in file order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Vdf {
+    /// A string value.
+    Value(String),
+    /// A braced section.
+    Section(Vec<(String, Self)>),
+}
+
+/// Why VDF text could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VdfError {
+    /// The line the problem was found on, starting at 1.
+    pub line: usize,
+    /// What was wrong.
+    pub message: String,
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid VDF at line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for VdfError {}
+
+impl Vdf {
+    /// Parses a VDF document, returning its top-level keys as a section.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the text is not valid VDF.
+    pub fn parse(input: &str) -> Result<Self, VdfError> {
+        let mut parser = Parser::new(input);
+        let entries = parser.entries(false)?;
+        Ok(Self::Section(entries))
+    }
+
+    /// Parses the first entry of `input`, ignoring whatever follows it, e.g. the rest
+    /// of SteamCMD's output. Returns the entry's key and value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the entry is not valid VDF.
+    pub fn parse_entry(input: &str) -> Result<(String, Self), VdfError> {
+        Parser::new(input).entry()?.ok_or_else(|| VdfError {
+            line: 1,
+            message: "expected a key".to_owned(),
+        })
+    }
+
+    /// Returns the value of `key` in this section, if it is one and has the key.
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Section(entries) => entries
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value),
+            Self::Value(_) => None,
+        }
+    }
+
+    /// Follows `path` through nested sections and returns the string value at its end.
+    pub fn find(&self, path: &[&str]) -> Option<&str> {
+        path.iter()
+            .try_fold(self, |value, key| value.get(key))?
+            .as_str()
+    }
+
+    /// Returns the string value, if this is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Section(_) => None,
+        }
+    }
+}
+
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> VdfError {
+        VdfError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// Parses entries until the end of input or, inside a section, its closing brace.
+    fn entries(&mut self, nested: bool) -> Result<Vec<(String, Vdf)>, VdfError> {
+        let mut entries = Vec::new();
+        loop {
+            match self.token()? {
+                Some(Token::String(key)) => entries.push((key, self.value()?)),
+                Some(Token::Close) if nested => return Ok(entries),
+                None if !nested => return Ok(entries),
+                Some(Token::Close) => return Err(self.error("unexpected '}'")),
+                Some(Token::Open) => return Err(self.error("expected a key, found '{'")),
+                None => return Err(self.error("unterminated section")),
+            }
+        }
+    }
+
+    fn entry(&mut self) -> Result<Option<(String, Vdf)>, VdfError> {
+        match self.token()? {
+            Some(Token::String(key)) => Ok(Some((key, self.value()?))),
+            Some(_) => Err(self.error("expected a key")),
+            None => Ok(None),
+        }
+    }
+
+    fn value(&mut self) -> Result<Vdf, VdfError> {
+        match self.token()? {
+            Some(Token::String(value)) => Ok(Vdf::Value(value)),
+            Some(Token::Open) => Ok(Vdf::Section(self.entries(true)?)),
+            Some(Token::Close) => Err(self.error("expected a value, found '}'")),
+            None => Err(self.error("expected a value, found the end of input")),
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    /// Reads the next token, skipping whitespace, `//` comments and `[$PLATFORM]`
+    /// conditionals.
+    fn token(&mut self) -> Result<Option<Token>, VdfError> {
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                c if c.is_whitespace() => {
+                    self.next_char();
+                }
+                '/' => while self.next_char().is_some_and(|c| c != '\n') {},
+                '[' => while self.next_char().is_some_and(|c| c != ']') {},
+                '{' => {
+                    self.next_char();
+                    return Ok(Some(Token::Open));
+                }
+                '}' => {
+                    self.next_char();
+                    return Ok(Some(Token::Close));
+                }
+                '"' => {
+                    self.next_char();
+                    return self.quoted().map(|s| Some(Token::String(s)));
+                }
+                _ => return Ok(Some(Token::String(self.unquoted()))),
+            }
+        }
+        Ok(None)
+    }
+
+    fn quoted(&mut self) -> Result<String, VdfError> {
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next_char() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn unquoted(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || matches!(c, '"' | '{' | '}') {
+                break;
+            }
+            value.push(c);
+            self.next_char();
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn parses_nested_sections_comments_and_escapes() {
+        let vdf = Vdf::parse(
+            r#"
+// An app manifest
+"AppState"
+{
+    "appid"        "2278520"
+    "name"         "Say \"hi\""
+    "InstalledDepots"
+    {
+        "2278521" { "manifest" "42" }
+    }
+    unquoted value
+}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(vdf.find(&["appstate", "APPID"]), Some("2278520"));
+        assert_eq!(vdf.find(&["AppState", "name"]), Some("Say \"hi\""));
+        assert_eq!(
+            vdf.find(&["AppState", "InstalledDepots", "2278521", "manifest"]),
+            Some("42")
+        );
+        assert_eq!(vdf.find(&["AppState", "unquoted"]), Some("value"));
+        assert_eq!(vdf.find(&["AppState", "InstalledDepots"]), None);
+    }
+
+    #[test]
+    fn parse_entry_ignores_trailing_output() {
+        let (key, value) =
+            Vdf::parse_entry("\"1\" { \"a\" \"b\" }\nUnloading Steam API...").unwrap();
+        assert_eq!(key, "1");
+        assert_eq!(value.find(&["a"]), Some("b"));
+    }
+
+    #[test]
+    fn malformed_documents_are_errors() {
+        assert_eq!(
+            Vdf::parse("\"AppState\"\n{\n\"buildid\" \"1\"").unwrap_err(),
+            VdfError {
+                line: 3,
+                message: "unterminated section".to_owned()
+            }
+        );
+        assert!(Vdf::parse("\"a\" }").is_err());
+        assert!(Vdf::parse("\"a\" \"b").is_err());
+        assert!(Vdf::parse("\"a\"").is_err());
+    }
+}
@@ -0,0 +1,385 @@
+//! # Steam Workshop Content
+//!
+//! This module downloads Steam Workshop items, such as the mods of a Project Zomboid or
+//! ARK server, with SteamCMD's `+workshop_download_item`. SteamCMD puts each item in
+//! `steamapps/workshop/content/<app id>/<item id>` under the working directory; when a
+//! [`WorkshopConfig::mods_dir`] is set, the items are also copied to
+//! `<mods_dir>/<item id>`, where games that do not read Steam's layout expect them.
+//!
+//! What was installed is recorded in `workshop.json` in the working directory (see
+//! [`WorkshopManifest`]), so tools can list the installed items and when they were
+//! last downloaded.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use gsm_instance::{Instance, InstanceConfig};
+//! use gsm_instance::workshop::WorkshopConfig;
+//!
+//! let instance = Instance::new(InstanceConfig {
+//!     app_id: 380_870,
+//!     workshop: WorkshopConfig {
+//!         // Project Zomboid's dedicated server downloads the game's workshop items.
+//!         app_id: Some(108_600),
+//!         mods_dir: None,
+//!     },
+//!     ..InstanceConfig::default()
+//! });
+//! let manifest = instance
+//!     .download_workshop_items(&[2_392_709_985])
+//!     .expect("Workshop download failed");
+//! println!("{} workshop items installed", manifest.items.len());
+//! ```
+use crate::clone::CloneStats;
+use crate::config::{DownloadConfig, InstanceConfig};
+use crate::dry_run;
+use crate::errors::InstanceError;
+use crate::steamcmd::{STEAMCMD_RETRY, run_with_retries};
+use gsm_cron::RetryPolicy;
+use gsm_shared::redact_args;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Where Steam Workshop items are downloaded from and installed to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkshopConfig {
+    /// The App ID the items belong to, when it differs from the server's, as it does for
+    /// games whose dedicated server has its own App ID (e.g. Project Zomboid's server is
+    /// 380870, its workshop is 108600).
+    pub app_id: Option<u32>,
+    /// The directory, relative to the working directory, to copy each item into as
+    /// `<mods_dir>/<item id>`, e.g. `ShooterGame/Content/Mods` for ARK. When unset, items
+    /// stay in Steam's workshop directory only.
+    pub mods_dir: Option<PathBuf>,
+}
+
+/// An installed Steam Workshop item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkshopItem {
+    /// The item's published file id.
+    pub id: u64,
+    /// Where the game reads the item from: the copy in `mods_dir`, or else SteamCMD's
+    /// download directory.
+    pub path: PathBuf,
+    /// The size of the item's files, in bytes.
+    pub size: u64,
+    /// When the item was last downloaded, in seconds since the Unix epoch.
+    pub downloaded_at: u64,
+}
+
+/// The Steam Workshop items installed for an instance, stored as `workshop.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkshopManifest {
+    /// The installed items, ordered by id.
+    pub items: Vec<WorkshopItem>,
+}
+
+impl WorkshopManifest {
+    /// Returns the manifest path under `working_dir`.
+    pub fn path(working_dir: &Path) -> PathBuf {
+        working_dir.join("workshop.json")
+    }
+
+    /// Reads the manifest at `path`, or an empty one when there is none yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be read or is not a valid manifest.
+    pub fn load(path: &Path) -> Result<Self, InstanceError> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data).map_err(io::Error::from)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the manifest to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), InstanceError> {
+        fs::write(
+            path,
+            serde_json::to_vec_pretty(self).map_err(io::Error::from)?,
+        )?;
+        Ok(())
+    }
+
+    /// Returns the installed item `id`, if any.
+    pub fn item(&self, id: u64) -> Option<&WorkshopItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Adds `item`, replacing an earlier install of the same item.
+    fn insert(&mut self, item: WorkshopItem) {
+        self.items.retain(|existing| existing.id != item.id);
+        self.items.push(item);
+        self.items.sort_by_key(|item| item.id);
+    }
+}
+
+/// Returns the directory SteamCMD downloads items of `config`'s workshop into.
+pub fn content_dir(config: &InstanceConfig) -> PathBuf {
+    let app_id = config.workshop.app_id.unwrap_or(config.app_id);
+    config
+        .working_dir
+        .join("steamapps")
+        .join("workshop")
+        .join("content")
+        .join(app_id.to_string())
+}
+
+/// Downloads (or updates) the workshop items `items` for `config`, copies them into
+/// `mods_dir` when one is set, and records them in the instance's [`WorkshopManifest`].
+/// Returns the updated manifest.
+///
+/// SteamCMD runs once for all items with the `downloads` rate limit and proxy, and is
+/// retried like an install; see [`run_with_retries`]. A dry run only logs the SteamCMD
+/// command line and returns the manifest unchanged.
+///
+/// # Errors
+///
+/// Returns an error when SteamCMD fails, an item was not downloaded (SteamCMD exits
+/// successfully even when a download fails), or the files or manifest cannot be written.
+pub fn download_items(
+    config: &InstanceConfig,
+    items: &[u64],
+    downloads: &DownloadConfig,
+) -> Result<WorkshopManifest, InstanceError> {
+    let manifest_path = WorkshopManifest::path(&config.working_dir);
+    let mut manifest = WorkshopManifest::load(&manifest_path)?;
+    if items.is_empty() {
+        return Ok(manifest);
+    }
+
+    let app_id = config.workshop.app_id.unwrap_or(config.app_id);
+    info!(
+        "Downloading {} workshop item(s) of app {app_id}",
+        items.len()
+    );
+    let mut args = vec![
+        format!("+force_install_dir {}", config.working_dir.display()),
+        "+login anonymous".to_owned(),
+    ];
+    if config.launch_mode.is_windows() {
+        args.insert(0, "+@sSteamCmdForcePlatformType windows".to_owned());
+    }
+    args.extend(
+        items
+            .iter()
+            .map(|item| format!("+workshop_download_item {app_id} {item}")),
+    );
+    args.push("+quit".to_owned());
+    if config.dry_run {
+        info!(
+            "Dry run: would run {}",
+            dry_run::steamcmd_line(&args, downloads)
+        );
+        return Ok(manifest);
+    }
+    debug!(
+        "Launching workshop download command: {:?}",
+        redact_args(&args)
+    );
+    run_with_retries(
+        &args,
+        downloads,
+        RetryPolicy::from_env("STEAMCMD", STEAMCMD_RETRY),
+        Some(&config.log_dir()),
+    )?;
+
+    let downloads = content_dir(config);
+    let downloaded_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    for &id in items {
+        let download = downloads.join(id.to_string());
+        if !download.is_dir() {
+            return Err(InstanceError::WorkshopError(format!(
+                "item {id} was not downloaded to {}",
+                download.display()
+            )));
+        }
+        let mut stats = CloneStats::default();
+        let path = if let Some(mods_dir) = &config.workshop.mods_dir {
+            let target = config.working_dir.join(mods_dir).join(id.to_string());
+            if target.exists() {
+                fs::remove_dir_all(&target)?;
+            }
+            install_files(&download, Some(&target), &mut stats)?;
+            target
+        } else {
+            install_files(&download, None, &mut stats)?;
+            download
+        };
+        info!(
+            "Installed workshop item {id} ({} files, {} bytes) at {}",
+            stats.files,
+            stats.bytes,
+            path.display()
+        );
+        manifest.insert(WorkshopItem {
+            id,
+            path,
+            size: stats.bytes,
+            downloaded_at,
+        });
+    }
+    manifest.save(&manifest_path)?;
+    Ok(manifest)
+}
+
+/// Adds the files under `source` to `stats`, copying them to `target` if one is given.
+fn install_files(source: &Path, target: Option<&Path>, stats: &mut CloneStats) -> io::Result<()> {
+    if let Some(target) = target {
+        fs::create_dir_all(target)?;
+    }
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = target.map(|target| target.join(entry.file_name()));
+        if file_type.is_dir() {
+            install_files(&entry.path(), target.as_deref(), stats)?;
+        } else if file_type.is_file() {
+            stats.files += 1;
+            stats.bytes += entry.metadata()?.len();
+            if let Some(target) = target {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_support::env_lock;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    /// Writes a fake SteamCMD that records its arguments and "downloads" every item
+    /// except 666.
+    fn fake_steamcmd(dir: &Path) -> PathBuf {
+        let script = dir.join("fake-steamcmd.sh");
+        fs::write(
+            &script,
+            format!(
+                r#"#!/bin/sh
+printf '%s\n' "$@" > '{args}'
+for arg in "$@"; do
+  case "$arg" in
+    "+workshop_download_item "*)
+      set -- $arg
+      [ "$3" = 666 ] && continue
+      mkdir -p "{root}/steamapps/workshop/content/$2/$3/maps"
+      echo "$3" > "{root}/steamapps/workshop/content/$2/$3/maps/map.txt"
+      ;;
+  esac
+done
+exit 0
+"#,
+                args = dir.join("args.txt").display(),
+                root = dir.display(),
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    #[test]
+    fn downloads_items_into_mods_dir_and_records_them() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", fake_steamcmd(temp_dir.path()));
+        }
+        let config = InstanceConfig {
+            app_id: 380_870,
+            working_dir: temp_dir.path().to_path_buf(),
+            workshop: WorkshopConfig {
+                app_id: Some(108_600),
+                mods_dir: Some(PathBuf::from("Mods")),
+            },
+            ..InstanceConfig::default()
+        };
+
+        let downloads = DownloadConfig {
+            throttle_kbps: Some(1024),
+            http_proxy: None,
+        };
+        let manifest = download_items(&config, &[20, 10], &downloads).unwrap();
+
+        let args = fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert!(args.starts_with("+set_download_throttle 1024\n"));
+        assert!(args.contains("+workshop_download_item 108600 20\n"));
+        assert!(args.contains("+workshop_download_item 108600 10\n"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("Mods/10/maps/map.txt")).unwrap(),
+            "10\n"
+        );
+        let ids: Vec<_> = manifest.items.iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![10, 20]);
+        assert_eq!(
+            manifest.item(20).unwrap().path,
+            temp_dir.path().join("Mods/20")
+        );
+        assert_eq!(manifest.item(20).unwrap().size, 3);
+        assert_eq!(
+            WorkshopManifest::load(&WorkshopManifest::path(temp_dir.path())).unwrap(),
+            manifest
+        );
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+
+    #[test]
+    fn items_steamcmd_did_not_download_are_errors() {
+        let _lock = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp_dir = tempdir().unwrap();
+        unsafe {
+            std::env::set_var("STEAMCMD_PATH", fake_steamcmd(temp_dir.path()));
+        }
+        let config = InstanceConfig {
+            app_id: 108_600,
+            working_dir: temp_dir.path().to_path_buf(),
+            ..InstanceConfig::default()
+        };
+
+        let manifest = download_items(&config, &[10], &DownloadConfig::default()).unwrap();
+        assert_eq!(
+            manifest.item(10).unwrap().path,
+            content_dir(&config).join("10")
+        );
+        assert!(matches!(
+            download_items(&config, &[666], &DownloadConfig::default()),
+            Err(InstanceError::WorkshopError(_))
+        ));
+        // The failed download leaves the earlier items recorded.
+        assert!(
+            WorkshopManifest::load(&WorkshopManifest::path(temp_dir.path()))
+                .unwrap()
+                .item(10)
+                .is_some()
+        );
+
+        unsafe {
+            std::env::remove_var("STEAMCMD_PATH");
+        }
+    }
+}
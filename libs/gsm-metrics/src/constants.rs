@@ -0,0 +1,12 @@
+pub const METRICS_MODE: &str = "METRICS_MODE";
+pub const METRICS_ADDRESS: &str = "METRICS_ADDRESS";
+pub const METRICS_PUSHGATEWAY_URL: &str = "METRICS_PUSHGATEWAY_URL";
+pub const METRICS_JOB: &str = "METRICS_JOB";
+pub const METRICS_STATSD_ADDRESS: &str = "METRICS_STATSD_ADDRESS";
+pub const METRICS_STATSD_TAGS: &str = "METRICS_STATSD_TAGS";
+pub const METRICS_PUSH_INTERVAL: &str = "METRICS_PUSH_INTERVAL";
+
+pub const DEFAULT_METRICS_ADDRESS: &str = "0.0.0.0:9100";
+pub const DEFAULT_STATSD_ADDRESS: &str = "127.0.0.1:8125";
+pub const DEFAULT_JOB: &str = "gsm";
+pub const DEFAULT_PUSH_INTERVAL_SECS: u64 = 15;
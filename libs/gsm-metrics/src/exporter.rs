@@ -0,0 +1,55 @@
+//! A minimal HTTP endpoint for Prometheus to scrape.
+
+use crate::{MetricsError, MetricsRegistry};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use tracing::{debug, info, warn};
+
+/// Binds `address` and serves `registry` at `/metrics` from a background thread.
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound.
+pub fn serve_scrape_endpoint(
+    address: &str,
+    registry: &MetricsRegistry,
+) -> Result<thread::JoinHandle<()>, MetricsError> {
+    let listener = TcpListener::bind(address)?;
+    info!("Serving metrics at http://{address}/metrics");
+
+    let registry = registry.clone();
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_connection(stream, &registry) {
+                        debug!("Metrics request failed: {e}");
+                    }
+                }
+                Err(e) => warn!("Failed to accept metrics connection: {e}"),
+            }
+        }
+    }))
+}
+
+fn handle_connection(stream: TcpStream, registry: &MetricsRegistry) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if path == "/metrics" {
+        ("200 OK", registry.render_prometheus())
+    } else {
+        ("404 Not Found", String::from("Not Found\n"))
+    };
+
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,56 @@
+//! # gsm-metrics
+//!
+//! A small metrics registry shared by every way of getting metrics out of a container.
+//!
+//! Metrics are recorded into a [`MetricsRegistry`] and then published according to
+//! `METRICS_MODE`:
+//!
+//! - `scrape`: serves the Prometheus text format over HTTP for a scraper to pull.
+//! - `pushgateway`: periodically pushes the same text format to a Prometheus Pushgateway.
+//! - `statsd` / `dogstatsd`: periodically sends samples over UDP to a StatsD agent.
+//!
+//! Every mode reads from the same registry, so switching modes never changes which
+//! metrics are reported.
+//!
+//! ## Usage
+//!
+//! ```rust,no_run
+//! use gsm_metrics::{MetricsRegistry, start_metrics};
+//!
+//! let registry = MetricsRegistry::default();
+//! let players = registry.gauge("gsm_players_online", "Players currently connected");
+//! players.set(3.0);
+//!
+//! start_metrics(&registry)?;
+//! # Ok::<(), gsm_metrics::MetricsError>(())
+//! ```
+
+mod constants;
+mod exporter;
+mod mode;
+mod push;
+mod registry;
+
+pub use exporter::serve_scrape_endpoint;
+pub use mode::{MetricsConfig, MetricsMode, start_metrics, start_metrics_with_config};
+pub use push::{MetricsEmitter, PushgatewayEmitter, StatsdEmitter, StatsdFlavor};
+pub use registry::{Counter, Gauge, MetricKind, MetricSample, MetricsRegistry};
+
+use thiserror::Error;
+
+/// Errors raised while configuring or publishing metrics.
+#[derive(Debug, Error)]
+pub enum MetricsError {
+    #[error("Unknown METRICS_MODE '{0}' (expected off, scrape, pushgateway, statsd or dogstatsd)")]
+    UnknownMode(String),
+    #[error("METRICS_PUSHGATEWAY_URL must be set when METRICS_MODE is pushgateway")]
+    MissingPushgatewayUrl,
+    #[error("Invalid metrics address '{0}'")]
+    InvalidAddress(String),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Pushgateway rejected metrics with status {0}")]
+    PushRejected(u16),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,212 @@
+//! Selecting and starting a metrics publisher from the environment.
+
+use crate::constants::{
+    DEFAULT_JOB, DEFAULT_METRICS_ADDRESS, DEFAULT_PUSH_INTERVAL_SECS, DEFAULT_STATSD_ADDRESS,
+    METRICS_ADDRESS, METRICS_JOB, METRICS_MODE, METRICS_PUSH_INTERVAL, METRICS_PUSHGATEWAY_URL,
+    METRICS_STATSD_ADDRESS, METRICS_STATSD_TAGS,
+};
+use crate::push::{MetricsEmitter, PushgatewayEmitter, StatsdEmitter, StatsdFlavor};
+use crate::{MetricsError, MetricsRegistry, serve_scrape_endpoint};
+use gsm_shared::{fetch_duration, fetch_var};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How metrics leave the container, selected with `METRICS_MODE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetricsMode {
+    /// Metrics are recorded but not published. This is the default.
+    #[default]
+    Off,
+    /// Serve `/metrics` over HTTP for a Prometheus scraper.
+    Scrape,
+    /// Push to a Prometheus Pushgateway.
+    Pushgateway,
+    /// Send to a StatsD agent.
+    Statsd,
+    /// Send to a DogStatsD agent, including `METRICS_STATSD_TAGS`.
+    DogStatsd,
+}
+
+impl FromStr for MetricsMode {
+    type Err = MetricsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "off" | "none" | "disabled" => Ok(Self::Off),
+            "scrape" | "http" | "prometheus" => Ok(Self::Scrape),
+            "pushgateway" | "push" => Ok(Self::Pushgateway),
+            "statsd" => Ok(Self::Statsd),
+            "dogstatsd" => Ok(Self::DogStatsd),
+            other => Err(MetricsError::UnknownMode(other.to_owned())),
+        }
+    }
+}
+
+/// Everything needed to start a publisher for a [`MetricsMode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsConfig {
+    pub mode: MetricsMode,
+    /// Listen address for `scrape` mode.
+    pub address: String,
+    /// Base URL of the Pushgateway for `pushgateway` mode.
+    pub pushgateway_url: Option<String>,
+    /// Job name used to group pushed metrics.
+    pub job: String,
+    /// Agent address for `statsd` and `dogstatsd` modes.
+    pub statsd_address: String,
+    /// Tags appended to every DogStatsD line, e.g. `game:palworld`.
+    pub statsd_tags: Vec<String>,
+    /// How often push-based modes publish.
+    pub push_interval: Duration,
+}
+
+impl MetricsConfig {
+    /// Reads the configuration from `METRICS_*` environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `METRICS_MODE` is not recognised.
+    pub fn from_env() -> Result<Self, MetricsError> {
+        let pushgateway_url = fetch_var(METRICS_PUSHGATEWAY_URL, "");
+        let push_interval = fetch_duration(
+            METRICS_PUSH_INTERVAL,
+            Duration::from_secs(DEFAULT_PUSH_INTERVAL_SECS),
+        )
+        .max(Duration::from_secs(1));
+
+        Ok(Self {
+            mode: fetch_var(METRICS_MODE, "off").parse()?,
+            address: fetch_var(METRICS_ADDRESS, DEFAULT_METRICS_ADDRESS),
+            pushgateway_url: (!pushgateway_url.is_empty()).then_some(pushgateway_url),
+            job: fetch_var(METRICS_JOB, DEFAULT_JOB),
+            statsd_address: fetch_var(METRICS_STATSD_ADDRESS, DEFAULT_STATSD_ADDRESS),
+            statsd_tags: fetch_var(METRICS_STATSD_TAGS, "")
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            push_interval,
+        })
+    }
+}
+
+/// Starts publishing `registry` according to the `METRICS_*` environment variables.
+///
+/// Returns the background thread's handle, or `None` when `METRICS_MODE` is `off`.
+///
+/// # Errors
+///
+/// Returns an error if the configuration is invalid or the publisher cannot be set up.
+pub fn start_metrics(
+    registry: &MetricsRegistry,
+) -> Result<Option<thread::JoinHandle<()>>, MetricsError> {
+    start_metrics_with_config(registry, &MetricsConfig::from_env()?)
+}
+
+/// Starts publishing `registry` according to an explicit configuration.
+///
+/// # Errors
+///
+/// Returns an error if the configuration is incomplete or the publisher cannot be set up.
+pub fn start_metrics_with_config(
+    registry: &MetricsRegistry,
+    config: &MetricsConfig,
+) -> Result<Option<thread::JoinHandle<()>>, MetricsError> {
+    let emitter: Box<dyn MetricsEmitter> = match config.mode {
+        MetricsMode::Off => return Ok(None),
+        MetricsMode::Scrape => {
+            return serve_scrape_endpoint(&config.address, registry).map(Some);
+        }
+        MetricsMode::Pushgateway => {
+            let url = config
+                .pushgateway_url
+                .as_deref()
+                .ok_or(MetricsError::MissingPushgatewayUrl)?;
+            info!("Pushing metrics to {url} every {:?}", config.push_interval);
+            Box::new(PushgatewayEmitter::new(url, &config.job))
+        }
+        MetricsMode::Statsd | MetricsMode::DogStatsd => {
+            let flavor = if config.mode == MetricsMode::DogStatsd {
+                StatsdFlavor::DogStatsd
+            } else {
+                StatsdFlavor::Statsd
+            };
+            info!(
+                "Sending metrics to {} every {:?}",
+                config.statsd_address, config.push_interval
+            );
+            Box::new(StatsdEmitter::new(
+                &config.statsd_address,
+                flavor,
+                config.statsd_tags.clone(),
+            )?)
+        }
+    };
+
+    Ok(Some(spawn_push_loop(
+        emitter,
+        registry.clone(),
+        config.push_interval,
+    )))
+}
+
+fn spawn_push_loop(
+    mut emitter: Box<dyn MetricsEmitter>,
+    registry: MetricsRegistry,
+    interval: Duration,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        loop {
+            if let Err(e) = emitter.emit(&registry) {
+                warn!("Failed to publish metrics: {e}");
+            }
+            thread::sleep(interval);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modes() -> Result<(), MetricsError> {
+        assert_eq!("".parse::<MetricsMode>()?, MetricsMode::Off);
+        assert_eq!("Scrape".parse::<MetricsMode>()?, MetricsMode::Scrape);
+        assert_eq!(
+            "pushgateway".parse::<MetricsMode>()?,
+            MetricsMode::Pushgateway
+        );
+        assert_eq!("statsd".parse::<MetricsMode>()?, MetricsMode::Statsd);
+        assert_eq!(
+            " DogStatsD ".parse::<MetricsMode>()?,
+            MetricsMode::DogStatsd
+        );
+        assert!(matches!(
+            "carrier-pigeon".parse::<MetricsMode>(),
+            Err(MetricsError::UnknownMode(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn pushgateway_requires_a_url() {
+        let config = MetricsConfig {
+            mode: MetricsMode::Pushgateway,
+            address: DEFAULT_METRICS_ADDRESS.to_owned(),
+            pushgateway_url: None,
+            job: DEFAULT_JOB.to_owned(),
+            statsd_address: DEFAULT_STATSD_ADDRESS.to_owned(),
+            statsd_tags: Vec::new(),
+            push_interval: Duration::from_secs(DEFAULT_PUSH_INTERVAL_SECS),
+        };
+
+        assert!(matches!(
+            start_metrics_with_config(&MetricsRegistry::default(), &config),
+            Err(MetricsError::MissingPushgatewayUrl)
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! Push-based emitters for environments where nothing can scrape the container.
+
+use crate::{MetricKind, MetricSample, MetricsError, MetricsRegistry};
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use tracing::debug;
+
+/// Publishes the contents of a registry to an external collector.
+pub trait MetricsEmitter: Send {
+    /// Sends the registry's current values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collector cannot be reached or rejects the payload.
+    fn emit(&mut self, registry: &MetricsRegistry) -> Result<(), MetricsError>;
+}
+
+/// Pushes the Prometheus text format to a Pushgateway, replacing the job's previous group.
+pub struct PushgatewayEmitter {
+    client: Client,
+    endpoint: String,
+}
+
+impl PushgatewayEmitter {
+    /// Creates an emitter that pushes to `{base_url}/metrics/job/{job}`.
+    pub fn new(base_url: &str, job: &str) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint: format!("{}/metrics/job/{job}", base_url.trim_end_matches('/')),
+        }
+    }
+}
+
+impl MetricsEmitter for PushgatewayEmitter {
+    fn emit(&mut self, registry: &MetricsRegistry) -> Result<(), MetricsError> {
+        let response = self
+            .client
+            .put(&self.endpoint)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(registry.render_prometheus())
+            .send()?;
+
+        if response.status().is_success() {
+            debug!("Pushed metrics to {}", self.endpoint);
+            Ok(())
+        } else {
+            Err(MetricsError::PushRejected(response.status().as_u16()))
+        }
+    }
+}
+
+/// Which StatsD dialect to speak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsdFlavor {
+    /// Plain StatsD; tags are dropped.
+    Statsd,
+    /// DogStatsD, which appends `|#tag:value,...` to each line.
+    DogStatsd,
+}
+
+/// Sends samples to a StatsD agent over UDP.
+///
+/// Gauges are sent as-is; counters are sent as the increase since the previous emit,
+/// which is what StatsD expects of a `|c` metric.
+pub struct StatsdEmitter {
+    socket: UdpSocket,
+    flavor: StatsdFlavor,
+    tags: Vec<String>,
+    last_counters: HashMap<String, f64>,
+}
+
+impl StatsdEmitter {
+    /// Creates an emitter that sends to `address` (for example `127.0.0.1:8125`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a local UDP socket cannot be bound or `address` cannot be resolved.
+    pub fn new(
+        address: &str,
+        flavor: StatsdFlavor,
+        tags: Vec<String>,
+    ) -> Result<Self, MetricsError> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket
+            .connect(address)
+            .map_err(|_| MetricsError::InvalidAddress(address.to_owned()))?;
+        Ok(Self {
+            socket,
+            flavor,
+            tags,
+            last_counters: HashMap::new(),
+        })
+    }
+
+    fn format_line(&mut self, sample: &MetricSample) -> Option<String> {
+        let (value, suffix) = match sample.kind {
+            MetricKind::Gauge => (sample.value, "g"),
+            MetricKind::Counter => {
+                let previous = self
+                    .last_counters
+                    .insert(sample.name.clone(), sample.value)
+                    .unwrap_or(0.0);
+                let delta = sample.value - previous;
+                if delta <= 0.0 {
+                    return None;
+                }
+                (delta, "c")
+            }
+        };
+
+        let mut line = format!("{}:{value}|{suffix}", sample.name);
+        if self.flavor == StatsdFlavor::DogStatsd && !self.tags.is_empty() {
+            line.push_str("|#");
+            line.push_str(&self.tags.join(","));
+        }
+        Some(line)
+    }
+}
+
+impl MetricsEmitter for StatsdEmitter {
+    fn emit(&mut self, registry: &MetricsRegistry) -> Result<(), MetricsError> {
+        for sample in registry.snapshot() {
+            if let Some(line) = self.format_line(&sample) {
+                self.socket.send(line.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn receive_lines(socket: &UdpSocket) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut buf = [0_u8; 512];
+        while let Ok(len) = socket.recv(&mut buf) {
+            lines.push(String::from_utf8_lossy(buf.get(..len).unwrap_or_default()).into_owned());
+        }
+        lines
+    }
+
+    #[test]
+    fn statsd_sends_gauges_and_counter_deltas() -> Result<(), Box<dyn std::error::Error>> {
+        let agent = UdpSocket::bind("127.0.0.1:0")?;
+        agent.set_read_timeout(Some(Duration::from_millis(200)))?;
+
+        let registry = MetricsRegistry::default();
+        let restarts = registry.counter("gsm_restarts_total", "Server restarts");
+        registry
+            .gauge("gsm_players_online", "Players online")
+            .set(2.0);
+        restarts.inc_by(3.0);
+
+        let mut emitter = StatsdEmitter::new(
+            &agent.local_addr()?.to_string(),
+            StatsdFlavor::DogStatsd,
+            vec!["game:palworld".to_owned()],
+        )?;
+
+        emitter.emit(&registry)?;
+        assert_eq!(
+            receive_lines(&agent),
+            vec![
+                "gsm_players_online:2|g|#game:palworld",
+                "gsm_restarts_total:3|c|#game:palworld",
+            ]
+        );
+
+        restarts.inc();
+        emitter.emit(&registry)?;
+        assert_eq!(
+            receive_lines(&agent),
+            vec![
+                "gsm_players_online:2|g|#game:palworld",
+                "gsm_restarts_total:1|c|#game:palworld",
+            ]
+        );
+
+        Ok(())
+    }
+}
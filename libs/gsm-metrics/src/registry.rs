@@ -0,0 +1,201 @@
+//! The shared metric registry.
+//!
+//! Counters and gauges are cheap handles onto a single map, so any part of a server can
+//! record a value and every emitter sees it on its next read.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Whether a metric only ever goes up or can move in both directions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    const fn prometheus_type(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+        }
+    }
+}
+
+/// A point-in-time reading of one metric.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    pub name: String,
+    pub help: String,
+    pub kind: MetricKind,
+    pub value: f64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    help: String,
+    kind: MetricKind,
+    value: f64,
+}
+
+type Entries = BTreeMap<String, Entry>;
+
+/// A thread-safe collection of named metrics.
+///
+/// Cloning a registry yields another handle onto the same metrics.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl MetricsRegistry {
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn register(&self, name: &str, help: &str, kind: MetricKind) {
+        self.lock().entry(name.to_owned()).or_insert_with(|| Entry {
+            help: help.to_owned(),
+            kind,
+            value: 0.0,
+        });
+    }
+
+    /// Registers (or looks up) a counter called `name`.
+    pub fn counter(&self, name: &str, help: &str) -> Counter {
+        self.register(name, help, MetricKind::Counter);
+        Counter {
+            registry: self.clone(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// Registers (or looks up) a gauge called `name`.
+    pub fn gauge(&self, name: &str, help: &str) -> Gauge {
+        self.register(name, help, MetricKind::Gauge);
+        Gauge {
+            registry: self.clone(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// Returns the current value of every metric, ordered by name.
+    pub fn snapshot(&self) -> Vec<MetricSample> {
+        self.lock()
+            .iter()
+            .map(|(name, entry)| MetricSample {
+                name: name.clone(),
+                help: entry.help.clone(),
+                kind: entry.kind,
+                value: entry.value,
+            })
+            .collect()
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        for sample in self.snapshot() {
+            let _ = writeln!(out, "# HELP {} {}", sample.name, sample.help);
+            let _ = writeln!(
+                out,
+                "# TYPE {} {}",
+                sample.name,
+                sample.kind.prometheus_type()
+            );
+            let _ = writeln!(out, "{} {}", sample.name, sample.value);
+        }
+        out
+    }
+}
+
+/// A handle to a monotonically increasing metric.
+#[derive(Debug, Clone)]
+pub struct Counter {
+    registry: MetricsRegistry,
+    name: String,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1.0);
+    }
+
+    /// Adds `amount` to the counter. Negative amounts are ignored.
+    pub fn inc_by(&self, amount: f64) {
+        if amount <= 0.0 {
+            return;
+        }
+        if let Some(entry) = self.registry.lock().get_mut(&self.name) {
+            entry.value += amount;
+        }
+    }
+}
+
+/// A handle to a metric that can be set to any value.
+#[derive(Debug, Clone)]
+pub struct Gauge {
+    registry: MetricsRegistry,
+    name: String,
+}
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        if let Some(entry) = self.registry.lock().get_mut(&self.name) {
+            entry.value = value;
+        }
+    }
+
+    pub fn add(&self, amount: f64) {
+        if let Some(entry) = self.registry.lock().get_mut(&self.name) {
+            entry.value += amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_share_the_same_registry() {
+        let registry = MetricsRegistry::default();
+        let restarts = registry.counter("gsm_restarts_total", "Server restarts");
+        restarts.inc();
+        registry
+            .counter("gsm_restarts_total", "Server restarts")
+            .inc_by(2.0);
+        restarts.inc_by(-5.0);
+
+        let players = registry.gauge("gsm_players_online", "Players online");
+        players.set(4.0);
+        players.add(-1.0);
+
+        let values: Vec<(String, f64)> = registry
+            .snapshot()
+            .into_iter()
+            .map(|sample| (sample.name, sample.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("gsm_players_online".to_owned(), 3.0),
+                ("gsm_restarts_total".to_owned(), 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let registry = MetricsRegistry::default();
+        registry
+            .gauge("gsm_up", "Whether the server is up")
+            .set(1.0);
+
+        assert_eq!(
+            registry.render_prometheus(),
+            "# HELP gsm_up Whether the server is up\n# TYPE gsm_up gauge\ngsm_up 1\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Cleanup of mod downloads keyed by the MD5 of their URL.
+//!
+//! Older releases kept downloaded archives in `<game>/mods_staging`, naming those without
+//! a file name in their URL `<md5 of url>.<ext>`, and later staged downloads in
+//! `<game>/.gsm-staging/mods-<md5 of url>-*`. Downloads are now keyed by SHA-256, so those
+//! entries are never reused and would otherwise stay on disk for good.
+use gsm_shared::staging_root;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Directory under the game directory where older releases kept downloaded mods.
+pub const LEGACY_STAGING_DIR: &str = "mods_staging";
+
+/// Namespace older releases used for mod staging directories.
+const LEGACY_STAGING_NAMESPACE: &str = "mods";
+
+/// Length of an MD5 checksum in hex.
+const MD5_HEX_LEN: usize = 32;
+
+/// Returns whether `key` is an MD5 checksum in hex.
+fn is_md5_key(key: &str) -> bool {
+    key.len() == MD5_HEX_LEN && key.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Returns whether `name` is an entry keyed by an MD5 checksum: a `<md5>.<ext>` download
+/// in [`LEGACY_STAGING_DIR`], or a `mods-<md5>-*` staging directory.
+fn is_md5_keyed(name: &str) -> bool {
+    if let Some(rest) = name
+        .strip_prefix(LEGACY_STAGING_NAMESPACE)
+        .and_then(|rest| rest.strip_prefix('-'))
+    {
+        return rest.split_once('-').is_some_and(|(key, _)| is_md5_key(key));
+    }
+    name.split_once('.').is_some_and(|(key, _)| is_md5_key(key))
+}
+
+/// Removes the MD5-keyed mod downloads left under `game_directory` by older releases,
+/// and [`LEGACY_STAGING_DIR`] once it is empty. Returns the paths that were removed.
+///
+/// # Errors
+///
+/// Returns an error when a directory holding legacy downloads cannot be listed.
+pub fn remove_md5_mod_cache(game_directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let legacy_dir = game_directory.join(LEGACY_STAGING_DIR);
+    let mut removed = Vec::new();
+    for dir in [legacy_dir.as_path(), &staging_root(game_directory)] {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !is_md5_keyed(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let path = entry.path();
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            match result {
+                Ok(()) => {
+                    debug!("Removed MD5-keyed mod download {:?}", path);
+                    removed.push(path);
+                }
+                Err(e) => warn!("Failed to remove MD5-keyed mod download {:?}: {e}", path),
+            }
+        }
+    }
+    // Only succeeds once nothing else is left in it.
+    if fs::remove_dir(&legacy_dir).is_ok() {
+        debug!("Removed {:?}", legacy_dir);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use gsm_shared::{get_sha256_hash, staging_dir};
+    use tempfile::tempdir;
+
+    const MD5_KEY: &str = "c3fcd3d76192e4007dfb496cca67e13b";
+
+    #[test]
+    fn md5_keyed_downloads_are_removed() {
+        let game_dir = tempdir().unwrap();
+        let legacy_dir = game_dir.path().join(LEGACY_STAGING_DIR);
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join(format!("{MD5_KEY}.zip")), "mod").unwrap();
+        let legacy_staging = staging_dir(game_dir.path(), "mods", MD5_KEY)
+            .unwrap()
+            .keep();
+        let current_staging = staging_dir(game_dir.path(), "mods", &get_sha256_hash("url"))
+            .unwrap()
+            .keep();
+
+        let removed = remove_md5_mod_cache(game_dir.path()).unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert!(!legacy_dir.exists());
+        assert!(!legacy_staging.exists());
+        assert!(current_staging.exists());
+    }
+
+    #[test]
+    fn downloads_named_by_their_url_are_kept() {
+        let game_dir = tempdir().unwrap();
+        let legacy_dir = game_dir.path().join(LEGACY_STAGING_DIR);
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join("BepInExPack.zip"), "mod").unwrap();
+
+        assert!(remove_md5_mod_cache(game_dir.path()).unwrap().is_empty());
+        assert!(legacy_dir.join("BepInExPack.zip").exists());
+    }
+}
@@ -1,6 +1,9 @@
 mod errors;
 pub use errors::*;
 
+mod legacy_cache;
+pub use legacy_cache::*;
+
 mod managed_mod;
 pub use managed_mod::ManagedMod;
 
@@ -1,7 +1,9 @@
 use crate::constants::SUPPORTED_FILE_TYPES;
 use crate::errors::ModError;
+use crate::legacy_cache::remove_md5_mod_cache;
 use gsm_shared::{
-    get_md5_hash, is_valid_url, normalize_paths, parse_file_name, url_parse_file_type,
+    content_disposition_file_name, file_type_from_headers, get_sha256_hash, http, is_valid_url,
+    normalize_paths, parse_file_name, staging_dir, url_parse_file_type,
 };
 
 use crate::parse_mod_string::parse_mod_string;
@@ -9,17 +11,28 @@ use fs_extra::dir;
 use fs_extra::dir::CopyOptions;
 use reqwest::Url;
 use std::convert::TryFrom;
-use std::fs::{File, create_dir_all};
+use std::fs::{self, File, create_dir_all};
 use std::path::{Path, PathBuf};
-use tempfile::tempdir;
-use tracing::{debug, error};
+use tempfile::TempDir;
+use tracing::{debug, error, warn};
 use walkdir::WalkDir;
 use zip::ZipArchive;
 
+/// Namespace of mod staging directories when the game directory has no name.
+const DEFAULT_STAGING_NAMESPACE: &str = "instance";
+
+/// Length of the URL hash that identifies a mod's operations in staging directory names.
+const OPERATION_ID_LEN: usize = 12;
+
+/// Name of a download in its staging directory until it is renamed after the response.
+const DOWNLOAD_FILE_NAME: &str = "download";
+
 pub struct ManagedMod {
     pub(crate) url: String,
     pub(crate) file_type: String,
     pub(crate) staging_location: PathBuf,
+    /// Per-download staging directory; removed once the mod is installed or dropped.
+    pub(crate) staging: Option<TempDir>,
     pub(crate) installed: bool,
     pub(crate) downloaded: bool,
     pub(crate) game_directory: PathBuf,
@@ -28,11 +41,12 @@ pub struct ManagedMod {
 
 impl ManagedMod {
     pub fn new(url: &str, game_directory: PathBuf, plugin_directory: PathBuf) -> Self {
-        let file_type = url_parse_file_type(url);
+        let file_type = url_parse_file_type(url).unwrap_or_default();
         Self {
             url: url.to_owned(),
             file_type,
-            staging_location: game_directory.join("mods_staging"),
+            staging_location: PathBuf::new(),
+            staging: None,
             installed: false,
             downloaded: false,
             game_directory,
@@ -40,6 +54,24 @@ impl ManagedMod {
         }
     }
 
+    /// Creates a staging directory for `step` of this mod's installation under the game
+    /// directory, named after the instance (the game directory's name) and an operation
+    /// id derived from the mod's URL, so staging left by one instance or mod is never
+    /// mistaken for another's.
+    fn staging_dir(&self, step: &str) -> std::io::Result<TempDir> {
+        let namespace = self.game_directory.file_name().map_or_else(
+            || DEFAULT_STAGING_NAMESPACE.to_owned(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let mut operation_id = get_sha256_hash(&self.url);
+        operation_id.truncate(OPERATION_ID_LEN);
+        staging_dir(
+            &self.game_directory,
+            &namespace,
+            &format!("mod-{operation_id}-{step}"),
+        )
+    }
+
     /// Checks if the extracted mod is a BepInEx framework mod.
     fn is_bepinex(extract_path: &Path) -> bool {
         debug!("Checking if mod is BepInEx framework...");
@@ -52,7 +84,12 @@ impl ManagedMod {
         false
     }
 
-    /// Downloads the configured mod archive into the staging location.
+    /// Downloads the configured mod archive into a fresh staging directory.
+    ///
+    /// Each download gets its own directory under `<game>/.gsm-staging` (see
+    /// [`staging_dir`]), so concurrent downloads never collide; it is cleaned up after
+    /// install or when the mod is dropped. Downloads left behind by older releases, which
+    /// were keyed by MD5, are removed first; see [`remove_md5_mod_cache`].
     ///
     /// # Errors
     ///
@@ -60,34 +97,37 @@ impl ManagedMod {
     /// creation/writes cannot be completed.
     pub fn download(&mut self) -> Result<(), ModError> {
         debug!("Initializing mod download...");
-        if !self.staging_location.exists() {
-            create_dir_all(&self.staging_location)
-                .map_err(|e| ModError::DirectoryCreationError(e.to_string()))?;
+        if let Err(e) = remove_md5_mod_cache(&self.game_directory) {
+            warn!("Failed to remove MD5-keyed mod downloads: {e}");
         }
+        let staging = self
+            .staging_dir("download")
+            .map_err(|e| ModError::DirectoryCreationError(e.to_string()))?;
 
         let parsed_url = Url::parse(&self.url).map_err(|_| ModError::InvalidUrl)?;
-        let mut response = reqwest::blocking::get(parsed_url)
+        // The file is named once the response shows what it is.
+        let download_path = staging.path().join(DOWNLOAD_FILE_NAME);
+        debug!("Downloading to: {:?}", download_path);
+        let download = http::download_to(parsed_url.as_str(), &download_path, None, |_| {})
             .map_err(|e| ModError::DownloadError(e.to_string()))?;
 
         if !SUPPORTED_FILE_TYPES.contains(&self.file_type.as_str()) {
             debug!("Updating redirect URL: {}", &self.url);
-            self.url = response.url().to_string();
-            self.file_type = url_parse_file_type(response.url().as_ref());
+            self.url = download.url.to_string();
+            // Download links such as `.../download?id=3` name the file in their headers.
+            self.file_type = url_parse_file_type(download.url.as_str())
+                .or_else(|| file_type_from_headers(&download.headers))
+                .unwrap_or_default();
         }
 
         let final_url = Url::parse(&self.url).map_err(|_| ModError::InvalidUrl)?;
-        let file_name = parse_file_name(
-            &final_url,
-            &format!("{}.{}", get_md5_hash(&self.url), self.file_type),
-        );
-        self.staging_location = self.staging_location.join(file_name);
-        debug!("Downloading to: {:?}", self.staging_location);
-
-        let mut file = File::create(&self.staging_location)
+        let file_name = content_disposition_file_name(&download.headers)
+            .or_else(|| parse_file_name(&final_url).filter(|name| name.contains('.')))
+            .unwrap_or_else(|| format!("{}.{}", get_sha256_hash(&self.url), self.file_type));
+        self.staging_location = staging.path().join(file_name);
+        fs::rename(&download_path, &self.staging_location)
             .map_err(|e| ModError::FileCreateError(e.to_string()))?;
-        response
-            .copy_to(&mut file)
-            .map_err(|e| ModError::DownloadError(e.to_string()))?;
+        self.staging = Some(staging);
         self.downloaded = true;
         debug!("Download complete: {}", &self.url);
         Ok(())
@@ -105,7 +145,9 @@ impl ManagedMod {
             return Err(ModError::InvalidStagingLocation);
         }
 
-        let temp_dir = tempdir().map_err(|e| ModError::TempDirCreationError(e.to_string()))?;
+        let temp_dir = self
+            .staging_dir("extract")
+            .map_err(|e| ModError::TempDirCreationError(e.to_string()))?;
         debug!("Created temp directory: {:?}", temp_dir.path());
 
         {
@@ -116,7 +158,7 @@ impl ManagedMod {
             archive
                 .extract(temp_dir.path())
                 .map_err(|e| ModError::ExtractionError(e.to_string()))?;
-            normalize_paths(temp_dir.path())
+            normalize_paths(temp_dir.path(), &self.game_directory)
                 .map_err(|e| ModError::ExtractionError(e.to_string()))?;
         }
 
@@ -141,6 +183,8 @@ impl ManagedMod {
             .map_err(|e| ModError::FileMoveError(e.to_string()))?;
 
         self.installed = true;
+        // Release the downloaded archive now that its contents are in place.
+        self.staging = None;
         Ok(())
     }
 }
@@ -209,6 +253,7 @@ mod tests {
             url: "http://example.com/dummy.zip".to_owned(),
             file_type: "zip".to_owned(),
             staging_location: staging_file,
+            staging: None,
             installed: false,
             downloaded: true,
             game_directory: game_dir.path().to_path_buf(),
@@ -246,6 +291,7 @@ mod tests {
             url: "http://example.com/bepinex_dummy.zip".to_owned(),
             file_type: "zip".to_owned(),
             staging_location: staging_file,
+            staging: None,
             installed: false,
             downloaded: true,
             game_directory: game_dir.path().to_path_buf(),
@@ -272,9 +318,62 @@ mod tests {
         assert_eq!(mod_instance.url, "http://example.com/mod.zip");
     }
 
+    #[test]
+    fn test_new_ignores_query_strings_in_the_file_type() {
+        let mod_instance = ManagedMod::new(
+            "https://example.com/files/mod.zip?token=abc.def",
+            PathBuf::new(),
+            PathBuf::new(),
+        );
+        assert_eq!(mod_instance.file_type, "zip");
+
+        let mod_instance = ManagedMod::new(
+            "https://example.com/download?id=3",
+            PathBuf::new(),
+            PathBuf::new(),
+        );
+        assert_eq!(mod_instance.file_type, "");
+    }
+
     #[test]
     fn test_try_from_invalid_url() {
         let result = ManagedMod::try_from("invalid_url".to_owned());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_install_cleans_up_extraction_staging() {
+        let root = tempdir().unwrap();
+        let game_dir = root.path().join("valheim");
+        let plugin_dir = tempdir().unwrap();
+        let mut mod_instance = ManagedMod {
+            url: "http://example.com/dummy.zip".to_owned(),
+            file_type: "zip".to_owned(),
+            staging_location: PathBuf::new(),
+            staging: None,
+            installed: false,
+            downloaded: true,
+            game_directory: game_dir.clone(),
+            plugin_directory: plugin_dir.path().to_path_buf(),
+        };
+        let staging = mod_instance.staging_dir("download").unwrap();
+        let staging_name = staging.path().file_name().unwrap().to_string_lossy();
+        let operation_id = &get_sha256_hash(&mod_instance.url)[..OPERATION_ID_LEN];
+        assert!(staging_name.starts_with(&format!("valheim-mod-{operation_id}-download-")));
+        let staging_file = staging.path().join("dummy.zip");
+        create_dummy_zip(&staging_file, false).unwrap();
+        let staging_path = staging.path().to_path_buf();
+        mod_instance.staging_location = staging_file;
+        mod_instance.staging = Some(staging);
+
+        mod_instance.install().unwrap();
+
+        assert!(!staging_path.exists());
+        let leftovers = fs::read_dir(game_dir.join(gsm_shared::STAGING_DIR_NAME))
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != gsm_shared::STAGING_MARKER)
+            .count();
+        assert_eq!(leftovers, 0);
+    }
 }
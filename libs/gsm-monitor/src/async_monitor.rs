@@ -0,0 +1,291 @@
+//! This module provides an async variant of the log file monitor, built on Tokio.
+//!
+//! An [`AsyncMonitor`] follows log files in Tokio tasks instead of dedicated threads and yields each line as a
+//! [`LogLine`] from a [`LogStream`], so an async binary can handle log events next to its other work with
+//! `tokio::select!`. Every line is passed through the monitor's rules first, as [`Monitor`] does; files that do
+//! not exist yet are waited for and rotated files re-attached the same way. Dropping the stream stops the tasks.
+//!
+//! ```rust,no_run
+//! use gsm_monitor::{AsyncMonitor, LogRules};
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! # async fn example() {
+//! let mut lines = AsyncMonitor::new(LogRules::default()).watch_instance(Path::new("/home/steam/palworld"));
+//! let shutdown = tokio::time::sleep(Duration::from_secs(3600));
+//! tokio::pin!(shutdown);
+//! loop {
+//!     tokio::select! {
+//!         Some(line) = lines.next() => println!("{}: {}", line.path.display(), line.line),
+//!         () = &mut shutdown => break,
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::constants::INSTANCE_TARGET;
+use crate::monitor::{INITIAL_BACKOFF, MAX_BACKOFF, Monitor, POLL_INTERVAL};
+use crate::rules::LogRules;
+use futures_core::Stream;
+use std::io::{self, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio::time::sleep;
+use tracing::{error, info, trace};
+
+/// How many lines a [`LogStream`] holds before its tasks wait for them to be consumed.
+const STREAM_CAPACITY: usize = 256;
+
+/// A line read from a log file, without its line ending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    /// The log file the line was read from.
+    pub path: PathBuf,
+    /// The line itself.
+    pub line: String,
+}
+
+/// Follows log files in Tokio tasks, applying its rules to each line and yielding it.
+#[derive(Clone)]
+pub struct AsyncMonitor {
+    monitor: Monitor,
+}
+
+impl AsyncMonitor {
+    /// Creates a new `AsyncMonitor` with the specified log rules.
+    pub fn new(rules: LogRules) -> Self {
+        trace!("Creating a new AsyncMonitor instance");
+        Self {
+            monitor: Monitor::new(rules),
+        }
+    }
+
+    /// Follows the log file at `path`, yielding each line appended to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    pub fn watch(&self, path: impl Into<PathBuf>) -> LogStream {
+        self.stream([path.into()])
+    }
+
+    /// Follows the `server.log` and `server.err` of the instance in `working_dir`, like
+    /// [`start_instance_log_monitor`](crate::start_instance_log_monitor) does.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    pub fn watch_instance(&self, working_dir: &Path) -> LogStream {
+        let log_dir = working_dir.join("logs");
+        self.stream([log_dir.join("server.log"), log_dir.join("server.err")])
+    }
+
+    fn stream(&self, paths: impl IntoIterator<Item = PathBuf>) -> LogStream {
+        let (sender, receiver) = mpsc::channel(STREAM_CAPACITY);
+        for path in paths {
+            tokio::spawn(follow(self.monitor.clone(), path, sender.clone()));
+        }
+        LogStream { receiver }
+    }
+}
+
+/// The lines of the log files an [`AsyncMonitor`] follows, in the order they were read.
+/// Dropping it stops following them.
+#[derive(Debug)]
+pub struct LogStream {
+    receiver: mpsc::Receiver<LogLine>,
+}
+
+impl LogStream {
+    /// Waits for the next line. Returns `None` once every followed file was given up on.
+    pub async fn next(&mut self) -> Option<LogLine> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for LogStream {
+    type Item = LogLine;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<LogLine>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Follows the log file at `path` until `sender`'s stream is dropped.
+async fn follow(monitor: Monitor, path: PathBuf, sender: mpsc::Sender<LogLine>) {
+    info!(target: INSTANCE_TARGET, "Starting async watch on {}", path.display());
+
+    let mut reader = match File::open(&path).await {
+        Ok(mut file) => {
+            if let Err(e) = file.seek(SeekFrom::End(0)).await {
+                error!("Failed to seek to end of {}: {}", path.display(), e);
+                return;
+            }
+            BufReader::new(file)
+        }
+        Err(_) => match wait_for_file(&path, &sender).await {
+            Some(file) => BufReader::new(file),
+            None => return,
+        },
+    };
+
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) => {
+                if sender.is_closed() {
+                    break;
+                }
+                if is_replaced(&mut reader, &path).await {
+                    info!(target: INSTANCE_TARGET,
+                        "Log file {} was truncated/rotated. Re-opening.",
+                        path.display()
+                    );
+                    match wait_for_file(&path, &sender).await {
+                        Some(file) => reader = BufReader::new(file),
+                        None => break,
+                    }
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+            Ok(_) => {
+                let line = line.trim_end().to_owned();
+                trace!("Read line from file: {line}");
+                // Rule actions may block, e.g. to send a webhook notification.
+                let monitor = monitor.clone();
+                let processed = line.clone();
+                let _ = task::spawn_blocking(move || monitor.process_rules(&processed)).await;
+                let line = LogLine {
+                    path: path.clone(),
+                    line,
+                };
+                if sender.send(line).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                error!("Error reading from {}: {}", path.display(), e);
+                sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+    info!(target: INSTANCE_TARGET, "Stopped async watch on {}", path.display());
+}
+
+/// Returns whether the file `reader` reads was truncated below its position, or is no
+/// longer the one at `path` because it was removed or replaced.
+async fn is_replaced(reader: &mut BufReader<File>, path: &Path) -> bool {
+    let Ok(open) = reader.get_ref().metadata().await else {
+        return false;
+    };
+    if reader
+        .stream_position()
+        .await
+        .is_ok_and(|position| open.len() < position)
+    {
+        return true;
+    }
+    !fs::metadata(path)
+        .await
+        .is_ok_and(|current| current.dev() == open.dev() && current.ino() == open.ino())
+}
+
+/// Opens the file at `path`, waiting with a growing backoff until it exists. Returns
+/// `None` when `sender`'s stream is dropped first.
+async fn wait_for_file(path: &Path, sender: &mpsc::Sender<LogLine>) -> Option<File> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut waiting = false;
+    while !sender.is_closed() {
+        match File::open(path).await {
+            Ok(file) => {
+                if waiting {
+                    info!(target: INSTANCE_TARGET, "Log file {} appeared", path.display());
+                }
+                return Some(file);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if !waiting {
+                    info!(target: INSTANCE_TARGET,
+                        "Waiting for log file {} to be created",
+                        path.display()
+                    );
+                    waiting = true;
+                }
+            }
+            Err(e) => error!("Failed to open log file {}: {}", path.display(), e),
+        }
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn lines_are_yielded_after_the_rules_run() {
+        let temp = tempdir().unwrap();
+        let log_dir = temp.path().join("logs");
+        let hit = Arc::new(AtomicBool::new(false));
+        let rules = LogRules::new();
+        {
+            let hit = Arc::clone(&hit);
+            rules.add_rule(
+                |line| line.contains("SENTINEL"),
+                move |_| hit.store(true, Ordering::SeqCst),
+                true,
+                None,
+            );
+        }
+
+        // The logs do not exist yet, as before a fresh install's first start.
+        let mut lines = AsyncMonitor::new(rules).watch_instance(temp.path());
+        sleep(Duration::from_millis(50)).await;
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(log_dir.join("server.err"), "line with SENTINEL\r\n").unwrap();
+
+        let line = timeout(Duration::from_secs(5), lines.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            line,
+            LogLine {
+                path: log_dir.join("server.err"),
+                line: "line with SENTINEL".to_owned(),
+            }
+        );
+        assert!(hit.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn dropping_the_stream_stops_following() {
+        let temp = tempdir().unwrap();
+        let log = temp.path().join("server.log");
+        std::fs::write(&log, "").unwrap();
+
+        let (sender, receiver) = mpsc::channel(1);
+        let task = tokio::spawn(follow(Monitor::new(LogRules::new()), log, sender));
+        sleep(Duration::from_millis(50)).await;
+        drop(LogStream { receiver });
+        timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
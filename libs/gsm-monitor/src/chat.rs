@@ -0,0 +1,123 @@
+//! This module provides rules that recognize in-game chat and relay it, e.g. to a Discord webhook.
+//!
+//! A chat pattern is a regular expression with a `player` and a `message` named group. Rules added with
+//! [`LogRules::add_chat_rule`](crate::LogRules::add_chat_rule) hand each chat line they match to a callback as a
+//! [`ChatMessage`]. [`PALWORLD_CHAT_PATTERN`] matches the chat lines Palworld logs.
+//!
+//! ```rust
+//! use gsm_monitor::{LogRules, PALWORLD_CHAT_PATTERN};
+//!
+//! let rules = LogRules::default();
+//! rules
+//!     .add_chat_rule(PALWORLD_CHAT_PATTERN, |chat| {
+//!         println!("{}: {}", chat.player, chat.message);
+//!     })
+//!     .expect("valid chat pattern");
+//! ```
+
+use crate::rules::LogRules;
+use regex::Regex;
+
+/// Matches Palworld's chat lines, e.g. `[2024-01-01 00:00:00] [CHAT] <mbround18> hello`.
+pub const PALWORLD_CHAT_PATTERN: &str = r"\[CHAT\]\s+<(?P<player>[^>]+)>\s*(?P<message>.*)";
+
+/// A chat message sent in game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    /// The name of the player who sent the message.
+    pub player: String,
+    /// What they said.
+    pub message: String,
+}
+
+impl LogRules {
+    /// Adds a rule passing each chat line `pattern` matches to `handler`. Chat lines are
+    /// not handled by later rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `pattern` is not a valid regular expression or lacks a
+    /// `player` or `message` named group.
+    pub fn add_chat_rule<H>(&self, pattern: &str, handler: H) -> Result<(), regex::Error>
+    where
+        H: Fn(ChatMessage) + Send + Sync + 'static,
+    {
+        let regex = Regex::new(pattern)?;
+        for group in ["player", "message"] {
+            if !regex.capture_names().flatten().any(|name| name == group) {
+                return Err(regex::Error::Syntax(format!(
+                    "chat pattern '{pattern}' has no '{group}' group; name it with (?P<{group}>...)"
+                )));
+            }
+        }
+        self.add_regex_rule(
+            pattern,
+            move |captures| {
+                if let (Some(player), Some(message)) =
+                    (captures.name("player"), captures.name("message"))
+                {
+                    handler(ChatMessage {
+                        player: player.as_str().to_owned(),
+                        message: message.as_str().trim_end().to_owned(),
+                    });
+                }
+            },
+            true,
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    #[test]
+    fn chat_lines_are_relayed_to_the_handler() {
+        let rules = LogRules::new();
+        let relayed = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&relayed);
+        rules
+            .add_chat_rule(PALWORLD_CHAT_PATTERN, move |chat| {
+                recorder
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(chat);
+            })
+            .unwrap();
+
+        for line in [
+            "[2024-01-01 00:00:00] [CHAT] <Cool Player_1> anyone seen my pal?",
+            "[2024-01-01 00:00:01] [LOG] mbround18 joined the server.",
+        ] {
+            if let Some(rule) = rules.get_rules().first()
+                && (rule.matcher)(line)
+            {
+                (rule.action)(line);
+            }
+        }
+        assert_eq!(
+            *relayed.lock().unwrap_or_else(PoisonError::into_inner),
+            [ChatMessage {
+                player: "Cool Player_1".to_owned(),
+                message: "anyone seen my pal?".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn chat_patterns_need_player_and_message_groups() {
+        let rules = LogRules::new();
+        assert!(rules.add_chat_rule(r"\[CHAT\] (.*)", |_| {}).is_err());
+        assert!(
+            rules
+                .add_chat_rule(r"(?P<player>\w+): (.*)", |_| {})
+                .is_err()
+        );
+        assert!(rules.add_chat_rule("(", |_| {}).is_err());
+        assert_eq!(rules.get_rules().len(), 1);
+    }
+}
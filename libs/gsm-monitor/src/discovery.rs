@@ -0,0 +1,175 @@
+//! This module provides monitoring for every log file matching a glob pattern.
+//!
+//! Not every server logs to `logs/server.log`: Palworld, for example, writes its own logs to
+//! `Pal/Saved/Logs/*.log`. A glob monitor watches each file matching its pattern and keeps looking for new ones,
+//! attaching a monitor to each as it appears. A file renamed by log rotation to a name that also matches is
+//! recognized as one already watched and not read a second time.
+
+use crate::constants::INSTANCE_TARGET;
+use crate::monitor::{Monitor, MonitorHandle, sleep_unless_stopped, spawn_thread};
+use crate::rules::LogRules;
+use glob::{Pattern, PatternError, glob};
+use std::collections::HashSet;
+use std::fs;
+use std::io::SeekFrom;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// How often a glob monitor looks for new files matching its pattern.
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watches every file in `dir` matching the glob `pattern`, such as `Pal/Saved/Logs/*.log`,
+/// with `rules`, until the returned handle stops them.
+///
+/// Files that match when monitoring starts are followed from their end, like
+/// [`start_monitor_in_thread`](crate::start_monitor_in_thread) does; files that appear later
+/// are read from their start.
+///
+/// # Errors
+///
+/// Returns an error when `pattern` is not a valid glob pattern.
+pub fn start_glob_monitor(
+    dir: &Path,
+    pattern: &str,
+    rules: LogRules,
+) -> Result<MonitorHandle, PatternError> {
+    let pattern = dir.join(pattern).to_string_lossy().into_owned();
+    Pattern::new(&pattern)?;
+    info!(target: INSTANCE_TARGET, "Starting log monitor for files matching: {pattern}");
+
+    let mut handle = MonitorHandle::default();
+    let stop = Arc::clone(&handle.stop);
+    let name = format!("log-discovery-{pattern}");
+    handle.threads.extend(spawn_thread(name, move || {
+        discover(&pattern, &rules, &stop);
+    }));
+    Ok(handle)
+}
+
+/// Attaches a monitor to each file matching `pattern`, as it appears, until `stop` is set,
+/// then waits for the monitors to finish.
+fn discover(pattern: &str, rules: &LogRules, stop: &Arc<AtomicBool>) {
+    let monitor = Monitor::new(rules.clone());
+    let mut paths = HashSet::new();
+    let mut files = HashSet::new();
+    let mut threads = Vec::new();
+    let mut start = SeekFrom::End(0);
+
+    loop {
+        for path in matching_files(pattern) {
+            if !paths.insert(path.clone()) {
+                continue;
+            }
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if !files.insert((metadata.dev(), metadata.ino())) {
+                debug!("{} is a watched log file renamed", path.display());
+                continue;
+            }
+
+            let monitor = monitor.clone();
+            let stop = Arc::clone(stop);
+            let name = format!("log-monitor-{}", path.display());
+            threads.extend(spawn_thread(name, move || {
+                monitor.watch(&path, &stop, start);
+            }));
+        }
+        start = SeekFrom::Start(0);
+
+        if !sleep_unless_stopped(DISCOVERY_INTERVAL, stop) {
+            break;
+        }
+    }
+
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+/// Returns the files matching `pattern`.
+fn matching_files(pattern: &str) -> Vec<PathBuf> {
+    glob(pattern).map_or_else(
+        |_| Vec::new(),
+        |paths| {
+            paths
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file())
+                .collect()
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::io::Write;
+    use std::sync::PoisonError;
+    use std::sync::RwLock;
+    use std::thread;
+    use tempfile::tempdir;
+
+    fn recording_rules(lines: &Arc<RwLock<Vec<String>>>) -> LogRules {
+        let lines = Arc::clone(lines);
+        let rules = LogRules::new();
+        rules.add_rule(
+            |line| line.starts_with("event"),
+            move |line| {
+                lines
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(line.to_owned());
+            },
+            true,
+            None,
+        );
+        rules
+    }
+
+    #[test]
+    fn files_matching_the_pattern_are_monitored_as_they_appear() {
+        let temp = tempdir().unwrap();
+        let logs = temp.path().join("Pal/Saved/Logs");
+        fs::create_dir_all(&logs).unwrap();
+        fs::write(logs.join("Pal.log"), "event before the monitor\n").unwrap();
+        fs::write(logs.join("notes.txt"), "").unwrap();
+        let lines = Arc::new(RwLock::new(Vec::new()));
+
+        let monitor =
+            start_glob_monitor(temp.path(), "Pal/Saved/Logs/*.log", recording_rules(&lines))
+                .unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let append = |name: &str, line: &str| {
+            let mut file = fs::OpenOptions::new()
+                .append(true)
+                .open(logs.join(name))
+                .unwrap();
+            writeln!(file, "{line}").unwrap();
+        };
+        append("Pal.log", "event one");
+        append("notes.txt", "event ignored");
+        fs::write(logs.join("Pal_2.log"), "event two\n").unwrap();
+        thread::sleep(Duration::from_millis(2_500));
+
+        // Rotation renames the watched file to a matching name, which is not re-read.
+        fs::rename(logs.join("Pal_2.log"), logs.join("Pal_2-backup.log")).unwrap();
+        thread::sleep(Duration::from_millis(2_500));
+        assert!(monitor.stop_and_join());
+
+        let mut lines = lines.read().unwrap_or_else(PoisonError::into_inner).clone();
+        lines.sort();
+        assert_eq!(lines, ["event one", "event two"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let temp = tempdir().unwrap();
+        assert!(start_glob_monitor(temp.path(), "logs/[*.log", LogRules::new()).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! This module provides structured log events for servers that write JSON log lines.
+//!
+//! Some servers, such as Palworld with `LogFormatType=Json`, write each log line as a JSON object. A [`LogEvent`]
+//! is such a line parsed into its level, message and remaining fields, so rules added with
+//! [`LogRules::add_event_rule`](crate::LogRules::add_event_rule) can match on them instead of searching the raw
+//! text. Lines that are not JSON objects become events with the whole line as their message.
+
+use serde_json::{Map, Value};
+
+/// The keys a JSON log line may hold its level under, in order of preference.
+const LEVEL_KEYS: [&str; 4] = ["level", "Level", "severity", "Severity"];
+
+/// The keys a JSON log line may hold its message under, in order of preference.
+const MESSAGE_KEYS: [&str; 4] = ["message", "Message", "msg", "log"];
+
+/// A log line, parsed into its parts when it is a JSON object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// The line's level, such as `Warning`, when it is JSON and has one.
+    pub level: Option<String>,
+    /// The line's message, or the whole line when it is not JSON.
+    pub message: String,
+    /// The line's other fields, empty when it is not JSON.
+    pub fields: Map<String, Value>,
+    /// Whether the line was parsed as JSON.
+    pub structured: bool,
+}
+
+impl LogEvent {
+    /// Parses `line` as a JSON object, falling back to a plain event holding the raw text.
+    pub fn parse(line: &str) -> Self {
+        let trimmed = line.trim();
+        if trimmed.starts_with('{')
+            && let Ok(Value::Object(mut fields)) = serde_json::from_str(trimmed)
+        {
+            let level = take_first(&mut fields, &LEVEL_KEYS).map(into_text);
+            let message = take_first(&mut fields, &MESSAGE_KEYS)
+                .map(into_text)
+                .unwrap_or_default();
+            return Self {
+                level,
+                message,
+                fields,
+                structured: true,
+            };
+        }
+        Self {
+            level: None,
+            message: line.to_owned(),
+            fields: Map::new(),
+            structured: false,
+        }
+    }
+
+    /// Returns whether the level is `level`, ignoring case.
+    pub fn is_level(&self, level: &str) -> bool {
+        self.level
+            .as_deref()
+            .is_some_and(|own| own.eq_ignore_ascii_case(level))
+    }
+
+    /// Returns the field `key` as text, when it is a string, number or boolean.
+    pub fn field(&self, key: &str) -> Option<String> {
+        match self.fields.get(key)? {
+            Value::String(text) => Some(text.clone()),
+            value @ (Value::Number(_) | Value::Bool(_)) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Removes and returns the value of the first of `keys` that `fields` holds.
+fn take_first(fields: &mut Map<String, Value>, keys: &[&str]) -> Option<Value> {
+    keys.iter().find_map(|key| fields.remove(*key))
+}
+
+/// Returns `value` as text, without the quotes of a JSON string.
+fn into_text(value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines_are_split_into_level_message_and_fields() {
+        let event = LogEvent::parse(
+            r#"{"level":"Warning","message":"mbround18 joined the server","userid":"steam_1","port":8211}"#,
+        );
+        assert!(event.structured);
+        assert!(event.is_level("warning"));
+        assert_eq!(event.message, "mbround18 joined the server");
+        assert_eq!(event.field("userid").as_deref(), Some("steam_1"));
+        assert_eq!(event.field("port").as_deref(), Some("8211"));
+        assert_eq!(event.field("missing"), None);
+        assert_eq!(event.fields.len(), 2);
+    }
+
+    #[test]
+    fn other_lines_fall_back_to_raw_text() {
+        for line in ["[LOG] mbround18 joined the server", "{not json", "[1, 2]"] {
+            let event = LogEvent::parse(line);
+            assert!(!event.structured);
+            assert_eq!(event.level, None);
+            assert_eq!(event.message, line);
+            assert!(event.fields.is_empty());
+        }
+    }
+}
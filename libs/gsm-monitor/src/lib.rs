@@ -1,6 +1,25 @@
+mod async_monitor;
+mod chat;
 mod constants;
+mod discovery;
+mod event;
 mod monitor;
+mod presets;
 mod rules;
+mod sinks;
+mod stats;
+mod traffic;
+mod watchdog;
 
-pub use monitor::{Monitor, start_instance_log_monitor, start_monitor_in_thread};
+pub use async_monitor::{AsyncMonitor, LogLine, LogStream};
+pub use chat::{ChatMessage, PALWORLD_CHAT_PATTERN};
+pub use discovery::start_glob_monitor;
+pub use event::LogEvent;
+pub use monitor::{Monitor, MonitorHandle, start_instance_log_monitor, start_monitor_in_thread};
+pub use presets::GameEvent;
+pub use regex::Captures;
 pub use rules::{LogRule, LogRules};
+pub use sinks::{LogSink, SYSLOG_SOCKET, Severity};
+pub use stats::{LogMetrics, MonitorStats};
+pub use traffic::{DEFAULT_UDP_ACTIVITY_THRESHOLD, TrafficSample, TrafficSampler};
+pub use watchdog::MonitorWatchdog;
@@ -1,45 +1,75 @@
 //! This module provides functionality for monitoring log files.
 //!
 //! The monitor continuously reads from a log file and processes each new line using the log rules
-//! defined in the `rules` module. It also detects if the file has been truncated or rotated and reopens it accordingly.
+//! defined in the `rules` module. It also detects if the file has been truncated or rotated and reopens it accordingly,
+//! and waits for a file that does not exist yet, such as the log of a server that has not started, to be created.
+//!
+//! Monitors started in their own thread return a [`MonitorHandle`], which stops them, e.g. on shutdown or before
+//! monitoring a different working directory, and waits for their threads to finish.
 
-use crate::LogRule;
 use crate::constants::INSTANCE_TARGET;
 use crate::rules::LogRules;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use crate::watchdog::Activity;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use tracing::{debug, error, info, trace};
 
+/// How often an attached log file is checked for new lines.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait before checking again for a log file that does not exist yet.
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The longest wait between checks for a log file that does not exist yet.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
 /// Represents a monitor that continuously reads a log file and processes its lines using provided rules.
 #[derive(Clone)]
 pub struct Monitor {
     rules: LogRules,
+    activity: Option<Arc<Activity>>,
 }
 
 impl Monitor {
     /// Creates a new `Monitor` instance with the specified log rules.
     pub fn new(rules: LogRules) -> Self {
         trace!("Creating a new Monitor instance");
-        Self { rules }
+        Self {
+            rules,
+            activity: None,
+        }
     }
 
-    fn process_rules(&self, line: &str) {
-        trace!("Processing rules for line: {line}");
-        let mut rules = self.rules.get_rules();
-
-        trace!("Sorting rules by ranking");
-        rules.sort_by_key(|rule| rule.ranking);
+    /// Returns a copy of the monitor that records each line it reads in `activity`.
+    pub(crate) fn with_activity(&self, activity: Arc<Activity>) -> Self {
+        Self {
+            rules: self.rules.clone(),
+            activity: Some(activity),
+        }
+    }
 
-        let filtered_rules: Vec<&LogRule> =
-            rules.iter().filter(|rule| (rule.matcher)(line)).collect();
+    pub(crate) fn process_rules(&self, line: &str) {
+        trace!("Processing rules for line: {line}");
+        let rules = self.rules.snapshot();
 
-        trace!("Filtered rules count: {}", filtered_rules.len());
-        for rule in filtered_rules {
+        for rule in rules.iter().filter(|rule| (rule.matcher)(line)) {
             trace!("Applying rule action for line");
-            (rule.action)(line);
+            // A panicking action must not end monitoring of the file, nor skip later rules.
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| (rule.action)(line))) {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| (*message).to_owned())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_owned());
+                error!("Log rule {} panicked on a line: {message}", rule.id);
+            }
 
             if rule.stop {
                 break;
@@ -47,84 +77,223 @@ impl Monitor {
         }
     }
 
+    /// Watches the log file at `path` forever, applying the rules to each line appended to
+    /// it.
+    ///
+    /// A file that does not exist yet, as on a fresh install before the server first
+    /// starts, is waited for, and read from its start once it appears. A file that is
+    /// truncated, removed or replaced by a new one, as log rotation does, is re-attached
+    /// the same way.
     pub fn run(&self, path: &Path) {
+        self.run_until(path, &AtomicBool::new(false));
+    }
+
+    /// Watches the log file at `path` like [`Monitor::run`], returning once `stop` is set.
+    pub fn run_until(&self, path: &Path, stop: &AtomicBool) {
+        self.watch(path, stop, SeekFrom::End(0));
+    }
+
+    /// Watches the log file at `path` until `stop` is set, starting at `start` when the
+    /// file already exists.
+    pub(crate) fn watch(&self, path: &Path, stop: &AtomicBool, start: SeekFrom) {
         info!(target: INSTANCE_TARGET, "Starting watch on {}", path.display());
 
-        let file = match File::open(path) {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Failed to open log file {}: {}", path.display(), e);
-                return;
+        let mut reader = match File::open(path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                if let Err(e) = reader.seek(start) {
+                    error!("Failed to seek in {}: {}", path.display(), e);
+                    return;
+                }
+                reader
             }
+            Err(_) => match wait_for_file(path, stop) {
+                Some(file) => BufReader::new(file),
+                None => return,
+            },
         };
 
-        let mut reader = BufReader::new(file);
-        if let Err(e) = reader.seek(SeekFrom::End(0)) {
-            error!("Failed to seek to end of {}: {}", path.display(), e);
-            return;
-        }
-
-        loop {
+        while !stop.load(Ordering::SeqCst) {
             let mut line = String::new();
             match reader.read_line(&mut line) {
                 Ok(0) => {
-                    if let Ok(metadata) = reader.get_ref().metadata()
-                        && let Ok(current_pos) = reader.stream_position()
-                        && metadata.len() < current_pos
-                    {
+                    if is_replaced(&mut reader, path) {
                         info!(target: INSTANCE_TARGET,
                             "Log file {} was truncated/rotated. Re-opening.",
                             path.display()
                         );
-                        match File::open(path) {
-                            Ok(new_file) => {
-                                trace!("Successfully reopened log file");
-                                reader = BufReader::new(new_file);
-                                if let Err(e) = reader.seek(SeekFrom::Start(0)) {
-                                    error!("Failed to seek to start of {}: {}", path.display(), e);
-                                }
-                            }
-                            Err(e) => {
-                                error!("Failed to re-open log file {}: {}", path.display(), e);
-                            }
+                        match wait_for_file(path, stop) {
+                            Some(file) => reader = BufReader::new(file),
+                            None => break,
                         }
+                        trace!("Successfully reopened log file");
                     }
-                    thread::sleep(Duration::from_millis(100));
+                    thread::sleep(POLL_INTERVAL);
                 }
                 Ok(_) => {
                     trace!("Read line from file: {line}");
+                    if let Some(activity) = &self.activity {
+                        activity.touch();
+                    }
                     self.process_rules(line.trim_end());
                 }
                 Err(e) => {
                     error!("Error reading from {}: {}", path.display(), e);
-                    thread::sleep(Duration::from_millis(100));
+                    thread::sleep(POLL_INTERVAL);
                 }
             }
         }
+        info!(target: INSTANCE_TARGET, "Stopped watch on {}", path.display());
     }
 }
 
-pub fn start_monitor_in_thread(log_file: PathBuf, rules: LogRules) {
-    info!(target: INSTANCE_TARGET,
-        "Spawning new log monitor thread for file: {}",
-        log_file.display()
-    );
-    let monitor = Monitor::new(rules);
+/// Stops the monitors started by [`start_monitor_in_thread`],
+/// [`start_instance_log_monitor`] or [`start_glob_monitor`](crate::start_glob_monitor).
+/// Dropping it leaves them running.
+#[derive(Debug, Default)]
+pub struct MonitorHandle {
+    pub(crate) stop: Arc<AtomicBool>,
+    pub(crate) threads: Vec<JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    /// Asks the monitors to stop. They finish within a poll interval of reading their
+    /// current line.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
 
-    let spawn_result = thread::Builder::new()
-        .name(format!("log-monitor-{}", log_file.display()))
-        .spawn(move || {
-            trace!("Log monitor thread started");
-            monitor.run(&log_file);
-        });
+    /// Returns whether any of the monitors is still running.
+    pub fn is_alive(&self) -> bool {
+        self.threads.iter().any(|thread| !thread.is_finished())
+    }
+
+    /// Waits for the monitors to finish; call [`MonitorHandle::stop`] first, as they
+    /// otherwise run forever. Returns `false` when one of them panicked.
+    pub fn join(self) -> bool {
+        // Every monitor is joined, even after one is found to have panicked.
+        let panicked = self
+            .threads
+            .into_iter()
+            .map(JoinHandle::join)
+            .filter(Result::is_err)
+            .count();
+        panicked == 0
+    }
+
+    /// Stops the monitors and waits for them to finish.
+    pub fn stop_and_join(self) -> bool {
+        self.stop();
+        self.join()
+    }
+
+    /// Watches `log_file` with `rules` in a new thread this handle stops.
+    fn spawn(&mut self, log_file: PathBuf, rules: LogRules) {
+        info!(target: INSTANCE_TARGET,
+            "Spawning new log monitor thread for file: {}",
+            log_file.display()
+        );
+        let monitor = Monitor::new(rules);
+        let stop = Arc::clone(&self.stop);
+
+        let name = format!("log-monitor-{}", log_file.display());
+        self.threads.extend(spawn_thread(name, move || {
+            monitor.run_until(&log_file, &stop);
+        }));
+    }
+}
+
+/// Runs `body` in a new thread called `name`, logging when it cannot be spawned.
+pub fn spawn_thread(name: String, body: impl FnOnce() + Send + 'static) -> Option<JoinHandle<()>> {
+    let spawn_result = thread::Builder::new().name(name).spawn(move || {
+        trace!("Log monitor thread started");
+        body();
+    });
 
     match spawn_result {
-        Ok(_) => trace!("Log monitor thread successfully spawned"),
-        Err(e) => error!("Failed to spawn log monitor thread: {}", e),
+        Ok(thread) => {
+            trace!("Log monitor thread successfully spawned");
+            Some(thread)
+        }
+        Err(e) => {
+            error!("Failed to spawn log monitor thread: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns whether the file `reader` reads was truncated below its position, or is no
+/// longer the one at `path` because it was removed or replaced.
+fn is_replaced(reader: &mut BufReader<File>, path: &Path) -> bool {
+    let Ok(open) = reader.get_ref().metadata() else {
+        return false;
+    };
+    if reader
+        .stream_position()
+        .is_ok_and(|position| open.len() < position)
+    {
+        return true;
     }
+    !fs::metadata(path)
+        .is_ok_and(|current| current.dev() == open.dev() && current.ino() == open.ino())
 }
 
-pub fn start_instance_log_monitor(working_dir: &Path, rules: LogRules) {
+/// Opens the file at `path`, waiting with a growing backoff until it exists. Returns
+/// `None` when `stop` is set first.
+fn wait_for_file(path: &Path, stop: &AtomicBool) -> Option<File> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut waiting = false;
+    loop {
+        match File::open(path) {
+            Ok(file) => {
+                if waiting {
+                    info!(target: INSTANCE_TARGET, "Log file {} appeared", path.display());
+                }
+                return Some(file);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if !waiting {
+                    info!(target: INSTANCE_TARGET,
+                        "Waiting for log file {} to be created",
+                        path.display()
+                    );
+                    waiting = true;
+                }
+            }
+            Err(e) => error!("Failed to open log file {}: {}", path.display(), e),
+        }
+        if !sleep_unless_stopped(backoff, stop) {
+            return None;
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Sleeps for `duration` in poll intervals, returning `false` early when `stop` is set.
+pub fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    let mut remaining = duration;
+    while !remaining.is_zero() {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let nap = remaining.min(POLL_INTERVAL);
+        thread::sleep(nap);
+        remaining -= nap;
+    }
+    !stop.load(Ordering::SeqCst)
+}
+
+/// Watches `log_file` with `rules` in a thread of its own, until the returned handle
+/// stops it.
+pub fn start_monitor_in_thread(log_file: PathBuf, rules: LogRules) -> MonitorHandle {
+    let mut handle = MonitorHandle::default();
+    handle.spawn(log_file, rules);
+    handle
+}
+
+/// Watches the `server.log` and `server.err` of the instance in `working_dir` with
+/// `rules`, until the returned handle stops them.
+pub fn start_instance_log_monitor(working_dir: &Path, rules: LogRules) -> MonitorHandle {
     let log_dir = working_dir.join("logs");
     let server_log = log_dir.join("server.log");
     let server_err = log_dir.join("server.err");
@@ -135,8 +304,10 @@ pub fn start_instance_log_monitor(working_dir: &Path, rules: LogRules) {
     );
     debug!(target: INSTANCE_TARGET, "Debugging log monitor startup");
 
-    start_monitor_in_thread(server_log, rules.clone());
-    start_monitor_in_thread(server_err, rules);
+    let mut handle = MonitorHandle::default();
+    handle.spawn(server_log, rules.clone());
+    handle.spawn(server_err, rules);
+    handle
 }
 
 #[cfg(test)]
@@ -156,14 +327,52 @@ mod tests {
         let _monitor = Monitor::new(rules);
     }
 
-    #[test]
-    fn run_returns_early_when_file_does_not_exist() {
+    fn sentinel_rules(hit: &Arc<AtomicBool>) -> LogRules {
+        let hit = Arc::clone(hit);
         let rules = LogRules::new();
-        let monitor = Monitor::new(rules);
-        // A path that does not exist: run() should open-fail and return immediately.
-        monitor.run(std::path::Path::new(
-            "/tmp/gsm-test-nonexistent-log-file-xyz.log",
-        ));
+        rules.add_rule(
+            |line| line.contains("SENTINEL"),
+            move |_| hit.store(true, Ordering::SeqCst),
+            true,
+            None,
+        );
+        rules
+    }
+
+    #[test]
+    fn run_waits_for_a_log_file_created_later() {
+        let temp = tempdir().unwrap();
+        let log_path = temp.path().join("server.log");
+        let hit = Arc::new(AtomicBool::new(false));
+
+        let monitor = start_monitor_in_thread(log_path.clone(), sentinel_rules(&hit));
+
+        thread::sleep(Duration::from_millis(150));
+        // Written before the monitor sees the file, so it must read from the start.
+        fs::write(&log_path, "line with SENTINEL keyword\n").unwrap();
+        thread::sleep(Duration::from_millis(600));
+
+        assert!(hit.load(Ordering::SeqCst), "rule action should have fired");
+        assert!(monitor.stop_and_join());
+    }
+
+    #[test]
+    fn run_reattaches_when_the_log_file_is_recreated() {
+        let temp = tempdir().unwrap();
+        let log_path = temp.path().join("server.log");
+        fs::write(&log_path, "old line\n").unwrap();
+        let hit = Arc::new(AtomicBool::new(false));
+
+        let monitor = start_monitor_in_thread(log_path.clone(), sentinel_rules(&hit));
+
+        thread::sleep(Duration::from_millis(50));
+        fs::rename(&log_path, temp.path().join("server.log.1")).unwrap();
+        thread::sleep(Duration::from_millis(150));
+        fs::write(&log_path, "new line with SENTINEL keyword\n").unwrap();
+        thread::sleep(Duration::from_millis(600));
+
+        assert!(hit.load(Ordering::SeqCst), "rule action should have fired");
+        assert!(monitor.stop_and_join());
     }
 
     #[test]
@@ -187,14 +396,17 @@ mod tests {
 
         let monitor = Monitor::new(rules);
         let path = log_path.clone();
-        let handle = thread::spawn(move || monitor.run(&path));
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let handle = thread::spawn(move || monitor.run_until(&path, &stop_clone));
 
         thread::sleep(Duration::from_millis(50));
         fs::write(&log_path, "line with SENTINEL keyword\n").unwrap();
         thread::sleep(Duration::from_millis(300));
 
         assert!(hit.load(Ordering::SeqCst), "rule action should have fired");
-        drop(handle); // thread runs forever; let it be reaped by the process
+        stop.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
     }
 
     #[test]
@@ -202,16 +414,25 @@ mod tests {
         let temp = tempdir().unwrap();
         let missing = temp.path().join("no-such-file.log");
         let rules = LogRules::new();
-        // Should spawn a thread that opens/fails and exits cleanly.
-        start_monitor_in_thread(missing, rules);
+        // Should spawn a thread that waits for the file to be created.
+        let monitor = start_monitor_in_thread(missing, rules);
         thread::sleep(Duration::from_millis(50));
+        assert!(monitor.is_alive());
+        assert!(monitor.stop_and_join());
     }
 
     #[test]
-    fn start_instance_log_monitor_spawns_without_panic() {
+    fn start_instance_log_monitor_stops_both_monitors() {
         let temp = tempdir().unwrap();
-        start_instance_log_monitor(temp.path(), LogRules::default());
+        let monitor = start_instance_log_monitor(temp.path(), LogRules::default());
         thread::sleep(Duration::from_millis(50));
+        assert_eq!(monitor.threads.len(), 2);
+        assert!(monitor.is_alive());
+
+        monitor.stop();
+        thread::sleep(Duration::from_millis(300));
+        assert!(!monitor.is_alive());
+        assert!(monitor.join());
     }
 
     #[test]
@@ -248,6 +469,30 @@ mod tests {
         assert_eq!(hits.load(Ordering::SeqCst), 11);
     }
 
+    #[test]
+    #[allow(clippy::panic)]
+    fn process_rules_survives_panicking_actions() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let rules = LogRules::new();
+        rules.add_rule(|_| true, |_| panic!("webhook failed"), false, Some(1));
+        {
+            let hits = Arc::clone(&hits);
+            rules.add_rule(
+                |_| true,
+                move |_| {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                },
+                true,
+                Some(2),
+            );
+        }
+
+        let monitor = Monitor::new(rules);
+        monitor.process_rules("first line");
+        monitor.process_rules("second line");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn process_rules_stops_after_first_stop_rule() {
         let hits = Arc::new(AtomicUsize::new(0));
@@ -0,0 +1,221 @@
+//! This module provides rule presets for the games this project supports.
+//!
+//! Each preset recognizes a game's server starting and players joining and leaving, and hands them to a handler as
+//! typed [`GameEvent`]s, so a new app binary gets monitoring without writing its own patterns:
+//!
+//! ```rust
+//! use gsm_monitor::{GameEvent, LogRules};
+//!
+//! let rules = LogRules::palworld(|event| match event {
+//!     GameEvent::Started => println!("Server started"),
+//!     GameEvent::PlayerJoined(name) => println!("{name} joined"),
+//!     GameEvent::PlayerLeft(name) => println!("{name} left"),
+//! });
+//! ```
+
+use crate::rules::LogRules;
+use std::sync::Arc;
+use tracing::error;
+
+/// Something that happened on a game server, recognized from its logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// The server finished starting and accepts players.
+    Started,
+    /// The named player joined the server.
+    PlayerJoined(String),
+    /// The named player left the server.
+    PlayerLeft(String),
+}
+
+/// The patterns a game's logs are matched against. The player patterns capture the
+/// player's name in their first group.
+struct Preset {
+    started: &'static str,
+    joined: &'static str,
+    left: Option<&'static str>,
+}
+
+/// Enshrouded, e.g. `[server] Player 'mbround18' logged in with Permissions:`.
+const ENSHROUDED: Preset = Preset {
+    started: r"\[Session\] 'HostOnline' \(up\)!",
+    joined: r"Player\s+'([^']+)'\s+logged in with Permissions:",
+    left: Some(r"Remove Entity for Player\s+'([^']+)'"),
+};
+
+/// Palworld, e.g. `[LOG] mbround18 joined the server.`.
+const PALWORLD: Preset = Preset {
+    started: r"Running Palworld dedicated server on",
+    joined: r"\[LOG\]\s+(\w+)\s+joined the server",
+    left: Some(r"\[LOG\]\s+(\w+)\s+left the server"),
+};
+
+/// Unreal Engine servers in general, from the engine's own log lines. The engine does not
+/// log who leaves, so leaves are not recognized.
+const UNREAL_GENERIC: Preset = Preset {
+    started: r"Engine is initialized\. Leaving FEngineLoop::Init\(\)",
+    joined: r"LogNet: Join succeeded: (.+?)\s*$",
+    left: None,
+};
+
+impl LogRules {
+    /// Returns the default rules plus rules recognizing Enshrouded's events.
+    pub fn enshrouded<H>(handler: H) -> Self
+    where
+        H: Fn(GameEvent) + Send + Sync + 'static,
+    {
+        Self::with_preset(&ENSHROUDED, handler)
+    }
+
+    /// Returns the default rules plus rules recognizing Palworld's events.
+    pub fn palworld<H>(handler: H) -> Self
+    where
+        H: Fn(GameEvent) + Send + Sync + 'static,
+    {
+        Self::with_preset(&PALWORLD, handler)
+    }
+
+    /// Returns the default rules plus rules recognizing the events any Unreal Engine
+    /// server logs: starting and players joining.
+    pub fn unreal_generic<H>(handler: H) -> Self
+    where
+        H: Fn(GameEvent) + Send + Sync + 'static,
+    {
+        Self::with_preset(&UNREAL_GENERIC, handler)
+    }
+
+    fn with_preset<H>(preset: &Preset, handler: H) -> Self
+    where
+        H: Fn(GameEvent) + Send + Sync + 'static,
+    {
+        let rules = Self::default();
+        let handler = Arc::new(handler);
+        let player_patterns = [
+            (
+                Some(preset.joined),
+                GameEvent::PlayerJoined as fn(String) -> GameEvent,
+            ),
+            (preset.left, GameEvent::PlayerLeft),
+        ];
+
+        let on_started = Arc::clone(&handler);
+        let mut result = rules.add_regex_rule(
+            preset.started,
+            move |_| on_started(GameEvent::Started),
+            false,
+            None,
+        );
+        for (pattern, event) in player_patterns {
+            let Some(pattern) = pattern else {
+                continue;
+            };
+            let handler = Arc::clone(&handler);
+            // Added even after an earlier pattern failed, so the other rules still work.
+            let added = rules.add_regex_rule(
+                pattern,
+                move |captures| {
+                    if let Some(name) = captures.get(1) {
+                        handler(event(name.as_str().to_owned()));
+                    }
+                },
+                false,
+                None,
+            );
+            result = result.and(added);
+        }
+        if let Err(e) = result {
+            error!("Invalid pattern in a log rule preset: {e}");
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, PoisonError};
+
+    type Handler = Box<dyn Fn(GameEvent) + Send + Sync>;
+
+    fn events(rules: fn(Handler) -> LogRules, lines: &[&str]) -> Vec<GameEvent> {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let rules = rules(Box::new(move |event| {
+            recorder
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(event);
+        }));
+        for line in lines {
+            for rule in rules.get_rules() {
+                if (rule.matcher)(line) {
+                    (rule.action)(line);
+                    if rule.stop {
+                        break;
+                    }
+                }
+            }
+        }
+        seen.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    #[test]
+    fn enshrouded_events_are_recognized() {
+        let events = events(
+            LogRules::enshrouded,
+            &[
+                "[Session] 'HostOnline' (up)!",
+                "[server] Player 'Cool Player_123' logged in with Permissions:",
+                "[server] Remove Entity for Player 'Cool Player_123'",
+                "[server] Some other log line",
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                GameEvent::Started,
+                GameEvent::PlayerJoined("Cool Player_123".to_owned()),
+                GameEvent::PlayerLeft("Cool Player_123".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn palworld_events_are_recognized() {
+        let events = events(
+            LogRules::palworld,
+            &[
+                "Running Palworld dedicated server on :8211",
+                "[2024.01.01-00.00.00:000][  0]LogNet: [LOG] mbround18 joined the server.",
+                "[2024.01.01-00.00.00:000][  0]LogNet: [LOG] mbround18 left the server.",
+                "[server] Server started.",
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                GameEvent::Started,
+                GameEvent::PlayerJoined("mbround18".to_owned()),
+                GameEvent::PlayerLeft("mbround18".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unreal_engine_events_are_recognized() {
+        let events = events(
+            LogRules::unreal_generic,
+            &[
+                "LogInit: Display: Engine is initialized. Leaving FEngineLoop::Init()",
+                "[2024.01.01-00.00.00:000][  0]LogNet: Join succeeded: mbround18 ",
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                GameEvent::Started,
+                GameEvent::PlayerJoined("mbround18".to_owned()),
+            ]
+        );
+    }
+}
@@ -2,8 +2,19 @@
 //!
 //! A log rule consists of a matcher and an action. The matcher determines if a given log line should trigger
 //! the associated action. Log rules are stored and processed in order of their ranking.
+//!
+//! Rules added with [`LogRules::add_regex_rule`] match a regular expression, compiled once
+//! when the rule is added, and hand its capture groups to the action, so extracting a
+//! player name from a line does not need a second, duplicated pattern. Rules added with
+//! [`LogRules::add_event_rule`] receive each line as a [`LogEvent`], parsed from JSON where
+//! the server writes structured logs. Rules added with [`LogRules::add_sink_rule`] forward
+//! the lines they match to a [`LogSink`].
 
 use crate::constants::INSTANCE_TARGET;
+use crate::event::LogEvent;
+use crate::sinks::LogSink;
+use regex::{Captures, Regex};
+use std::io;
 use std::sync::PoisonError;
 use std::sync::{Arc, RwLock};
 use tracing::{error, info, trace, warn};
@@ -28,6 +39,8 @@ fn default_ranking(current_count: usize) -> i32 {
 
 #[derive(Clone)]
 pub struct LogRule {
+    /// Identifies the rule in logs: the rules added before it, as the default rule is 0.
+    pub id: usize,
     pub matcher: Matcher,
     pub action: Action,
     pub ranking: i32,
@@ -38,6 +51,7 @@ impl Default for LogRule {
     fn default() -> Self {
         trace!("Creating default LogRule");
         Self {
+            id: 0,
             matcher: Arc::new(|_| true),
             action: Arc::new(|line| info!(target: INSTANCE_TARGET, "{line}")),
             ranking: DEFAULT_STOP_INT,
@@ -53,16 +67,19 @@ impl LogRule {
     }
 }
 
+/// The rules a monitor applies, kept sorted by ranking. Clones share their rules.
 #[derive(Clone)]
 pub struct LogRules {
-    rules: Arc<RwLock<Vec<LogRule>>>,
+    /// Replaced, never modified, when a rule is added, so a snapshot stays valid while
+    /// lines are processed.
+    rules: Arc<RwLock<Arc<[LogRule]>>>,
 }
 
 impl LogRules {
     pub fn new() -> Self {
         trace!("Initializing LogRules");
         Self {
-            rules: Arc::new(RwLock::new(vec![LogRule::default()])),
+            rules: Arc::new(RwLock::new(Arc::from(vec![LogRule::default()]))),
         }
     }
 
@@ -74,23 +91,90 @@ impl LogRules {
         trace!("Adding new rule with stop flag: {stop}");
         let mut rules = self.rules.write().unwrap_or_else(PoisonError::into_inner);
         let mut rule = LogRule::new();
+        rule.id = rules.len();
         rule.stop = stop;
         rule.matcher = Arc::new(matcher);
         rule.action = Arc::new(action);
         rule.ranking = ranking.unwrap_or_else(|| default_ranking(rules.len()));
-        rules.push(rule);
+
+        // Insert after the rules ranked the same, so they keep the order they were added in.
+        let mut sorted = rules.to_vec();
+        let position = sorted.partition_point(|existing| existing.ranking <= rule.ranking);
+        sorted.insert(position, rule);
+        *rules = sorted.into();
+    }
+
+    /// Adds a rule matching lines against the regular expression `pattern`, whose action
+    /// receives the groups it captured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `pattern` is not a valid regular expression.
+    pub fn add_regex_rule<G>(
+        &self,
+        pattern: &str,
+        action: G,
+        stop: bool,
+        ranking: Option<i32>,
+    ) -> Result<(), regex::Error>
+    where
+        G: Fn(&Captures) + Send + Sync + 'static,
+    {
+        let regex = Arc::new(Regex::new(pattern)?);
+        let matcher = Arc::clone(&regex);
+        self.add_rule(
+            move |line| matcher.is_match(line),
+            move |line| {
+                if let Some(captures) = regex.captures(line) {
+                    action(&captures);
+                }
+            },
+            stop,
+            ranking,
+        );
+        Ok(())
+    }
+
+    /// Adds a rule whose matcher and action receive each line parsed as a [`LogEvent`], so
+    /// JSON log lines can be matched on their level and fields.
+    pub fn add_event_rule<F, G>(&self, matcher: F, action: G, stop: bool, ranking: Option<i32>)
+    where
+        F: Fn(&LogEvent) -> bool + Send + Sync + 'static,
+        G: Fn(&LogEvent) + Send + Sync + 'static,
+    {
+        self.add_rule(
+            move |line| matcher(&LogEvent::parse(line)),
+            move |line| action(&LogEvent::parse(line)),
+            stop,
+            ranking,
+        );
+    }
+
+    /// Adds a rule forwarding the lines `matcher` matches to `sink`. It is ranked first and
+    /// does not stop, so forwarded lines are still handled by the other rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the sink cannot be opened, e.g. when its file cannot be
+    /// created or no syslog daemon is listening.
+    pub fn add_sink_rule<F>(&self, matcher: F, sink: &LogSink) -> io::Result<()>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        let action = sink.action()?;
+        self.add_rule(matcher, move |line| action(line), false, Some(i32::MIN));
+        Ok(())
     }
 
     pub fn get_rules(&self) -> Vec<LogRule> {
-        trace!("Retrieving and sorting rules");
-        let mut rules = self
-            .rules
-            .read()
-            .unwrap_or_else(PoisonError::into_inner)
-            .clone();
-        rules.sort_by_key(|r| r.ranking);
-        trace!("Sorted rules count: {}", rules.len());
-        rules
+        trace!("Retrieving sorted rules");
+        self.snapshot().to_vec()
+    }
+
+    /// Returns the rules sorted by ranking, shared rather than copied, for processing a
+    /// line.
+    pub fn snapshot(&self) -> Arc<[LogRule]> {
+        Arc::clone(&self.rules.read().unwrap_or_else(PoisonError::into_inner))
     }
 }
 
@@ -116,6 +200,8 @@ impl Default for LogRules {
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
 
     #[test]
@@ -140,6 +226,95 @@ mod tests {
         assert_eq!(rankings, vec![5, 20, DEFAULT_STOP_INT]);
     }
 
+    #[test]
+    fn snapshots_are_only_replaced_when_rules_change() {
+        let rules = LogRules::new();
+        rules.add_rule(|_| true, |_| {}, false, Some(5));
+        let before = rules.snapshot();
+        assert!(Arc::ptr_eq(&before, &rules.snapshot()));
+
+        rules.add_rule(|_| true, |_| {}, true, Some(5));
+        let after = rules.snapshot();
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(before.len(), 2);
+        let order: Vec<(i32, bool)> = after.iter().map(|rule| (rule.ranking, rule.stop)).collect();
+        assert_eq!(order, [(5, false), (5, true), (DEFAULT_STOP_INT, true)]);
+    }
+
+    #[test]
+    fn regex_rules_pass_their_captures_to_the_action() {
+        let rules = LogRules::new();
+        let names = Arc::new(RwLock::new(Vec::new()));
+        let seen = Arc::clone(&names);
+        rules
+            .add_regex_rule(
+                r"Player '([^']+)' joined",
+                move |captures| {
+                    if let Some(name) = captures.get(1) {
+                        seen.write()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(name.as_str().to_owned());
+                    }
+                },
+                false,
+                Some(1),
+            )
+            .unwrap();
+        assert!(
+            rules
+                .add_regex_rule("(unclosed", |_| {}, false, None)
+                .is_err()
+        );
+
+        for line in ["Player 'Cool Player_1' joined", "Player left"] {
+            for rule in rules.get_rules() {
+                if (rule.matcher)(line) {
+                    (rule.action)(line);
+                    if rule.stop {
+                        break;
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            *names.read().unwrap_or_else(PoisonError::into_inner),
+            ["Cool Player_1"]
+        );
+    }
+
+    #[test]
+    fn event_rules_match_on_json_fields() {
+        let rules = LogRules::new();
+        let messages = Arc::new(RwLock::new(Vec::new()));
+        let seen = Arc::clone(&messages);
+        rules.add_event_rule(
+            |event| event.is_level("warning"),
+            move |event| {
+                seen.write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(event.message.clone());
+            },
+            true,
+            Some(1),
+        );
+
+        for line in [
+            r#"{"level":"Warning","message":"Server is low on memory"}"#,
+            r#"{"level":"Info","message":"Server is running"}"#,
+            "Warning in plain text",
+        ] {
+            if let Some(rule) = rules.get_rules().first()
+                && (rule.matcher)(line)
+            {
+                (rule.action)(line);
+            }
+        }
+        assert_eq!(
+            *messages.read().unwrap_or_else(PoisonError::into_inner),
+            ["Server is low on memory"]
+        );
+    }
+
     #[test]
     fn default_rules_include_warning_and_error_handlers() {
         let rules = LogRules::default();
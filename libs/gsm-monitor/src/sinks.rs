@@ -0,0 +1,328 @@
+//! This module provides sinks that forward matched log lines to existing log aggregation.
+//!
+//! A [`LogSink`] appends lines to a file of their own, sends them to the local syslog daemon, or pushes them to a
+//! Loki endpoint labelled with the server's name and each line's severity. Rules added with
+//! [`LogRules::add_sink_rule`](crate::LogRules::add_sink_rule) forward the lines they match without a custom
+//! action. Loki pushes are batched in a thread of their own, so a slow endpoint does not hold up the monitor.
+//!
+//! ```rust,no_run
+//! use gsm_monitor::{LogRules, LogSink};
+//!
+//! let rules = LogRules::default();
+//! rules
+//!     .add_sink_rule(|_| true, &LogSink::loki("http://loki:3100", "palworld"))
+//!     .expect("Failed to start the Loki sink");
+//! rules
+//!     .add_sink_rule(|line| line.contains("ERROR"), &LogSink::syslog("palworld"))
+//!     .expect("Failed to connect to syslog");
+//! ```
+
+use crate::event::LogEvent;
+use crate::rules::Action;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Where the local syslog daemon listens.
+pub const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// The path of Loki's push API, relative to its base URL.
+const LOKI_PUSH_PATH: &str = "/loki/api/v1/push";
+
+/// How long a Loki sink collects lines before pushing them.
+const LOKI_BATCH_WINDOW: Duration = Duration::from_secs(1);
+
+/// The most lines a Loki sink pushes at once.
+const LOKI_MAX_BATCH: usize = 500;
+
+/// The syslog facility lines are sent with: user-level messages.
+const SYSLOG_FACILITY_USER: u8 = 1;
+
+/// Where forwarded log lines are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogSink {
+    /// Appends each line to the file at this path, creating it if needed.
+    File(PathBuf),
+    /// Sends each line to the syslog daemon listening on `socket`, tagged with `ident`.
+    Syslog {
+        /// The program name syslog records the lines under.
+        ident: String,
+        /// The syslog daemon's datagram socket, usually [`SYSLOG_SOCKET`].
+        socket: PathBuf,
+    },
+    /// Pushes lines to the Loki instance at `url`, e.g. `http://loki:3100`, labelled with
+    /// `labels` and each line's `severity`.
+    Loki {
+        /// The base URL of the Loki instance.
+        url: String,
+        /// The labels every pushed line carries.
+        labels: BTreeMap<String, String>,
+    },
+}
+
+/// How severe a forwarded log line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// An error, or a fatal or critical failure.
+    Error,
+    /// A warning.
+    Warning,
+    /// Anything else.
+    Info,
+}
+
+impl Severity {
+    /// Returns the severity of `line`, from its level when it is a JSON log line and
+    /// otherwise from the markers the default rules look for.
+    pub fn of(line: &str) -> Self {
+        let event = LogEvent::parse(line);
+        if let Some(level) = event.level {
+            let level = level.to_ascii_lowercase();
+            if ["err", "fatal", "crit"]
+                .iter()
+                .any(|prefix| level.starts_with(prefix))
+            {
+                return Self::Error;
+            }
+            return if level.starts_with("warn") {
+                Self::Warning
+            } else {
+                Self::Info
+            };
+        }
+        if line.contains("ERROR") {
+            Self::Error
+        } else if line.contains("WARNING") {
+            Self::Warning
+        } else {
+            Self::Info
+        }
+    }
+
+    /// Returns the severity's name, as used for the Loki `severity` label.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+        }
+    }
+
+    /// Returns the severity's syslog code.
+    const fn syslog_code(self) -> u8 {
+        match self {
+            Self::Error => 3,
+            Self::Warning => 4,
+            Self::Info => 6,
+        }
+    }
+}
+
+impl LogSink {
+    /// Sends lines to the local syslog daemon, tagged with `ident`.
+    pub fn syslog(ident: impl Into<String>) -> Self {
+        Self::Syslog {
+            ident: ident.into(),
+            socket: PathBuf::from(SYSLOG_SOCKET),
+        }
+    }
+
+    /// Pushes lines to the Loki instance at `url`, labelled with the `server` they came
+    /// from.
+    pub fn loki(url: impl Into<String>, server: impl Into<String>) -> Self {
+        Self::Loki {
+            url: url.into(),
+            labels: BTreeMap::from([("server".to_owned(), server.into())]),
+        }
+    }
+
+    /// Opens the sink, returning the action that forwards a line to it.
+    pub(crate) fn action(&self) -> io::Result<Action> {
+        match self {
+            Self::File(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let file = OpenOptions::new().append(true).create(true).open(path)?;
+                Ok(file_action(file))
+            }
+            Self::Syslog { ident, socket } => {
+                let datagram = UnixDatagram::unbound()?;
+                datagram.connect(socket)?;
+                let ident = ident.clone();
+                Ok(Arc::new(move |line| {
+                    let priority = SYSLOG_FACILITY_USER * 8 + Severity::of(line).syslog_code();
+                    // Syslog drops what it cannot take; so does the sink.
+                    let _ = datagram.send(format!("<{priority}>{ident}: {line}").as_bytes());
+                }))
+            }
+            Self::Loki { url, labels } => {
+                let (sender, receiver) = mpsc::channel();
+                let url = format!("{}{LOKI_PUSH_PATH}", url.trim_end_matches('/'));
+                let labels = labels.clone();
+                thread::Builder::new()
+                    .name("loki-sink".to_owned())
+                    .spawn(move || push_to_loki(&url, &labels, &receiver))?;
+                Ok(Arc::new(move |line| {
+                    let _ = sender.send((timestamp(), line.to_owned()));
+                }))
+            }
+        }
+    }
+}
+
+fn file_action(file: File) -> Action {
+    let file = Mutex::new(file);
+    Arc::new(move |line| {
+        let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = writeln!(file, "{line}") {
+            warn!("Failed to forward a log line to a file: {e}");
+        }
+    })
+}
+
+/// Returns the current time in nanoseconds since the Unix epoch, as Loki expects it.
+fn timestamp() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Pushes the lines received on `receiver` to `url` in batches, until every sender is
+/// dropped.
+fn push_to_loki(
+    url: &str,
+    labels: &BTreeMap<String, String>,
+    receiver: &Receiver<(String, String)>,
+) {
+    let client = reqwest::blocking::Client::new();
+    while let Ok(first) = receiver.recv() {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + LOKI_BATCH_WINDOW;
+        while batch.len() < LOKI_MAX_BATCH {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(entry) => batch.push(entry),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        let result = client
+            .post(url)
+            .json(&loki_body(labels, &batch))
+            .send()
+            .and_then(reqwest::blocking::Response::error_for_status);
+        if let Err(e) = result {
+            warn!("Failed to push {} log line(s) to Loki: {e}", batch.len());
+        }
+    }
+}
+
+/// Builds a Loki push request for `batch`, with one stream per severity.
+fn loki_body(labels: &BTreeMap<String, String>, batch: &[(String, String)]) -> Value {
+    let mut streams: BTreeMap<Severity, Vec<Value>> = BTreeMap::new();
+    for (timestamp, line) in batch {
+        streams
+            .entry(Severity::of(line))
+            .or_default()
+            .push(json!([timestamp, line]));
+    }
+    let streams: Vec<Value> = streams
+        .into_iter()
+        .map(|(severity, values)| {
+            let mut stream = labels.clone();
+            stream.insert("severity".to_owned(), severity.as_str().to_owned());
+            json!({ "stream": stream, "values": values })
+        })
+        .collect();
+    json!({ "streams": streams })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn severities_come_from_json_levels_or_markers() {
+        assert_eq!(
+            Severity::of(r#"{"level":"Error","message":"x"}"#),
+            Severity::Error
+        );
+        assert_eq!(
+            Severity::of(r#"{"level":"warn","message":"x"}"#),
+            Severity::Warning
+        );
+        assert_eq!(
+            Severity::of(r#"{"level":"Display","message":"ERROR"}"#),
+            Severity::Info
+        );
+        assert_eq!(Severity::of("WARNING: low memory"), Severity::Warning);
+        assert_eq!(Severity::of("Server started"), Severity::Info);
+    }
+
+    #[test]
+    fn file_sinks_append_each_line() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("forwarded/errors.log");
+        let action = LogSink::File(path.clone()).action().unwrap();
+        action("ERROR: first");
+        action("ERROR: second");
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "ERROR: first\nERROR: second\n"
+        );
+    }
+
+    #[test]
+    fn syslog_sinks_send_messages_with_their_priority() {
+        let temp = tempdir().unwrap();
+        let socket = temp.path().join("log.sock");
+        let daemon = UnixDatagram::bind(&socket).unwrap();
+        let sink = LogSink::Syslog {
+            ident: "palworld".to_owned(),
+            socket,
+        };
+
+        sink.action().unwrap()("WARNING: low memory");
+        let mut buffer = [0; 256];
+        let read = daemon.recv(&mut buffer).unwrap();
+        assert_eq!(
+            buffer.get(..read).unwrap(),
+            b"<12>palworld: WARNING: low memory"
+        );
+    }
+
+    #[test]
+    fn loki_pushes_group_lines_by_severity() {
+        let labels = BTreeMap::from([("server".to_owned(), "palworld".to_owned())]);
+        let batch = [
+            ("1".to_owned(), "Server started".to_owned()),
+            ("2".to_owned(), "ERROR: save failed".to_owned()),
+            ("3".to_owned(), "Player joined".to_owned()),
+        ];
+        assert_eq!(
+            loki_body(&labels, &batch),
+            json!({ "streams": [
+                {
+                    "stream": { "server": "palworld", "severity": "error" },
+                    "values": [["2", "ERROR: save failed"]],
+                },
+                {
+                    "stream": { "server": "palworld", "severity": "info" },
+                    "values": [["1", "Server started"], ["3", "Player joined"]],
+                },
+            ]})
+        );
+    }
+}
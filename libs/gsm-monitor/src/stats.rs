@@ -0,0 +1,243 @@
+//! This module provides metrics extracted from log lines.
+//!
+//! A [`LogMetrics`] adds rules that count matching lines, such as errors, keep gauges up to date, such as the
+//! players online derived from join and leave lines, or read a gauge from a regex group, such as a tick rate.
+//! [`LogMetrics::snapshot`] returns the current values as [`MonitorStats`], e.g. for a metrics endpoint.
+//!
+//! ```rust
+//! use gsm_monitor::{LogMetrics, LogRules};
+//!
+//! let rules = LogRules::default();
+//! let metrics = LogMetrics::default();
+//! metrics.count(&rules, "errors", |line| line.contains("ERROR"));
+//! metrics.adjust(&rules, "players_online", |line| line.contains("joined the server"), 1.0);
+//! metrics.adjust(&rules, "players_online", |line| line.contains("left the server"), -1.0);
+//! metrics
+//!     .gauge_from_regex(&rules, "tick_rate", r"Tick rate: ([\d.]+)")
+//!     .expect("valid pattern");
+//!
+//! let stats = metrics.snapshot();
+//! assert_eq!(stats.counters.get("errors"), Some(&0));
+//! ```
+
+use crate::rules::LogRules;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+/// The ranking of metric rules, so they see every line before a rule that stops.
+const METRICS_RANKING: i32 = i32::MIN;
+
+/// The window counters report their recent matches over.
+const RATE_WINDOW: Duration = Duration::from_mins(1);
+
+/// The values of a [`LogMetrics`] at one moment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonitorStats {
+    /// How many lines each counter matched since monitoring started.
+    pub counters: BTreeMap<String, u64>,
+    /// How many lines each counter matched in the last minute.
+    pub per_minute: BTreeMap<String, u64>,
+    /// The current value of each gauge.
+    pub gauges: BTreeMap<String, f64>,
+}
+
+#[derive(Default)]
+struct Values {
+    counters: BTreeMap<String, u64>,
+    recent: BTreeMap<String, VecDeque<Instant>>,
+    gauges: BTreeMap<String, f64>,
+}
+
+/// Named counters and gauges kept up to date by log rules. Clones share their values.
+#[derive(Clone, Default)]
+pub struct LogMetrics {
+    values: Arc<RwLock<Values>>,
+}
+
+impl LogMetrics {
+    /// Counts the lines `matcher` matches in the counter `name`.
+    pub fn count<F>(&self, rules: &LogRules, name: &str, matcher: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.with_values(|values| {
+            values.counters.entry(name.to_owned()).or_default();
+            values.recent.entry(name.to_owned()).or_default();
+        });
+        let metrics = self.clone();
+        let name = name.to_owned();
+        rules.add_rule(
+            matcher,
+            move |_| metrics.increment(&name, Instant::now()),
+            false,
+            Some(METRICS_RANKING),
+        );
+    }
+
+    /// Adds `delta` to the gauge `name` for each line `matcher` matches. The gauge does
+    /// not go below zero, so a leave seen without its join does not skew a count.
+    pub fn adjust<F>(&self, rules: &LogRules, name: &str, matcher: F, delta: f64)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.with_values(|values| {
+            values.gauges.entry(name.to_owned()).or_default();
+        });
+        let metrics = self.clone();
+        let name = name.to_owned();
+        rules.add_rule(
+            matcher,
+            move |_| metrics.set_gauge(&name, |gauge| (gauge + delta).max(0.0)),
+            false,
+            Some(METRICS_RANKING),
+        );
+    }
+
+    /// Sets the gauge `name` to the number the first group of `pattern` captures from
+    /// each line it matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `pattern` is not a valid regular expression.
+    pub fn gauge_from_regex(
+        &self,
+        rules: &LogRules,
+        name: &str,
+        pattern: &str,
+    ) -> Result<(), regex::Error> {
+        let metrics = self.clone();
+        let name = name.to_owned();
+        rules.add_regex_rule(
+            pattern,
+            move |captures| {
+                if let Some(value) = captures
+                    .get(1)
+                    .and_then(|value| value.as_str().parse::<f64>().ok())
+                {
+                    metrics.set_gauge(&name, |_| value);
+                }
+            },
+            false,
+            Some(METRICS_RANKING),
+        )
+    }
+
+    /// Returns the current values of the counters and gauges.
+    pub fn snapshot(&self) -> MonitorStats {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> MonitorStats {
+        self.with_values(|values| {
+            let per_minute = values
+                .recent
+                .iter_mut()
+                .map(|(name, recent)| {
+                    prune(recent, now);
+                    (name.clone(), recent.len() as u64)
+                })
+                .collect();
+            MonitorStats {
+                counters: values.counters.clone(),
+                per_minute,
+                gauges: values.gauges.clone(),
+            }
+        })
+    }
+
+    fn increment(&self, name: &str, now: Instant) {
+        self.with_values(|values| {
+            *values.counters.entry(name.to_owned()).or_default() += 1;
+            let recent = values.recent.entry(name.to_owned()).or_default();
+            recent.push_back(now);
+            prune(recent, now);
+        });
+    }
+
+    fn set_gauge(&self, name: &str, update: impl FnOnce(f64) -> f64) {
+        self.with_values(|values| {
+            let gauge = values.gauges.entry(name.to_owned()).or_default();
+            *gauge = update(*gauge);
+        });
+    }
+
+    fn with_values<T>(&self, f: impl FnOnce(&mut Values) -> T) -> T {
+        f(&mut self.values.write().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Drops the matches in `recent` older than the rate window.
+fn prune(recent: &mut VecDeque<Instant>, now: Instant) {
+    while recent
+        .front()
+        .is_some_and(|&matched| now.duration_since(matched) > RATE_WINDOW)
+    {
+        recent.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn apply(rules: &LogRules, line: &str) {
+        for rule in rules.get_rules() {
+            if (rule.matcher)(line) {
+                (rule.action)(line);
+                if rule.stop {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn gauge(stats: &MonitorStats, name: &str) -> f64 {
+        stats.gauges.get(name).copied().unwrap()
+    }
+
+    #[test]
+    fn rules_keep_counters_and_gauges_up_to_date() {
+        let rules = LogRules::default();
+        let metrics = LogMetrics::default();
+        metrics.count(&rules, "errors", |line| line.contains("ERROR"));
+        metrics.adjust(&rules, "players", |line| line.contains("joined"), 1.0);
+        metrics.adjust(&rules, "players", |line| line.contains("left"), -1.0);
+        metrics
+            .gauge_from_regex(&rules, "tick_rate", r"Tick rate: ([\d.]+)")
+            .unwrap();
+
+        for line in [
+            "ghost left",
+            "alice joined",
+            "bob joined",
+            "ERROR: save failed",
+            "bob left",
+            "Tick rate: 29.5",
+            "Tick rate: unknown",
+        ] {
+            apply(&rules, line);
+        }
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.counters.get("errors"), Some(&1));
+        assert_eq!(stats.per_minute.get("errors"), Some(&1));
+        assert!((gauge(&stats, "players") - 1.0).abs() < f64::EPSILON);
+        assert!((gauge(&stats, "tick_rate") - 29.5).abs() < f64::EPSILON);
+        assert!(metrics.gauge_from_regex(&rules, "bad", "(").is_err());
+    }
+
+    #[test]
+    fn per_minute_rates_only_count_recent_matches() {
+        let metrics = LogMetrics::default();
+        let start = Instant::now();
+        metrics.increment("errors", start);
+        metrics.increment("errors", start + Duration::from_secs(30));
+
+        let stats = metrics.snapshot_at(start + Duration::from_secs(75));
+        assert_eq!(stats.counters.get("errors"), Some(&2));
+        assert_eq!(stats.per_minute.get("errors"), Some(&1));
+    }
+}
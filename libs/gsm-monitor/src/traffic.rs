@@ -0,0 +1,231 @@
+//! This module estimates player activity from network traffic on the game port.
+//!
+//! Some games expose neither a query protocol nor RCON, and their logs do not always
+//! report joins and leaves reliably. As a fallback signal, the sampler reads the kernel's
+//! socket tables under `/proc/net`: established TCP connections on the game port, and
+//! the number of UDP datagrams received since the previous sample. Inside a container the
+//! network namespace usually belongs to the game server alone, so the namespace-wide UDP
+//! counter is a reasonable proxy for game traffic.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+/// Datagrams per second above which a UDP game server is assumed to have players.
+///
+/// Idle servers still see a trickle of server-browser queries and heartbeats; a single
+/// connected client sends far more than this.
+pub const DEFAULT_UDP_ACTIVITY_THRESHOLD: f64 = 10.0;
+
+const TCP_ESTABLISHED: &str = "01";
+
+/// Traffic observed on the game port over one sampling interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrafficSample {
+    /// Established TCP connections whose local port is the game port.
+    pub tcp_connections: usize,
+    /// UDP datagrams received since the previous sample.
+    pub udp_datagrams: u64,
+    /// Time elapsed since the previous sample.
+    pub interval: Duration,
+}
+
+impl TrafficSample {
+    /// Average UDP datagrams received per second over the interval.
+    pub fn udp_datagrams_per_sec(&self) -> f64 {
+        let secs = self.interval.as_secs_f64();
+        if secs > 0.0 {
+            f64::from(u32::try_from(self.udp_datagrams).unwrap_or(u32::MAX)) / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Periodically samples `/proc/net` to guess whether players are connected.
+pub struct TrafficSampler {
+    port: u16,
+    proc_root: PathBuf,
+    udp_threshold: f64,
+    last: Option<(u64, Instant)>,
+}
+
+impl TrafficSampler {
+    /// Creates a sampler for the given game port.
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            proc_root: PathBuf::from("/proc"),
+            udp_threshold: DEFAULT_UDP_ACTIVITY_THRESHOLD,
+            last: None,
+        }
+    }
+
+    /// Reads socket tables from `proc_root` instead of `/proc`.
+    #[must_use]
+    pub fn with_proc_root(mut self, proc_root: impl Into<PathBuf>) -> Self {
+        self.proc_root = proc_root.into();
+        self
+    }
+
+    /// Sets the datagrams-per-second rate treated as player activity.
+    #[must_use]
+    pub const fn with_udp_threshold(mut self, datagrams_per_sec: f64) -> Self {
+        self.udp_threshold = datagrams_per_sec;
+        self
+    }
+
+    /// Takes a sample. The first sample only establishes a baseline for the UDP
+    /// counter, so its `udp_datagrams` is always zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `/proc/net` tables cannot be read or parsed.
+    pub fn sample(&mut self) -> io::Result<TrafficSample> {
+        let tcp_connections = self.count_tcp_connections()?;
+        let received = self.read_udp_in_datagrams()?;
+        let now = Instant::now();
+
+        let (udp_datagrams, interval) = self.last.map_or((0, Duration::ZERO), |(prev, at)| {
+            (received.saturating_sub(prev), now.duration_since(at))
+        });
+        self.last = Some((received, now));
+
+        let sample = TrafficSample {
+            tcp_connections,
+            udp_datagrams,
+            interval,
+        };
+        trace!("Traffic sample on port {}: {:?}", self.port, sample);
+        Ok(sample)
+    }
+
+    /// Samples traffic and reports whether players are likely connected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `/proc/net` tables cannot be read or parsed.
+    pub fn players_likely_online(&mut self) -> io::Result<bool> {
+        let sample = self.sample()?;
+        let online =
+            sample.tcp_connections > 0 || sample.udp_datagrams_per_sec() >= self.udp_threshold;
+        debug!(
+            "Port {}: {} TCP connections, {:.1} UDP datagrams/s -> players likely online: {}",
+            self.port,
+            sample.tcp_connections,
+            sample.udp_datagrams_per_sec(),
+            online
+        );
+        Ok(online)
+    }
+
+    fn count_tcp_connections(&self) -> io::Result<usize> {
+        let mut count = 0;
+        for table in ["tcp", "tcp6"] {
+            let path = self.proc_root.join("net").join(table);
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                // IPv6 may be disabled, in which case tcp6 does not exist.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            count += contents
+                .lines()
+                .skip(1)
+                .filter(|line| self.is_established_on_port(line))
+                .count();
+        }
+        Ok(count)
+    }
+
+    fn is_established_on_port(&self, line: &str) -> bool {
+        let mut fields = line.split_whitespace().skip(1);
+        let (Some(local), Some(_remote), Some(state)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return false;
+        };
+        state == TCP_ESTABLISHED
+            && local
+                .rsplit_once(':')
+                .and_then(|(_, port)| u16::from_str_radix(port, 16).ok())
+                == Some(self.port)
+    }
+
+    fn read_udp_in_datagrams(&self) -> io::Result<u64> {
+        let path = self.proc_root.join("net").join("snmp");
+        let contents = fs::read_to_string(&path)?;
+        parse_udp_in_datagrams(&contents).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No Udp InDatagrams counter in {}", path.display()),
+            )
+        })
+    }
+}
+
+/// Extracts `Udp: InDatagrams` from the header/value line pair in `/proc/net/snmp`.
+fn parse_udp_in_datagrams(snmp: &str) -> Option<u64> {
+    let mut udp_lines = snmp.lines().filter(|line| line.starts_with("Udp:"));
+    let header = udp_lines.next()?;
+    let values = udp_lines.next()?;
+    let index = header
+        .split_whitespace()
+        .position(|field| field == "InDatagrams")?;
+    values.split_whitespace().nth(index)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use tempfile::tempdir;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:3B82 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:3B82 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:1F90 0100007F:D432 01 00000000:00000000 00:00000000 00000000  1000        0 3 1 0000000000000000 20 4 30 10 -1
+";
+
+    fn snmp(in_datagrams: u64) -> String {
+        format!("Udp: InDatagrams NoPorts InErrors OutDatagrams\nUdp: {in_datagrams} 0 0 10\n")
+    }
+
+    #[test]
+    fn counts_established_tcp_connections_on_port_only() {
+        let proc_root = tempdir().unwrap();
+        fs::create_dir_all(proc_root.path().join("net")).unwrap();
+        fs::write(proc_root.path().join("net/tcp"), TCP).unwrap();
+        fs::write(proc_root.path().join("net/snmp"), snmp(0)).unwrap();
+
+        // 0x3B82 == 15234
+        let mut sampler = TrafficSampler::new(15234).with_proc_root(proc_root.path());
+        assert_eq!(sampler.sample().unwrap().tcp_connections, 1);
+        assert!(sampler.players_likely_online().unwrap());
+    }
+
+    #[test]
+    fn udp_datagrams_are_reported_as_deltas() {
+        let proc_root = tempdir().unwrap();
+        fs::create_dir_all(proc_root.path().join("net")).unwrap();
+        fs::write(proc_root.path().join("net/snmp"), snmp(100)).unwrap();
+
+        let mut sampler = TrafficSampler::new(8211)
+            .with_proc_root(proc_root.path())
+            .with_udp_threshold(0.0);
+        assert_eq!(sampler.sample().unwrap().udp_datagrams, 0);
+
+        fs::write(proc_root.path().join("net/snmp"), snmp(150)).unwrap();
+        let sample = sampler.sample().unwrap();
+        assert_eq!(sample.udp_datagrams, 50);
+        assert_eq!(sample.tcp_connections, 0);
+    }
+
+    #[test]
+    fn parse_udp_in_datagrams_handles_missing_counter() {
+        assert_eq!(parse_udp_in_datagrams(&snmp(42)), Some(42));
+        assert_eq!(parse_udp_in_datagrams("Tcp: RtoAlgorithm\nTcp: 1\n"), None);
+    }
+}
@@ -0,0 +1,305 @@
+//! This module provides a watchdog that keeps log monitors running and notices servers that stop logging.
+//!
+//! A [`MonitorWatchdog`] starts a monitor thread for each log file plus a thread of its own that checks on them
+//! periodically. A monitor whose thread has ended, e.g. because it panicked, is restarted and followed from the
+//! current end of its file. When set up with [`MonitorWatchdog::on_quiet`], a log file that has produced no lines for
+//! a while is reported as a server that might be hung, once until it logs again.
+//!
+//! ```rust,no_run
+//! use gsm_monitor::{LogRules, MonitorWatchdog};
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! let monitor = MonitorWatchdog::default()
+//!     .on_quiet(Duration::from_secs(15 * 60), |path, quiet_for| {
+//!         println!("{} logged nothing for {}s", path.display(), quiet_for.as_secs());
+//!     })
+//!     .start_instance(Path::new("/home/steam/palworld"), LogRules::default());
+//! ```
+
+use crate::constants::INSTANCE_TARGET;
+use crate::monitor::{Monitor, MonitorHandle, sleep_unless_stopped, spawn_thread};
+use crate::rules::LogRules;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often a watchdog checks on its monitors by default.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+type QuietHandler = Arc<dyn Fn(&Path, Duration) + Send + Sync>;
+
+/// When a monitored log file last produced a line.
+pub struct Activity {
+    last: Mutex<Instant>,
+}
+
+impl Activity {
+    fn new() -> Self {
+        Self {
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Records that the file produced a line.
+    pub fn touch(&self) {
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Returns how long ago the file last produced a line, or monitoring started.
+    fn idle(&self) -> Duration {
+        self.last
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed()
+    }
+}
+
+/// Starts log monitors and supervises them: dead monitors are restarted and, optionally,
+/// quiet log files are reported.
+#[derive(Clone)]
+pub struct MonitorWatchdog {
+    check_interval: Duration,
+    quiet: Option<(Duration, QuietHandler)>,
+}
+
+impl Default for MonitorWatchdog {
+    fn default() -> Self {
+        Self {
+            check_interval: CHECK_INTERVAL,
+            quiet: None,
+        }
+    }
+}
+
+/// A log file the watchdog supervises the monitor of.
+struct Watched {
+    path: PathBuf,
+    /// Whether the file going quiet is reported.
+    checked: bool,
+    activity: Arc<Activity>,
+    thread: Option<JoinHandle<()>>,
+    quiet: bool,
+}
+
+impl Watched {
+    fn new(path: PathBuf, checked: bool) -> Self {
+        Self {
+            path,
+            checked,
+            activity: Arc::new(Activity::new()),
+            thread: None,
+            quiet: false,
+        }
+    }
+
+    /// Starts a monitor for the file, following it from its end.
+    fn spawn(&self, monitor: &Monitor, stop: &Arc<AtomicBool>) -> Option<JoinHandle<()>> {
+        let monitor = monitor.with_activity(Arc::clone(&self.activity));
+        let path = self.path.clone();
+        let stop = Arc::clone(stop);
+        let name = format!("log-monitor-{}", path.display());
+        spawn_thread(name, move || monitor.run_until(&path, &stop))
+    }
+}
+
+impl MonitorWatchdog {
+    /// Checks on the monitors every `interval` instead of every 10 seconds.
+    #[must_use]
+    pub const fn check_every(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Calls `handler` with a log file's path and how long it has been quiet once it has
+    /// produced no lines for `after`, as the log of a hung server does. The handler is
+    /// called again only after the file has logged something in between.
+    #[must_use]
+    pub fn on_quiet<H>(mut self, after: Duration, handler: H) -> Self
+    where
+        H: Fn(&Path, Duration) + Send + Sync + 'static,
+    {
+        self.quiet = Some((after, Arc::new(handler)));
+        self
+    }
+
+    /// Watches each of `files` with `rules` under the watchdog, until the returned handle
+    /// stops them.
+    pub fn start(self, files: impl IntoIterator<Item = PathBuf>, rules: LogRules) -> MonitorHandle {
+        let watched = files
+            .into_iter()
+            .map(|path| Watched::new(path, true))
+            .collect();
+        self.supervise(watched, rules)
+    }
+
+    /// Watches the `server.log` and `server.err` of the instance in `working_dir` with
+    /// `rules` under the watchdog, like
+    /// [`start_instance_log_monitor`](crate::start_instance_log_monitor). Only
+    /// `server.log` is reported when quiet, as a healthy server rarely writes errors.
+    pub fn start_instance(self, working_dir: &Path, rules: LogRules) -> MonitorHandle {
+        let log_dir = working_dir.join("logs");
+        info!(target: INSTANCE_TARGET,
+            "Starting supervised instance log monitor for logs in: {}",
+            log_dir.display()
+        );
+        let watched = vec![
+            Watched::new(log_dir.join("server.log"), true),
+            Watched::new(log_dir.join("server.err"), false),
+        ];
+        self.supervise(watched, rules)
+    }
+
+    fn supervise(self, mut watched: Vec<Watched>, rules: LogRules) -> MonitorHandle {
+        let mut handle = MonitorHandle::default();
+        let stop = Arc::clone(&handle.stop);
+        let monitor = Monitor::new(rules);
+        for file in &mut watched {
+            file.thread = file.spawn(&monitor, &stop);
+        }
+
+        handle
+            .threads
+            .extend(spawn_thread("log-watchdog".to_owned(), move || {
+                while sleep_unless_stopped(self.check_interval, &stop) {
+                    self.check(&mut watched, &monitor, &stop);
+                }
+                for thread in watched.into_iter().filter_map(|file| file.thread) {
+                    let _ = thread.join();
+                }
+            }));
+        handle
+    }
+
+    /// Restarts the monitors that have ended and reports the files that went quiet.
+    fn check(&self, watched: &mut [Watched], monitor: &Monitor, stop: &Arc<AtomicBool>) {
+        for file in watched {
+            if file.thread.as_ref().is_none_or(JoinHandle::is_finished) {
+                let panicked = file
+                    .thread
+                    .take()
+                    .is_some_and(|thread| thread.join().is_err());
+                warn!(target: INSTANCE_TARGET,
+                    "Log monitor for {} {}; restarting it",
+                    file.path.display(),
+                    if panicked { "panicked" } else { "stopped" }
+                );
+                file.thread = file.spawn(monitor, stop);
+            }
+
+            let Some((after, handler)) = self.quiet.as_ref().filter(|_| file.checked) else {
+                continue;
+            };
+            let idle = file.activity.idle();
+            if idle < *after {
+                if file.quiet {
+                    info!(target: INSTANCE_TARGET, "{} is logging again", file.path.display());
+                }
+                file.quiet = false;
+            } else if !file.quiet {
+                file.quiet = true;
+                warn!(target: INSTANCE_TARGET,
+                    "{} has logged nothing for {}s; the server might be hung",
+                    file.path.display(),
+                    idle.as_secs()
+                );
+                handler(&file.path, idle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use tempfile::tempdir;
+
+    fn append(path: &Path, line: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        writeln!(file, "{line}").unwrap();
+    }
+
+    #[test]
+    fn quiet_files_are_reported_once_until_they_log_again() {
+        let temp = tempdir().unwrap();
+        let log_path = temp.path().join("server.log");
+        fs::write(&log_path, "").unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&reports);
+
+        let monitor = MonitorWatchdog::default()
+            .check_every(Duration::from_millis(50))
+            .on_quiet(Duration::from_millis(300), move |path, _| {
+                recorder
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(path.to_owned());
+            })
+            .start([log_path.clone()], LogRules::new());
+        let reported = || reports.lock().unwrap_or_else(PoisonError::into_inner).len();
+
+        thread::sleep(Duration::from_millis(600));
+        assert_eq!(reported(), 1);
+        append(&log_path, "still alive");
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(reported(), 1);
+        thread::sleep(Duration::from_millis(450));
+        assert_eq!(reported(), 2);
+
+        assert!(monitor.stop_and_join());
+        assert!(
+            reports
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .all(|path| *path == log_path)
+        );
+    }
+
+    #[test]
+    fn ended_monitors_are_restarted() {
+        let temp = tempdir().unwrap();
+        let log_path = temp.path().join("server.log");
+        fs::write(&log_path, "").unwrap();
+        let hit = Arc::new(AtomicBool::new(false));
+        let recorder = Arc::clone(&hit);
+        let rules = LogRules::new();
+        rules.add_rule(
+            |line| line.contains("SENTINEL"),
+            move |_| recorder.store(true, Ordering::SeqCst),
+            true,
+            None,
+        );
+
+        let monitor = Monitor::new(rules);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut watched = [Watched::new(log_path.clone(), true)];
+        if let Some(file) = watched.first_mut() {
+            file.thread = Some(thread::spawn(|| {}));
+        }
+        thread::sleep(Duration::from_millis(50));
+
+        MonitorWatchdog::default().check(&mut watched, &monitor, &stop);
+        thread::sleep(Duration::from_millis(100));
+        append(&log_path, "line with SENTINEL keyword");
+        thread::sleep(Duration::from_millis(300));
+
+        assert!(
+            hit.load(Ordering::SeqCst),
+            "restarted monitor should read the line"
+        );
+        stop.store(true, Ordering::SeqCst);
+        for file in watched {
+            assert!(file.thread.unwrap().join().is_ok());
+        }
+    }
+}
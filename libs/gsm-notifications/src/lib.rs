@@ -20,11 +20,13 @@
 //! ```
 
 pub mod notifications;
+pub mod patch_notes;
 
-use reqwest::blocking::Client;
+use gsm_shared::http::{self, HttpError};
 use serde::Serialize;
 use std::error::Error;
 use std::fmt;
+use std::sync::{LazyLock, PoisonError, RwLock};
 
 /// Custom error type for notifications.
 #[derive(Debug)]
@@ -33,6 +35,7 @@ pub enum NotificationError {
     InvalidWebhookUrl(String),
     SerializationError(serde_json::Error),
     DispatcherNotFound(String),
+    DispatchFailed(String),
 }
 
 impl fmt::Display for NotificationError {
@@ -44,6 +47,7 @@ impl fmt::Display for NotificationError {
             Self::DispatcherNotFound(url) => {
                 write!(f, "No dispatcher for webhook URL: {url}")
             }
+            Self::DispatchFailed(reason) => write!(f, "Dispatch failed: {reason}"),
         }
     }
 }
@@ -56,6 +60,15 @@ impl From<reqwest::Error> for NotificationError {
     }
 }
 
+impl From<HttpError> for NotificationError {
+    fn from(err: HttpError) -> Self {
+        match err {
+            HttpError::Request(err) => Self::HttpError(err),
+            other => Self::DispatchFailed(other.to_string()),
+        }
+    }
+}
+
 impl From<serde_json::Error> for NotificationError {
     fn from(err: serde_json::Error) -> Self {
         Self::SerializationError(err)
@@ -144,9 +157,7 @@ impl NotificationDispatcher for GenericDispatcher {
             message: message.to_owned(),
             data,
         };
-        let client = Client::new();
-        let response = client.post(webhook_url).json(&payload).send()?;
-        response.error_for_status()?;
+        http::send(|client| client.post(webhook_url).json(&payload))?;
         Ok(())
     }
 }
@@ -170,9 +181,7 @@ impl NotificationDispatcher for DiscordDispatcher {
                 color: get_discord_color(notification_type),
             }],
         };
-        let client = Client::new();
-        let response = client.post(webhook_url).json(&payload).send()?;
-        response.error_for_status()?;
+        http::send(|client| client.post(webhook_url).json(&payload))?;
         Ok(())
     }
 }
@@ -224,6 +233,25 @@ fn default_registry() -> DispatcherRegistry {
     registry
 }
 
+/// Dispatchers registered at runtime, consulted before the built-in ones.
+static CUSTOM_DISPATCHERS: LazyLock<RwLock<DispatcherRegistry>> =
+    LazyLock::new(|| RwLock::new(DispatcherRegistry::new()));
+
+/// Registers an additional dispatcher for webhook URLs matching `predicate`.
+///
+/// Registered dispatchers take precedence over the built-in Discord and generic ones,
+/// in registration order. This lets extensions handle their own URL schemes (for
+/// example `plugin://name`) without changing this crate.
+pub fn register_dispatcher<F>(predicate: F, dispatcher: Box<dyn NotificationDispatcher>)
+where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+    CUSTOM_DISPATCHERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register(predicate, dispatcher);
+}
+
 /// Sends a notification to the given webhook URL.
 ///
 /// It converts any extra data into a JSON value and selects the appropriate dispatcher
@@ -249,11 +277,19 @@ pub fn send_notification<T: Serialize>(
     data: Option<T>,
 ) -> Result<(), NotificationError> {
     validate_webhook_url(webhook_url)?;
-    let registry = default_registry();
     let data_value = match data {
         Some(d) => Some(serde_json::to_value(d)?),
         None => None,
     };
+    let custom = CUSTOM_DISPATCHERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, dispatcher)) = custom.get_dispatcher(webhook_url) {
+        return dispatcher.send_payload(webhook_url, notification_type, message, data_value);
+    }
+    drop(custom);
+
+    let registry = default_registry();
     if let Some((_, dispatcher)) = registry.get_dispatcher(webhook_url) {
         dispatcher.send_payload(webhook_url, notification_type, message, data_value)
     } else {
@@ -372,6 +408,35 @@ mod tests {
         assert_eq!(get_discord_color("custom"), 0x007F66);
     }
 
+    struct RecordingDispatcher(mpsc::Sender<String>);
+
+    impl NotificationDispatcher for RecordingDispatcher {
+        fn send_payload(
+            &self,
+            _webhook_url: &str,
+            notification_type: &str,
+            message: &str,
+            _data: Option<serde_json::Value>,
+        ) -> Result<(), NotificationError> {
+            self.0
+                .send(format!("{notification_type}: {message}"))
+                .unwrap();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registered_dispatcher_takes_precedence() {
+        let (tx, rx) = mpsc::channel();
+        register_dispatcher(
+            |url| url.starts_with("recording://"),
+            Box::new(RecordingDispatcher(tx)),
+        );
+
+        send_notification::<()>("recording://test", "INFO", "custom", None).unwrap();
+        assert_eq!(rx.recv().unwrap(), "INFO: custom");
+    }
+
     #[test]
     fn registry_and_validation_choose_expected_dispatcher() {
         assert!(matches!(
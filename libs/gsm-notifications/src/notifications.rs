@@ -1,6 +1,8 @@
+use crate::patch_notes::{PatchNotes, fetch_patch_notes, update_note};
 use crate::{NotificationError, send_notification};
 use gsm_shared::fetch_var;
-use tracing::debug;
+use std::time::Duration;
+use tracing::{debug, warn};
 
 pub enum StandardServerEvents {
     PlayerJoined(String),
@@ -8,6 +10,39 @@ pub enum StandardServerEvents {
     Started,
     Stopping,
     Stopped,
+    /// The server came back up after an update; carries the installed build and,
+    /// when available, the latest Steam patch notes headline.
+    Updated {
+        build_id: String,
+        patch_notes: Option<PatchNotes>,
+    },
+    BackupStarted,
+    /// A backup finished; `size` is the archive size in bytes.
+    BackupCompleted {
+        size: u64,
+        duration: Duration,
+    },
+    BackupFailed {
+        error: String,
+    },
+    /// A countdown warning sent before a scheduled restart, e.g. "The server will
+    /// restart in 5 minutes."
+    RestartWarning(String),
+    /// A scheduled job, such as `auto-update`, failed after all of its retries.
+    JobFailed {
+        job: String,
+        error: String,
+    },
+    /// A chat message sent in game, relayed from the server logs.
+    ChatMessage {
+        player: String,
+        message: String,
+    },
+    /// The server log produced no lines for `quiet_for`; the server might be hung.
+    Unresponsive {
+        log: String,
+        quiet_for: Duration,
+    },
 }
 
 /// Sends notifications based on the server event.
@@ -34,44 +69,144 @@ pub fn send_notifications(event: StandardServerEvents) -> Result<(), Notificatio
         debug!("Skipping notification, WEBHOOK_URL is not present.");
         return Ok(());
     }
+    let (title, message) = describe(event);
+    send_notification::<Option<String>>(
+        &webhook_url,
+        &format!("{server_name}: {title}"),
+        &message,
+        None,
+    )
+}
+
+/// Sends `event` like [`send_notifications`], logging a failure instead of returning it.
+pub fn notify(event: StandardServerEvents) {
+    if let Err(e) = send_notifications(event) {
+        warn!("Failed to send webhook notification: {e}");
+    }
+}
+
+/// Returns the title, without the server name, and the message announcing `event`.
+fn describe(event: StandardServerEvents) -> (&'static str, String) {
     match event {
-        StandardServerEvents::PlayerJoined(name) => send_notification::<Option<String>>(
-            &webhook_url,
-            &format!("{server_name}: Player Joined"),
-            &format!("Player {name} has joined the adventure!"),
-            None,
+        StandardServerEvents::PlayerJoined(name) => (
+            "Player Joined",
+            format!("Player {name} has joined the adventure!"),
+        ),
+        StandardServerEvents::PlayerLeft(name) => (
+            "Player Left",
+            format!("Player {name} has left the adventure."),
+        ),
+        StandardServerEvents::Started => (
+            "Server Started",
+            "The server has started successfully.".to_owned(),
+        ),
+        StandardServerEvents::Stopping => (
+            "Server Stopping",
+            "The server is shutting down gracefully.".to_owned(),
+        ),
+        StandardServerEvents::Stopped => {
+            ("Server Stopped", "The server has been stopped.".to_owned())
+        }
+        StandardServerEvents::Updated {
+            build_id,
+            patch_notes,
+        } => (
+            "Server Updated",
+            update_note(&build_id, patch_notes.as_ref()),
         ),
-        StandardServerEvents::PlayerLeft(name) => send_notification::<Option<String>>(
-            &webhook_url,
-            &format!("{server_name}: Player Left"),
-            &format!("Player {name} has left the adventure."),
-            None,
+        StandardServerEvents::BackupStarted => (
+            "Backup Started",
+            "A backup of the world is in progress.".to_owned(),
         ),
-        StandardServerEvents::Started => send_notification::<Option<String>>(
-            &webhook_url,
-            &format!("{server_name}: Server Started"),
-            "The server has started successfully.",
-            None,
+        StandardServerEvents::BackupCompleted { size, duration } => (
+            "Backup Completed",
+            format!(
+                "Backup finished: {} in {}s.",
+                format_size(size),
+                duration.as_secs()
+            ),
         ),
-        StandardServerEvents::Stopping => send_notification::<Option<String>>(
-            &webhook_url,
-            &format!("{server_name}: Server Stopping"),
-            "The server is shutting down gracefully.",
-            None,
+        StandardServerEvents::BackupFailed { error } => (
+            "Backup Failed",
+            format!("The backup could not be completed: {error}"),
         ),
-        StandardServerEvents::Stopped => send_notification::<Option<String>>(
-            &webhook_url,
-            &format!("{server_name}: Server Stopped"),
-            "The server has been stopped.",
-            None,
+        StandardServerEvents::RestartWarning(message) => ("Restart Scheduled", message),
+        StandardServerEvents::JobFailed { job, error } => (
+            "Scheduled Job Failed",
+            format!("The scheduled job '{job}' failed: {error}"),
         ),
+        StandardServerEvents::ChatMessage { player, message } => {
+            ("Chat", format!("{player}: {message}"))
+        }
+        StandardServerEvents::Unresponsive { log, quiet_for } => (
+            "Server Unresponsive",
+            format!(
+                "{log} has logged nothing for {} minutes; the server might be hung.",
+                quiet_for.as_secs() / 60
+            ),
+        ),
+    }
+}
+
+/// Formats a byte count with a binary unit suffix, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut unit = "B";
+    let mut tenths = bytes.saturating_mul(10);
+    for next in UNITS {
+        if tenths < 1024 * 10 {
+            break;
+        }
+        tenths /= 1024;
+        unit = next;
     }
+    format!("{}.{} {unit}", tenths / 10, tenths % 10)
+}
+
+/// Announces that the server came back up on `build_id` after an update, in-game through
+/// `broadcast` and over the webhook when `WEBHOOK_URL` is set.
+///
+/// The latest Steam patch notes for `app_id` are looked up once and included when
+/// available; a failed lookup is logged and the announcement is sent without them. A
+/// failed in-game broadcast is logged and does not stop the webhook notification.
+///
+/// # Errors
+///
+/// Returns any notification dispatch error produced while sending the announcement.
+pub fn send_update_notification(
+    app_id: u32,
+    build_id: String,
+    broadcast: impl FnOnce(&str) -> Result<(), String>,
+) -> Result<(), NotificationError> {
+    let patch_notes = fetch_patch_notes(app_id).unwrap_or_else(|e| {
+        warn!("Failed to fetch patch notes for app {app_id}: {e}");
+        None
+    });
+    if let Err(e) = broadcast(&update_note(&build_id, patch_notes.as_ref())) {
+        warn!("Failed to announce the update in-game: {e}");
+    }
+    if fetch_var("WEBHOOK_URL", "").is_empty() {
+        debug!("Skipping update notification, WEBHOOK_URL is not present.");
+        return Ok(());
+    }
+    send_notifications(StandardServerEvents::Updated {
+        build_id,
+        patch_notes,
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
+
     use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
     use std::sync::{Mutex, OnceLock};
+    use std::thread;
 
     fn env_lock() -> &'static Mutex<()> {
         static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
@@ -90,6 +225,73 @@ mod tests {
         assert!(send_notifications(StandardServerEvents::Stopped).is_ok());
         assert!(send_notifications(StandardServerEvents::PlayerJoined("Alice".to_owned())).is_ok());
         assert!(send_notifications(StandardServerEvents::PlayerLeft("Alice".to_owned())).is_ok());
+        assert!(
+            send_notifications(StandardServerEvents::Updated {
+                build_id: "12345".to_owned(),
+                patch_notes: None,
+            })
+            .is_ok()
+        );
+        assert!(send_notifications(StandardServerEvents::BackupStarted).is_ok());
+        assert!(
+            send_notifications(StandardServerEvents::BackupCompleted {
+                size: 1024,
+                duration: Duration::from_secs(3),
+            })
+            .is_ok()
+        );
+        assert!(
+            send_notifications(StandardServerEvents::BackupFailed {
+                error: "disk full".to_owned(),
+            })
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn update_is_announced_in_game_without_a_webhook() {
+        let _guard = env_lock()
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0_u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            let body =
+                r#"{"appnews":{"newsitems":[{"title":"Hotfix","url":"https://example.com/1"}]}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        unsafe {
+            std::env::remove_var("WEBHOOK_URL");
+            std::env::set_var("STEAM_NEWS_API_URL", format!("http://{address}/news"));
+        }
+
+        let mut announced = None;
+        let result = send_update_notification(42, "123".to_owned(), |message| {
+            announced = Some(message.to_owned());
+            Ok(())
+        });
+        unsafe { std::env::remove_var("STEAM_NEWS_API_URL") };
+
+        assert!(result.is_ok());
+        assert_eq!(
+            announced.as_deref(),
+            Some("Server updated to build 123: Hotfix https://example.com/1")
+        );
     }
 
     #[test]
@@ -0,0 +1,132 @@
+//! # Patch Notes
+//!
+//! Fetches the latest Steam news post for an app so that post-update restart
+//! notifications can tell players what changed.
+//!
+//! ```rust,no_run
+//! use gsm_notifications::patch_notes::fetch_patch_notes;
+//!
+//! if let Some(notes) = fetch_patch_notes(2_278_520)? {
+//!     println!("{} ({})", notes.title, notes.url);
+//! }
+//! # Ok::<(), gsm_notifications::NotificationError>(())
+//! ```
+
+use crate::NotificationError;
+use gsm_shared::{fetch_var, http};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Default Steam Web API endpoint for app news; can be overridden with `STEAM_NEWS_API_URL`.
+const STEAM_NEWS_API_URL: &str = "https://api.steampowered.com/ISteamNews/GetNewsForApp/v2/";
+
+/// How long the Steam news API may take to answer.
+const NEWS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The headline and link of the most recent Steam announcement for an app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchNotes {
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct NewsResponse {
+    appnews: AppNews,
+}
+
+#[derive(Deserialize)]
+struct AppNews {
+    #[serde(default)]
+    newsitems: Vec<NewsItem>,
+}
+
+#[derive(Deserialize)]
+struct NewsItem {
+    title: String,
+    url: String,
+}
+
+/// Fetches the latest community announcement for `app_id` from the Steam news API.
+///
+/// Returns `Ok(None)` when Steam has no announcements for the app.
+///
+/// # Errors
+///
+/// Returns an error when the request fails, Steam responds with a failure status,
+/// or the response body cannot be parsed.
+pub fn fetch_patch_notes(app_id: u32) -> Result<Option<PatchNotes>, NotificationError> {
+    let endpoint = fetch_var("STEAM_NEWS_API_URL", STEAM_NEWS_API_URL);
+    let url = format!("{endpoint}?appid={app_id}&count=1&feeds=steam_community_announcements");
+    let body = http::send(|client| client.get(&url).timeout(NEWS_TIMEOUT))?.text()?;
+    parse_news_response(&body)
+}
+
+/// Parses a `GetNewsForApp` response body, returning the first news item.
+fn parse_news_response(body: &str) -> Result<Option<PatchNotes>, NotificationError> {
+    let response: NewsResponse = serde_json::from_str(body)?;
+    Ok(response
+        .appnews
+        .newsitems
+        .into_iter()
+        .next()
+        .map(|item| PatchNotes {
+            title: item.title.trim().to_owned(),
+            url: item.url,
+        }))
+}
+
+/// Formats the short "server updated" note posted after an update restart.
+pub fn update_note(build_id: &str, patch_notes: Option<&PatchNotes>) -> String {
+    let build = if build_id.is_empty() {
+        String::from("the latest build")
+    } else {
+        format!("build {build_id}")
+    };
+    patch_notes.map_or_else(
+        || format!("Server updated to {build}."),
+        |notes| format!("Server updated to {build}: {} {}", notes.title, notes.url),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn parse_news_response_returns_first_item() {
+        let body = r#"{"appnews":{"appid":2278520,"newsitems":[
+            {"gid":"1","title":" Hotfix #3 ","url":"https://store.steampowered.com/news/1"},
+            {"gid":"2","title":"Older","url":"https://store.steampowered.com/news/2"}
+        ]}}"#;
+
+        let notes = parse_news_response(body).unwrap().unwrap();
+        assert_eq!(notes.title, "Hotfix #3");
+        assert_eq!(notes.url, "https://store.steampowered.com/news/1");
+    }
+
+    #[test]
+    fn parse_news_response_handles_missing_items() {
+        assert_eq!(
+            parse_news_response(r#"{"appnews":{"appid":1}}"#).unwrap(),
+            None
+        );
+        assert!(parse_news_response("not json").is_err());
+    }
+
+    #[test]
+    fn update_note_includes_headline_and_link() {
+        let notes = PatchNotes {
+            title: String::from("Hotfix #3"),
+            url: String::from("https://example.com/n/1"),
+        };
+
+        assert_eq!(
+            update_note("12345", Some(&notes)),
+            "Server updated to build 12345: Hotfix #3 https://example.com/n/1"
+        );
+        assert_eq!(update_note("", None), "Server updated to the latest build.");
+    }
+}
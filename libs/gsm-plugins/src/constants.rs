@@ -0,0 +1,3 @@
+pub const GSM_PLUGIN_DIR: &str = "GSM_PLUGIN_DIR";
+
+pub const DEFAULT_PLUGIN_DIR: &str = "/home/steam/plugins";
@@ -0,0 +1,312 @@
+//! Discovering plugins and connecting them to log rules, notifications and lifecycle events.
+
+use crate::PluginError;
+use crate::constants::{DEFAULT_PLUGIN_DIR, GSM_PLUGIN_DIR};
+use crate::process::PluginProcess;
+use crate::protocol::{HostMessage, LifecycleEvent, PluginManifest};
+use gsm_monitor::LogRules;
+use gsm_notifications::{NotificationDispatcher, NotificationError, register_dispatcher};
+use gsm_shared::fetch_var;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+
+/// URL scheme that routes a notification to the plugin of the same name.
+pub const PLUGIN_URL_SCHEME: &str = "plugin://";
+
+/// The set of plugins loaded for one server.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Arc<PluginProcess>>,
+}
+
+impl PluginHost {
+    /// Loads every plugin in `GSM_PLUGIN_DIR` (default `/home/steam/plugins`).
+    ///
+    /// A missing directory simply means no plugins are installed.
+    pub fn from_env(game: &str) -> Self {
+        let dir = PathBuf::from(fetch_var(GSM_PLUGIN_DIR, DEFAULT_PLUGIN_DIR));
+        Self::load_dir(&dir, game)
+    }
+
+    /// Starts every executable file in `dir` as a plugin.
+    ///
+    /// Plugins are started in file name order. A plugin that fails to start is logged
+    /// and skipped so it cannot keep the server from running.
+    pub fn load_dir(dir: &Path, game: &str) -> Self {
+        let mut host = Self::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("No plugins loaded from {}: {e}", dir.display());
+                return host;
+            }
+        };
+
+        let mut executables: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| is_executable(path))
+            .collect();
+        executables.sort();
+
+        for path in executables {
+            if let Err(e) = host.load(&path, game) {
+                error!("Failed to load plugin {}: {e}", path.display());
+            }
+        }
+        host
+    }
+
+    /// Starts a single plugin executable and adds it to the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin cannot be started or fails the handshake, or if
+    /// another loaded plugin already uses the same name.
+    pub fn load(&mut self, path: &Path, game: &str) -> Result<(), PluginError> {
+        let plugin = PluginProcess::spawn(path, game)?;
+        if self.manifests().any(|m| m.name == plugin.manifest().name) {
+            return Err(PluginError::DuplicateName(plugin.manifest().name.clone()));
+        }
+        self.plugins.push(Arc::new(plugin));
+        Ok(())
+    }
+
+    /// Manifests of the loaded plugins.
+    pub fn manifests(&self) -> impl Iterator<Item = &PluginManifest> {
+        self.plugins.iter().map(|plugin| plugin.manifest())
+    }
+
+    /// Returns whether no plugins are loaded.
+    pub const fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Adds a rule per plugin that forwards matching log lines to it.
+    ///
+    /// Plugin rules run before every other rule and never stop processing, so plugins
+    /// observe lines without changing how the server's own rules handle them.
+    pub fn register_log_rules(&self, rules: &LogRules) {
+        for plugin in &self.plugins {
+            let patterns = plugin.manifest().log_patterns.clone();
+            if patterns.is_empty() {
+                continue;
+            }
+            let plugin = Arc::clone(plugin);
+            rules.add_rule(
+                move |line| {
+                    patterns
+                        .iter()
+                        .any(|pattern| line.contains(pattern.as_str()))
+                },
+                move |line| {
+                    let _ = plugin.send(&HostMessage::LogLine {
+                        line: line.to_owned(),
+                    });
+                },
+                false,
+                Some(i32::MIN),
+            );
+        }
+    }
+
+    /// Registers a notification dispatcher for each plugin that handles notifications,
+    /// reachable through the webhook URL `plugin://<name>`.
+    pub fn register_dispatchers(&self) {
+        for plugin in &self.plugins {
+            if !plugin.manifest().notifications {
+                continue;
+            }
+            let url = format!("{PLUGIN_URL_SCHEME}{}", plugin.manifest().name);
+            debug!("Registering notification dispatcher for {url}");
+            register_dispatcher(
+                move |webhook_url| webhook_url.trim_end_matches('/') == url,
+                Box::new(PluginDispatcher(Arc::clone(plugin))),
+            );
+        }
+    }
+
+    /// Forwards a lifecycle event to every plugin subscribed to it.
+    pub fn emit_lifecycle(&self, event: LifecycleEvent) {
+        for plugin in &self.plugins {
+            if plugin.manifest().lifecycle.contains(&event)
+                && let Err(e) = plugin.send(&HostMessage::Lifecycle { event })
+            {
+                warn!("Failed to deliver {event:?} to plugin: {e}");
+            }
+        }
+    }
+
+    /// Shuts down every plugin.
+    pub fn shutdown(&self) {
+        for plugin in &self.plugins {
+            plugin.shutdown();
+        }
+    }
+}
+
+struct PluginDispatcher(Arc<PluginProcess>);
+
+impl NotificationDispatcher for PluginDispatcher {
+    fn send_payload(
+        &self,
+        _webhook_url: &str,
+        notification_type: &str,
+        message: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<(), NotificationError> {
+        self.0
+            .send(&HostMessage::Notification {
+                notification_type: notification_type.to_owned(),
+                message: message.to_owned(),
+                data,
+            })
+            .map_err(|e| NotificationError::DispatchFailed(e.to_string()))
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::process::SEND_QUEUE_CAPACITY;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    /// Writes a shell plugin that announces `manifest` and appends every message it
+    /// receives to `received`.
+    fn write_plugin(dir: &Path, name: &str, manifest: &str, received: &Path) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(
+            &path,
+            format!(
+                "#!/bin/sh\nread hello\necho '{manifest}'\nwhile read line; do echo \"$line\" >> {}; done\n",
+                received.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn wait_for_contents(path: &Path, needle: &str) -> String {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let contents = fs::read_to_string(path).unwrap_or_default();
+            if contents.contains(needle) || Instant::now() > deadline {
+                return contents;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn loads_executables_and_forwards_matching_log_lines() {
+        let dir = tempdir().unwrap();
+        let received = dir.path().join("received.log");
+        write_plugin(
+            dir.path(),
+            "audit",
+            r#"{"type":"manifest","log_patterns":["joined"],"lifecycle":["started"]}"#,
+            &received,
+        );
+        fs::write(dir.path().join("README.txt"), "not a plugin").unwrap();
+
+        let host = PluginHost::load_dir(dir.path(), "test");
+        assert_eq!(
+            host.manifests()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["audit"]
+        );
+
+        let rules = LogRules::new();
+        host.register_log_rules(&rules);
+        for rule in rules.get_rules() {
+            if (rule.matcher)("Player joined") {
+                (rule.action)("Player joined");
+            }
+        }
+        host.emit_lifecycle(LifecycleEvent::Started);
+        host.emit_lifecycle(LifecycleEvent::Stopped);
+
+        let contents = wait_for_contents(&received, "\"started\"");
+        assert!(contents.contains(r#""line":"Player joined""#), "{contents}");
+        assert!(contents.contains(r#""event":"started""#), "{contents}");
+        assert!(!contents.contains("stopped"), "{contents}");
+        host.shutdown();
+    }
+
+    #[test]
+    fn routes_plugin_urls_to_the_plugin() {
+        let dir = tempdir().unwrap();
+        let received = dir.path().join("received.log");
+        write_plugin(
+            dir.path(),
+            "relay",
+            r#"{"type":"manifest","notifications":true}"#,
+            &received,
+        );
+
+        let host = PluginHost::load_dir(dir.path(), "test");
+        host.register_dispatchers();
+        gsm_notifications::send_notification::<()>("plugin://relay", "INFO", "routed", None)
+            .unwrap();
+
+        let contents = wait_for_contents(&received, "routed");
+        assert!(contents.contains(r#""type":"notification""#), "{contents}");
+        assert!(contents.contains(r#""message":"routed""#), "{contents}");
+        host.shutdown();
+    }
+
+    #[test]
+    fn drops_messages_instead_of_blocking_on_a_stalled_plugin() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stalled");
+        fs::write(
+            &path,
+            "#!/bin/sh\nread hello\necho '{\"type\":\"manifest\"}'\nexec sleep 30\n",
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let host = PluginHost::load_dir(dir.path(), "test");
+        let plugin = host.plugins.first().unwrap();
+        let line = "x".repeat(1024);
+        let started = Instant::now();
+        let dropped = (0..2 * SEND_QUEUE_CAPACITY + 128)
+            .map(|_| plugin.send(&HostMessage::LogLine { line: line.clone() }))
+            .filter(|sent| matches!(sent, Err(PluginError::QueueFull(_))))
+            .count();
+
+        assert!(dropped > 0);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        host.shutdown();
+    }
+
+    #[test]
+    fn skips_plugins_that_fail_the_handshake() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("silent");
+        fs::write(&path, "#!/bin/sh\nexit 0\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let host = PluginHost::load_dir(dir.path(), "test");
+        assert!(host.is_empty());
+    }
+
+    #[test]
+    fn missing_directory_loads_nothing() {
+        let host = PluginHost::load_dir(Path::new("/nonexistent/gsm-plugins"), "test");
+        assert!(host.is_empty());
+    }
+}
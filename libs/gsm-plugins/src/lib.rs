@@ -0,0 +1,69 @@
+//! # gsm-plugins
+//!
+//! Lets third parties extend a server without forking the workspace. A plugin is any
+//! executable placed in `GSM_PLUGIN_DIR` (default `/home/steam/plugins`); it talks to the
+//! host over stdin/stdout using line-delimited JSON (see [`protocol`]).
+//!
+//! On start the host sends `hello`, and the plugin answers with a manifest naming the
+//! things it wants to receive:
+//!
+//! - `log_patterns`: server log lines containing any of these substrings.
+//! - `notifications`: notifications sent to the webhook URL `plugin://<name>`, which makes
+//!   the plugin a custom notification dispatcher.
+//! - `lifecycle`: server lifecycle events such as `started`, `stopped` or
+//!   `backup_completed`.
+//!
+//! Messages are queued for each plugin and written by a thread of its own, so a plugin
+//! that reads slowly never stalls the server; once [`SEND_QUEUE_CAPACITY`] messages are
+//! waiting, further ones are dropped with a warning.
+//!
+//! A minimal plugin in shell:
+//!
+//! ```sh
+//! #!/bin/sh
+//! read hello
+//! echo '{"type":"manifest","name":"joins","log_patterns":["joined"]}'
+//! while read message; do echo "$message" >> /home/steam/joins.log; done
+//! ```
+//!
+//! ## Usage
+//!
+//! ```rust,no_run
+//! use gsm_monitor::LogRules;
+//! use gsm_plugins::{LifecycleEvent, PluginHost};
+//!
+//! let rules = LogRules::default();
+//! let plugins = PluginHost::from_env("palworld");
+//! plugins.register_log_rules(&rules);
+//! plugins.register_dispatchers();
+//! plugins.emit_lifecycle(LifecycleEvent::Started);
+//! ```
+
+mod constants;
+mod host;
+mod process;
+pub mod protocol;
+
+pub use constants::{DEFAULT_PLUGIN_DIR, GSM_PLUGIN_DIR};
+pub use host::{PLUGIN_URL_SCHEME, PluginHost};
+pub use process::{HANDSHAKE_TIMEOUT, PluginProcess, SEND_QUEUE_CAPACITY};
+pub use protocol::{LifecycleEvent, PluginManifest};
+
+use thiserror::Error;
+
+/// Errors raised while starting or talking to a plugin.
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Plugin {0} did not send a manifest in time")]
+    HandshakeTimeout(String),
+    #[error("Plugin protocol error: {0}")]
+    Protocol(String),
+    #[error("A plugin named '{0}' is already loaded")]
+    DuplicateName(String),
+    #[error("Plugin '{0}' is no longer accepting messages")]
+    Closed(String),
+    #[error("Plugin '{0}' has too many unread messages; the message was dropped")]
+    QueueFull(String),
+}
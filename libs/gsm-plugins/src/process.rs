@@ -0,0 +1,249 @@
+//! Spawning a plugin executable and exchanging protocol messages with it.
+
+use crate::PluginError;
+use crate::protocol::{HostMessage, LogLevel, PROTOCOL_VERSION, PluginManifest, PluginMessage};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// How long a plugin has to answer `hello` with its manifest.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many messages may wait for a plugin to read them. Messages sent while the queue
+/// is full are dropped, so a slow plugin cannot stall the log monitor or the scheduler.
+pub const SEND_QUEUE_CAPACITY: usize = 256;
+
+/// How long a plugin has to exit after `shutdown` before it is killed.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// A running plugin executable.
+pub struct PluginProcess {
+    path: PathBuf,
+    manifest: PluginManifest,
+    child: Mutex<Child>,
+    /// Messages waiting for the writer thread, which owns the plugin's stdin.
+    queue: Mutex<Option<SyncSender<HostMessage>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl PluginProcess {
+    /// Starts the plugin at `path` and performs the handshake.
+    ///
+    /// A manifest without a name is named after the executable.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executable cannot be started, or if it does not answer
+    /// `hello` with a manifest within [`HANDSHAKE_TIMEOUT`].
+    pub fn spawn(path: &Path, game: &str) -> Result<Self, PluginError> {
+        let label = path.display().to_string();
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(PluginError::Protocol(format!(
+                "{label}: plugin stdio is unavailable"
+            )));
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let reader_label = label.clone();
+        thread::spawn(move || read_messages(stdout, &tx, &reader_label));
+
+        let hello = HostMessage::Hello {
+            version: PROTOCOL_VERSION,
+            game: game.to_owned(),
+        };
+        let manifest = write_message(&mut stdin, &hello)
+            .map_err(PluginError::from)
+            .and_then(|()| {
+                rx.recv_timeout(HANDSHAKE_TIMEOUT)
+                    .map_err(|_| PluginError::HandshakeTimeout(label.clone()))
+            });
+        let mut manifest = match manifest {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+
+        if manifest.name.is_empty() {
+            manifest.name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        info!(
+            "Loaded plugin '{}' from {} ({} log patterns, notifications: {}, lifecycle: {:?})",
+            manifest.name,
+            label,
+            manifest.log_patterns.len(),
+            manifest.notifications,
+            manifest.lifecycle
+        );
+
+        let (queue, messages) = mpsc::sync_channel(SEND_QUEUE_CAPACITY);
+        let writer_label = manifest.name.clone();
+        let writer = thread::spawn(move || write_messages(stdin, &messages, &writer_label));
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            manifest,
+            child: Mutex::new(child),
+            queue: Mutex::new(Some(queue)),
+            writer: Mutex::new(Some(writer)),
+        })
+    }
+
+    /// The manifest the plugin announced during the handshake.
+    pub const fn manifest(&self) -> &PluginManifest {
+        &self.manifest
+    }
+
+    /// The executable this plugin was started from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues a message for the plugin without waiting for the plugin to read it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PluginError::QueueFull`] if [`SEND_QUEUE_CAPACITY`] messages are already
+    /// waiting, in which case this one is dropped, and [`PluginError::Closed`] if the
+    /// plugin has been shut down or its stdin is closed.
+    pub fn send(&self, message: &HostMessage) -> Result<(), PluginError> {
+        let sender = self
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .ok_or_else(|| PluginError::Closed(self.manifest.name.clone()))?;
+        match sender.try_send(message.clone()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    "Plugin '{}' is not keeping up; dropping a message",
+                    self.manifest.name
+                );
+                Err(PluginError::QueueFull(self.manifest.name.clone()))
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                Err(PluginError::Closed(self.manifest.name.clone()))
+            }
+        }
+    }
+
+    /// Asks the plugin to exit, killing it if it has not exited after a short grace period.
+    pub fn shutdown(&self) {
+        let _ = self.send(&HostMessage::Shutdown);
+        // Closing the queue ends the writer thread once it has written what is queued,
+        // which closes stdin so plugins that simply read until EOF exit as well.
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+
+        let mut child = self.child.lock().unwrap_or_else(PoisonError::into_inner);
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        let mut exited = false;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    debug!("Plugin '{}' exited with {status}", self.manifest.name);
+                    exited = true;
+                    break;
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(50)),
+                Err(_) => break,
+            }
+        }
+        if !exited {
+            warn!(
+                "Plugin '{}' did not exit in time; killing it",
+                self.manifest.name
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        drop(child);
+
+        // A writer blocked on a full pipe fails once the plugin is gone.
+        let writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(writer) = writer
+            && writer.join().is_err()
+        {
+            warn!("Writer thread of plugin '{}' panicked", self.manifest.name);
+        }
+    }
+}
+
+impl Drop for PluginProcess {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Writes queued messages to the plugin's stdin until the queue closes or the plugin
+/// stops reading.
+fn write_messages(mut stdin: ChildStdin, messages: &Receiver<HostMessage>, name: &str) {
+    for message in messages {
+        if let Err(e) = write_message(&mut stdin, &message) {
+            warn!("Plugin '{name}' stopped accepting messages: {e}");
+            return;
+        }
+    }
+}
+
+fn write_message(writer: &mut impl Write, message: &HostMessage) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+/// Reads plugin messages until stdout closes. The first manifest is handed to the
+/// handshake through `manifest_tx`; log messages are forwarded to `tracing`.
+fn read_messages(stdout: ChildStdout, manifest_tx: &Sender<PluginManifest>, label: &str) {
+    for line in BufReader::new(stdout).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to read from plugin {label}: {e}");
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PluginMessage>(&line) {
+            Ok(PluginMessage::Manifest(manifest)) => {
+                if manifest_tx.send(manifest).is_err() {
+                    debug!("Ignoring repeated manifest from plugin {label}");
+                }
+            }
+            Ok(PluginMessage::Log { level, message }) => match level {
+                LogLevel::Debug => debug!("[plugin {label}] {message}"),
+                LogLevel::Info => info!("[plugin {label}] {message}"),
+                LogLevel::Warn => warn!("[plugin {label}] {message}"),
+                LogLevel::Error => error!("[plugin {label}] {message}"),
+            },
+            Err(e) => warn!("Ignoring malformed message from plugin {label}: {e}"),
+        }
+    }
+    debug!("Plugin {label} closed its output");
+}
@@ -0,0 +1,117 @@
+//! The wire protocol spoken between the host and a plugin process.
+//!
+//! Every message is a single line of JSON with a `type` field. The host writes
+//! [`HostMessage`]s to the plugin's stdin and reads [`PluginMessage`]s from its stdout;
+//! anything the plugin writes to stderr is passed through to the container log.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Protocol version announced in the `hello` message.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A message sent from the host to a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HostMessage {
+    /// First message of every session; the plugin must answer with a manifest.
+    Hello { version: u32, game: String },
+    /// A server log line matching one of the plugin's `log_patterns`.
+    LogLine { line: String },
+    /// A notification addressed to `plugin://<name>`.
+    Notification {
+        notification_type: String,
+        message: String,
+        data: Option<Value>,
+    },
+    /// A server lifecycle transition.
+    Lifecycle { event: LifecycleEvent },
+    /// The host is shutting down; the plugin should exit.
+    Shutdown,
+}
+
+/// A message sent from a plugin to the host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PluginMessage {
+    /// The plugin's answer to `hello`, describing what it wants to receive.
+    Manifest(PluginManifest),
+    /// A message for the host to write to its own log.
+    Log { level: LogLevel, message: String },
+}
+
+/// What a plugin subscribes to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginManifest {
+    /// Unique name; notifications to `plugin://<name>` are routed to this plugin.
+    pub name: String,
+    /// Substrings that select which server log lines are forwarded.
+    pub log_patterns: Vec<String>,
+    /// Whether the plugin handles notifications as a dispatcher.
+    pub notifications: bool,
+    /// Lifecycle events the plugin wants to receive.
+    pub lifecycle: Vec<LifecycleEvent>,
+}
+
+/// Server lifecycle transitions forwarded to plugins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    Starting,
+    Started,
+    Stopping,
+    Stopped,
+    Updated,
+    BackupStarted,
+    BackupCompleted,
+    BackupFailed,
+}
+
+/// Severity of a [`PluginMessage::Log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn host_messages_are_tagged_by_type() {
+        let line = serde_json::to_value(HostMessage::LogLine {
+            line: "Player joined".to_owned(),
+        })
+        .unwrap();
+        assert_eq!(line, json!({"type": "log_line", "line": "Player joined"}));
+
+        let lifecycle = serde_json::to_value(HostMessage::Lifecycle {
+            event: LifecycleEvent::Started,
+        })
+        .unwrap();
+        assert_eq!(lifecycle, json!({"type": "lifecycle", "event": "started"}));
+    }
+
+    #[test]
+    fn manifest_fields_are_optional() {
+        let message: PluginMessage =
+            serde_json::from_str(r#"{"type":"manifest","name":"audit","log_patterns":["joined"]}"#)
+                .unwrap();
+        assert_eq!(
+            message,
+            PluginMessage::Manifest(PluginManifest {
+                name: "audit".to_owned(),
+                log_patterns: vec!["joined".to_owned()],
+                ..Default::default()
+            })
+        );
+    }
+}
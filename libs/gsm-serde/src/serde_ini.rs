@@ -1,5 +1,15 @@
-use serde::{Serialize, de::DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use std::collections::HashSet;
 use std::fmt::Write;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+mod document;
+mod error;
+pub mod unquoted;
+pub use document::IniDocument;
+pub use error::IniError;
 
 /// Trait for types that require a custom INI header.
 ///
@@ -23,6 +33,47 @@ use std::fmt::Write;
 /// ```
 pub trait IniHeader {
     fn ini_header() -> &'static str;
+
+    /// Whether entries are written in field order rather than sorted by key.
+    ///
+    /// Sorted unless overridden; the `ini-derive` macro keeps field order.
+    fn ini_preserve_order() -> bool {
+        false
+    }
+}
+
+/// How lists, e.g. a `Vec<u32>` field, are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayStyle {
+    /// One `Key=(60,30,5,1)` entry, Unreal Engine's tuple syntax.
+    #[default]
+    Tuple,
+    /// One `Key=60`, `Key=30`, ... entry per element.
+    RepeatedKeys,
+}
+
+/// Options for [`to_string_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IniOptions {
+    /// Writes nested blocks on one line, as [`to_string_compact`] does.
+    pub compact: bool,
+    /// How lists are written.
+    pub array_style: ArrayStyle,
+    /// Writes entries in the order the struct declares its fields, as the game's own
+    /// files usually do, instead of sorting them by key. Map fields keep their
+    /// iteration order, so a `HashMap` should be a `BTreeMap` for a stable file.
+    pub preserve_order: bool,
+}
+
+impl IniOptions {
+    /// Returns the default options for `T`, keeping field order when
+    /// [`IniHeader::ini_preserve_order`] says so.
+    pub fn for_type<T: IniHeader>() -> Self {
+        Self {
+            preserve_order: T::ini_preserve_order(),
+            ..Self::default()
+        }
+    }
 }
 
 /// Helper: Format a serde_json number with up to 5 decimal places, trimming trailing zeros.
@@ -44,15 +95,116 @@ fn format_number(n: &serde_json::Number) -> String {
 }
 
 /// Helper: Format a JSON value appropriately.
-fn format_json_value(value: &serde_json::Value) -> String {
+///
+/// Strings are quoted and escaped unless they are [`unquoted`]. Lists are written as
+/// `(a,b,c)` tuples, and objects inside them as one-line blocks.
+fn format_json_value(value: &serde_json::Value, options: IniOptions) -> String {
+    if let Some(text) = unquoted::text(value) {
+        return text.to_owned();
+    }
     match value {
-        serde_json::Value::String(s) => format!("\"{s}\""),
+        serde_json::Value::String(s) => format!("\"{}\"", escape(s)),
         serde_json::Value::Bool(b) => b.to_string(),
         serde_json::Value::Number(n) => format_number(n),
+        serde_json::Value::Array(items) => {
+            let inline = IniOptions {
+                compact: true,
+                ..options
+            };
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| {
+                    if is_block(item) {
+                        format!("({})", serialize_value(item, 0, inline))
+                    } else {
+                        format_json_value(item, inline)
+                    }
+                })
+                .collect();
+            format!("({})", items.join(","))
+        }
         _ => format!("{value}"),
     }
 }
 
+/// Helper: Whether `value` is written as a `(...)` block of entries.
+fn is_block(value: &serde_json::Value) -> bool {
+    value.is_object() && unquoted::text(value).is_none()
+}
+
+/// Helper: Escape backslashes, quotes and line breaks so `s` can be written between
+/// quotes. Other characters, including non-ASCII ones, are written as they are.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Helper: Read a quoted string written by [`escape`], returning `None` unless `value`
+/// is exactly one quoted string.
+fn parse_quoted(value: &str) -> Option<String> {
+    let mut chars = value.strip_prefix('"')?.chars();
+    let mut unescaped = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return chars.as_str().is_empty().then_some(unescaped),
+            '\\' => match chars.next()? {
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                't' => unescaped.push('\t'),
+                c @ ('\\' | '"') => unescaped.push(c),
+                // Unknown escapes, e.g. in a Windows path, are kept as written.
+                c => {
+                    unescaped.push('\\');
+                    unescaped.push(c);
+                }
+            },
+            _ => unescaped.push(c),
+        }
+    }
+    None
+}
+
+/// Helper: Returns the entries of `map` sorted by key unless `options` preserves their
+/// order, with each list split into one entry per element when `options` asks for
+/// repeated keys.
+fn entries(
+    map: &serde_json::Map<String, serde_json::Value>,
+    options: IniOptions,
+) -> Vec<(&str, &serde_json::Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    if !options.preserve_order {
+        entries.sort_by_key(|(key, _)| *key);
+    }
+    entries
+        .into_iter()
+        .flat_map(|(key, value)| match value {
+            serde_json::Value::Array(items) if options.array_style == ArrayStyle::RepeatedKeys => {
+                items.iter().map(|item| (key.as_str(), item)).collect()
+            }
+            _ => vec![(key.as_str(), value)],
+        })
+        .collect()
+}
+
+/// Helper: Formats one `key=value` entry on a single line.
+fn compact_entry(key: &str, value: &serde_json::Value, options: IniOptions) -> String {
+    if is_block(value) {
+        format!("{key}=({})", serialize_value(value, 0, options))
+    } else {
+        format!("{key}={}", format_json_value(value, options))
+    }
+}
+
 /// Serializes a JSON object into INI body text.
 ///
 /// In `compact` mode, entries are joined on a single line with commas and no
@@ -61,46 +213,39 @@ fn format_json_value(value: &serde_json::Value) -> String {
 /// `OptionSettings=(...)` block and require it on one line. Non-compact mode
 /// keeps the original indented, one-entry-per-line, trailing-comma format
 /// intended for human-readable display.
-fn serialize_value(value: &serde_json::Value, indent: usize, compact: bool) -> String {
+fn serialize_value(value: &serde_json::Value, indent: usize, options: IniOptions) -> String {
+    let indent_str = if options.compact {
+        String::new()
+    } else {
+        "\t".repeat(indent)
+    };
+    let serde_json::Value::Object(map) = value else {
+        return format!("{indent_str}{}", format_json_value(value, options));
+    };
+    if options.compact {
+        return entries(map, options)
+            .into_iter()
+            .map(|(key, val)| compact_entry(key, val, options))
+            .collect::<Vec<_>>()
+            .join(",");
+    }
+
     let mut output = String::new();
-    let indent_str = if compact { String::new() } else { "\t".repeat(indent) };
-    if let serde_json::Value::Object(map) = value {
-        // Collect and sort keys alphabetically.
-        let mut entries: Vec<_> = map.iter().collect();
-        entries.sort_by_key(|(a, _)| *a);
-        let entry_count = entries.len();
-        for (i, (key, val)) in entries.into_iter().enumerate() {
-            let is_last = i + 1 == entry_count;
-            output.push_str(&indent_str);
-            output.push_str(key);
-            output.push('=');
-            if let serde_json::Value::Object(_) = val {
-                // Start a new nested block.
-                output.push('(');
-                if !compact {
-                    output.push('\n');
-                }
-                output.push_str(&serialize_value(val, indent + 1, compact));
-                output.push_str(&indent_str);
-                output.push(')');
-                if !compact {
-                    output.push('\n');
-                } else if !is_last {
-                    output.push(',');
-                }
-            } else {
-                output.push_str(&format_json_value(val));
-                if !compact || !is_last {
-                    output.push(',');
-                }
-                if !compact {
-                    output.push('\n');
-                }
-            }
+    for (key, val) in entries(map, options) {
+        if is_block(val) {
+            // Start a new nested block.
+            let _ = write!(
+                output,
+                "{indent_str}{key}=(\n{}{indent_str})\n",
+                serialize_value(val, indent + 1, options)
+            );
+        } else {
+            let _ = writeln!(
+                output,
+                "{indent_str}{key}={},",
+                format_json_value(val, options)
+            );
         }
-    } else {
-        output.push_str(&indent_str);
-        output.push_str(&format_json_value(value));
     }
     output
 }
@@ -186,39 +331,10 @@ fn serialize_value(value: &serde_json::Value, indent: usize, compact: bool) -> S
 ///
 /// # Errors
 ///
-/// Returns an error when `serde_json` conversion of `value` fails.
-pub fn to_string<T: Serialize + IniHeader>(value: &T) -> Result<String, serde_json::Error> {
-    let mut output = String::new();
-
-    // Write the header section.
-    let section = T::ini_header();
-    output.push('[');
-    output.push_str(section);
-    output.push_str("]\n");
-
-    // Convert the value into a serde_json::Value.
-    let serialized = serde_json::to_value(value)?;
-    if let serde_json::Value::Object(map) = serialized {
-        // Sort top-level keys alphabetically.
-        let mut entries: Vec<(String, serde_json::Value)> = map.into_iter().collect();
-        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
-        for (key, val) in entries {
-            match val {
-                serde_json::Value::Object(_) => {
-                    // For nested objects, use the recursive helper with indent level 1.
-                    output.push_str(&key);
-                    output.push_str("=(\n");
-                    output.push_str(&serialize_value(&val, 1, false));
-                    output.push_str(")\n");
-                }
-                _ => {
-                    let _ = writeln!(output, "{key}={},", format_json_value(&val));
-                }
-            }
-        }
-    }
-
-    Ok(output)
+/// Returns [`IniError::Data`] when `value` cannot be represented as INI, e.g. a map
+/// with non-string keys.
+pub fn to_string<T: Serialize + IniHeader>(value: &T) -> Result<String, IniError> {
+    to_string_with(value, IniOptions::for_type::<T>())
 }
 
 /// Serializes a struct into a single-line INI-formatted string.
@@ -231,41 +347,82 @@ pub fn to_string<T: Serialize + IniHeader>(value: &T) -> Result<String, serde_js
 ///
 /// # Errors
 ///
-/// Returns an error when `serde_json` conversion of `value` fails.
-pub fn to_string_compact<T: Serialize + IniHeader>(value: &T) -> Result<String, serde_json::Error> {
+/// Returns [`IniError::Data`] when `value` cannot be represented as INI, e.g. a map
+/// with non-string keys.
+pub fn to_string_compact<T: Serialize + IniHeader>(value: &T) -> Result<String, IniError> {
+    to_string_with(
+        value,
+        IniOptions {
+            compact: true,
+            ..IniOptions::for_type::<T>()
+        },
+    )
+}
+
+/// Serializes a struct into an INI-formatted string, laid out as `options` asks.
+///
+/// Lists are written as `(60,30,5,1)` tuples by default, or as one entry per element
+/// with [`ArrayStyle::RepeatedKeys`]; [`from_str`] reads both back.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::Serialize;
+/// use gsm_serde::serde_ini::{to_string_with, ArrayStyle, IniHeader, IniOptions};
+///
+/// #[derive(Serialize)]
+/// struct Settings {
+///     reset_warnings: Vec<u32>,
+/// }
+///
+/// impl IniHeader for Settings {
+///     fn ini_header() -> &'static str {
+///         "Server"
+///     }
+/// }
+///
+/// let settings = Settings { reset_warnings: vec![60, 5] };
+/// assert_eq!(
+///     to_string_with(&settings, IniOptions::default()).unwrap(),
+///     "[Server]\nreset_warnings=(60,5),\n"
+/// );
+///
+/// let options = IniOptions { array_style: ArrayStyle::RepeatedKeys, ..IniOptions::default() };
+/// assert_eq!(
+///     to_string_with(&settings, options).unwrap(),
+///     "[Server]\nreset_warnings=60,\nreset_warnings=5,\n"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns [`IniError::Data`] when `value` cannot be represented as INI, e.g. a map
+/// with non-string keys.
+pub fn to_string_with<T: Serialize + IniHeader>(
+    value: &T,
+    options: IniOptions,
+) -> Result<String, IniError> {
     let mut output = String::new();
 
+    // Write the header section.
     let section = T::ini_header();
     output.push('[');
     output.push_str(section);
     output.push_str("]\n");
 
+    // Convert the value into a serde_json::Value.
     let serialized = serde_json::to_value(value)?;
-    if let serde_json::Value::Object(map) = serialized {
-        let mut entries: Vec<(String, serde_json::Value)> = map.into_iter().collect();
-        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
-        let entry_count = entries.len();
-        for (i, (key, val)) in entries.into_iter().enumerate() {
-            let is_last = i + 1 == entry_count;
-            match val {
-                serde_json::Value::Object(_) => {
-                    output.push_str(&key);
-                    output.push_str("=(");
-                    output.push_str(&serialize_value(&val, 0, true));
-                    output.push(')');
-                    if !is_last {
-                        output.push(',');
-                    }
-                    output.push('\n');
-                }
-                _ => {
-                    let _ = write!(output, "{key}={}", format_json_value(&val));
-                    if !is_last {
-                        output.push(',');
-                    }
-                    output.push('\n');
-                }
-            }
+    if let serde_json::Value::Object(map) = &serialized {
+        if !options.compact {
+            output.push_str(&serialize_value(&serialized, 0, options));
+        } else if !map.is_empty() {
+            // Unlike entries inside a block, top-level entries each get their own line.
+            let lines: Vec<String> = entries(map, options)
+                .into_iter()
+                .map(|(key, val)| compact_entry(key, val, options))
+                .collect();
+            output.push_str(&lines.join(",\n"));
+            output.push('\n');
         }
     }
 
@@ -275,6 +432,7 @@ pub fn to_string_compact<T: Serialize + IniHeader>(value: &T) -> Result<String,
 /// Helper: Parse a string value from INI into a proper JSON value.
 ///
 /// If the value is unquoted, this helper attempts to parse it as an integer, float, or bool.
+/// A `(60,30,5,1)` tuple becomes a list, and a `(Key=1,Other=2)` block an object.
 ///
 /// This is used during deserialization to recover the original types.
 ///
@@ -289,13 +447,25 @@ pub fn to_string_compact<T: Serialize + IniHeader>(value: &T) -> Result<String,
 ///
 /// let v2 = parse_ini_value("\"Hello\"");
 /// assert_eq!(v2, Value::String("Hello".into()));
+///
+/// let v3 = parse_ini_value("(60,30)");
+/// assert_eq!(v3, serde_json::json!([60, 30]));
 /// ```
 pub fn parse_ini_value(value: &str) -> serde_json::Value {
     let trimmed = value.trim();
-    if trimmed.starts_with('\"') && trimmed.ends_with('\"') && trimmed.len() >= 2 {
-        // Remove the surrounding quotes.
-        let inner = &trimmed[1..trimmed.len() - 1];
-        serde_json::Value::String(inner.to_owned())
+    if let Some(inner) = trimmed
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_tuple(inner);
+    }
+    if let Some(unescaped) = parse_quoted(trimmed) {
+        return serde_json::Value::String(unescaped);
+    }
+    if trimmed.eq_ignore_ascii_case("true") {
+        serde_json::Value::Bool(true)
+    } else if trimmed.eq_ignore_ascii_case("false") {
+        serde_json::Value::Bool(false)
     } else if let Ok(i) = trimmed.parse::<i64>() {
         serde_json::Value::Number(i.into())
     } else if let Ok(f) = trimmed.parse::<f64>() {
@@ -303,18 +473,140 @@ pub fn parse_ini_value(value: &str) -> serde_json::Value {
             || serde_json::Value::String(trimmed.to_owned()),
             serde_json::Value::Number,
         )
-    } else if trimmed.eq_ignore_ascii_case("true") {
-        serde_json::Value::Bool(true)
-    } else if trimmed.eq_ignore_ascii_case("false") {
-        serde_json::Value::Bool(false)
     } else {
         serde_json::Value::String(trimmed.to_owned())
     }
 }
 
+/// Helper: Parse the inside of a `(...)` tuple into a list, or into an object when every
+/// element is a `key=value` entry.
+fn parse_tuple(inner: &str) -> serde_json::Value {
+    let elements: Vec<&str> = split_top_level(inner)
+        .into_iter()
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .collect();
+    let entries: Option<Vec<(&str, &str)>> = elements.iter().map(|e| split_entry(e)).collect();
+    match entries {
+        Some(entries) if !entries.is_empty() => {
+            let mut map = serde_json::Map::new();
+            let mut repeated = HashSet::new();
+            for (key, value) in entries {
+                insert_entry(
+                    &mut map,
+                    &mut repeated,
+                    key.to_owned(),
+                    parse_ini_value(value),
+                );
+            }
+            serde_json::Value::Object(map)
+        }
+        _ => serde_json::Value::Array(elements.into_iter().map(parse_ini_value).collect()),
+    }
+}
+
+/// Helper: Split `value` on the commas that are not inside quotes or parentheses.
+fn split_top_level(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => depth = depth.saturating_sub(1),
+            ',' if !in_quotes && depth == 0 => {
+                parts.push(value.get(start..i).unwrap_or_default());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(value.get(start..).unwrap_or_default());
+    parts
+}
+
+/// Helper: Split a `key=value` tuple element, or return `None` for a plain value.
+fn split_entry(element: &str) -> Option<(&str, &str)> {
+    let (key, value) = element.split_once('=')?;
+    let key = key.trim();
+    (!key.is_empty() && !key.contains(['"', '(', ')'])).then_some((key, value))
+}
+
+/// Helper: Insert `key` into `map`, collecting the values of a key that repeats into a
+/// list in the order they appear.
+fn insert_entry(
+    map: &mut serde_json::Map<String, serde_json::Value>,
+    repeated: &mut HashSet<String>,
+    key: String,
+    value: serde_json::Value,
+) {
+    match map.get_mut(&key) {
+        Some(serde_json::Value::Array(items)) if repeated.contains(&key) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = serde_json::Value::Array(vec![first, value]);
+            repeated.insert(key);
+        }
+        None => {
+            map.insert(key, value);
+        }
+    }
+}
+
+/// Deserializes a list field that may hold a single value.
+///
+/// A list written with [`ArrayStyle::RepeatedKeys`] that has one element reads back as
+/// that element alone, since a lone `Key=60` entry cannot be told apart from a plain
+/// value. Use this with `#[serde(deserialize_with = "...")]` on such fields.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use gsm_serde::serde_ini::{from_str, one_or_many};
+///
+/// #[derive(Deserialize)]
+/// struct Settings {
+///     #[serde(deserialize_with = "one_or_many")]
+///     reset_warnings: Vec<u32>,
+/// }
+///
+/// let settings: Settings = from_str("[Server]\nreset_warnings=60,\n").unwrap();
+/// assert_eq!(settings.reset_warnings, [60]);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error when the value is neither a list of `T` nor a single `T`.
+pub fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(T),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(items) => items,
+        OneOrMany::One(item) => vec![item],
+    })
+}
+
 /// Deserializes an INI-formatted string into a struct.
 ///
-/// This basic implementation supports a single header and one level of nested fields.
+/// Sections are not told apart, so all entries are read as if under one header. Blocks
+/// may nest to any depth, written over several lines as [`to_string`] does or inline as
+/// `Key=(A=1,B=(C=2))`. Lists may be written as `(60,30,5,1)` tuples or as a key that repeats, one element
+/// per entry.
 ///
 /// # Examples
 ///
@@ -368,45 +660,170 @@ pub fn parse_ini_value(value: &str) -> serde_json::Value {
 ///
 /// # Errors
 ///
-/// Returns an error when the parsed JSON representation cannot be deserialized into `T`.
-pub fn from_str<T: DeserializeOwned>(ini_str: &str) -> Result<T, serde_json::Error> {
+/// Returns [`IniError::Parse`] with the line and column of a line that is not a
+/// `key=value` entry, unbalanced quotes or parentheses, or a block that is never closed,
+/// and [`IniError::Data`] when the values cannot be deserialized into `T`.
+pub fn from_str<T: DeserializeOwned>(ini_str: &str) -> Result<T, IniError> {
     let mut map = serde_json::Map::new();
-    let mut current_key: Option<String> = None;
-    let mut nested_map = serde_json::Map::new();
-    let mut in_nested = false;
+    let mut repeated = HashSet::new();
+    // The nested blocks that are open, innermost last.
+    let mut blocks: Vec<Block> = Vec::new();
 
-    for line in ini_str.lines() {
-        let line = line.trim();
-        if line.starts_with('[') || line.is_empty() || line.starts_with(';') {
+    for (index, raw_line) in ini_str.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+        if line.starts_with('[') || line.is_empty() || line.starts_with([';', '#']) {
             continue; // Skip header and comment lines.
         }
         // Detect start of a nested block (e.g., OptionSettings=()
         if line.ends_with("=(") {
-            let key = line.trim_end_matches("=(").trim().to_owned();
-            current_key = Some(key);
-            in_nested = true;
-            nested_map = serde_json::Map::new();
-        } else if in_nested && line == ")" {
-            if let Some(key) = current_key.take() {
-                map.insert(key, serde_json::Value::Object(nested_map.clone()));
-            }
-            in_nested = false;
-        } else if in_nested {
-            // Process nested key=value lines (remove trailing commas).
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim().to_owned();
-                let value = value.trim().trim_end_matches(',').to_owned();
-                nested_map.insert(key, parse_ini_value(&value));
+            blocks.push(Block {
+                key: line.trim_end_matches("=(").trim().to_owned(),
+                map: serde_json::Map::new(),
+                repeated: HashSet::new(),
+                start: (line_number, column_of(raw_line, line) + line.len() - 1),
+            });
+            continue;
+        }
+        if !blocks.is_empty() && line.trim_end_matches(',') == ")" {
+            if let Some(block) = blocks.pop() {
+                let (map, repeated) = match blocks.last_mut() {
+                    Some(parent) => (&mut parent.map, &mut parent.repeated),
+                    None => (&mut map, &mut repeated),
+                };
+                insert_entry(
+                    map,
+                    repeated,
+                    block.key,
+                    serde_json::Value::Object(block.map),
+                );
             }
-        } else if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim().to_owned();
-            let value = value.trim().trim_end_matches(',').to_owned();
-            map.insert(key, parse_ini_value(&value));
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(IniError::parse(
+                line_number,
+                column_of(raw_line, line),
+                "expected `key=value`",
+            ));
+        };
+        let key = key.trim().to_owned();
+        let value = value.trim();
+        if let Err((offset, message)) = check_value(value) {
+            let column = column_of(raw_line, value)
+                + value.get(..offset).unwrap_or_default().chars().count();
+            return Err(IniError::parse(line_number, column, message));
         }
+        // Remove trailing commas.
+        let value = parse_ini_value(value.trim_end_matches(','));
+        let (map, repeated) = match blocks.last_mut() {
+            Some(block) => (&mut block.map, &mut block.repeated),
+            None => (&mut map, &mut repeated),
+        };
+        insert_entry(map, repeated, key, value);
     }
 
+    if let Some(Block {
+        start: (line, column),
+        ..
+    }) = blocks.last()
+    {
+        return Err(IniError::parse(*line, *column, "`(` is never closed"));
+    }
     let json_value = serde_json::Value::Object(map);
-    serde_json::from_value(json_value)
+    Ok(serde_json::from_value(json_value)?)
+}
+
+/// A `Key=(` block [`from_str`] is reading, spread over several lines.
+struct Block {
+    key: String,
+    map: serde_json::Map<String, serde_json::Value>,
+    repeated: HashSet<String>,
+    /// The line and column of the block's `(`.
+    start: (usize, usize),
+}
+
+/// Helper: Returns the 1-based column `part`, a trimmed piece of `line`, starts at.
+fn column_of(line: &str, part: &str) -> usize {
+    // `part` ends where the trimmed line does.
+    let offset = line.trim_end().len().saturating_sub(part.len());
+    line.get(..offset).unwrap_or_default().chars().count() + 1
+}
+
+/// Helper: Checks that the quotes and parentheses in `value` are balanced, returning the
+/// byte offset of the first one that is not, and what is wrong with it.
+fn check_value(value: &str) -> Result<(), (usize, &'static str)> {
+    let mut open = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            '"' if quote.is_some() => quote = None,
+            '"' => quote = Some(offset),
+            '(' if quote.is_none() => open.push(offset),
+            ')' if quote.is_none() && open.pop().is_none() => {
+                return Err((offset, "`)` without a matching `(`"));
+            }
+            _ => {}
+        }
+    }
+    if let Some(offset) = quote {
+        return Err((offset, "string is never closed"));
+    }
+    open.last()
+        .map_or(Ok(()), |&offset| Err((offset, "`(` is never closed")))
+}
+
+/// Deserializes an INI document read from `reader` into a struct; see [`from_str`].
+///
+/// # Errors
+///
+/// Returns an error when reading fails, the text is malformed, or it cannot be
+/// deserialized into `T`.
+pub fn from_reader<R: Read, T: DeserializeOwned>(mut reader: R) -> Result<T, IniError> {
+    let mut ini_str = String::new();
+    reader.read_to_string(&mut ini_str)?;
+    from_str(&ini_str)
+}
+
+/// Deserializes the INI file at `path` into a struct; see [`from_str`].
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be read, is malformed, or cannot be
+/// deserialized into `T`.
+pub fn from_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, IniError> {
+    from_str(&fs::read_to_string(path)?)
+}
+
+/// Serializes a struct as INI into `writer`; see [`to_string`].
+///
+/// # Errors
+///
+/// Returns an error when `value` cannot be serialized or writing fails.
+pub fn to_writer<W: io::Write, T: Serialize + IniHeader>(
+    mut writer: W,
+    value: &T,
+) -> Result<(), IniError> {
+    writer.write_all(to_string(value)?.as_bytes())?;
+    Ok(())
+}
+
+/// Serializes a struct as INI into the file at `path`, replacing it; see [`to_string`].
+/// Use [`IniDocument::to_file`] to keep what the file has that `T` does not.
+///
+/// # Errors
+///
+/// Returns an error when `value` cannot be serialized or the file cannot be written.
+pub fn to_file<T: Serialize + IniHeader>(
+    path: impl AsRef<Path>,
+    value: &T,
+) -> Result<(), IniError> {
+    fs::write(path, to_string(value)?)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -449,13 +866,34 @@ mod tests {
         let ini_string = to_string(&settings).unwrap();
         let expected_ini = "[/Script/Pal.PalGameWorldSettings]\n\
 OptionSettings=(\n\
-\tDayTimeSpeedRate=1.5,\n\
 \tDifficulty=\"Hard\",\n\
+\tDayTimeSpeedRate=1.5,\n\
 \tNightTimeSpeedRate=0.8,\n\
 )\n";
         assert_eq!(ini_string, expected_ini);
     }
 
+    #[test]
+    fn entries_are_sorted_when_the_derive_opts_out_of_field_order() {
+        #[derive(Serialize, IniSerialize)]
+        #[INIHeader(name = "Sorted", preserve_order = false)]
+        struct Sorted {
+            zeta: u8,
+            alpha: u8,
+        }
+
+        let sorted = Sorted { zeta: 1, alpha: 2 };
+        assert_eq!(to_string(&sorted).unwrap(), "[Sorted]\nalpha=2,\nzeta=1,\n");
+        let options = IniOptions {
+            preserve_order: true,
+            ..IniOptions::for_type::<Sorted>()
+        };
+        assert_eq!(
+            to_string_with(&sorted, options).unwrap(),
+            "[Sorted]\nzeta=1,\nalpha=2,\n"
+        );
+    }
+
     #[test]
     fn to_string_compact_serializes_nested_struct_on_one_line() {
         let settings = GameSettings {
@@ -468,7 +906,7 @@ OptionSettings=(\n\
 
         let ini_string = to_string_compact(&settings).unwrap();
         let expected_ini = "[/Script/Pal.PalGameWorldSettings]\n\
-OptionSettings=(DayTimeSpeedRate=1.5,Difficulty=\"Hard\",NightTimeSpeedRate=0.8)\n";
+OptionSettings=(Difficulty=\"Hard\",DayTimeSpeedRate=1.5,NightTimeSpeedRate=0.8)\n";
         assert_eq!(ini_string, expected_ini);
     }
 
@@ -588,4 +1026,279 @@ NightTimeSpeedRate=0.8,\n\
         let deserialized: GameSettings = from_str(&ini_string).unwrap();
         assert_eq!(settings, deserialized);
     }
+
+    #[derive(Serialize, Deserialize, IniSerialize, Debug, PartialEq)]
+    #[INIHeader(name = "Server")]
+    struct ListSettings {
+        reset_warnings_in_minutes: Vec<u32>,
+        admins: Vec<String>,
+    }
+
+    fn list_settings() -> ListSettings {
+        ListSettings {
+            reset_warnings_in_minutes: vec![60, 30, 5, 1],
+            admins: vec!["alice".to_owned(), "bob, jr".to_owned()],
+        }
+    }
+
+    #[test]
+    fn lists_round_trip_as_tuples() {
+        let settings = list_settings();
+        let ini_string = to_string(&settings).unwrap();
+        assert_eq!(
+            ini_string,
+            "[Server]\nreset_warnings_in_minutes=(60,30,5,1),\nadmins=(\"alice\",\"bob, jr\"),\n"
+        );
+        assert_eq!(from_str::<ListSettings>(&ini_string).unwrap(), settings);
+        assert_eq!(
+            from_str::<ListSettings>("[Server]\nadmins=(),\nreset_warnings_in_minutes=(5),\n")
+                .unwrap(),
+            ListSettings {
+                reset_warnings_in_minutes: vec![5],
+                admins: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn lists_round_trip_as_repeated_keys() {
+        let settings = list_settings();
+        let options = IniOptions {
+            array_style: ArrayStyle::RepeatedKeys,
+            ..IniOptions::for_type::<ListSettings>()
+        };
+        let ini_string = to_string_with(&settings, options).unwrap();
+        assert_eq!(
+            ini_string,
+            "[Server]\nreset_warnings_in_minutes=60,\nreset_warnings_in_minutes=30,\n\
+reset_warnings_in_minutes=5,\nreset_warnings_in_minutes=1,\n\
+admins=\"alice\",\nadmins=\"bob, jr\",\n"
+        );
+        assert_eq!(from_str::<ListSettings>(&ini_string).unwrap(), settings);
+
+        let compact = IniOptions {
+            compact: true,
+            ..options
+        };
+        let ini_string = to_string_with(&settings, compact).unwrap();
+        assert_eq!(from_str::<ListSettings>(&ini_string).unwrap(), settings);
+    }
+
+    #[test]
+    fn single_repeated_keys_read_back_with_one_or_many() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Warnings {
+            #[serde(deserialize_with = "one_or_many")]
+            reset_warnings_in_minutes: Vec<u32>,
+        }
+
+        let ini = "[Server]\nreset_warnings_in_minutes=60,\n";
+        assert!(from_str::<ListSettings>(ini).is_err());
+        assert_eq!(
+            from_str::<Warnings>(ini).unwrap().reset_warnings_in_minutes,
+            [60]
+        );
+        let ini = "[Server]\nreset_warnings_in_minutes=(60,5),\n";
+        assert_eq!(
+            from_str::<Warnings>(ini).unwrap().reset_warnings_in_minutes,
+            [60, 5]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_report_where_they_are() {
+        let parse_error = |ini: &str| match from_str::<serde_json::Value>(ini) {
+            Err(IniError::Parse { line, column, .. }) => Some((line, column)),
+            _ => None,
+        };
+        assert_eq!(parse_error("[s]\nkey=1\n  oops\n"), Some((3, 3)));
+        assert_eq!(parse_error("key=(1,2\n"), Some((1, 5)));
+        assert_eq!(parse_error("key=1)\n"), Some((1, 6)));
+        assert_eq!(parse_error("name=\"open\n"), Some((1, 6)));
+        assert_eq!(parse_error("; block\nBlock=(\nA=1,\n"), Some((2, 7)));
+
+        let error = from_str::<GameSettings>("OptionSettings=(Difficulty=1)").unwrap_err();
+        assert!(matches!(error, IniError::Data(_)), "{error}");
+    }
+
+    #[test]
+    fn readers_writers_and_files_round_trip() {
+        let settings = list_settings();
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, &settings).unwrap();
+        assert_eq!(buffer, to_string(&settings).unwrap().as_bytes());
+        assert_eq!(
+            from_reader::<_, ListSettings>(buffer.as_slice()).unwrap(),
+            settings
+        );
+
+        let path = std::env::temp_dir().join(format!("gsm-serde-{}.ini", std::process::id()));
+        to_file(&path, &settings).unwrap();
+        assert_eq!(from_file::<ListSettings>(&path).unwrap(), settings);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            from_file::<ListSettings>(&path),
+            Err(IniError::Io(_))
+        ));
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Boss {
+        #[serde(rename = "Name")]
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Limits {
+        #[serde(rename = "Max")]
+        max: u32,
+        #[serde(rename = "Boss")]
+        boss: Boss,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Spawns {
+        #[serde(rename = "Rate")]
+        rate: f32,
+        #[serde(rename = "Limits")]
+        limits: Limits,
+    }
+
+    #[derive(Serialize, Deserialize, IniSerialize, Debug, PartialEq)]
+    #[INIHeader(name = "Deep")]
+    struct DeepSettings {
+        #[serde(rename = "OptionSettings")]
+        option_settings: Spawns,
+    }
+
+    #[test]
+    fn blocks_nest_to_any_depth() {
+        let settings = DeepSettings {
+            option_settings: Spawns {
+                rate: 1.5,
+                limits: Limits {
+                    max: 3,
+                    boss: Boss {
+                        name: "Dragon".to_owned(),
+                    },
+                },
+            },
+        };
+        let ini_string = to_string(&settings).unwrap();
+        assert_eq!(
+            ini_string,
+            "[Deep]\nOptionSettings=(\n\
+\tRate=1.5,\n\
+\tLimits=(\n\
+\t\tMax=3,\n\
+\t\tBoss=(\n\
+\t\t\tName=\"Dragon\",\n\
+\t\t)\n\
+\t)\n\
+)\n"
+        );
+        assert_eq!(from_str::<DeepSettings>(&ini_string).unwrap(), settings);
+
+        let compact = to_string_compact(&settings).unwrap();
+        assert_eq!(
+            compact,
+            "[Deep]\nOptionSettings=(Rate=1.5,Limits=(Max=3,Boss=(Name=\"Dragon\")))\n"
+        );
+        assert_eq!(from_str::<DeepSettings>(&compact).unwrap(), settings);
+
+        let document = IniDocument::parse(&ini_string);
+        assert_eq!(document.to_string(), ini_string);
+        assert_eq!(document.deserialize::<DeepSettings>().unwrap(), settings);
+
+        // The innermost block left open is reported.
+        let unclosed = ini_string.strip_suffix("\t)\n)\n").unwrap();
+        assert!(matches!(
+            from_str::<DeepSettings>(unclosed),
+            Err(IniError::Parse {
+                line: 4,
+                column: 9,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn inline_blocks_nest() {
+        let value: serde_json::Value =
+            from_str("[s]\nKey=(A=1,B=(C=2,D=(E=\"x, y\")),F=(1,2))\n").unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"Key": {"A": 1, "B": {"C": 2, "D": {"E": "x, y"}}, "F": [1, 2]}})
+        );
+    }
+
+    #[test]
+    fn strings_are_escaped_and_read_back() {
+        let settings = ListSettings {
+            reset_warnings_in_minutes: Vec::new(),
+            admins: vec![
+                "He said \"hi\", (twice)".to_owned(),
+                "C:\\Servers\\Café ☕\nline two".to_owned(),
+            ],
+        };
+        let ini_string = to_string(&settings).unwrap();
+        assert_eq!(
+            ini_string,
+            "[Server]\nreset_warnings_in_minutes=(),\n\
+admins=(\"He said \\\"hi\\\", (twice)\",\"C:\\\\Servers\\\\Café ☕\\nline two\"),\n"
+        );
+        assert_eq!(from_str::<ListSettings>(&ini_string).unwrap(), settings);
+        let compact = to_string_compact(&settings).unwrap();
+        assert_eq!(from_str::<ListSettings>(&compact).unwrap(), settings);
+
+        // Unknown escapes and unbalanced quotes inside a string are kept as written.
+        assert_eq!(
+            parse_ini_value(r#""C:\Games\Pal" "#),
+            serde_json::Value::String(r"C:\Games\Pal".to_owned())
+        );
+        assert_eq!(
+            parse_ini_value(r#""a"b""#),
+            serde_json::Value::String(r#""a"b""#.to_owned())
+        );
+    }
+
+    #[test]
+    fn unquoted_fields_are_written_bare() {
+        #[derive(Serialize, Deserialize, IniSerialize, Debug, PartialEq)]
+        #[INIHeader(name = "/Script/Pal.PalGameWorldSettings")]
+        struct Bare {
+            #[serde(rename = "Difficulty", with = "unquoted")]
+            difficulty: String,
+            #[serde(rename = "CrossplayPlatforms", with = "unquoted")]
+            crossplay_platforms: String,
+            #[serde(rename = "Players", with = "unquoted")]
+            players: u32,
+            #[serde(rename = "ServerName")]
+            server_name: String,
+        }
+
+        let settings = Bare {
+            difficulty: "None".to_owned(),
+            crossplay_platforms: "(Steam,Xbox,PS5,Mac)".to_owned(),
+            players: 32,
+            server_name: "None".to_owned(),
+        };
+        let ini_string = to_string_compact(&settings).unwrap();
+        assert_eq!(
+            ini_string,
+            "[/Script/Pal.PalGameWorldSettings]\n\
+Difficulty=None,\nCrossplayPlatforms=(Steam,Xbox,PS5,Mac),\nPlayers=32,\nServerName=\"None\"\n"
+        );
+        assert_eq!(from_str::<Bare>(&ini_string).unwrap(), settings);
+
+        let game_file = "[/Script/Pal.PalGameWorldSettings]\n\
+Difficulty=None\nCrossplayPlatforms=(Steam,Xbox,PS5,Mac)\nPlayers=16\nServerName=\"None\"\n";
+        let mut document = IniDocument::parse(game_file);
+        assert_eq!(document.deserialize::<Bare>().unwrap().players, 16);
+        document.patch(&settings).unwrap();
+        assert_eq!(
+            document.to_string(),
+            game_file.replace("Players=16", "Players=32")
+        );
+    }
 }
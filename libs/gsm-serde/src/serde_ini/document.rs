@@ -0,0 +1,543 @@
+use super::{
+    IniError, IniHeader, IniOptions, entries, format_json_value, insert_entry, is_block,
+    parse_ini_value, serialize_value, split_entry, split_top_level, unquoted,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// An INI file kept line by line, so it can be written back with the comments and keys
+/// no struct describes, e.g. settings a game update added.
+///
+/// Typed structs read their section with [`IniDocument::deserialize`] and write it back
+/// with [`IniDocument::patch`], which only rewrites the entries whose values changed.
+/// Entries inside a `(...)` block are patched the same way, so a block keeps the keys
+/// the struct does not know about.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use gsm_serde::serde_ini::{IniDocument, IniHeader};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Settings {
+///     #[serde(rename = "MaxPlayers")]
+///     max_players: u32,
+/// }
+///
+/// impl IniHeader for Settings {
+///     fn ini_header() -> &'static str {
+///         "Server"
+///     }
+/// }
+///
+/// let mut document = IniDocument::parse("[Server]\n; Set by the host\nMaxPlayers=8\nNewOption=True\n");
+/// let mut settings: Settings = document.deserialize().unwrap();
+/// settings.max_players = 16;
+/// document.patch(&settings).unwrap();
+/// assert_eq!(
+///     document.to_string(),
+///     "[Server]\n; Set by the host\nMaxPlayers=16\nNewOption=True\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IniDocument {
+    lines: Vec<Line>,
+    crlf: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// A blank line, comment or other line written back as it was.
+    Verbatim(String),
+    /// A `[section]` header.
+    Section { name: String, text: String },
+    /// A `key=value` entry.
+    Entry(Entry),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    /// The value on one line, without a trailing comma.
+    value: String,
+    /// Whether the entry ends with a comma.
+    comma: bool,
+    /// The entry's lines as read, until its value changes.
+    text: Option<String>,
+}
+
+impl Entry {
+    const fn new(key: String, value: String, comma: bool) -> Self {
+        Self {
+            key,
+            value,
+            comma,
+            text: None,
+        }
+    }
+
+    fn set_value(&mut self, value: String) {
+        if value != self.value {
+            self.value = value;
+            self.text = None;
+        }
+    }
+}
+
+impl IniDocument {
+    /// Parses `text`, keeping every line so unchanged ones are written back as they were.
+    ///
+    /// Entries before the first `[section]` header belong to the section named `""`.
+    pub fn parse(text: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut input = text.lines();
+        while let Some(line) = input.next() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                lines.push(Line::Section {
+                    name: name.trim().to_owned(),
+                    text: line.to_owned(),
+                });
+                continue;
+            }
+            let Some((key, value)) = trimmed
+                .split_once('=')
+                .filter(|_| !trimmed.starts_with([';', '#']))
+            else {
+                lines.push(Line::Verbatim(line.to_owned()));
+                continue;
+            };
+            let key = key.trim().to_owned();
+            let value = value.trim();
+            if value == "(" {
+                // A block written over several lines, as `to_string` does, which may
+                // hold further blocks. Each open block collects its elements.
+                let mut text = vec![line];
+                let mut blocks = vec![(String::new(), Vec::new())];
+                let mut value = None;
+                let mut comma = false;
+                for inner in input.by_ref() {
+                    text.push(inner);
+                    let inner = inner.trim();
+                    if let Some(close) = inner.strip_prefix(')') {
+                        value = close_block(&mut blocks);
+                        if value.is_some() {
+                            comma = close.trim() == ",";
+                            break;
+                        }
+                    } else if let Some(key) = inner.strip_suffix("=(") {
+                        blocks.push((key.trim().to_owned(), Vec::new()));
+                    } else if let Some((_, elements)) = blocks.last_mut() {
+                        let element = inner.strip_suffix(',').unwrap_or(inner);
+                        if !element.is_empty() {
+                            elements.push(element.to_owned());
+                        }
+                    }
+                }
+                // A block left open at the end is read as if it were closed there.
+                while value.is_none() && !blocks.is_empty() {
+                    value = close_block(&mut blocks);
+                }
+                lines.push(Line::Entry(Entry {
+                    key,
+                    value: value.unwrap_or_default(),
+                    comma,
+                    text: Some(text.join("\n")),
+                }));
+            } else {
+                let (value, comma) = value
+                    .strip_suffix(',')
+                    .map_or((value, false), |value| (value.trim_end(), true));
+                lines.push(Line::Entry(Entry {
+                    key,
+                    value: value.to_owned(),
+                    comma,
+                    text: Some(line.to_owned()),
+                }));
+            }
+        }
+        Self {
+            lines,
+            crlf: text.contains("\r\n"),
+        }
+    }
+
+    /// Reads and parses the INI file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IniError::Io`] when the file cannot be read.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, IniError> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Writes the document to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IniError::Io`] when the file cannot be written.
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<(), IniError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Returns the value of `key` in `section`, with a key that repeats read as a list.
+    pub fn get(&self, section: &str, key: &str) -> Option<serde_json::Value> {
+        self.section_map(section).remove(key)
+    }
+
+    /// Sets `key` in `section`, adding the section and key when they are missing.
+    ///
+    /// A `(...)` block only has the entries `value` sets rewritten, and a key written
+    /// once per element keeps that style when `value` is a list.
+    pub fn set(&mut self, section: &str, key: &str, value: &serde_json::Value) {
+        self.set_with(section, key, value, IniOptions::default());
+    }
+
+    /// Reads `T` from its section, ignoring keys `T` does not have.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the section's entries cannot be deserialized into `T`.
+    pub fn deserialize<T: DeserializeOwned + IniHeader>(&self) -> Result<T, IniError> {
+        let section = serde_json::Value::Object(self.section_map(T::ini_header()));
+        Ok(serde_json::from_value(section)?)
+    }
+
+    /// Writes the fields of `value` into its section, leaving unknown keys, comments and
+    /// entries whose values did not change as they were. `None` fields are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IniError::Data`] when `value` cannot be represented as INI.
+    pub fn patch<T: Serialize + IniHeader>(&mut self, value: &T) -> Result<(), IniError> {
+        let options = IniOptions::for_type::<T>();
+        let serialized = serde_json::to_value(value)?;
+        if let serde_json::Value::Object(map) = &serialized {
+            for (key, value) in entries(map, options) {
+                if !value.is_null() {
+                    self.set_with(T::ini_header(), key, value, options);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_with(
+        &mut self,
+        section: &str,
+        key: &str,
+        value: &serde_json::Value,
+        options: IniOptions,
+    ) {
+        let range = self
+            .section_range(section)
+            .unwrap_or_else(|| self.push_section(section));
+        let positions: Vec<usize> = self
+            .entries_in(range.clone())
+            .filter(|(_, entry)| entry.key == key)
+            .map(|(index, _)| index)
+            .collect();
+
+        match (positions.as_slice(), value) {
+            ([], _) => {
+                let last = self.entries_in(range.clone()).last();
+                let comma = last.is_some_and(|(_, entry)| entry.comma);
+                let at = last.map_or(range.start, |(index, _)| index + 1);
+                let entry = Entry::new(key.to_owned(), inline_value(value, options), comma);
+                self.lines.insert(at, Line::Entry(entry));
+            }
+            ([first, _, ..], serde_json::Value::Array(items)) => {
+                // The file writes this list one element per entry; keep it that way.
+                if self.get(section, key).as_ref() == Some(value) {
+                    return;
+                }
+                let first = *first;
+                let comma =
+                    matches!(self.lines.get(first), Some(Line::Entry(entry)) if entry.comma);
+                for &index in positions.iter().rev() {
+                    self.lines.remove(index);
+                }
+                for (offset, item) in items.iter().enumerate() {
+                    let entry = Entry::new(key.to_owned(), inline_value(item, options), comma);
+                    self.lines.insert(first + offset, Line::Entry(entry));
+                }
+            }
+            ([first, rest @ ..], _) => {
+                if let Some(Line::Entry(entry)) = self.lines.get_mut(*first) {
+                    let merged = merge_value(&entry.value, value, options);
+                    entry.set_value(merged);
+                }
+                for &index in rest.iter().rev() {
+                    self.lines.remove(index);
+                }
+            }
+        }
+    }
+
+    /// Returns the lines of `section` after its header, or `None` when it is missing.
+    fn section_range(&self, section: &str) -> Option<Range<usize>> {
+        let is_header = |line: &Line| matches!(line, Line::Section { .. });
+        let start = if section.is_empty() {
+            0
+        } else {
+            self.lines
+                .iter()
+                .position(|line| matches!(line, Line::Section { name, .. } if name == section))?
+                + 1
+        };
+        let end = self
+            .lines
+            .iter()
+            .skip(start)
+            .position(is_header)
+            .map_or(self.lines.len(), |offset| start + offset);
+        Some(start..end)
+    }
+
+    /// Adds a `[section]` header at the end, returning its (empty) range of lines.
+    fn push_section(&mut self, section: &str) -> Range<usize> {
+        if self
+            .lines
+            .last()
+            .is_some_and(|line| !matches!(line, Line::Verbatim(text) if text.trim().is_empty()))
+        {
+            self.lines.push(Line::Verbatim(String::new()));
+        }
+        self.lines.push(Line::Section {
+            name: section.to_owned(),
+            text: format!("[{section}]"),
+        });
+        self.lines.len()..self.lines.len()
+    }
+
+    fn entries_in(&self, range: Range<usize>) -> impl Iterator<Item = (usize, &Entry)> {
+        let start = range.start;
+        self.lines
+            .get(range)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .filter_map(move |(offset, line)| match line {
+                Line::Entry(entry) => Some((start + offset, entry)),
+                _ => None,
+            })
+    }
+
+    fn section_map(&self, section: &str) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = serde_json::Map::new();
+        let mut repeated = HashSet::new();
+        if let Some(range) = self.section_range(section) {
+            for (_, entry) in self.entries_in(range) {
+                insert_entry(
+                    &mut map,
+                    &mut repeated,
+                    entry.key.clone(),
+                    parse_ini_value(&entry.value),
+                );
+            }
+        }
+        map
+    }
+}
+
+impl fmt::Display for IniDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        for line in &self.lines {
+            match line {
+                Line::Verbatim(text) | Line::Section { text, .. } => f.write_str(text)?,
+                Line::Entry(Entry {
+                    text: Some(text), ..
+                }) => f.write_str(&text.replace('\n', newline))?,
+                Line::Entry(entry) => {
+                    let comma = if entry.comma { "," } else { "" };
+                    write!(f, "{}={}{comma}", entry.key, entry.value)?;
+                }
+            }
+            f.write_str(newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Closes the innermost of the open `blocks`, adding it to the block around it, and
+/// returns the outermost block's value once that closes.
+fn close_block(blocks: &mut Vec<(String, Vec<String>)>) -> Option<String> {
+    let (key, elements) = blocks.pop()?;
+    let block = format!("({})", elements.join(","));
+    match blocks.last_mut() {
+        Some((_, parent)) => {
+            parent.push(format!("{key}={block}"));
+            None
+        }
+        None => Some(block),
+    }
+}
+
+/// Formats `value` as the value of an entry, with blocks and lists on one line.
+fn inline_value(value: &serde_json::Value, options: IniOptions) -> String {
+    let options = IniOptions {
+        compact: true,
+        ..options
+    };
+    if is_block(value) {
+        format!("({})", serialize_value(value, 0, options))
+    } else {
+        format_json_value(value, options)
+    }
+}
+
+/// Returns `raw` updated to `value`: unchanged when it already holds `value`, and for a
+/// `(...)` block, with only the entries `value` sets rewritten and the rest kept as-is.
+fn merge_value(raw: &str, value: &serde_json::Value, options: IniOptions) -> String {
+    if parse_ini_value(raw) == *value || unquoted::text(value) == Some(raw) {
+        return raw.to_owned();
+    }
+    let inner = raw
+        .trim()
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .filter(|_| is_block(value));
+    let (Some(inner), serde_json::Value::Object(map)) = (inner, value) else {
+        return inline_value(value, options);
+    };
+
+    let mut elements: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for element in split_top_level(inner) {
+        let known = split_entry(element.trim()).and_then(|(key, old)| {
+            let new = map.get(key).filter(|new| !new.is_null())?;
+            Some((key, old, new))
+        });
+        let Some((key, old, new)) = known else {
+            elements.push(element.to_owned());
+            continue;
+        };
+        seen.insert(key.to_owned());
+        let indent = element
+            .get(..element.len() - element.trim_start().len())
+            .unwrap_or_default();
+        let merged = merge_value(old.trim(), new, options);
+        elements.push(format!("{indent}{key}={merged}"));
+    }
+    // A trailing comma leaves an empty last element; new entries go before it.
+    let at = if elements.last().is_some_and(|last| last.trim().is_empty()) {
+        elements.len() - 1
+    } else {
+        elements.len()
+    };
+    let added: Vec<String> = entries(map, options)
+        .into_iter()
+        .filter(|(key, new)| !new.is_null() && !seen.contains(*key))
+        .map(|(key, new)| format!("{key}={}", inline_value(new, options)))
+        .collect();
+    elements.splice(at..at, added);
+    format!("({})", elements.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use ini_derive::IniSerialize;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct OptionSettings {
+        #[serde(rename = "Difficulty")]
+        difficulty: String,
+        #[serde(rename = "DayTimeSpeedRate")]
+        day_time_speed_rate: f32,
+        #[serde(rename = "NightTimeSpeedRate")]
+        night_time_speed_rate: f32,
+    }
+
+    #[derive(Serialize, Deserialize, IniSerialize, Debug, PartialEq)]
+    #[INIHeader(name = "/Script/Pal.PalGameWorldSettings")]
+    struct GameSettings {
+        #[serde(rename = "OptionSettings")]
+        option_settings: OptionSettings,
+    }
+
+    const GAME_FILE: &str = "; Generated by the game\r\n\
+[/Script/Pal.PalGameWorldSettings]\r\n\
+OptionSettings=(Difficulty=None, DayTimeSpeedRate=1.000000, NightTimeSpeedRate=1.000000, BuildObjectHpRate=1.000000)\r\n";
+
+    #[test]
+    fn unchanged_documents_are_written_back_as_they_were() {
+        assert_eq!(IniDocument::parse(GAME_FILE).to_string(), GAME_FILE);
+
+        let written = "[Section]\nOptionSettings=(\n\tA=1,\n\tB=\"two\",\n)\n\n; end\n";
+        let mut document = IniDocument::parse(written);
+        assert_eq!(
+            document.get("Section", "OptionSettings"),
+            Some(serde_json::json!({"A": 1, "B": "two"}))
+        );
+        document.set("Section", "OptionSettings", &serde_json::json!({"A": 1}));
+        assert_eq!(document.to_string(), written);
+    }
+
+    #[test]
+    fn patching_keeps_unknown_keys_and_comments() {
+        let mut document = IniDocument::parse(GAME_FILE);
+        let mut settings: GameSettings = document.deserialize().unwrap();
+        assert_eq!(settings.option_settings.difficulty, "None");
+
+        settings.option_settings.day_time_speed_rate = 2.0;
+        document.patch(&settings).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "; Generated by the game\r\n\
+[/Script/Pal.PalGameWorldSettings]\r\n\
+OptionSettings=(Difficulty=None, DayTimeSpeedRate=2, NightTimeSpeedRate=1.000000, BuildObjectHpRate=1.000000)\r\n"
+        );
+        assert_eq!(document.deserialize::<GameSettings>().unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_keys_and_sections_are_added() {
+        let mut document = IniDocument::parse("; Server settings\nName=\"Home\",\n");
+        document.set("", "Port", &serde_json::json!(7777));
+        document.set("Mods", "Enabled", &serde_json::json!(["a", "b"]));
+        document
+            .patch(&GameSettings {
+                option_settings: OptionSettings {
+                    difficulty: "Hard".to_owned(),
+                    day_time_speed_rate: 1.5,
+                    night_time_speed_rate: 0.5,
+                },
+            })
+            .unwrap();
+        assert_eq!(
+            document.to_string(),
+            "; Server settings\nName=\"Home\",\nPort=7777,\n\n\
+[Mods]\nEnabled=(\"a\",\"b\")\n\n\
+[/Script/Pal.PalGameWorldSettings]\n\
+OptionSettings=(Difficulty=\"Hard\",DayTimeSpeedRate=1.5,NightTimeSpeedRate=0.5)\n"
+        );
+    }
+
+    #[test]
+    fn repeated_keys_stay_repeated() {
+        let mut document = IniDocument::parse("[Server]\nWarn=60\nWarn=5\nOther=1\n");
+        assert_eq!(
+            document.get("Server", "Warn"),
+            Some(serde_json::json!([60, 5]))
+        );
+        document.set("Server", "Warn", &serde_json::json!([30, 10, 1]));
+        assert_eq!(
+            document.to_string(),
+            "[Server]\nWarn=30\nWarn=10\nWarn=1\nOther=1\n"
+        );
+    }
+}
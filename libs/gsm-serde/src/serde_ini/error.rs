@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors from reading or writing INI files.
+#[derive(Debug)]
+pub enum IniError {
+    /// The INI text is malformed.
+    Parse {
+        /// The 1-based line the problem is on.
+        line: usize,
+        /// The 1-based column, in characters, the problem starts at.
+        column: usize,
+        /// What is wrong.
+        message: String,
+    },
+    /// The values do not fit the type they are read into or written from, e.g. a
+    /// number where a string is expected.
+    Data(serde_json::Error),
+    /// Reading or writing the file failed.
+    Io(io::Error),
+}
+
+impl IniError {
+    pub(crate) fn parse(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for IniError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "line {line}, column {column}: {message}"),
+            Self::Data(e) => write!(f, "invalid INI values: {e}"),
+            Self::Io(e) => write!(f, "INI file I/O failed: {e}"),
+        }
+    }
+}
+
+impl Error for IniError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Parse { .. } => None,
+            Self::Data(e) => Some(e),
+            Self::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for IniError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Data(error)
+    }
+}
+
+impl From<io::Error> for IniError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
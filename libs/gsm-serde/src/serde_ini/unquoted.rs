@@ -0,0 +1,97 @@
+//! Writes a field's value without quotes, for settings the game reads as bare words or
+//! tuples, such as Palworld's `Difficulty=None` or `CrossplayPlatforms=(Steam,Xbox)`.
+//!
+//! Use it with `#[serde(with = "gsm_serde::serde_ini::unquoted")]` on any field whose
+//! type is [`Display`] and [`FromStr`], e.g. a `String` or a settings enum.
+//!
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use gsm_serde::serde_ini::{from_str, to_string, IniHeader};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Settings {
+//!     #[serde(rename = "Difficulty", with = "gsm_serde::serde_ini::unquoted")]
+//!     difficulty: String,
+//!     #[serde(rename = "ServerName")]
+//!     server_name: String,
+//! }
+//!
+//! impl IniHeader for Settings {
+//!     fn ini_header() -> &'static str {
+//!         "Server"
+//!     }
+//! }
+//!
+//! let settings = Settings { difficulty: "None".into(), server_name: "Home".into() };
+//! let ini = to_string(&settings).unwrap();
+//! assert_eq!(ini, "[Server]\nDifficulty=None,\nServerName=\"Home\",\n");
+//! assert_eq!(from_str::<Settings>(&ini).unwrap().difficulty, "None");
+//! ```
+
+use super::format_json_value;
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The key of the one-entry object an unquoted value is serialized as, so the INI writer
+/// can tell it apart from a nested block.
+const MARKER: &str = "$gsm_serde::serde_ini::unquoted";
+
+/// Serializes `value` to be written as its `Display` text, without quotes.
+///
+/// # Errors
+///
+/// Returns an error when `serializer` fails.
+pub fn serialize<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(MARKER, &value.to_string())?;
+    map.end()
+}
+
+/// Deserializes a value written with or without quotes by parsing its text.
+///
+/// # Errors
+///
+/// Returns an error when the text does not parse as `T`.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    bare_text(&value).parse().map_err(D::Error::custom)
+}
+
+/// Returns the text of a value serialized by [`serialize`].
+pub(super) fn text(value: &serde_json::Value) -> Option<&str> {
+    match value {
+        serde_json::Value::Object(map) if map.len() == 1 => map.get(MARKER)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Returns `value` as it would be written unquoted: strings as-is, and tuples and blocks
+/// with their strings as-is too.
+fn bare_text(value: &serde_json::Value) -> String {
+    if let Some(text) = text(value) {
+        return text.to_owned();
+    }
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(bare_text).collect();
+            format!("({})", items.join(","))
+        }
+        serde_json::Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(key, value)| format!("{key}={}", bare_text(value)))
+                .collect();
+            format!("({})", entries.join(","))
+        }
+        _ => format_json_value(value, super::IniOptions::default()),
+    }
+}
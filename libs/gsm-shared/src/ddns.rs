@@ -0,0 +1,486 @@
+//! Keeps a DNS record pointed at the server's public address, so players can join a
+//! home-hosted server by name even when the ISP changes its address.
+//!
+//! [`DdnsUpdater::from_env`] reads the provider from `DDNS_PROVIDER`:
+//!
+//! - `cloudflare`: updates the `A` or `AAAA` record `DDNS_HOSTNAME` in the zone
+//!   `DDNS_ZONE_ID`, with the API token `DDNS_TOKEN`, creating the record if needed.
+//! - `duckdns`: updates the DuckDNS domain `DDNS_HOSTNAME` with the token `DDNS_TOKEN`.
+//! - `url`: gets `DDNS_UPDATE_URL`, with `{ip}` and `{hostname}` replaced, for providers
+//!   with a plain update URL such as No-IP or Dynu.
+//!
+//! [`DdnsUpdater::check`] is meant to run on a schedule: it looks the address up with
+//! [`fetch_public_address`] and only contacts the provider when the address changed.
+//!
+//! ```rust,no_run
+//! use gsm_shared::ddns::DdnsUpdater;
+//!
+//! if let Some(updater) = DdnsUpdater::from_env()? {
+//!     updater.check()?;
+//! }
+//! # Ok::<(), gsm_shared::ddns::DdnsError>(())
+//! ```
+
+use crate::http::{self, HttpError};
+use crate::{Secret, fetch_public_address, fetch_var, redact_secrets};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::{Mutex, PoisonError};
+use std::{error, fmt};
+use tracing::{debug, info};
+
+/// Environment variable naming the DDNS provider: `cloudflare`, `duckdns` or `url`.
+/// Unset, the DNS record is not updated.
+pub const DDNS_PROVIDER: &str = "DDNS_PROVIDER";
+
+/// Environment variable naming the record to update, e.g. `play.example.com`.
+pub const DDNS_HOSTNAME: &str = "DDNS_HOSTNAME";
+
+/// Environment variable holding the provider's API token.
+pub const DDNS_TOKEN: &str = "DDNS_TOKEN";
+
+/// Environment variable holding the Cloudflare zone ID of `DDNS_HOSTNAME`.
+pub const DDNS_ZONE_ID: &str = "DDNS_ZONE_ID";
+
+/// Environment variable holding the update URL of the `url` provider.
+pub const DDNS_UPDATE_URL: &str = "DDNS_UPDATE_URL";
+
+/// How often the public address is checked unless `DDNS_SCHEDULE` says otherwise.
+pub const DEFAULT_DDNS_SCHEDULE: &str = "*/5 * * * *";
+
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+
+const DUCKDNS_UPDATE: &str = "https://www.duckdns.org/update";
+/// Replaced with the address in a `DDNS_UPDATE_URL`.
+const IP_PLACEHOLDER: &str = "{ip}";
+/// Replaced with `DDNS_HOSTNAME` in a `DDNS_UPDATE_URL`.
+const HOSTNAME_PLACEHOLDER: &str = "{hostname}";
+
+/// Why the DNS record could not be updated.
+#[derive(Debug)]
+pub enum DdnsError {
+    /// The `DDNS_*` variables are incomplete or invalid.
+    Config(String),
+    /// The public address could not be looked up.
+    NoAddress(String),
+    /// The provider could not be reached.
+    Http(HttpError),
+    /// The provider refused the update.
+    Rejected(String),
+}
+
+impl fmt::Display for DdnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(reason) => write!(f, "Invalid DDNS configuration: {reason}"),
+            Self::NoAddress(reason) => write!(f, "Failed to look up the public address: {reason}"),
+            // Update URLs often carry the token, so it is scrubbed from request errors.
+            Self::Http(err) => write!(
+                f,
+                "DDNS request failed: {}",
+                redact_secrets(&err.to_string())
+            ),
+            Self::Rejected(reason) => write!(f, "The DDNS provider refused the update: {reason}"),
+        }
+    }
+}
+
+impl error::Error for DdnsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Http(err) => Some(err),
+            Self::Config(_) | Self::NoAddress(_) | Self::Rejected(_) => None,
+        }
+    }
+}
+
+impl From<HttpError> for DdnsError {
+    fn from(err: HttpError) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl From<reqwest::Error> for DdnsError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(HttpError::from(err))
+    }
+}
+
+/// Where the DNS record is hosted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DdnsProvider {
+    /// A record in a Cloudflare zone, updated through the API.
+    Cloudflare {
+        zone_id: String,
+        api_token: Secret<String>,
+    },
+    /// A DuckDNS domain.
+    DuckDns { token: Secret<String> },
+    /// Any provider with an update URL; `{ip}` and `{hostname}` in it are replaced.
+    Url(Secret<String>),
+}
+
+impl DdnsProvider {
+    /// Points `hostname` at `ip`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the provider cannot be reached or refuses the update.
+    pub fn update(&self, hostname: &str, ip: IpAddr) -> Result<(), DdnsError> {
+        match self {
+            Self::Cloudflare { zone_id, api_token } => {
+                cloudflare_update(zone_id, api_token.expose(), hostname, ip)
+            }
+            Self::DuckDns { token } => {
+                let url = duckdns_url(hostname, token.expose(), ip);
+                let body = http::send(|client| client.get(&url))?.text()?;
+                match body.trim() {
+                    "OK" => Ok(()),
+                    answer => Err(DdnsError::Rejected(format!(
+                        "DuckDNS answered '{answer}'; check {DDNS_HOSTNAME} and {DDNS_TOKEN}"
+                    ))),
+                }
+            }
+            Self::Url(template) => {
+                let url = template
+                    .expose()
+                    .replace(IP_PLACEHOLDER, &ip.to_string())
+                    .replace(HOSTNAME_PLACEHOLDER, hostname);
+                http::send(|client| client.get(&url))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads the settings of the provider named `name` from the `DDNS_*` variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DdnsError::Config`] when the provider is unknown or a variable it needs
+    /// is missing.
+    pub fn from_env(name: &str) -> Result<Self, DdnsError> {
+        match name.trim().to_lowercase().as_str() {
+            "cloudflare" => Ok(Self::Cloudflare {
+                zone_id: required(DDNS_ZONE_ID)?,
+                api_token: Secret::new(required(DDNS_TOKEN)?),
+            }),
+            "duckdns" => Ok(Self::DuckDns {
+                token: Secret::new(required(DDNS_TOKEN)?),
+            }),
+            "url" => Ok(Self::Url(Secret::new(required(DDNS_UPDATE_URL)?))),
+            _ => Err(DdnsError::Config(format!(
+                "unknown {DDNS_PROVIDER} '{name}'; expected cloudflare, duckdns or url"
+            ))),
+        }
+    }
+}
+
+/// Points a DNS record at the server's public address whenever it changes.
+#[derive(Debug)]
+pub struct DdnsUpdater {
+    provider: DdnsProvider,
+    hostname: String,
+    last: Mutex<Option<IpAddr>>,
+}
+
+impl DdnsUpdater {
+    pub const fn new(provider: DdnsProvider, hostname: String) -> Self {
+        Self {
+            provider,
+            hostname,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Creates an updater from the `DDNS_*` variables, or returns `None` when
+    /// `DDNS_PROVIDER` is not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DdnsError::Config`] when the provider is unknown or a variable it needs
+    /// is missing.
+    pub fn from_env() -> Result<Option<Self>, DdnsError> {
+        let provider = fetch_var(DDNS_PROVIDER, "");
+        if provider.trim().is_empty() {
+            return Ok(None);
+        }
+        let provider = DdnsProvider::from_env(&provider)?;
+        let hostname = if matches!(provider, DdnsProvider::Url(_)) {
+            fetch_var(DDNS_HOSTNAME, "")
+        } else {
+            required(DDNS_HOSTNAME)?
+        };
+        Ok(Some(Self::new(provider, hostname)))
+    }
+
+    /// Returns the name of the record kept up to date.
+    pub const fn hostname(&self) -> &str {
+        self.hostname.as_str()
+    }
+
+    /// Looks up the public address and updates the record when it changed since the last
+    /// update, returning whether it did. The first check always updates the record.
+    ///
+    /// Addresses are cached by [`public_ip_resolver`](crate::public_ip_resolver), so a
+    /// change is noticed once `PUBLIC_IP_CACHE_SECONDS` have passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the address cannot be looked up or the update fails; the
+    /// next check tries again.
+    pub fn check(&self) -> Result<bool, DdnsError> {
+        let address = fetch_public_address();
+        let ip: IpAddr = address
+            .ip
+            .parse()
+            .map_err(|e| DdnsError::NoAddress(format!("'{}': {e}", address.ip)))?;
+        if ip.is_loopback() || ip.is_unspecified() {
+            return Err(DdnsError::NoAddress(
+                "no resolver endpoint answered".to_owned(),
+            ));
+        }
+        self.update_to(ip)
+    }
+
+    /// Points the record at `ip` unless it was last pointed there, returning whether it
+    /// was updated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the provider cannot be reached or refuses the update.
+    pub fn update_to(&self, ip: IpAddr) -> Result<bool, DdnsError> {
+        let last = *self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        if last == Some(ip) {
+            debug!("Public address is still {ip}");
+            return Ok(false);
+        }
+        self.provider.update(&self.hostname, ip)?;
+        info!("Pointed {} at {ip}", self.display_name());
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Some(ip);
+        Ok(true)
+    }
+
+    const fn display_name(&self) -> &str {
+        if self.hostname.is_empty() {
+            "the DDNS record"
+        } else {
+            self.hostname.as_str()
+        }
+    }
+}
+
+fn required(name: &str) -> Result<String, DdnsError> {
+    let value = fetch_var(name, "");
+    if value.trim().is_empty() {
+        Err(DdnsError::Config(format!("{name} is not set")))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Returns the DuckDNS update URL pointing `hostname`, with or without the
+/// `.duckdns.org` suffix, at `ip`.
+fn duckdns_url(hostname: &str, token: &str, ip: IpAddr) -> String {
+    let domain = hostname.trim_end_matches(".duckdns.org");
+    let family = match ip {
+        IpAddr::V4(_) => "ip",
+        IpAddr::V6(_) => "ipv6",
+    };
+    format!("{DUCKDNS_UPDATE}?domains={domain}&token={token}&{family}={ip}")
+}
+
+/// A Cloudflare API answer.
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<CloudflareMessage>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareMessage {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DnsRecord {
+    id: String,
+    content: String,
+}
+
+impl<T> CloudflareResponse<T> {
+    /// Returns the result of a successful answer.
+    fn into_result(self) -> Result<Option<T>, DdnsError> {
+        if self.success {
+            return Ok(self.result);
+        }
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|error| format!("{} ({})", error.message, error.code))
+            .collect();
+        Err(DdnsError::Rejected(format!(
+            "Cloudflare answered: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+fn cloudflare_update(
+    zone_id: &str,
+    api_token: &str,
+    hostname: &str,
+    ip: IpAddr,
+) -> Result<(), DdnsError> {
+    let record_type = match ip {
+        IpAddr::V4(_) => "A",
+        IpAddr::V6(_) => "AAAA",
+    };
+    let records_url = format!("{CLOUDFLARE_API}/zones/{zone_id}/dns_records");
+    let lookup_url =
+        Url::parse_with_params(&records_url, [("type", record_type), ("name", hostname)])
+            .map_err(|e| DdnsError::Config(format!("{DDNS_ZONE_ID} '{zone_id}': {e}")))?;
+    let records = http::send(|client| client.get(lookup_url.clone()).bearer_auth(api_token))?
+        .json::<CloudflareResponse<Vec<DnsRecord>>>()?
+        .into_result()?
+        .unwrap_or_default();
+
+    let content = ip.to_string();
+    let response = match records.first() {
+        Some(record) if record.content == content => {
+            debug!("{hostname} already points at {ip}");
+            return Ok(());
+        }
+        Some(record) => {
+            let patch_url = format!("{records_url}/{}", record.id);
+            http::send(|client| {
+                client
+                    .patch(&patch_url)
+                    .bearer_auth(api_token)
+                    .json(&json!({ "content": content }))
+            })?
+        }
+        None => {
+            debug!("Creating the {record_type} record {hostname}");
+            http::send(|client| {
+                client
+                    .post(&records_url)
+                    .bearer_auth(api_token)
+                    .json(&json!({
+                        "type": record_type,
+                        "name": hostname,
+                        "content": content,
+                        "ttl": 1,
+                        "proxied": false,
+                    }))
+            })?
+        }
+    };
+    response
+        .json::<CloudflareResponse<DnsRecord>>()?
+        .into_result()
+        .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn duckdns_urls_name_the_domain_and_address_family() {
+        assert_eq!(
+            duckdns_url(
+                "myserver.duckdns.org",
+                "t0k3n",
+                "203.0.113.7".parse().unwrap()
+            ),
+            "https://www.duckdns.org/update?domains=myserver&token=t0k3n&ip=203.0.113.7"
+        );
+        assert_eq!(
+            duckdns_url("myserver", "t0k3n", "2001:db8::1".parse().unwrap()),
+            "https://www.duckdns.org/update?domains=myserver&token=t0k3n&ipv6=2001:db8::1"
+        );
+    }
+
+    #[test]
+    fn cloudflare_errors_are_reported() {
+        let answer: CloudflareResponse<Vec<DnsRecord>> = serde_json::from_str(
+            r#"{"success":false,"errors":[{"code":9109,"message":"Invalid access token"}],"result":null}"#,
+        )
+        .unwrap();
+        let error = answer.into_result().err().map(|e| e.to_string());
+        assert_eq!(
+            error.as_deref(),
+            Some(
+                "The DDNS provider refused the update: Cloudflare answered: Invalid access token (9109)"
+            )
+        );
+
+        let answer: CloudflareResponse<Vec<DnsRecord>> = serde_json::from_str(
+            r#"{"success":true,"result":[{"id":"abc","content":"203.0.113.7","type":"A"}]}"#,
+        )
+        .unwrap();
+        let records = answer.into_result().unwrap().unwrap();
+        assert_eq!(
+            records.first().map(|record| record.id.as_str()),
+            Some("abc")
+        );
+    }
+
+    #[test]
+    fn update_urls_are_only_called_when_the_address_changes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let template = format!(
+            "http://{}/update?host={{hostname}}&myip={{ip}}",
+            listener.local_addr().unwrap()
+        );
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let len = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(request.get(..len).unwrap_or_default());
+                requests.push(request.lines().next().unwrap_or_default().to_owned());
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\ngood",
+                );
+            }
+            requests
+        });
+
+        let updater = DdnsUpdater::new(
+            DdnsProvider::Url(Secret::new(template)),
+            "play.example.com".to_owned(),
+        );
+        let first: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!(updater.update_to(first).unwrap());
+        assert!(!updater.update_to(first).unwrap());
+        assert!(updater.update_to("203.0.113.8".parse().unwrap()).unwrap());
+
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "GET /update?host=play.example.com&myip=203.0.113.7 HTTP/1.1",
+                "GET /update?host=play.example.com&myip=203.0.113.8 HTTP/1.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn request_errors_do_not_leak_tokens() {
+        let error = DdnsError::Http(HttpError::InvalidUrl(
+            "https://www.duckdns.org/update?domains=x&token=t0k3n&ip=1.2.3.4".to_owned(),
+        ));
+        assert!(!error.to_string().contains("t0k3n"));
+    }
+}
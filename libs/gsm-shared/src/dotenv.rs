@@ -0,0 +1,222 @@
+use crate::invalidate_env_cache;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Environment variable naming the `.env` file [`load_default_dotenv`] reads.
+pub const GSM_ENV_FILE: &str = "GSM_ENV_FILE";
+
+/// The file [`load_default_dotenv`] reads from the current directory when `GSM_ENV_FILE`
+/// is unset.
+pub const DEFAULT_DOTENV_FILE: &str = ".env";
+
+/// Parses the contents of a `.env` file into its variables, in file order.
+///
+/// Each line is `KEY=value`, optionally prefixed with `export`. Blank lines and lines
+/// starting with `#` are skipped, as are lines without a valid key, which are logged.
+/// Values may be wrapped in single quotes, taken literally, or double quotes, which
+/// understand `\n`, `\"` and `\\`. Unquoted values end at a ` #` comment.
+pub fn parse_dotenv(contents: &str) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let Some((key, value)) = line.split_once('=') else {
+            warn!(
+                "Ignoring line {} of .env file: expected KEY=value",
+                number + 1
+            );
+            continue;
+        };
+        let key = key.trim();
+        if !is_valid_key(key) {
+            warn!(
+                "Ignoring line {} of .env file: invalid key {key:?}",
+                number + 1
+            );
+            continue;
+        }
+        vars.push((key.to_owned(), parse_value(value.trim())));
+    }
+    vars
+}
+
+/// Returns whether `key` is a portable environment variable name.
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(value: &str) -> String {
+    if let Some(inner) = value
+        .strip_prefix('\'')
+        .and_then(|rest| rest.split_once('\''))
+        .map(|(inner, _)| inner)
+    {
+        return inner.to_owned();
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::with_capacity(rest.len());
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return parsed,
+                '\\' => match chars.next() {
+                    Some('n') => parsed.push('\n'),
+                    Some(escaped) => parsed.push(escaped),
+                    None => parsed.push('\\'),
+                },
+                c => parsed.push(c),
+            }
+        }
+        return parsed;
+    }
+    value
+        .split_once(" #")
+        .map_or(value, |(value, _)| value)
+        .trim_end()
+        .to_owned()
+}
+
+/// Sets each of `vars` that is not already set in the process environment and returns
+/// the names of those it set. `source` names where they came from, for the logs.
+///
+/// # Safety
+///
+/// This calls [`env::set_var`], so no other thread may read or write the environment
+/// while it runs. Call it from a synchronous `main` before building the async runtime or
+/// spawning any thread.
+pub unsafe fn export_missing_vars(
+    vars: impl IntoIterator<Item = (String, String)>,
+    source: &str,
+) -> Vec<String> {
+    let mut exported = Vec::new();
+    for (key, value) in vars {
+        if env::var_os(&key).is_some() {
+            debug!("Keeping {key} from the environment over {source}");
+            continue;
+        }
+        // SAFETY: the caller guarantees no other thread uses the environment.
+        unsafe {
+            env::set_var(&key, value);
+        }
+        exported.push(key);
+    }
+    invalidate_env_cache();
+    exported
+}
+
+/// Loads the variables of the `.env` file at `path` into the process environment and
+/// returns the names of those it set.
+///
+/// Variables already set in the environment win over the file, so a single value can
+/// still be overridden by the container or shell.
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be read.
+///
+/// # Safety
+///
+/// The same as [`export_missing_vars`]: call it at the start of a synchronous `main`,
+/// before the async runtime is built and before any variable is read.
+pub unsafe fn load_dotenv(path: &Path) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    // SAFETY: the caller upholds the contract of `export_missing_vars`.
+    let loaded =
+        unsafe { export_missing_vars(parse_dotenv(&contents), &path.display().to_string()) };
+    debug!("Loaded {} variables from {}", loaded.len(), path.display());
+    Ok(loaded)
+}
+
+/// Loads the `.env` file named by `GSM_ENV_FILE`, or else `.env` in the current
+/// directory if there is one; see [`load_dotenv`]. Returns the names of the variables
+/// it set.
+///
+/// # Errors
+///
+/// Returns an error when `GSM_ENV_FILE` names a missing file, or the file cannot be
+/// read.
+///
+/// # Safety
+///
+/// The same as [`load_dotenv`].
+pub unsafe fn load_default_dotenv() -> io::Result<Vec<String>> {
+    // SAFETY: the caller upholds the contract of `load_dotenv`.
+    env::var_os(GSM_ENV_FILE).map_or_else(
+        || match unsafe { load_dotenv(Path::new(DEFAULT_DOTENV_FILE)) } {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            result => result,
+        },
+        |path| unsafe { load_dotenv(&PathBuf::from(path)) },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parses_quotes_comments_and_exports() {
+        let vars = parse_dotenv(
+            r#"
+# Palworld settings
+SERVER_NAME="My \"Pal\" Server"
+export PORT=8211
+MOTD='Welcome\n'
+DIFFICULTY=Normal # comment
+EMPTY=
+not a variable
+1BAD=value
+"#,
+        );
+        assert_eq!(
+            vars,
+            [
+                ("SERVER_NAME".to_owned(), "My \"Pal\" Server".to_owned()),
+                ("PORT".to_owned(), "8211".to_owned()),
+                ("MOTD".to_owned(), "Welcome\\n".to_owned()),
+                ("DIFFICULTY".to_owned(), "Normal".to_owned()),
+                ("EMPTY".to_owned(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn environment_wins_over_the_file() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join(".env");
+        fs::write(
+            &path,
+            "TEST_DOTENV_FROM_FILE=file\nTEST_DOTENV_ALREADY_SET=file\n",
+        )?;
+        unsafe {
+            env::remove_var("TEST_DOTENV_FROM_FILE");
+            env::set_var("TEST_DOTENV_ALREADY_SET", "environment");
+        }
+
+        let loaded = unsafe { load_dotenv(&path)? };
+        assert_eq!(loaded, ["TEST_DOTENV_FROM_FILE"]);
+        assert_eq!(env::var("TEST_DOTENV_FROM_FILE").as_deref(), Ok("file"));
+        assert_eq!(
+            env::var("TEST_DOTENV_ALREADY_SET").as_deref(),
+            Ok("environment")
+        );
+
+        unsafe {
+            env::remove_var("TEST_DOTENV_FROM_FILE");
+            env::remove_var("TEST_DOTENV_ALREADY_SET");
+        }
+        assert!(unsafe { load_dotenv(&temp_dir.path().join("missing.env")) }.is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use crate::parse_truthy;
-use cached::macros::cached;
-use std::env;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+use std::{env, error, fmt};
+use tracing::warn;
 
 /// Strips a single matching pair of wrapping double or single quotes, if present.
 ///
@@ -43,13 +46,255 @@ pub fn fetch_multiple_var(name: &str, default: &str) -> String {
     }
 }
 
+/// How long [`is_env_var_truthy`] reuses a variable's value before reading it again.
+pub const TRUTHY_CACHE_TTL: Duration = Duration::from_mins(1);
+
+fn truthy_cache() -> &'static Mutex<HashMap<String, (Instant, bool)>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, (Instant, bool)>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
 /// Determines if the named environment variable is truthy.
-/// Uses caching for improved performance.
-#[cached]
-pub fn is_env_var_truthy(name: &'static str) -> bool {
+///
+/// Flags are checked often, e.g. for every log line, so the answer is reused for
+/// [`TRUTHY_CACHE_TTL`]. After changing the environment, call [`invalidate_env_cache`]
+/// for the change to be seen straight away, or use [`is_env_var_truthy_uncached`].
+pub fn is_env_var_truthy(name: &str) -> bool {
+    let mut cache = truthy_cache()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(&(read, value)) = cache.get(name)
+        && read.elapsed() < TRUTHY_CACHE_TTL
+    {
+        return value;
+    }
+    let value = is_env_var_truthy_uncached(name);
+    cache.insert(name.to_owned(), (Instant::now(), value));
+    value
+}
+
+/// Determines if the named environment variable is truthy, reading it every time.
+pub fn is_env_var_truthy_uncached(name: &str) -> bool {
     parse_truthy(&fetch_var(name, "0")).unwrap_or(false)
 }
 
+/// Forgets the values [`is_env_var_truthy`] reused, so the next checks read the
+/// environment again. Call it after setting variables, as loading a `.env` file does.
+pub fn invalidate_env_cache() {
+    truthy_cache()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Why an environment variable could not be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvVarError {
+    /// The variable is not set, or is empty. `suggestion` names a set variable with a
+    /// similar name, which may be a misspelling of it.
+    Missing {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// The variable is set to a value its validator rejects.
+    Invalid { name: String, reason: String },
+}
+
+impl fmt::Display for EnvVarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "{name} is not set; {suggestion} is, did you mean {name}?"
+            ),
+            Self::Missing {
+                name,
+                suggestion: None,
+            } => write!(f, "{name} is not set"),
+            Self::Invalid { name, reason } => write!(f, "{name} is invalid: {reason}"),
+        }
+    }
+}
+
+impl error::Error for EnvVarError {}
+
+/// Every problem [`validate_env`] found, so they can be fixed in one go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvValidationError {
+    pub issues: Vec<EnvVarError>,
+}
+
+impl fmt::Display for EnvValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid environment:")?;
+        for issue in &self.issues {
+            write!(f, "\n  - {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for EnvValidationError {}
+
+/// Fetches an environment variable that must be set, like [`fetch_var`] without a
+/// default.
+///
+/// # Errors
+///
+/// Returns [`EnvVarError::Missing`] when the variable is not set or empty, suggesting
+/// a set variable whose name is one or two typos away.
+pub fn fetch_var_required(name: &str) -> Result<String, EnvVarError> {
+    let value = fetch_var(name, "");
+    if value.is_empty() {
+        let set: Vec<String> = env::vars_os()
+            .filter_map(|(key, _)| key.into_string().ok())
+            .collect();
+        Err(EnvVarError::Missing {
+            name: name.to_owned(),
+            suggestion: closest_name(name, set.iter().map(String::as_str)).map(str::to_owned),
+        })
+    } else {
+        Ok(value)
+    }
+}
+
+/// Checks a value, returning why it is invalid.
+pub type EnvValidator = fn(&str) -> Result<(), String>;
+
+/// An environment variable an app reads, for [`validate_env`].
+#[derive(Debug, Clone, Copy)]
+pub struct VarSpec {
+    name: &'static str,
+    required: bool,
+    validator: Option<EnvValidator>,
+}
+
+impl VarSpec {
+    /// A variable that must be set.
+    pub const fn required(name: &'static str) -> Self {
+        Self {
+            name,
+            required: true,
+            validator: None,
+        }
+    }
+
+    /// A variable that may be left unset, falling back to a default.
+    pub const fn optional(name: &'static str) -> Self {
+        Self {
+            name,
+            required: false,
+            validator: None,
+        }
+    }
+
+    /// Checks the variable's value with `validator` when it is set.
+    #[must_use]
+    pub const fn with_validator(mut self, validator: EnvValidator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Returns the variable's name.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Checks the environment against `specs` at startup, so a misconfigured container
+/// fails straight away rather than running on silent defaults.
+///
+/// Unset optional variables are fine, but when a set variable that no spec names is a
+/// typo or two away from one, e.g. `WEBOOK_URL` for `WEBHOOK_URL`, it is logged as a
+/// likely misspelling.
+///
+/// # Errors
+///
+/// Returns every required variable that is missing and every value a validator
+/// rejects.
+pub fn validate_env(specs: &[VarSpec]) -> Result<(), EnvValidationError> {
+    let unknown: Vec<String> = env::vars_os()
+        .filter_map(|(key, _)| key.into_string().ok())
+        .filter(|key| !specs.iter().any(|spec| spec.name == *key))
+        .collect();
+    let mut issues = Vec::new();
+    for spec in specs {
+        match fetch_var_required(spec.name) {
+            Ok(value) => {
+                if let Some(validator) = spec.validator
+                    && let Err(reason) = validator(&value)
+                {
+                    issues.push(EnvVarError::Invalid {
+                        name: spec.name.to_owned(),
+                        reason,
+                    });
+                }
+            }
+            Err(missing) if spec.required => issues.push(missing),
+            Err(_) => {
+                if let Some(typo) = closest_name(spec.name, unknown.iter().map(String::as_str)) {
+                    warn!(
+                        "{typo} is set but {} is not; did you mean {}?",
+                        spec.name, spec.name
+                    );
+                }
+            }
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(EnvValidationError { issues })
+    }
+}
+
+/// An [`EnvValidator`] for on/off flags, which accepts what [`is_env_var_truthy`]
+/// understands: `true`, `false`, `1` and `0`.
+///
+/// # Errors
+///
+/// Returns the reason for any other value, which would silently count as off.
+pub fn validate_flag(value: &str) -> Result<(), String> {
+    if matches!(value.to_lowercase().as_str(), "true" | "false" | "1" | "0") {
+        Ok(())
+    } else {
+        Err(format!("expected true or false, got '{value}'"))
+    }
+}
+
+/// Returns the name among `candidates` that is closest to `name` without being it, if
+/// one is a likely misspelling: one edit away for short names, two for longer ones.
+fn closest_name<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = if name.len() < 6 { 1 } else { 2 };
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Returns the Levenshtein distance between `a` and `b`, ignoring ASCII case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().map(|c| c.to_ascii_uppercase()).collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().map(|c| c.to_ascii_uppercase()).enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous.get(j).copied().unwrap_or_default() + usize::from(a != b);
+            let deletion = previous.get(j + 1).copied().unwrap_or_default() + 1;
+            let insertion = current.get(j).copied().unwrap_or_default() + 1;
+            current.push(substitution.min(deletion).min(insertion));
+        }
+        previous = current;
+    }
+    previous.last().copied().unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +413,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_is_env_var_truthy_accepts_dynamic_names() {
+        let key = format!("TEST_IS_ENV_VAR_TRUTHY_{}", "DYNAMIC");
+        unsafe {
+            env::set_var(&key, "1");
+        }
+        assert!(is_env_var_truthy(&key));
+        unsafe {
+            env::set_var(&key, "0");
+        }
+        assert!(!is_env_var_truthy_uncached(&key));
+        invalidate_env_cache();
+        assert!(!is_env_var_truthy(&key));
+        unsafe {
+            env::remove_var(&key);
+        }
+    }
+
     #[test]
     fn test_is_env_var_truthy_falsy() {
         let key = "TEST_IS_ENV_VAR_TRUTHY_FALSY";
@@ -179,4 +442,73 @@ mod tests {
             env::remove_var(key);
         }
     }
+
+    #[test]
+    fn test_edit_distance_ignores_case() {
+        assert_eq!(edit_distance("WEBHOOK_URL", "WEBOOK_URL"), 1);
+        assert_eq!(edit_distance("webhook_url", "WEBHOOK_URL"), 0);
+        assert_eq!(edit_distance("PORT", "NAME"), 4);
+        assert_eq!(edit_distance("", "ABC"), 3);
+    }
+
+    #[test]
+    fn test_fetch_var_required_suggests_near_misses() {
+        let key = "TEST_FETCH_VAR_REQUIRED_SERVER_NAME";
+        let typo = "TEST_FETCH_VAR_REQUIRED_SERVR_NAME";
+        unsafe {
+            env::remove_var(key);
+            env::set_var(typo, "My Server");
+        }
+        let error = fetch_var_required(key).err();
+        assert_eq!(
+            error,
+            Some(EnvVarError::Missing {
+                name: key.to_owned(),
+                suggestion: Some(typo.to_owned()),
+            })
+        );
+        assert_eq!(fetch_var_required(typo), Ok("My Server".to_owned()));
+        unsafe {
+            env::remove_var(typo);
+        }
+    }
+
+    #[test]
+    fn test_validate_env_reports_every_issue() {
+        let flag = "TEST_VALIDATE_ENV_FLAG";
+        let required = "TEST_VALIDATE_ENV_REQUIRED";
+        let optional = "TEST_VALIDATE_ENV_OPTIONAL";
+        unsafe {
+            env::set_var(flag, "yes");
+            env::remove_var(required);
+            env::remove_var(optional);
+        }
+        let specs = [
+            VarSpec::optional(flag).with_validator(validate_flag),
+            VarSpec::required(required),
+            VarSpec::optional(optional),
+        ];
+        let error = validate_env(&specs).err();
+        assert_eq!(
+            error.as_ref().map(|error| error.issues.len()),
+            Some(2),
+            "{error:?}"
+        );
+        let message = error.map(|error| error.to_string()).unwrap_or_default();
+        assert!(
+            message
+                .contains("TEST_VALIDATE_ENV_FLAG is invalid: expected true or false, got 'yes'")
+        );
+        assert!(message.contains("TEST_VALIDATE_ENV_REQUIRED is not set"));
+
+        unsafe {
+            env::set_var(flag, "TRUE");
+            env::set_var(required, "set");
+        }
+        assert_eq!(validate_env(&specs), Ok(()));
+        unsafe {
+            env::remove_var(flag);
+            env::remove_var(required);
+        }
+    }
 }
@@ -1,13 +1,246 @@
-use reqwest::blocking::Client;
+//! Looks up the server's public address, from `ADDRESS` and `PORT` or from public resolver endpoints.
+//!
+//! Resolvers are asked over IPv4 or IPv6 as requested, so a dual-stack endpoint answers with the address of that
+//! family. Answers are cached for a while, so repeated lookups do not hit the endpoints again. The endpoints and how
+//! long answers are cached can be set with `PUBLIC_IP_ENDPOINTS` and `PUBLIC_IP_CACHE_SECONDS`.
+
+use crate::http;
+use reqwest::blocking::{Client, Response};
+use std::collections::HashMap;
 use std::env::VarError;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
 use std::{env, fmt};
 use tracing::{debug, error};
 
+/// The endpoints asked for the public address unless `PUBLIC_IP_ENDPOINTS` lists others.
+/// Each answers with the address it was reached from, as JSON with an `ip` field or as
+/// plain text.
+pub const DEFAULT_PUBLIC_IP_ENDPOINTS: [&str; 4] = [
+    "https://api64.ipify.org?format=json",
+    "https://api.seeip.org/jsonip?",
+    "https://icanhazip.com",
+    "https://ipinfo.io",
+];
+
+/// How long a looked up address is reused unless `PUBLIC_IP_CACHE_SECONDS` says otherwise.
+pub const DEFAULT_PUBLIC_IP_TTL: Duration = Duration::from_mins(10);
+
+/// How long a resolver endpoint may take to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 struct IPResponse {
     ip: String,
 }
 
+/// An IP address family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    /// IPv4.
+    V4,
+    /// IPv6.
+    V6,
+}
+
+impl IpFamily {
+    /// Returns the unspecified address of the family, which requests are sent from so
+    /// they go out over it.
+    const fn unspecified(self) -> IpAddr {
+        match self {
+            Self::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Self::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+
+    const fn of(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Self::V4,
+            IpAddr::V6(_) => Self::V6,
+        }
+    }
+}
+
+/// The server's public addresses, for the families it has one in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PublicAddresses {
+    /// The public IPv4 address.
+    pub v4: Option<Ipv4Addr>,
+    /// The public IPv6 address.
+    pub v6: Option<Ipv6Addr>,
+}
+
+/// Looks up public addresses from resolver endpoints, caching each answer for a while.
+#[derive(Debug)]
+pub struct PublicIpResolver {
+    endpoints: Vec<String>,
+    ttl: Duration,
+    cache: Mutex<HashMap<IpFamily, (Instant, IpAddr)>>,
+}
+
+impl PublicIpResolver {
+    /// Creates a resolver asking `endpoints`, in order, and reusing answers for `ttl`.
+    pub fn new(endpoints: Vec<String>, ttl: Duration) -> Self {
+        Self {
+            endpoints,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a resolver from `PUBLIC_IP_ENDPOINTS`, a comma-separated list of URLs, and
+    /// `PUBLIC_IP_CACHE_SECONDS`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let endpoints: Vec<String> = env::var("PUBLIC_IP_ENDPOINTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|endpoint| !endpoint.is_empty())
+            .map(str::to_owned)
+            .collect();
+        let endpoints = if endpoints.is_empty() {
+            Vec::from(DEFAULT_PUBLIC_IP_ENDPOINTS.map(str::to_owned))
+        } else {
+            endpoints
+        };
+        let ttl = env::var("PUBLIC_IP_CACHE_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .map_or(DEFAULT_PUBLIC_IP_TTL, Duration::from_secs);
+        Self::new(endpoints, ttl)
+    }
+
+    /// Returns the public address of `family`, from the cache or the first endpoint that
+    /// answers with one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no endpoint answers with an address of `family`, e.g. as the
+    /// server has no route out over it.
+    pub async fn fetch(&self, family: IpFamily) -> io::Result<IpAddr> {
+        if let Some(ip) = self.cached(family) {
+            return Ok(ip);
+        }
+        let client = http::async_client_bound_to(family.unspecified()).map_err(io::Error::other)?;
+        for url in &self.endpoints {
+            let body = match client.get(url).timeout(REQUEST_TIMEOUT).send().await {
+                Ok(response) => response.text().await,
+                Err(e) => Err(e),
+            };
+            match body {
+                Ok(body) => {
+                    if let Some(ip) = self.accept(url, &body, family) {
+                        return Ok(ip);
+                    }
+                }
+                Err(e) => debug!("Request to {} failed: {}", url, e),
+            }
+        }
+        Err(no_address(family))
+    }
+
+    /// Returns the public addresses of both families, leaving out those that cannot be
+    /// looked up.
+    pub async fn fetch_all(&self) -> PublicAddresses {
+        let v4 = self.fetch(IpFamily::V4).await.ok();
+        let v6 = self.fetch(IpFamily::V6).await.ok();
+        PublicAddresses {
+            v4: v4.and_then(|ip| match ip {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            }),
+            v6: v6.and_then(|ip| match ip {
+                IpAddr::V6(ip) => Some(ip),
+                IpAddr::V4(_) => None,
+            }),
+        }
+    }
+
+    /// Returns the public address of `family` like [`PublicIpResolver::fetch`], blocking
+    /// until it is looked up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no endpoint answers with an address of `family`.
+    pub fn fetch_blocking(&self, family: IpFamily) -> io::Result<IpAddr> {
+        if let Some(ip) = self.cached(family) {
+            return Ok(ip);
+        }
+        let client = http::client_bound_to(family.unspecified()).map_err(io::Error::other)?;
+        for url in &self.endpoints {
+            match client
+                .get(url)
+                .timeout(REQUEST_TIMEOUT)
+                .send()
+                .and_then(Response::text)
+            {
+                Ok(body) => {
+                    if let Some(ip) = self.accept(url, &body, family) {
+                        return Ok(ip);
+                    }
+                }
+                Err(e) => debug!("Request to {} failed: {}", url, e),
+            }
+        }
+        Err(no_address(family))
+    }
+
+    fn cached(&self, family: IpFamily) -> Option<IpAddr> {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&family)
+            .filter(|(fetched, _)| fetched.elapsed() < self.ttl)
+            .map(|&(_, ip)| ip)
+    }
+
+    fn store(&self, ip: IpAddr) {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(IpFamily::of(ip), (Instant::now(), ip));
+    }
+
+    /// Caches and returns the address `url` answered with, when it is of `family`.
+    fn accept(&self, url: &str, body: &str, family: IpFamily) -> Option<IpAddr> {
+        match parse_ip(body) {
+            Some(ip) if IpFamily::of(ip) == family => {
+                self.store(ip);
+                Some(ip)
+            }
+            Some(ip) => {
+                debug!("{} answered with {}, not an {:?} address", url, ip, family);
+                None
+            }
+            None => {
+                debug!("Failed to parse an address from {}: {}", url, body.trim());
+                None
+            }
+        }
+    }
+}
+
+/// Returns the resolver built from the environment, shared so its cache is.
+pub fn public_ip_resolver() -> &'static PublicIpResolver {
+    static RESOLVER: OnceLock<PublicIpResolver> = OnceLock::new();
+    RESOLVER.get_or_init(PublicIpResolver::from_env)
+}
+
+/// Parses a resolver's answer, JSON with an `ip` field or the bare address.
+fn parse_ip(body: &str) -> Option<IpAddr> {
+    serde_json::from_str::<IPResponse>(body)
+        .map_or_else(|_| body.trim().parse().ok(), |json| json.ip.parse().ok())
+}
+
+fn no_address(family: IpFamily) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("All {family:?} IP fetch attempts failed"),
+    )
+}
+
 pub struct IPConfig {
     pub(crate) ip: String,
     pub(crate) port: u16,
@@ -15,7 +248,11 @@ pub struct IPConfig {
 
 impl fmt::Display for IPConfig {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}:{}", self.ip, self.port)
+        if self.ip.contains(':') {
+            write!(f, "[{}]:{}", self.ip, self.port)
+        } else {
+            write!(f, "{}:{}", self.ip, self.port)
+        }
     }
 }
 
@@ -59,62 +296,69 @@ impl IPConfig {
         }
     }
 
-    /// Fetches the public IP from known API endpoints.
+    /// Fetches the public IPv4 address from the default endpoints.
     ///
     /// # Errors
     ///
     /// Returns an error when all configured endpoints fail to return a parseable
     /// response.
     pub fn fetch_ip_from_api(&self, client: &Client) -> Result<String, Box<dyn std::error::Error>> {
-        let urls = [
-            "https://api.ipify.org?format=json",
-            "https://api.seeip.org/jsonip?",
-            "https://ipinfo.io",
-        ];
-
-        for url in urls {
-            match client.get(url).send() {
-                Ok(response) => match response.json::<IPResponse>() {
-                    Ok(json) => return Ok(json.ip),
-                    Err(e) => {
-                        debug!("Failed to parse JSON from {}: {}", url, e);
+        for url in DEFAULT_PUBLIC_IP_ENDPOINTS {
+            match client.get(url).send().and_then(Response::text) {
+                Ok(body) => {
+                    if let Some(ip @ IpAddr::V4(_)) = parse_ip(&body) {
+                        return Ok(ip.to_string());
                     }
-                },
+                    debug!("Failed to parse an IPv4 address from {}", url);
+                }
                 Err(e) => {
                     debug!("Request to {} failed: {}", url, e);
                 }
             }
         }
 
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "All IP fetch attempts failed",
-        )))
+        Err(Box::new(no_address(IpFamily::V4)))
     }
 }
 
-// Standardized way of fetching public address.
+/// Returns the public address from `ADDRESS` and `PORT`, or else the public IPv4 address
+/// looked up through [`public_ip_resolver`] on the default port.
 pub fn fetch_public_address() -> IPConfig {
-    let client = Client::new();
-    let mut ip_config = IPConfig::default();
     debug!("Checking for address in env");
-    match ip_config.to_string_from_env() {
+    IPConfig::default().to_string_from_env().map_or_else(
+        |_| with_looked_up_ip(public_ip_resolver().fetch_blocking(IpFamily::V4)),
+        |ip| {
+            debug!("Fetched IP from env: {}", ip);
+            ip
+        },
+    )
+}
+
+/// Returns the public address like [`fetch_public_address`], looking it up without
+/// blocking and in `family`.
+pub async fn fetch_public_address_async(family: IpFamily) -> IPConfig {
+    debug!("Checking for address in env");
+    match IPConfig::default().to_string_from_env() {
         Ok(ip) => {
             debug!("Fetched IP from env: {}", ip);
             ip
         }
-        Err(_) => match ip_config.fetch_ip_from_api(&client) {
-            Ok(ip) => {
-                debug!("Fetched IP from API: {}", ip);
-                ip_config.ip = ip;
-                ip_config
-            }
-            Err(e) => {
-                debug!("Failed to fetch IP from API: {}", e);
-                ip_config
-            }
-        },
+        Err(_) => with_looked_up_ip(public_ip_resolver().fetch(family).await),
+    }
+}
+
+fn with_looked_up_ip(looked_up: io::Result<IpAddr>) -> IPConfig {
+    let mut ip_config = IPConfig::default();
+    match looked_up {
+        Ok(ip) => {
+            debug!("Fetched IP from API: {}", ip);
+            ip_config.ip = ip.to_string();
+        }
+        Err(e) => {
+            debug!("Failed to fetch IP from API: {}", e);
+        }
     }
+    ip_config
 }
 
 #[cfg(test)]
@@ -203,4 +447,41 @@ mod tests {
         let config = IPConfig::new("1.2.3.4".to_owned(), 1234);
         assert_eq!(config.to_string(), "1.2.3.4:1234");
     }
+
+    #[test]
+    fn test_display_brackets_ipv6_addresses() {
+        let config = IPConfig::new("2001:db8::1".to_owned(), 8211);
+        assert_eq!(config.to_string(), "[2001:db8::1]:8211");
+    }
+
+    #[test]
+    fn test_parse_ip_accepts_json_and_plain_text() {
+        assert_eq!(
+            parse_ip(r#"{"ip":"203.0.113.7"}"#),
+            Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)))
+        );
+        assert_eq!(
+            parse_ip("2001:db8::1\n"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(parse_ip("<html>rate limited</html>"), None);
+    }
+
+    #[test]
+    fn test_resolver_reuses_answers_until_they_expire() {
+        let resolver = PublicIpResolver::new(Vec::new(), Duration::from_mins(1));
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(resolver.accept("test", "2001:db8::1", IpFamily::V4), None);
+        assert_eq!(
+            resolver.accept("test", "2001:db8::1", IpFamily::V6),
+            Some(v6)
+        );
+
+        assert_eq!(resolver.fetch_blocking(IpFamily::V6).unwrap(), v6);
+        assert!(resolver.fetch_blocking(IpFamily::V4).is_err());
+
+        let expired = PublicIpResolver::new(Vec::new(), Duration::ZERO);
+        expired.store(v6);
+        assert!(expired.fetch_blocking(IpFamily::V6).is_err());
+    }
 }
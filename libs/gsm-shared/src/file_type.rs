@@ -0,0 +1,195 @@
+use reqwest::Url;
+use reqwest::header::{CONTENT_DISPOSITION, CONTENT_TYPE, HeaderMap};
+
+/// File types of common download `Content-Type`s, for URLs that do not name a file.
+const CONTENT_TYPES: [(&str, &str); 10] = [
+    ("application/zip", "zip"),
+    ("application/x-zip-compressed", "zip"),
+    ("application/gzip", "gz"),
+    ("application/x-gzip", "gz"),
+    ("application/x-tar", "tar"),
+    ("application/x-7z-compressed", "7z"),
+    ("application/java-archive", "jar"),
+    ("application/x-msdownload", "dll"),
+    ("application/json", "json"),
+    ("text/plain", "txt"),
+];
+
+/// Returns the file name at the end of `url`'s path, e.g. `archive.tar.gz` for
+/// `https://example.com/files/archive.tar.gz?download=1`, or `None` when the path ends
+/// in a directory.
+pub fn parse_file_name(url: &Url) -> Option<String> {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(safe_file_name)
+}
+
+/// Returns the file type, the lowercase extension, of the file `url` names.
+///
+/// For `https://example.com/archive.tar.gz?token=1#top` that is `gz`. Returns `None`
+/// when the file has no extension, as with `https://example.com/download?id=3`; see
+/// [`file_type_from_headers`] for such downloads.
+///
+/// `url` may also be a bare path or file name.
+pub fn url_parse_file_type(url: &str) -> Option<String> {
+    let name = if let Ok(url) = Url::parse(url) {
+        parse_file_name(&url)?
+    } else {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        safe_file_name(path.rsplit(['/', '\\']).next().unwrap_or_default())?
+    };
+    file_type_of(&name)
+}
+
+/// Returns the file name a download's `Content-Disposition` header suggests, without
+/// any directories, so it cannot escape the directory it is saved in.
+pub fn content_disposition_file_name(headers: &HeaderMap) -> Option<String> {
+    let disposition = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+    let mut plain = None;
+    for parameter in disposition.split(';').map(str::trim) {
+        let Some((key, value)) = parameter.split_once('=') else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            // RFC 6266 prefers the encoded `filename*=UTF-8''name` form when both are sent.
+            "filename*" => {
+                let value = value.trim();
+                let encoded = value.split_once("''").map_or(value, |(_, name)| name);
+                if let Some(name) = percent_decode(encoded).as_deref().and_then(safe_file_name) {
+                    return Some(name);
+                }
+            }
+            "filename" => plain = safe_file_name(value.trim().trim_matches('"')),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Returns the file type of a download from its headers: the extension of the
+/// `Content-Disposition` file name, or else the type its `Content-Type` stands for.
+pub fn file_type_from_headers(headers: &HeaderMap) -> Option<String> {
+    if let Some(file_type) = content_disposition_file_name(headers)
+        .as_deref()
+        .and_then(file_type_of)
+    {
+        return Some(file_type);
+    }
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| *known == mime)
+        .map(|(_, file_type)| (*file_type).to_owned())
+}
+
+/// Returns the lowercase extension of the file `name`, ignoring a leading dot.
+fn file_type_of(name: &str) -> Option<String> {
+    let (stem, extension) = name.rsplit_once('.')?;
+    (!stem.is_empty() && !extension.is_empty()).then(|| extension.to_ascii_lowercase())
+}
+
+/// Returns the last component of `name`, unless it is empty, `.` or `..`.
+fn safe_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    (!name.is_empty() && name != "." && name != "..").then(|| name.to_owned())
+}
+
+/// Decodes `%XX` escapes in `value`, or returns `None` when they are malformed or do
+/// not decode to UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2)?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = tail.get(2..)?;
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn file_types_ignore_queries_and_fragments() {
+        assert_eq!(
+            url_parse_file_type("https://example.com/mods/Mod.ZIP?token=1.2#v1.0").as_deref(),
+            Some("zip")
+        );
+        assert_eq!(
+            url_parse_file_type("https://example.com/archive.tar.gz").as_deref(),
+            Some("gz")
+        );
+        assert_eq!(
+            url_parse_file_type("https://example.com/download?id=3"),
+            None
+        );
+        assert_eq!(url_parse_file_type("https://example.com/"), None);
+        assert_eq!(url_parse_file_type("https://example.com/.config"), None);
+        assert_eq!(
+            url_parse_file_type("plugins/mod.dll?raw").as_deref(),
+            Some("dll")
+        );
+        assert_eq!(url_parse_file_type("no_extension"), None);
+    }
+
+    #[test]
+    fn file_names_come_from_the_path() {
+        let url = Url::parse("https://example.com/path/to/archive.tar.gz?x=y").unwrap();
+        assert_eq!(parse_file_name(&url).as_deref(), Some("archive.tar.gz"));
+        assert_eq!(
+            parse_file_name(&Url::parse("https://example.com/dir/").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn headers_name_the_file_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/zip; charset=binary"),
+        );
+        assert_eq!(file_type_from_headers(&headers).as_deref(), Some("zip"));
+
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"../../etc/Mod Pack.dll\""),
+        );
+        assert_eq!(
+            content_disposition_file_name(&headers).as_deref(),
+            Some("Mod Pack.dll")
+        );
+        assert_eq!(file_type_from_headers(&headers).as_deref(), Some("dll"));
+
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_static(
+                "attachment; filename=\"fallback.zip\"; filename*=UTF-8''caf%C3%A9.cfg",
+            ),
+        );
+        assert_eq!(
+            content_disposition_file_name(&headers).as_deref(),
+            Some("café.cfg")
+        );
+
+        headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static("inline"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        assert_eq!(file_type_from_headers(&headers), None);
+    }
+}
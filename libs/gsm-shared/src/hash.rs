@@ -0,0 +1,108 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How many bytes [`hash_file`] reads at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns `bytes` as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Returns the SHA-256 checksum of `data`, in lowercase hex.
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Returns the SHA-256 checksum of `context`, in lowercase hex, e.g. as a cache key for
+/// a URL.
+pub fn get_sha256_hash(context: &str) -> String {
+    sha256_hex(context.as_bytes())
+}
+
+/// Returns the MD5 checksum of `context`, in lowercase hex.
+#[deprecated(note = "use get_sha256_hash")]
+pub fn get_md5_hash(context: &str) -> String {
+    format!("{:x}", md5::compute(context.as_bytes()))
+}
+
+/// Returns the SHA-256 checksum of the file at `path`, in lowercase hex.
+///
+/// The file is read in chunks, so multi-gigabyte archives are hashed without loading them
+/// into memory.
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be opened or read.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let Some(chunk) = buffer.get(..read).filter(|chunk| !chunk.is_empty()) else {
+            break;
+        };
+        hasher.update(chunk);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const ALPHABET_SHA256: &str =
+        "71c480df93d6ae2f1efad1447c66c9525e316218cf51fc8d9ed832f2daf18b73";
+
+    #[test]
+    fn hashes_strings() {
+        assert_eq!(
+            get_sha256_hash("abcdefghijklmnopqrstuvwxyz"),
+            ALPHABET_SHA256
+        );
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn md5_hash_is_kept_for_existing_callers() {
+        assert_eq!(
+            get_md5_hash("abcdefghijklmnopqrstuvwxyz"),
+            "c3fcd3d76192e4007dfb496cca67e13b"
+        );
+    }
+
+    #[test]
+    fn hash_file_matches_the_in_memory_hash() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let small = temp_dir.path().join("small.txt");
+        fs::write(&small, "abcdefghijklmnopqrstuvwxyz")?;
+        assert_eq!(hash_file(&small)?, ALPHABET_SHA256);
+
+        let large = temp_dir.path().join("large.bin");
+        let data = vec![42_u8; CHUNK_SIZE * 3 + 17];
+        fs::write(&large, &data)?;
+        assert_eq!(hash_file(&large)?, sha256_hex(&data));
+
+        assert!(hash_file(&temp_dir.path().join("missing")).is_err());
+        Ok(())
+    }
+}
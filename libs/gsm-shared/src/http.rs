@@ -0,0 +1,466 @@
+//! Shared HTTP helpers.
+//!
+//! One pooled client with connect and read timeouts, retries with backoff for transient failures,
+//! redirects followed hop by hop so callers can see where a URL led, and
+//! [`download_to`], which streams a file to disk with progress reports and an optional
+//! SHA-256 check.
+//!
+//! ```rust,no_run
+//! use gsm_shared::http::download_to;
+//! use std::path::Path;
+//!
+//! let download = download_to(
+//!     "https://example.com/mod.zip",
+//!     Path::new("/tmp/mod.zip"),
+//!     None,
+//!     |progress| println!("{} of {:?} bytes", progress.downloaded, progress.total),
+//! )?;
+//! println!("Downloaded {} from {}", download.size, download.url);
+//! # Ok::<(), gsm_shared::http::HttpError>(())
+//! ```
+
+use crate::hash::to_hex;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::HeaderMap;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{error, fmt, thread};
+use tracing::{debug, warn};
+
+/// How long to wait for a response, and then for each read of its body. Downloads may
+/// take as long as they need while bytes keep arriving.
+pub const READ_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// How long connecting to a server may take.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many times a request is tried before its failure is returned.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// How long to wait before the first retry; each later retry waits twice as long.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The most redirects [`get`] follows.
+pub const MAX_REDIRECTS: usize = 10;
+
+/// How many bytes [`download_to`] reads between progress reports.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const USER_AGENT: &str = concat!("game-server-management/", env!("CARGO_PKG_VERSION"));
+
+/// Why a request failed.
+#[derive(Debug)]
+pub enum HttpError {
+    /// The URL could not be parsed.
+    InvalidUrl(String),
+    /// The request could not be sent or its response read.
+    Request(reqwest::Error),
+    /// The server answered with an error status.
+    Status { url: Url, status: StatusCode },
+    /// More than [`MAX_REDIRECTS`] redirects, or a redirect without a valid `Location`.
+    Redirect { url: Url, reason: String },
+    /// The download could not be written to disk.
+    Io(io::Error),
+    /// The downloaded file does not have the expected SHA-256 checksum.
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUrl(url) => write!(f, "Invalid URL {url}"),
+            Self::Request(err) => write!(f, "HTTP request failed: {err}"),
+            Self::Status { url, status } => write!(f, "{url} answered with {status}"),
+            Self::Redirect { url, reason } => write!(f, "Bad redirect from {url}: {reason}"),
+            Self::Io(err) => write!(f, "Failed to write the download: {err}"),
+            Self::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch: expected {expected}, got {actual}")
+            }
+        }
+    }
+}
+
+impl error::Error for HttpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Request(err) => Some(err),
+            Self::Io(err) => Some(err),
+            Self::InvalidUrl(_)
+            | Self::Status { .. }
+            | Self::Redirect { .. }
+            | Self::ChecksumMismatch { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// A response to [`get`], with the redirects that led to it.
+#[derive(Debug)]
+pub struct Fetched {
+    pub response: Response,
+    /// The URLs redirected to, in order; empty when the first URL answered.
+    pub redirects: Vec<Url>,
+}
+
+/// How far a [`download_to`] has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The bytes written so far.
+    pub downloaded: u64,
+    /// The size of the file, when the server reported it.
+    pub total: Option<u64>,
+}
+
+/// A file written by [`download_to`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    /// The URL the file was downloaded from, after redirects.
+    pub url: Url,
+    /// The URLs redirected to, in order.
+    pub redirects: Vec<Url>,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The SHA-256 checksum of the file, in lowercase hex.
+    pub sha256: String,
+    /// The headers of the final response, e.g. to read a `Content-Disposition` file name.
+    pub headers: HeaderMap,
+}
+
+/// Returns the shared client, whose connections are reused across requests. It does not
+/// follow redirects itself; [`get`] does.
+pub fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        blocking_builder()
+            .redirect(Policy::none())
+            .build()
+            .unwrap_or_else(|e| {
+                warn!("Failed to configure the HTTP client, using defaults: {e}");
+                Client::new()
+            })
+    })
+}
+
+/// Builds a client with the shared timeouts that sends from `local_address`, e.g. the
+/// unspecified address of a family so requests go out over it.
+///
+/// # Errors
+///
+/// Returns an error when the client cannot be configured.
+pub fn client_bound_to(local_address: IpAddr) -> Result<Client, HttpError> {
+    Ok(blocking_builder().local_address(local_address).build()?)
+}
+
+/// Builds an async client like [`client_bound_to`].
+///
+/// # Errors
+///
+/// Returns an error when the client cannot be configured.
+pub fn async_client_bound_to(local_address: IpAddr) -> Result<reqwest::Client, HttpError> {
+    Ok(reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .local_address(local_address)
+        .build()?)
+}
+
+/// The settings the blocking clients share. Their `timeout` bounds each wait on the
+/// connection rather than the whole request, so it serves as the read timeout.
+fn blocking_builder() -> reqwest::blocking::ClientBuilder {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(READ_TIMEOUT)
+        .user_agent(USER_AGENT)
+}
+
+/// Sends the request `build` makes with the shared client, retrying with backoff when it
+/// fails to connect, times out or gets a 429 or 5xx answer. `build` is called once per
+/// attempt.
+///
+/// # Errors
+///
+/// Returns the last failure once [`MAX_ATTEMPTS`] attempts have failed, or the first
+/// failure that retrying would not fix, such as a 404.
+pub fn send(build: impl Fn(&Client) -> RequestBuilder) -> Result<Response, HttpError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = build(client())
+            .send()
+            .map_err(HttpError::from)
+            .and_then(check_status);
+        match result {
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                debug!("Attempt {attempt} of {MAX_ATTEMPTS} failed, retrying in {backoff:?}: {e}");
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Gets `url`, following redirects and retrying transient failures like [`send`].
+///
+/// # Errors
+///
+/// Returns an error when `url` is invalid, the request fails, the server answers with an
+/// error status, or redirects exceed [`MAX_REDIRECTS`].
+pub fn get(url: &str) -> Result<Fetched, HttpError> {
+    let mut url = Url::parse(url).map_err(|e| HttpError::InvalidUrl(format!("{url}: {e}")))?;
+    let mut redirects = Vec::new();
+    loop {
+        let response = send(|client| client.get(url.clone()))?;
+        if !response.status().is_redirection() {
+            return Ok(Fetched {
+                response,
+                redirects,
+            });
+        }
+        if redirects.len() >= MAX_REDIRECTS {
+            return Err(HttpError::Redirect {
+                url,
+                reason: format!("more than {MAX_REDIRECTS} redirects"),
+            });
+        }
+        let next = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok())
+            .ok_or_else(|| HttpError::Redirect {
+                url: url.clone(),
+                reason: "missing or invalid Location header".to_owned(),
+            })?;
+        debug!("{url} redirected to {next}");
+        redirects.push(next.clone());
+        url = next;
+    }
+}
+
+/// Downloads `url` to `path`, calling `progress` as bytes arrive.
+///
+/// When `sha256` is given, the file must have that checksum, in hex, or it is removed.
+/// The file is written next to `path` first and moved into place once complete, so a
+/// failed download never leaves a partial file at `path`.
+///
+/// # Errors
+///
+/// Returns an error when the request fails, the file cannot be written, or the checksum
+/// does not match.
+pub fn download_to(
+    url: &str,
+    path: &Path,
+    sha256: Option<&str>,
+    mut progress: impl FnMut(Progress),
+) -> Result<Download, HttpError> {
+    let Fetched {
+        mut response,
+        redirects,
+    } = get(url)?;
+    let final_url = response.url().clone();
+    let headers = response.headers().clone();
+    let total = response.content_length();
+
+    let partial = partial_path(path);
+    let result = write_body(&mut response, &partial, total, &mut progress)
+        .and_then(|(size, actual)| verify(sha256, actual).map(|actual| (size, actual)));
+    match result {
+        Ok((size, sha256)) => {
+            fs::rename(&partial, path)?;
+            debug!("Downloaded {final_url} to {}", path.display());
+            Ok(Download {
+                url: final_url,
+                redirects,
+                size,
+                sha256,
+                headers,
+            })
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Writes the body of `response` to `path`, returning its size and SHA-256 checksum.
+fn write_body(
+    response: &mut Response,
+    path: &Path,
+    total: Option<u64>,
+    progress: &mut impl FnMut(Progress),
+) -> Result<(u64, String), HttpError> {
+    let mut file = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut downloaded = 0;
+    loop {
+        let read = response.read(&mut buffer)?;
+        let Some(chunk) = buffer.get(..read).filter(|chunk| !chunk.is_empty()) else {
+            break;
+        };
+        file.write_all(chunk)?;
+        hasher.update(chunk);
+        downloaded += read as u64;
+        progress(Progress { downloaded, total });
+    }
+    file.sync_all()?;
+    let sha256 = to_hex(&hasher.finalize());
+    Ok((downloaded, sha256))
+}
+
+/// Checks `actual` against the `expected` checksum, if any.
+fn verify(expected: Option<&str>, actual: String) -> Result<String, HttpError> {
+    match expected.map(|expected| expected.trim().to_ascii_lowercase()) {
+        Some(expected) if expected != actual => {
+            Err(HttpError::ChecksumMismatch { expected, actual })
+        }
+        _ => Ok(actual),
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+fn check_status(response: Response) -> Result<Response, HttpError> {
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(HttpError::Status {
+            url: response.url().clone(),
+            status,
+        });
+    }
+    Ok(response)
+}
+
+/// Returns whether retrying might fix `error`.
+fn is_transient(error: &HttpError) -> bool {
+    match error {
+        HttpError::Request(err) => err.is_connect() || err.is_timeout(),
+        HttpError::Status { status, .. } => {
+            status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+        }
+        HttpError::InvalidUrl(_)
+        | HttpError::Redirect { .. }
+        | HttpError::Io(_)
+        | HttpError::ChecksumMismatch { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use std::net::TcpListener;
+    use tempfile::tempdir;
+
+    /// Serves `responses` in order, one per connection, returning the server's base URL.
+    fn serve(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        base
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn downloads_follow_redirects_and_retry_transient_failures() {
+        let base = serve(vec![
+            response("302 Found", "Location: /files/mod.zip\r\n", ""),
+            response("503 Service Unavailable", "", ""),
+            response("200 OK", "", "mod contents"),
+        ]);
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("mod.zip");
+        let mut reports = Vec::new();
+
+        let download = download_to(&format!("{base}/latest"), &path, None, |progress| {
+            reports.push(progress);
+        })
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "mod contents");
+        assert_eq!(download.url.path(), "/files/mod.zip");
+        assert_eq!(download.redirects.len(), 1);
+        assert_eq!(download.size, 12);
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                downloaded: 12,
+                total: Some(12)
+            })
+        );
+    }
+
+    #[test]
+    fn checksum_mismatches_leave_no_file_behind() {
+        let base = serve(vec![response("200 OK", "", "tampered")]);
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("mod.zip");
+
+        let error = download_to(&base, &path, Some("00ff"), |_| {}).unwrap_err();
+
+        assert!(matches!(error, HttpError::ChecksumMismatch { .. }));
+        assert!(!path.exists());
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let base = serve(vec![
+            response("404 Not Found", "", ""),
+            response("200 OK", "", "too late"),
+        ]);
+
+        let error = get(&base).unwrap_err();
+
+        assert!(matches!(
+            error,
+            HttpError::Status {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }
+        ));
+    }
+}
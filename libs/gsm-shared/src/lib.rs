@@ -1,23 +1,54 @@
 mod fetch_public_ip_address;
 
 pub use fetch_public_ip_address::*;
-use reqwest::Url;
+pub mod ddns;
+pub mod http;
+pub mod logging;
+pub mod paths;
+pub mod port_mapping;
 use std::env;
 use std::path::Path;
 use tracing::debug;
 
+mod hash;
+pub use hash::*;
+
 mod is_valid_url;
 pub use is_valid_url::*;
 
+mod file_type;
+pub use file_type::*;
+
 mod normalize_paths;
 pub use normalize_paths::*;
 
+mod staging;
+pub use staging::*;
+
+mod move_path;
+pub use move_path::*;
+
+mod ports;
+pub use ports::*;
+
 mod parse_truthy;
 pub use parse_truthy::*;
 
 mod environment;
 pub use environment::*;
 
+mod dotenv;
+pub use dotenv::*;
+
+mod units;
+pub use units::*;
+
+mod secret;
+pub use secret::*;
+
+mod workdir_lock;
+pub use workdir_lock::*;
+
 mod constants;
 
 pub fn get_working_dir() -> String {
@@ -38,24 +69,6 @@ pub fn path_exists(path: &str) -> bool {
     state
 }
 
-pub fn parse_file_name(url: &Url, default: &str) -> String {
-    url.path_segments()
-        .and_then(|mut segments| segments.next_back())
-        .filter(|name| !name.is_empty())
-        .map_or_else(|| default.to_owned(), std::borrow::ToOwned::to_owned)
-}
-
-pub fn get_md5_hash(context: &str) -> String {
-    format!("{:x}", md5::compute(context.as_bytes()))
-}
-
-pub fn url_parse_file_type(url: &str) -> String {
-    url.rsplit('.')
-        .next()
-        .filter(|part| !part.is_empty())
-        .map_or_else(String::new, std::borrow::ToOwned::to_owned)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,14 +76,6 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
-    #[test]
-    fn hash_str() {
-        assert_eq!(
-            get_md5_hash("abcdefghijklmnopqrstuvwxyz"),
-            "c3fcd3d76192e4007dfb496cca67e13b"
-        );
-    }
-
     #[test]
     fn working_dir_prefers_environment_override() -> Result<(), Box<dyn std::error::Error>> {
         let temp_dir = tempdir()?;
@@ -107,13 +112,10 @@ mod tests {
         ));
 
         let url = Url::parse("https://example.com/path/to/archive.tar.gz")?;
-        assert_eq!(parse_file_name(&url, "default.txt"), "archive.tar.gz");
-        assert_eq!(
-            parse_file_name(&Url::parse("https://example.com/")?, "default.txt"),
-            "default.txt"
-        );
-        assert_eq!(url_parse_file_type("archive.tar.gz"), "gz");
-        assert_eq!(url_parse_file_type("no_extension"), "no_extension");
+        assert_eq!(parse_file_name(&url).as_deref(), Some("archive.tar.gz"));
+        assert_eq!(parse_file_name(&Url::parse("https://example.com/")?), None);
+        assert_eq!(url_parse_file_type("archive.tar.gz").as_deref(), Some("gz"));
+        assert_eq!(url_parse_file_type("no_extension"), None);
 
         Ok(())
     }
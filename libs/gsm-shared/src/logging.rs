@@ -0,0 +1,279 @@
+//! Sets up logging for the game binaries, so container logs can be read by people and
+//! by log collectors alike.
+//!
+//! [`init`] logs to stdout in the format `LOG_FORMAT` names: `full` (the default),
+//! `compact`, `pretty` or `json`, one object per line. Which events are logged follows
+//! `RUST_LOG`, or [`DEFAULT_LOG_FILTER`] when it is unset. With `LOG_FILE` set, logs are
+//! also written to that file, which is rotated once it grows past `LOG_FILE_MAX_SIZE`,
+//! keeping `LOG_FILE_KEEP` old files next to it as `<file>.1`, `<file>.2` and so on.
+//!
+//! ```rust,no_run
+//! use gsm_shared::logging;
+//! use tracing::Instrument;
+//!
+//! # async fn install() {}
+//! # async fn run() {
+//! logging::init();
+//! // Every event logged while installing carries `command: "install"`.
+//! install().instrument(logging::command_span("install")).await;
+//! # }
+//! ```
+
+use crate::{fetch_size, fetch_var};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::{Span, Subscriber, debug, info_span, warn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Environment variable choosing the log format: `full`, `compact`, `pretty` or `json`.
+pub const LOG_FORMAT: &str = "LOG_FORMAT";
+
+/// Environment variable naming a file to also write logs to.
+pub const LOG_FILE: &str = "LOG_FILE";
+
+/// Environment variable setting the size `LOG_FILE` is rotated at, e.g. `50MiB`.
+pub const LOG_FILE_MAX_SIZE: &str = "LOG_FILE_MAX_SIZE";
+
+/// Environment variable setting how many rotated log files are kept.
+pub const LOG_FILE_KEEP: &str = "LOG_FILE_KEEP";
+
+/// Which events are logged when `RUST_LOG` is unset.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// The size `LOG_FILE` is rotated at unless `LOG_FILE_MAX_SIZE` says otherwise.
+pub const DEFAULT_LOG_FILE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How many rotated log files are kept unless `LOG_FILE_KEEP` says otherwise.
+pub const DEFAULT_LOG_FILE_KEEP: usize = 5;
+
+/// How log events are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One line per event, with its target and the spans it happened in.
+    #[default]
+    Full,
+    /// One shorter line per event.
+    Compact,
+    /// Several lines per event, for reading in a terminal.
+    Pretty,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "full" | "text" => Ok(Self::Full),
+            "compact" => Ok(Self::Compact),
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid {LOG_FORMAT} value '{value}'; expected full, compact, pretty or json"
+            )),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Returns a layer writing events in this format to `writer`, with terminal colors
+    /// when `ansi` is set.
+    fn layer<S, W>(self, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+        W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(ansi);
+        match self {
+            Self::Full => layer.boxed(),
+            Self::Compact => layer.compact().boxed(),
+            Self::Pretty => layer.pretty().boxed(),
+            Self::Json => layer.json().boxed(),
+        }
+    }
+}
+
+/// Sets up logging from `LOG_FORMAT`, `RUST_LOG` and `LOG_FILE`, as described in the
+/// [module documentation](self). Call it first thing in `main`; when logging is already
+/// set up, this does nothing.
+///
+/// An invalid `LOG_FORMAT` falls back to the full format, and a `LOG_FILE` that cannot
+/// be opened is skipped; both are logged as warnings.
+pub fn init() {
+    let format = fetch_var(LOG_FORMAT, "").parse::<LogFormat>();
+    let log_format = format.as_ref().copied().unwrap_or_default();
+    let mut layers = vec![log_format.layer(io::stdout, io::stdout().is_terminal())];
+
+    let log_file = fetch_var(LOG_FILE, "");
+    let file = (!log_file.is_empty()).then(|| {
+        RotatingFile::open(
+            Path::new(&log_file),
+            fetch_size(LOG_FILE_MAX_SIZE, DEFAULT_LOG_FILE_MAX_SIZE),
+            fetch_var(LOG_FILE_KEEP, "")
+                .parse()
+                .unwrap_or(DEFAULT_LOG_FILE_KEEP),
+        )
+    });
+    let file_error = match file {
+        Some(Ok(file)) => {
+            layers.push(log_format.layer(Mutex::new(file), false));
+            None
+        }
+        Some(Err(e)) => Some(e),
+        None => None,
+    };
+
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    if tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .try_init()
+        .is_err()
+    {
+        debug!("Logging is already set up");
+        return;
+    }
+    if let Err(e) = format {
+        warn!("{e}");
+    }
+    if let Some(e) = file_error {
+        warn!("Not writing logs to {log_file}: {e}");
+    }
+    debug!("Logging set up with the {log_format:?} format");
+}
+
+/// Returns the span a subcommand runs in, so each of its events records which
+/// subcommand logged it, e.g. `command: "update"`.
+pub fn command_span(command: &str) -> Span {
+    info_span!("command", command)
+}
+
+/// A log file that is rotated once it grows past a size.
+///
+/// When a write would take the file past `max_size`, the file is renamed to
+/// `<file>.1`, older files move up by one, and the oldest beyond `keep` is removed.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending, creating it and its directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the directory cannot be created or the file opened.
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep > 0 {
+            for index in (1..self.keep).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Returns the path of the `index`th rotated copy of `path`, e.g. `gsm.log.1`.
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(format!(".{index}"));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn log_formats_parse_from_their_names() {
+        assert_eq!("".parse(), Ok(LogFormat::Full));
+        assert_eq!("JSON".parse(), Ok(LogFormat::Json));
+        assert_eq!(" compact ".parse(), Ok(LogFormat::Compact));
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn log_files_rotate_and_keep_the_newest_copies() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("logs/gsm.log");
+        let mut file = RotatingFile::open(&path, 10, 2)?;
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes())?;
+        }
+        file.flush()?;
+
+        assert_eq!(fs::read_to_string(&path)?, "fourth\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1))?, "third\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2))?, "second\n");
+        assert!(!rotated_path(&path, 3).exists());
+
+        // Reopening appends to the current file and counts its size.
+        let mut file = RotatingFile::open(&path, 10, 2)?;
+        file.write_all(b"fifth\n")?;
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1))?, "fourth\n");
+        assert_eq!(fs::read_to_string(&path)?, "fifth\n");
+        Ok(())
+    }
+}
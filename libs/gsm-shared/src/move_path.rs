@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::debug;
+use walkdir::WalkDir;
+
+/// Moves a file or directory from `src` to `dst`, like [`fs::rename`], but falls back to
+/// copying and then deleting the source when the two paths are on different filesystems.
+///
+/// Containers commonly mount the game directory separately from `/tmp` (or from
+/// `GSM_TMPDIR`), where a plain rename fails with a cross-device error.
+///
+/// # Errors
+///
+/// Returns an error when the rename fails for any other reason, or when the copy or the
+/// removal of the source fails.
+pub fn move_path(src: &Path, dst: &Path) -> io::Result<()> {
+    match fs::rename(src, dst) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!(
+                "Rename of {} crosses filesystems; copying instead",
+                src.display()
+            );
+            copy_then_remove(src, dst)
+        }
+        result => result,
+    }
+}
+
+fn copy_then_remove(src: &Path, dst: &Path) -> io::Result<()> {
+    if !src.is_dir() {
+        fs::copy(src, dst)?;
+        return fs::remove_file(src);
+    }
+
+    for entry in WalkDir::new(src) {
+        let entry = entry.map_err(io::Error::other)?;
+        let relative = entry.path().strip_prefix(src).map_err(io::Error::other)?;
+        let target = dst.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    fs::remove_dir_all(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn moves_files_and_directories() -> io::Result<()> {
+        let root = tempdir()?;
+        let file = root.path().join("file.txt");
+        fs::write(&file, "hello")?;
+        move_path(&file, &root.path().join("moved.txt"))?;
+        assert!(!file.exists());
+        assert_eq!(fs::read_to_string(root.path().join("moved.txt"))?, "hello");
+
+        let dir = root.path().join("dir");
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(dir.join("nested/inner.txt"), "inner")?;
+        move_path(&dir, &root.path().join("moved_dir"))?;
+        assert!(!dir.exists());
+        assert_eq!(
+            fs::read_to_string(root.path().join("moved_dir/nested/inner.txt"))?,
+            "inner"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn copy_fallback_preserves_tree_and_removes_source() -> io::Result<()> {
+        let root = tempdir()?;
+        let dir = root.path().join("dir");
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(dir.join("nested/inner.txt"), "inner")?;
+
+        copy_then_remove(&dir, &root.path().join("copied"))?;
+        assert!(!dir.exists());
+        assert_eq!(
+            fs::read_to_string(root.path().join("copied/nested/inner.txt"))?,
+            "inner"
+        );
+        Ok(())
+    }
+}
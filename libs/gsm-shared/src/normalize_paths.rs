@@ -1,6 +1,6 @@
+use crate::{move_path, staging_dir};
 use std::fs;
 use std::path::{Path, PathBuf};
-use tempfile::tempdir;
 use walkdir::WalkDir;
 
 /// Replaces backslashes with forward slashes in the string representation of a path.
@@ -40,7 +40,7 @@ fn move_and_normalize_to_temp(src_dir: &Path, temp_root: &Path) -> std::io::Resu
             if let Some(parent) = temp_dest_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::rename(src_path, &temp_dest_path)?;
+            move_path(src_path, &temp_dest_path)?;
         }
     }
     Ok(())
@@ -61,25 +61,35 @@ fn move_normalized_back(temp_root: &Path, src_dir: &Path) -> std::io::Result<()>
             if let Some(parent) = original_dest_path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::rename(entry_path, &original_dest_path)?;
+            move_path(entry_path, &original_dest_path)?;
         }
     }
     Ok(())
 }
 
-/// Normalizes paths in `src_dir` by moving its contents to a temporary location,
+/// Normalizes paths in `src_dir` by moving its contents to a staging location,
 /// rewriting each relative path to use forward slashes, and moving the normalized
 /// contents back into a fresh `src_dir`.
 ///
+/// The staging directory is created under the staging root of `root` (see
+/// [`staging_dir`]), so concurrent normalizations never share a path. Pass the
+/// instance's working directory, so [`clean_orphaned_staging`](crate::clean_orphaned_staging)
+/// finds the directory if the normalization is killed. When `GSM_TMPDIR` places it on
+/// another filesystem, moves fall back to copying (see [`move_path`]).
+///
 /// # Errors
 ///
 /// Returns an error if any file system operation fails.
-pub fn normalize_paths(src_dir: &Path) -> std::io::Result<()> {
+pub fn normalize_paths(src_dir: &Path, root: &Path) -> std::io::Result<()> {
     // Ensure the source directory exists.
     validate_source_dir(src_dir)?;
 
-    // Create a temporary directory.
-    let temp_dir = tempdir()?;
+    // Create a per-operation staging directory under the instance's staging root.
+    let namespace = src_dir.file_name().map_or_else(
+        || String::from("dir"),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let temp_dir = staging_dir(root, &namespace, "normalize")?;
     let temp_root = temp_dir.path();
 
     // Move and normalize contents from src_dir to the temporary directory.
@@ -92,7 +102,7 @@ pub fn normalize_paths(src_dir: &Path) -> std::io::Result<()> {
     // Move the normalized contents back into the source directory.
     move_normalized_back(temp_root, src_dir)?;
 
-    // temp_dir is automatically deleted when it goes out of scope.
+    // The staging directory is automatically deleted when it goes out of scope.
     Ok(())
 }
 
@@ -139,11 +149,18 @@ mod tests {
         let temp_dir = tempdir()?;
         let src_dir = setup_test_dir(temp_dir.path())?;
         // Call normalize_paths on the source directory.
-        normalize_paths(&src_dir)?;
+        normalize_paths(&src_dir, temp_dir.path())?;
 
         // Assert that the normalized directory structure contains no backslashes.
         assert_no_backslashes(&src_dir);
 
+        // The staging directory was created under the instance's root and removed.
+        let staged: Vec<_> = fs::read_dir(temp_dir.path().join(crate::STAGING_DIR_NAME))?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != crate::STAGING_MARKER)
+            .collect();
+        assert!(staged.is_empty());
+
         // Optionally, check that the test file exists with its content preserved.
         let normalized_file = src_dir.join("foo").join("bar").join("test.txt");
         let content = fs::read_to_string(normalized_file)?;
@@ -154,7 +171,7 @@ mod tests {
     #[test]
     fn test_normalize_paths_errors_on_nonexistent_dir() {
         let non_existent = PathBuf::from("this_directory_should_not_exist");
-        let result = normalize_paths(&non_existent);
+        let result = normalize_paths(&non_existent, Path::new("."));
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,164 @@
+//! Resolves where Steam and the servers it installs live, so nothing has to hard-code
+//! `/home/steam`.
+//!
+//! Every path starts from [`home_dir`], which is `HOME` (`USERPROFILE` on Windows), or
+//! [`DEFAULT_HOME`] when neither is set. The Steam root is `STEAM_ROOT` when set, or
+//! else the first existing per-OS default:
+//!
+//! - Linux: `~/.local/share/Steam`, `~/.steam/steam` or `~/.steam/root`
+//! - macOS: `~/Library/Application Support/Steam`
+//! - Windows: `C:\Program Files (x86)\Steam`
+//!
+//! ```rust,no_run
+//! use gsm_shared::paths;
+//!
+//! let appinfo = paths::steam_appcache().join("appinfo.vdf");
+//! let server_dir = paths::default_install_root().join("palworld");
+//! ```
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the Steam installation directory.
+pub const STEAM_ROOT: &str = "STEAM_ROOT";
+
+/// Environment variable overriding where SteamCMD is installed.
+pub const STEAMCMD_DIR: &str = "STEAMCMD_DIR";
+
+/// The home directory used when `HOME` is unset, as in the server containers.
+pub const DEFAULT_HOME: &str = "/home/steam";
+
+/// Returns the home directory of the user running the server: `HOME`, else
+/// `USERPROFILE` on Windows, else [`DEFAULT_HOME`].
+pub fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("USERPROFILE")
+            } else {
+                None
+            }
+        })
+        .filter(|home| !home.is_empty())
+        .map_or_else(|| PathBuf::from(DEFAULT_HOME), PathBuf::from)
+}
+
+/// Returns the Steam installation directory: `STEAM_ROOT`, else the first per-OS
+/// default that exists, else the first per-OS default.
+pub fn steam_root() -> PathBuf {
+    env::var_os(STEAM_ROOT)
+        .filter(|root| !root.is_empty())
+        .map_or_else(|| default_steam_root(&home_dir()), PathBuf::from)
+}
+
+/// Returns Steam's `appcache` directory, which holds `appinfo.vdf`.
+pub fn steam_appcache() -> PathBuf {
+    steam_root().join("appcache")
+}
+
+/// Returns the directory Steam installs apps into, `steamapps/common`, where Valve's
+/// Proton builds live.
+pub fn steam_apps_common() -> PathBuf {
+    steam_root().join("steamapps").join("common")
+}
+
+/// Returns the `compatibilitytools.d` directory new compatibility tools such as
+/// GE-Proton are installed into.
+pub fn compatibility_tools_dir() -> PathBuf {
+    steam_root().join("compatibilitytools.d")
+}
+
+/// Returns the `compatibilitytools.d` directories custom compatibility tools may be
+/// installed into, starting with [`compatibility_tools_dir`].
+pub fn compatibility_tool_dirs() -> Vec<PathBuf> {
+    compatibility_tool_dirs_in(&steam_root(), &home_dir())
+}
+
+/// Returns where SteamCMD is installed when it is missing: `STEAMCMD_DIR`, else
+/// `~/steamcmd`.
+pub fn steamcmd_dir() -> PathBuf {
+    env::var_os(STEAMCMD_DIR)
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home_dir().join("steamcmd"), PathBuf::from)
+}
+
+/// Returns the directory game servers are installed under by default, the home
+/// directory, e.g. `/home/steam/palworld`.
+pub fn default_install_root() -> PathBuf {
+    home_dir()
+}
+
+/// Returns the per-OS Steam root under `home`, preferring one that exists.
+fn default_steam_root(home: &Path) -> PathBuf {
+    let candidates = if cfg!(windows) {
+        vec![PathBuf::from(r"C:\Program Files (x86)\Steam")]
+    } else if cfg!(target_os = "macos") {
+        vec![home.join("Library/Application Support/Steam")]
+    } else {
+        vec![
+            home.join(".local/share/Steam"),
+            home.join(".steam/steam"),
+            home.join(".steam/root"),
+        ]
+    };
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_dir())
+        .or_else(|| candidates.first())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the `compatibilitytools.d` directories of `steam_root` and, on Linux, the
+/// `~/.steam` links to it, without duplicates.
+fn compatibility_tool_dirs_in(steam_root: &Path, home: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![steam_root.join("compatibilitytools.d")];
+    if !cfg!(windows) && !cfg!(target_os = "macos") {
+        for dir in [".steam/steam", ".steam/root", ".steam"] {
+            let dir = home.join(dir).join("compatibilitytools.d");
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn steam_root_prefers_an_existing_default() -> std::io::Result<()> {
+        let home = tempdir()?;
+        assert_eq!(
+            default_steam_root(home.path()),
+            home.path().join(".local/share/Steam")
+        );
+
+        fs::create_dir_all(home.path().join(".steam/steam"))?;
+        assert_eq!(
+            default_steam_root(home.path()),
+            home.path().join(".steam/steam")
+        );
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn compatibility_tool_dirs_start_with_the_steam_root() {
+        let home = Path::new("/home/player");
+        let dirs = compatibility_tool_dirs_in(&home.join(".steam/steam"), home);
+        assert_eq!(
+            dirs,
+            [
+                home.join(".steam/steam/compatibilitytools.d"),
+                home.join(".steam/root/compatibilitytools.d"),
+                home.join(".steam/compatibilitytools.d"),
+            ]
+        );
+    }
+}
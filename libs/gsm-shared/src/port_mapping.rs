@@ -0,0 +1,769 @@
+//! Forwards the server's ports on a home router with UPnP or NAT-PMP, so players can reach
+//! a home-hosted server without setting up port forwarding by hand.
+//!
+//! Forwarding is opt-in: [`PortForwarder::from_env`] does nothing unless `PORT_FORWARDING`
+//! is set to `auto`, `upnp` or `natpmp`. Mappings are leased for `PORT_FORWARDING_LEASE`
+//! (one hour by default), renewed at half that, and removed when the forwarder is dropped,
+//! so a router does not keep forwarding to a server that is gone.
+//!
+//! ```rust,no_run
+//! use gsm_shared::Protocol;
+//! use gsm_shared::port_mapping::PortForwarder;
+//!
+//! // Held for as long as the server runs; dropping it removes the mappings.
+//! let _forwarding = PortForwarder::from_env([(8211, Protocol::Udp), (27015, Protocol::Udp)]);
+//! ```
+
+use crate::http::{self, HttpError};
+use crate::{Protocol, fetch_duration, fetch_var};
+use reqwest::Url;
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::{error, fmt, fs};
+use tracing::{debug, info, warn};
+
+/// Environment variable choosing how ports are forwarded: `auto`, `upnp` or `natpmp`.
+/// Unset, empty or `off`, ports are not forwarded.
+pub const PORT_FORWARDING: &str = "PORT_FORWARDING";
+
+/// Environment variable setting how long mappings are leased for, e.g. `30m`.
+pub const PORT_FORWARDING_LEASE: &str = "PORT_FORWARDING_LEASE";
+
+/// Environment variable naming the NAT-PMP gateway, for when the default route's gateway
+/// is not the router.
+pub const PORT_FORWARDING_GATEWAY: &str = "PORT_FORWARDING_GATEWAY";
+
+/// How long mappings are leased for unless `PORT_FORWARDING_LEASE` says otherwise.
+pub const DEFAULT_LEASE: Duration = Duration::from_hours(1);
+
+/// The description routers list the mappings under.
+const DESCRIPTION: &str = "game-server-management";
+
+/// The port NAT-PMP gateways listen on.
+const NAT_PMP_PORT: u16 = 5351;
+
+/// How long to wait for the first NAT-PMP answer; each retry waits twice as long.
+const NAT_PMP_INITIAL_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How many NAT-PMP requests are sent before giving up.
+const NAT_PMP_ATTEMPTS: u32 = 4;
+
+/// The multicast address SSDP discovery is sent to.
+const SSDP_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 255, 250), 1900);
+
+/// How long to wait for routers to answer SSDP discovery.
+const SSDP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The UPnP services that manage port mappings, most capable first.
+const UPNP_SERVICES: [&str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// The UPnP error routers answer with when they only accept permanent mappings.
+const ONLY_PERMANENT_LEASES: u16 = 725;
+
+/// How ports are forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// NAT-PMP, falling back to UPnP.
+    Auto,
+    /// UPnP Internet Gateway Device port mappings.
+    Upnp,
+    /// NAT-PMP, as spoken by Apple routers and many others.
+    NatPmp,
+}
+
+impl Method {
+    /// Returns the method `PORT_FORWARDING` selects, or `None` when forwarding is off.
+    /// Unknown values are logged and leave forwarding off.
+    pub fn from_env() -> Option<Self> {
+        let value = fetch_var(PORT_FORWARDING, "");
+        if value.is_empty() || matches!(value.to_lowercase().as_str(), "off" | "false" | "0") {
+            return None;
+        }
+        value
+            .parse()
+            .inspect_err(|e| warn!("Not forwarding ports: {e}"))
+            .ok()
+    }
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" | "true" | "1" => Ok(Self::Auto),
+            "upnp" => Ok(Self::Upnp),
+            "natpmp" | "nat-pmp" => Ok(Self::NatPmp),
+            _ => Err(format!(
+                "invalid {PORT_FORWARDING} value '{value}'; expected auto, upnp or natpmp"
+            )),
+        }
+    }
+}
+
+/// Why a port could not be forwarded.
+#[derive(Debug)]
+pub enum PortMappingError {
+    /// No router answered, or none could be found to ask.
+    NoGateway(String),
+    /// The router refused the request.
+    Refused(String),
+    /// A UPnP request failed with the router's error code.
+    UpnpFault { code: u16, description: String },
+    /// A UPnP request could not be sent.
+    Http(HttpError),
+    /// A NAT-PMP request could not be sent.
+    Io(io::Error),
+}
+
+impl fmt::Display for PortMappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoGateway(reason) => write!(f, "No router to forward ports on: {reason}"),
+            Self::Refused(reason) => write!(f, "The router refused the mapping: {reason}"),
+            Self::UpnpFault { code, description } => {
+                write!(
+                    f,
+                    "The router answered with UPnP error {code}: {description}"
+                )
+            }
+            Self::Http(err) => write!(f, "UPnP request failed: {err}"),
+            Self::Io(err) => write!(f, "NAT-PMP request failed: {err}"),
+        }
+    }
+}
+
+impl error::Error for PortMappingError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Http(err) => Some(err),
+            Self::Io(err) => Some(err),
+            Self::NoGateway(_) | Self::Refused(_) | Self::UpnpFault { .. } => None,
+        }
+    }
+}
+
+impl From<HttpError> for PortMappingError {
+    fn from(err: HttpError) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl From<reqwest::Error> for PortMappingError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(HttpError::from(err))
+    }
+}
+
+impl From<io::Error> for PortMappingError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// A router that forwards ports on request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gateway {
+    /// A NAT-PMP gateway at this address.
+    NatPmp(SocketAddrV4),
+    /// A UPnP Internet Gateway Device, controlled through `service` at `control_url`,
+    /// forwarding to this machine's `local_ip`.
+    Upnp {
+        control_url: Url,
+        service: String,
+        local_ip: Ipv4Addr,
+    },
+}
+
+impl fmt::Display for Gateway {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NatPmp(addr) => write!(f, "NAT-PMP gateway {addr}"),
+            Self::Upnp { control_url, .. } => write!(f, "UPnP gateway {control_url}"),
+        }
+    }
+}
+
+impl Gateway {
+    /// Finds the router to forward ports on with `method`.
+    ///
+    /// NAT-PMP asks the gateway of the default route, or `PORT_FORWARDING_GATEWAY`; UPnP
+    /// finds routers with SSDP discovery on the local network.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no router answers.
+    pub fn discover(method: Method) -> Result<Self, PortMappingError> {
+        match method {
+            Method::NatPmp => discover_nat_pmp(),
+            Method::Upnp => discover_upnp(),
+            Method::Auto => discover_nat_pmp().or_else(|e| {
+                debug!("NAT-PMP discovery failed, trying UPnP: {e}");
+                discover_upnp()
+            }),
+        }
+    }
+
+    /// Forwards `port` on the router to the same port on this machine for `lease`,
+    /// returning how long the router granted it for. A granted lease of zero never
+    /// expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the router cannot be reached or refuses the mapping, e.g.
+    /// because the port is forwarded to another machine.
+    pub fn map(
+        &self,
+        port: u16,
+        protocol: Protocol,
+        lease: Duration,
+    ) -> Result<Duration, PortMappingError> {
+        match self {
+            Self::NatPmp(addr) => nat_pmp_map(*addr, port, protocol, lease),
+            Self::Upnp {
+                control_url,
+                service,
+                local_ip,
+            } => {
+                let add = |lease: Duration| {
+                    soap(
+                        control_url,
+                        service,
+                        "AddPortMapping",
+                        &[
+                            ("NewRemoteHost", String::new()),
+                            ("NewExternalPort", port.to_string()),
+                            ("NewProtocol", upnp_protocol(protocol).to_owned()),
+                            ("NewInternalPort", port.to_string()),
+                            ("NewInternalClient", local_ip.to_string()),
+                            ("NewEnabled", "1".to_owned()),
+                            ("NewPortMappingDescription", DESCRIPTION.to_owned()),
+                            ("NewLeaseDuration", lease.as_secs().to_string()),
+                        ],
+                    )
+                    .map(|_| lease)
+                };
+                match add(lease) {
+                    Err(PortMappingError::UpnpFault {
+                        code: ONLY_PERMANENT_LEASES,
+                        ..
+                    }) => {
+                        debug!("{self} only accepts permanent mappings");
+                        add(Duration::ZERO)
+                    }
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Removes the mapping of `port` made by [`Gateway::map`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the router cannot be reached or refuses to remove it.
+    pub fn unmap(&self, port: u16, protocol: Protocol) -> Result<(), PortMappingError> {
+        match self {
+            Self::NatPmp(addr) => nat_pmp_map(*addr, port, protocol, Duration::ZERO).map(drop),
+            Self::Upnp {
+                control_url,
+                service,
+                ..
+            } => soap(
+                control_url,
+                service,
+                "DeletePortMapping",
+                &[
+                    ("NewRemoteHost", String::new()),
+                    ("NewExternalPort", port.to_string()),
+                    ("NewProtocol", upnp_protocol(protocol).to_owned()),
+                ],
+            )
+            .map(drop),
+        }
+    }
+}
+
+/// Keeps the server's ports forwarded while it is alive, renewing their leases in the
+/// background, and removes the mappings when dropped.
+#[derive(Debug)]
+pub struct PortForwarder {
+    gateway: Gateway,
+    ports: Vec<(u16, Protocol)>,
+    stop: Option<Sender<()>>,
+    renewer: Option<JoinHandle<()>>,
+}
+
+impl PortForwarder {
+    /// Forwards `ports` on the router chosen by `PORT_FORWARDING`, leased for
+    /// `PORT_FORWARDING_LEASE`. Returns `None`, after logging why, when forwarding is off
+    /// or no router can be found; the server is still reachable on the local network.
+    pub fn from_env(ports: impl IntoIterator<Item = (u16, Protocol)>) -> Option<Self> {
+        let method = Method::from_env()?;
+        let gateway = Gateway::discover(method)
+            .inspect_err(|e| warn!("Not forwarding ports: {e}"))
+            .ok()?;
+        let lease = fetch_duration(PORT_FORWARDING_LEASE, DEFAULT_LEASE);
+        Some(Self::start(gateway, ports.into_iter().collect(), lease))
+    }
+
+    /// Forwards `ports` on `gateway` for `lease`, and renews them at half the lease until
+    /// dropped. Ports that cannot be forwarded are logged and retried at each renewal.
+    pub fn start(gateway: Gateway, ports: Vec<(u16, Protocol)>, lease: Duration) -> Self {
+        info!("Forwarding ports through the {gateway}");
+        map_all(&gateway, &ports, lease);
+        let (stop, stopped) = mpsc::channel();
+        let renewer = {
+            let gateway = gateway.clone();
+            let ports = ports.clone();
+            let interval = (lease / 2).max(Duration::from_secs(30));
+            thread::spawn(move || {
+                while stopped.recv_timeout(interval) == Err(RecvTimeoutError::Timeout) {
+                    debug!("Renewing port mappings");
+                    map_all(&gateway, &ports, lease);
+                }
+            })
+        };
+        Self {
+            gateway,
+            ports,
+            stop: Some(stop),
+            renewer: Some(renewer),
+        }
+    }
+
+    /// Returns the router the ports are forwarded on.
+    pub const fn gateway(&self) -> &Gateway {
+        &self.gateway
+    }
+}
+
+impl Drop for PortForwarder {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(renewer) = self.renewer.take()
+            && renewer.join().is_err()
+        {
+            warn!("The port mapping renewal thread panicked");
+        }
+        for &(port, protocol) in &self.ports {
+            match self.gateway.unmap(port, protocol) {
+                Ok(()) => info!("Stopped forwarding port {port}/{protocol}"),
+                Err(e) => warn!("Failed to stop forwarding port {port}/{protocol}: {e}"),
+            }
+        }
+    }
+}
+
+/// Maps each of `ports` on `gateway`, logging the outcome.
+fn map_all(gateway: &Gateway, ports: &[(u16, Protocol)], lease: Duration) {
+    for &(port, protocol) in ports {
+        match gateway.map(port, protocol, lease) {
+            Ok(granted) => debug!("Forwarded port {port}/{protocol} for {granted:?}"),
+            Err(e) => warn!("Failed to forward port {port}/{protocol}: {e}"),
+        }
+    }
+}
+
+fn discover_nat_pmp() -> Result<Gateway, PortMappingError> {
+    let configured = fetch_var(PORT_FORWARDING_GATEWAY, "");
+    let ip = if configured.is_empty() {
+        fs::read_to_string("/proc/net/route")
+            .ok()
+            .and_then(|routes| default_gateway(&routes))
+            .ok_or_else(|| {
+                PortMappingError::NoGateway(format!(
+                    "the default gateway is unknown; set {PORT_FORWARDING_GATEWAY}"
+                ))
+            })?
+    } else {
+        configured.parse().map_err(|_| {
+            PortMappingError::NoGateway(format!(
+                "{PORT_FORWARDING_GATEWAY} '{configured}' is not an IPv4 address"
+            ))
+        })?
+    };
+    let gateway = SocketAddrV4::new(ip, NAT_PMP_PORT);
+    // Asking for the external address checks the gateway speaks NAT-PMP.
+    nat_pmp_request(gateway, &[0, 0])?;
+    Ok(Gateway::NatPmp(gateway))
+}
+
+/// Returns the gateway of the default route in a `/proc/net/route` table.
+fn default_gateway(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|route| {
+        let mut fields = route.split_whitespace().skip(1);
+        let destination = fields.next()?;
+        let gateway = u32::from_str_radix(fields.next()?, 16).ok()?;
+        (destination == "00000000" && gateway != 0).then(|| Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+/// Requests a NAT-PMP mapping of `port` for `lease`, or its removal when `lease` is zero,
+/// returning the lease granted.
+fn nat_pmp_map(
+    gateway: SocketAddrV4,
+    port: u16,
+    protocol: Protocol,
+    lease: Duration,
+) -> Result<Duration, PortMappingError> {
+    let opcode = match protocol {
+        Protocol::Udp => 1,
+        Protocol::Tcp => 2,
+    };
+    let [port_high, port_low] = port.to_be_bytes();
+    // A removal asks for external port 0.
+    let [external_high, external_low] = if lease.is_zero() {
+        [0, 0]
+    } else {
+        [port_high, port_low]
+    };
+    let seconds = u32::try_from(lease.as_secs()).unwrap_or(u32::MAX);
+    let [l0, l1, l2, l3] = seconds.to_be_bytes();
+    let response = nat_pmp_request(
+        gateway,
+        &[
+            0,
+            opcode,
+            0,
+            0,
+            port_high,
+            port_low,
+            external_high,
+            external_low,
+            l0,
+            l1,
+            l2,
+            l3,
+        ],
+    )?;
+    let [.., g0, g1, g2, g3] = response;
+    Ok(Duration::from_secs(u64::from(u32::from_be_bytes([
+        g0, g1, g2, g3,
+    ]))))
+}
+
+/// Sends a NAT-PMP `request` to `gateway`, retrying with growing timeouts as RFC 6886
+/// asks, and returns the 16 bytes of a successful answer.
+fn nat_pmp_request(gateway: SocketAddrV4, request: &[u8]) -> Result<[u8; 16], PortMappingError> {
+    let opcode = request.get(1).copied().unwrap_or_default();
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(gateway)?;
+    let mut timeout = NAT_PMP_INITIAL_TIMEOUT;
+    for _ in 0..NAT_PMP_ATTEMPTS {
+        socket.send(request)?;
+        socket.set_read_timeout(Some(timeout))?;
+        let mut response = [0; 16];
+        match socket.recv(&mut response) {
+            Ok(len) if len >= 8 => {
+                let [_, answered, code_high, code_low, ..] = response;
+                if answered != opcode | 0x80 {
+                    debug!("Ignoring an unexpected answer from {gateway}");
+                    continue;
+                }
+                return match u16::from_be_bytes([code_high, code_low]) {
+                    0 => Ok(response),
+                    code => Err(PortMappingError::Refused(nat_pmp_result(code).to_owned())),
+                };
+            }
+            Ok(_) => debug!("Ignoring a truncated answer from {gateway}"),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                timeout *= 2;
+            }
+            // The gateway's port is closed, so it does not speak NAT-PMP.
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                return Err(PortMappingError::NoGateway(format!(
+                    "{gateway} does not speak NAT-PMP"
+                )));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(PortMappingError::NoGateway(format!(
+        "{gateway} did not answer NAT-PMP requests"
+    )))
+}
+
+/// Describes a NAT-PMP result code.
+const fn nat_pmp_result(code: u16) -> &'static str {
+    match code {
+        1 => "unsupported NAT-PMP version",
+        2 => "port forwarding is disabled on the router",
+        3 => "the router has no external address",
+        4 => "the router is out of mappings",
+        5 => "unsupported request",
+        _ => "unknown NAT-PMP error",
+    }
+}
+
+fn discover_upnp() -> Result<Gateway, PortMappingError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_read_timeout(Some(SSDP_TIMEOUT))?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDR}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\
+         ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n"
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR)?;
+    let mut buffer = [0; 2048];
+    loop {
+        let len = match socket.recv(&mut buffer) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Err(PortMappingError::NoGateway(
+                    "no UPnP router answered discovery".to_owned(),
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let answer = String::from_utf8_lossy(buffer.get(..len).unwrap_or_default());
+        let Some(location) = ssdp_location(&answer) else {
+            continue;
+        };
+        match upnp_gateway(&location) {
+            Ok(gateway) => return Ok(gateway),
+            Err(e) => debug!("Skipping UPnP device at {location}: {e}"),
+        }
+    }
+}
+
+/// Returns the device description URL of an SSDP answer.
+fn ssdp_location(answer: &str) -> Option<Url> {
+    answer.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("location")
+            .then(|| Url::parse(value.trim()).ok())?
+    })
+}
+
+/// Reads the device description at `location` into a gateway.
+fn upnp_gateway(location: &Url) -> Result<Gateway, PortMappingError> {
+    let description = http::get(location.as_str())?.response.text()?;
+    let (service, control_url) = control_url(&description, location).ok_or_else(|| {
+        PortMappingError::NoGateway(format!("{location} offers no port mapping service"))
+    })?;
+    let host = control_url.socket_addrs(|| None)?;
+    let local_ip = local_ip_towards(host.first().copied())?;
+    Ok(Gateway::Upnp {
+        control_url,
+        service: service.to_owned(),
+        local_ip,
+    })
+}
+
+/// Finds the port mapping service in a UPnP device description, returning its type and
+/// control URL.
+fn control_url(description: &str, location: &Url) -> Option<(&'static str, Url)> {
+    let base = xml_text(description, "URLBase")
+        .and_then(|base| Url::parse(base).ok())
+        .unwrap_or_else(|| location.clone());
+    UPNP_SERVICES.iter().find_map(|service| {
+        let start = description.find(&format!("<serviceType>{service}</serviceType>"))?;
+        let rest = description.get(start..)?;
+        let end = rest.find("</service>").unwrap_or(rest.len());
+        let control = xml_text(rest.get(..end)?, "controlURL")?;
+        Some((*service, base.join(control).ok()?))
+    })
+}
+
+/// Returns the text of the first `<tag>` element in `xml`.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let rest = xml.get(start..)?;
+    let end = rest.find(&format!("</{tag}>"))?;
+    rest.get(..end).map(str::trim)
+}
+
+/// Returns the address of this machine on the interface that reaches `target`.
+fn local_ip_towards(target: Option<SocketAddr>) -> Result<Ipv4Addr, PortMappingError> {
+    let target = target
+        .ok_or_else(|| PortMappingError::NoGateway("the router's address is unknown".to_owned()))?;
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(target)?;
+    match socket.local_addr()?.ip() {
+        std::net::IpAddr::V4(ip) => Ok(ip),
+        std::net::IpAddr::V6(ip) => Err(PortMappingError::NoGateway(format!(
+            "the router is reached over IPv6 ({ip}), which UPnP port mapping does not support"
+        ))),
+    }
+}
+
+const fn upnp_protocol(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Tcp => "TCP",
+        Protocol::Udp => "UDP",
+    }
+}
+
+/// Calls the UPnP `action` of `service` at `control_url` with `args`, returning the
+/// response body.
+fn soap(
+    control_url: &Url,
+    service: &str,
+    action: &str,
+    args: &[(&str, String)],
+) -> Result<String, PortMappingError> {
+    // Writing to a `String` cannot fail.
+    let args = args.iter().fold(String::new(), |mut xml, (name, value)| {
+        let _ = write!(xml, "<{name}>{value}</{name}>");
+        xml
+    });
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{action} xmlns:u=\"{service}\">{args}</u:{action}></s:Body></s:Envelope>"
+    );
+    let response = http::client()
+        .post(control_url.clone())
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header("SOAPAction", format!("\"{service}#{action}\""))
+        .body(body)
+        .send()?;
+    let status = response.status();
+    let text = response.text()?;
+    if status.is_success() {
+        return Ok(text);
+    }
+    Err(xml_text(&text, "errorCode")
+        .and_then(|code| code.parse().ok())
+        .map_or_else(
+            || PortMappingError::Refused(format!("{action} answered with {status}")),
+            |code| PortMappingError::UpnpFault {
+                code,
+                description: xml_text(&text, "errorDescription")
+                    .unwrap_or_default()
+                    .to_owned(),
+            },
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used, clippy::indexing_slicing)]
+
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn methods_parse_from_their_names() {
+        assert_eq!("auto".parse(), Ok(Method::Auto));
+        assert_eq!("UPnP".parse(), Ok(Method::Upnp));
+        assert_eq!("nat-pmp".parse(), Ok(Method::NatPmp));
+        assert!("pcp".parse::<Method>().is_err());
+    }
+
+    #[test]
+    fn default_gateway_is_read_from_the_route_table() {
+        let routes = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                      eth0\t0011A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+                      eth0\t00000000\t0111A8C0\t0003\t0\t0\t0\t00000000\n";
+        assert_eq!(
+            default_gateway(routes),
+            Some(Ipv4Addr::new(192, 168, 17, 1))
+        );
+        assert_eq!(default_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn nat_pmp_mappings_report_the_granted_lease() {
+        let router = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let SocketAddr::V4(addr) = router.local_addr().unwrap() else {
+            unreachable!("bound to an IPv4 address");
+        };
+        thread::spawn(move || {
+            let mut request = [0; 12];
+            let (_, client) = router.recv_from(&mut request).unwrap();
+            let mut response = [0; 16];
+            response[1] = request[1] | 0x80;
+            response[8..12].copy_from_slice(&request[4..8]);
+            response[12..16].copy_from_slice(&1_800_u32.to_be_bytes());
+            router.send_to(&response, client).unwrap();
+        });
+
+        let granted = Gateway::NatPmp(addr)
+            .map(8211, Protocol::Udp, DEFAULT_LEASE)
+            .unwrap();
+        assert_eq!(granted, Duration::from_mins(30));
+    }
+
+    #[test]
+    fn upnp_descriptions_name_the_control_url() {
+        let description = "<root><device><serviceList>\
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+            <controlURL>/l3f</controlURL></service>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+            <controlURL>/upnp/control/WANIPConn1</controlURL></service>\
+            </serviceList></device></root>";
+        let location = Url::parse("http://192.168.1.1:5000/rootDesc.xml").unwrap();
+        let (service, url) = control_url(description, &location).unwrap();
+        assert_eq!(service, "urn:schemas-upnp-org:service:WANIPConnection:1");
+        assert_eq!(
+            url.as_str(),
+            "http://192.168.1.1:5000/upnp/control/WANIPConn1"
+        );
+
+        assert_eq!(
+            ssdp_location("HTTP/1.1 200 OK\r\nLOCATION: http://192.168.1.1:5000/rootDesc.xml\r\n"),
+            Some(location)
+        );
+    }
+
+    #[test]
+    fn upnp_falls_back_to_permanent_mappings() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let control_url = Url::parse(&format!(
+            "http://{}/control",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        let requests = thread::spawn(move || {
+            let fault = "<s:Envelope><s:Body><s:Fault><detail><UPnPError>\
+                <errorCode>725</errorCode><errorDescription>OnlyPermanentLeasesSupported\
+                </errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>";
+            let mut requests = Vec::new();
+            for (status, body) in [("500 Internal Server Error", fault), ("200 OK", "")] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let mut buffer = [0; 4096];
+                while !request.ends_with("</s:Envelope>") {
+                    let len = stream.read(&mut buffer).unwrap();
+                    assert!(len > 0, "the request ended early");
+                    request.push_str(&String::from_utf8_lossy(&buffer[..len]));
+                }
+                requests.push(request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+            requests
+        });
+
+        let gateway = Gateway::Upnp {
+            control_url,
+            service: UPNP_SERVICES[1].to_owned(),
+            local_ip: Ipv4Addr::new(192, 168, 1, 20),
+        };
+        let granted = gateway.map(15636, Protocol::Udp, DEFAULT_LEASE).unwrap();
+        assert_eq!(granted, Duration::ZERO);
+
+        let requests = requests.join().unwrap();
+        assert!(requests[0].contains("<NewLeaseDuration>3600</NewLeaseDuration>"));
+        assert!(requests[1].contains("<NewLeaseDuration>0</NewLeaseDuration>"));
+        assert!(requests[1].contains("<NewInternalClient>192.168.1.20</NewInternalClient>"));
+        assert!(requests[1].contains("#AddPortMapping\""));
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+use std::ops::RangeInclusive;
+use tracing::debug;
+
+/// The transport protocol a port is bound with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// Returns whether `port` can be bound on all interfaces with `protocol`, i.e. no other
+/// process is listening on it.
+///
+/// Game servers that find their port taken often only log a bind failure deep in their
+/// own logs, so check before launching them. The answer can change as soon as it is
+/// returned; it is a diagnostic, not a reservation.
+pub fn is_port_free(port: u16, protocol: Protocol) -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let result = match protocol {
+        Protocol::Tcp => TcpListener::bind(addr).map(drop),
+        Protocol::Udp => UdpSocket::bind(addr).map(drop),
+    };
+    if let Err(e) = &result {
+        debug!("Port {port}/{protocol} is not free: {e}");
+    }
+    result.is_ok()
+}
+
+/// Returns the first port in `range` that is free for `protocol`, or `None` when every
+/// port in it is taken.
+pub fn find_free_port(range: RangeInclusive<u16>, protocol: Protocol) -> Option<u16> {
+    range
+        .filter(|port| *port != 0)
+        .find(|port| is_port_free(*port, protocol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bound_ports_are_not_free() -> std::io::Result<()> {
+        let tcp = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        let tcp_port = tcp.local_addr()?.port();
+        assert!(!is_port_free(tcp_port, Protocol::Tcp));
+
+        let udp = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        let udp_port = udp.local_addr()?.port();
+        assert!(!is_port_free(udp_port, Protocol::Udp));
+
+        drop(udp);
+        assert!(is_port_free(udp_port, Protocol::Udp));
+        Ok(())
+    }
+
+    #[test]
+    fn find_free_port_skips_taken_ports() -> std::io::Result<()> {
+        let taken = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        let port = taken.local_addr()?.port();
+
+        assert_eq!(find_free_port(port..=port, Protocol::Udp), None);
+        let found = find_free_port(port..=port.saturating_add(50), Protocol::Udp);
+        assert!(found.is_some_and(|found| found > port));
+        Ok(())
+    }
+
+    #[test]
+    fn protocols_use_lowercase_names() -> serde_json::Result<()> {
+        assert_eq!(Protocol::Udp.to_string(), "udp");
+        assert_eq!(serde_json::from_str::<Protocol>("\"tcp\"")?, Protocol::Tcp);
+        Ok(())
+    }
+}
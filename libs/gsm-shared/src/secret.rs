@@ -0,0 +1,268 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// What secrets are replaced with in logs and debug output.
+pub const REDACTED: &str = "<redacted>";
+
+/// Parts of a name that mark its value as secret, e.g. `SERVER_PASSWORD` or `API_TOKEN`.
+const SECRET_MARKERS: [&str; 6] = ["PASSWORD", "PASSWD", "SECRET", "TOKEN", "KEY", "CREDENTIAL"];
+
+/// A value, such as a password, that must not show up in logs.
+///
+/// Its `Debug` and `Display` output is [`REDACTED`], so structs holding one can derive
+/// `Debug` and still be logged. It serializes as the plain value, so config files the
+/// game reads keep working; call [`Secret::expose`] where the real value is needed.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    /// Wraps `value` so it is redacted in logs.
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the secret value.
+    pub const fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns the secret value, consuming the wrapper.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl FromStr for Secret<String> {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(value))
+    }
+}
+
+impl<T: Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Returns `true` when a variable or field named `key` likely holds a secret, such as
+/// `SERVER_PASSWORD`, `AdminPassword` or `API_TOKEN`, and so should not be logged.
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// Replaces the values of secret-looking keys (see [`is_secret_key`]) in `text` with
+/// [`REDACTED`].
+///
+/// Use it to log debug dumps of structs, JSON, environments and command lines that were
+/// not written with [`Secret`] in mind.
+///
+/// Recognizes `KEY=value`, `key: value`, `"key": "value"`, `key: Some("value")` and
+/// command-line flags such as `-betapassword value`.
+pub fn redact_secrets(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_word_char) {
+        let (before, word) = rest.split_at(start);
+        let end = word.find(|c| !is_word_char(c)).unwrap_or(word.len());
+        let (word, after) = word.split_at(end);
+        output.push_str(before);
+        output.push_str(word);
+        rest = after;
+        if !is_secret_key(word) {
+            continue;
+        }
+        if let Some((prefix_len, value_len)) = secret_value(after, before.ends_with('-')) {
+            let (prefix, value) = after.split_at(prefix_len);
+            output.push_str(prefix);
+            output.push_str(REDACTED);
+            rest = value.get(value_len..).unwrap_or_default();
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Applies [`redact_secrets`] to each of a command's `args`, and also redacts an
+/// argument that follows a secret flag on its own, such as `["-adminpassword", "pw"]`.
+pub fn redact_args<S: AsRef<str>>(args: &[S]) -> Vec<String> {
+    let mut after_flag = false;
+    args.iter()
+        .map(|arg| {
+            let arg = arg.as_ref();
+            let redacted = if after_flag && !arg.starts_with(['-', '+']) {
+                REDACTED.to_owned()
+            } else {
+                redact_secrets(arg)
+            };
+            after_flag = arg
+                .strip_prefix('-')
+                .map(|flag| flag.trim_start_matches('-'))
+                .is_some_and(|flag| flag.chars().all(is_word_char) && is_secret_key(flag));
+            redacted
+        })
+        .collect()
+}
+
+const fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Finds the value assigned to a secret key in `text`, which follows the key. Returns
+/// the length of what precedes the value and of the value itself, or `None` when the key
+/// is not assigned a value. Flags, such as `-betapassword`, may be followed by their
+/// value after whitespace alone.
+fn secret_value(text: &str, flag: bool) -> Option<(usize, usize)> {
+    let after_key = text.strip_prefix(['"', '\'']).unwrap_or(text);
+    let trimmed = after_key.trim_start();
+    let after_separator = match trimmed.strip_prefix(['=', ':']) {
+        Some(rest) => rest.trim_start(),
+        None if flag && trimmed.len() < after_key.len() => trimmed,
+        None => return None,
+    };
+    let value = after_separator
+        .strip_prefix("Some(")
+        .unwrap_or(after_separator);
+    let (value, value_len) = if let Some(quote @ ('"' | '\'')) = value.chars().next() {
+        let inner = value.get(1..)?;
+        (inner, quoted_len(inner, quote)?)
+    } else {
+        let len = value
+            .find(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '&' | ')' | ']' | '}'))
+            .unwrap_or(value.len());
+        if value.get(..len) == Some("None") {
+            return None;
+        }
+        (value, len)
+    };
+    (value_len > 0).then_some((text.len() - value.len(), value_len))
+}
+
+/// Returns the length of a string ending at an unescaped `quote`.
+fn quoted_len(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Settings {
+        name: String,
+        password: Secret<String>,
+    }
+
+    #[test]
+    fn secrets_are_redacted_but_serialize_plainly() -> serde_json::Result<()> {
+        let settings = Settings {
+            name: "server".to_owned(),
+            password: Secret::from("hunter2"),
+        };
+        let rendered = format!("{settings:?}");
+        assert!(!rendered.contains("hunter2"));
+        assert!(rendered.contains("password: <redacted>"));
+        assert_eq!(settings.password.to_string(), REDACTED);
+        assert_eq!(settings.password.expose(), "hunter2");
+
+        assert_eq!(serde_json::to_string(&settings.password)?, "\"hunter2\"");
+        let parsed: Secret<String> = serde_json::from_str("\"pw\"")?;
+        assert_eq!(parsed.into_inner(), "pw");
+        Ok(())
+    }
+
+    #[test]
+    fn redact_secrets_scrubs_common_formats() {
+        assert_eq!(
+            redact_secrets("SERVER_PASSWORD=hunter2 PORT=8211"),
+            "SERVER_PASSWORD=<redacted> PORT=8211"
+        );
+        assert_eq!(
+            redact_secrets(r#"{"AdminPassword": "a \"b\" c", "Port": 8211}"#),
+            r#"{"AdminPassword": "<redacted>", "Port": 8211}"#
+        );
+        assert_eq!(
+            redact_secrets(r#"BetaConfig { branch: "staging", password: Some("pw") }"#),
+            r#"BetaConfig { branch: "staging", password: Some("<redacted>") }"#
+        );
+        assert_eq!(
+            redact_secrets("+app_update 1 -beta staging -betapassword pw +quit"),
+            "+app_update 1 -beta staging -betapassword <redacted> +quit"
+        );
+        assert_eq!(
+            redact_secrets("password: None, token=, name=server"),
+            "password: None, token=, name=server"
+        );
+    }
+
+    #[test]
+    fn redact_args_handles_separate_flag_values() {
+        assert_eq!(
+            redact_args(&[
+                "-port",
+                "8211",
+                "-adminpassword",
+                "pw",
+                "-ServerPassword=pw"
+            ]),
+            [
+                "-port",
+                "8211",
+                "-adminpassword",
+                REDACTED,
+                "-ServerPassword=<redacted>"
+            ]
+        );
+    }
+
+    #[test]
+    fn secret_keys_are_matched_case_insensitively() {
+        assert!(is_secret_key("BETA_BRANCH_PASSWORD"));
+        assert!(is_secret_key("steam_api_key"));
+        assert!(!is_secret_key("LD_PRELOAD"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::fetch_var;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::{Builder, TempDir};
+use tracing::{debug, warn};
+
+/// Name of the directory, under an instance root, that holds all staging directories.
+pub const STAGING_DIR_NAME: &str = ".gsm-staging";
+
+/// Name of the marker file that identifies a directory as a staging root, written when
+/// [`staging_dir`] creates the root.
+pub const STAGING_MARKER: &str = ".gsm-staging-root";
+
+/// Environment variable that relocates every staging root, e.g. onto a scratch volume.
+pub const GSM_TMPDIR: &str = "GSM_TMPDIR";
+
+/// Staging directories older than this are considered orphaned by default.
+pub const DEFAULT_STAGING_MAX_AGE: Duration = Duration::from_hours(24);
+
+/// Keeps only characters that are safe in a single path component.
+fn sanitize_component(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.is_empty() {
+        String::from("_")
+    } else {
+        sanitized
+    }
+}
+
+/// Returns the staging root used for `root`: `$GSM_TMPDIR/.gsm-staging` when
+/// `GSM_TMPDIR` is set, otherwise `root/.gsm-staging`.
+///
+/// If `root` already is a staging root (see [`is_staging_root`]), it is returned as-is
+/// instead of nesting another root.
+pub fn staging_root(root: &Path) -> PathBuf {
+    if is_staging_root(root) {
+        return root.to_path_buf();
+    }
+    let override_root = fetch_var(GSM_TMPDIR, "");
+    if override_root.is_empty() {
+        root.join(STAGING_DIR_NAME)
+    } else {
+        Path::new(&override_root).join(STAGING_DIR_NAME)
+    }
+}
+
+/// Returns whether `path` is a staging root created by [`staging_dir`], i.e. holds a
+/// [`STAGING_MARKER`]. A directory that merely shares the root's name does not count.
+pub fn is_staging_root(path: &Path) -> bool {
+    path.join(STAGING_MARKER).is_file()
+}
+
+/// Creates a unique staging directory for one operation under [`staging_root`].
+///
+/// The directory name is prefixed with `namespace` (usually the instance) and
+/// `operation` so concurrent operations never share a path. By default it lives on the
+/// same filesystem as `root` so its contents can be renamed into place; when
+/// `GSM_TMPDIR` moves it elsewhere, use [`move_path`](crate::move_path) to move files
+/// out of it. It is removed when the returned [`TempDir`] is dropped, including on early
+/// error returns.
+///
+/// # Errors
+///
+/// Returns an error when the staging root or the directory itself cannot be created.
+pub fn staging_dir(root: &Path, namespace: &str, operation: &str) -> io::Result<TempDir> {
+    let staging_root = staging_root(root);
+    fs::create_dir_all(&staging_root)?;
+    let marker = staging_root.join(STAGING_MARKER);
+    if !marker.is_file() {
+        fs::write(&marker, "")?;
+    }
+    let prefix = format!(
+        "{}-{}-",
+        sanitize_component(namespace),
+        sanitize_component(operation)
+    );
+    let dir = Builder::new().prefix(&prefix).tempdir_in(&staging_root)?;
+    debug!("Created staging directory {:?}", dir.path());
+    Ok(dir)
+}
+
+/// Removes staging directories under [`staging_root`] older than `max_age`.
+///
+/// Intended to run once at startup with the instance's working directory, to clear
+/// directories left behind by crashed or killed operations. Every staging directory an
+/// instance creates must therefore be rooted there (or under `GSM_TMPDIR`). Returns the
+/// paths that were removed.
+///
+/// # Errors
+///
+/// Returns an error when the staging root exists but cannot be listed.
+pub fn clean_orphaned_staging(root: &Path, max_age: Duration) -> io::Result<Vec<PathBuf>> {
+    let staging_root = staging_root(root);
+    if !staging_root.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for entry in fs::read_dir(&staging_root)? {
+        let entry = entry?;
+        if entry.file_name() == STAGING_MARKER {
+            continue;
+        }
+        let path = entry.path();
+        let age = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok());
+        if age.is_none_or(|age| age < max_age) {
+            continue;
+        }
+
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => {
+                debug!("Removed orphaned staging path {:?}", path);
+                removed.push(path);
+            }
+            Err(e) => warn!("Failed to remove orphaned staging path {:?}: {e}", path),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn staging_dirs_are_unique_and_removed_on_drop() -> io::Result<()> {
+        let root = tempdir()?;
+        let first = staging_dir(root.path(), "palworld", "mods/install")?;
+        let second = staging_dir(root.path(), "palworld", "mods/install")?;
+
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().starts_with(root.path().join(STAGING_DIR_NAME)));
+        assert!(
+            first
+                .path()
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("palworld-mods_install-"))
+        );
+
+        let first_path = first.path().to_path_buf();
+        drop(first);
+        assert!(!first_path.exists());
+        assert!(second.path().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn clean_orphaned_staging_respects_max_age() -> io::Result<()> {
+        let root = tempdir()?;
+        let staged = staging_dir(root.path(), "instance", "backup")?.keep();
+
+        assert!(clean_orphaned_staging(root.path(), DEFAULT_STAGING_MAX_AGE)?.is_empty());
+        assert!(staged.exists());
+
+        let removed = clean_orphaned_staging(root.path(), Duration::ZERO)?;
+        assert_eq!(removed, vec![staged.clone()]);
+        assert!(!staged.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn staging_roots_are_recognized_by_their_marker() -> io::Result<()> {
+        let root = tempdir()?;
+        let staged = staging_dir(root.path(), "instance", "normalize")?;
+        let marked = root.path().join(STAGING_DIR_NAME);
+        assert!(is_staging_root(&marked));
+        assert_eq!(staging_root(&marked), marked);
+        assert!(staged.path().starts_with(&marked));
+
+        // A directory that is only named like a staging root gets a root of its own.
+        let lookalike = root.path().join("saves").join(STAGING_DIR_NAME);
+        fs::create_dir_all(&lookalike)?;
+        assert!(!is_staging_root(&lookalike));
+        assert_eq!(staging_root(&lookalike), lookalike.join(STAGING_DIR_NAME));
+        Ok(())
+    }
+
+    #[test]
+    fn clean_orphaned_staging_ignores_missing_root() -> io::Result<()> {
+        let root = tempdir()?;
+        assert!(clean_orphaned_staging(&root.path().join("missing"), Duration::ZERO)?.is_empty());
+        Ok(())
+    }
+}
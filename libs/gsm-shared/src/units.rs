@@ -0,0 +1,301 @@
+//! Parses the durations and sizes the environment and config files are written in.
+//!
+//! Durations combine amounts with units, e.g. `90s`, `5m`, `1h30m` or `500ms`; sizes
+//! take one unit, e.g. `10GB` or `512MiB`. Every setting that takes a duration goes
+//! through [`parse_duration_in`], so they all accept the same syntax and differ only in
+//! what a bare number means: seconds for most, minutes for settings named in minutes,
+//! nanoseconds for values stored by a game in nanoseconds.
+
+use crate::fetch_var;
+use std::fmt;
+use std::time::Duration;
+use tracing::warn;
+
+/// Duration units, in nanoseconds.
+const DURATION_UNITS: [(&str, u64); 6] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("ns", 1),
+];
+
+/// Size units, in bytes. `KB`, `MB`, ... are decimal; `KiB`, `MiB`, ... are binary.
+const SIZE_UNITS: [(&str, u64); 9] = [
+    ("b", 1),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+];
+
+/// Why a value could not be read as a duration or size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUnitError {
+    value: String,
+    reason: &'static str,
+}
+
+impl ParseUnitError {
+    /// Returns why the value was rejected, without the value itself.
+    pub const fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value '{}': {}", self.value, self.reason)
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+/// Parses a duration such as `"90s"`, `"5m"`, `"1h30m"` or `"500ms"`.
+///
+/// Units are `d`, `h`, `m`, `s`, `ms` and `ns`, in any case; a bare number is seconds,
+/// so existing values that were plain seconds keep their meaning.
+///
+/// # Errors
+///
+/// Returns an error when the value is empty, a number has an unknown unit, or the
+/// duration overflows.
+pub fn parse_duration(value: &str) -> Result<Duration, ParseUnitError> {
+    parse_duration_in(value, Duration::from_secs(1))
+}
+
+/// Parses a duration like [`parse_duration`], reading a bare number as a count of
+/// `bare`, e.g. `Duration::from_mins(1)` for a setting documented in minutes.
+///
+/// # Errors
+///
+/// Returns an error when the value is empty, a number has an unknown unit, or the
+/// duration overflows.
+pub fn parse_duration_in(value: &str, bare: Duration) -> Result<Duration, ParseUnitError> {
+    let error = |reason| ParseUnitError {
+        value: value.to_owned(),
+        reason,
+    };
+    let trimmed = value.trim();
+    let amounts = match trimmed.parse::<u64>() {
+        Ok(count) => {
+            let scale =
+                u64::try_from(bare.as_nanos()).map_err(|_| error("duration is too long"))?;
+            vec![(count, scale)]
+        }
+        Err(_) => split_amounts(trimmed)
+            .map_err(error)?
+            .into_iter()
+            .map(|(number, unit)| {
+                DURATION_UNITS
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                    .map(|(_, scale)| (number, *scale))
+                    .ok_or_else(|| error("unknown unit; expected d, h, m, s, ms or ns"))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let mut nanos: u64 = 0;
+    for (number, scale) in amounts {
+        nanos = number
+            .checked_mul(scale)
+            .and_then(|part| nanos.checked_add(part))
+            .ok_or_else(|| error("duration is too long"))?;
+    }
+    Ok(Duration::from_nanos(nanos))
+}
+
+/// Parses a size in bytes such as `"10GB"`, `"512MiB"` or `"1.5 GB"`. `KB`, `MB`, `GB`
+/// and `TB` are powers of 1000, `KiB` to `TiB` powers of 1024, in any case; a bare number
+/// is bytes.
+///
+/// # Errors
+///
+/// Returns an error when the value is empty, has an unknown unit, or overflows.
+pub fn parse_size(value: &str) -> Result<u64, ParseUnitError> {
+    let error = |reason| ParseUnitError {
+        value: value.to_owned(),
+        reason,
+    };
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(error("value is empty"));
+    }
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let unit = unit.trim();
+    let scale = if unit.is_empty() {
+        1
+    } else {
+        SIZE_UNITS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+            .map(|(_, scale)| *scale)
+            .ok_or_else(|| error("unknown unit; expected B, KB, MB, GB, TB or KiB to TiB"))?
+    };
+    if let Ok(number) = number.parse::<u64>() {
+        return number
+            .checked_mul(scale)
+            .ok_or_else(|| error("size is too large"));
+    }
+    let number: f64 = number
+        .parse()
+        .map_err(|_| error("expected a number before the unit"))?;
+    scale_fraction(number, scale).ok_or_else(|| error("size is too large"))
+}
+
+/// Returns `number * scale` rounded to whole bytes, or `None` if it does not fit.
+#[allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+fn scale_fraction(number: f64, scale: u64) -> Option<u64> {
+    let bytes = (number * scale as f64).round();
+    (bytes.is_finite() && bytes >= 0.0 && bytes < u64::MAX as f64).then_some(bytes as u64)
+}
+
+/// Splits `value` into its numbers and the units after them, e.g. `1h30m` into
+/// `[(1, "h"), (30, "m")]`.
+fn split_amounts(value: &str) -> Result<Vec<(u64, &str)>, &'static str> {
+    if value.is_empty() {
+        return Err("value is empty");
+    }
+    let mut amounts = Vec::new();
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or("a number is missing its unit")?;
+        if digits == 0 {
+            return Err("expected a number before each unit");
+        }
+        let (number, tail) = rest.split_at(digits);
+        let number = number.parse().map_err(|_| "number is too large")?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        amounts.push((number, unit.trim()));
+        rest = tail.trim_start();
+    }
+    Ok(amounts)
+}
+
+/// Fetches the environment variable `name` as a duration (see [`parse_duration`]),
+/// returning `default` when it is unset, empty or invalid. Invalid values are logged.
+pub fn fetch_duration(name: &str, default: Duration) -> Duration {
+    fetch_duration_in(name, default, Duration::from_secs(1))
+}
+
+/// Fetches the environment variable `name` like [`fetch_duration`], reading a bare
+/// number as a count of `bare` (see [`parse_duration_in`]).
+pub fn fetch_duration_in(name: &str, default: Duration, bare: Duration) -> Duration {
+    let value = fetch_var(name, "");
+    if value.is_empty() {
+        return default;
+    }
+    parse_duration_in(&value, bare).unwrap_or_else(|e| {
+        warn!("Ignoring {name}: {e}");
+        default
+    })
+}
+
+/// Fetches the environment variable `name` as a size in bytes (see [`parse_size`]),
+/// returning `default` when it is unset, empty or invalid. Invalid values are logged.
+pub fn fetch_size(name: &str, default: u64) -> u64 {
+    let value = fetch_var(name, "");
+    if value.is_empty() {
+        return default;
+    }
+    parse_size(&value).unwrap_or_else(|e| {
+        warn!("Ignoring {name}: {e}");
+        default
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_mins(5)));
+        assert_eq!(parse_duration(" 1H "), Ok(Duration::from_hours(1)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_mins(90)));
+        assert_eq!(parse_duration("2m 30s"), Ok(Duration::from_secs(150)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_hours(24)));
+        assert_eq!(parse_duration("15ns"), Ok(Duration::from_nanos(15)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5 minutes").is_err());
+        assert!(parse_duration("m5").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn bare_numbers_are_read_in_the_given_unit() {
+        let minute = Duration::from_mins(1);
+        assert_eq!(parse_duration_in("45", minute), Ok(Duration::from_mins(45)));
+        assert_eq!(
+            parse_duration_in("90s", minute),
+            Ok(Duration::from_secs(90))
+        );
+        assert_eq!(
+            parse_duration_in("720000000000", Duration::from_nanos(1)),
+            Ok(Duration::from_mins(12))
+        );
+        assert!(parse_duration_in("99999999999999", Duration::from_hours(1)).is_err());
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("10GB"), Ok(10_000_000_000));
+        assert_eq!(parse_size("10 gb"), Ok(10_000_000_000));
+        assert_eq!(parse_size("512MiB"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_size("1.5KiB"), Ok(1536));
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("10 GBs").is_err());
+        assert!(parse_size("GB").is_err());
+        assert!(parse_size("99999999999TB").is_err());
+    }
+
+    #[test]
+    fn fetch_helpers_fall_back_to_the_default() {
+        let key = "TEST_FETCH_DURATION_AND_SIZE";
+        unsafe {
+            env::remove_var(key);
+        }
+        assert_eq!(
+            fetch_duration(key, Duration::from_secs(30)),
+            Duration::from_secs(30)
+        );
+        assert_eq!(fetch_size(key, 7), 7);
+
+        unsafe {
+            env::set_var(key, "2m");
+        }
+        assert_eq!(
+            fetch_duration(key, Duration::from_secs(30)),
+            Duration::from_mins(2)
+        );
+        assert_eq!(fetch_size(key, 7), 7);
+
+        unsafe {
+            env::set_var(key, "\"2MB\"");
+        }
+        assert_eq!(fetch_size(key, 7), 2_000_000);
+        unsafe {
+            env::remove_var(key);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// The file, inside a locked directory, that [`WorkdirLock`] locks and records its
+/// holder in.
+pub const LOCK_FILE_NAME: &str = ".gsm.lock";
+
+/// An advisory lock on a working directory, held until it is dropped.
+///
+/// Two containers pointing at the same directory must not run SteamCMD or a backup in it
+/// at the same time, so installs, updates and backups take this lock first. The lock is
+/// taken on [`LOCK_FILE_NAME`] with the operating system's file locking, so it is
+/// released when its process exits, even if it crashes, and never needs cleaning up.
+#[derive(Debug)]
+pub struct WorkdirLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl WorkdirLock {
+    /// Locks `dir`, creating it if needed, and records this process as the holder.
+    ///
+    /// This does not wait: if another process, or another lock in this one, holds `dir`,
+    /// it fails straight away, naming the holder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::WouldBlock`] reading
+    /// "`dir` is locked by pid X since T" when `dir` is already locked, or the I/O
+    /// error when the lock file cannot be created or written.
+    pub fn acquire(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!(
+                        "{} is locked by {}",
+                        dir.display(),
+                        describe_holder(&holder)
+                    ),
+                ));
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        file.set_len(0)?;
+        writeln!(file, "{} {since}", process::id())?;
+        debug!("Locked {}", dir.display());
+        Ok(Self { file, path })
+    }
+
+    /// Returns the path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for WorkdirLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            warn!("Failed to unlock {}: {e}", self.path.display());
+        }
+        debug!("Unlocked {}", self.path.display());
+    }
+}
+
+/// Describes the holder recorded in a lock file as `pid X since T`.
+fn describe_holder(contents: &str) -> String {
+    let mut fields = contents.split_whitespace();
+    match (
+        fields.next().and_then(|pid| pid.parse::<u32>().ok()),
+        fields.next().and_then(|since| since.parse::<u64>().ok()),
+    ) {
+        (Some(pid), Some(since)) => format!("pid {pid} since {}", format_utc(since)),
+        (Some(pid), None) => format!("pid {pid}"),
+        _ => "another process".to_owned(),
+    }
+}
+
+/// Formats `secs` since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`.
+fn format_utc(secs: u64) -> String {
+    let (days, time) = (secs / 86_400, secs % 86_400);
+    // Converts days since 1970-01-01 to a civil date, counting years from March so the
+    // leap day falls at the end of each year.
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn a_locked_directory_names_its_holder() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("server");
+
+        let lock = WorkdirLock::acquire(&dir)?;
+        assert_eq!(lock.path(), dir.join(LOCK_FILE_NAME));
+
+        let error = WorkdirLock::acquire(&dir).err();
+        assert_eq!(
+            error.as_ref().map(io::Error::kind),
+            Some(io::ErrorKind::WouldBlock)
+        );
+        let message = error.map(|e| e.to_string()).unwrap_or_default();
+        assert!(message.contains(&format!("is locked by pid {} since ", process::id())));
+        assert!(message.ends_with(" UTC"));
+
+        drop(lock);
+        WorkdirLock::acquire(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn holders_are_described_from_the_lock_file() {
+        assert_eq!(
+            describe_holder("42 1700000000\n"),
+            "pid 42 since 2023-11-14 22:13:20 UTC"
+        );
+        assert_eq!(describe_holder("42"), "pid 42");
+        assert_eq!(describe_holder(""), "another process");
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+    }
+}
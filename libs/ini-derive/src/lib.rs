@@ -1,10 +1,11 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{DeriveInput, LitStr, parse_macro_input};
+use syn::{DeriveInput, LitBool, LitStr, parse_macro_input};
 
 struct IniHeaderArgs {
     name: LitStr,
+    preserve_order: Option<LitBool>,
 }
 
 impl syn::parse::Parse for IniHeaderArgs {
@@ -18,13 +19,32 @@ impl syn::parse::Parse for IniHeaderArgs {
         input.parse::<syn::Token![=]>()?;
         // Parse a literal string value
         let lit: LitStr = input.parse()?;
-        Ok(Self { name: lit })
+
+        // Optionally followed by `, preserve_order = <bool>`
+        let preserve_order =
+            if input.parse::<Option<syn::Token![,]>>()?.is_some() && !input.is_empty() {
+                let ident: syn::Ident = input.parse()?;
+                if ident != "preserve_order" {
+                    return Err(syn::Error::new(ident.span(), "expected `preserve_order`"));
+                }
+                input.parse::<syn::Token![=]>()?;
+                Some(input.parse()?)
+            } else {
+                None
+            };
+        Ok(Self {
+            name: lit,
+            preserve_order,
+        })
     }
 }
 
 #[proc_macro_derive(IniSerialize, attributes(INIHeader))]
 /// Derives `IniHeader` from an `#[INIHeader(name = \"...\")]` attribute.
 ///
+/// Entries are written in field order; add `preserve_order = false` to the attribute to
+/// sort them by key instead.
+///
 /// # Panics
 ///
 /// Panics when the derive target does not include a valid `INIHeader(name = \"...\")`
@@ -35,11 +55,13 @@ pub fn ini_serialize(input: TokenStream) -> TokenStream {
 
     // Look for #[INIHeader(name = "...")]
     let mut header_value = None;
+    let mut preserve_order = true;
     for attr in &input.attrs {
         if attr.path().is_ident("INIHeader")
             && let Ok(args) = attr.parse_args::<IniHeaderArgs>()
         {
             header_value = Some(args.name.value());
+            preserve_order = args.preserve_order.is_none_or(|lit| lit.value);
         }
     }
 
@@ -57,6 +79,10 @@ pub fn ini_serialize(input: TokenStream) -> TokenStream {
             fn ini_header() -> &'static str {
                 #header_value
             }
+
+            fn ini_preserve_order() -> bool {
+                #preserve_order
+            }
         }
 
         // Also implement IniHeader for a reference to this type.
@@ -64,6 +90,10 @@ pub fn ini_serialize(input: TokenStream) -> TokenStream {
             fn ini_header() -> &'static str {
                 <#name as IniHeader>::ini_header()
             }
+
+            fn ini_preserve_order() -> bool {
+                <#name as IniHeader>::ini_preserve_order()
+            }
         }
     };
 
@@ -182,6 +182,47 @@ fn extract_env_vars_from_file(
         }
     }
 
+    extract_env_config_fields(&content, env_vars)
+}
+
+/// Collects the fields of `#[derive(EnvConfig)]` structs, e.g.
+/// `#[env(name = "EXP_RATE", default = 1.0)] pub exp_rate: f32`.
+fn extract_env_config_fields(
+    content: &str,
+    env_vars: &mut HashMap<String, EnvVarInfo>,
+) -> Result<(), Box<dyn Error>> {
+    let field_regex = Regex::new(
+        r"(?s)#\[env\(([^\]]*)\)\](?:\s*#\[[^\]]*\])*\s*(?:pub(?:\([^)]*\))?\s+)?([a-z0-9_]+)\s*:\s*([A-Za-z0-9_:<>]+)",
+    )?;
+    let name_regex = Regex::new(r#"name\s*=\s*"([A-Z0-9_]+)""#)?;
+    let default_regex = Regex::new(r#"default\s*=\s*("[^"]*"|[^,]+)"#)?;
+    let skip_regex = Regex::new(r"\bskip\b")?;
+
+    for caps in field_regex.captures_iter(content) {
+        let (Some(args), Some(field), Some(var_type)) = (caps.get(1), caps.get(2), caps.get(3))
+        else {
+            continue;
+        };
+        let args = args.as_str();
+        if skip_regex.is_match(args) {
+            continue;
+        }
+        let var_name = name_regex
+            .captures(args)
+            .and_then(|name| name.get(1))
+            .map_or_else(
+                || field.as_str().to_uppercase(),
+                |name| name.as_str().to_owned(),
+            );
+        let entry = env_vars.entry(var_name).or_default();
+        entry.field = Some(field.as_str().to_owned());
+        entry.var_type = Some(var_type.as_str().to_owned());
+        entry.default = default_regex
+            .captures(args)
+            .and_then(|default| default.get(1))
+            .map(|default| default.as_str().trim().to_owned());
+    }
+
     Ok(())
 }
 
@@ -206,6 +247,18 @@ pub fn example() {
     let _ = fetch_var("FETCH_ENV", "fallback");
     let _ = is_env_var_truthy("TRUTHY_ENV");
 }
+
+#[derive(EnvConfig)]
+pub struct Settings {
+    #[serde(rename = "ExpRate")]
+    #[env(default = 1.0)]
+    pub exp_rate: f32,
+    #[env(name = "BAN_LIST", default = "https://example.com/banlist.txt")]
+    #[serde(rename = "BanListURL")]
+    pub ban_list_url: String,
+    #[env(skip)]
+    loaded: bool,
+}
 "#,
         )
         .unwrap();
@@ -254,6 +307,19 @@ pub fn example() {
 
         let truthy_env = env_vars.get("TRUTHY_ENV").unwrap();
         assert_eq!(truthy_env.var_type.as_deref(), Some("bool"));
+
+        let exp_rate = env_vars.get("EXP_RATE").unwrap();
+        assert_eq!(exp_rate.field.as_deref(), Some("exp_rate"));
+        assert_eq!(exp_rate.var_type.as_deref(), Some("f32"));
+        assert_eq!(exp_rate.default.as_deref(), Some("1.0"));
+
+        let ban_list = env_vars.get("BAN_LIST").unwrap();
+        assert_eq!(ban_list.field.as_deref(), Some("ban_list_url"));
+        assert_eq!(
+            ban_list.default.as_deref(),
+            Some("\"https://example.com/banlist.txt\"")
+        );
+        assert!(!env_vars.contains_key("LOADED"));
     }
 
     #[test]
@@ -0,0 +1,77 @@
+//! # Fake Game Server
+//!
+//! A stand-in for a real dedicated server, used by the workspace integration tests. It
+//! behaves just enough like one for the management crates to drive it:
+//!
+//! - listens on a TCP port (`--port`, default `0` for any free port) and logs which;
+//! - writes a save file to `savegame/world.sav` in its working directory;
+//! - logs a `Player joined` line and a heartbeat periodically, like a busy server;
+//! - shuts down cleanly on SIGINT, logging `Shutdown complete`.
+//!
+//! It exits on its own after `--max-runtime` seconds (default 120) so a failed test
+//! never leaves it running.
+use nix::sys::signal::{SigHandler, Signal, signal};
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: nix::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+fn arg_value(args: &[String], name: &str) -> Option<u64> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .and_then(|value| value.parse().ok())
+}
+
+fn write_save(tick: u64) -> std::io::Result<()> {
+    let save_dir = Path::new("savegame");
+    fs::create_dir_all(save_dir)?;
+    fs::write(
+        save_dir.join("world.sav"),
+        format!("world at tick {tick}\n"),
+    )
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let port = arg_value(&args, "--port").unwrap_or(0);
+    let max_runtime = Duration::from_secs(arg_value(&args, "--max-runtime").unwrap_or(120));
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe { signal(Signal::SIGINT, SigHandler::Handler(on_interrupt)) }
+        .map_err(std::io::Error::other)?;
+
+    println!("Server starting (pid {})", std::process::id());
+    let listener = TcpListener::bind(("127.0.0.1", u16::try_from(port).unwrap_or(0)))?;
+    listener.set_nonblocking(true)?;
+    println!("Listening on port {}", listener.local_addr()?.port());
+    write_save(0)?;
+    println!("Server ready");
+
+    let started = Instant::now();
+    let mut tick = 0;
+    while !INTERRUPTED.load(Ordering::SeqCst) && started.elapsed() < max_runtime {
+        // Accept and immediately drop connections; the tests only probe the port.
+        while listener.accept().is_ok() {}
+        tick += 1;
+        if tick % 5 == 0 {
+            println!("Player joined: Tester");
+            write_save(tick)?;
+        }
+        println!("tick {tick}");
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    println!("Saving world before shutdown");
+    write_save(tick)?;
+    println!("Shutdown complete");
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! End-to-end lifecycle of a managed server, driving the real workspace crates against
+//! the `fake-game-server` binary and a fake SteamCMD script:
+//!
+//! install → start → monitor rules firing → scheduled restart → backup → stop.
+//!
+//! Each crate is unit tested on its own; this suite guards the hand-offs between them,
+//! such as the monitor following the log file across a restart.
+#![allow(clippy::unwrap_used, clippy::expect_used)]
+
+use gsm_backup::{BackupEvent, BackupOptions, backup_with_hooks};
+use gsm_cron::register_job;
+use gsm_instance::config::LaunchMode;
+use gsm_instance::{Instance, InstanceConfig, StopOutcome};
+use gsm_monitor::{LogRules, start_instance_log_monitor};
+use std::fs;
+use std::net::TcpStream;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+const APP_ID: u32 = 999_999;
+const FAKE_SERVER: &str = env!("CARGO_BIN_EXE_fake-game-server");
+
+/// Writes a SteamCMD stand-in that "installs" the fake server: it records an app
+/// manifest and copies the binary into `+force_install_dir`.
+fn write_fake_steamcmd(dir: &Path) -> PathBuf {
+    let path = dir.join("steamcmd");
+    let script = format!(
+        r#"#!/bin/sh
+for arg in "$@"; do
+  case "$arg" in
+    "+force_install_dir "*) dir="${{arg#+force_install_dir }}" ;;
+    "+app_update "*) app="${{arg#+app_update }}"; app="${{app%% *}}" ;;
+  esac
+done
+mkdir -p "$dir/steamapps"
+printf '"AppState"\n{{\n\t"appid"\t\t"%s"\n\t"buildid"\t\t"1000"\n}}\n' "$app" > "$dir/steamapps/appmanifest_$app.acf"
+cp '{FAKE_SERVER}' "$dir/fake-game-server"
+"#
+    );
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Stops the server when the test ends, even when an assertion failed first, and reaps
+/// the process `start` returned so it is not left a zombie.
+struct ServerGuard {
+    instance: Instance,
+    child: Child,
+}
+
+impl Drop for ServerGuard {
+    fn drop(&mut self) {
+        // The server may already be stopped, or replaced by a restart.
+        let _ = self.instance.stop();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn wait_until(what: &str, timeout: Duration, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Adds a rule counting log lines that contain `needle`.
+fn count_lines(rules: &LogRules, needle: &'static str) -> Arc<AtomicUsize> {
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&count);
+    rules.add_rule(
+        move |line| line.contains(needle),
+        move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        },
+        false,
+        Some(0),
+    );
+    count
+}
+
+fn listening_port(log: &Path) -> Option<u16> {
+    fs::read_to_string(log)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("Listening on port "))
+        .and_then(|port| port.trim().parse().ok())
+}
+
+#[test]
+fn install_start_monitor_restart_backup_stop() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    let root = tempdir().unwrap();
+    let steamcmd = write_fake_steamcmd(root.path());
+    // SAFETY: this is the only test in this binary, so nothing reads the environment
+    // concurrently.
+    unsafe {
+        std::env::set_var("STEAMCMD_PATH", &steamcmd);
+    }
+
+    let working_dir = root.path().join("server");
+    let instance = Instance::new(InstanceConfig {
+        app_id: APP_ID,
+        name: "fake".to_owned(),
+        command: working_dir.join("fake-game-server").display().to_string(),
+        launch_args: vec!["--max-runtime".to_owned(), "90".to_owned()],
+        working_dir: working_dir.clone(),
+        launch_mode: LaunchMode::Native,
+        ..InstanceConfig::default()
+    });
+
+    // Install through the fake SteamCMD.
+    instance.install().expect("install");
+    assert!(working_dir.join("fake-game-server").is_file());
+    assert_eq!(instance.installed_build_id().as_deref(), Some("1000"));
+
+    // Start, then check the server is up and reachable.
+    let _server = ServerGuard {
+        instance: instance.clone(),
+        child: instance.start().expect("start"),
+    };
+    let first_pid = instance.pid().expect("pid after start");
+    let log = instance.config.stdout();
+    let port = listening_port(&log).expect("server logged its port");
+    TcpStream::connect(("127.0.0.1", port)).expect("game port accepts connections");
+
+    // Monitor rules fire for lines the server writes after the monitor attaches.
+    let rules = LogRules::new();
+    let joins = count_lines(&rules, "Player joined");
+    let shutdowns = count_lines(&rules, "Shutdown complete");
+    let monitor = start_instance_log_monitor(&working_dir, rules);
+    wait_until("a join rule to fire", Duration::from_secs(10), || {
+        joins.load(Ordering::SeqCst) > 0
+    });
+
+    // A scheduled restart replaces the process, and the monitor keeps following the log
+    // the new process writes.
+    let restarted = Arc::new(AtomicBool::new(false));
+    let job = {
+        let instance = instance.clone();
+        let restarted = Arc::clone(&restarted);
+        let fired = AtomicBool::new(false);
+        register_job("scheduled-restart", "* * * * * *", move || {
+            if fired.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            let instance = instance.clone();
+            let restarted = Arc::clone(&restarted);
+            thread::spawn(move || {
+                instance.restart().expect("restart");
+                restarted.store(true, Ordering::SeqCst);
+            });
+        })
+        .expect("valid schedule")
+    };
+    assert!(job.is_running());
+    wait_until("the scheduled restart", Duration::from_secs(30), || {
+        restarted.load(Ordering::SeqCst)
+    });
+    job.cancel();
+    assert_ne!(instance.pid().expect("pid after restart"), first_pid);
+    let joins_before = joins.load(Ordering::SeqCst);
+    wait_until(
+        "joins from the new process",
+        Duration::from_secs(40),
+        || joins.load(Ordering::SeqCst) > joins_before,
+    );
+
+    // Back up the save the server keeps writing.
+    let archive = root.path().join("world.tar.gz");
+    let mut events = Vec::new();
+    backup_with_hooks(
+        working_dir.join("savegame"),
+        &archive,
+        &BackupOptions::default(),
+        |event| events.push(event.clone()),
+    )
+    .expect("backup");
+    assert!(matches!(
+        events.last(),
+        Some(BackupEvent::Completed { size, .. }) if *size > 0
+    ));
+
+    // Stop gracefully: SIGINT reaches the server, which logs its shutdown.
+    let shutdowns_before = shutdowns.load(Ordering::SeqCst);
+    assert_eq!(instance.stop().expect("stop"), StopOutcome::Interrupted);
+    assert!(!instance.config.state_file().exists());
+    wait_until("a graceful shutdown", Duration::from_secs(10), || {
+        shutdowns.load(Ordering::SeqCst) > shutdowns_before
+    });
+    assert!(monitor.stop_and_join());
+
+    // SAFETY: the monitor threads have been joined and this is still the only test,
+    // so nothing reads the environment concurrently.
+    unsafe {
+        std::env::remove_var("STEAMCMD_PATH");
+    }
+}